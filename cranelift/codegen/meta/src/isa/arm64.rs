@@ -7,6 +7,38 @@ fn define_settings(_shared: &SettingGroup) -> SettingGroup {
     let mut setting = SettingGroupBuilder::new("arm64");
     let has_lse = setting.add_bool("has_lse", "Has Large System Extensions support.", "", false);
 
+    setting.add_bool(
+        "sign_return_address",
+        "Sign return addresses with pointer authentication (PAC).",
+        r#"
+            Sign the return address with `paciasp` before a function saves it
+            to the stack, and authenticate it with `autiasp` before returning,
+            so a return address overwritten on the stack faults instead of
+            being jumped to. Both instructions are NOPs on CPUs without
+            pointer authentication.
+        "#,
+        false,
+    );
+    setting.add_bool(
+        "sign_return_address_with_bkey",
+        "Use the B key instead of the A key to sign return addresses.",
+        r#"
+            Use `pacibsp` and `autibsp` for `sign_return_address`, as
+            required by Apple's arm64e ABI.
+        "#,
+        false,
+    );
+    setting.add_bool(
+        "use_bti",
+        "Use Branch Target Identification (BTI) landing pads.",
+        r#"
+            Emit a `bti c` instruction at the start of each function and a
+            `bti j` at each target of a jump table, so the generated code can
+            run in pages with branch target enforcement enabled. `bti` is a
+            NOP on CPUs without BTI.
+        "#,
+        false,
+    );
     setting.add_predicate("use_lse", predicate!(has_lse));
     setting.build()
 }
@@ -111,6 +111,18 @@ fn define_settings(shared: &SettingGroup) -> SettingGroup {
         false,
     );
 
+    settings.add_bool(
+        "use_ibt",
+        "Use Indirect Branch Tracking (IBT) landing pads.",
+        r#"
+            Emit an `endbr64` instruction at the start of each function and each
+            target of a jump table, so the generated code can run with Intel
+            CET's indirect branch tracking enforced. `endbr64` is a NOP on CPUs
+            without CET.
+        "#,
+        false,
+    );
+
     let shared_enable_simd = shared.get_bool("enable_simd");
 
     settings.add_predicate("use_ssse3", predicate!(has_ssse3));
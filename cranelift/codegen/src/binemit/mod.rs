@@ -6,6 +6,8 @@
 mod stack_map;
 
 pub use self::stack_map::StackMap;
+use crate::ir::{ExternalName, Opcode, SourceLoc, TrapCode};
+use alloc::vec::Vec;
 use core::fmt;
 #[cfg(feature = "enable-serde")]
 use serde::{Deserialize, Serialize};
@@ -83,6 +85,46 @@ impl fmt::Display for Reloc {
     }
 }
 
+/// A sink which receives the machine code of a compiled function along with its relocations, trap
+/// sites, call sites and stack maps.
+///
+/// This is what [`Context::compile_with_sink`](crate::Context::compile_with_sink) streams a
+/// function into, so that an embedder can collect it directly into its own buffers. The code is
+/// passed first, and its metadata afterwards in the order it was emitted. Offsets are relative to
+/// the start of the function's code. Metadata the sink doesn't override a method for is ignored.
+pub trait CodeSink {
+    /// Receives the function's machine code, which hasn't been relocated, along with any jump
+    /// tables and constants following it.
+    fn put_code(&mut self, code: &[u8]);
+
+    /// Receives a relocation of `reloc` kind at `offset` referring to the external symbol `name`.
+    fn reloc_external(
+        &mut self,
+        _offset: CodeOffset,
+        _srcloc: SourceLoc,
+        _reloc: Reloc,
+        _name: &ExternalName,
+        _addend: Addend,
+    ) {
+    }
+
+    /// Receives a trap site: an instruction at `offset` which may trap with `code`.
+    fn trap(&mut self, _offset: CodeOffset, _srcloc: SourceLoc, _code: TrapCode) {}
+
+    /// Receives a call site, whose return address is `ret_addr`.
+    fn call_site(&mut self, _ret_addr: CodeOffset, _srcloc: SourceLoc, _opcode: Opcode) {}
+
+    /// Receives the stack map of the safepoint instruction spanning `offset` to `offset_end`.
+    fn stack_map(&mut self, _offset: CodeOffset, _offset_end: CodeOffset, _stack_map: &StackMap) {}
+}
+
+/// Collects only the machine code, appending it to the vector.
+impl CodeSink for Vec<u8> {
+    fn put_code(&mut self, code: &[u8]) {
+        self.extend_from_slice(code);
+    }
+}
+
 /// Container for information about a vector of compiled code and its supporting read-only data.
 ///
 /// The code starts at offset 0 and is followed optionally by relocatable jump tables and copyable
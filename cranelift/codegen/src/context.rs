@@ -9,7 +9,7 @@
 //! contexts concurrently. Typically, you would have one context per compilation thread and only a
 //! single ISA instance.
 
-use crate::binemit::CodeInfo;
+use crate::binemit::{CodeInfo, CodeSink};
 use crate::dce::do_dce;
 use crate::dominator_tree::DominatorTree;
 use crate::flowgraph::ControlFlowGraph;
@@ -168,6 +168,57 @@ impl Context {
         Ok(info)
     }
 
+    /// Compile the function, and stream its machine code and metadata into `sink`.
+    ///
+    /// This is the same as `compile` followed by `emit_to_sink`, and lets the caller collect the
+    /// code, relocations, traps, call sites and stack maps into its own data structures as they
+    /// are passed to it, rather than copying them out of `mach_compile_result` afterwards.
+    ///
+    /// Returns information about the function's code and read-only data.
+    pub fn compile_with_sink(
+        &mut self,
+        isa: &dyn TargetIsa,
+        sink: &mut dyn CodeSink,
+    ) -> CodegenResult<CodeInfo> {
+        self.compile(isa)?;
+        Ok(self.emit_to_sink(sink))
+    }
+
+    /// Stream the machine code of the function compiled by `compile`, followed by its relocations,
+    /// traps, call sites and stack maps, into `sink`.
+    ///
+    /// Returns information about the emitted code and data.
+    pub fn emit_to_sink(&self, sink: &mut dyn CodeSink) -> CodeInfo {
+        let _tt = timing::binemit();
+        let result = self
+            .mach_compile_result
+            .as_ref()
+            .expect("only using mach backend now");
+        let buffer = &result.buffer;
+
+        sink.put_code(buffer.data());
+        for reloc in buffer.relocs() {
+            sink.reloc_external(
+                reloc.offset,
+                reloc.srcloc,
+                reloc.kind,
+                &reloc.name,
+                reloc.addend,
+            );
+        }
+        for trap in buffer.traps() {
+            sink.trap(trap.offset, trap.srcloc, trap.code);
+        }
+        for call_site in buffer.call_sites() {
+            sink.call_site(call_site.ret_addr, call_site.srcloc, call_site.opcode);
+        }
+        for stack_map in buffer.stack_maps() {
+            sink.stack_map(stack_map.offset, stack_map.offset_end, &stack_map.stack_map);
+        }
+
+        result.code_info()
+    }
+
     /// Emit machine code directly into raw memory.
     ///
     /// Write all of the function's machine code to the memory at `mem`. The size of the machine
@@ -694,6 +694,38 @@ impl ABIMachineSpec for AArch64MachineDeps {
         insts
     }
 
+    fn gen_return_address_sign(
+        flags: &settings::Flags,
+        isa_flags: &Vec<settings::Value>,
+    ) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+        if isa_flag(isa_flags, "sign_return_address") {
+            // `paciasp` / `pacibsp`
+            insts.push(Inst::Pacisp {
+                key: return_address_key(isa_flags),
+            });
+            if flags.unwind_info() {
+                insts.push(Inst::Unwind {
+                    inst: UnwindInst::Aarch64SetPointerAuth {
+                        return_addresses: true,
+                    },
+                });
+            }
+        }
+        insts
+    }
+
+    fn gen_return_address_auth(isa_flags: &Vec<settings::Value>) -> SmallInstVec<Inst> {
+        let mut insts = SmallVec::new();
+        if isa_flag(isa_flags, "sign_return_address") {
+            // `autiasp` / `autibsp`
+            insts.push(Inst::Autisp {
+                key: return_address_key(isa_flags),
+            });
+        }
+        insts
+    }
+
     fn gen_probestack(_: u32) -> SmallInstVec<Self::I> {
         // TODO: implement if we ever require stack probes on an AArch64 host
         // (unlikely unless Lucet is ported)
@@ -1323,3 +1355,19 @@ fn is_reg_clobbered_by_call(call_conv_of_callee: isa::CallConv, r: RealReg) -> b
         }
     }
 }
+
+/// Returns whether the boolean ISA setting `name` is enabled.
+fn isa_flag(isa_flags: &Vec<settings::Value>, name: &str) -> bool {
+    isa_flags
+        .iter()
+        .any(|value| value.name == name && value.as_bool() == Some(true))
+}
+
+/// Returns the key return addresses are signed with.
+fn return_address_key(isa_flags: &Vec<settings::Value>) -> APIKey {
+    if isa_flag(isa_flags, "sign_return_address_with_bkey") {
+        APIKey::B
+    } else {
+        APIKey::A
+    }
+}
@@ -7,6 +7,7 @@ use crate::ir::types::*;
 use crate::ir::{LibCall, MemFlags, TrapCode};
 use crate::isa::aarch64::inst::*;
 use crate::isa::aarch64::lower::is_valid_atomic_transaction_ty;
+use crate::isa::aarch64::settings as aarch64_settings;
 use crate::machinst::{ty_bits, Reg, RegClass, Writable};
 use core::convert::TryFrom;
 
@@ -662,11 +663,14 @@ impl EmitState {
 }
 
 /// Constant state used during function compilation.
-pub struct EmitInfo(settings::Flags);
+pub struct EmitInfo {
+    flags: settings::Flags,
+    isa_flags: aarch64_settings::Flags,
+}
 
 impl EmitInfo {
-    pub(crate) fn new(flags: settings::Flags) -> Self {
-        Self(flags)
+    pub(crate) fn new(flags: settings::Flags, isa_flags: aarch64_settings::Flags) -> Self {
+        Self { flags, isa_flags }
     }
 }
 
@@ -1657,6 +1661,25 @@ impl MachInstEmit for Inst {
             &Inst::Fence {} => {
                 sink.put4(enc_dmb_ish()); // dmb ish
             }
+            &Inst::Pacisp { key } => {
+                // `paciasp` and `pacibsp` are in the hint space, so they're NOPs without FEAT_PAuth.
+                sink.put4(match key {
+                    APIKey::A => 0xd503233f,
+                    APIKey::B => 0xd503237f,
+                });
+            }
+            &Inst::Autisp { key } => {
+                sink.put4(match key {
+                    APIKey::A => 0xd50323bf,
+                    APIKey::B => 0xd50323ff,
+                });
+            }
+            &Inst::Bti { targets } => {
+                sink.put4(match targets {
+                    BranchTargetType::C => 0xd503245f,
+                    BranchTargetType::J => 0xd503249f,
+                });
+            }
             &Inst::FpuMove64 { rd, rn } => {
                 let rd = allocs.next_writable(rd);
                 let rn = allocs.next(rn);
@@ -2960,7 +2983,7 @@ impl MachInstEmit for Inst {
                 inst.emit(&[], sink, emit_info, state);
                 let srcloc = state.cur_srcloc();
                 sink.add_reloc(srcloc, Reloc::Abs8, name, offset);
-                if emit_info.0.emit_all_ones_funcaddrs() {
+                if emit_info.flags.emit_all_ones_funcaddrs() {
                     sink.put8(u64::max_value());
                 } else {
                     sink.put8(0);
@@ -3110,4 +3133,26 @@ impl MachInstEmit for Inst {
         let mut allocs = AllocationConsumer::new(allocs);
         self.print_with_state(state, &mut allocs)
     }
+
+    fn gen_block_start(
+        is_entry: bool,
+        is_indirect_branch_target: bool,
+        info: &Self::Info,
+    ) -> Option<Self> {
+        // Functions are entered through indirect calls, and jump table
+        // targets through the indirect branch of `JTSequence`.
+        if !info.isa_flags.use_bti() {
+            None
+        } else if is_entry {
+            Some(Inst::Bti {
+                targets: BranchTargetType::C,
+            })
+        } else if is_indirect_branch_target {
+            Some(Inst::Bti {
+                targets: BranchTargetType::J,
+            })
+        } else {
+            None
+        }
+    }
 }
@@ -1,6 +1,7 @@
 use crate::ir::types::*;
 use crate::ir::TrapCode;
 use crate::isa::aarch64::inst::*;
+use crate::isa::aarch64::settings as aarch64_settings;
 use crate::isa::CallConv;
 use crate::settings;
 
@@ -6870,9 +6871,28 @@ fn test_aarch64_binemit() {
     ));
 
     insns.push((Inst::Fence {}, "BF3B03D5", "dmb ish"));
+    insns.push((Inst::Pacisp { key: APIKey::A }, "3F2303D5", "paciasp"));
+    insns.push((Inst::Pacisp { key: APIKey::B }, "7F2303D5", "pacibsp"));
+    insns.push((Inst::Autisp { key: APIKey::A }, "BF2303D5", "autiasp"));
+    insns.push((Inst::Autisp { key: APIKey::B }, "FF2303D5", "autibsp"));
+    insns.push((
+        Inst::Bti {
+            targets: BranchTargetType::C,
+        },
+        "5F2403D5",
+        "bti c",
+    ));
+    insns.push((
+        Inst::Bti {
+            targets: BranchTargetType::J,
+        },
+        "9F2403D5",
+        "bti j",
+    ));
 
     let flags = settings::Flags::new(settings::builder());
-    let emit_info = EmitInfo::new(flags);
+    let isa_flags = aarch64_settings::Flags::new(&flags, aarch64_settings::builder());
+    let emit_info = EmitInfo::new(flags, isa_flags);
     for (insn, expected_encoding, expected_printing) in insns {
         println!(
             "AArch64: {:?}, {}, {}",
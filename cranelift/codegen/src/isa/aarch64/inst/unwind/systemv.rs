@@ -74,7 +74,7 @@ mod tests {
         StackSlotKind,
     };
     use crate::isa::{lookup, CallConv};
-    use crate::settings::{builder, Flags};
+    use crate::settings::{builder, Configurable, Flags};
     use crate::Context;
     use gimli::write::Address;
     use std::str::FromStr;
@@ -150,6 +150,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_signed_return_address_with_bkey() {
+        let mut shared = builder();
+        shared.enable("unwind_info").unwrap();
+        let mut isa = lookup(triple!("aarch64")).expect("expect aarch64 ISA");
+        isa.enable("sign_return_address").unwrap();
+        isa.enable("sign_return_address_with_bkey").unwrap();
+        let isa = isa
+            .finish(Flags::new(shared))
+            .expect("Creating compiler backend");
+
+        let mut context = Context::for_function(create_function(
+            CallConv::SystemV,
+            Some(StackSlotData::new(StackSlotKind::ExplicitSlot, 64)),
+        ));
+
+        context.compile(&*isa).expect("expected compilation");
+
+        let fde = match context
+            .create_unwind_info(isa.as_ref())
+            .expect("can create unwind info")
+        {
+            Some(crate::isa::unwind::UnwindInfo::SystemV(info)) => {
+                info.to_fde(Address::Constant(1234))
+            }
+            _ => panic!("expected unwind information"),
+        };
+
+        // The return address is signed by the first instruction, and the CIE
+        // says which key it's signed with.
+        assert_eq!(format!("{:?}", fde), "FrameDescriptionEntry { address: Constant(1234), length: 32, lsda: None, instructions: [(0, ValExpression(Register(34), Expression { operations: [Simple(DwOp(48))] })), (4, ValExpression(Register(34), Expression { operations: [Simple(DwOp(49))] })), (8, CfaOffset(16)), (8, Offset(Register(29), -16)), (8, Offset(Register(30), -8)), (12, CfaRegister(Register(29)))] }");
+        assert_eq!(isa.systemv_cie_augmentation(), b"B");
+    }
+
     fn create_multi_return_function(call_conv: CallConv) -> Function {
         let mut sig = Signature::new(call_conv);
         sig.params.push(AbiParam::new(types::I32));
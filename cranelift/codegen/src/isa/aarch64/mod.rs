@@ -19,7 +19,7 @@ mod abi;
 pub(crate) mod inst;
 mod lower;
 mod lower_inst;
-mod settings;
+pub(crate) mod settings;
 
 use inst::create_reg_env;
 
@@ -56,7 +56,7 @@ impl AArch64Backend {
         func: &Function,
         flags: shared_settings::Flags,
     ) -> CodegenResult<(VCode<inst::Inst>, regalloc2::Output)> {
-        let emit_info = EmitInfo::new(flags.clone());
+        let emit_info = EmitInfo::new(flags.clone(), self.isa_flags.clone());
         let abi = Box::new(abi::AArch64ABICallee::new(func, flags, self.isa_flags())?);
         compile::compile::<AArch64Backend>(func, self, abi, &self.machine_env, emit_info)
     }
@@ -109,6 +109,10 @@ impl TargetIsa for AArch64Backend {
         self.isa_flags.iter().collect()
     }
 
+    fn is_branch_protection_enabled(&self) -> bool {
+        self.isa_flags.use_bti()
+    }
+
     fn unsigned_add_overflow_condition(&self) -> IntCC {
         // Unsigned `>=`; this corresponds to the carry flag set on aarch64, which happens on
         // overflow of an add.
@@ -147,6 +151,17 @@ impl TargetIsa for AArch64Backend {
         Some(inst::unwind::systemv::create_cie())
     }
 
+    #[cfg(feature = "unwind")]
+    fn systemv_cie_augmentation(&self) -> &'static [u8] {
+        // Unwinders must be told with `B` that return addresses are signed
+        // with the B key, since they assume the A key otherwise.
+        if self.isa_flags.sign_return_address() && self.isa_flags.sign_return_address_with_bkey() {
+            b"B"
+        } else {
+            &[]
+        }
+    }
+
     fn text_section_builder(&self, num_funcs: u32) -> Box<dyn TextSectionBuilder> {
         Box::new(MachTextSectionBuilder::<inst::Inst>::new(num_funcs))
     }
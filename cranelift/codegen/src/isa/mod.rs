@@ -226,6 +226,12 @@ pub trait TargetIsa: fmt::Display + Send + Sync {
     /// Get the ISA-dependent flag values that were used to make this trait object.
     fn isa_flags(&self) -> Vec<settings::Value>;
 
+    /// Returns whether generated code is protected against indirect branches
+    /// to arbitrary locations, such as with landing pads for Intel's IBT.
+    fn is_branch_protection_enabled(&self) -> bool {
+        false
+    }
+
     /// Compile the given function.
     fn compile_function(
         &self,
@@ -264,6 +270,17 @@ pub trait TargetIsa: fmt::Display + Send + Sync {
         None
     }
 
+    /// Returns the characters which the augmentation string of the CIE from
+    /// `create_systemv_cie` needs in addition to those gimli writes, which
+    /// have no augmentation data of their own.
+    ///
+    /// gimli can't express these, so whoever encodes the CIE has to add them
+    /// to it.
+    #[cfg(feature = "unwind")]
+    fn systemv_cie_augmentation(&self) -> &'static [u8] {
+        &[]
+    }
+
     /// Returns an object that can be used to build the text section of an
     /// executable.
     ///
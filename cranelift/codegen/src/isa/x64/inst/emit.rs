@@ -2793,6 +2793,11 @@ pub(crate) fn emit(
             sink.put1(0xcc);
         }
 
+        Inst::Endbr64 => {
+            // endbr64 = F3 0F 1E FA
+            sink.put4(0xfa1e0ff3);
+        }
+
         Inst::Ud2 { trap_code } => {
             let cur_srcloc = state.cur_srcloc();
             sink.add_trap(cur_srcloc, *trap_code);
@@ -4821,6 +4821,7 @@ fn test_x64_emit() {
     // Misc instructions.
 
     insns.push((Inst::Hlt, "CC", "hlt"));
+    insns.push((Inst::Endbr64, "F30F1EFA", "endbr64"));
 
     let trap_code = TrapCode::UnreachableCodeReached;
     insns.push((Inst::Ud2 { trap_code }, "0F0B", "ud2 unreachable"));
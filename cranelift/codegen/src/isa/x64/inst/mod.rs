@@ -58,6 +58,7 @@ impl Inst {
             | Inst::EpiloguePlaceholder
             | Inst::Fence { .. }
             | Inst::Hlt
+            | Inst::Endbr64
             | Inst::Imm { .. }
             | Inst::JmpCond { .. }
             | Inst::JmpIf { .. }
@@ -1651,6 +1652,8 @@ impl PrettyPrint for Inst {
 
             Inst::Hlt => "hlt".into(),
 
+            Inst::Endbr64 => "endbr64".into(),
+
             Inst::Ud2 { trap_code } => format!("ud2 {}", trap_code),
 
             Inst::ElfTlsGetAddr { ref symbol } => {
@@ -2065,6 +2068,7 @@ fn x64_get_operands<F: Fn(VReg) -> VReg>(inst: &Inst, collector: &mut OperandCol
         | Inst::TrapIf { .. }
         | Inst::VirtualSPOffsetAdj { .. }
         | Inst::Hlt
+        | Inst::Endbr64
         | Inst::Ud2 { .. }
         | Inst::Fence { .. } => {
             // No registers are used.
@@ -2403,6 +2407,20 @@ impl MachInstEmit for Inst {
     fn pretty_print_inst(&self, allocs: &[Allocation], _: &mut Self::State) -> String {
         PrettyPrint::pretty_print(self, 0, &mut AllocationConsumer::new(allocs))
     }
+
+    fn gen_block_start(
+        is_entry: bool,
+        is_indirect_branch_target: bool,
+        info: &Self::Info,
+    ) -> Option<Self> {
+        // Functions are entered through indirect calls, and jump table
+        // targets through the indirect jump of `JmpTableSeq`.
+        if (is_entry || is_indirect_branch_target) && info.isa_flags.use_ibt() {
+            Some(Inst::Endbr64)
+        } else {
+            None
+        }
+    }
 }
 
 impl MachInstEmitState<Inst> for EmitState {
@@ -806,6 +806,130 @@ fn emit_cmoves<C: LowerCtx<I = Inst>>(
     }
 }
 
+/// Emits a saturating conversion of the scalar float `src`, of type `src_ty`,
+/// to the integer type `dst_ty` in `dst`, without any branches.
+///
+/// The truncating conversion instructions produce the "integer indefinite"
+/// value, `INT_MIN`, for any input which doesn't fit in their result, NaN
+/// included. That result is corrected with conditional moves by comparing the
+/// input against the bounds of the result type. Unsigned 64-bit results are
+/// converted from both the input and the input less 2^63, as the conversion
+/// instructions only produce signed results.
+fn emit_fcvt_to_int_sat<C: LowerCtx<I = Inst>>(
+    ctx: &mut C,
+    signed: bool,
+    src_ty: Type,
+    dst_ty: Type,
+    src: Reg,
+    dst: Writable<Reg>,
+) {
+    let (cvt_op, cmp_op, mov_op, sub_op, src_size) = if src_ty == types::F32 {
+        (
+            SseOpcode::Cvttss2si,
+            SseOpcode::Ucomiss,
+            SseOpcode::Movd,
+            SseOpcode::Subss,
+            OperandSize::Size32,
+        )
+    } else {
+        assert_eq!(src_ty, types::F64);
+        (
+            SseOpcode::Cvttsd2si,
+            SseOpcode::Ucomisd,
+            SseOpcode::Movq,
+            SseOpcode::Subsd,
+            OperandSize::Size64,
+        )
+    };
+    let float_bits = |value: f64| -> u64 {
+        if src_ty == types::F32 {
+            (value as f32).to_bits().into()
+        } else {
+            value.to_bits()
+        }
+    };
+
+    let tmp = ctx.alloc_tmp(types::I64).only_reg().unwrap();
+    let bound = ctx.alloc_tmp(src_ty).only_reg().unwrap();
+    // Loads the float `value` into `bound`, clobbering `tmp`.
+    let load_bound = |ctx: &mut C, value: f64| {
+        ctx.emit(Inst::imm(OperandSize::Size64, float_bits(value), tmp));
+        ctx.emit(Inst::gpr_to_xmm(
+            mov_op,
+            RegMem::reg(tmp.to_reg()),
+            src_size,
+            bound,
+        ));
+    };
+    // Sets the flags from comparing `src` to `bound`, where the carry flag is
+    // set if `src` is less than `bound` or NaN.
+    let compare_bound = |ctx: &mut C| {
+        ctx.emit(Inst::xmm_cmp_rm_r(cmp_op, RegMem::reg(bound.to_reg()), src));
+    };
+    // Moves `value` into `dst` if `cc` holds, using `tmp`, which leaves the
+    // flags alone.
+    let saturate = |ctx: &mut C, size: OperandSize, cc: CC, value: u64| {
+        ctx.emit(Inst::imm(size, value, tmp));
+        ctx.emit(Inst::cmove(size, cc, RegMem::reg(tmp.to_reg()), dst));
+    };
+
+    if signed {
+        // Inputs below `INT_MIN` already produce `INT_MIN`, while inputs of at
+        // least 2^(N-1) become `INT_MAX` and NaN becomes zero.
+        let (size, max, limit) = if dst_ty == types::I32 {
+            (OperandSize::Size32, i32::MAX as u64, 2147483648.0)
+        } else {
+            assert_eq!(dst_ty, types::I64);
+            (OperandSize::Size64, i64::MAX as u64, 9223372036854775808.0)
+        };
+        ctx.emit(Inst::xmm_to_gpr(cvt_op, src, dst, size));
+        load_bound(ctx, limit);
+        compare_bound(ctx);
+        saturate(ctx, size, CC::NB, max);
+        ctx.emit(Inst::xmm_cmp_rm_r(cmp_op, RegMem::reg(src), src));
+        saturate(ctx, size, CC::P, 0);
+        return;
+    }
+
+    // The 64-bit conversion is exact for all inputs in `[0, 2^63)`, which
+    // covers every unsigned 32-bit result.
+    let size = OperandSize::Size64;
+    ctx.emit(Inst::xmm_to_gpr(cvt_op, src, dst, size));
+    let (max, limit) = if dst_ty == types::I32 {
+        (u64::from(u32::MAX), 4294967296.0)
+    } else {
+        assert_eq!(dst_ty, types::I64);
+
+        // Inputs in `[2^63, 2^64)` produce `INT_MIN` above, which is exactly
+        // their top bit, and the rest of their bits are the conversion of
+        // the input less 2^63.
+        let high = ctx.alloc_tmp(types::I64).only_reg().unwrap();
+        let shifted = ctx.alloc_tmp(src_ty).only_reg().unwrap();
+        load_bound(ctx, 9223372036854775808.0);
+        ctx.emit(Inst::gen_move(shifted, src, src_ty));
+        ctx.emit(Inst::xmm_rm_r(sub_op, RegMem::reg(bound.to_reg()), shifted));
+        ctx.emit(Inst::xmm_to_gpr(cvt_op, shifted.to_reg(), high, size));
+        ctx.emit(Inst::alu_rmi_r(
+            size,
+            AluRmiROpcode::Or,
+            RegMemImm::reg(dst.to_reg()),
+            high,
+        ));
+        compare_bound(ctx);
+        ctx.emit(Inst::cmove(size, CC::NB, RegMem::reg(high.to_reg()), dst));
+        (u64::MAX, 18446744073709551616.0)
+    };
+
+    // Negative inputs and NaN become zero, and inputs of at least 2^N become
+    // the maximum.
+    load_bound(ctx, 0.0);
+    compare_bound(ctx);
+    saturate(ctx, size, CC::B, 0);
+    load_bound(ctx, limit);
+    compare_bound(ctx);
+    saturate(ctx, size, CC::NB, max);
+}
+
 //=============================================================================
 // Top-level instruction lowering entry point, for one instruction.
 
@@ -1335,6 +1459,11 @@ fn lower_insn_to_regs<C: LowerCtx<I = Inst>>(
                 let to_signed = op == Opcode::FcvtToSint || op == Opcode::FcvtToSintSat;
                 let is_sat = op == Opcode::FcvtToUintSat || op == Opcode::FcvtToSintSat;
 
+                if is_sat {
+                    emit_fcvt_to_int_sat(ctx, to_signed, input_ty, output_ty, src, dst);
+                    return Ok(());
+                }
+
                 let src_copy = ctx.alloc_tmp(input_ty).only_reg().unwrap();
                 ctx.emit(Inst::gen_move(src_copy, src, input_ty));
 
@@ -97,6 +97,10 @@ impl TargetIsa for X64Backend {
         self.x64_flags.iter().collect()
     }
 
+    fn is_branch_protection_enabled(&self) -> bool {
+        self.x64_flags.use_ibt()
+    }
+
     fn name(&self) -> &'static str {
         "x64"
     }
@@ -201,12 +205,15 @@ fn isa_constructor(
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc};
     use crate::cursor::{Cursor, FuncCursor};
     use crate::ir::{types::*, SourceLoc, ValueLabel, ValueLabelStart};
     use crate::ir::{AbiParam, ExternalName, Function, InstBuilder, JumpTableData, Signature};
+    use crate::ir::{ExtFuncData, Opcode, TrapCode};
     use crate::isa::CallConv;
     use crate::settings;
     use crate::settings::Configurable;
+    use crate::Context;
     use core::str::FromStr;
     use cranelift_entity::EntityRef;
     use target_lexicon::Triple;
@@ -457,4 +464,83 @@ mod test {
 
         assert_eq!(code, &golden[..]);
     }
+
+    // Check that compiling into a `CodeSink` passes it the same code and
+    // metadata as the compile result holds.
+    #[test]
+    fn code_sink() {
+        #[derive(Default)]
+        struct Sink {
+            code: Vec<u8>,
+            relocs: Vec<(CodeOffset, Reloc, ExternalName)>,
+            traps: Vec<(CodeOffset, TrapCode)>,
+            call_sites: Vec<CodeOffset>,
+        }
+
+        impl CodeSink for Sink {
+            fn put_code(&mut self, code: &[u8]) {
+                assert!(self.code.is_empty() && self.relocs.is_empty());
+                self.code.extend_from_slice(code);
+            }
+            fn reloc_external(
+                &mut self,
+                offset: CodeOffset,
+                _srcloc: SourceLoc,
+                reloc: Reloc,
+                name: &ExternalName,
+                _addend: Addend,
+            ) {
+                self.relocs.push((offset, reloc, name.clone()));
+            }
+            fn trap(&mut self, offset: CodeOffset, _srcloc: SourceLoc, code: TrapCode) {
+                self.traps.push((offset, code));
+            }
+            fn call_site(&mut self, ret_addr: CodeOffset, _srcloc: SourceLoc, _opcode: Opcode) {
+                self.call_sites.push(ret_addr);
+            }
+        }
+
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(I32));
+        let mut func = Function::with_name_signature(ExternalName::testcase("test0"), sig);
+        let callee = func.import_signature(Signature::new(CallConv::SystemV));
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature: callee,
+            colocated: true,
+        });
+
+        let bb0 = func.dfg.make_block();
+        let arg0 = func.dfg.append_block_param(bb0, I32);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(bb0);
+        pos.ins().call(callee, &[]);
+        pos.ins().trapz(arg0, TrapCode::User(7));
+        pos.ins().return_(&[]);
+
+        let isa = crate::isa::lookup_by_name("x86_64")
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+            .unwrap();
+        let mut context = Context::for_function(func);
+        let mut sink = Sink::default();
+        let info = context.compile_with_sink(&*isa, &mut sink).unwrap();
+
+        let buffer = &context.mach_compile_result.as_ref().unwrap().buffer;
+        assert_eq!(info.total_size as usize, sink.code.len());
+        assert_eq!(sink.code, buffer.data());
+        assert_eq!(sink.relocs.len(), 1);
+        assert_eq!(sink.relocs[0].1, Reloc::X86CallPCRel4);
+        assert_eq!(sink.relocs[0].2, ExternalName::testcase("callee"));
+        let traps = buffer.traps().iter().map(|t| (t.offset, t.code));
+        assert_eq!(sink.traps, traps.collect::<Vec<_>>());
+        assert!(sink.traps.iter().any(|t| t.1 == TrapCode::User(7)));
+        assert_eq!(sink.call_sites.len(), 1);
+        assert!(sink.relocs[0].0 < sink.call_sites[0]);
+
+        // Collecting into a vector only keeps the code.
+        let mut code = Vec::new();
+        context.emit_to_sink(&mut code);
+        assert_eq!(code, sink.code);
+    }
 }
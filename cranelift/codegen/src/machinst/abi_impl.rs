@@ -421,6 +421,25 @@ pub trait ABIMachineSpec {
     /// Generate the usual frame-restore sequence for this architecture.
     fn gen_epilogue_frame_restore(flags: &settings::Flags) -> SmallInstVec<Self::I>;
 
+    /// Generate a sequence protecting the return address before the
+    /// frame-setup sequence saves it to the stack, such as signing it with
+    /// pointer authentication on AArch64.
+    fn gen_return_address_sign(
+        _flags: &settings::Flags,
+        _isa_flags: &Vec<settings::Value>,
+    ) -> SmallInstVec<Self::I> {
+        // By default, generates nothing.
+        smallvec![]
+    }
+
+    /// Generate a sequence checking the return address protected by
+    /// `gen_return_address_sign`, after the frame-restore sequence has
+    /// reloaded it.
+    fn gen_return_address_auth(_isa_flags: &Vec<settings::Value>) -> SmallInstVec<Self::I> {
+        // By default, generates nothing.
+        smallvec![]
+    }
+
     /// Generate a probestack call.
     fn gen_probestack(_frame_size: u32) -> SmallInstVec<Self::I>;
 
@@ -1240,6 +1259,7 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
 
             if self.setup_frame {
                 // set up frame
+                insts.extend(M::gen_return_address_sign(&self.flags, &self.isa_flags).into_iter());
                 insts.extend(M::gen_prologue_frame_setup(&self.flags).into_iter());
             }
 
@@ -1305,6 +1325,7 @@ impl<M: ABIMachineSpec> ABICallee for ABICalleeImpl<M> {
         if !self.call_conv.extends_baldrdash() {
             if self.setup_frame {
                 insts.extend(M::gen_epilogue_frame_restore(&self.flags));
+                insts.extend(M::gen_return_address_auth(&self.isa_flags));
             }
 
             // This `ret` doesn't need any return registers attached
@@ -255,6 +255,18 @@ pub trait MachInstEmit: MachInst {
     );
     /// Pretty-print the instruction.
     fn pretty_print_inst(&self, allocs: &[Allocation], state: &mut Self::State) -> String;
+
+    /// Generate the instruction a block should start with, if any, such as a
+    /// landing pad for control-flow integrity. `is_entry` is set for the
+    /// function's entry block, which is emitted before the prologue, and
+    /// `is_indirect_branch_target` for the targets of jump tables.
+    fn gen_block_start(
+        _is_entry: bool,
+        _is_indirect_branch_target: bool,
+        _info: &Self::Info,
+    ) -> Option<Self> {
+        None
+    }
 }
 
 /// A trait describing the emission state carried between MachInsts when
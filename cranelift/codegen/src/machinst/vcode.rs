@@ -782,6 +782,16 @@ impl<I: VCodeInst> VCode<I> {
         // hit the right block below.
         let prologue_insts = self.abi.gen_prologue();
 
+        // Find the targets of indirect branches, which may need landing pads.
+        let mut indirect_branch_targets = vec![false; self.num_blocks()];
+        for (block, &(start, end)) in self.block_ranges.iter().enumerate() {
+            if end > start && self.insts[end.index() - 1].is_term() == MachTerminator::Indirect {
+                for succ in self.succs(BlockIndex::new(block)) {
+                    indirect_branch_targets[succ.index()] = true;
+                }
+            }
+        }
+
         // Emit blocks.
         let mut cur_srcloc = None;
         let mut last_offset = None;
@@ -821,6 +831,9 @@ impl<I: VCodeInst> VCode<I> {
                 log::trace!(" -> entry block");
                 buffer.start_srcloc(SourceLoc::default());
                 state.pre_sourceloc(SourceLoc::default());
+                if let Some(inst) = I::gen_block_start(true, false, &self.emit_info) {
+                    do_emit(&inst, &[], &mut disasm, &mut buffer, &mut state);
+                }
                 for inst in &prologue_insts {
                     do_emit(&inst, &[], &mut disasm, &mut buffer, &mut state);
                 }
@@ -835,6 +848,13 @@ impl<I: VCodeInst> VCode<I> {
                 writeln!(&mut disasm, "block{}:", block.index()).unwrap();
             }
 
+            let is_indirect_branch_target = indirect_branch_targets[block.index()];
+            if let Some(inst) =
+                I::gen_block_start(false, is_indirect_branch_target, &self.emit_info)
+            {
+                do_emit(&inst, &[], &mut disasm, &mut buffer, &mut state);
+            }
+
             if want_metadata {
                 // Track BB starts. If we have backed up due to MachBuffer
                 // branch opts, note that the removed blocks were removed.
@@ -23,7 +23,8 @@
     )
 )]
 
-use cranelift_codegen::isa;
+use cranelift_codegen::isa::{self, TargetIsa};
+use cranelift_codegen::settings::{self, Configurable};
 use target_lexicon::Triple;
 
 /// Return an `isa` builder configured for the current host
@@ -48,73 +49,78 @@ pub fn builder_with_options(infer_native_flags: bool) -> Result<isa::Builder, &'
 
     #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
     {
-        use cranelift_codegen::settings::Configurable;
-
         if !std::is_x86_feature_detected!("sse2") {
             return Err("x86 support requires SSE2");
         }
+    }
 
-        if !infer_native_flags {
-            return Ok(isa_builder);
-        }
+    if infer_native_flags {
+        self::infer_native_flags(&mut isa_builder)?;
+    }
+    Ok(isa_builder)
+}
 
-        if std::is_x86_feature_detected!("sse3") {
-            isa_builder.enable("has_sse3").unwrap();
-        }
-        if std::is_x86_feature_detected!("ssse3") {
-            isa_builder.enable("has_ssse3").unwrap();
-        }
-        if std::is_x86_feature_detected!("sse4.1") {
-            isa_builder.enable("has_sse41").unwrap();
-        }
-        if std::is_x86_feature_detected!("sse4.2") {
-            isa_builder.enable("has_sse42").unwrap();
-        }
-        if std::is_x86_feature_detected!("popcnt") {
-            isa_builder.enable("has_popcnt").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx") {
-            isa_builder.enable("has_avx").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx2") {
-            isa_builder.enable("has_avx2").unwrap();
-        }
-        if std::is_x86_feature_detected!("bmi1") {
-            isa_builder.enable("has_bmi1").unwrap();
-        }
-        if std::is_x86_feature_detected!("bmi2") {
-            isa_builder.enable("has_bmi2").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx512bitalg") {
-            isa_builder.enable("has_avx512bitalg").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx512dq") {
-            isa_builder.enable("has_avx512dq").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx512f") {
-            isa_builder.enable("has_avx512f").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx512vl") {
-            isa_builder.enable("has_avx512vl").unwrap();
-        }
-        if std::is_x86_feature_detected!("avx512vbmi") {
-            isa_builder.enable("has_avx512vbmi").unwrap();
-        }
-        if std::is_x86_feature_detected!("lzcnt") {
-            isa_builder.enable("has_lzcnt").unwrap();
-        }
+/// Return a `TargetIsa` for the current host machine which uses
+/// all of the features detected on it, compiling with the given
+/// shared flags.
+pub fn isa(shared_flags: settings::Flags) -> Result<Box<dyn TargetIsa>, &'static str> {
+    builder()?
+        .finish(shared_flags)
+        .map_err(|_| "failed to build an isa for the host machine")
+}
+
+/// Enable the ISA flags of every feature detected on the current
+/// host machine in `isa_builder`, which must be the builder of an
+/// `isa` for the host's architecture.
+///
+/// This can be used to infer the flags of a builder which was looked
+/// up by its triple rather than with [`builder`].
+pub fn infer_native_flags(isa_builder: &mut dyn Configurable) -> Result<(), &'static str> {
+    for flag in detected_flags() {
+        isa_builder
+            .enable(flag)
+            .map_err(|_| "isa builder isn't for the host architecture")?;
     }
+    Ok(())
+}
 
-    #[cfg(target_arch = "aarch64")]
-    {
-        use cranelift_codegen::settings::Configurable;
+/// Return the names of the ISA flags of the features detected on
+/// the current host machine, such as `has_avx2` on x86-64.
+pub fn detected_flags() -> Vec<&'static str> {
+    let mut flags = Vec::new();
 
-        if !infer_native_flags {
-            return Ok(isa_builder);
-        }
+    #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+    {
+        let features = [
+            (std::is_x86_feature_detected!("sse3"), "has_sse3"),
+            (std::is_x86_feature_detected!("ssse3"), "has_ssse3"),
+            (std::is_x86_feature_detected!("sse4.1"), "has_sse41"),
+            (std::is_x86_feature_detected!("sse4.2"), "has_sse42"),
+            (std::is_x86_feature_detected!("popcnt"), "has_popcnt"),
+            (std::is_x86_feature_detected!("avx"), "has_avx"),
+            (std::is_x86_feature_detected!("avx2"), "has_avx2"),
+            (std::is_x86_feature_detected!("bmi1"), "has_bmi1"),
+            (std::is_x86_feature_detected!("bmi2"), "has_bmi2"),
+            (
+                std::is_x86_feature_detected!("avx512bitalg"),
+                "has_avx512bitalg",
+            ),
+            (std::is_x86_feature_detected!("avx512dq"), "has_avx512dq"),
+            (std::is_x86_feature_detected!("avx512f"), "has_avx512f"),
+            (std::is_x86_feature_detected!("avx512vl"), "has_avx512vl"),
+            (
+                std::is_x86_feature_detected!("avx512vbmi"),
+                "has_avx512vbmi",
+            ),
+            (std::is_x86_feature_detected!("lzcnt"), "has_lzcnt"),
+        ];
+        flags.extend(features.iter().filter(|(has, _)| *has).map(|(_, f)| *f));
+    }
 
+    #[cfg(target_arch = "aarch64")]
+    {
         if std::arch::is_aarch64_feature_detected!("lse") {
-            isa_builder.enable("has_lse").unwrap();
+            flags.push("has_lse");
         }
     }
 
@@ -122,34 +128,25 @@ pub fn builder_with_options(infer_native_flags: bool) -> Result<isa::Builder, &'
     // we use getauxval from the libc crate directly.
     #[cfg(all(target_arch = "s390x", target_os = "linux"))]
     {
-        use cranelift_codegen::settings::Configurable;
-
-        if !infer_native_flags {
-            return Ok(isa_builder);
-        }
-
         let v = unsafe { libc::getauxval(libc::AT_HWCAP) };
         const HWCAP_S390X_VXRS_EXT2: libc::c_ulong = 32768;
         if (v & HWCAP_S390X_VXRS_EXT2) != 0 {
-            isa_builder.enable("has_vxrs_ext2").unwrap();
+            flags.push("has_vxrs_ext2");
             // There is no separate HWCAP bit for mie2, so assume
             // that any machine with vxrs_ext2 also has mie2.
-            isa_builder.enable("has_mie2").unwrap();
+            flags.push("has_mie2");
         }
     }
 
-    // squelch warnings about unused mut/variables on some platforms.
-    drop(&mut isa_builder);
-    drop(infer_native_flags);
-
-    Ok(isa_builder)
+    flags
 }
 
 #[cfg(test)]
 mod tests {
-    use super::builder;
-    use cranelift_codegen::isa::CallConv;
+    use super::{builder, builder_with_options, detected_flags, infer_native_flags};
+    use cranelift_codegen::isa::{self, CallConv};
     use cranelift_codegen::settings;
+    use target_lexicon::Triple;
 
     #[test]
     fn test() {
@@ -176,6 +173,33 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn infer_flags_of_looked_up_builder() {
+        let flags = |builder: isa::Builder| {
+            let isa = builder
+                .finish(settings::Flags::new(settings::builder()))
+                .unwrap();
+            isa.isa_flags()
+                .iter()
+                .map(|value| (value.name, value.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let native = match builder() {
+            Ok(builder) => flags(builder),
+            Err(_) => return,
+        };
+        let baseline = flags(isa::lookup(Triple::host()).unwrap());
+        assert_eq!(flags(builder_with_options(false).unwrap()), baseline);
+
+        let mut looked_up = isa::lookup(Triple::host()).unwrap();
+        infer_native_flags(&mut looked_up).unwrap();
+        assert_eq!(flags(looked_up), native);
+        for flag in detected_flags() {
+            let value = native.iter().find(|f| f.0 == flag).unwrap();
+            assert_eq!(value.1, format!("{}=1", flag));
+        }
+    }
 }
 
 /// Version number of this crate.
@@ -357,7 +357,7 @@ impl FuncTranslationState {
     }
 
     /// Peek at the top `n` values on the stack in the order they were pushed.
-    pub(crate) fn peekn(&self, n: usize) -> &[Value] {
+    pub fn peekn(&self, n: usize) -> &[Value] {
         self.ensure_length_is_at_least(n);
         &self.stack[self.stack.len() - n..]
     }
@@ -39,6 +39,30 @@ extern "C" {
  */
 WASI_DECLARE_OWN(config)
 
+/**
+ * \typedef wasi_output_pipe_t
+ * \brief Convenience alias for #wasi_output_pipe_t
+ *
+ * \struct wasi_output_pipe_t
+ * \brief An in-memory buffer collecting the stdout or stderr of WASI programs.
+ *
+ * \fn void wasi_output_pipe_delete(wasi_output_pipe_t *);
+ * \brief Deletes a pipe, after which its contents can no longer be read.
+ */
+WASI_DECLARE_OWN(output_pipe)
+
+/**
+ * \brief Creates a new, empty output pipe.
+ */
+WASI_API_EXTERN own wasi_output_pipe_t* wasi_output_pipe_new();
+
+/**
+ * \brief Copies everything written to the pipe so far into `out`.
+ *
+ * The caller is expected to deallocate `out` with `wasm_byte_vec_delete`.
+ */
+WASI_API_EXTERN void wasi_output_pipe_contents(const wasi_output_pipe_t* pipe, own wasm_byte_vec_t* out);
+
 /**
  * \brief Creates a new empty configuration object.
  *
@@ -100,6 +124,14 @@ WASI_API_EXTERN bool wasi_config_set_stdin_file(wasi_config_t* config, const cha
  */
 WASI_API_EXTERN void wasi_config_inherit_stdin(wasi_config_t* config);
 
+/**
+ * \brief Configures standard input to read the given bytes.
+ *
+ * The bytes are taken from `binary`, which is left empty, and the WASI program
+ * reads end-of-file after them.
+ */
+WASI_API_EXTERN void wasi_config_set_stdin_bytes(wasi_config_t* config, wasm_byte_vec_t* binary);
+
 /**
  * \brief Configures standard output to be written to the specified file.
  *
@@ -117,6 +149,14 @@ WASI_API_EXTERN bool wasi_config_set_stdout_file(wasi_config_t* config, const ch
  */
 WASI_API_EXTERN void wasi_config_inherit_stdout(wasi_config_t* config);
 
+/**
+ * \brief Configures standard output to be collected in the given pipe.
+ *
+ * The pipe can be deleted once this configuration has been used, and what the
+ * WASI program writes can be read with #wasi_output_pipe_contents.
+ */
+WASI_API_EXTERN void wasi_config_set_stdout_pipe(wasi_config_t* config, const wasi_output_pipe_t* pipe);
+
 /**
  * \brief Configures standard output to be written to the specified file.
  *
@@ -134,6 +174,12 @@ WASI_API_EXTERN bool wasi_config_set_stderr_file(wasi_config_t* config, const ch
  */
 WASI_API_EXTERN void wasi_config_inherit_stderr(wasi_config_t* config);
 
+/**
+ * \brief Configures standard error to be collected in the given pipe, like
+ * #wasi_config_set_stdout_pipe.
+ */
+WASI_API_EXTERN void wasi_config_set_stderr_pipe(wasi_config_t* config, const wasi_output_pipe_t* pipe);
+
 /**
  * \brief Configures a "preopened directory" to be available to WASI APIs.
  *
@@ -146,6 +192,71 @@ WASI_API_EXTERN void wasi_config_inherit_stderr(wasi_config_t* config);
  */
 WASI_API_EXTERN bool wasi_config_preopen_dir(wasi_config_t* config, const char* path, const char* guest_path);
 
+/// Lets WASI programs list and open what's in a preopened directory.
+#define WASI_DIR_PERMS_READ 1
+/// Lets WASI programs create, rename and remove things in a preopened
+/// directory.
+#define WASI_DIR_PERMS_WRITE 2
+/// Lets WASI programs read the files they open in a preopened directory.
+#define WASI_FILE_PERMS_READ 1
+/// Lets WASI programs modify the files they open in a preopened directory.
+#define WASI_FILE_PERMS_WRITE 2
+
+/**
+ * \brief Configures a "preopened directory" like #wasi_config_preopen_dir, but
+ * only with the given permissions.
+ *
+ * `dir_perms` is a combination of `WASI_DIR_PERMS_*` flags applying to the
+ * directory and its subdirectories, and `file_perms` a combination of
+ * `WASI_FILE_PERMS_*` flags applying to the files opened in them. Passing only
+ * the `READ` flags makes the directory read-only;
+ * #wasi_config_preopen_dir grants all of them.
+ */
+WASI_API_EXTERN bool wasi_config_preopen_dir_with_perms(
+    wasi_config_t* config,
+    const char* path,
+    const char* guest_path,
+    uint32_t dir_perms,
+    uint32_t file_perms
+);
+
+/**
+ * \brief A callback returning the current time of a clock, in nanoseconds.
+ *
+ * It's passed the `data` given along with it.
+ */
+typedef uint64_t (*wasi_clock_now_callback_t)(void *data);
+
+/**
+ * \brief Replaces the clock WASI programs read the time of day from.
+ *
+ * `now` returns the number of nanoseconds since the Unix epoch, and
+ * `resolution_nanos` is the resolution reported to the program. `finalizer`,
+ * if not `NULL`, is called with `data` once the clock is no longer used.
+ */
+WASI_API_EXTERN void wasi_config_set_system_clock(
+    wasi_config_t* config,
+    wasi_clock_now_callback_t now,
+    uint64_t resolution_nanos,
+    void *data,
+    void (*finalizer)(void*)
+);
+
+/**
+ * \brief Replaces the monotonic clock WASI programs measure time with.
+ *
+ * `now` returns the number of nanoseconds since an arbitrary point in time,
+ * and must never go backwards. The other arguments are as for
+ * #wasi_config_set_system_clock.
+ */
+WASI_API_EXTERN void wasi_config_set_monotonic_clock(
+    wasi_config_t* config,
+    wasi_clock_now_callback_t now,
+    uint64_t resolution_nanos,
+    void *data,
+    void (*finalizer)(void*)
+);
+
 #undef own
 
 #ifdef __cplusplus
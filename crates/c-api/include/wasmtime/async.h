@@ -0,0 +1,156 @@
+/**
+ * \file wasmtime/async.h
+ *
+ * \brief Wasmtime async functionality
+ *
+ * Async support lets wasm be called and instantiated without blocking the
+ * calling thread until it finishes. Each async operation returns a
+ * #wasmtime_call_future_t which the embedder polls with
+ * #wasmtime_call_future_poll. Every time wasm yields, which it does when it
+ * runs out of fuel after #wasmtime_context_out_of_fuel_async_yield, consumes
+ * the interval of fuel given to #wasmtime_context_fuel_async_yield_interval or
+ * reaches its epoch deadline after
+ * #wasmtime_context_epoch_deadline_async_yield_and_update, polling returns
+ * `false` and the embedder is free to do other work, such as running other
+ * guests, before polling again.
+ *
+ * Async support must be enabled with #wasmtime_config_async_support_set, after
+ * which wasm in the engine's stores can only be run with the functions in this
+ * header.
+ */
+
+#ifndef WASMTIME_ASYNC_H
+#define WASMTIME_ASYNC_H
+
+#include <wasm.h>
+#include <wasmtime/error.h>
+#include <wasmtime/config.h>
+#include <wasmtime/store.h>
+#include <wasmtime/func.h>
+#include <wasmtime/linker.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/**
+ * \brief Whether or not to enable support for async calls and instantiation.
+ *
+ * This setting is `false` by default.
+ */
+WASM_API_EXTERN void wasmtime_config_async_support_set(wasm_config_t*, bool);
+
+/**
+ * \brief The size of the stack async wasm runs on, in bytes.
+ *
+ * This defaults to 2 MiB, and `false` is returned if it's smaller than the
+ * maximum wasm stack size.
+ */
+WASM_API_EXTERN bool wasmtime_config_async_stack_size_set(wasm_config_t*, size_t);
+
+/**
+ * \brief Makes wasm yield when it runs out of fuel, instead of trapping.
+ *
+ * Each time the store runs out of fuel, `fuel_to_inject` fuel is added to it
+ * and wasm yields to the embedder polling it. After `injection_count` yields
+ * running out of fuel traps as usual.
+ */
+WASM_API_EXTERN void wasmtime_context_out_of_fuel_async_yield(
+    wasmtime_context_t *context,
+    uint64_t injection_count,
+    uint64_t fuel_to_inject
+);
+
+/**
+ * \brief Makes wasm yield every time it consumes `interval` units of fuel,
+ * without trapping until all of the store's fuel is consumed.
+ *
+ * An `interval` of 0 turns yielding off again. An error is returned if fuel
+ * consumption or async support isn't enabled in the store's config.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_context_fuel_async_yield_interval(
+    wasmtime_context_t *context,
+    uint64_t interval
+);
+
+/**
+ * \brief Makes wasm yield when it reaches its epoch deadline, instead of
+ * trapping, after which the deadline is `delta` ticks beyond the current
+ * epoch.
+ */
+WASM_API_EXTERN void wasmtime_context_epoch_deadline_async_yield_and_update(
+    wasmtime_context_t *context,
+    uint64_t delta
+);
+
+/**
+ * \typedef wasmtime_call_future_t
+ * \brief Convenience alias for #wasmtime_call_future
+ *
+ * \struct wasmtime_call_future
+ * \brief An async call or instantiation in progress.
+ *
+ * Everything passed to the function which returned the future must stay
+ * alive, and the store must not be used otherwise, until the future has
+ * completed or been deleted.
+ */
+typedef struct wasmtime_call_future wasmtime_call_future_t;
+
+/**
+ * \brief Runs the operation until it either completes or wasm yields.
+ *
+ * Returns `true` once the operation has completed, after which its results,
+ * trap or error have been written to the locations passed when it was started
+ * and it must not be polled again. If the operation panics, which is a bug in
+ * Wasmtime, it completes with an error describing the panic, and the store
+ * shouldn't be used anymore.
+ */
+WASM_API_EXTERN bool wasmtime_call_future_poll(wasmtime_call_future_t *future);
+
+/**
+ * \brief Deletes a future, cancelling the operation if it hasn't completed.
+ */
+WASM_API_EXTERN void wasmtime_call_future_delete(wasmtime_call_future_t *future);
+
+/**
+ * \brief Calls a function like #wasmtime_func_call, but asynchronously.
+ *
+ * Once the returned future completes, either the `nresults` results have been
+ * written to `results`, `trap_ret` points to the trap the call ended with, or
+ * `error_ret` points to the error it failed with. `trap_ret` and `error_ret`
+ * must be initialized to `NULL`.
+ */
+WASM_API_EXTERN wasmtime_call_future_t *wasmtime_func_call_async(
+    wasmtime_context_t *context,
+    const wasmtime_func_t *func,
+    const wasmtime_val_t *args,
+    size_t nargs,
+    wasmtime_val_t *results,
+    size_t nresults,
+    wasm_trap_t **trap_ret,
+    wasmtime_error_t **error_ret
+);
+
+/**
+ * \brief Instantiates a module like #wasmtime_linker_instantiate, but
+ * asynchronously.
+ *
+ * Once the returned future completes, either `instance` has been set,
+ * `trap_ret` points to the trap the start function ended with, or
+ * `error_ret` points to the error instantiation failed with. `trap_ret` and
+ * `error_ret` must be initialized to `NULL`.
+ */
+WASM_API_EXTERN wasmtime_call_future_t *wasmtime_linker_instantiate_async(
+    const wasmtime_linker_t *linker,
+    wasmtime_context_t *context,
+    const wasmtime_module_t *module,
+    wasmtime_instance_t *instance,
+    wasm_trap_t **trap_ret,
+    wasmtime_error_t **error_ret
+);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif
+
+#endif // WASMTIME_ASYNC_H
@@ -33,6 +33,11 @@ enum wasmtime_strategy_enum { // Strategy
   /// Indicates that Wasmtime will unconditionally use Cranelift to compile
   /// WebAssembly code.
   WASMTIME_STRATEGY_CRANELIFT,
+
+  /// Indicates that Wasmtime will use its single-pass baseline compiler,
+  /// which compiles faster than Cranelift but generates slower code. Functions
+  /// it doesn't support are compiled with Cranelift instead.
+  WASMTIME_STRATEGY_BASELINE,
 };
 
 /**
@@ -80,6 +85,9 @@ enum wasmtime_profiling_strategy_enum { // ProfilingStrategy
   ///
   /// Note that this isn't always enabled at build time.
   WASMTIME_PROFILING_STRATEGY_VTUNE,
+  /// Linux's simple "perf map" support in `perf` is enabled and the address
+  /// and name of JIT code is written to `/tmp/perf-<pid>.map`.
+  WASMTIME_PROFILING_STRATEGY_PERFMAP,
 };
 
 #define WASMTIME_CONFIG_PROP(ret, name, ty) \
@@ -251,6 +259,19 @@ WASMTIME_CONFIG_PROP(void, static_memory_guard_size, uint64_t)
  */
 WASMTIME_CONFIG_PROP(void, dynamic_memory_guard_size, uint64_t)
 
+/**
+ * \brief Configures whether generated code may rely on signals to raise traps.
+ *
+ * This setting is `true` by default. When disabled Wasmtime doesn't install
+ * any signal handlers, all memory accesses are bounds-checked explicitly, and
+ * all traps are raised by calls from generated code into Wasmtime. This is
+ * useful for hosts which manage signal handling themselves.
+ *
+ * For more information see the Rust documentation at
+ * https://bytecodealliance.github.io/wasmtime/api/wasmtime/struct.Config.html#method.signals_based_traps.
+ */
+WASMTIME_CONFIG_PROP(void, signals_based_traps, bool)
+
 /**
  * \brief Enables Wasmtime's cache and loads configuration from the specified
  * path.
@@ -159,6 +159,56 @@ WASM_API_EXTERN wasmtime_error_t* wasmtime_linker_define_wasi(
     wasmtime_linker_t *linker
 );
 
+/**
+ * \brief Defines an existing item of this linker under another name.
+ *
+ * \param linker the linker the name is being defined in.
+ * \param module the module name of the existing item.
+ * \param module_len the byte length of `module`
+ * \param name the field name of the existing item.
+ * \param name_len the byte length of `name`
+ * \param as_module the module name to also define the item under.
+ * \param as_module_len the byte length of `as_module`
+ * \param as_name the field name to also define the item under.
+ * \param as_name_len the byte length of `as_name`
+ *
+ * \return On success `NULL` is returned, otherwise an error is returned which
+ * describes why the definition failed, for example because no item is defined
+ * under `module` and `name`.
+ */
+WASM_API_EXTERN wasmtime_error_t* wasmtime_linker_alias(
+    wasmtime_linker_t *linker,
+    const char *module,
+    size_t module_len,
+    const char *name,
+    size_t name_len,
+    const char *as_module,
+    size_t as_module_len,
+    const char *as_name,
+    size_t as_name_len
+);
+
+/**
+ * \brief Defines all items of this linker under the module name `module` under
+ * the module name `as_module` too.
+ *
+ * \param linker the linker the names are being defined in.
+ * \param module the module name of the existing items.
+ * \param module_len the byte length of `module`
+ * \param as_module the module name to also define the items under.
+ * \param as_module_len the byte length of `as_module`
+ *
+ * \return On success `NULL` is returned, otherwise an error is returned which
+ * describes why the definition failed.
+ */
+WASM_API_EXTERN wasmtime_error_t* wasmtime_linker_alias_module(
+    wasmtime_linker_t *linker,
+    const char *module,
+    size_t module_len,
+    const char *as_module,
+    size_t as_module_len
+);
+
 /**
  * \brief Defines an instance under the specified name in this linker.
  *
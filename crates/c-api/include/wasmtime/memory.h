@@ -116,6 +116,48 @@ WASM_API_EXTERN wasmtime_error_t *wasmtime_memory_grow(
     uint64_t *prev_size
 );
 
+/**
+ * \brief Copies bytes out of a linear memory into a buffer.
+ *
+ * \param store the store that owns `memory`
+ * \param memory the memory to read from
+ * \param offset the byte offset within `memory` to start reading at
+ * \param buffer where to write the bytes that are read
+ * \param len the number of bytes to read
+ *
+ * If `offset + len` is out of bounds of the memory then `buffer` is left
+ * untouched and an error is returned. Unlike #wasmtime_memory_data this does
+ * not require the caller to perform their own bounds checks, and it remains
+ * correct if the memory has been grown since it was last accessed.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_memory_read(
+    const wasmtime_context_t *store,
+    const wasmtime_memory_t *memory,
+    size_t offset,
+    uint8_t *buffer,
+    size_t len
+);
+
+/**
+ * \brief Copies bytes from a buffer into a linear memory.
+ *
+ * \param store the store that owns `memory`
+ * \param memory the memory to write to
+ * \param offset the byte offset within `memory` to start writing at
+ * \param buffer the bytes to write
+ * \param len the number of bytes to write
+ *
+ * If `offset + len` is out of bounds of the memory then the memory is left
+ * unmodified and an error is returned.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_memory_write(
+    wasmtime_context_t *store,
+    const wasmtime_memory_t *memory,
+    size_t offset,
+    const uint8_t *buffer,
+    size_t len
+);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif
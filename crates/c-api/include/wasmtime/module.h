@@ -93,6 +93,32 @@ WASM_API_EXTERN wasmtime_error_t *wasmtime_module_validate(
     size_t wasm_len
 );
 
+/**
+ * \brief Compiles a WebAssembly binary into serialized artifacts without
+ * creating a module.
+ *
+ * \param engine the engine whose configuration, and possibly non-native
+ *   target, the binary is compiled for
+ * \param wasm the WebAssembly binary to compile
+ * \param wasm_len the length of `wasm`
+ * \param ret if compilation is successful, this byte vector is filled in with
+ *   the serialized compiled module.
+ *
+ * \return a non-null error if compilation fails, or returns `NULL`. If
+ * compilation fails then `ret` isn't touched.
+ *
+ * The output is the same as that of #wasmtime_module_serialize and can be
+ * passed to #wasmtime_module_deserialize. This function does not take
+ * ownership of its arguments, and the caller is expected to deallocate the
+ * returned #wasmtime_error_t and #wasm_byte_vec_t.
+ */
+WASM_API_EXTERN wasmtime_error_t* wasmtime_engine_precompile_module(
+    wasm_engine_t *engine,
+    const uint8_t *wasm,
+    size_t wasm_len,
+    wasm_byte_vec_t *ret
+);
+
 /**
  * \brief This function serializes compiled module artifacts as blob data.
  *
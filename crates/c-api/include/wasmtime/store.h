@@ -177,6 +177,52 @@ WASM_API_EXTERN wasmtime_error_t *wasmtime_context_set_wasi(wasmtime_context_t *
  */
 WASM_API_EXTERN void wasmtime_context_set_epoch_deadline(wasmtime_context_t *context, uint64_t ticks_beyond_current);
 
+/**
+ * \brief Transitions between WebAssembly and the host passed to a
+ * #wasmtime_call_hook_callback_t, values are in #wasmtime_call_hook_enum.
+ */
+typedef uint8_t wasmtime_call_hook_t;
+
+/**
+ * \brief The different transitions which a call hook is invoked for.
+ */
+enum wasmtime_call_hook_enum {
+  /// The host is calling into WebAssembly.
+  WASMTIME_CALL_HOOK_CALLING_WASM,
+  /// WebAssembly is returning to the host.
+  WASMTIME_CALL_HOOK_RETURNING_FROM_WASM,
+  /// WebAssembly is calling a host function.
+  WASMTIME_CALL_HOOK_CALLING_HOST,
+  /// A host function is returning to WebAssembly.
+  WASMTIME_CALL_HOOK_RETURNING_FROM_HOST,
+};
+
+/**
+ * \brief Callback signature for #wasmtime_store_call_hook.
+ *
+ * The `env` argument is the data passed to #wasmtime_store_call_hook. A
+ * callback may return a trap to abort the transition: a trap returned when
+ * WebAssembly calls the host is raised as if the host function had returned
+ * it, and a trap returned when WebAssembly returns to the host replaces the
+ * results of the call. Returning `NULL` lets execution continue.
+ */
+typedef wasm_trap_t *(*wasmtime_call_hook_callback_t)(void *env, wasmtime_call_hook_t hook);
+
+/**
+ * \brief Configures a callback to invoke on every transition between
+ * WebAssembly and host code within `store`.
+ *
+ * This replaces any previously configured call hook. The `finalizer`, if not
+ * `NULL`, is called with `env` once the hook is replaced or the store is
+ * deleted.
+ */
+WASM_API_EXTERN void wasmtime_store_call_hook(
+    wasmtime_store_t *store,
+    wasmtime_call_hook_callback_t callback,
+    void *env,
+    void (*finalizer)(void*)
+);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif
@@ -118,6 +118,56 @@ WASM_API_EXTERN wasmtime_error_t *wasmtime_table_grow(
     uint32_t *prev_size
 );
 
+/**
+ * \brief Fills a range of a table with a value.
+ *
+ * \param store the store that owns `table`
+ * \param table the table to fill
+ * \param dst the index of the first element to fill
+ * \param value the value to store in each element
+ * \param len the number of elements to fill
+ *
+ * This function will store `value` into elements `dst` through `dst + len` of
+ * the table, like the `table.fill` instruction. This can fail if `value` has
+ * the wrong type for the table or if the range is out of bounds, in which case
+ * no elements are modified.
+ *
+ * This function does not take ownership of any of its arguments.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_table_fill(
+    wasmtime_context_t *store,
+    const wasmtime_table_t *table,
+    uint32_t dst,
+    const wasmtime_val_t *value,
+    uint32_t len
+);
+
+/**
+ * \brief Copies a range of elements from one table to another.
+ *
+ * \param store the store that owns both tables
+ * \param dst_table the table to copy elements into
+ * \param dst_index the index in `dst_table` of the first element to write
+ * \param src_table the table to copy elements from, which may be `dst_table`
+ * \param src_index the index in `src_table` of the first element to read
+ * \param len the number of elements to copy
+ *
+ * This function behaves like the `table.copy` instruction, and overlapping
+ * ranges within one table are handled as if the elements were first copied to
+ * a temporary buffer. This can fail if the tables have different element
+ * types or if either range is out of bounds.
+ *
+ * This function does not take ownership of any of its arguments.
+ */
+WASM_API_EXTERN wasmtime_error_t *wasmtime_table_copy(
+    wasmtime_context_t *store,
+    const wasmtime_table_t *dst_table,
+    uint32_t dst_index,
+    const wasmtime_table_t *src_table,
+    uint32_t src_index,
+    uint32_t len
+);
+
 #ifdef __cplusplus
 }  // extern "C"
 #endif
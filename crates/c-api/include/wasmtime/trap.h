@@ -46,6 +46,8 @@ enum wasmtime_trap_code_enum {
   WASMTIME_TRAP_CODE_UNREACHABLE_CODE_REACHED,
   /// Execution has potentially run too long and may be interrupted.
   WASMTIME_TRAP_CODE_INTERRUPT,
+  /// A `memory.grow` failed in a store configured to trap instead.
+  WASMTIME_TRAP_CODE_MEMORY_GROW_FAILED,
 };
 
 /**
@@ -0,0 +1,314 @@
+//! Async calls and instantiation, which return a future for the embedder to
+//! poll instead of blocking the calling thread until wasm finishes.
+
+use anyhow::anyhow;
+use std::any::Any;
+use std::future::Future;
+use std::mem::{self, MaybeUninit};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, RawWaker, RawWakerVTable, Waker};
+use wasmtime::{Func, Instance, Trap, Val};
+
+use crate::{
+    wasm_config_t, wasm_trap_t, wasmtime_error_t, wasmtime_linker_t, wasmtime_module_t,
+    wasmtime_val_t, CStoreContextMut,
+};
+
+#[no_mangle]
+pub extern "C" fn wasmtime_config_async_support_set(c: &mut wasm_config_t, enable: bool) {
+    c.config.async_support(enable);
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_config_async_stack_size_set(c: &mut wasm_config_t, size: usize) -> bool {
+    c.config.async_stack_size(size).is_ok()
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_context_out_of_fuel_async_yield(
+    mut store: CStoreContextMut<'_>,
+    injection_count: u64,
+    fuel_to_inject: u64,
+) {
+    store.out_of_fuel_async_yield(injection_count, fuel_to_inject);
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_context_fuel_async_yield_interval(
+    mut store: CStoreContextMut<'_>,
+    interval: u64,
+) -> Option<Box<wasmtime_error_t>> {
+    let interval = if interval == 0 { None } else { Some(interval) };
+    crate::handle_result(store.fuel_async_yield_interval(interval), |()| {})
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_context_epoch_deadline_async_yield_and_update(
+    mut store: CStoreContextMut<'_>,
+    delta: u64,
+) {
+    store.epoch_deadline_async_yield_and_update(delta);
+}
+
+/// An async call or instantiation in progress.
+///
+/// The future borrows everything passed to the function which created it,
+/// which the C API documents must outlive it, so its lifetime is erased.
+pub struct wasmtime_call_future_t {
+    /// The operation, until it has panicked.
+    future: Option<Pin<Box<dyn Future<Output = ()> + 'static>>>,
+    /// Where the error the operation fails with if it panics is written.
+    error_ret: *mut *mut wasmtime_error_t,
+}
+
+impl wasmtime_call_future_t {
+    unsafe fn new<'a>(
+        error_ret: *mut *mut wasmtime_error_t,
+        future: impl Future<Output = ()> + 'a,
+    ) -> Box<wasmtime_call_future_t> {
+        let future: Pin<Box<dyn Future<Output = ()> + 'a>> = Box::pin(future);
+        Box::new(wasmtime_call_future_t {
+            future: Some(mem::transmute(future)),
+            error_ret,
+        })
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_call_future_poll(future: &mut wasmtime_call_future_t) -> bool {
+    // Wasm only suspends itself to yield, waking its waker right away, so
+    // there's nothing to be woken and the embedder simply polls again.
+    let waker = noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let inner = match &mut future.future {
+        Some(inner) => inner,
+        None => return true,
+    };
+    // Panics can't unwind into C, so a panicking operation completes with an
+    // error instead. It's dropped first, along with its borrow of
+    // `error_ret`.
+    match panic::catch_unwind(AssertUnwindSafe(|| inner.as_mut().poll(&mut cx))) {
+        Ok(poll) => poll.is_ready(),
+        Err(payload) => {
+            future.future = None;
+            let error = anyhow!("the operation panicked: {}", panic_message(&*payload));
+            unsafe {
+                *future.error_ret = Box::into_raw(Box::new(wasmtime_error_t::from(error)));
+            }
+            true
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_call_future_delete(_future: Box<wasmtime_call_future_t>) {}
+
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |_| RawWaker::new(ptr::null(), &VTABLE),
+        |_| {},
+        |_| {},
+        |_| {},
+    );
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
+
+/// Reports the failure of an async call through `trap_ret` if it trapped, or
+/// `error_ret` otherwise.
+fn report_error(
+    error: anyhow::Error,
+    trap_ret: &mut *mut wasm_trap_t,
+    error_ret: &mut *mut wasmtime_error_t,
+) {
+    match error.downcast::<Trap>() {
+        Ok(trap) => *trap_ret = Box::into_raw(Box::new(wasm_trap_t::new(trap))),
+        Err(error) => *error_ret = Box::into_raw(Box::new(wasmtime_error_t::from(error))),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_func_call_async<'a>(
+    store: CStoreContextMut<'a>,
+    func: &Func,
+    args: *const wasmtime_val_t,
+    nargs: usize,
+    results: *mut MaybeUninit<wasmtime_val_t>,
+    nresults: usize,
+    trap_ret: &'a mut *mut wasm_trap_t,
+    error_ret: &'a mut *mut wasmtime_error_t,
+) -> Box<wasmtime_call_future_t> {
+    let func = *func;
+    let params = crate::slice_from_raw_parts(args, nargs)
+        .iter()
+        .map(|i| i.to_val())
+        .collect::<Vec<_>>();
+    let results = crate::slice_from_raw_parts_mut(results, nresults);
+    let error_ret: *mut *mut wasmtime_error_t = error_ret;
+    wasmtime_call_future_t::new(error_ret, async move {
+        let mut wt_results = vec![Val::null(); results.len()];
+        match func.call_async(store, &params, &mut wt_results).await {
+            Ok(()) => {
+                for (slot, val) in results.iter_mut().zip(wt_results) {
+                    crate::initialize(slot, wasmtime_val_t::from_val(val));
+                }
+            }
+            Err(error) => report_error(error, trap_ret, &mut *error_ret),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_linker_instantiate_async<'a>(
+    linker: &'a wasmtime_linker_t,
+    store: CStoreContextMut<'a>,
+    module: &'a wasmtime_module_t,
+    instance_ptr: &'a mut Instance,
+    trap_ret: &'a mut *mut wasm_trap_t,
+    error_ret: &'a mut *mut wasmtime_error_t,
+) -> Box<wasmtime_call_future_t> {
+    let error_ret: *mut *mut wasmtime_error_t = error_ret;
+    wasmtime_call_future_t::new(error_ret, async move {
+        match linker.linker.instantiate_async(store, &module.module).await {
+            Ok(instance) => *instance_ptr = instance,
+            Err(error) => report_error(error, trap_ret, &mut *error_ret),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        wasm_config_new, wasm_engine_new_with_config, wasm_name_t, wasmtime_linker_new,
+        wasmtime_module_t, wasmtime_store_context, wasmtime_store_new, WASMTIME_I32,
+    };
+
+    #[test]
+    fn poll_to_completion() {
+        let mut config = wasm_config_new();
+        wasmtime_config_async_support_set(&mut config, true);
+        config.config.consume_fuel(true);
+        let engine = wasm_engine_new_with_config(config);
+        let mut store = wasmtime_store_new(&engine, ptr::null_mut(), None);
+        wasmtime_store_context(&mut store)
+            .add_fuel(u64::MAX)
+            .unwrap();
+        assert!(wasmtime_context_fuel_async_yield_interval(
+            wasmtime_store_context(&mut store),
+            1000
+        )
+        .is_none());
+
+        let linker = wasmtime_linker_new(&engine);
+        let wasm = wat::parse_str(
+            r#"
+                (module
+                    (func (export "count") (param i32) (result i32)
+                        (local i32)
+                        (loop
+                            (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+                            (br_if 0 (i32.lt_u (local.get 1) (local.get 0))))
+                        (local.get 1))
+                )
+            "#,
+        )
+        .unwrap();
+        let module = wasmtime_module_t {
+            module: wasmtime::Module::new(&engine.engine, wasm).unwrap(),
+        };
+
+        unsafe {
+            let mut instance = MaybeUninit::<Instance>::uninit();
+            let mut trap = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            let mut future = wasmtime_linker_instantiate_async(
+                &linker,
+                wasmtime_store_context(&mut store),
+                &module,
+                &mut *instance.as_mut_ptr(),
+                &mut trap,
+                &mut error,
+            );
+            while !wasmtime_call_future_poll(&mut future) {}
+            wasmtime_call_future_delete(future);
+            assert!(trap.is_null() && error.is_null());
+            let instance = instance.assume_init();
+            let func = instance
+                .get_func(wasmtime_store_context(&mut store), "count")
+                .unwrap();
+
+            let arg = wasmtime_val_t::from_val(Val::I32(100_000));
+            let mut result = MaybeUninit::uninit();
+            let mut future = wasmtime_func_call_async(
+                wasmtime_store_context(&mut store),
+                &func,
+                &arg,
+                1,
+                &mut result,
+                1,
+                &mut trap,
+                &mut error,
+            );
+            let mut polls = 1;
+            while !wasmtime_call_future_poll(&mut future) {
+                polls += 1;
+            }
+            wasmtime_call_future_delete(future);
+            assert!(trap.is_null() && error.is_null());
+            assert!(polls > 1);
+            let result = result.assume_init();
+            assert_eq!(result.kind, WASMTIME_I32);
+            assert_eq!(result.of.i32, 100_000);
+        }
+    }
+
+    #[test]
+    fn panic_is_an_error() {
+        let mut config = wasm_config_new();
+        wasmtime_config_async_support_set(&mut config, true);
+        let engine = wasm_engine_new_with_config(config);
+        let mut store = wasmtime_store_new(&engine, ptr::null_mut(), None);
+        let func = Func::wrap(wasmtime_store_context(&mut store), || {
+            panic!("the host function panicked")
+        });
+
+        unsafe {
+            let mut trap = ptr::null_mut();
+            let mut error = ptr::null_mut();
+            let mut future = wasmtime_func_call_async(
+                wasmtime_store_context(&mut store),
+                &func,
+                ptr::null(),
+                0,
+                ptr::null_mut(),
+                0,
+                &mut trap,
+                &mut error,
+            );
+            while !wasmtime_call_future_poll(&mut future) {}
+            assert!(wasmtime_call_future_poll(&mut future));
+            wasmtime_call_future_delete(future);
+            assert!(trap.is_null());
+            assert!(!error.is_null());
+            let error = Box::from_raw(error);
+            let mut message: wasm_name_t = Vec::new().into();
+            crate::wasmtime_error_message(&error, &mut message);
+            assert_eq!(
+                message.as_slice(),
+                b"the operation panicked: the host function panicked"
+            );
+        }
+    }
+}
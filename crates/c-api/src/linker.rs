@@ -9,7 +9,7 @@ use wasmtime::{Func, Instance, Linker};
 
 #[repr(C)]
 pub struct wasmtime_linker_t {
-    linker: Linker<crate::StoreData>,
+    pub(crate) linker: Linker<crate::StoreData>,
 }
 
 #[no_mangle]
@@ -55,6 +55,40 @@ pub unsafe extern "C" fn wasmtime_linker_define(
     handle_result(linker.define(module, name, item), |_linker| ())
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_linker_alias(
+    linker: &mut wasmtime_linker_t,
+    module: *const u8,
+    module_len: usize,
+    name: *const u8,
+    name_len: usize,
+    as_module: *const u8,
+    as_module_len: usize,
+    as_name: *const u8,
+    as_name_len: usize,
+) -> Option<Box<wasmtime_error_t>> {
+    let linker = &mut linker.linker;
+    let module = to_str!(module, module_len);
+    let name = to_str!(name, name_len);
+    let as_module = to_str!(as_module, as_module_len);
+    let as_name = to_str!(as_name, as_name_len);
+    handle_result(linker.alias(module, name, as_module, as_name), |_linker| ())
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_linker_alias_module(
+    linker: &mut wasmtime_linker_t,
+    module: *const u8,
+    module_len: usize,
+    as_module: *const u8,
+    as_module_len: usize,
+) -> Option<Box<wasmtime_error_t>> {
+    let linker = &mut linker.linker;
+    let module = to_str!(module, module_len);
+    let as_module = to_str!(as_module, as_module_len);
+    handle_result(linker.alias_module(module, as_module), |()| ())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn wasmtime_linker_define_func(
     linker: &mut wasmtime_linker_t,
@@ -1,6 +1,6 @@
 use crate::{
-    handle_result, wasm_extern_t, wasm_memorytype_t, wasm_store_t, wasmtime_error_t, CStoreContext,
-    CStoreContextMut,
+    handle_result, slice_from_raw_parts, slice_from_raw_parts_mut, wasm_extern_t,
+    wasm_memorytype_t, wasm_store_t, wasmtime_error_t, CStoreContext, CStoreContextMut,
 };
 use std::convert::TryFrom;
 use wasmtime::{Extern, Memory};
@@ -127,3 +127,30 @@ pub extern "C" fn wasmtime_memory_grow(
 ) -> Option<Box<wasmtime_error_t>> {
     handle_result(mem.grow(store, delta), |prev| *prev_size = prev)
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_memory_read(
+    store: CStoreContext<'_>,
+    mem: &Memory,
+    offset: usize,
+    buffer: *mut u8,
+    len: usize,
+) -> Option<Box<wasmtime_error_t>> {
+    let buffer = slice_from_raw_parts_mut(buffer, len);
+    handle_result(mem.read(store, offset, buffer).map_err(Into::into), |()| {})
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_memory_write(
+    store: CStoreContextMut<'_>,
+    mem: &Memory,
+    offset: usize,
+    buffer: *const u8,
+    len: usize,
+) -> Option<Box<wasmtime_error_t>> {
+    let buffer = slice_from_raw_parts(buffer, len);
+    handle_result(
+        mem.write(store, offset, buffer).map_err(Into::into),
+        |()| {},
+    )
+}
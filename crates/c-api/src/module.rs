@@ -176,6 +176,19 @@ pub unsafe extern "C" fn wasmtime_module_validate(
     handle_result(Module::validate(&engine.engine, binary), |()| {})
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_engine_precompile_module(
+    engine: &wasm_engine_t,
+    wasm: *const u8,
+    len: usize,
+    ret: &mut wasm_byte_vec_t,
+) -> Option<Box<wasmtime_error_t>> {
+    let binary = crate::slice_from_raw_parts(wasm, len);
+    handle_result(engine.engine.precompile_module(binary), |buf| {
+        ret.set_buffer(buf)
+    })
+}
+
 #[no_mangle]
 pub extern "C" fn wasmtime_module_serialize(
     module: &wasmtime_module_t,
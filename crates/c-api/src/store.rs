@@ -1,8 +1,8 @@
-use crate::{wasm_engine_t, wasmtime_error_t, wasmtime_val_t, ForeignData};
+use crate::{wasm_engine_t, wasm_trap_t, wasmtime_error_t, wasmtime_val_t, ForeignData};
 use std::cell::UnsafeCell;
 use std::ffi::c_void;
 use std::sync::Arc;
-use wasmtime::{AsContext, AsContextMut, Store, StoreContext, StoreContextMut, Val};
+use wasmtime::{AsContext, AsContextMut, CallHook, Store, StoreContext, StoreContextMut, Val};
 
 /// This representation of a `Store` is used to implement the `wasm.h` API.
 ///
@@ -168,3 +168,39 @@ pub extern "C" fn wasmtime_context_set_epoch_deadline(
 ) {
     store.set_epoch_deadline(ticks_beyond_current);
 }
+
+#[repr(u8)]
+#[derive(Clone, Copy)]
+pub enum wasmtime_call_hook_t {
+    WASMTIME_CALL_HOOK_CALLING_WASM,
+    WASMTIME_CALL_HOOK_RETURNING_FROM_WASM,
+    WASMTIME_CALL_HOOK_CALLING_HOST,
+    WASMTIME_CALL_HOOK_RETURNING_FROM_HOST,
+}
+
+pub type wasmtime_call_hook_callback_t =
+    extern "C" fn(*mut c_void, wasmtime_call_hook_t) -> Option<Box<wasm_trap_t>>;
+
+#[no_mangle]
+pub extern "C" fn wasmtime_store_call_hook(
+    store: &mut wasmtime_store_t,
+    callback: wasmtime_call_hook_callback_t,
+    data: *mut c_void,
+    finalizer: Option<extern "C" fn(*mut c_void)>,
+) {
+    let foreign = ForeignData { data, finalizer };
+    store.store.call_hook(move |_, hook| {
+        drop(&foreign); // move entire foreign into this closure
+        use wasmtime_call_hook_t::*;
+        let hook = match hook {
+            CallHook::CallingWasm => WASMTIME_CALL_HOOK_CALLING_WASM,
+            CallHook::ReturningFromWasm => WASMTIME_CALL_HOOK_RETURNING_FROM_WASM,
+            CallHook::CallingHost => WASMTIME_CALL_HOOK_CALLING_HOST,
+            CallHook::ReturningFromHost => WASMTIME_CALL_HOOK_RETURNING_FROM_HOST,
+        };
+        match callback(foreign.data, hook) {
+            Some(trap) => Err(trap.trap),
+            None => Ok(()),
+        }
+    });
+}
@@ -179,3 +179,27 @@ pub unsafe extern "C" fn wasmtime_table_grow(
         *prev_size = prev
     })
 }
+
+#[no_mangle]
+pub unsafe extern "C" fn wasmtime_table_fill(
+    store: CStoreContextMut<'_>,
+    table: &Table,
+    dst: u32,
+    val: &wasmtime_val_t,
+    len: u32,
+) -> Option<Box<wasmtime_error_t>> {
+    handle_result(table.fill(store, dst, val.to_val(), len), |()| {})
+}
+
+#[no_mangle]
+pub extern "C" fn wasmtime_table_copy(
+    store: CStoreContextMut<'_>,
+    dst_table: &Table,
+    dst_index: u32,
+    src_table: &Table,
+    src_index: u32,
+    len: u32,
+) -> Option<Box<wasmtime_error_t>> {
+    let result = Table::copy(store, dst_table, dst_index, src_table, src_index, len);
+    handle_result(result, |()| {})
+}
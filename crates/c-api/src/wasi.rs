@@ -1,15 +1,20 @@
 //! The WASI embedding API definitions for Wasmtime.
 
+use crate::{wasm_byte_vec_t, ForeignData};
 use anyhow::Result;
 use cap_std::ambient_authority;
-use std::ffi::CStr;
+use cap_std::time::{Duration, Instant, SystemTime};
+use std::ffi::{c_void, CStr};
 use std::fs::File;
+use std::io::Cursor;
 use std::os::raw::{c_char, c_int};
 use std::path::{Path, PathBuf};
 use std::slice;
 use wasmtime_wasi::{
+    fs::{DirCaps, FileCaps},
+    pipe::{ReadPipe, WritePipe},
     sync::{Dir, WasiCtxBuilder},
-    WasiCtx,
+    WasiCtx, WasiFile, WasiMonotonicClock, WasiSystemClock,
 };
 
 unsafe fn cstr_to_path<'a>(path: *const c_char) -> Option<&'a Path> {
@@ -24,15 +29,27 @@ unsafe fn create_file(path: *const c_char) -> Option<File> {
     File::create(cstr_to_path(path)?).ok()
 }
 
+fn wasi_file(file: File) -> Box<dyn WasiFile> {
+    let file = cap_std::fs::File::from_std(file);
+    Box::new(wasi_cap_std_sync::file::File::from_cap_std(file))
+}
+
+const WASI_DIR_PERMS_READ: u32 = 1;
+const WASI_DIR_PERMS_WRITE: u32 = 2;
+const WASI_FILE_PERMS_READ: u32 = 1;
+const WASI_FILE_PERMS_WRITE: u32 = 2;
+
 #[repr(C)]
 #[derive(Default)]
 pub struct wasi_config_t {
     args: Vec<Vec<u8>>,
     env: Vec<(Vec<u8>, Vec<u8>)>,
-    stdin: Option<File>,
-    stdout: Option<File>,
-    stderr: Option<File>,
-    preopens: Vec<(Dir, PathBuf)>,
+    stdin: Option<Box<dyn WasiFile>>,
+    stdout: Option<Box<dyn WasiFile>>,
+    stderr: Option<Box<dyn WasiFile>>,
+    preopens: Vec<(Dir, PathBuf, DirCaps, FileCaps)>,
+    system_clock: Option<CClock>,
+    monotonic_clock: Option<CClock>,
     inherit_args: bool,
     inherit_env: bool,
     inherit_stdin: bool,
@@ -40,6 +57,43 @@ pub struct wasi_config_t {
     inherit_stderr: bool,
 }
 
+/// A clock implemented by a C callback returning nanoseconds.
+struct CClock {
+    now: wasi_clock_now_callback_t,
+    resolution: Duration,
+    foreign: ForeignData,
+}
+
+impl CClock {
+    fn elapsed(&self) -> Duration {
+        Duration::from_nanos((self.now)(self.foreign.data))
+    }
+}
+
+impl WasiSystemClock for CClock {
+    fn resolution(&self) -> Duration {
+        self.resolution
+    }
+    fn now(&self, _precision: Duration) -> SystemTime {
+        SystemTime::from_std(std::time::UNIX_EPOCH + self.elapsed())
+    }
+}
+
+/// A monotonic clock whose callback counts from `base`.
+struct CMonotonicClock {
+    clock: CClock,
+    base: Instant,
+}
+
+impl WasiMonotonicClock for CMonotonicClock {
+    fn resolution(&self) -> Duration {
+        self.clock.resolution
+    }
+    fn now(&self, _precision: Duration) -> Instant {
+        self.base + self.clock.elapsed()
+    }
+}
+
 impl wasi_config_t {
     pub fn into_wasi_ctx(self) -> Result<WasiCtx> {
         let mut builder = WasiCtxBuilder::new();
@@ -70,26 +124,27 @@ impl wasi_config_t {
         if self.inherit_stdin {
             builder = builder.inherit_stdin();
         } else if let Some(file) = self.stdin {
-            let file = cap_std::fs::File::from_std(file);
-            let file = wasi_cap_std_sync::file::File::from_cap_std(file);
-            builder = builder.stdin(Box::new(file));
+            builder = builder.stdin(file);
         }
         if self.inherit_stdout {
             builder = builder.inherit_stdout();
         } else if let Some(file) = self.stdout {
-            let file = cap_std::fs::File::from_std(file);
-            let file = wasi_cap_std_sync::file::File::from_cap_std(file);
-            builder = builder.stdout(Box::new(file));
+            builder = builder.stdout(file);
         }
         if self.inherit_stderr {
             builder = builder.inherit_stderr();
         } else if let Some(file) = self.stderr {
-            let file = cap_std::fs::File::from_std(file);
-            let file = wasi_cap_std_sync::file::File::from_cap_std(file);
-            builder = builder.stderr(Box::new(file));
+            builder = builder.stderr(file);
         }
-        for (dir, path) in self.preopens {
-            builder = builder.preopened_dir(dir, path)?;
+        for (dir, path, caps, file_caps) in self.preopens {
+            builder = builder.preopened_dir_with_caps(dir, path, caps, file_caps)?;
+        }
+        if let Some(clock) = self.system_clock {
+            builder = builder.system_clock(clock);
+        }
+        if let Some(clock) = self.monotonic_clock {
+            let base = Instant::from_std(std::time::Instant::now());
+            builder = builder.monotonic_clock(CMonotonicClock { clock, base });
         }
         Ok(builder.build())
     }
@@ -160,12 +215,21 @@ pub unsafe extern "C" fn wasi_config_set_stdin_file(
         None => return false,
     };
 
-    config.stdin = Some(file);
+    config.stdin = Some(wasi_file(file));
     config.inherit_stdin = false;
 
     true
 }
 
+#[no_mangle]
+pub extern "C" fn wasi_config_set_stdin_bytes(
+    config: &mut wasi_config_t,
+    binary: &mut wasm_byte_vec_t,
+) {
+    config.stdin = Some(Box::new(ReadPipe::from(binary.take())));
+    config.inherit_stdin = false;
+}
+
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stdin(config: &mut wasi_config_t) {
     config.stdin = None;
@@ -182,12 +246,21 @@ pub unsafe extern "C" fn wasi_config_set_stdout_file(
         None => return false,
     };
 
-    config.stdout = Some(file);
+    config.stdout = Some(wasi_file(file));
     config.inherit_stdout = false;
 
     true
 }
 
+#[no_mangle]
+pub extern "C" fn wasi_config_set_stdout_pipe(
+    config: &mut wasi_config_t,
+    pipe: &wasi_output_pipe_t,
+) {
+    config.stdout = Some(Box::new(pipe.pipe.clone()));
+    config.inherit_stdout = false;
+}
+
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stdout(config: &mut wasi_config_t) {
     config.stdout = None;
@@ -204,12 +277,21 @@ pub unsafe extern "C" fn wasi_config_set_stderr_file(
         None => return false,
     };
 
-    (*config).stderr = Some(file);
+    (*config).stderr = Some(wasi_file(file));
     (*config).inherit_stderr = false;
 
     true
 }
 
+#[no_mangle]
+pub extern "C" fn wasi_config_set_stderr_pipe(
+    config: &mut wasi_config_t,
+    pipe: &wasi_output_pipe_t,
+) {
+    config.stderr = Some(Box::new(pipe.pipe.clone()));
+    config.inherit_stderr = false;
+}
+
 #[no_mangle]
 pub extern "C" fn wasi_config_inherit_stderr(config: &mut wasi_config_t) {
     config.stderr = None;
@@ -222,6 +304,38 @@ pub unsafe extern "C" fn wasi_config_preopen_dir(
     path: *const c_char,
     guest_path: *const c_char,
 ) -> bool {
+    wasi_config_preopen_dir_with_perms(
+        config,
+        path,
+        guest_path,
+        WASI_DIR_PERMS_READ | WASI_DIR_PERMS_WRITE,
+        WASI_FILE_PERMS_READ | WASI_FILE_PERMS_WRITE,
+    )
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn wasi_config_preopen_dir_with_perms(
+    config: &mut wasi_config_t,
+    path: *const c_char,
+    guest_path: *const c_char,
+    dir_perms: u32,
+    file_perms: u32,
+) -> bool {
+    let mut caps = DirCaps::empty();
+    if dir_perms & WASI_DIR_PERMS_READ != 0 {
+        caps |= DirCaps::read_only();
+    }
+    if dir_perms & WASI_DIR_PERMS_WRITE != 0 {
+        caps |= DirCaps::all() - DirCaps::read_only();
+    }
+    let mut file_caps = FileCaps::empty();
+    if file_perms & WASI_FILE_PERMS_READ != 0 {
+        file_caps |= FileCaps::read_only();
+    }
+    if file_perms & WASI_FILE_PERMS_WRITE != 0 {
+        file_caps |= FileCaps::all() - FileCaps::read_only();
+    }
+
     let guest_path = match cstr_to_path(guest_path) {
         Some(p) => p,
         None => return false,
@@ -235,7 +349,233 @@ pub unsafe extern "C" fn wasi_config_preopen_dir(
         None => return false,
     };
 
-    (*config).preopens.push((dir, guest_path.to_owned()));
+    (*config)
+        .preopens
+        .push((dir, guest_path.to_owned(), caps, file_caps));
 
     true
 }
+
+pub type wasi_clock_now_callback_t = extern "C" fn(*mut c_void) -> u64;
+
+#[no_mangle]
+pub extern "C" fn wasi_config_set_system_clock(
+    config: &mut wasi_config_t,
+    now: wasi_clock_now_callback_t,
+    resolution_nanos: u64,
+    data: *mut c_void,
+    finalizer: Option<extern "C" fn(*mut c_void)>,
+) {
+    config.system_clock = Some(CClock {
+        now,
+        resolution: Duration::from_nanos(resolution_nanos),
+        foreign: ForeignData { data, finalizer },
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn wasi_config_set_monotonic_clock(
+    config: &mut wasi_config_t,
+    now: wasi_clock_now_callback_t,
+    resolution_nanos: u64,
+    data: *mut c_void,
+    finalizer: Option<extern "C" fn(*mut c_void)>,
+) {
+    config.monotonic_clock = Some(CClock {
+        now,
+        resolution: Duration::from_nanos(resolution_nanos),
+        foreign: ForeignData { data, finalizer },
+    });
+}
+
+/// An in-memory buffer collecting what a WASI program writes to its stdout
+/// or stderr.
+pub struct wasi_output_pipe_t {
+    pipe: WritePipe<Cursor<Vec<u8>>>,
+}
+
+#[no_mangle]
+pub extern "C" fn wasi_output_pipe_new() -> Box<wasi_output_pipe_t> {
+    Box::new(wasi_output_pipe_t {
+        pipe: WritePipe::new_in_memory(),
+    })
+}
+
+#[no_mangle]
+pub extern "C" fn wasi_output_pipe_delete(_pipe: Box<wasi_output_pipe_t>) {}
+
+#[no_mangle]
+pub extern "C" fn wasi_output_pipe_contents(pipe: &wasi_output_pipe_t, out: &mut wasm_byte_vec_t) {
+    out.set_buffer(pipe.pipe.contents());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use wasmtime::{Engine, Instance, Linker, Module, Store};
+
+    /// Instantiates `wat` with WASI configured by `config`.
+    fn instantiate(config: Box<wasi_config_t>, wat: &str) -> Result<(Store<WasiCtx>, Instance)> {
+        let engine = Engine::default();
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |wasi| wasi)?;
+        let mut store = Store::new(&engine, config.into_wasi_ctx()?);
+        let module = Module::new(&engine, wat::parse_str(wat)?)?;
+        let instance = linker.instantiate(&mut store, &module)?;
+        Ok((store, instance))
+    }
+
+    #[test]
+    fn stdio_pipes() -> Result<()> {
+        let stdout = wasi_output_pipe_new();
+        let stderr = wasi_output_pipe_new();
+        let mut config = wasi_config_new();
+        wasi_config_set_stdin_bytes(&mut config, &mut b"hello".to_vec().into());
+        wasi_config_set_stdout_pipe(&mut config, &stdout);
+        wasi_config_set_stderr_pipe(&mut config, &stderr);
+
+        // Copies stdin to stdout, and then writes "error" to stderr.
+        let (mut store, instance) = instantiate(
+            config,
+            r#"
+                (module
+                    (import "wasi_snapshot_preview1" "fd_read"
+                        (func $fd_read (param i32 i32 i32 i32) (result i32)))
+                    (import "wasi_snapshot_preview1" "fd_write"
+                        (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                    (memory (export "memory") 1)
+                    ;; An iovec of 100 bytes at 100, and one of "error" at 8.
+                    (data (i32.const 0) "\64\00\00\00\64\00\00\00\20\00\00\00\05\00\00\00")
+                    (data (i32.const 32) "error")
+                    (func (export "run") (result i32)
+                        (drop (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 16)))
+                        (i32.store (i32.const 4) (i32.load (i32.const 16)))
+                        (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 16)))
+                        (call $fd_write (i32.const 2) (i32.const 8) (i32.const 1) (i32.const 16)))
+                )
+            "#,
+        )?;
+        let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+        assert_eq!(run.call(&mut store, ())?, 0);
+        drop(store);
+
+        let mut contents = Vec::new().into();
+        wasi_output_pipe_contents(&stdout, &mut contents);
+        assert_eq!(contents.as_slice(), b"hello");
+        wasi_output_pipe_contents(&stderr, &mut contents);
+        assert_eq!(contents.as_slice(), b"error");
+        wasi_output_pipe_delete(stdout);
+        wasi_output_pipe_delete(stderr);
+        Ok(())
+    }
+
+    /// Opens a path in the first preopen with every right, creating it if
+    /// `oflags` is 1, and returns the errno.
+    const OPEN: &str = r#"
+        (module
+            (import "wasi_snapshot_preview1" "path_open"
+                (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "open") (param i32 i32 i32) (result i32)
+                (call $path_open
+                    (i32.const 3) (i32.const 0) (local.get 0) (local.get 1) (local.get 2)
+                    (i64.const 0x1fffffff) (i64.const 0x1fffffff) (i32.const 0) (i32.const 0)))
+        )
+    "#;
+
+    fn open(config: Box<wasi_config_t>, path: &str, oflags: i32) -> Result<i32> {
+        let (mut store, instance) = instantiate(config, OPEN)?;
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        memory.write(&mut store, 16, path.as_bytes())?;
+        let open = instance.get_typed_func::<(i32, i32, i32), i32, _>(&mut store, "open")?;
+        Ok(open.call(&mut store, (16, path.len() as i32, oflags))?)
+    }
+
+    #[test]
+    fn preopen_dir_with_perms() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("a.txt"), "a")?;
+        let path = CString::new(dir.path().to_str().unwrap())?;
+        let guest_path = CString::new("/")?;
+
+        let config = || unsafe {
+            let mut config = wasi_config_new();
+            assert!(wasi_config_preopen_dir_with_perms(
+                &mut config,
+                path.as_ptr(),
+                guest_path.as_ptr(),
+                WASI_DIR_PERMS_READ,
+                WASI_FILE_PERMS_READ,
+            ));
+            config
+        };
+        assert_eq!(open(config(), "a.txt", 0)?, 0);
+        assert_ne!(open(config(), "b.txt", 1)?, 0);
+        assert!(!dir.path().join("b.txt").exists());
+
+        let mut config = wasi_config_new();
+        assert!(unsafe {
+            wasi_config_preopen_dir(&mut config, path.as_ptr(), guest_path.as_ptr())
+        });
+        assert_eq!(open(config, "b.txt", 1)?, 0);
+        assert!(dir.path().join("b.txt").exists());
+        Ok(())
+    }
+
+    extern "C" fn now(data: *mut c_void) -> u64 {
+        unsafe { (*(data as *const AtomicU64)).load(Ordering::SeqCst) }
+    }
+
+    static FINALIZED: AtomicU64 = AtomicU64::new(0);
+
+    extern "C" fn finalize(_data: *mut c_void) {
+        FINALIZED.fetch_add(1, Ordering::SeqCst);
+    }
+
+    #[test]
+    fn clocks() -> Result<()> {
+        let system = AtomicU64::new(5_000_000_000);
+        let monotonic = AtomicU64::new(100);
+        let mut config = wasi_config_new();
+        wasi_config_set_system_clock(
+            &mut config,
+            now,
+            1,
+            &system as *const _ as *mut c_void,
+            Some(finalize),
+        );
+        wasi_config_set_monotonic_clock(
+            &mut config,
+            now,
+            1,
+            &monotonic as *const _ as *mut c_void,
+            Some(finalize),
+        );
+
+        let (mut store, instance) = instantiate(
+            config,
+            r#"
+                (module
+                    (import "wasi_snapshot_preview1" "clock_time_get"
+                        (func $clock_time_get (param i32 i64 i32) (result i32)))
+                    (memory (export "memory") 1)
+                    (func (export "now") (param i32) (result i64)
+                        (drop (call $clock_time_get (local.get 0) (i64.const 1) (i32.const 0)))
+                        (i64.load (i32.const 0)))
+                )
+            "#,
+        )?;
+        let now = instance.get_typed_func::<i32, i64, _>(&mut store, "now")?;
+        assert_eq!(now.call(&mut store, 0)?, 5_000_000_000);
+        // The monotonic clock counts from when the context was created.
+        monotonic.store(142, Ordering::SeqCst);
+        assert_eq!(now.call(&mut store, 1)?, 42);
+
+        assert_eq!(FINALIZED.load(Ordering::SeqCst), 0);
+        drop(store);
+        assert_eq!(FINALIZED.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+}
@@ -39,6 +39,18 @@ pub const SUPPORTED_WASM_FEATURES: &[(&str, &str)] = &[
     ("simd", "enables support for proposed SIMD instructions"),
     ("threads", "enables support for WebAssembly threads"),
     ("memory64", "enables support for 64-bit memories"),
+    (
+        "sat-float-to-int",
+        "enables support for non-trapping float-to-int conversions",
+    ),
+    (
+        "sign-extension",
+        "enables support for sign-extension operators",
+    ),
+    (
+        "mutable-global",
+        "enables support for importing and exporting mutable globals",
+    ),
 ];
 
 pub const SUPPORTED_WASI_MODULES: &[(&str, &str)] = &[
@@ -60,15 +72,16 @@ pub const SUPPORTED_WASI_MODULES: &[(&str, &str)] = &[
     ),
 ];
 
-fn pick_profiling_strategy(jitdump: bool, vtune: bool) -> Result<ProfilingStrategy> {
-    Ok(match (jitdump, vtune) {
-        (true, false) => ProfilingStrategy::JitDump,
-        (false, true) => ProfilingStrategy::VTune,
-        (true, true) => {
-            println!("Can't enable --jitdump and --vtune at the same time. Profiling not enabled.");
+fn pick_profiling_strategy(jitdump: bool, vtune: bool, perfmap: bool) -> Result<ProfilingStrategy> {
+    Ok(match (jitdump, vtune, perfmap) {
+        (true, false, false) => ProfilingStrategy::JitDump,
+        (false, true, false) => ProfilingStrategy::VTune,
+        (false, false, true) => ProfilingStrategy::PerfMap,
+        (false, false, false) => ProfilingStrategy::None,
+        _ => {
+            println!("Can't enable more than one of --jitdump, --vtune and --perfmap at the same time. Profiling not enabled.");
             ProfilingStrategy::None
         }
-        _ => ProfilingStrategy::None,
     })
 }
 
@@ -131,13 +144,17 @@ pub struct CommonOptions {
     pub wasi_modules: Option<WasiModules>,
 
     /// Generate jitdump file (supported on --features=profiling build)
-    #[clap(long, conflicts_with = "vtune")]
+    #[clap(long, conflicts_with_all = &["vtune", "perfmap"])]
     pub jitdump: bool,
 
     /// Generate vtune (supported on --features=vtune build)
-    #[clap(long, conflicts_with = "jitdump")]
+    #[clap(long, conflicts_with_all = &["jitdump", "perfmap"])]
     pub vtune: bool,
 
+    /// Generate perf map file (supported on Linux)
+    #[clap(long, conflicts_with_all = &["jitdump", "vtune"])]
+    pub perfmap: bool,
+
     /// Run optimization passes on translated functions, on by default
     #[clap(short = 'O', long)]
     pub optimize: bool,
@@ -184,6 +201,14 @@ pub struct CommonOptions {
     #[clap(long, value_name = "SIZE")]
     pub dynamic_memory_guard_size: Option<u64>,
 
+    /// Bytes to reserve at the end of dynamic memories for growth into.
+    #[clap(long, value_name = "SIZE")]
+    pub dynamic_memory_reserved_for_growth: Option<u64>,
+
+    /// Disables the guard region that is placed before linear memories.
+    #[clap(long)]
+    pub disable_guard_before_linear_memory: bool,
+
     /// Enable Cranelift's internal debug verifier (expensive)
     #[clap(long)]
     pub enable_cranelift_debug_verifier: bool,
@@ -192,13 +217,19 @@ pub struct CommonOptions {
     #[clap(long)]
     pub enable_cranelift_nan_canonicalization: bool,
 
+    /// Guarantee that execution produces the same results on every host,
+    /// which includes stubbing out WASI's clocks and random numbers
+    #[clap(long)]
+    pub deterministic: bool,
+
     /// Enable execution fuel with N units fuel, where execution will trap after
     /// running out of fuel.
     ///
     /// Most WebAssembly instructions consume 1 unit of fuel. Some instructions,
     /// such as `nop`, `drop`, `block`, and `loop`, consume 0 units, as any
     /// execution cost associated with them involves other instructions which do
-    /// consume fuel.
+    /// consume fuel. `wasmtime run` exits with status 125 when the module runs
+    /// out of fuel.
     #[clap(long, value_name = "N")]
     pub fuel: Option<u64>,
 
@@ -258,7 +289,11 @@ impl CommonOptions {
             .cranelift_debug_verifier(self.enable_cranelift_debug_verifier)
             .debug_info(self.debug_info)
             .cranelift_opt_level(self.opt_level())
-            .profiler(pick_profiling_strategy(self.jitdump, self.vtune)?)?
+            .profiler(pick_profiling_strategy(
+                self.jitdump,
+                self.vtune,
+                self.perfmap,
+            )?)?
             .cranelift_nan_canonicalization(self.enable_cranelift_nan_canonicalization);
 
         self.enable_wasm_features(&mut config);
@@ -300,12 +335,19 @@ impl CommonOptions {
             config.dynamic_memory_guard_size(size);
         }
 
+        if let Some(size) = self.dynamic_memory_reserved_for_growth {
+            config.dynamic_memory_reserved_for_growth(size);
+        }
+
+        config.guard_before_linear_memory(!self.disable_guard_before_linear_memory);
+
         // If fuel has been configured, set the `consume fuel` flag on the config.
         if self.fuel.is_some() {
             config.consume_fuel(true);
         }
 
         config.epoch_interruption(self.epoch_interruption);
+        config.deterministic(self.deterministic);
         config.generate_address_map(!self.disable_address_map);
         #[cfg(feature = "memory-init-cow")]
         config.memory_init_cow(!self.disable_memory_init_cow);
@@ -333,6 +375,9 @@ impl CommonOptions {
             threads,
             multi_memory,
             memory64,
+            sat_float_to_int,
+            sign_extension,
+            mutable_global,
         } = self.wasm_features.unwrap_or_default();
 
         if let Some(enable) = simd {
@@ -358,6 +403,15 @@ impl CommonOptions {
         if let Some(enable) = memory64 {
             config.wasm_memory64(enable);
         }
+        if let Some(enable) = sat_float_to_int {
+            config.wasm_sat_float_to_int(enable);
+        }
+        if let Some(enable) = sign_extension {
+            config.wasm_sign_extension(enable);
+        }
+        if let Some(enable) = mutable_global {
+            config.wasm_mutable_global(enable);
+        }
     }
 
     pub fn opt_level(&self) -> wasmtime::OptLevel {
@@ -391,6 +445,9 @@ pub struct WasmFeatures {
     pub threads: Option<bool>,
     pub multi_memory: Option<bool>,
     pub memory64: Option<bool>,
+    pub sat_float_to_int: Option<bool>,
+    pub sign_extension: Option<bool>,
+    pub mutable_global: Option<bool>,
 }
 
 fn parse_wasm_features(features: &str) -> Result<WasmFeatures> {
@@ -439,6 +496,9 @@ fn parse_wasm_features(features: &str) -> Result<WasmFeatures> {
         threads: all.or(values["threads"]),
         multi_memory: all.or(values["multi-memory"]),
         memory64: all.or(values["memory64"]),
+        sat_float_to_int: all.or(values["sat-float-to-int"]),
+        sign_extension: all.or(values["sign-extension"]),
+        mutable_global: all.or(values["mutable-global"]),
     })
 }
 
@@ -541,6 +601,9 @@ mod test {
             threads,
             multi_memory,
             memory64,
+            sat_float_to_int,
+            sign_extension,
+            mutable_global,
         } = options.wasm_features.unwrap();
 
         assert_eq!(reference_types, Some(true));
@@ -550,6 +613,9 @@ mod test {
         assert_eq!(threads, Some(true));
         assert_eq!(multi_memory, Some(true));
         assert_eq!(memory64, Some(true));
+        assert_eq!(sat_float_to_int, Some(true));
+        assert_eq!(sign_extension, Some(true));
+        assert_eq!(mutable_global, Some(true));
 
         Ok(())
     }
@@ -566,6 +632,9 @@ mod test {
             threads,
             multi_memory,
             memory64,
+            sat_float_to_int,
+            sign_extension,
+            mutable_global,
         } = options.wasm_features.unwrap();
 
         assert_eq!(reference_types, Some(false));
@@ -575,6 +644,9 @@ mod test {
         assert_eq!(threads, Some(false));
         assert_eq!(multi_memory, Some(false));
         assert_eq!(memory64, Some(false));
+        assert_eq!(sat_float_to_int, Some(false));
+        assert_eq!(sign_extension, Some(false));
+        assert_eq!(mutable_global, Some(false));
 
         Ok(())
     }
@@ -594,6 +666,9 @@ mod test {
             threads,
             multi_memory,
             memory64,
+            sat_float_to_int,
+            sign_extension,
+            mutable_global,
         } = options.wasm_features.unwrap();
 
         assert_eq!(reference_types, Some(false));
@@ -603,6 +678,9 @@ mod test {
         assert_eq!(threads, None);
         assert_eq!(multi_memory, Some(true));
         assert_eq!(memory64, Some(true));
+        assert_eq!(sat_float_to_int, None);
+        assert_eq!(sign_extension, None);
+        assert_eq!(mutable_global, None);
 
         Ok(())
     }
@@ -643,6 +721,21 @@ mod test {
     feature_test!(test_threads_feature, threads, "threads");
     feature_test!(test_multi_memory_feature, multi_memory, "multi-memory");
     feature_test!(test_memory64_feature, memory64, "memory64");
+    feature_test!(
+        test_sat_float_to_int_feature,
+        sat_float_to_int,
+        "sat-float-to-int"
+    );
+    feature_test!(
+        test_sign_extension_feature,
+        sign_extension,
+        "sign-extension"
+    );
+    feature_test!(
+        test_mutable_global_feature,
+        mutable_global,
+        "mutable-global"
+    );
 
     #[test]
     fn test_default_modules() {
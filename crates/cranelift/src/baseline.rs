@@ -0,0 +1,306 @@
+//! A single-pass baseline compiler for x86_64.
+//!
+//! The baseline compiler translates each wasm instruction directly to a fixed
+//! sequence of machine code, without building any IR or allocating registers,
+//! which makes compiling it much faster than with Cranelift at the cost of the
+//! quality of the generated code. Functions use the same calling convention
+//! and `VMContext` layout as code compiled by Cranelift, so the two can be
+//! mixed freely within a module.
+//!
+//! Only a subset of wasm is supported, mostly integer and floating point
+//! arithmetic, control flow, calls, globals and linear memories, along with
+//! configurations which only need the default instrumentation. Functions using
+//! anything else are compiled with Cranelift instead, which is decided by a
+//! quick scan of the function's body before compiling it.
+
+use crate::CompiledFunction;
+use codegen::FuncCodegen;
+use cranelift_codegen::isa::{CallConv, TargetIsa};
+use cranelift_wasm::{
+    DefinedFuncIndex, FuncIndex, GlobalIndex, MemoryIndex, TypeIndex, WasmFuncType, WasmType,
+};
+use gimli::write::{Address, CallFrameInstruction, FrameDescriptionEntry};
+use wasmparser::{BlockType, FunctionBody, MemoryImmediate, Operator, Type};
+use wasmtime_environ::{
+    CompileError, FunctionBodyData, LazyFunctionTable, Module, Tunables, TypeTables,
+};
+
+mod asm;
+mod codegen;
+
+/// Returns whether the baseline compiler can generate code for `isa`.
+pub(crate) fn supports_isa(isa: &dyn TargetIsa) -> bool {
+    isa.triple().architecture == target_lexicon::Architecture::X86_64
+        && isa.default_call_conv() == CallConv::SystemV
+}
+
+/// Compiles the function `index` of `module` with the baseline compiler,
+/// returning `None` without touching `input` if it isn't supported.
+///
+/// Functions of lazily compiled modules are compiled with their `lazy_table`,
+/// which they call the module's other functions through, and which may also
+/// have the hotness counters of tiered compilation.
+pub(crate) fn compile(
+    isa: &dyn TargetIsa,
+    module: &Module,
+    index: DefinedFuncIndex,
+    input: &mut FunctionBodyData<'_>,
+    tunables: &Tunables,
+    types: &TypeTables,
+    lazy_table: Option<&LazyFunctionTable>,
+) -> Result<Option<CompiledFunction>, CompileError> {
+    let func_index = module.func_index(index);
+    let scan = Scan { isa, module, types };
+    if !supports_tunables(tunables) || !scan.function(func_index, &input.body) {
+        log::debug!("{:?} isn't supported by the baseline compiler", func_index);
+        return Ok(None);
+    }
+    let func = FuncCodegen::new(isa, module, types, tunables, func_index, lazy_table)
+        .compile(&input.body, &mut input.validator)?;
+    Ok(Some(func))
+}
+
+/// Returns the unwinding information of a function of `len` bytes compiled by
+/// the baseline compiler for `isa` at `address`.
+///
+/// Every such function starts with `push rbp; mov rbp, rsp`, preceded by
+/// `endbr64` with branch protection, and only ever returns or unwinds through
+/// its frame pointer afterwards.
+pub(crate) fn frame_description(
+    isa: &dyn TargetIsa,
+    address: Address,
+    len: u32,
+) -> FrameDescriptionEntry {
+    let start = if isa.is_branch_protection_enabled() {
+        4
+    } else {
+        0
+    };
+    let mut fde = FrameDescriptionEntry::new(address, len);
+    fde.add_instruction(start + 1, CallFrameInstruction::CfaOffset(16));
+    fde.add_instruction(
+        start + 1,
+        CallFrameInstruction::Offset(gimli::X86_64::RBP, -16),
+    );
+    fde.add_instruction(
+        start + 4,
+        CallFrameInstruction::CfaRegister(gimli::X86_64::RBP),
+    );
+    fde
+}
+
+/// Returns whether code compiled with `tunables` can be generated by the
+/// baseline compiler, which doesn't implement any of the optional
+/// instrumentation of functions.
+fn supports_tunables(tunables: &Tunables) -> bool {
+    tunables.signals_based_traps
+        && !tunables.consume_fuel
+        && !tunables.epoch_interruption
+        && !tunables.generate_native_debuginfo
+        && !tunables.guest_debug
+        && !tunables.coredump_frames
+        && !tunables.count_instructions
+        && !tunables.trace_memory_accesses
+        && !tunables.check_integer_overflow
+        && !tunables.collect_profile
+}
+
+/// A scan of function bodies for anything the baseline compiler doesn't
+/// support.
+///
+/// Bodies aren't validated here, so anything malformed is simply reported as
+/// unsupported and left for Cranelift to report.
+struct Scan<'a> {
+    isa: &'a dyn TargetIsa,
+    module: &'a Module,
+    types: &'a TypeTables,
+}
+
+impl Scan<'_> {
+    fn function(&self, index: FuncIndex, body: &FunctionBody<'_>) -> bool {
+        if !self.signature(&self.types[self.module.functions[index].signature]) {
+            return false;
+        }
+        self.body(body).unwrap_or(false)
+    }
+
+    fn body(&self, body: &FunctionBody<'_>) -> wasmparser::Result<bool> {
+        let mut locals = body.get_locals_reader()?;
+        for _ in 0..locals.get_count() {
+            if !numeric_type(locals.read()?.1) {
+                return Ok(false);
+            }
+        }
+        let mut operators = body.get_operators_reader()?;
+        while !operators.eof() {
+            if !self.operator(&operators.read()?) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Returns whether functions of type `sig` can be compiled or called.
+    fn signature(&self, sig: &WasmFuncType) -> bool {
+        sig.params().iter().copied().all(numeric)
+            && sig.returns().iter().copied().all(numeric)
+            && sig.returns().len() <= 1
+    }
+
+    fn block_type(&self, ty: BlockType) -> bool {
+        match ty {
+            BlockType::Empty => true,
+            BlockType::Type(ty) => numeric_type(ty),
+            BlockType::FuncType(index) => match self.module.types.get(TypeIndex::from_u32(index)) {
+                Some(ty) => {
+                    let sig = &self.types[ty.unwrap_function()];
+                    sig.params()
+                        .iter()
+                        .chain(sig.returns())
+                        .copied()
+                        .all(numeric)
+                }
+                None => false,
+            },
+        }
+    }
+
+    fn memory(&self, memory: u32) -> bool {
+        match self.module.memory_plans.get(MemoryIndex::from_u32(memory)) {
+            Some(plan) => !plan.memory.memory64,
+            None => false,
+        }
+    }
+
+    fn memarg(&self, memarg: &MemoryImmediate) -> bool {
+        self.memory(memarg.memory)
+    }
+
+    /// Returns whether floating point arithmetic can be compiled, which the
+    /// baseline compiler doesn't canonicalize NaNs for.
+    fn float_arithmetic(&self) -> bool {
+        !self.isa.flags().enable_nan_canonicalization()
+    }
+
+    fn isa_flag(&self, name: &str) -> bool {
+        self.isa
+            .isa_flags()
+            .iter()
+            .any(|flag| flag.name == name && flag.as_bool() == Some(true))
+    }
+
+    fn operator(&self, op: &Operator<'_>) -> bool {
+        use Operator::*;
+        match op {
+            Unreachable
+            | Nop
+            | Else
+            | End
+            | Br { .. }
+            | BrIf { .. }
+            | BrTable { .. }
+            | Return
+            | Drop
+            | Select
+            | LocalGet { .. }
+            | LocalSet { .. }
+            | LocalTee { .. } => true,
+            Block { ty } | Loop { ty } | If { ty } => self.block_type(*ty),
+            TypedSelect { ty } => numeric_type(*ty),
+            Call { function_index } => {
+                match self
+                    .module
+                    .functions
+                    .get(FuncIndex::from_u32(*function_index))
+                {
+                    Some(func) => self.signature(&self.types[func.signature]),
+                    None => false,
+                }
+            }
+            CallIndirect { index, .. } => {
+                match self.module.types.get(TypeIndex::from_u32(*index)) {
+                    Some(ty) => self.signature(&self.types[ty.unwrap_function()]),
+                    None => false,
+                }
+            }
+            GlobalGet { global_index } | GlobalSet { global_index } => {
+                match self
+                    .module
+                    .globals
+                    .get(GlobalIndex::from_u32(*global_index))
+                {
+                    Some(global) => numeric(global.wasm_ty),
+                    None => false,
+                }
+            }
+
+            I32Load { memarg }
+            | I64Load { memarg }
+            | F32Load { memarg }
+            | F64Load { memarg }
+            | I32Load8S { memarg }
+            | I32Load8U { memarg }
+            | I32Load16S { memarg }
+            | I32Load16U { memarg }
+            | I64Load8S { memarg }
+            | I64Load8U { memarg }
+            | I64Load16S { memarg }
+            | I64Load16U { memarg }
+            | I64Load32S { memarg }
+            | I64Load32U { memarg }
+            | I32Store { memarg }
+            | I64Store { memarg }
+            | F32Store { memarg }
+            | F64Store { memarg }
+            | I32Store8 { memarg }
+            | I32Store16 { memarg }
+            | I64Store8 { memarg }
+            | I64Store16 { memarg }
+            | I64Store32 { memarg } => self.memarg(memarg),
+            MemorySize { mem, .. } | MemoryGrow { mem, .. } => self.memory(*mem),
+
+            I32Const { .. } | I64Const { .. } | F32Const { .. } | F64Const { .. } => true,
+
+            I32Eqz | I32Eq | I32Ne | I32LtS | I32LtU | I32GtS | I32GtU | I32LeS | I32LeU
+            | I32GeS | I32GeU | I64Eqz | I64Eq | I64Ne | I64LtS | I64LtU | I64GtS | I64GtU
+            | I64LeS | I64LeU | I64GeS | I64GeU => true,
+            I32Clz | I32Ctz | I32Add | I32Sub | I32Mul | I32DivS | I32DivU | I32RemS | I32RemU
+            | I32And | I32Or | I32Xor | I32Shl | I32ShrS | I32ShrU | I32Rotl | I32Rotr | I64Clz
+            | I64Ctz | I64Add | I64Sub | I64Mul | I64DivS | I64DivU | I64RemS | I64RemU
+            | I64And | I64Or | I64Xor | I64Shl | I64ShrS | I64ShrU | I64Rotl | I64Rotr => true,
+            I32Popcnt | I64Popcnt => self.isa_flag("has_popcnt"),
+            I32WrapI64 | I64ExtendI32S | I64ExtendI32U | I32Extend8S | I32Extend16S
+            | I64Extend8S | I64Extend16S | I64Extend32S => true,
+            I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => true,
+
+            F32Eq | F32Ne | F32Lt | F32Gt | F32Le | F32Ge | F64Eq | F64Ne | F64Lt | F64Gt
+            | F64Le | F64Ge => true,
+            F32Abs | F32Neg | F32Copysign | F64Abs | F64Neg | F64Copysign => true,
+            F32ConvertI32S | F32ConvertI32U | F32ConvertI64S | F32ConvertI64U | F64ConvertI32S
+            | F64ConvertI32U | F64ConvertI64S | F64ConvertI64U => true,
+            I32TruncF32S | I32TruncF32U | I32TruncF64S | I32TruncF64U | I64TruncF32S
+            | I64TruncF32U | I64TruncF64S | I64TruncF64U => true,
+            I32TruncSatF32S | I32TruncSatF32U | I32TruncSatF64S | I32TruncSatF64U
+            | I64TruncSatF32S | I64TruncSatF32U | I64TruncSatF64S | I64TruncSatF64U => true,
+            F32Add | F32Sub | F32Mul | F32Div | F32Min | F32Max | F32Sqrt | F64Add | F64Sub
+            | F64Mul | F64Div | F64Min | F64Max | F64Sqrt | F32DemoteF64 | F64PromoteF32 => {
+                self.float_arithmetic()
+            }
+            F32Ceil | F32Floor | F32Trunc | F32Nearest | F64Ceil | F64Floor | F64Trunc
+            | F64Nearest => self.float_arithmetic() && self.isa_flag("has_sse41"),
+
+            _ => false,
+        }
+    }
+}
+
+fn numeric(ty: WasmType) -> bool {
+    matches!(
+        ty,
+        WasmType::I32 | WasmType::I64 | WasmType::F32 | WasmType::F64
+    )
+}
+
+fn numeric_type(ty: Type) -> bool {
+    matches!(ty, Type::I32 | Type::I64 | Type::F32 | Type::F64)
+}
@@ -0,0 +1,714 @@
+//! A small x86_64 assembler for the baseline compiler.
+//!
+//! Only the handful of encodings that the baseline compiler needs are
+//! supported here. All jumps between labels use 32-bit displacements, which
+//! are patched once the whole function has been emitted.
+
+/// A general purpose register, identified by its hardware encoding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Gpr(u8);
+
+pub const RAX: Gpr = Gpr(0);
+pub const RCX: Gpr = Gpr(1);
+pub const RDX: Gpr = Gpr(2);
+pub const RSP: Gpr = Gpr(4);
+pub const RBP: Gpr = Gpr(5);
+pub const RSI: Gpr = Gpr(6);
+pub const RDI: Gpr = Gpr(7);
+pub const R8: Gpr = Gpr(8);
+pub const R9: Gpr = Gpr(9);
+pub const R10: Gpr = Gpr(10);
+pub const R11: Gpr = Gpr(11);
+
+/// An SSE register, identified by its hardware encoding.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Xmm(pub u8);
+
+/// A memory operand of the form `[base + index * scale + disp]`.
+#[derive(Copy, Clone, Debug)]
+pub struct Mem {
+    base: Gpr,
+    index: Option<(Gpr, u8)>,
+    disp: i32,
+}
+
+impl Mem {
+    pub const fn base(base: Gpr, disp: i32) -> Mem {
+        Mem {
+            base,
+            index: None,
+            disp,
+        }
+    }
+
+    /// Returns `[base + index * scale + disp]`, where `scale` is 1, 2, 4 or
+    /// 8.
+    pub fn index(base: Gpr, index: Gpr, scale: u8, disp: i32) -> Mem {
+        debug_assert!(index != RSP);
+        Mem {
+            base,
+            index: Some((index, scale.trailing_zeros() as u8)),
+            disp,
+        }
+    }
+}
+
+/// The operand encoded in the `r/m` field of an instruction.
+#[derive(Copy, Clone, Debug)]
+pub enum Rm {
+    Reg(u8),
+    Mem(Mem),
+}
+
+impl From<Gpr> for Rm {
+    fn from(reg: Gpr) -> Rm {
+        Rm::Reg(reg.0)
+    }
+}
+
+impl From<Xmm> for Rm {
+    fn from(reg: Xmm) -> Rm {
+        Rm::Reg(reg.0)
+    }
+}
+
+impl From<Mem> for Rm {
+    fn from(mem: Mem) -> Rm {
+        Rm::Mem(mem)
+    }
+}
+
+/// The width of an integer operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Size {
+    S32,
+    S64,
+}
+
+/// The width of a floating point operation.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Float {
+    F32,
+    F64,
+}
+
+impl Float {
+    /// The prefix selecting the scalar single or double precision form of
+    /// SSE instructions.
+    fn prefix(self) -> u8 {
+        match self {
+            Float::F32 => 0xf3,
+            Float::F64 => 0xf2,
+        }
+    }
+}
+
+/// A condition code, as encoded in `jcc`, `setcc` and `cmovcc`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Cc {
+    B = 0x2,
+    AE = 0x3,
+    E = 0x4,
+    NE = 0x5,
+    BE = 0x6,
+    A = 0x7,
+    S = 0x8,
+    P = 0xa,
+    NP = 0xb,
+    L = 0xc,
+    GE = 0xd,
+    LE = 0xe,
+    G = 0xf,
+}
+
+/// Arithmetic operations which share the classic x86 encodings.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Alu {
+    Add = 0,
+    Or = 1,
+    And = 4,
+    Sub = 5,
+    Xor = 6,
+    Cmp = 7,
+}
+
+/// Shifts and rotates, encoded in the `reg` field of their opcode.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Shift {
+    Rol = 0,
+    Ror = 1,
+    Shl = 4,
+    Shr = 5,
+    Sar = 7,
+}
+
+/// Scalar SSE arithmetic.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sse {
+    Sqrt = 0x51,
+    Add = 0x58,
+    Mul = 0x59,
+    Sub = 0x5c,
+    Min = 0x5d,
+    Div = 0x5e,
+    Max = 0x5f,
+}
+
+/// A position in the code, which may not have been bound yet.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Label(u32);
+
+/// A 32-bit value in the code that is `label - base` once all labels are
+/// bound.
+struct Fixup {
+    at: u32,
+    label: Label,
+    base: u32,
+}
+
+#[derive(Default)]
+pub struct Assembler {
+    code: Vec<u8>,
+    labels: Vec<Option<u32>>,
+    fixups: Vec<Fixup>,
+}
+
+impl Assembler {
+    /// The offset at which the next instruction is emitted.
+    pub fn offset(&self) -> u32 {
+        self.code.len() as u32
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() as u32 - 1)
+    }
+
+    pub fn bind(&mut self, label: Label) {
+        debug_assert!(self.labels[label.0 as usize].is_none());
+        self.labels[label.0 as usize] = Some(self.offset());
+    }
+
+    /// Overwrites the 32-bit value at `at`, which was emitted earlier.
+    pub fn patch_u32(&mut self, at: u32, value: u32) {
+        let at = at as usize;
+        self.code[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+
+    /// Resolves all uses of labels and returns the code.
+    pub fn finish(mut self) -> Vec<u8> {
+        for fixup in std::mem::take(&mut self.fixups) {
+            let target = self.labels[fixup.label.0 as usize].expect("unbound label");
+            self.patch_u32(fixup.at, target.wrapping_sub(fixup.base));
+        }
+        self.code
+    }
+
+    fn byte(&mut self, byte: u8) {
+        self.code.push(byte);
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn u32(&mut self, value: u32) {
+        self.bytes(&value.to_le_bytes());
+    }
+
+    /// Emits a 32-bit displacement to `label`, relative to the end of the
+    /// displacement.
+    fn rel32(&mut self, label: Label) {
+        let at = self.offset();
+        self.fixups.push(Fixup {
+            at,
+            label,
+            base: at + 4,
+        });
+        self.u32(0);
+    }
+
+    /// Emits an instruction with a ModRM byte.
+    ///
+    /// `byte_regs` forces a REX prefix so that register operands 4 to 7 name
+    /// `spl` to `dil` rather than `ah` to `bh` in byte-sized operations.
+    fn op(&mut self, prefix: Option<u8>, w: bool, opcode: &[u8], reg: u8, rm: Rm, byte_regs: bool) {
+        if let Some(prefix) = prefix {
+            self.byte(prefix);
+        }
+        let (x, b) = match rm {
+            Rm::Reg(r) => (0, r),
+            Rm::Mem(m) => (m.index.map_or(0, |(i, _)| i.0), m.base.0),
+        };
+        let rex = 0x40 | (u8::from(w) << 3) | ((reg >> 3) << 2) | ((x >> 3) << 1) | (b >> 3);
+        let byte_rex = byte_regs && (reg >= 4 || matches!(rm, Rm::Reg(r) if r >= 4));
+        if rex != 0x40 || byte_rex {
+            self.byte(rex);
+        }
+        self.bytes(opcode);
+        let reg = (reg & 7) << 3;
+        let m = match rm {
+            Rm::Reg(r) => return self.byte(0xc0 | reg | (r & 7)),
+            Rm::Mem(m) => m,
+        };
+        let base = m.base.0 & 7;
+        let (mode, disp8) = if m.disp == 0 && base != RBP.0 {
+            (0x00, false)
+        } else if i8::try_from(m.disp).is_ok() {
+            (0x40, true)
+        } else {
+            (0x80, false)
+        };
+        match m.index {
+            Some((index, scale)) => {
+                self.byte(mode | reg | 4);
+                self.byte((scale << 6) | ((index.0 & 7) << 3) | base);
+            }
+            None if base == RSP.0 => {
+                self.byte(mode | reg | 4);
+                self.byte(0x24);
+            }
+            None => self.byte(mode | reg | base),
+        }
+        match mode {
+            0x00 => {}
+            _ if disp8 => self.byte(m.disp as u8),
+            _ => self.u32(m.disp as u32),
+        }
+    }
+
+    // Moves and loads.
+
+    /// `mov dst, src` between registers.
+    pub fn mov_rr(&mut self, size: Size, dst: Gpr, src: Gpr) {
+        self.op(None, size == Size::S64, &[0x89], src.0, dst.into(), false);
+    }
+
+    /// Loads `dst` from `src`, zero-extending 32-bit loads.
+    pub fn load(&mut self, size: Size, dst: Gpr, src: Mem) {
+        self.op(None, size == Size::S64, &[0x8b], dst.0, src.into(), false);
+    }
+
+    /// Stores the low `bits` bits of `src` to `dst`.
+    pub fn store(&mut self, bits: u8, dst: Mem, src: Gpr) {
+        match bits {
+            8 => self.op(None, false, &[0x88], src.0, dst.into(), true),
+            16 => self.op(Some(0x66), false, &[0x89], src.0, dst.into(), false),
+            32 => self.op(None, false, &[0x89], src.0, dst.into(), false),
+            64 => self.op(None, true, &[0x89], src.0, dst.into(), false),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Stores `imm`, sign-extended to 64 bits for `Size::S64`, to `dst`.
+    pub fn store_imm(&mut self, size: Size, dst: Mem, imm: i32) {
+        self.op(None, size == Size::S64, &[0xc7], 0, dst.into(), false);
+        self.u32(imm as u32);
+    }
+
+    /// Loads the constant `imm` into `dst`.
+    pub fn mov_imm(&mut self, dst: Gpr, imm: u64) {
+        if let Ok(imm) = u32::try_from(imm) {
+            if dst.0 >= 8 {
+                self.byte(0x41);
+            }
+            self.byte(0xb8 | (dst.0 & 7));
+            self.u32(imm);
+        } else if let Ok(imm) = i32::try_from(imm as i64) {
+            self.op(None, true, &[0xc7], 0, dst.into(), false);
+            self.u32(imm as u32);
+        } else {
+            self.byte(0x48 | (dst.0 >> 3));
+            self.byte(0xb8 | (dst.0 & 7));
+            self.bytes(&imm.to_le_bytes());
+        }
+    }
+
+    /// Zero-extends the low `bits` bits of `src` into `dst`.
+    pub fn movzx(&mut self, bits: u8, dst: Gpr, src: Rm) {
+        match bits {
+            8 => self.op(None, false, &[0x0f, 0xb6], dst.0, src, true),
+            16 => self.op(None, false, &[0x0f, 0xb7], dst.0, src, false),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Sign-extends the low `bits` bits of `src` into `dst`.
+    pub fn movsx(&mut self, size: Size, bits: u8, dst: Gpr, src: Rm) {
+        let w = size == Size::S64;
+        match bits {
+            8 => self.op(None, w, &[0x0f, 0xbe], dst.0, src, true),
+            16 => self.op(None, w, &[0x0f, 0xbf], dst.0, src, false),
+            32 => self.op(None, true, &[0x63], dst.0, src, false),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn lea(&mut self, dst: Gpr, src: Mem) {
+        self.op(None, true, &[0x8d], dst.0, src.into(), false);
+    }
+
+    /// Loads the address of `label` into `dst`.
+    pub fn lea_label(&mut self, dst: Gpr, label: Label) {
+        self.byte(0x48 | ((dst.0 >> 3) << 2));
+        self.byte(0x8d);
+        self.byte(((dst.0 & 7) << 3) | 0x05);
+        self.rel32(label);
+    }
+
+    // Arithmetic.
+
+    /// `op dst, src`.
+    pub fn alu(&mut self, op: Alu, size: Size, dst: Gpr, src: Rm) {
+        let opcode = ((op as u8) << 3) | 0x03;
+        self.op(None, size == Size::S64, &[opcode], dst.0, src, false);
+    }
+
+    /// `op dst, imm`, where `imm` is sign-extended for `Size::S64`.
+    pub fn alu_imm(&mut self, op: Alu, size: Size, dst: Rm, imm: i32) {
+        let w = size == Size::S64;
+        match i8::try_from(imm) {
+            Ok(imm) => {
+                self.op(None, w, &[0x83], op as u8, dst, false);
+                self.byte(imm as u8);
+            }
+            Err(_) => {
+                self.op(None, w, &[0x81], op as u8, dst, false);
+                self.u32(imm as u32);
+            }
+        }
+    }
+
+    pub fn test(&mut self, size: Size, a: Gpr, b: Gpr) {
+        self.op(None, size == Size::S64, &[0x85], b.0, a.into(), false);
+    }
+
+    pub fn imul(&mut self, size: Size, dst: Gpr, src: Rm) {
+        self.op(None, size == Size::S64, &[0x0f, 0xaf], dst.0, src, false);
+    }
+
+    /// Shifts or rotates `dst` by `cl`.
+    pub fn shift_cl(&mut self, shift: Shift, size: Size, dst: Gpr) {
+        self.op(
+            None,
+            size == Size::S64,
+            &[0xd3],
+            shift as u8,
+            dst.into(),
+            false,
+        );
+    }
+
+    /// Shifts or rotates `dst` by `imm`.
+    pub fn shift_imm(&mut self, shift: Shift, size: Size, dst: Gpr, imm: u8) {
+        self.op(
+            None,
+            size == Size::S64,
+            &[0xc1],
+            shift as u8,
+            dst.into(),
+            false,
+        );
+        self.byte(imm);
+    }
+
+    /// Bit scan reverse, setting ZF if `src` is zero.
+    pub fn bsr(&mut self, size: Size, dst: Gpr, src: Rm) {
+        self.op(None, size == Size::S64, &[0x0f, 0xbd], dst.0, src, false);
+    }
+
+    /// Bit scan forward, setting ZF if `src` is zero.
+    pub fn bsf(&mut self, size: Size, dst: Gpr, src: Rm) {
+        self.op(None, size == Size::S64, &[0x0f, 0xbc], dst.0, src, false);
+    }
+
+    pub fn popcnt(&mut self, size: Size, dst: Gpr, src: Rm) {
+        self.op(
+            Some(0xf3),
+            size == Size::S64,
+            &[0x0f, 0xb8],
+            dst.0,
+            src,
+            false,
+        );
+    }
+
+    pub fn cmov(&mut self, cc: Cc, size: Size, dst: Gpr, src: Rm) {
+        self.op(
+            None,
+            size == Size::S64,
+            &[0x0f, 0x40 | cc as u8],
+            dst.0,
+            src,
+            false,
+        );
+    }
+
+    /// Sets the low byte of `dst` to whether `cc` holds.
+    pub fn setcc(&mut self, cc: Cc, dst: Gpr) {
+        self.op(None, false, &[0x0f, 0x90 | cc as u8], 0, dst.into(), true);
+    }
+
+    /// Sign-extends `eax` or `rax` into `edx` or `rdx`.
+    pub fn sign_extend_rax(&mut self, size: Size) {
+        if size == Size::S64 {
+            self.byte(0x48);
+        }
+        self.byte(0x99);
+    }
+
+    /// Divides `edx:eax` or `rdx:rax` by `src`.
+    pub fn div(&mut self, size: Size, signed: bool, src: Gpr) {
+        let op = if signed { 7 } else { 6 };
+        self.op(None, size == Size::S64, &[0xf7], op, src.into(), false);
+    }
+
+    // Floating point.
+
+    pub fn movs_load(&mut self, ty: Float, dst: Xmm, src: Mem) {
+        self.op(
+            Some(ty.prefix()),
+            false,
+            &[0x0f, 0x10],
+            dst.0,
+            src.into(),
+            false,
+        );
+    }
+
+    pub fn movs_store(&mut self, ty: Float, dst: Mem, src: Xmm) {
+        self.op(
+            Some(ty.prefix()),
+            false,
+            &[0x0f, 0x11],
+            src.0,
+            dst.into(),
+            false,
+        );
+    }
+
+    pub fn sse(&mut self, op: Sse, ty: Float, dst: Xmm, src: Rm) {
+        self.op(
+            Some(ty.prefix()),
+            false,
+            &[0x0f, op as u8],
+            dst.0,
+            src,
+            false,
+        );
+    }
+
+    /// Compares `a` with `b`, setting ZF, PF and CF like an unsigned
+    /// comparison, with all three set if either operand is NaN.
+    pub fn ucomis(&mut self, ty: Float, a: Xmm, b: Rm) {
+        let prefix = match ty {
+            Float::F32 => None,
+            Float::F64 => Some(0x66),
+        };
+        self.op(prefix, false, &[0x0f, 0x2e], a.0, b, false);
+    }
+
+    /// Converts the signed integer `src` to a float.
+    pub fn cvtsi2s(&mut self, ty: Float, size: Size, dst: Xmm, src: Rm) {
+        self.op(
+            Some(ty.prefix()),
+            size == Size::S64,
+            &[0x0f, 0x2a],
+            dst.0,
+            src,
+            false,
+        );
+    }
+
+    /// Converts the float `src` to a signed integer, truncating towards zero.
+    /// Values which are out of range or NaN produce the minimum integer.
+    pub fn cvtts2si(&mut self, ty: Float, size: Size, dst: Gpr, src: Rm) {
+        self.op(
+            Some(ty.prefix()),
+            size == Size::S64,
+            &[0x0f, 0x2c],
+            dst.0,
+            src,
+            false,
+        );
+    }
+
+    /// Rounds `src` to an integral value with the SSE4.1 rounding `mode`.
+    pub fn round(&mut self, ty: Float, mode: u8, dst: Xmm, src: Rm) {
+        let opcode = match ty {
+            Float::F32 => 0x0a,
+            Float::F64 => 0x0b,
+        };
+        self.op(Some(0x66), false, &[0x0f, 0x3a, opcode], dst.0, src, false);
+        self.byte(mode);
+    }
+
+    /// Converts `src` from the precision `from` to the other precision.
+    pub fn cvt_float(&mut self, from: Float, dst: Xmm, src: Rm) {
+        self.op(Some(from.prefix()), false, &[0x0f, 0x5a], dst.0, src, false);
+    }
+
+    // Control flow.
+
+    pub fn jmp(&mut self, label: Label) {
+        self.byte(0xe9);
+        self.rel32(label);
+    }
+
+    pub fn jcc(&mut self, cc: Cc, label: Label) {
+        self.bytes(&[0x0f, 0x80 | cc as u8]);
+        self.rel32(label);
+    }
+
+    pub fn jmp_reg(&mut self, target: Gpr) {
+        self.op(None, false, &[0xff], 4, target.into(), false);
+    }
+
+    /// Emits a call with a zero 32-bit displacement, returning the offset of
+    /// the displacement so that it can be relocated.
+    pub fn call_rel32(&mut self) -> u32 {
+        self.byte(0xe8);
+        let at = self.offset();
+        self.u32(0);
+        at
+    }
+
+    pub fn call(&mut self, target: Rm) {
+        self.op(None, false, &[0xff], 2, target, false);
+    }
+
+    /// Emits a 32-bit table entry holding `label - base`.
+    pub fn table_entry(&mut self, label: Label, base: Label) {
+        let at = self.offset();
+        let base = self.labels[base.0 as usize].expect("table must be bound");
+        self.fixups.push(Fixup { at, label, base });
+        self.u32(0);
+    }
+
+    pub fn endbr64(&mut self) {
+        self.bytes(&[0xf3, 0x0f, 0x1e, 0xfa]);
+    }
+
+    pub fn push_rbp(&mut self) {
+        self.byte(0x55);
+    }
+
+    pub fn pop_rbp(&mut self) {
+        self.byte(0x5d);
+    }
+
+    pub fn ret(&mut self) {
+        self.byte(0xc3);
+    }
+
+    /// Emits `ud2`, returning its offset.
+    pub fn ud2(&mut self) -> u32 {
+        let at = self.offset();
+        self.bytes(&[0x0f, 0x0b]);
+        at
+    }
+
+    /// Emits `sub rsp, imm32`, returning the offset of the immediate so that
+    /// it can be patched once the size of the frame is known.
+    pub fn sub_rsp(&mut self) -> u32 {
+        self.bytes(&[0x48, 0x81, 0xec]);
+        let at = self.offset();
+        self.u32(0);
+        at
+    }
+
+    /// Stores `rax` to `rcx` quadwords starting at `rdi`.
+    pub fn rep_stosq(&mut self) {
+        self.bytes(&[0xf3, 0x48, 0xab]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assemble(f: impl FnOnce(&mut Assembler)) -> Vec<u8> {
+        let mut asm = Assembler::default();
+        f(&mut asm);
+        asm.finish()
+    }
+
+    #[test]
+    fn memory_operands() {
+        // mov eax, [rbp - 8]
+        assert_eq!(
+            assemble(|a| a.load(Size::S32, RAX, Mem::base(RBP, -8))),
+            [0x8b, 0x45, 0xf8]
+        );
+        // mov [rsp + 16], rax
+        assert_eq!(
+            assemble(|a| a.store(64, Mem::base(RSP, 16), RAX)),
+            [0x48, 0x89, 0x44, 0x24, 0x10]
+        );
+        // mov r11, [r10 + 0x1000]
+        assert_eq!(
+            assemble(|a| a.load(Size::S64, R11, Mem::base(R10, 0x1000))),
+            [0x4d, 0x8b, 0x9a, 0x00, 0x10, 0x00, 0x00]
+        );
+        // mov [rdx + rax], cl
+        assert_eq!(
+            assemble(|a| a.store(8, Mem::index(RDX, RAX, 1, 0), RCX)),
+            [0x88, 0x0c, 0x02]
+        );
+        // movsxd rax, dword [rcx + rax * 4]
+        assert_eq!(
+            assemble(|a| a.movsx(Size::S64, 32, RAX, Mem::index(RCX, RAX, 4, 0).into())),
+            [0x48, 0x63, 0x04, 0x81]
+        );
+    }
+
+    #[test]
+    fn immediates() {
+        assert_eq!(assemble(|a| a.mov_imm(RAX, 1)), [0xb8, 1, 0, 0, 0]);
+        assert_eq!(
+            assemble(|a| a.mov_imm(R10, u64::MAX)),
+            [0x49, 0xc7, 0xc2, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(
+            assemble(|a| a.mov_imm(RCX, 1 << 40)),
+            [0x48, 0xb9, 0, 0, 0, 0, 0, 1, 0, 0]
+        );
+        // and r10, -2
+        assert_eq!(
+            assemble(|a| a.alu_imm(Alu::And, Size::S64, R10.into(), -2)),
+            [0x49, 0x83, 0xe2, 0xfe]
+        );
+    }
+
+    #[test]
+    fn floats() {
+        // ucomisd xmm0, [rbp - 8]
+        assert_eq!(
+            assemble(|a| a.ucomis(Float::F64, Xmm(0), Mem::base(RBP, -8).into())),
+            [0x66, 0x0f, 0x2e, 0x45, 0xf8]
+        );
+        // cvttsd2si rax, xmm0
+        assert_eq!(
+            assemble(|a| a.cvtts2si(Float::F64, Size::S64, RAX, Xmm(0).into())),
+            [0xf2, 0x48, 0x0f, 0x2c, 0xc0]
+        );
+        // roundsd xmm0, [rbp - 16], 1
+        assert_eq!(
+            assemble(|a| a.round(Float::F64, 1, Xmm(0), Mem::base(RBP, -16).into())),
+            [0x66, 0x0f, 0x3a, 0x0b, 0x45, 0xf0, 0x01]
+        );
+    }
+
+    #[test]
+    fn labels() {
+        let code = assemble(|a| {
+            let top = a.new_label();
+            let end = a.new_label();
+            a.bind(top);
+            a.jcc(Cc::E, end);
+            a.jmp(top);
+            a.bind(end);
+        });
+        assert_eq!(code, [0x0f, 0x84, 5, 0, 0, 0, 0xe9, 0xf5, 0xff, 0xff, 0xff]);
+    }
+}
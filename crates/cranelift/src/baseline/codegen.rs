@@ -0,0 +1,1618 @@
+//! Translation of a function body to machine code in a single pass.
+//!
+//! Every wasm local and every entry of the operand stack has a fixed 8-byte
+//! slot in the function's frame, addressed relative to `rbp`:
+//!
+//! ```text
+//!              [rbp + 16 ..]     stack arguments
+//!              [rbp + 8]         return address
+//!              [rbp]             caller's rbp
+//!              [rbp - 8]         vmctx
+//!              [rbp - 16 ..]     locals, then the operand stack
+//!              [rsp ..]          outgoing stack arguments
+//! ```
+//!
+//! Since the height of the operand stack is known statically at every
+//! instruction, each instruction loads its operands from their slots into
+//! fixed scratch registers and stores its results back, so no register
+//! allocation is needed. Values in slots are only defined in their low bits
+//! for their type, so for example `i32` values are always read with 32-bit
+//! loads.
+//!
+//! With tiered compilation, functions count how hot they are by decrementing
+//! their counter on entry and at the start of each loop iteration, points at
+//! which every value is in its slot, so calling into the runtime once the
+//! counter reaches zero doesn't need to save any registers.
+
+use super::asm::*;
+use crate::{CompiledFunction, FunctionAddressMap, Relocation, RelocationTarget};
+use cranelift_codegen::binemit::Reloc;
+use cranelift_codegen::isa::TargetIsa;
+use cranelift_wasm::{
+    FuncIndex, GlobalIndex, MemoryIndex, TableIndex, TypeIndex, WasmFuncType, WasmResult, WasmType,
+};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::mem;
+use wasmparser::{BlockType, FuncValidator, FunctionBody, MemoryImmediate, Operator};
+use wasmtime_environ::{
+    BuiltinFunctionIndex, FilePos, FunctionInfo, GlobalSlot, InstructionAddressMap,
+    LazyFunctionTable, MemoryPlan, MemoryStyle, Module, TrapCode, TrapInformation, Tunables,
+    TypeTables, VMOffsets, WASM_PAGE_SIZE,
+};
+
+/// The slot holding the function's `VMContext`.
+const VMCTX: Mem = Mem::base(RBP, -8);
+
+/// The registers of integer arguments in the System V calling convention.
+const INT_ARGS: [Gpr; 6] = [RDI, RSI, RDX, RCX, R8, R9];
+
+/// The scratch register for floating point values.
+const XMM0: Xmm = Xmm(0);
+
+/// Where an argument is passed to a function.
+enum ArgLoc {
+    Gpr(Gpr),
+    Xmm(Xmm),
+    /// An offset from the stack pointer at the call.
+    Stack(i32),
+}
+
+/// Assigns locations to the wasm parameters `params` of a function, which are
+/// passed after its callee and caller `VMContext`s, returning them along with
+/// the number of bytes of stack arguments.
+fn arg_locations(params: &[WasmType]) -> (Vec<ArgLoc>, u32) {
+    let mut gprs = 2;
+    let mut xmms = 0;
+    let mut stack = 0;
+    let locations = params
+        .iter()
+        .map(|ty| match ty {
+            WasmType::I32 | WasmType::I64 if gprs < INT_ARGS.len() => {
+                gprs += 1;
+                ArgLoc::Gpr(INT_ARGS[gprs - 1])
+            }
+            WasmType::F32 | WasmType::F64 if xmms < 8 => {
+                xmms += 1;
+                ArgLoc::Xmm(Xmm(xmms - 1))
+            }
+            _ => {
+                stack += 8;
+                ArgLoc::Stack(stack - 8)
+            }
+        })
+        .collect();
+    (locations, (stack as u32 + 15) & !15)
+}
+
+fn disp(offset: impl Into<u32>) -> i32 {
+    i32::try_from(offset.into()).unwrap()
+}
+
+enum FrameKind {
+    Function,
+    Block,
+    Loop,
+    If {
+        /// Where the `else` arm starts, until it's reached.
+        else_label: Option<Label>,
+    },
+}
+
+/// A block of structured control flow.
+struct Frame {
+    kind: FrameKind,
+    /// The height of the operand stack below the block's parameters.
+    height: u32,
+    params: u32,
+    results: u32,
+    /// The target of branches to this block, which is its start for loops and
+    /// its end otherwise.
+    label: Label,
+    /// Whether the end of the block is branched to.
+    branched: bool,
+}
+
+/// The ways in which loads extend the value they load.
+#[derive(Copy, Clone)]
+enum Load {
+    I32,
+    I64,
+    U8,
+    U16,
+    S8(Size),
+    S16(Size),
+    S32,
+}
+
+pub(super) struct FuncCodegen<'a> {
+    asm: Assembler,
+    isa: &'a dyn TargetIsa,
+    module: &'a Module,
+    types: &'a TypeTables,
+    tunables: &'a Tunables,
+    offsets: VMOffsets<u8>,
+    func_index: FuncIndex,
+    /// Where the module's other functions are found, if it's compiled lazily.
+    lazy_table: Option<&'a LazyFunctionTable>,
+    num_locals: u32,
+    /// The current height of the operand stack.
+    depth: u32,
+    max_depth: u32,
+    /// The number of bytes needed for the stack arguments of calls.
+    outgoing_args: u32,
+    control: Vec<Frame>,
+    /// Whether the current instruction is reachable.
+    reachable: bool,
+    /// The number of blocks entered within unreachable code, which no code is
+    /// generated for.
+    dead_blocks: u32,
+    /// The location of the current instruction in the wasm module.
+    srcloc: FilePos,
+    address_map: Vec<InstructionAddressMap>,
+    traps: Vec<TrapInformation>,
+    /// Out-of-line trapping instructions which are placed after the body.
+    trap_stubs: Vec<(Label, TrapCode, FilePos)>,
+    relocations: Vec<Relocation>,
+}
+
+impl<'a> FuncCodegen<'a> {
+    pub(super) fn new(
+        isa: &'a dyn TargetIsa,
+        module: &'a Module,
+        types: &'a TypeTables,
+        tunables: &'a Tunables,
+        func_index: FuncIndex,
+        lazy_table: Option<&'a LazyFunctionTable>,
+    ) -> FuncCodegen<'a> {
+        FuncCodegen {
+            asm: Assembler::default(),
+            isa,
+            module,
+            types,
+            tunables,
+            offsets: VMOffsets::new(isa.pointer_bytes(), module),
+            func_index,
+            lazy_table,
+            num_locals: 0,
+            depth: 0,
+            max_depth: 0,
+            outgoing_args: 0,
+            control: Vec::new(),
+            reachable: true,
+            dead_blocks: 0,
+            srcloc: FilePos::default(),
+            address_map: Vec::new(),
+            traps: Vec::new(),
+            trap_stubs: Vec::new(),
+            relocations: Vec::new(),
+        }
+    }
+
+    fn signature(&self, func: FuncIndex) -> &'a WasmFuncType {
+        &self.types[self.module.functions[func].signature]
+    }
+
+    /// Compiles `body`, validating it with `validator`.
+    pub(super) fn compile(
+        mut self,
+        body: &FunctionBody<'_>,
+        validator: &mut FuncValidator<impl wasmparser::WasmModuleResources>,
+    ) -> WasmResult<CompiledFunction> {
+        let mut reader = body.get_binary_reader();
+        let start = reader.original_position();
+        let end = start + reader.bytes_remaining();
+
+        let params = self.signature(self.func_index).params();
+        let mut num_locals = params.len() as u32;
+        for _ in 0..reader.read_var_u32()? {
+            let pos = reader.original_position();
+            let count = reader.read_var_u32()?;
+            let ty = reader.read_type()?;
+            validator.define_locals(pos, count, ty)?;
+            num_locals += count;
+        }
+        self.num_locals = num_locals;
+        let frame_size_offset = self.prologue(params);
+
+        let result = self.signature(self.func_index).returns().first().copied();
+        let epilogue = self.asm.new_label();
+        self.control.push(Frame {
+            kind: FrameKind::Function,
+            height: 0,
+            params: 0,
+            results: result.is_some() as u32,
+            label: epilogue,
+            branched: false,
+        });
+        while !reader.eof() {
+            let pos = reader.original_position();
+            let op = reader.read_operator()?;
+            validator.op(pos, &op)?;
+            self.srcloc = FilePos::new(pos as u32);
+            self.mark_srcloc();
+            self.operator(&op)?;
+        }
+        validator.finish(reader.original_position())?;
+
+        match result {
+            Some(WasmType::F32 | WasmType::F64) => {
+                self.asm.movs_load(Float::F64, XMM0, self.slot(0))
+            }
+            Some(_) => self.asm.load(Size::S64, RAX, self.slot(0)),
+            None => {}
+        }
+        self.asm.mov_rr(Size::S64, RSP, RBP);
+        self.asm.pop_rbp();
+        self.asm.ret();
+
+        for (label, code, srcloc) in mem::take(&mut self.trap_stubs) {
+            self.asm.bind(label);
+            self.srcloc = srcloc;
+            self.mark_srcloc();
+            let at = self.asm.ud2();
+            self.trap_at(at, code);
+        }
+
+        let size = 8 * (1 + self.num_locals + self.max_depth) + self.outgoing_args;
+        let frame_size = (size + 15) & !15;
+        self.asm.patch_u32(frame_size_offset, frame_size);
+        let code = self.asm.finish();
+        let length = code.len() as u32;
+        let start_srcloc = FilePos::new(start as u32);
+        Ok(CompiledFunction {
+            body: code,
+            unwind_info: None,
+            baseline_unwind_info: self.isa.flags().unwind_info(),
+            address_map: FunctionAddressMap {
+                instructions: self.address_map.into(),
+                start_srcloc,
+                end_srcloc: FilePos::new(end as u32),
+                body_offset: 0,
+                body_len: length,
+            },
+            traps: self.traps,
+            relocations: self.relocations,
+            value_labels_ranges: Default::default(),
+            stack_slots: Default::default(),
+            info: FunctionInfo {
+                start_srcloc,
+                stack_maps: Vec::new(),
+                start: 0,
+                length,
+                frame_size,
+                wasm_size: (end - start) as u32,
+                compile_time: None,
+            },
+        })
+    }
+
+    /// Sets up the function's frame and moves its arguments to their slots,
+    /// returning the offset of the frame size, which is patched once the whole
+    /// function has been compiled.
+    fn prologue(&mut self, params: &[WasmType]) -> u32 {
+        if self.isa.is_branch_protection_enabled() {
+            self.asm.endbr64();
+        }
+        self.asm.push_rbp();
+        self.asm.mov_rr(Size::S64, RBP, RSP);
+        let frame_size = self.asm.sub_rsp();
+
+        // Check for stack overflow before touching the new frame. See the top
+        // of `lib.rs` for more details.
+        let limits = disp(self.offsets.vmctx_runtime_limits());
+        let stack_limit = disp(self.offsets.vmruntime_limits_stack_limit());
+        self.asm.load(Size::S64, R11, Mem::base(RDI, limits));
+        self.asm.load(Size::S64, R11, Mem::base(R11, stack_limit));
+        self.asm.alu(Alu::Cmp, Size::S64, RSP, R11.into());
+        let overflow = self.asm.new_label();
+        self.trap_stubs
+            .push((overflow, TrapCode::StackOverflow, FilePos::default()));
+        self.asm.jcc(Cc::B, overflow);
+
+        self.asm.store(64, VMCTX, RDI);
+        let (locations, _) = arg_locations(params);
+        for (i, location) in locations.into_iter().enumerate() {
+            let local = self.local(i as u32);
+            match location {
+                ArgLoc::Gpr(reg) => self.asm.store(64, local, reg),
+                ArgLoc::Xmm(reg) => self.asm.movs_store(Float::F64, local, reg),
+                ArgLoc::Stack(offset) => {
+                    self.asm.load(Size::S64, RAX, Mem::base(RBP, 16 + offset));
+                    self.asm.store(64, local, RAX);
+                }
+            }
+        }
+
+        // Zero the remaining locals.
+        let zeroed = self.num_locals - params.len() as u32;
+        if zeroed <= 8 {
+            for i in params.len() as u32..self.num_locals {
+                self.asm.store_imm(Size::S64, self.local(i), 0);
+            }
+        } else {
+            self.asm.alu(Alu::Xor, Size::S32, RAX, RAX.into());
+            self.asm.lea(RDI, self.local(self.num_locals - 1));
+            self.asm.mov_imm(RCX, zeroed.into());
+            self.asm.rep_stosq();
+        }
+        self.count_hotness();
+        frame_size
+    }
+
+    /// Decrements the function's hotness counter, if it has one, asking the
+    /// runtime to recompile it with Cranelift once it reaches zero.
+    ///
+    /// The counter isn't decremented atomically, since it only needs to be
+    /// roughly right, so updates from concurrent calls may be lost.
+    fn count_hotness(&mut self) {
+        let counters = match self.lazy_table.and_then(|t| t.counters) {
+            Some(counters) => counters,
+            None => return,
+        };
+        let index = self.module.defined_func_index(self.func_index).unwrap();
+        let counter = counters + index.as_u32() as usize * 4;
+        self.asm.mov_imm(R11, counter as u64);
+        self.asm
+            .alu_imm(Alu::Sub, Size::S32, Mem::base(R11, 0).into(), 1);
+        let warm = self.asm.new_label();
+        self.asm.jcc(Cc::NE, warm);
+        self.asm.mov_imm(RSI, self.func_index.as_u32().into());
+        self.call_builtin(BuiltinFunctionIndex::tier_up());
+        self.asm.bind(warm);
+    }
+
+    fn local(&self, index: u32) -> Mem {
+        Mem::base(RBP, -8 * (2 + index as i32))
+    }
+
+    /// The slot of the operand stack entry `index`, counted from the bottom.
+    fn slot(&self, index: u32) -> Mem {
+        self.local(self.num_locals + index)
+    }
+
+    fn push(&mut self) -> Mem {
+        let slot = self.slot(self.depth);
+        self.depth += 1;
+        self.max_depth = self.max_depth.max(self.depth);
+        slot
+    }
+
+    fn push_gpr(&mut self, reg: Gpr) {
+        let slot = self.push();
+        self.asm.store(64, slot, reg);
+    }
+
+    /// Pushes the value of type `ty` in `xmm0`.
+    fn push_xmm(&mut self, ty: Float) {
+        let slot = self.push();
+        self.asm.movs_store(ty, slot, XMM0);
+    }
+
+    fn push_imm32(&mut self, value: i32) {
+        let slot = self.push();
+        self.asm.store_imm(Size::S32, slot, value);
+    }
+
+    fn pop(&mut self) -> Mem {
+        self.depth -= 1;
+        self.slot(self.depth)
+    }
+
+    /// Records that the code emitted from here on belongs to the current
+    /// instruction.
+    fn mark_srcloc(&mut self) {
+        if !self.tunables.generate_address_map {
+            return;
+        }
+        let code_offset = self.asm.offset();
+        match self.address_map.last_mut() {
+            Some(last) if last.code_offset == code_offset => last.srcloc = self.srcloc,
+            _ => self.address_map.push(InstructionAddressMap {
+                srcloc: self.srcloc,
+                code_offset,
+            }),
+        }
+    }
+
+    fn trap_at(&mut self, code_offset: u32, trap_code: TrapCode) {
+        self.traps.push(TrapInformation {
+            code_offset,
+            trap_code,
+        });
+    }
+
+    /// Returns a label which raises the trap `code` for the current
+    /// instruction.
+    fn trap_label(&mut self, code: TrapCode) -> Label {
+        let label = self.asm.new_label();
+        self.trap_stubs.push((label, code, self.srcloc));
+        label
+    }
+
+    fn block_arity(&self, ty: BlockType) -> (u32, u32) {
+        match ty {
+            BlockType::Empty => (0, 0),
+            BlockType::Type(_) => (0, 1),
+            BlockType::FuncType(index) => {
+                let sig = self.module.types[TypeIndex::from_u32(index)].unwrap_function();
+                let sig = &self.types[sig];
+                (sig.params().len() as u32, sig.returns().len() as u32)
+            }
+        }
+    }
+
+    fn push_frame(&mut self, kind: FrameKind, ty: BlockType, label: Label) {
+        let (params, results) = self.block_arity(ty);
+        self.control.push(Frame {
+            kind,
+            height: self.depth - params,
+            params,
+            results,
+            label,
+            branched: false,
+        });
+    }
+
+    fn operator(&mut self, op: &Operator<'_>) -> WasmResult<()> {
+        use Operator::*;
+
+        // No code is generated for unreachable instructions, other than the
+        // ends of the blocks they're in.
+        if !self.reachable {
+            match op {
+                Block { .. } | Loop { .. } | If { .. } => {
+                    self.dead_blocks += 1;
+                    return Ok(());
+                }
+                End if self.dead_blocks > 0 => {
+                    self.dead_blocks -= 1;
+                    return Ok(());
+                }
+                Else | End if self.dead_blocks == 0 => {}
+                _ => return Ok(()),
+            }
+        }
+
+        match *op {
+            Unreachable => {
+                let at = self.asm.ud2();
+                self.trap_at(at, TrapCode::UnreachableCodeReached);
+                self.reachable = false;
+            }
+            Nop => {}
+            Block { ty } => {
+                let label = self.asm.new_label();
+                self.push_frame(FrameKind::Block, ty, label);
+            }
+            Loop { ty } => {
+                let label = self.asm.new_label();
+                self.asm.bind(label);
+                self.count_hotness();
+                self.push_frame(FrameKind::Loop, ty, label);
+            }
+            If { ty } => {
+                let cond = self.pop();
+                self.asm.load(Size::S32, RAX, cond);
+                self.asm.test(Size::S32, RAX, RAX);
+                let else_label = self.asm.new_label();
+                self.asm.jcc(Cc::E, else_label);
+                let label = self.asm.new_label();
+                let kind = FrameKind::If {
+                    else_label: Some(else_label),
+                };
+                self.push_frame(kind, ty, label);
+            }
+            Else => {
+                let reachable = self.reachable;
+                let frame = self.control.last_mut().unwrap();
+                if reachable {
+                    frame.branched = true;
+                    self.asm.jmp(frame.label);
+                }
+                if let FrameKind::If { else_label } = &mut frame.kind {
+                    self.asm.bind(else_label.take().unwrap());
+                }
+                self.depth = frame.height + frame.params;
+                self.reachable = true;
+            }
+            End => {
+                let frame = self.control.pop().unwrap();
+                match frame.kind {
+                    FrameKind::Loop => {}
+                    FrameKind::If {
+                        else_label: Some(else_label),
+                    } => {
+                        self.asm.bind(else_label);
+                        self.asm.bind(frame.label);
+                        self.reachable = true;
+                    }
+                    _ => {
+                        self.asm.bind(frame.label);
+                        self.reachable |= frame.branched;
+                    }
+                }
+                self.depth = frame.height + frame.results;
+            }
+            Br { relative_depth } => {
+                self.branch(relative_depth);
+                self.reachable = false;
+            }
+            BrIf { relative_depth } => {
+                let cond = self.pop();
+                self.asm.load(Size::S32, RAX, cond);
+                self.asm.test(Size::S32, RAX, RAX);
+                let (arity, height, label) = self.branch_target(relative_depth);
+                if arity > 0 && self.depth - arity != height {
+                    let skip = self.asm.new_label();
+                    self.asm.jcc(Cc::E, skip);
+                    self.branch(relative_depth);
+                    self.asm.bind(skip);
+                } else {
+                    self.mark_branched(relative_depth);
+                    self.asm.jcc(Cc::NE, label);
+                }
+            }
+            BrTable { ref table } => {
+                let targets = table.targets().collect::<Result<Vec<_>, _>>()?;
+                self.br_table(&targets, table.default());
+                self.reachable = false;
+            }
+            Return => {
+                self.branch(self.control.len() as u32 - 1);
+                self.reachable = false;
+            }
+            Call { function_index } => self.call(FuncIndex::from_u32(function_index)),
+            CallIndirect {
+                index, table_index, ..
+            } => self.call_indirect(
+                TypeIndex::from_u32(index),
+                TableIndex::from_u32(table_index),
+            ),
+            Drop => {
+                self.pop();
+            }
+            Select | TypedSelect { .. } => {
+                let cond = self.pop();
+                let b = self.pop();
+                let a = self.pop();
+                self.asm.load(Size::S32, RAX, cond);
+                self.asm.load(Size::S64, RCX, a);
+                self.asm.load(Size::S64, RDX, b);
+                self.asm.test(Size::S32, RAX, RAX);
+                self.asm.cmov(Cc::E, Size::S64, RCX, RDX.into());
+                self.push_gpr(RCX);
+            }
+
+            LocalGet { local_index } => {
+                self.asm.load(Size::S64, RAX, self.local(local_index));
+                self.push_gpr(RAX);
+            }
+            LocalSet { local_index } => {
+                let value = self.pop();
+                self.asm.load(Size::S64, RAX, value);
+                self.asm.store(64, self.local(local_index), RAX);
+            }
+            LocalTee { local_index } => {
+                self.asm.load(Size::S64, RAX, self.slot(self.depth - 1));
+                self.asm.store(64, self.local(local_index), RAX);
+            }
+            GlobalGet { global_index } => {
+                let global = self.global(GlobalIndex::from_u32(global_index));
+                self.asm.load(Size::S64, RAX, global);
+                self.push_gpr(RAX);
+            }
+            GlobalSet { global_index } => {
+                let index = GlobalIndex::from_u32(global_index);
+                let bits = match self.module.globals[index].wasm_ty {
+                    WasmType::I32 | WasmType::F32 => 32,
+                    _ => 64,
+                };
+                let value = self.pop();
+                self.asm.load(Size::S64, RAX, value);
+                let global = self.global(index);
+                self.asm.store(bits, global, RAX);
+            }
+
+            I32Load { memarg } => self.load(memarg, 4, Load::I32),
+            I64Load { memarg } => self.load(memarg, 8, Load::I64),
+            F32Load { memarg } => self.load(memarg, 4, Load::I32),
+            F64Load { memarg } => self.load(memarg, 8, Load::I64),
+            I32Load8S { memarg } => self.load(memarg, 1, Load::S8(Size::S32)),
+            I32Load8U { memarg } | I64Load8U { memarg } => self.load(memarg, 1, Load::U8),
+            I32Load16S { memarg } => self.load(memarg, 2, Load::S16(Size::S32)),
+            I32Load16U { memarg } | I64Load16U { memarg } => self.load(memarg, 2, Load::U16),
+            I64Load8S { memarg } => self.load(memarg, 1, Load::S8(Size::S64)),
+            I64Load16S { memarg } => self.load(memarg, 2, Load::S16(Size::S64)),
+            I64Load32S { memarg } => self.load(memarg, 4, Load::S32),
+            I64Load32U { memarg } => self.load(memarg, 4, Load::I32),
+            I32Store { memarg } | F32Store { memarg } | I64Store32 { memarg } => {
+                self.store(memarg, 32)
+            }
+            I64Store { memarg } | F64Store { memarg } => self.store(memarg, 64),
+            I32Store8 { memarg } | I64Store8 { memarg } => self.store(memarg, 8),
+            I32Store16 { memarg } | I64Store16 { memarg } => self.store(memarg, 16),
+            MemorySize { mem, .. } => {
+                let (vmmemory, _, current_length) = self.memory(MemoryIndex::from_u32(mem));
+                self.asm
+                    .load(Size::S64, RAX, Mem::base(vmmemory, current_length));
+                self.asm.shift_imm(Shift::Shr, Size::S64, RAX, 16);
+                self.push_gpr(RAX);
+            }
+            MemoryGrow { mem, .. } => {
+                let delta = self.pop();
+                self.asm.load(Size::S32, RSI, delta);
+                self.asm.mov_imm(RDX, mem.into());
+                self.call_builtin(BuiltinFunctionIndex::memory32_grow());
+                self.push_gpr(RAX);
+            }
+
+            I32Const { value } => self.push_imm32(value),
+            I64Const { value } => self.const64(value as u64),
+            F32Const { value } => self.push_imm32(value.bits() as i32),
+            F64Const { value } => self.const64(value.bits()),
+
+            I32Eqz => self.int_unop(Size::S32, |asm| {
+                asm.test(Size::S32, RAX, RAX);
+                asm.setcc(Cc::E, RAX);
+                asm.movzx(8, RAX, RAX.into());
+            }),
+            I64Eqz => self.int_unop(Size::S64, |asm| {
+                asm.test(Size::S64, RAX, RAX);
+                asm.setcc(Cc::E, RAX);
+                asm.movzx(8, RAX, RAX.into());
+            }),
+            I32Eq => self.compare(Size::S32, Cc::E),
+            I32Ne => self.compare(Size::S32, Cc::NE),
+            I32LtS => self.compare(Size::S32, Cc::L),
+            I32LtU => self.compare(Size::S32, Cc::B),
+            I32GtS => self.compare(Size::S32, Cc::G),
+            I32GtU => self.compare(Size::S32, Cc::A),
+            I32LeS => self.compare(Size::S32, Cc::LE),
+            I32LeU => self.compare(Size::S32, Cc::BE),
+            I32GeS => self.compare(Size::S32, Cc::GE),
+            I32GeU => self.compare(Size::S32, Cc::AE),
+            I64Eq => self.compare(Size::S64, Cc::E),
+            I64Ne => self.compare(Size::S64, Cc::NE),
+            I64LtS => self.compare(Size::S64, Cc::L),
+            I64LtU => self.compare(Size::S64, Cc::B),
+            I64GtS => self.compare(Size::S64, Cc::G),
+            I64GtU => self.compare(Size::S64, Cc::A),
+            I64LeS => self.compare(Size::S64, Cc::LE),
+            I64LeU => self.compare(Size::S64, Cc::BE),
+            I64GeS => self.compare(Size::S64, Cc::GE),
+            I64GeU => self.compare(Size::S64, Cc::AE),
+
+            I32Clz => self.clz(Size::S32),
+            I64Clz => self.clz(Size::S64),
+            I32Ctz => self.ctz(Size::S32),
+            I64Ctz => self.ctz(Size::S64),
+            I32Popcnt => self.int_unop(Size::S32, |asm| asm.popcnt(Size::S32, RAX, RAX.into())),
+            I64Popcnt => self.int_unop(Size::S64, |asm| asm.popcnt(Size::S64, RAX, RAX.into())),
+            I32Add => self.alu(Alu::Add, Size::S32),
+            I32Sub => self.alu(Alu::Sub, Size::S32),
+            I32And => self.alu(Alu::And, Size::S32),
+            I32Or => self.alu(Alu::Or, Size::S32),
+            I32Xor => self.alu(Alu::Xor, Size::S32),
+            I64Add => self.alu(Alu::Add, Size::S64),
+            I64Sub => self.alu(Alu::Sub, Size::S64),
+            I64And => self.alu(Alu::And, Size::S64),
+            I64Or => self.alu(Alu::Or, Size::S64),
+            I64Xor => self.alu(Alu::Xor, Size::S64),
+            I32Mul => self.int_binop(Size::S32, |asm, b| asm.imul(Size::S32, RAX, b.into())),
+            I64Mul => self.int_binop(Size::S64, |asm, b| asm.imul(Size::S64, RAX, b.into())),
+            I32DivS => self.div(Size::S32, true, false),
+            I32DivU => self.div(Size::S32, false, false),
+            I32RemS => self.div(Size::S32, true, true),
+            I32RemU => self.div(Size::S32, false, true),
+            I64DivS => self.div(Size::S64, true, false),
+            I64DivU => self.div(Size::S64, false, false),
+            I64RemS => self.div(Size::S64, true, true),
+            I64RemU => self.div(Size::S64, false, true),
+            I32Shl => self.shift(Shift::Shl, Size::S32),
+            I32ShrS => self.shift(Shift::Sar, Size::S32),
+            I32ShrU => self.shift(Shift::Shr, Size::S32),
+            I32Rotl => self.shift(Shift::Rol, Size::S32),
+            I32Rotr => self.shift(Shift::Ror, Size::S32),
+            I64Shl => self.shift(Shift::Shl, Size::S64),
+            I64ShrS => self.shift(Shift::Sar, Size::S64),
+            I64ShrU => self.shift(Shift::Shr, Size::S64),
+            I64Rotl => self.shift(Shift::Rol, Size::S64),
+            I64Rotr => self.shift(Shift::Ror, Size::S64),
+
+            // Values are only read with the width of their type, so
+            // truncation and zero-extension happen when they're loaded.
+            I32WrapI64 | I64ExtendI32U => self.int_unop(Size::S32, |_| {}),
+            I64ExtendI32S | I64Extend32S => {
+                self.int_unop(Size::S32, |asm| asm.movsx(Size::S64, 32, RAX, RAX.into()))
+            }
+            I32Extend8S => self.int_unop(Size::S32, |asm| asm.movsx(Size::S32, 8, RAX, RAX.into())),
+            I32Extend16S => {
+                self.int_unop(Size::S32, |asm| asm.movsx(Size::S32, 16, RAX, RAX.into()))
+            }
+            I64Extend8S => self.int_unop(Size::S64, |asm| asm.movsx(Size::S64, 8, RAX, RAX.into())),
+            I64Extend16S => {
+                self.int_unop(Size::S64, |asm| asm.movsx(Size::S64, 16, RAX, RAX.into()))
+            }
+            I32ReinterpretF32 | I64ReinterpretF64 | F32ReinterpretI32 | F64ReinterpretI64 => {}
+
+            F32Eq => self.float_compare(Float::F32, Cc::E, false),
+            F32Ne => self.float_compare(Float::F32, Cc::NE, false),
+            F32Lt => self.float_compare(Float::F32, Cc::A, true),
+            F32Gt => self.float_compare(Float::F32, Cc::A, false),
+            F32Le => self.float_compare(Float::F32, Cc::AE, true),
+            F32Ge => self.float_compare(Float::F32, Cc::AE, false),
+            F64Eq => self.float_compare(Float::F64, Cc::E, false),
+            F64Ne => self.float_compare(Float::F64, Cc::NE, false),
+            F64Lt => self.float_compare(Float::F64, Cc::A, true),
+            F64Gt => self.float_compare(Float::F64, Cc::A, false),
+            F64Le => self.float_compare(Float::F64, Cc::AE, true),
+            F64Ge => self.float_compare(Float::F64, Cc::AE, false),
+
+            F32Add => self.float_binop(Sse::Add, Float::F32),
+            F32Sub => self.float_binop(Sse::Sub, Float::F32),
+            F32Mul => self.float_binop(Sse::Mul, Float::F32),
+            F32Div => self.float_binop(Sse::Div, Float::F32),
+            F64Add => self.float_binop(Sse::Add, Float::F64),
+            F64Sub => self.float_binop(Sse::Sub, Float::F64),
+            F64Mul => self.float_binop(Sse::Mul, Float::F64),
+            F64Div => self.float_binop(Sse::Div, Float::F64),
+            F32Sqrt => self.float_unop(Float::F32, |asm, a| {
+                asm.sse(Sse::Sqrt, Float::F32, XMM0, a.into())
+            }),
+            F64Sqrt => self.float_unop(Float::F64, |asm, a| {
+                asm.sse(Sse::Sqrt, Float::F64, XMM0, a.into())
+            }),
+            F32DemoteF64 => self.float_unop(Float::F32, |asm, a| {
+                asm.cvt_float(Float::F64, XMM0, a.into())
+            }),
+            F64PromoteF32 => self.float_unop(Float::F64, |asm, a| {
+                asm.cvt_float(Float::F32, XMM0, a.into())
+            }),
+            F32Min => self.min_max(Float::F32, Sse::Min),
+            F32Max => self.min_max(Float::F32, Sse::Max),
+            F64Min => self.min_max(Float::F64, Sse::Min),
+            F64Max => self.min_max(Float::F64, Sse::Max),
+            F32Nearest => self.round(Float::F32, 0),
+            F32Floor => self.round(Float::F32, 1),
+            F32Ceil => self.round(Float::F32, 2),
+            F32Trunc => self.round(Float::F32, 3),
+            F64Nearest => self.round(Float::F64, 0),
+            F64Floor => self.round(Float::F64, 1),
+            F64Ceil => self.round(Float::F64, 2),
+            F64Trunc => self.round(Float::F64, 3),
+            F32ConvertI32S => self.convert(Float::F32, Size::S32),
+            F32ConvertI32U | F32ConvertI64S => self.convert(Float::F32, Size::S64),
+            F64ConvertI32S => self.convert(Float::F64, Size::S32),
+            F64ConvertI32U | F64ConvertI64S => self.convert(Float::F64, Size::S64),
+            F32ConvertI64U => self.convert_u64(Float::F32),
+            F64ConvertI64U => self.convert_u64(Float::F64),
+            I32TruncF32S => self.trunc_signed(Float::F32, Size::S32, false),
+            I32TruncF64S => self.trunc_signed(Float::F64, Size::S32, false),
+            I64TruncF32S => self.trunc_signed(Float::F32, Size::S64, false),
+            I64TruncF64S => self.trunc_signed(Float::F64, Size::S64, false),
+            I32TruncSatF32S => self.trunc_signed(Float::F32, Size::S32, true),
+            I32TruncSatF64S => self.trunc_signed(Float::F64, Size::S32, true),
+            I64TruncSatF32S => self.trunc_signed(Float::F32, Size::S64, true),
+            I64TruncSatF64S => self.trunc_signed(Float::F64, Size::S64, true),
+            I32TruncF32U => self.trunc_u32(Float::F32, false),
+            I32TruncF64U => self.trunc_u32(Float::F64, false),
+            I32TruncSatF32U => self.trunc_u32(Float::F32, true),
+            I32TruncSatF64U => self.trunc_u32(Float::F64, true),
+            I64TruncF32U => self.trunc_u64(Float::F32, false),
+            I64TruncF64U => self.trunc_u64(Float::F64, false),
+            I64TruncSatF32U => self.trunc_u64(Float::F32, true),
+            I64TruncSatF64U => self.trunc_u64(Float::F64, true),
+
+            // The sign of floats is manipulated as bits.
+            F32Abs => self.int_unop(Size::S32, |asm| {
+                asm.alu_imm(Alu::And, Size::S32, RAX.into(), i32::MAX)
+            }),
+            F32Neg => self.int_unop(Size::S32, |asm| {
+                asm.alu_imm(Alu::Xor, Size::S32, RAX.into(), i32::MIN)
+            }),
+            F64Abs => self.int_unop(Size::S64, |asm| {
+                asm.mov_imm(RCX, i64::MAX as u64);
+                asm.alu(Alu::And, Size::S64, RAX, RCX.into());
+            }),
+            F64Neg => self.int_unop(Size::S64, |asm| {
+                asm.mov_imm(RCX, i64::MIN as u64);
+                asm.alu(Alu::Xor, Size::S64, RAX, RCX.into());
+            }),
+            F32Copysign => self.int_binop(Size::S32, |asm, b| {
+                asm.load(Size::S32, RCX, b);
+                asm.alu_imm(Alu::And, Size::S32, RAX.into(), i32::MAX);
+                asm.alu_imm(Alu::And, Size::S32, RCX.into(), i32::MIN);
+                asm.alu(Alu::Or, Size::S32, RAX, RCX.into());
+            }),
+            F64Copysign => self.int_binop(Size::S64, |asm, b| {
+                asm.load(Size::S64, RCX, b);
+                asm.mov_imm(RDX, i64::MAX as u64);
+                asm.alu(Alu::And, Size::S64, RAX, RDX.into());
+                asm.mov_imm(RDX, i64::MIN as u64);
+                asm.alu(Alu::And, Size::S64, RCX, RDX.into());
+                asm.alu(Alu::Or, Size::S64, RAX, RCX.into());
+            }),
+
+            _ => unreachable!("unsupported operator {:?}", op),
+        }
+        Ok(())
+    }
+
+    // Control flow.
+
+    /// Returns the number of values passed to the block `relative_depth`
+    /// when branching to it, along with its height and branch target.
+    fn branch_target(&self, relative_depth: u32) -> (u32, u32, Label) {
+        let frame = &self.control[self.control.len() - 1 - relative_depth as usize];
+        let arity = match frame.kind {
+            FrameKind::Loop => frame.params,
+            _ => frame.results,
+        };
+        (arity, frame.height, frame.label)
+    }
+
+    fn mark_branched(&mut self, relative_depth: u32) {
+        let index = self.control.len() - 1 - relative_depth as usize;
+        self.control[index].branched = true;
+    }
+
+    /// Moves the values passed to the block `relative_depth` into place and
+    /// jumps to it.
+    fn branch(&mut self, relative_depth: u32) {
+        let (arity, height, label) = self.branch_target(relative_depth);
+        self.mark_branched(relative_depth);
+        let from = self.depth - arity;
+        if from != height {
+            for i in 0..arity {
+                self.asm.load(Size::S64, RAX, self.slot(from + i));
+                self.asm.store(64, self.slot(height + i), RAX);
+            }
+        }
+        self.asm.jmp(label);
+    }
+
+    /// Branches through a jump table of offsets to stubs which branch to each
+    /// distinct target.
+    fn br_table(&mut self, targets: &[u32], default: u32) {
+        let index = self.pop();
+        if targets.is_empty() {
+            return self.branch(default);
+        }
+        let mut stubs = BTreeMap::new();
+        let default_stub = self.asm.new_label();
+        stubs.insert(default, default_stub);
+
+        self.asm.load(Size::S32, RAX, index);
+        self.asm
+            .alu_imm(Alu::Cmp, Size::S32, RAX.into(), targets.len() as i32);
+        self.asm.jcc(Cc::AE, default_stub);
+        let table = self.asm.new_label();
+        self.asm.lea_label(RCX, table);
+        self.asm
+            .movsx(Size::S64, 32, RAX, Mem::index(RCX, RAX, 4, 0).into());
+        self.asm.alu(Alu::Add, Size::S64, RAX, RCX.into());
+        self.asm.jmp_reg(RAX);
+
+        self.asm.bind(table);
+        for target in targets {
+            let asm = &mut self.asm;
+            let stub = *stubs.entry(*target).or_insert_with(|| asm.new_label());
+            self.asm.table_entry(stub, table);
+        }
+        for (target, stub) in stubs {
+            self.asm.bind(stub);
+            if self.isa.is_branch_protection_enabled() {
+                self.asm.endbr64();
+            }
+            self.branch(target);
+        }
+    }
+
+    // Calls.
+
+    /// Moves the arguments of a call with the wasm parameters `params` from
+    /// the operand stack to where they're passed.
+    fn load_args(&mut self, params: &[WasmType]) {
+        let (locations, stack) = arg_locations(params);
+        self.outgoing_args = self.outgoing_args.max(stack);
+        self.depth -= params.len() as u32;
+        let base = self.depth;
+
+        // Stack arguments are moved through `rax`, which isn't used to pass
+        // arguments.
+        for (i, location) in locations.iter().enumerate() {
+            if let ArgLoc::Stack(offset) = location {
+                self.asm.load(Size::S64, RAX, self.slot(base + i as u32));
+                self.asm.store(64, Mem::base(RSP, *offset), RAX);
+            }
+        }
+        for (i, location) in locations.iter().enumerate() {
+            let slot = self.slot(base + i as u32);
+            match location {
+                ArgLoc::Gpr(reg) => self.asm.load(Size::S64, *reg, slot),
+                ArgLoc::Xmm(reg) => self.asm.movs_load(Float::F64, *reg, slot),
+                ArgLoc::Stack(_) => {}
+            }
+        }
+    }
+
+    fn push_result(&mut self, sig: &WasmFuncType) {
+        match sig.returns().first() {
+            Some(WasmType::F32 | WasmType::F64) => self.push_xmm(Float::F64),
+            Some(_) => self.push_gpr(RAX),
+            None => {}
+        }
+    }
+
+    fn call(&mut self, func: FuncIndex) {
+        let sig = self.signature(func);
+        self.load_args(sig.params());
+        if self.module.is_imported_function(func) {
+            let body = disp(self.offsets.vmctx_vmfunction_import_body(func));
+            let vmctx = disp(self.offsets.vmctx_vmfunction_import_vmctx(func));
+            self.asm.load(Size::S64, RSI, VMCTX);
+            self.asm.load(Size::S64, R11, Mem::base(RSI, body));
+            self.asm.load(Size::S64, RDI, Mem::base(RSI, vmctx));
+            self.asm.call(R11.into());
+        } else if let Some(table) = self.lazy_table {
+            // Lazily compiled functions are placed on their own, so they call
+            // other functions through the module's table of compiled code,
+            // falling back to the callee's stub if it hasn't been compiled.
+            let index = self.module.defined_func_index(func).unwrap();
+            let slot = table.code + index.as_u32() as usize * usize::from(self.isa.pointer_bytes());
+            self.asm.mov_imm(RAX, slot as u64);
+            self.asm.load(Size::S64, RAX, Mem::base(RAX, 0));
+            self.asm.mov_imm(R11, table.stubs[index] as u64);
+            self.asm.test(Size::S64, RAX, RAX);
+            self.asm.cmov(Cc::E, Size::S64, RAX, R11.into());
+            self.asm.load(Size::S64, RDI, VMCTX);
+            self.asm.mov_rr(Size::S64, RSI, RDI);
+            self.asm.call(RAX.into());
+        } else {
+            self.asm.load(Size::S64, RDI, VMCTX);
+            self.asm.mov_rr(Size::S64, RSI, RDI);
+            let offset = self.asm.call_rel32();
+            self.relocations.push(Relocation {
+                reloc: Reloc::X86CallPCRel4,
+                reloc_target: RelocationTarget::UserFunc(func),
+                offset,
+                addend: -4,
+            });
+        }
+        self.push_result(sig);
+    }
+
+    fn call_indirect(&mut self, ty: TypeIndex, table: TableIndex) {
+        let sig_index = self.module.types[ty].unwrap_function();
+        let sig = &self.types[sig_index];
+        let index = self.pop();
+
+        // Find the table element, leaving its index in `rax` and its address
+        // in `rdx`.
+        self.asm.load(Size::S32, RAX, index);
+        self.asm.load(Size::S64, R10, VMCTX);
+        let (base, current_elements) = match self.module.defined_table_index(table) {
+            Some(def) => (
+                disp(self.offsets.vmctx_vmtable_definition_base(def)),
+                disp(self.offsets.vmctx_vmtable_definition_current_elements(def)),
+            ),
+            None => {
+                let from = disp(self.offsets.vmctx_vmtable_import_from(table));
+                self.asm.load(Size::S64, R10, Mem::base(R10, from));
+                (
+                    disp(self.offsets.vmtable_definition_base()),
+                    disp(self.offsets.vmtable_definition_current_elements()),
+                )
+            }
+        };
+        self.asm
+            .load(Size::S32, RCX, Mem::base(R10, current_elements));
+        self.asm.load(Size::S64, RDX, Mem::base(R10, base));
+        self.asm.alu(Alu::Cmp, Size::S32, RAX, RCX.into());
+        let out_of_bounds = self.trap_label(TrapCode::TableOutOfBounds);
+        self.asm.jcc(Cc::AE, out_of_bounds);
+        if self.isa.flags().enable_table_access_spectre_mitigation() {
+            self.asm.mov_rr(Size::S64, R11, RDX);
+            self.asm.lea(RDX, Mem::index(RDX, RAX, 8, 0));
+            self.asm.cmov(Cc::AE, Size::S64, RDX, R11.into());
+        } else {
+            self.asm.lea(RDX, Mem::index(RDX, RAX, 8, 0));
+        }
+
+        // Load the funcref into `r10`, initializing the element first if it
+        // hasn't been yet. See `FUNCREF_INIT_BIT` for more details. The
+        // elements of shared tables are instead the offsets of their anyfuncs
+        // within the `VMContext`, or zero for null.
+        self.asm.load(Size::S64, R10, Mem::base(RDX, 0));
+        self.asm.test(Size::S64, R10, R10);
+        let done = self.asm.new_label();
+        if self.module.is_shared_table(table) {
+            self.asm.jcc(Cc::E, done);
+            self.asm.alu(Alu::Add, Size::S64, R10, VMCTX.into());
+        } else {
+            let initialized = self.asm.new_label();
+            self.asm.jcc(Cc::NE, initialized);
+            self.asm.mov_rr(Size::S32, RDX, RAX);
+            self.asm.mov_imm(RSI, table.as_u32().into());
+            self.call_builtin(BuiltinFunctionIndex::table_get_lazy_init_funcref());
+            self.asm.mov_rr(Size::S64, R10, RAX);
+            self.asm.jmp(done);
+            self.asm.bind(initialized);
+            let mask = wasmtime_environ::FUNCREF_MASK as i64 as i32;
+            self.asm.alu_imm(Alu::And, Size::S64, R10.into(), mask);
+        }
+        self.asm.bind(done);
+
+        self.asm.test(Size::S64, R10, R10);
+        let null = self.trap_label(TrapCode::IndirectCallToNull);
+        self.asm.jcc(Cc::E, null);
+        let sig_id_size = u32::from(self.offsets.size_of_vmshared_signature_index());
+        let signatures = disp(self.offsets.vmctx_signature_ids_array());
+        self.asm.load(Size::S64, R11, VMCTX);
+        self.asm.load(Size::S64, R11, Mem::base(R11, signatures));
+        let caller_sig = disp(sig_index.as_u32() * sig_id_size);
+        self.asm.load(Size::S32, R11, Mem::base(R11, caller_sig));
+        let callee_sig = disp(self.offsets.vmcaller_checked_anyfunc_type_index());
+        self.asm
+            .alu(Alu::Cmp, Size::S32, R11, Mem::base(R10, callee_sig).into());
+        let bad_signature = self.trap_label(TrapCode::BadSignature);
+        self.asm.jcc(Cc::NE, bad_signature);
+
+        self.load_args(sig.params());
+        let func_ptr = disp(self.offsets.vmcaller_checked_anyfunc_func_ptr());
+        let vmctx = disp(self.offsets.vmcaller_checked_anyfunc_vmctx());
+        self.asm.load(Size::S64, R11, Mem::base(R10, func_ptr));
+        self.asm.load(Size::S64, RDI, Mem::base(R10, vmctx));
+        self.asm.load(Size::S64, RSI, VMCTX);
+        self.asm.call(R11.into());
+        self.push_result(sig);
+    }
+
+    /// Calls the builtin function `index`, whose arguments after the
+    /// `VMContext` have already been set up.
+    fn call_builtin(&mut self, index: BuiltinFunctionIndex) {
+        let builtins = disp(self.offsets.vmctx_builtin_functions());
+        self.asm.load(Size::S64, RDI, VMCTX);
+        self.asm.load(Size::S64, RAX, Mem::base(RDI, builtins));
+        let offset = disp(index.index() * u32::from(self.isa.pointer_bytes()));
+        self.asm.call(Mem::base(RAX, offset).into());
+    }
+
+    // Globals and memories.
+
+    /// Returns the location of the value of `global`.
+    fn global(&mut self, global: GlobalIndex) -> Mem {
+        self.asm.load(Size::S64, R10, VMCTX);
+        match self.module.defined_global_index(global) {
+            Some(def) => match self.module.defined_global_slot(def) {
+                GlobalSlot::VMContext(slot) => {
+                    Mem::base(R10, disp(self.offsets.vmctx_vmglobal_definition(slot)))
+                }
+                GlobalSlot::Shared(slot) => {
+                    let globals = disp(self.offsets.vmctx_shared_globals());
+                    self.asm.load(Size::S64, R10, Mem::base(R10, globals));
+                    Mem::base(R10, disp(self.offsets.shared_vmglobal_definition(slot)))
+                }
+            },
+            None => {
+                let from = disp(self.offsets.vmctx_vmglobal_import_from(global));
+                self.asm.load(Size::S64, R10, Mem::base(R10, from));
+                Mem::base(R10, 0)
+            }
+        }
+    }
+
+    /// Loads the address of the `VMMemoryDefinition` of `memory` into a
+    /// register, returning it along with the offsets of the memory's base and
+    /// current length from it.
+    fn memory(&mut self, memory: MemoryIndex) -> (Gpr, i32, i32) {
+        self.asm.load(Size::S64, R10, VMCTX);
+        match self.module.defined_memory_index(memory) {
+            Some(def) => (
+                R10,
+                disp(self.offsets.vmctx_vmmemory_definition_base(def)),
+                disp(self.offsets.vmctx_vmmemory_definition_current_length(def)),
+            ),
+            None => {
+                let from = disp(self.offsets.vmctx_vmmemory_import_from(memory));
+                self.asm.load(Size::S64, R10, Mem::base(R10, from));
+                (
+                    R10,
+                    disp(self.offsets.vmmemory_definition_base()),
+                    disp(self.offsets.vmmemory_definition_current_length()),
+                )
+            }
+        }
+    }
+
+    /// Returns the address of an access of `size` bytes by `memarg`, whose
+    /// dynamic index has been loaded into `rax`, trapping if it's out of
+    /// bounds.
+    ///
+    /// Like Cranelift, bounds checks are omitted when the access is known to
+    /// land in the memory's reservation or its guard pages.
+    fn heap_addr(&mut self, memarg: MemoryImmediate, size: u32) -> Mem {
+        let memory = MemoryIndex::from_u32(memarg.memory);
+        let (vmmemory, base, current_length) = self.memory(memory);
+        let end = memarg.offset + u64::from(size);
+        let static_bound = match self.module.memory_plans[memory] {
+            MemoryPlan {
+                style: MemoryStyle::Static { bound },
+                offset_guard_size,
+                ..
+            } => {
+                let bound = bound * u64::from(WASM_PAGE_SIZE);
+                if u64::from(u32::MAX) + end <= bound + offset_guard_size
+                    && i32::try_from(memarg.offset).is_ok()
+                {
+                    self.asm.load(Size::S64, RDX, Mem::base(vmmemory, base));
+                    return Mem::index(RDX, RAX, 1, memarg.offset as i32);
+                }
+                Some(bound)
+            }
+            MemoryPlan {
+                style: MemoryStyle::Dynamic { .. },
+                ..
+            } => None,
+        };
+
+        // Check that the end of the access, in `r11`, is within bounds.
+        match i32::try_from(end) {
+            Ok(end) => self.asm.lea(R11, Mem::base(RAX, end)),
+            Err(_) => {
+                self.asm.mov_imm(R11, end);
+                self.asm.alu(Alu::Add, Size::S64, R11, RAX.into());
+            }
+        }
+        self.asm.load(Size::S64, RDX, Mem::base(vmmemory, base));
+        self.asm.lea(RDX, Mem::index(RDX, RAX, 1, 0));
+        match static_bound {
+            Some(bound) => {
+                self.asm.mov_imm(R10, bound);
+                self.asm.alu(Alu::Cmp, Size::S64, R11, R10.into());
+            }
+            None => {
+                let current_length = Mem::base(vmmemory, current_length);
+                self.asm
+                    .alu(Alu::Cmp, Size::S64, R11, current_length.into());
+            }
+        }
+        let out_of_bounds = self.trap_label(TrapCode::HeapOutOfBounds);
+        self.asm.jcc(Cc::A, out_of_bounds);
+        if self.isa.flags().enable_heap_access_spectre_mitigation() {
+            // Accesses which are speculatively out of bounds go to address
+            // zero instead.
+            self.asm.mov_imm(R10, 0);
+            self.asm.cmov(Cc::A, Size::S64, RDX, R10.into());
+        }
+        match i32::try_from(memarg.offset) {
+            Ok(offset) => Mem::base(RDX, offset),
+            Err(_) => {
+                self.asm.mov_imm(R10, memarg.offset);
+                self.asm.alu(Alu::Add, Size::S64, RDX, R10.into());
+                Mem::base(RDX, 0)
+            }
+        }
+    }
+
+    fn load(&mut self, memarg: MemoryImmediate, size: u32, load: Load) {
+        let index = self.pop();
+        self.asm.load(Size::S32, RAX, index);
+        let addr = self.heap_addr(memarg, size);
+        let at = self.asm.offset();
+        self.trap_at(at, TrapCode::HeapOutOfBounds);
+        match load {
+            Load::I32 => self.asm.load(Size::S32, RAX, addr),
+            Load::I64 => self.asm.load(Size::S64, RAX, addr),
+            Load::U8 => self.asm.movzx(8, RAX, addr.into()),
+            Load::U16 => self.asm.movzx(16, RAX, addr.into()),
+            Load::S8(size) => self.asm.movsx(size, 8, RAX, addr.into()),
+            Load::S16(size) => self.asm.movsx(size, 16, RAX, addr.into()),
+            Load::S32 => self.asm.movsx(Size::S64, 32, RAX, addr.into()),
+        }
+        self.push_gpr(RAX);
+    }
+
+    fn store(&mut self, memarg: MemoryImmediate, bits: u8) {
+        let value = self.pop();
+        let index = self.pop();
+        self.asm.load(Size::S64, RCX, value);
+        self.asm.load(Size::S32, RAX, index);
+        let addr = self.heap_addr(memarg, u32::from(bits / 8));
+        let at = self.asm.offset();
+        self.trap_at(at, TrapCode::HeapOutOfBounds);
+        self.asm.store(bits, addr, RCX);
+    }
+
+    // Arithmetic.
+
+    fn const64(&mut self, value: u64) {
+        let slot = self.push();
+        match i32::try_from(value as i64) {
+            Ok(value) => self.asm.store_imm(Size::S64, slot, value),
+            Err(_) => {
+                self.asm.mov_imm(RAX, value);
+                self.asm.store(64, slot, RAX);
+            }
+        }
+    }
+
+    /// Applies `emit` to the operand, which is loaded into `rax`, leaving the
+    /// result in `rax`.
+    fn int_unop(&mut self, size: Size, emit: impl FnOnce(&mut Assembler)) {
+        let a = self.pop();
+        self.asm.load(size, RAX, a);
+        emit(&mut self.asm);
+        self.push_gpr(RAX);
+    }
+
+    /// Applies `emit` to the operands, the first of which is loaded into
+    /// `rax`, leaving the result in `rax`.
+    fn int_binop(&mut self, size: Size, emit: impl FnOnce(&mut Assembler, Mem)) {
+        let b = self.pop();
+        let a = self.pop();
+        self.asm.load(size, RAX, a);
+        emit(&mut self.asm, b);
+        self.push_gpr(RAX);
+    }
+
+    fn alu(&mut self, op: Alu, size: Size) {
+        self.int_binop(size, |asm, b| asm.alu(op, size, RAX, b.into()));
+    }
+
+    fn compare(&mut self, size: Size, cc: Cc) {
+        self.int_binop(size, |asm, b| {
+            asm.alu(Alu::Cmp, size, RAX, b.into());
+            asm.setcc(cc, RAX);
+            asm.movzx(8, RAX, RAX.into());
+        });
+    }
+
+    fn shift(&mut self, shift: Shift, size: Size) {
+        // The shift amount is masked to the width of the operation, just like
+        // in wasm.
+        self.int_binop(size, |asm, b| {
+            asm.load(Size::S32, RCX, b);
+            asm.shift_cl(shift, size, RAX);
+        });
+    }
+
+    fn clz(&mut self, size: Size) {
+        let bits = if size == Size::S32 { 32 } else { 64 };
+        self.int_unop(size, |asm| {
+            // `bsr` finds the index of the highest set bit, and leaves its
+            // destination undefined for zero, which is handled so that the
+            // final xor produces the width.
+            asm.mov_imm(RCX, 2 * bits - 1);
+            asm.bsr(size, RAX, RAX.into());
+            asm.cmov(Cc::E, size, RAX, RCX.into());
+            asm.alu_imm(Alu::Xor, size, RAX.into(), bits as i32 - 1);
+        });
+    }
+
+    fn ctz(&mut self, size: Size) {
+        let bits = if size == Size::S32 { 32 } else { 64 };
+        self.int_unop(size, |asm| {
+            asm.mov_imm(RCX, bits);
+            asm.bsf(size, RAX, RAX.into());
+            asm.cmov(Cc::E, size, RAX, RCX.into());
+        });
+    }
+
+    /// Divides with explicit checks for the cases which trap in wasm, since
+    /// those would raise a different signal in hardware.
+    fn div(&mut self, size: Size, signed: bool, rem: bool) {
+        let b = self.pop();
+        let a = self.pop();
+        self.asm.load(size, RCX, b);
+        self.asm.load(size, RAX, a);
+        self.asm.test(size, RCX, RCX);
+        let zero = self.trap_label(TrapCode::IntegerDivisionByZero);
+        self.asm.jcc(Cc::E, zero);
+        let done = self.asm.new_label();
+        if signed {
+            let normal = self.asm.new_label();
+            self.asm.alu_imm(Alu::Cmp, size, RCX.into(), -1);
+            self.asm.jcc(Cc::NE, normal);
+            if rem {
+                // The remainder of a division by -1 is always zero, including
+                // for the minimum value, whose quotient overflows.
+                self.asm.alu(Alu::Xor, Size::S32, RDX, RDX.into());
+                self.asm.jmp(done);
+            } else {
+                match size {
+                    Size::S32 => self.asm.alu_imm(Alu::Cmp, size, RAX.into(), i32::MIN),
+                    Size::S64 => {
+                        self.asm.mov_imm(RDX, i64::MIN as u64);
+                        self.asm.alu(Alu::Cmp, size, RAX, RDX.into());
+                    }
+                }
+                let overflow = self.trap_label(TrapCode::IntegerOverflow);
+                self.asm.jcc(Cc::E, overflow);
+            }
+            self.asm.bind(normal);
+            self.asm.sign_extend_rax(size);
+        } else {
+            self.asm.alu(Alu::Xor, Size::S32, RDX, RDX.into());
+        }
+        self.asm.div(size, signed, RCX);
+        self.asm.bind(done);
+        let result = if rem { RDX } else { RAX };
+        self.push_gpr(result);
+    }
+
+    fn float_binop(&mut self, op: Sse, ty: Float) {
+        let b = self.pop();
+        let a = self.pop();
+        self.asm.movs_load(ty, XMM0, a);
+        self.asm.sse(op, ty, XMM0, b.into());
+        self.push_xmm(ty);
+    }
+
+    /// Applies `emit` to the operand, leaving the result of type `to` in
+    /// `xmm0`.
+    fn float_unop(&mut self, to: Float, emit: impl FnOnce(&mut Assembler, Mem)) {
+        let a = self.pop();
+        emit(&mut self.asm, a);
+        self.push_xmm(to);
+    }
+
+    /// Converts an integer to a float, where unsigned 32-bit integers are
+    /// converted from their zero-extended 64-bit value.
+    fn convert(&mut self, ty: Float, size: Size) {
+        let a = self.pop();
+        self.asm.load(size, RAX, a);
+        self.asm.cvtsi2s(ty, size, XMM0, RAX.into());
+        self.push_xmm(ty);
+    }
+
+    /// Compares two floats with `ucomis`, where `cc` is an unsigned condition
+    /// which is false for unordered operands, other than for equality.
+    fn float_compare(&mut self, ty: Float, cc: Cc, swap: bool) {
+        let b = self.pop();
+        let a = self.pop();
+        let (a, b) = if swap { (b, a) } else { (a, b) };
+        self.asm.movs_load(ty, XMM0, a);
+        self.asm.ucomis(ty, XMM0, b.into());
+        match cc {
+            // Unordered operands set the zero flag as well as the parity flag.
+            Cc::E => {
+                self.asm.setcc(Cc::E, RAX);
+                self.asm.setcc(Cc::NP, RCX);
+                self.asm.alu(Alu::And, Size::S32, RAX, RCX.into());
+            }
+            Cc::NE => {
+                self.asm.setcc(Cc::NE, RAX);
+                self.asm.setcc(Cc::P, RCX);
+                self.asm.alu(Alu::Or, Size::S32, RAX, RCX.into());
+            }
+            _ => self.asm.setcc(cc, RAX),
+        }
+        self.asm.movzx(8, RAX, RAX.into());
+        self.push_gpr(RAX);
+    }
+
+    /// Computes the minimum or maximum of two floats, where NaNs propagate
+    /// and negative zero is less than positive zero, unlike with `minss` and
+    /// friends.
+    fn min_max(&mut self, ty: Float, op: Sse) {
+        let b = self.pop();
+        let a = self.pop();
+        let (size, bitwise) = match (ty, op) {
+            (Float::F32, Sse::Min) => (Size::S32, Alu::Or),
+            (Float::F32, _) => (Size::S32, Alu::And),
+            (Float::F64, Sse::Min) => (Size::S64, Alu::Or),
+            (Float::F64, _) => (Size::S64, Alu::And),
+        };
+        let nan = self.asm.new_label();
+        let ordered = self.asm.new_label();
+        let done = self.asm.new_label();
+        self.asm.movs_load(ty, XMM0, a);
+        self.asm.ucomis(ty, XMM0, b.into());
+        self.asm.jcc(Cc::P, nan);
+        self.asm.jcc(Cc::NE, ordered);
+        // Equal values only differ in the sign of zeros, which is the sign of
+        // either for the minimum and of both for the maximum.
+        self.asm.load(size, RAX, a);
+        self.asm.alu(bitwise, size, RAX, b.into());
+        self.asm.store(64, a, RAX);
+        self.asm.jmp(done);
+        self.asm.bind(ordered);
+        self.asm.sse(op, ty, XMM0, b.into());
+        self.asm.movs_store(ty, a, XMM0);
+        self.asm.jmp(done);
+        self.asm.bind(nan);
+        self.asm.sse(Sse::Add, ty, XMM0, b.into());
+        self.asm.movs_store(ty, a, XMM0);
+        self.asm.bind(done);
+        self.push();
+    }
+
+    /// Rounds a float to an integral value with an SSE4.1 rounding `mode`.
+    fn round(&mut self, ty: Float, mode: u8) {
+        self.float_unop(ty, |asm, a| asm.round(ty, mode, XMM0, a.into()));
+    }
+
+    /// Stores the float `value` of type `ty` to `slot`, to be used as an
+    /// operand.
+    fn float_constant(&mut self, ty: Float, slot: Mem, value: f64) -> Mem {
+        match ty {
+            Float::F32 => self
+                .asm
+                .store_imm(Size::S32, slot, (value as f32).to_bits() as i32),
+            Float::F64 => {
+                self.asm.mov_imm(RCX, value.to_bits());
+                self.asm.store(64, slot, RCX);
+            }
+        }
+        slot
+    }
+
+    fn convert_u64(&mut self, ty: Float) {
+        let a = self.pop();
+        let large = self.asm.new_label();
+        let done = self.asm.new_label();
+        self.asm.load(Size::S64, RAX, a);
+        self.asm.test(Size::S64, RAX, RAX);
+        self.asm.jcc(Cc::S, large);
+        self.asm.cvtsi2s(ty, Size::S64, XMM0, RAX.into());
+        self.asm.jmp(done);
+        // Values with the top bit set are halved, keeping the lowest bit so
+        // that they still round correctly, and then doubled.
+        self.asm.bind(large);
+        self.asm.mov_rr(Size::S64, RCX, RAX);
+        self.asm.shift_imm(Shift::Shr, Size::S64, RCX, 1);
+        self.asm.alu_imm(Alu::And, Size::S32, RAX.into(), 1);
+        self.asm.alu(Alu::Or, Size::S64, RCX, RAX.into());
+        self.asm.cvtsi2s(ty, Size::S64, XMM0, RCX.into());
+        self.asm.sse(Sse::Add, ty, XMM0, XMM0.into());
+        self.asm.bind(done);
+        self.push_xmm(ty);
+    }
+
+    /// Truncates a float to a signed integer of `size`, either trapping or
+    /// saturating when it's out of range.
+    fn trunc_signed(&mut self, ty: Float, size: Size, saturating: bool) {
+        let a = self.pop();
+        let done = self.asm.new_label();
+        self.asm.movs_load(ty, XMM0, a);
+        self.asm.cvtts2si(ty, size, RAX, XMM0.into());
+
+        // Out of range values and NaNs produce the minimum integer, which is
+        // otherwise only produced by values just above the lower bound.
+        match size {
+            Size::S32 => self.asm.alu_imm(Alu::Cmp, size, RAX.into(), i32::MIN),
+            Size::S64 => {
+                self.asm.mov_imm(RCX, i64::MIN as u64);
+                self.asm.alu(Alu::Cmp, size, RAX, RCX.into());
+            }
+        }
+        self.asm.jcc(Cc::NE, done);
+        self.asm.ucomis(ty, XMM0, XMM0.into());
+        if saturating {
+            let nan = self.asm.new_label();
+            self.asm.jcc(Cc::P, nan);
+            let zero = self.float_constant(ty, a, 0.0);
+            self.asm.ucomis(ty, XMM0, zero.into());
+            self.asm.jcc(Cc::B, done);
+            let max = match size {
+                Size::S32 => i32::MAX as u64,
+                Size::S64 => i64::MAX as u64,
+            };
+            self.asm.mov_imm(RAX, max);
+            self.asm.jmp(done);
+            self.asm.bind(nan);
+            self.asm.alu(Alu::Xor, Size::S32, RAX, RAX.into());
+        } else {
+            let bad_conversion = self.trap_label(TrapCode::BadConversionToInteger);
+            self.asm.jcc(Cc::P, bad_conversion);
+            let overflow = self.trap_label(TrapCode::IntegerOverflow);
+            // The lower bound is exclusive if it's exactly representable
+            // but would be truncated to an integer out of range.
+            let (lower, below) = match (ty, size) {
+                (Float::F64, Size::S32) => (-2147483649.0, Cc::BE),
+                (_, Size::S32) => (-2147483648.0, Cc::B),
+                (_, Size::S64) => (-9223372036854775808.0, Cc::B),
+            };
+            let lower = self.float_constant(ty, a, lower);
+            self.asm.ucomis(ty, XMM0, lower.into());
+            self.asm.jcc(below, overflow);
+            let zero = self.float_constant(ty, a, 0.0);
+            self.asm.ucomis(ty, XMM0, zero.into());
+            self.asm.jcc(Cc::AE, overflow);
+        }
+        self.asm.bind(done);
+        self.push_gpr(RAX);
+    }
+
+    /// Truncates a float to an unsigned 32-bit integer, which is done through
+    /// a signed 64-bit conversion.
+    fn trunc_u32(&mut self, ty: Float, saturating: bool) {
+        let a = self.pop();
+        self.asm.movs_load(ty, XMM0, a);
+        if saturating {
+            let zero = self.asm.new_label();
+            let done = self.asm.new_label();
+            // NaNs and values at most zero produce zero.
+            let constant = self.float_constant(ty, a, 0.0);
+            self.asm.ucomis(ty, XMM0, constant.into());
+            self.asm.jcc(Cc::BE, zero);
+            self.asm.cvtts2si(ty, Size::S64, RAX, XMM0.into());
+            self.asm.mov_imm(RCX, u32::MAX.into());
+            self.asm.test(Size::S64, RAX, RAX);
+            self.asm.cmov(Cc::S, Size::S64, RAX, RCX.into());
+            self.asm.alu(Alu::Cmp, Size::S64, RAX, RCX.into());
+            self.asm.cmov(Cc::A, Size::S64, RAX, RCX.into());
+            self.asm.jmp(done);
+            self.asm.bind(zero);
+            self.asm.alu(Alu::Xor, Size::S32, RAX, RAX.into());
+            self.asm.bind(done);
+        } else {
+            self.asm.ucomis(ty, XMM0, XMM0.into());
+            let bad_conversion = self.trap_label(TrapCode::BadConversionToInteger);
+            self.asm.jcc(Cc::P, bad_conversion);
+            self.asm.cvtts2si(ty, Size::S64, RAX, XMM0.into());
+            self.asm.mov_rr(Size::S64, RCX, RAX);
+            self.asm.shift_imm(Shift::Shr, Size::S64, RCX, 32);
+            let overflow = self.trap_label(TrapCode::IntegerOverflow);
+            self.asm.jcc(Cc::NE, overflow);
+        }
+        self.push_gpr(RAX);
+    }
+
+    /// Truncates a float to an unsigned 64-bit integer, where values of at
+    /// least 2^63 are converted after subtracting 2^63.
+    fn trunc_u64(&mut self, ty: Float, saturating: bool) {
+        let a = self.pop();
+        let large = self.asm.new_label();
+        let done = self.asm.new_label();
+        let (out_of_range, zero) = if saturating {
+            (self.asm.new_label(), Some(self.asm.new_label()))
+        } else {
+            (self.trap_label(TrapCode::IntegerOverflow), None)
+        };
+        self.asm.movs_load(ty, XMM0, a);
+        match zero {
+            // NaNs and values at most zero produce zero.
+            Some(zero) => {
+                let constant = self.float_constant(ty, a, 0.0);
+                self.asm.ucomis(ty, XMM0, constant.into());
+                self.asm.jcc(Cc::BE, zero);
+            }
+            None => {
+                self.asm.ucomis(ty, XMM0, XMM0.into());
+                let bad_conversion = self.trap_label(TrapCode::BadConversionToInteger);
+                self.asm.jcc(Cc::P, bad_conversion);
+            }
+        }
+        let threshold = self.float_constant(ty, a, 9223372036854775808.0);
+        self.asm.ucomis(ty, XMM0, threshold.into());
+        self.asm.jcc(Cc::AE, large);
+        self.asm.cvtts2si(ty, Size::S64, RAX, XMM0.into());
+        if !saturating {
+            // Negative values which don't truncate to zero are out of range.
+            self.asm.test(Size::S64, RAX, RAX);
+            self.asm.jcc(Cc::S, out_of_range);
+        }
+        self.asm.jmp(done);
+
+        self.asm.bind(large);
+        self.asm.sse(Sse::Sub, ty, XMM0, threshold.into());
+        self.asm.cvtts2si(ty, Size::S64, RAX, XMM0.into());
+        self.asm.test(Size::S64, RAX, RAX);
+        self.asm.jcc(Cc::S, out_of_range);
+        self.asm.mov_imm(RCX, 1 << 63);
+        self.asm.alu(Alu::Xor, Size::S64, RAX, RCX.into());
+        if let Some(zero) = zero {
+            self.asm.jmp(done);
+            self.asm.bind(out_of_range);
+            self.asm.mov_imm(RAX, u64::MAX);
+            self.asm.jmp(done);
+            self.asm.bind(zero);
+            self.asm.alu(Alu::Xor, Size::S32, RAX, RAX.into());
+        }
+        self.asm.bind(done);
+        self.push_gpr(RAX);
+    }
+}
@@ -3,17 +3,27 @@
 //! This module contains the implementation of how Cranelift is configured, as
 //! well as providing a function to return the default configuration to build.
 
-use anyhow::Result;
+use anyhow::{bail, Result};
 use cranelift_codegen::isa;
 use cranelift_codegen::settings::{self, Configurable, SetError};
 use std::fmt;
-use wasmtime_environ::{CompilerBuilder, Setting, SettingKind};
+use std::path::PathBuf;
+use std::sync::Arc;
+use wasmtime_environ::{CompilerBuilder, FunctionHook, Setting, SettingKind};
 
 #[derive(Clone)]
 struct Builder {
     flags: settings::Builder,
     isa_flags: isa::Builder,
     linkopts: LinkOptions,
+    /// Whether functions are compiled with the baseline compiler, falling back
+    /// to Cranelift for the ones it doesn't support.
+    baseline: bool,
+    /// A directory to write each function's CLIF to, along with its
+    /// relocations, traps and unwind info, when it's compiled.
+    clif_dir: Option<PathBuf>,
+    /// What each function is reported to after it's compiled, if anything.
+    function_hook: Option<Arc<dyn FunctionHook>>,
 }
 
 #[derive(Clone, Default)]
@@ -47,6 +57,9 @@ pub fn builder() -> Box<dyn CompilerBuilder> {
         flags,
         isa_flags: cranelift_native::builder().expect("host machine is not a supported target"),
         linkopts: LinkOptions::default(),
+        baseline: false,
+        clif_dir: None,
+        function_hook: None,
     })
 }
 
@@ -74,6 +87,14 @@ impl CompilerBuilder for Builder {
             self.linkopts.force_jump_veneers = value.parse()?;
             return Ok(());
         }
+        if name == "wasmtime_baseline" {
+            self.baseline = value.parse()?;
+            return Ok(());
+        }
+        if name == "wasmtime_clif_dir" {
+            self.clif_dir = Some(value.into());
+            return Ok(());
+        }
 
         // ... then forward this to Cranelift
         if let Err(err) = self.flags.set(name, value) {
@@ -101,14 +122,24 @@ impl CompilerBuilder for Builder {
         Ok(())
     }
 
+    fn function_hook(&mut self, hook: Arc<dyn FunctionHook>) {
+        self.function_hook = Some(hook);
+    }
+
     fn build(&self) -> Result<Box<dyn wasmtime_environ::Compiler>> {
         let isa = self
             .isa_flags
             .clone()
             .finish(settings::Flags::new(self.flags.clone()))?;
+        if self.baseline && !crate::baseline::supports_isa(&*isa) {
+            bail!("the baseline compiler only supports x86_64 System V targets");
+        }
         Ok(Box::new(crate::compiler::Compiler::new(
             isa,
             self.linkopts.clone(),
+            self.baseline,
+            self.clif_dir.clone(),
+            self.function_hook.clone(),
         )))
     }
 
@@ -137,6 +168,7 @@ impl fmt::Debug for Builder {
                 "flags",
                 &settings::Flags::new(self.flags.clone()).to_string(),
             )
+            .field("baseline", &self.baseline)
             .finish()
     }
 }
@@ -4,48 +4,96 @@ use crate::func_environ::{get_func_name, FuncEnvironment};
 use crate::obj::ObjectBuilder;
 use crate::{
     blank_sig, func_signature, indirect_signature, value_type, wasmtime_call_conv,
-    CompiledFunction, FunctionAddressMap, Relocation, RelocationTarget,
+    wasmtime_trap_code, CompiledFunction, FunctionAddressMap, Relocation, RelocationTarget,
 };
 use anyhow::{Context as _, Result};
+use cranelift_codegen::binemit::{Addend, CodeOffset, CodeSink, Reloc, StackMap};
 use cranelift_codegen::ir::{self, ExternalName, InstBuilder, MemFlags};
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::print_errors::pretty_error;
+use cranelift_codegen::settings;
 use cranelift_codegen::Context;
-use cranelift_codegen::{settings, MachReloc, MachTrap};
-use cranelift_codegen::{MachSrcLoc, MachStackMap};
+use cranelift_codegen::MachSrcLoc;
 use cranelift_entity::{EntityRef, PrimaryMap};
 use cranelift_frontend::FunctionBuilder;
 use cranelift_wasm::{
     DefinedFuncIndex, DefinedMemoryIndex, FuncIndex, FuncTranslator, MemoryIndex, SignatureIndex,
-    WasmFuncType,
+    WasmError, WasmFuncType, WasmType,
 };
 use object::write::Object;
 use std::any::Any;
 use std::cmp;
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
+use std::fmt::Write as _;
 use std::mem;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
 use wasmtime_environ::{
-    AddressMapSection, CompileError, FilePos, FlagValue, FunctionBodyData, FunctionInfo,
-    InstructionAddressMap, Module, ModuleTranslation, StackMapInformation, Trampoline, TrapCode,
+    AddressMapSection, CompileError, EmittedCode, FilePos, FlagValue, FunctionBodyData,
+    FunctionHook, FunctionInfo, FunctionListing, FunctionProfile, InstructionAddressMap,
+    LazyFunctionTable, Module, ModuleTranslation, StackMapInformation, Trampoline,
     TrapEncodingBuilder, TrapInformation, Tunables, TypeTables, VMOffsets,
 };
 
+/// The trampolines the host calls the wasm functions of one signature through,
+/// passed from `compile_host_to_wasm_trampoline` to `emit_obj`.
+struct HostToWasmTrampolines {
+    /// The trampoline passing arguments and results through a `ValRaw` array.
+    array: CompiledFunction,
+    /// The trampoline taking arguments natively, which is only compiled when
+    /// the context is pinned and calls can't go to wasm functions directly.
+    native: Option<CompiledFunction>,
+}
+
 /// A compiler that compiles a WebAssembly module with Compiler, translating
 /// the Wasm to Compiler IR, optimizing it and then translating to assembly.
 pub(crate) struct Compiler {
     translators: Mutex<Vec<FuncTranslator>>,
     isa: Box<dyn TargetIsa>,
     linkopts: LinkOptions,
+    baseline: bool,
+    clif_dir: Option<PathBuf>,
+    function_hook: Option<Arc<dyn FunctionHook>>,
 }
 
 impl Compiler {
-    pub(crate) fn new(isa: Box<dyn TargetIsa>, linkopts: LinkOptions) -> Compiler {
+    pub(crate) fn new(
+        isa: Box<dyn TargetIsa>,
+        linkopts: LinkOptions,
+        baseline: bool,
+        clif_dir: Option<PathBuf>,
+        function_hook: Option<Arc<dyn FunctionHook>>,
+    ) -> Compiler {
         Compiler {
             translators: Default::default(),
             isa,
             linkopts,
+            baseline,
+            clif_dir,
+            function_hook,
+        }
+    }
+
+    /// Returns the function hook if it wants to be told about the function
+    /// `index` named `name`.
+    fn function_hook(&self, index: FuncIndex, name: Option<&str>) -> Option<&dyn FunctionHook> {
+        self.function_hook
+            .as_deref()
+            .filter(|hook| hook.wants(index, name))
+    }
+
+    /// Reports a function compiled by the baseline compiler, which has no IR
+    /// or disassembly, to the function hook if it wants it.
+    fn report_baseline(&self, index: FuncIndex, name: Option<&str>, func: &CompiledFunction) {
+        if let Some(hook) = self.function_hook(index, name) {
+            hook.compiled(&FunctionListing {
+                index,
+                name,
+                ir: None,
+                code: &func.body,
+                disassembly: None,
+            });
         }
     }
 
@@ -100,28 +148,65 @@ impl Compiler {
             body_len,
         }
     }
-}
 
-impl wasmtime_environ::Compiler for Compiler {
-    fn compile_function(
+    /// Compiles the function `func_index` of `module`, named `name`, which
+    /// goes through `lazy_table` to call the module's other functions when
+    /// it's compiled lazily, and whose blocks are laid out according to
+    /// `profile` if it has one.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_function_body(
         &self,
-        translation: &ModuleTranslation<'_>,
-        func_index: DefinedFuncIndex,
+        module: &Module,
+        defined_index: DefinedFuncIndex,
+        name: Option<&str>,
         mut input: FunctionBodyData<'_>,
         tunables: &Tunables,
         types: &TypeTables,
+        lazy_table: Option<&LazyFunctionTable>,
+        profile: Option<&FunctionProfile>,
     ) -> Result<Box<dyn Any + Send>, CompileError> {
         let isa = &*self.isa;
-        let module = &translation.module;
-        let func_index = module.func_index(func_index);
+        let func_index = module.func_index(defined_index);
+        let hook = self.function_hook(func_index, name);
         let mut context = Context::new();
         context.func.name = get_func_name(func_index);
-        context.func.signature = func_signature(isa, translation, types, func_index);
+        context.func.signature = func_signature(isa, module, types, func_index);
+        context.set_disasm(hook.is_some());
         if tunables.generate_native_debuginfo {
             context.func.collect_debug_info();
         }
 
-        let mut func_env = FuncEnvironment::new(isa, translation, types, tunables);
+        let mut func_env = FuncEnvironment::new(isa, module, types, tunables);
+        func_env.set_func_index(func_index);
+        if tunables.check_integer_overflow {
+            let allowed = name.map_or(false, |name| {
+                tunables.integer_overflow_allowlist.contains(name)
+            });
+            func_env.set_check_integer_overflow(!allowed);
+        }
+        if let Some(table) = lazy_table {
+            func_env.set_lazy_table(table);
+            if let Some(counters) = &table.profile {
+                func_env.set_profile_counters(counters[defined_index]);
+            }
+        }
+        if let Some(profile) = profile {
+            func_env.set_profile(profile);
+        }
+        if tunables.guest_debug || tunables.coredump_frames {
+            let mut locals = types[module.functions[func_index].signature]
+                .params()
+                .to_vec();
+            let mut reader = input.body.get_locals_reader().map_err(WasmError::from)?;
+            for _ in 0..reader.get_count() {
+                let (count, ty) = reader.read().map_err(WasmError::from)?;
+                let ty = WasmType::try_from(ty)?;
+                locals.extend(std::iter::repeat(ty).take(count as usize));
+            }
+            func_env.set_debug_locals(locals);
+            let func_start = input.body.get_binary_reader().original_position();
+            func_env.set_func_start(u32::try_from(func_start).unwrap());
+        }
 
         // We use these as constant offsets below in
         // `stack_limit_from_arguments`, so assert their values here. This
@@ -155,7 +240,12 @@ impl wasmtime_environ::Compiler for Compiler {
             global_type: isa.pointer_type(),
             readonly: false,
         });
-        context.func.stack_limit = Some(stack_limit);
+        let stack_check_slot = if tunables.signals_based_traps {
+            context.func.stack_limit = Some(stack_limit);
+            None
+        } else {
+            Some(func_env.create_stack_check_slot(&mut context.func))
+        };
         let mut func_translator = self.take_translator();
         func_translator.translate_body(
             &mut input.validator,
@@ -164,29 +254,46 @@ impl wasmtime_environ::Compiler for Compiler {
             &mut func_env,
         )?;
         self.save_translator(func_translator);
+        let clif = self
+            .clif_dir
+            .as_ref()
+            .map(|_| context.func.display().to_string());
+
+        // Without signals-based traps, legalize the function up front so that
+        // bounds checks are expanded into trap instructions, and then replace
+        // all of its traps with calls into the runtime.
+        if let Some(slot) = stack_check_slot {
+            func_env.insert_stack_check(&mut context.func, slot, stack_limit);
+            context.compute_cfg();
+            context
+                .legalize(isa)
+                .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
+            func_env.convert_traps_to_calls(&mut context.func)?;
+            context.compute_cfg();
+        }
 
-        let mut code_buf: Vec<u8> = Vec::new();
+        let mut sink = FunctionSink::default();
         context
-            .compile_and_emit(isa, &mut code_buf)
+            .compile_with_sink(isa, &mut sink)
             .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
-
-        let result = context.mach_compile_result.as_ref().unwrap();
-
-        let func_relocs = result
-            .buffer
-            .relocs()
-            .into_iter()
-            .map(mach_reloc_to_reloc)
-            .collect::<Vec<_>>();
-
-        let traps = result
-            .buffer
-            .traps()
-            .into_iter()
-            .map(mach_trap_to_trap)
-            .collect::<Vec<_>>();
-
-        let stack_maps = mach_stack_maps_to_stack_maps(result.buffer.stack_maps());
+        let FunctionSink {
+            body: code_buf,
+            relocations: func_relocs,
+            traps,
+            stack_maps,
+        } = sink.finish();
+
+        if let Some(hook) = hook {
+            let ir = context.func.display().to_string();
+            let result = context.mach_compile_result.as_ref().unwrap();
+            hook.compiled(&FunctionListing {
+                index: func_index,
+                name,
+                ir: Some(&ir),
+                code: &code_buf,
+                disassembly: result.disasm.as_deref(),
+            });
+        }
 
         let unwind_info = if isa.flags().unwind_info() {
             context
@@ -216,56 +323,185 @@ impl wasmtime_environ::Compiler for Compiler {
         log::debug!("{:?} translated in {:?}", func_index, timing.total());
         log::trace!("{:?} timing info\n{}", func_index, timing);
 
+        if let (Some(dir), Some(clif)) = (&self.clif_dir, clif) {
+            write_clif(dir, func_index, clif, &func_relocs, &traps, &unwind_info)?;
+        }
+
         let length = u32::try_from(code_buf.len()).unwrap();
+        let frame_size = context.mach_compile_result.as_ref().unwrap().frame_size;
+        let wasm_size = u32::try_from(input.body.range().end - input.body.range().start).unwrap();
         Ok(Box::new(CompiledFunction {
             body: code_buf,
             relocations: func_relocs,
             value_labels_ranges: ranges.unwrap_or(Default::default()),
             stack_slots: context.func.stack_slots,
             unwind_info,
+            baseline_unwind_info: false,
             traps,
             info: FunctionInfo {
                 start_srcloc: address_transform.start_srcloc,
                 stack_maps,
                 start: 0,
                 length,
+                frame_size,
+                wasm_size,
+                compile_time: None,
             },
             address_map: address_transform,
         }))
     }
+}
+
+impl wasmtime_environ::Compiler for Compiler {
+    fn compile_function(
+        &self,
+        translation: &ModuleTranslation<'_>,
+        func_index: DefinedFuncIndex,
+        mut input: FunctionBodyData<'_>,
+        tunables: &Tunables,
+        types: &TypeTables,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        let module = &translation.module;
+        let name = translation
+            .debuginfo
+            .name_section
+            .func_names
+            .get(&module.func_index(func_index))
+            .copied();
+        if self.baseline {
+            if let Some(func) = crate::baseline::compile(
+                &*self.isa, module, func_index, &mut input, tunables, types, None,
+            )? {
+                self.report_baseline(module.func_index(func_index), name, &func);
+                return Ok(Box::new(func));
+            }
+        }
+        self.compile_function_body(
+            module,
+            func_index,
+            name,
+            input,
+            tunables,
+            types,
+            None,
+            translation.profile.get(func_index),
+        )
+    }
+
+    fn compile_lazy_stub(
+        &self,
+        translation: &ModuleTranslation<'_>,
+        func_index: DefinedFuncIndex,
+        tunables: &Tunables,
+        types: &TypeTables,
+        code: usize,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        let isa = &*self.isa;
+        let module = &translation.module;
+        let func_index = module.func_index(func_index);
+        let mut context = Context::new();
+        context.func.name = get_func_name(func_index);
+        context.func.signature = func_signature(isa, module, types, func_index);
+
+        let mut func_env = FuncEnvironment::new(isa, module, types, tunables);
+        func_env.set_func_index(func_index);
+        let mut func_translator = self.take_translator();
+        let mut builder = FunctionBuilder::new(&mut context.func, func_translator.context());
+        func_env.translate_lazy_stub(&mut builder, code);
+        self.save_translator(func_translator);
+
+        // Stubs don't have relocations or traps of their own, so they're
+        // finished like trampolines.
+        Ok(Box::new(self.finish_trampoline(context, isa)?))
+    }
+
+    fn compile_lazy_function(
+        &self,
+        module: &Module,
+        func_index: DefinedFuncIndex,
+        name: Option<&str>,
+        mut input: FunctionBodyData<'_>,
+        tunables: &Tunables,
+        types: &TypeTables,
+        table: &LazyFunctionTable,
+        optimize: bool,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        // With tiered compilation the baseline compiler is the first tier,
+        // where the target supports it, and Cranelift the optimizing one.
+        let isa = &*self.isa;
+        let tiered = table.counters.is_some() && crate::baseline::supports_isa(isa);
+        if (self.baseline || tiered) && !optimize {
+            if let Some(func) = crate::baseline::compile(
+                isa,
+                module,
+                func_index,
+                &mut input,
+                tunables,
+                types,
+                Some(table),
+            )? {
+                self.report_baseline(module.func_index(func_index), name, &func);
+                return Ok(Box::new(func));
+            }
+        }
+        self.compile_function_body(
+            module,
+            func_index,
+            name,
+            input,
+            tunables,
+            types,
+            Some(table),
+            None,
+        )
+    }
+
+    fn compile_host_to_wasm_trampoline(
+        &self,
+        ty: &WasmFuncType,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        let native = if self.isa.flags().enable_pinned_reg() {
+            Some(self.native_host_to_wasm_trampoline(ty)?)
+        } else {
+            None
+        };
+        Ok(Box::new(HostToWasmTrampolines {
+            array: self.host_to_wasm_trampoline(ty)?,
+            native,
+        }))
+    }
 
     fn emit_obj(
         &self,
         translation: &ModuleTranslation,
-        types: &TypeTables,
+        _types: &TypeTables,
         funcs: PrimaryMap<DefinedFuncIndex, Box<dyn Any + Send>>,
+        trampolines: Vec<Box<dyn Any + Send>>,
         tunables: &Tunables,
         obj: &mut Object<'static>,
-    ) -> Result<(PrimaryMap<DefinedFuncIndex, FunctionInfo>, Vec<Trampoline>)> {
+    ) -> Result<EmittedCode> {
         let funcs: crate::CompiledFunctions = funcs
             .into_iter()
             .map(|(_i, f)| *f.downcast().unwrap())
             .collect();
 
-        let mut builder = ObjectBuilder::new(obj, &translation.module, &*self.isa);
+        let mut builder = ObjectBuilder::new(
+            obj,
+            &translation.module,
+            &*self.isa,
+            tunables.devirtualize_imports,
+        );
         if self.linkopts.force_jump_veneers {
             builder.text.force_veneers();
         }
-        let mut addrs = AddressMapSection::default();
-        let mut traps = TrapEncodingBuilder::default();
-        let compiled_trampolines = translation
-            .exported_signatures
-            .iter()
-            .map(|i| self.host_to_wasm_trampoline(&types[*i]))
-            .collect::<Result<Vec<_>, _>>()?;
+        let compiled_trampolines = trampolines
+            .into_iter()
+            .map(|t| *t.downcast::<HostToWasmTrampolines>().unwrap())
+            .collect::<Vec<_>>();
 
         let mut func_starts = Vec::with_capacity(funcs.len());
         for (i, func) in funcs.iter() {
             let range = builder.func(i, func);
-            if tunables.generate_address_map {
-                addrs.push(range.clone(), &func.address_map.instructions);
-            }
-            traps.push(range.clone(), &func.traps);
             func_starts.push(range.start);
             if self.linkopts.padding_between_functions > 0 {
                 builder.text.append(
@@ -275,15 +511,23 @@ impl wasmtime_environ::Compiler for Compiler {
                 );
             }
         }
+        let import_thunks = match tunables.devirtualize_imports {
+            true => builder.import_thunks(),
+            false => Vec::new(),
+        };
 
         // Build trampolines for every signature that can be used by this module.
         let mut trampolines = Vec::with_capacity(translation.exported_signatures.len());
-        for (i, func) in translation
+        for (i, compiled) in translation
             .exported_signatures
             .iter()
             .zip(&compiled_trampolines)
         {
-            trampolines.push(builder.trampoline(*i, &func));
+            let mut trampoline = builder.trampoline(*i, &compiled.array);
+            if let Some(native) = &compiled.native {
+                trampoline.native = Some(builder.native_trampoline(*i, native));
+            }
+            trampolines.push(trampoline);
         }
 
         builder.unwind_info();
@@ -320,22 +564,50 @@ impl wasmtime_environ::Compiler for Compiler {
 
         builder.finish()?;
 
+        let mut code = EmittedCode {
+            funcs: PrimaryMap::with_capacity(funcs.len()),
+            trampolines,
+            import_thunks,
+            traps: PrimaryMap::with_capacity(funcs.len()),
+            address_maps: PrimaryMap::with_capacity(funcs.len()),
+        };
+        for ((_, mut func), start) in funcs.into_iter().zip(func_starts) {
+            func.info.start = start;
+            code.funcs.push(func.info);
+            code.traps.push(func.traps);
+            code.address_maps
+                .push(func.address_map.instructions.into_vec());
+        }
+        Ok(code)
+    }
+
+    fn emit_lazy_function_obj(
+        &self,
+        module: &Module,
+        func_index: DefinedFuncIndex,
+        func: Box<dyn Any + Send>,
+        tunables: &Tunables,
+        obj: &mut Object<'static>,
+    ) -> Result<FunctionInfo> {
+        let func: CompiledFunction = *func.downcast().unwrap();
+        let empty = Module::new();
+        let mut builder = ObjectBuilder::new(obj, &empty, &*self.isa, false);
+        let range = builder.lazy_func(module.func_index(func_index), &func);
+        builder.unwind_info();
+        builder.finish()?;
+
         if tunables.generate_address_map {
+            let mut addrs = AddressMapSection::default();
+            addrs.push(range.clone(), &func.address_map.instructions);
             addrs.append_to(obj);
         }
+        let mut traps = TrapEncodingBuilder::default();
+        traps.push(range.clone(), &func.traps);
         traps.append_to(obj);
 
-        Ok((
-            funcs
-                .into_iter()
-                .zip(func_starts)
-                .map(|((_, mut f), start)| {
-                    f.info.start = start;
-                    f.info
-                })
-                .collect(),
-            trampolines,
-        ))
+        let mut info = func.info;
+        info.start = range.start;
+        Ok(info)
     }
 
     fn emit_trampoline_obj(
@@ -347,7 +619,7 @@ impl wasmtime_environ::Compiler for Compiler {
         let host_to_wasm = self.host_to_wasm_trampoline(ty)?;
         let wasm_to_host = self.wasm_to_host_trampoline(ty, host_fn)?;
         let module = Module::new();
-        let mut builder = ObjectBuilder::new(obj, &module, &*self.isa);
+        let mut builder = ObjectBuilder::new(obj, &module, &*self.isa, false);
         let a = builder.trampoline(SignatureIndex::new(0), &host_to_wasm);
         let b = builder.trampoline(SignatureIndex::new(1), &wasm_to_host);
         builder.unwind_info();
@@ -378,6 +650,10 @@ impl wasmtime_environ::Compiler for Compiler {
             .map(|val| (val.name.to_string(), to_flag_value(val)))
             .collect()
     }
+
+    fn is_baseline(&self) -> bool {
+        self.baseline
+    }
 }
 
 fn to_flag_value(v: &settings::Value) -> FlagValue {
@@ -425,6 +701,7 @@ impl Compiler {
             let params = builder.func.dfg.block_params(block0);
             (params[0], params[1], params[2], params[3])
         };
+        let saved_context = self.pin_context(&mut builder, caller_vmctx_ptr_val);
 
         // Load the argument values out of `values_vec`.
         let mut mflags = ir::MemFlags::trusted();
@@ -464,6 +741,7 @@ impl Compiler {
                 .ins()
                 .store(mflags, *r, values_vec_ptr_val, (i * value_size) as i32);
         }
+        Self::unpin_context(&mut builder, saved_context);
         builder.ins().return_(&[]);
         builder.finalize();
 
@@ -472,6 +750,87 @@ impl Compiler {
         Ok(func)
     }
 
+    /// Compiles the trampoline the host calls a wasm function with the
+    /// signature `ty` through when the context has to be pinned on the way in
+    /// but the arguments are known statically.
+    ///
+    /// This takes the arguments of the callee natively, with the callee's
+    /// code pointer inserted after its two `VMContext` arguments, and returns
+    /// its results natively too.
+    fn native_host_to_wasm_trampoline(
+        &self,
+        ty: &WasmFuncType,
+    ) -> Result<CompiledFunction, CompileError> {
+        let isa = &*self.isa;
+        let wasm_signature = indirect_signature(isa, ty);
+        let mut native_signature = wasm_signature.clone();
+        native_signature
+            .params
+            .insert(2, ir::AbiParam::new(isa.pointer_type()));
+
+        let mut func_translator = self.take_translator();
+        let mut context = Context::new();
+        context.func =
+            ir::Function::with_name_signature(ExternalName::user(0, 0), native_signature);
+
+        let mut builder = FunctionBuilder::new(&mut context.func, func_translator.context());
+        let block0 = builder.create_block();
+
+        builder.append_block_params_for_function_params(block0);
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+
+        let mut callee_args = builder.func.dfg.block_params(block0).to_vec();
+        let callee_value = callee_args.remove(2);
+        let saved_context = self.pin_context(&mut builder, callee_args[1]);
+
+        let new_sig = builder.import_signature(wasm_signature);
+        let call = builder
+            .ins()
+            .call_indirect(new_sig, callee_value, &callee_args);
+        let results = builder.func.dfg.inst_results(call).to_vec();
+        Self::unpin_context(&mut builder, saved_context);
+        builder.ins().return_(&results);
+        builder.finalize();
+
+        let func = self.finish_trampoline(context, isa)?;
+        self.save_translator(func_translator);
+        Ok(func)
+    }
+
+    /// Saves the pinned register and puts the `VMRuntimeLimits` pointer of
+    /// `caller_vmctx` in it, if the context is pinned, returning the saved
+    /// value to restore with `unpin_context` before returning to the host.
+    ///
+    /// The register is restored rather than left alone since the host may
+    /// have entered wasm from within a call out of a different store's wasm.
+    fn pin_context(
+        &self,
+        builder: &mut FunctionBuilder<'_>,
+        caller_vmctx: ir::Value,
+    ) -> Option<ir::Value> {
+        if !self.isa.flags().enable_pinned_reg() {
+            return None;
+        }
+        let pointer_type = self.isa.pointer_type();
+        let offsets = VMOffsets::new(self.isa.pointer_bytes(), &Module::new());
+        let saved = builder.ins().get_pinned_reg(pointer_type);
+        let limits = builder.ins().load(
+            pointer_type,
+            MemFlags::trusted(),
+            caller_vmctx,
+            i32::try_from(offsets.vmctx_runtime_limits()).unwrap(),
+        );
+        builder.ins().set_pinned_reg(limits);
+        Some(saved)
+    }
+
+    fn unpin_context(builder: &mut FunctionBuilder<'_>, saved: Option<ir::Value>) {
+        if let Some(saved) = saved {
+            builder.ins().set_pinned_reg(saved);
+        }
+    }
+
     fn wasm_to_host_trampoline(
         &self,
         ty: &WasmFuncType,
@@ -552,23 +911,23 @@ impl Compiler {
         mut context: Context,
         isa: &dyn TargetIsa,
     ) -> Result<CompiledFunction, CompileError> {
-        let mut code_buf = Vec::new();
+        let mut sink = FunctionSink::default();
         context
-            .compile_and_emit(isa, &mut code_buf)
+            .compile_with_sink(isa, &mut sink)
             .map_err(|error| CompileError::Codegen(pretty_error(&context.func, error)))?;
+        let FunctionSink {
+            body: code_buf,
+            relocations,
+            stack_maps,
+            ..
+        } = sink.finish();
 
         // Processing relocations isn't the hardest thing in the world here but
         // no trampoline should currently generate a relocation, so assert that
         // they're all empty and if this ever trips in the future then handling
         // will need to be added here to ensure they make their way into the
         // `CompiledFunction` below.
-        assert!(context
-            .mach_compile_result
-            .as_ref()
-            .unwrap()
-            .buffer
-            .relocs()
-            .is_empty());
+        assert!(relocations.is_empty());
 
         let unwind_info = if isa.flags().unwind_info() {
             context
@@ -578,13 +937,19 @@ impl Compiler {
             None
         };
 
+        let length = u32::try_from(code_buf.len()).unwrap();
         Ok(CompiledFunction {
             body: code_buf,
             unwind_info,
+            baseline_unwind_info: false,
             relocations: Vec::new(),
             stack_slots: Default::default(),
             value_labels_ranges: Default::default(),
-            info: Default::default(),
+            info: FunctionInfo {
+                stack_maps,
+                length,
+                ..Default::default()
+            },
             address_map: Default::default(),
             traps: Vec::new(),
         })
@@ -654,77 +1019,103 @@ fn collect_address_maps(
     }
 }
 
-fn mach_reloc_to_reloc(reloc: &MachReloc) -> Relocation {
-    let &MachReloc {
-        offset,
-        srcloc: _,
-        kind,
-        ref name,
-        addend,
-    } = reloc;
-    let reloc_target = if let ExternalName::User { namespace, index } = *name {
-        debug_assert_eq!(namespace, 0);
-        RelocationTarget::UserFunc(FuncIndex::from_u32(index))
-    } else if let ExternalName::LibCall(libcall) = *name {
-        RelocationTarget::LibCall(libcall)
-    } else {
-        panic!("unrecognized external name")
-    };
-    Relocation {
-        reloc: kind,
-        reloc_target,
-        offset,
-        addend,
+/// Writes the CLIF of `func_index`, as it was translated from wasm, to
+/// `wasm_func_N.clif` in `dir`, with its relocations, traps and unwind info
+/// appended as comments.
+fn write_clif(
+    dir: &Path,
+    func_index: FuncIndex,
+    mut clif: String,
+    relocs: &[Relocation],
+    traps: &[TrapInformation],
+    unwind_info: &Option<cranelift_codegen::isa::unwind::UnwindInfo>,
+) -> Result<(), CompileError> {
+    clif.push_str("\n; relocations:\n");
+    for r in relocs {
+        writeln!(
+            clif,
+            ";   {:#x}: {} {:?} + {}",
+            r.offset, r.reloc, r.reloc_target, r.addend
+        )
+        .unwrap();
     }
+    clif.push_str("; traps:\n");
+    for trap in traps {
+        writeln!(clif, ";   {:#x}: {:?}", trap.code_offset, trap.trap_code).unwrap();
+    }
+    if let Some(info) = unwind_info {
+        writeln!(clif, "; unwind info:\n;   {:?}", info).unwrap();
+    }
+    let path = dir.join(format!("wasm_func_{}.clif", func_index.as_u32()));
+    std::fs::write(&path, clif)
+        .map_err(|e| CompileError::Codegen(format!("failed to write {}: {}", path.display(), e)))
 }
 
-fn mach_trap_to_trap(trap: &MachTrap) -> TrapInformation {
-    let &MachTrap {
-        offset,
-        srcloc: _,
-        code,
-    } = trap;
-    TrapInformation {
-        code_offset: offset,
-        trap_code: match code {
-            ir::TrapCode::StackOverflow => TrapCode::StackOverflow,
-            ir::TrapCode::HeapOutOfBounds => TrapCode::HeapOutOfBounds,
-            ir::TrapCode::HeapMisaligned => TrapCode::HeapMisaligned,
-            ir::TrapCode::TableOutOfBounds => TrapCode::TableOutOfBounds,
-            ir::TrapCode::IndirectCallToNull => TrapCode::IndirectCallToNull,
-            ir::TrapCode::BadSignature => TrapCode::BadSignature,
-            ir::TrapCode::IntegerOverflow => TrapCode::IntegerOverflow,
-            ir::TrapCode::IntegerDivisionByZero => TrapCode::IntegerDivisionByZero,
-            ir::TrapCode::BadConversionToInteger => TrapCode::BadConversionToInteger,
-            ir::TrapCode::UnreachableCodeReached => TrapCode::UnreachableCodeReached,
-            ir::TrapCode::Interrupt => TrapCode::Interrupt,
-
-            // these should never be emitted by wasmtime-cranelift
-            ir::TrapCode::User(_) => unreachable!(),
-        },
+/// Collects the code of a function, and its relocations, traps and stack
+/// maps in Wasmtime's representation, as Cranelift emits them.
+#[derive(Default)]
+struct FunctionSink {
+    body: Vec<u8>,
+    relocations: Vec<Relocation>,
+    traps: Vec<TrapInformation>,
+    stack_maps: Vec<StackMapInformation>,
+}
+
+impl FunctionSink {
+    fn finish(mut self) -> Self {
+        self.stack_maps
+            .sort_unstable_by_key(|info| info.code_offset);
+        self
     }
 }
 
-fn mach_stack_maps_to_stack_maps(mach_stack_maps: &[MachStackMap]) -> Vec<StackMapInformation> {
-    // This is converting from Cranelift's representation of a stack map to
-    // Wasmtime's representation. They happen to align today but that may
-    // not always be true in the future.
-    let mut stack_maps = Vec::new();
-    for &MachStackMap {
-        offset_end,
-        ref stack_map,
-        ..
-    } in mach_stack_maps
-    {
+impl CodeSink for FunctionSink {
+    fn put_code(&mut self, code: &[u8]) {
+        self.body.extend_from_slice(code);
+    }
+
+    fn reloc_external(
+        &mut self,
+        offset: CodeOffset,
+        _srcloc: ir::SourceLoc,
+        reloc: Reloc,
+        name: &ExternalName,
+        addend: Addend,
+    ) {
+        let reloc_target = if let ExternalName::User { namespace, index } = *name {
+            debug_assert_eq!(namespace, 0);
+            RelocationTarget::UserFunc(FuncIndex::from_u32(index))
+        } else if let ExternalName::LibCall(libcall) = *name {
+            RelocationTarget::LibCall(libcall)
+        } else {
+            panic!("unrecognized external name")
+        };
+        self.relocations.push(Relocation {
+            reloc,
+            reloc_target,
+            offset,
+            addend,
+        });
+    }
+
+    fn trap(&mut self, offset: CodeOffset, _srcloc: ir::SourceLoc, code: ir::TrapCode) {
+        self.traps.push(TrapInformation {
+            code_offset: offset,
+            trap_code: wasmtime_trap_code(code),
+        });
+    }
+
+    fn stack_map(&mut self, _offset: CodeOffset, offset_end: CodeOffset, stack_map: &StackMap) {
+        // This is converting from Cranelift's representation of a stack map to
+        // Wasmtime's representation. They happen to align today but that may
+        // not always be true in the future.
         let stack_map = wasmtime_environ::StackMap::new(
             stack_map.mapped_words(),
             stack_map.as_slice().iter().map(|a| a.0),
         );
-        stack_maps.push(StackMapInformation {
+        self.stack_maps.push(StackMapInformation {
             code_offset: offset_end,
             stack_map,
         });
     }
-    stack_maps.sort_unstable_by_key(|info| info.code_offset);
-    stack_maps
 }
@@ -21,13 +21,17 @@ pub struct FunctionFrameInfo<'a> {
 }
 
 impl<'a> FunctionFrameInfo<'a> {
-    fn vmctx_memory_offset(&self) -> Option<i64> {
+    /// Returns the offset within the vmctx of the pointer that leads to the
+    /// base of linear memory, and whether that pointer needs to be followed
+    /// once more to reach the base.
+    ///
+    /// For an imported memory the vmctx holds a `VMMemoryImport`, whose first
+    /// field is a pointer to the `VMMemoryDefinition` which in turn starts
+    /// with the base pointer.
+    fn vmctx_memory_offset(&self) -> Option<(i64, bool)> {
         match self.memory_offset {
-            ModuleMemoryOffset::Defined(x) => Some(x as i64),
-            ModuleMemoryOffset::Imported(_) => {
-                // TODO implement memory offset for imported memory
-                None
-            }
+            ModuleMemoryOffset::Defined(x) => Some((x as i64, false)),
+            ModuleMemoryOffset::Imported(x) => Some((x as i64, true)),
             ModuleMemoryOffset::None => None,
         }
     }
@@ -165,17 +169,16 @@ fn append_memory_deref(
     isa: &dyn TargetIsa,
 ) -> Result<bool> {
     let mut writer = ExpressionWriter::new();
-    // FIXME for imported memory
+    let (memory_offset, imported) = match frame_info.vmctx_memory_offset() {
+        Some(offset) => offset,
+        None => {
+            return Ok(false);
+        }
+    };
     match vmctx_loc {
         LabelValueLoc::Reg(r) => {
             let reg = isa.map_regalloc_reg_to_dwarf(r)?;
             writer.write_op_breg(reg)?;
-            let memory_offset = match frame_info.vmctx_memory_offset() {
-                Some(offset) => offset,
-                None => {
-                    return Ok(false);
-                }
-            };
             writer.write_sleb128(memory_offset)?;
         }
         LabelValueLoc::SPOffset(off) => {
@@ -183,17 +186,15 @@ fn append_memory_deref(
             writer.write_sleb128(off)?;
             writer.write_op(gimli::constants::DW_OP_deref)?;
             writer.write_op(gimli::constants::DW_OP_consts)?;
-            let memory_offset = match frame_info.vmctx_memory_offset() {
-                Some(offset) => offset,
-                None => {
-                    return Ok(false);
-                }
-            };
             writer.write_sleb128(memory_offset)?;
             writer.write_op(gimli::constants::DW_OP_plus)?;
         }
     }
     writer.write_op(gimli::constants::DW_OP_deref)?;
+    if imported {
+        // Follow the `VMMemoryImport::from` pointer to the base.
+        writer.write_op(gimli::constants::DW_OP_deref)?;
+    }
     writer.write_op(gimli::constants::DW_OP_swap)?;
     writer.write_op(gimli::constants::DW_OP_const4u)?;
     writer.write_u32(0xffff_ffff)?;
@@ -1246,4 +1247,60 @@ mod tests {
         assert_eq!(ranges[1].start, 20);
         assert_eq!(ranges[1].end, 23);
     }
+
+    #[test]
+    fn test_debug_memory_deref() {
+        use super::append_memory_deref;
+        use crate::debug::ModuleMemoryOffset;
+        use cranelift_codegen::ir::{LabelValueLoc, StackSlots};
+        use cranelift_codegen::settings;
+        use gimli::constants::{DW_OP_breg7, DW_OP_deref};
+
+        let isa = cranelift_native::builder()
+            .unwrap()
+            .finish(settings::Flags::new(settings::builder()))
+            .unwrap();
+        let stack_slots = StackSlots::new();
+        let value_ranges = Default::default();
+        let deref = |memory_offset| {
+            let fi = FunctionFrameInfo {
+                memory_offset,
+                stack_slots: &stack_slots,
+                value_ranges: &value_ranges,
+            };
+            let mut buf = Vec::new();
+            let loc = LabelValueLoc::SPOffset(16);
+            let found = append_memory_deref(&mut buf, &fi, loc, &*isa).unwrap();
+            if found {
+                Some(buf)
+            } else {
+                None
+            }
+        };
+
+        assert_eq!(deref(ModuleMemoryOffset::None), None);
+
+        // The vmctx is loaded from the stack, then the base is loaded from
+        // the vmctx.
+        let defined = deref(ModuleMemoryOffset::Defined(0x20)).unwrap();
+        assert_eq!(
+            &defined[..6],
+            &[
+                DW_OP_breg7.0,
+                16,
+                DW_OP_deref.0,
+                gimli::constants::DW_OP_consts.0,
+                0x20,
+                gimli::constants::DW_OP_plus.0,
+            ]
+        );
+        assert_eq!(defined[6], DW_OP_deref.0);
+        assert_ne!(defined[7], DW_OP_deref.0);
+
+        // An imported memory's base is behind its `VMMemoryImport`.
+        let imported = deref(ModuleMemoryOffset::Imported(0x20)).unwrap();
+        assert_eq!(&imported[..7], &defined[..7]);
+        assert_eq!(imported[7], DW_OP_deref.0);
+        assert_eq!(&imported[8..], &defined[7..]);
+    }
 }
@@ -13,7 +13,8 @@ use std::path::PathBuf;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use wasmparser::Type as WasmType;
 use wasmtime_environ::{
-    DebugInfoData, DefinedFuncIndex, EntityRef, FuncIndex, FunctionMetadata, WasmFileInfo,
+    symbol_name, DebugInfoData, DefinedFuncIndex, EntityRef, FuncIndex, FunctionMetadata,
+    WasmFileInfo,
 };
 
 const PRODUCER_NAME: &str = "wasmtime";
@@ -368,16 +369,26 @@ pub fn generate_simulated_dwarf(
         );
 
         let func_index = imported_func_count + (index as u32);
-        let id = match func_names
-            .get(&FuncIndex::from_u32(func_index))
-            .and_then(|s| check_invalid_chars_in_name(s))
-        {
+        let func_name = func_names.get(&FuncIndex::from_u32(func_index)).copied();
+        let id = match func_name.and_then(|s| check_invalid_chars_in_name(s)) {
             Some(n) => out_strings.add(assert_dwarf_str!(n)),
             None => out_strings.add(format!("wasm-function[{}]", func_index)),
         };
 
         die.set(gimli::DW_AT_name, write::AttributeValue::StringRef(id));
 
+        // The linkage name is the `module!function` symbol name used by
+        // profilers and backtraces, so debuggers can be cross-referenced with
+        // them.
+        let name = symbol_name(di.name_section.module_name, func_name, func_index as usize);
+        if let Some(n) = check_invalid_chars_in_name(&name) {
+            let id = out_strings.add(assert_dwarf_str!(n));
+            die.set(
+                gimli::DW_AT_linkage_name,
+                write::AttributeValue::StringRef(id),
+            );
+        }
+
         die.set(
             gimli::DW_AT_decl_file,
             write::AttributeValue::StringRef(name_id),
@@ -1,7 +1,7 @@
-use cranelift_codegen::cursor::FuncCursor;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
 use cranelift_codegen::ir;
 use cranelift_codegen::ir::condcodes::*;
-use cranelift_codegen::ir::immediates::{Imm64, Offset32, Uimm64};
+use cranelift_codegen::ir::immediates::{Ieee32, Ieee64, Imm64, Offset32, Uimm64};
 use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{AbiParam, ArgumentPurpose, Function, InstBuilder, Signature};
 use cranelift_codegen::isa::{self, TargetFrontendConfig, TargetIsa};
@@ -12,11 +12,13 @@ use cranelift_wasm::{
     self, FuncIndex, FuncTranslationState, GlobalIndex, GlobalVariable, MemoryIndex, TableIndex,
     TargetEnvironment, TypeIndex, WasmError, WasmResult, WasmType,
 };
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::mem;
-use wasmparser::Operator;
+use wasmparser::{MemoryImmediate, Operator};
 use wasmtime_environ::{
-    BuiltinFunctionIndex, MemoryPlan, MemoryStyle, Module, ModuleTranslation, TableStyle, Tunables,
+    BranchProfile, BuiltinFunctionIndex, CoreDumpFrameOffsets, FunctionProfile, GlobalSlot,
+    LazyFunctionTable, MemoryPlan, MemoryStyle, Module, OverflowingOp, TableStyle, Tunables,
     TypeTables, VMOffsets, WASM_PAGE_SIZE,
 };
 use wasmtime_environ::{FUNCREF_INIT_BIT, FUNCREF_MASK};
@@ -30,9 +32,23 @@ macro_rules! declare_function_signatures {
     (
         $(
             $( #[$attr:meta] )*
-            $name:ident( $( $param:ident ),* ) -> ( $( $result:ident ),* );
+            $name:ident $params:tt -> $results:tt;
         )*
     ) => {
+        declare_function_signatures!(@filter [] $( $name $params -> $results; )*);
+    };
+
+    // Builtins which only the baseline compiler calls are left out, since
+    // code compiled by Cranelift never needs their signatures.
+    (@filter [ $( $done:tt )* ] tier_up $params:tt -> $results:tt; $( $rest:tt )*) => {
+        declare_function_signatures!(@filter [ $( $done )* ] $( $rest )*);
+    };
+
+    (@filter [ $( $done:tt )* ] $name:ident $params:tt -> $results:tt; $( $rest:tt )*) => {
+        declare_function_signatures!(@filter [ $( $done )* $name $params -> $results; ] $( $rest )*);
+    };
+
+    (@filter [ $( $name:ident( $( $param:ident ),* ) -> ( $( $result:ident ),* ); )* ]) => {
         /// A struct with an `Option<ir::SigRef>` member for every builtin
         /// function, to de-duplicate constructing/getting its signature.
         struct BuiltinFunctionSignatures {
@@ -112,7 +128,6 @@ wasmtime_environ::foreach_builtin_function!(declare_function_signatures);
 pub struct FuncEnvironment<'module_environment> {
     isa: &'module_environment (dyn TargetIsa + 'module_environment),
     module: &'module_environment Module,
-    translation: &'module_environment ModuleTranslation<'module_environment>,
     types: &'module_environment TypeTables,
 
     /// The Cranelift global holding the vmctx address.
@@ -152,12 +167,78 @@ pub struct FuncEnvironment<'module_environment> {
     epoch_ptr_var: cranelift_frontend::Variable,
 
     fuel_consumed: i64,
+
+    /// The index of the function being translated, which is passed to the
+    /// runtime by guest debugging and instruction counting.
+    func_index: FuncIndex,
+
+    /// The number of instructions executed since the start of the current
+    /// straight-line run of instructions, which begins at `count_start`, when
+    /// counting instructions.
+    instructions_counted: u32,
+    count_start: ir::SourceLoc,
+
+    /// The address operand, and for stores the value operand, of the memory
+    /// access being translated, saved before the instruction pops them when
+    /// tracing memory accesses.
+    traced_operands: Option<(ir::Value, Option<ir::Value>)>,
+
+    /// Whether the function's integer arithmetic is checked for overflow,
+    /// which is the case for all functions when integer overflow checks are
+    /// enabled except for those exempted from them.
+    check_integer_overflow: bool,
+
+    /// The operands of the integer arithmetic being translated, saved before
+    /// the instruction pops them when checking for integer overflow.
+    checked_operands: Option<(ir::Value, ir::Value)>,
+
+    /// The types of the function's locals, parameters first, which are passed
+    /// to the runtime before each instruction when compiling with guest
+    /// debugging, and recorded with the function's frames when compiling to
+    /// capture core dumps.
+    debug_locals: Vec<WasmType>,
+
+    /// The offset of the function's body within the wasm module, from which
+    /// the offsets of instructions recorded with core dump frames start.
+    func_start: u32,
+
+    /// The stack slot holding the record of the function's frame, when
+    /// compiling to capture core dumps.
+    coredump_slot: Option<ir::StackSlot>,
+
+    /// The stack slot to which locals are spilled before calling the
+    /// `debug_hook` builtin, or `None` if the function has no locals.
+    debug_slot: Option<ir::StackSlot>,
+
+    /// Where to find the code of the module's other functions, when compiling
+    /// a function of a lazily compiled module on its first call.
+    lazy_table: Option<&'module_environment LazyFunctionTable>,
+
+    /// The address of the function's profile counters, when collecting a
+    /// profile.
+    profile_counters: Option<usize>,
+
+    /// The function's profile from earlier executions, when it's compiled
+    /// with one.
+    profile: Option<&'module_environment FunctionProfile>,
+
+    /// The index of the next `if` or `br_if` to be translated, counting all of
+    /// the function's profiled branches.
+    profile_branch: usize,
+
+    /// The profile of the branch being translated, saved before translating
+    /// it to lay out the blocks it creates afterwards.
+    profile_hint: Option<BranchProfile>,
+
+    /// Whether the alternative of each enclosing control construct is cold,
+    /// which only an `if` with a profile can have.
+    profile_cold_else: Vec<bool>,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
     pub fn new(
         isa: &'module_environment (dyn TargetIsa + 'module_environment),
-        translation: &'module_environment ModuleTranslation<'module_environment>,
+        module: &'module_environment Module,
         types: &'module_environment TypeTables,
         tunables: &'module_environment Tunables,
     ) -> Self {
@@ -172,12 +253,11 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         );
         Self {
             isa,
-            module: &translation.module,
-            translation,
+            module,
             types,
             vmctx: None,
             builtin_function_signatures,
-            offsets: VMOffsets::new(isa.pointer_bytes(), &translation.module),
+            offsets: VMOffsets::new(isa.pointer_bytes(), module),
             tunables,
             fuel_var: Variable::new(0),
             epoch_deadline_var: Variable::new(0),
@@ -187,9 +267,73 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             // Start with at least one fuel being consumed because even empty
             // functions should consume at least some fuel.
             fuel_consumed: 1,
+
+            func_index: FuncIndex::from_u32(0),
+            instructions_counted: 0,
+            count_start: ir::SourceLoc::default(),
+            traced_operands: None,
+            check_integer_overflow: false,
+            checked_operands: None,
+            debug_locals: Vec::new(),
+            debug_slot: None,
+            func_start: 0,
+            coredump_slot: None,
+            lazy_table: None,
+            profile_counters: None,
+            profile: None,
+            profile_branch: 0,
+            profile_hint: None,
+            profile_cold_else: Vec::new(),
         }
     }
 
+    /// Configures the index of the function being translated.
+    pub fn set_func_index(&mut self, func_index: FuncIndex) {
+        self.func_index = func_index;
+    }
+
+    /// Configures whether the function's integer arithmetic is checked for
+    /// overflow.
+    pub fn set_check_integer_overflow(&mut self, check: bool) {
+        self.check_integer_overflow = check;
+    }
+
+    /// Configures the types of the locals which are reported to the runtime
+    /// when compiling with guest debugging or to capture core dumps.
+    pub fn set_debug_locals(&mut self, locals: Vec<WasmType>) {
+        self.debug_locals = locals;
+    }
+
+    /// Configures the offset of the function's body within the wasm module.
+    pub fn set_func_start(&mut self, func_start: u32) {
+        self.func_start = func_start;
+    }
+
+    /// Configures where calls to the module's other functions go, when
+    /// compiling a function of a lazily compiled module on its first call.
+    pub fn set_lazy_table(&mut self, table: &'module_environment LazyFunctionTable) {
+        self.lazy_table = Some(table);
+    }
+
+    /// Returns whether calls to imported functions go through the module's
+    /// import thunks, which aren't in the text sections of functions compiled
+    /// lazily.
+    fn calls_imports_through_thunks(&self) -> bool {
+        self.tunables.devirtualize_imports && self.lazy_table.is_none()
+    }
+
+    /// Configures the address of the function's profile counters, when
+    /// collecting a profile.
+    pub fn set_profile_counters(&mut self, counters: usize) {
+        self.profile_counters = Some(counters);
+    }
+
+    /// Configures the profile which guides how the function's blocks are
+    /// laid out.
+    pub fn set_profile(&mut self, profile: &'module_environment FunctionProfile) {
+        self.profile = Some(profile);
+    }
+
     fn pointer_type(&self) -> ir::Type {
         self.isa.pointer_type()
     }
@@ -330,8 +474,23 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         let pointer_type = self.pointer_type();
         let vmctx = self.vmctx(func);
         if let Some(def_index) = self.module.defined_global_index(index) {
-            let offset = i32::try_from(self.offsets.vmctx_vmglobal_definition(def_index)).unwrap();
-            (vmctx, offset)
+            match self.module.defined_global_slot(def_index) {
+                GlobalSlot::VMContext(slot) => {
+                    let offset = i32::try_from(self.offsets.vmctx_vmglobal_definition(slot));
+                    (vmctx, offset.unwrap())
+                }
+                GlobalSlot::Shared(slot) => {
+                    let globals_offset = self.offsets.vmctx_shared_globals();
+                    let globals = func.create_global_value(ir::GlobalValueData::Load {
+                        base: vmctx,
+                        offset: Offset32::new(i32::try_from(globals_offset).unwrap()),
+                        global_type: pointer_type,
+                        readonly: true,
+                    });
+                    let offset = i32::try_from(self.offsets.shared_vmglobal_definition(slot));
+                    (globals, offset.unwrap())
+                }
+            }
         } else {
             let from_offset = self.offsets.vmctx_vmglobal_import_from(index);
             let global = func.create_global_value(ir::GlobalValueData::Load {
@@ -351,6 +510,15 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         // changes for the lifetime of the function.
         let pointer_type = self.pointer_type();
         builder.declare_var(self.vmruntime_limits_ptr, pointer_type);
+
+        // With a pinned context register the trampoline which entered wasm
+        // has already put the pointer there.
+        if self.tunables.pinned_context_register {
+            let interrupt_ptr = builder.ins().get_pinned_reg(pointer_type);
+            builder.def_var(self.vmruntime_limits_ptr, interrupt_ptr);
+            return;
+        }
+
         let vmctx = self.vmctx(builder.func);
         let base = builder.ins().global_value(pointer_type, vmctx);
         let offset = i32::try_from(self.offsets.vmctx_runtime_limits()).unwrap();
@@ -698,6 +866,498 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         builder.switch_to_block(continuation_block);
     }
 
+    /// Increments the profile counter at the address `counter`, by `amount` or
+    /// else by one.
+    ///
+    /// Like hotness counters, profile counters aren't updated atomically, so
+    /// updates from concurrent calls may be lost.
+    fn profile_count(
+        &mut self,
+        builder: &mut FunctionBuilder<'_>,
+        counter: usize,
+        amount: Option<ir::Value>,
+    ) {
+        let addr = builder.ins().iconst(self.pointer_type(), counter as i64);
+        let flags = ir::MemFlags::trusted();
+        let count = builder.ins().load(I64, flags, addr, 0);
+        let count = match amount {
+            Some(amount) => builder.ins().iadd(count, amount),
+            None => builder.ins().iadd_imm(count, 1),
+        };
+        builder.ins().store(flags, count, addr, 0);
+    }
+
+    fn profile_before_op(
+        &mut self,
+        op: &Operator<'_>,
+        builder: &mut FunctionBuilder<'_>,
+        state: &FuncTranslationState,
+    ) {
+        match op {
+            Operator::If { .. } | Operator::BrIf { .. } => {}
+            _ => return,
+        }
+        let branch = self.profile_branch;
+        self.profile_branch += 1;
+
+        // Both instructions branch on the condition on top of the stack, so
+        // count it before it's popped.
+        if let (Some(counters), true) = (self.profile_counters, state.reachable()) {
+            let taken = builder
+                .ins()
+                .icmp_imm(IntCC::NotEqual, state.peekn(1)[0], 0);
+            let taken = builder.ins().bint(I64, taken);
+            let executed = counters + 8 * (1 + 2 * branch);
+            self.profile_count(builder, executed, None);
+            self.profile_count(builder, executed + 8, Some(taken));
+        }
+        self.profile_hint = self.profile.and_then(|p| p.branches.get(branch).copied());
+    }
+
+    /// Marks the block the translation of `op` switched to as cold if the
+    /// profile shows that it's rarely reached: the fallthrough of a `br_if`
+    /// which is nearly always taken, or the consequent or alternative of an
+    /// `if` which nearly always goes the other way.
+    fn profile_after_op(
+        &mut self,
+        op: &Operator<'_>,
+        builder: &mut FunctionBuilder<'_>,
+        state: &FuncTranslationState,
+    ) {
+        let branch = self.profile_hint.take();
+        let cold = match op {
+            Operator::Block { .. } | Operator::Loop { .. } => {
+                self.profile_cold_else.push(false);
+                false
+            }
+            Operator::If { .. } => {
+                let cold_else = branch.map_or(false, |b| b.not_taken_is_cold());
+                self.profile_cold_else.push(cold_else);
+                branch.map_or(false, |b| b.taken_is_cold())
+            }
+            Operator::BrIf { .. } => branch.map_or(false, |b| b.not_taken_is_cold()),
+            Operator::Else => self.profile_cold_else.last() == Some(&true),
+            Operator::End => {
+                self.profile_cold_else.pop();
+                false
+            }
+            _ => false,
+        };
+        if cold && state.reachable() {
+            let block = builder.current_block().unwrap();
+            builder.set_cold_block(block);
+        }
+    }
+
+    fn count_before_op(
+        &mut self,
+        op: &Operator<'_>,
+        builder: &mut FunctionBuilder<'_>,
+        reachable: bool,
+    ) {
+        if !reachable {
+            // Whatever made this code unreachable also ended the previous run
+            // of instructions and already reported it.
+            debug_assert_eq!(self.instructions_counted, 0);
+            return;
+        }
+
+        if self.instructions_counted == 0 {
+            self.count_start = builder.srcloc();
+        }
+        self.instructions_counted += 1;
+
+        // Unlike fuel, every instruction is counted, and the count is reported
+        // at the same points at which fuel is added to `fuel_var`, including
+        // before control leaves the function. Those are the ends of the
+        // function's straight-line runs of instructions, whose start offsets
+        // identify them as basic blocks for coverage.
+        match op {
+            Operator::Unreachable
+            | Operator::Return
+            | Operator::CallIndirect { .. }
+            | Operator::Call { .. }
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. }
+            | Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::End
+            | Operator::Else => self.count_report(builder),
+            _ => {}
+        }
+    }
+
+    fn count_report(&mut self, builder: &mut FunctionBuilder<'_>) {
+        let count = mem::replace(&mut self.instructions_counted, 0);
+        let func_index = builder
+            .ins()
+            .iconst(I32, i64::from(self.func_index.as_u32()));
+        let offset = builder
+            .ins()
+            .iconst(I32, i64::from(self.count_start.bits()));
+        let count = builder.ins().iconst(I32, i64::from(count));
+        let count_instructions_sig = self
+            .builtin_function_signatures
+            .count_instructions(builder.func);
+        let (vmctx, count_instructions) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::count_instructions(),
+        );
+        builder.ins().call_indirect(
+            count_instructions_sig,
+            count_instructions,
+            &[vmctx, func_index, offset, count],
+        );
+    }
+
+    fn trace_before_op(&mut self, op: &Operator<'_>, state: &FuncTranslationState) {
+        self.traced_operands = match traced_memory_access(op) {
+            Some((_, _, false)) => Some((state.peekn(1)[0], None)),
+            Some((_, _, true)) => {
+                let operands = state.peekn(2);
+                Some((operands[0], Some(operands[1])))
+            }
+            None => None,
+        };
+    }
+
+    fn trace_after_op(
+        &mut self,
+        op: &Operator<'_>,
+        builder: &mut FunctionBuilder<'_>,
+        state: &FuncTranslationState,
+    ) {
+        let (addr, stored) = match self.traced_operands.take() {
+            Some(operands) => operands,
+            None => return,
+        };
+        let (memarg, size, is_store) = traced_memory_access(op).unwrap();
+        // Loads report the value they produced, which is still on top of the
+        // stack.
+        let value = match stored {
+            Some(value) => value,
+            None => state.peekn(1)[0],
+        };
+
+        let addr = if builder.func.dfg.value_type(addr) == I32 {
+            builder.ins().uextend(I64, addr)
+        } else {
+            addr
+        };
+        let addr = builder.ins().iadd_imm(addr, memarg.offset as i64);
+
+        // The value is passed as two 64-bit halves, of which only the low
+        // `size` bytes are meaningful.
+        let ty = builder.func.dfg.value_type(value);
+        let (low, high) = if ty.is_vector() {
+            let value = builder.ins().raw_bitcast(I64X2, value);
+            let low = builder.ins().extractlane(value, 0);
+            let high = builder.ins().extractlane(value, 1);
+            (low, high)
+        } else {
+            let low = match ty {
+                F32 => builder.ins().bitcast(I32, value),
+                F64 => builder.ins().bitcast(I64, value),
+                _ => value,
+            };
+            let low = if builder.func.dfg.value_type(low) == I32 {
+                builder.ins().uextend(I64, low)
+            } else {
+                low
+            };
+            (low, builder.ins().iconst(I64, 0))
+        };
+
+        let memory = builder.ins().iconst(I32, i64::from(memarg.memory));
+        let is_store = builder.ins().iconst(I32, i64::from(is_store));
+        let size = builder.ins().iconst(I32, i64::from(size));
+        let trace_sig = self
+            .builtin_function_signatures
+            .trace_memory_access(builder.func);
+        let (vmctx, trace) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::trace_memory_access(),
+        );
+        builder.ins().call_indirect(
+            trace_sig,
+            trace,
+            &[vmctx, memory, is_store, addr, size, low, high],
+        );
+    }
+
+    fn overflow_before_op(&mut self, op: &Operator<'_>, state: &FuncTranslationState) {
+        self.checked_operands = match overflowing_op(op) {
+            Some(_) => {
+                let operands = state.peekn(2);
+                Some((operands[0], operands[1]))
+            }
+            None => None,
+        };
+    }
+
+    fn overflow_after_op(
+        &mut self,
+        op: &Operator<'_>,
+        builder: &mut FunctionBuilder<'_>,
+        state: &FuncTranslationState,
+    ) {
+        let (lhs, rhs) = match self.checked_operands.take() {
+            Some(operands) => operands,
+            None => return,
+        };
+        let kind = overflowing_op(op).unwrap();
+        let result = state.peekn(1)[0];
+        let ty = builder.func.dfg.value_type(result);
+
+        // Additions overflow when both operands have a different sign than
+        // the result, and subtractions when the operands' signs differ and
+        // the result's sign differs from the left-hand operand's. Products
+        // overflow when they don't fit in the result, which for `i64` is when
+        // the high half of the full product isn't just the result's sign.
+        let overflowed = match kind {
+            OverflowingOp::Add => {
+                let a = builder.ins().bxor(lhs, result);
+                let b = builder.ins().bxor(rhs, result);
+                let signs = builder.ins().band(a, b);
+                builder.ins().icmp_imm(IntCC::SignedLessThan, signs, 0)
+            }
+            OverflowingOp::Sub => {
+                let a = builder.ins().bxor(lhs, rhs);
+                let b = builder.ins().bxor(lhs, result);
+                let signs = builder.ins().band(a, b);
+                builder.ins().icmp_imm(IntCC::SignedLessThan, signs, 0)
+            }
+            OverflowingOp::Mul if ty == I32 => {
+                let lhs = builder.ins().sextend(I64, lhs);
+                let rhs = builder.ins().sextend(I64, rhs);
+                let product = builder.ins().imul(lhs, rhs);
+                let result = builder.ins().sextend(I64, result);
+                builder.ins().icmp(IntCC::NotEqual, product, result)
+            }
+            OverflowingOp::Mul => {
+                let high = builder.ins().smulhi(lhs, rhs);
+                let sign = builder.ins().sshr_imm(result, 63);
+                builder.ins().icmp(IntCC::NotEqual, high, sign)
+            }
+        };
+
+        let overflow_block = builder.create_block();
+        let continuation_block = builder.create_block();
+        builder.set_cold_block(overflow_block);
+        builder.ins().brnz(overflowed, overflow_block, &[]);
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(overflow_block);
+
+        // The runtime either reports the overflow and returns, in which case
+        // execution continues with the wrapped result, or raises a trap.
+        builder.switch_to_block(overflow_block);
+        let (lhs, rhs) = if ty == I32 {
+            (
+                builder.ins().sextend(I64, lhs),
+                builder.ins().sextend(I64, rhs),
+            )
+        } else {
+            (lhs, rhs)
+        };
+        let func_index = builder
+            .ins()
+            .iconst(I32, i64::from(self.func_index.as_u32()));
+        let kind = builder.ins().iconst(I32, i64::from(kind as u8));
+        let bits = builder.ins().iconst(I32, i64::from(ty.bits()));
+        let overflow_sig = self
+            .builtin_function_signatures
+            .integer_overflow(builder.func);
+        let (vmctx, overflow) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::integer_overflow(),
+        );
+        builder.ins().call_indirect(
+            overflow_sig,
+            overflow,
+            &[vmctx, func_index, kind, bits, lhs, rhs],
+        );
+        builder.ins().jump(continuation_block, &[]);
+        builder.seal_block(continuation_block);
+
+        builder.switch_to_block(continuation_block);
+    }
+
+    fn debug_function_entry(&mut self, builder: &mut FunctionBuilder<'_>) {
+        if self.debug_locals.is_empty() {
+            return;
+        }
+
+        // The slot holds a `ValRaw` for each local followed by a tag byte for
+        // each local's type. Types never change so the tags are written once
+        // here.
+        let count = self.debug_locals.len();
+        let slot = builder.create_stack_slot(ir::StackSlotData::new(
+            ir::StackSlotKind::ExplicitSlot,
+            u32::try_from(count * 17).unwrap(),
+        ));
+        for (i, ty) in self.debug_locals.iter().enumerate() {
+            let tag = wasmtime_environ::debug_local_tag(*ty);
+            let tag = builder.ins().iconst(I8, i64::from(tag));
+            builder
+                .ins()
+                .stack_store(tag, slot, i32::try_from(count * 16 + i).unwrap());
+        }
+        self.debug_slot = Some(slot);
+    }
+
+    fn debug_before_op(&mut self, builder: &mut FunctionBuilder<'_>) {
+        let pointer_type = self.pointer_type();
+        let count = self.debug_locals.len();
+        let locals = match self.debug_slot {
+            Some(slot) => {
+                // Spill the current value of every local in the same format
+                // as `ValRaw`, which is always little-endian.
+                let addr = builder.ins().stack_addr(pointer_type, slot, 0);
+                let mut flags = ir::MemFlags::new();
+                flags.set_notrap();
+                flags.set_endianness(ir::Endianness::Little);
+                for i in 0..count {
+                    let val = builder.use_var(Variable::new(i));
+                    builder
+                        .ins()
+                        .store(flags, val, addr, i32::try_from(i * 16).unwrap());
+                }
+                addr
+            }
+            None => builder.ins().iconst(pointer_type, 0),
+        };
+        let func_index = builder
+            .ins()
+            .iconst(I32, i64::from(self.func_index.as_u32()));
+        let offset = i64::from(builder.srcloc().bits());
+        let offset = builder.ins().iconst(I32, offset);
+        let count = builder.ins().iconst(I32, i64::try_from(count).unwrap());
+
+        // The hook may pause execution to run the embedder's debugger, and it
+        // may raise a trap, but otherwise returns normally.
+        let debug_hook_sig = self.builtin_function_signatures.debug_hook(builder.func);
+        let (vmctx, debug_hook) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::debug_hook(),
+        );
+        builder.ins().call_indirect(
+            debug_hook_sig,
+            debug_hook,
+            &[vmctx, func_index, offset, locals, count],
+        );
+    }
+
+    fn coredump_function_entry(&mut self, builder: &mut FunctionBuilder<'_>) {
+        // Push a record of this frame onto the list whose head is in the
+        // `VMRuntimeLimits`. Everything but the instruction's offset and the
+        // values of locals stays the same for the whole call, so it's written
+        // once here.
+        let pointer_type = self.pointer_type();
+        let offsets = CoreDumpFrameOffsets::new(self.isa.pointer_bytes());
+        let count = u32::try_from(self.debug_locals.len()).unwrap();
+        let slot = builder.create_stack_slot(ir::StackSlotData::new(
+            ir::StackSlotKind::ExplicitSlot,
+            offsets.size(count),
+        ));
+        let limits = builder.use_var(self.vmruntime_limits_ptr);
+        let head = i32::from(self.offsets.vmruntime_limits_last_coredump_frame());
+        let prev = builder
+            .ins()
+            .load(pointer_type, ir::MemFlags::trusted(), limits, head);
+        builder
+            .ins()
+            .stack_store(prev, slot, i32::try_from(offsets.prev).unwrap());
+        let vmctx = self.vmctx(builder.func);
+        let vmctx = builder.ins().global_value(pointer_type, vmctx);
+        builder
+            .ins()
+            .stack_store(vmctx, slot, i32::try_from(offsets.vmctx).unwrap());
+        for (value, offset) in [
+            (self.func_index.as_u32(), offsets.func_index),
+            (0, offsets.func_offset),
+            (count, offsets.count),
+        ] {
+            let value = builder.ins().iconst(I32, i64::from(value));
+            builder
+                .ins()
+                .stack_store(value, slot, i32::try_from(offset).unwrap());
+        }
+        let locals = offsets.locals + count * 16;
+        for (i, ty) in self.debug_locals.iter().enumerate() {
+            let tag = wasmtime_environ::debug_local_tag(*ty);
+            let tag = builder.ins().iconst(I8, i64::from(tag));
+            let offset = locals + u32::try_from(i).unwrap();
+            builder
+                .ins()
+                .stack_store(tag, slot, i32::try_from(offset).unwrap());
+        }
+        let record = builder.ins().stack_addr(pointer_type, slot, 0);
+        builder
+            .ins()
+            .store(ir::MemFlags::trusted(), record, limits, head);
+        self.coredump_slot = Some(slot);
+    }
+
+    fn coredump_before_op(
+        &mut self,
+        op: &Operator,
+        builder: &mut FunctionBuilder<'_>,
+        reachable: bool,
+    ) {
+        if !reachable {
+            return;
+        }
+        let slot = self.coredump_slot.unwrap();
+        let offsets = CoreDumpFrameOffsets::new(self.isa.pointer_bytes());
+
+        // Record where the frame is, and the values of its locals, in case
+        // this instruction traps or calls a function which does.
+        let offset = builder.srcloc().bits().wrapping_sub(self.func_start);
+        let offset = builder.ins().iconst(I32, i64::from(offset));
+        builder
+            .ins()
+            .stack_store(offset, slot, i32::try_from(offsets.func_offset).unwrap());
+        // Locals are spilled in the same format as `ValRaw`, which is always
+        // little-endian.
+        let addr = builder.ins().stack_addr(self.pointer_type(), slot, 0);
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        flags.set_endianness(ir::Endianness::Little);
+        for i in 0..self.debug_locals.len() {
+            let val = builder.use_var(Variable::new(i));
+            let offset = offsets.locals + u32::try_from(i * 16).unwrap();
+            builder
+                .ins()
+                .store(flags, val, addr, i32::try_from(offset).unwrap());
+        }
+
+        match op {
+            Operator::Return
+            | Operator::ReturnCall { .. }
+            | Operator::ReturnCallIndirect { .. } => self.coredump_function_exit(builder),
+            _ => {}
+        }
+    }
+
+    fn coredump_function_exit(&mut self, builder: &mut FunctionBuilder<'_>) {
+        // Pop this frame's record off the list before returning.
+        let pointer_type = self.pointer_type();
+        let slot = self.coredump_slot.unwrap();
+        let offsets = CoreDumpFrameOffsets::new(self.isa.pointer_bytes());
+        let prev =
+            builder
+                .ins()
+                .stack_load(pointer_type, slot, i32::try_from(offsets.prev).unwrap());
+        let limits = builder.use_var(self.vmruntime_limits_ptr);
+        let head = i32::from(self.offsets.vmruntime_limits_last_coredump_frame());
+        builder
+            .ins()
+            .store(ir::MemFlags::trusted(), prev, limits, head);
+    }
+
     fn memory_index_type(&self, index: MemoryIndex) -> ir::Type {
         if self.module.memory_plans[index].memory.memory64 {
             I64
@@ -752,6 +1412,76 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         }
     }
 
+    /// Returns the length of a `memory.copy` or `memory.fill` if it's the
+    /// constant `len` and short enough for the operation to be performed
+    /// inline rather than by calling into the runtime.
+    fn inline_bulk_memory_len(&self, func: &Function, len: ir::Value) -> Option<u32> {
+        let inst = match func.dfg.value_def(len) {
+            ir::ValueDef::Result(inst, _) => inst,
+            ir::ValueDef::Param(..) => return None,
+        };
+        let len = match func.dfg[inst] {
+            ir::InstructionData::UnaryImm {
+                opcode: ir::Opcode::Iconst,
+                imm,
+            } if func.dfg.value_type(len) == I32 => u64::from(imm.bits() as u32),
+            ir::InstructionData::UnaryImm {
+                opcode: ir::Opcode::Iconst,
+                imm,
+            } => imm.bits() as u64,
+            _ => return None,
+        };
+        // Empty operations are left to the runtime, which still has to check
+        // that their offsets are in bounds.
+        if len == 0 || len > u64::from(self.tunables.bulk_memory_inline_threshold) {
+            return None;
+        }
+        Some(len as u32)
+    }
+
+    /// Splits the `len` bytes of an inline `memory.copy` or `memory.fill`
+    /// into the offset and type of each of the loads or stores performing it,
+    /// using 128-bit vectors where they're available.
+    fn bulk_memory_chunks(&self, len: u32) -> Vec<(u32, ir::Type)> {
+        let vectors = self.isa.flags().enable_simd()
+            && matches!(
+                self.isa.triple().architecture,
+                target_lexicon::Architecture::X86_64 | target_lexicon::Architecture::Aarch64(_)
+            );
+        let mut chunks = Vec::new();
+        let mut offset = 0;
+        for ty in [I8X16, I64, I32, I16, I8] {
+            if ty.is_vector() && !vectors {
+                continue;
+            }
+            while len - offset >= ty.bytes() {
+                chunks.push((offset, ty));
+                offset += ty.bytes();
+            }
+        }
+        chunks
+    }
+
+    /// Returns a value of type `ty` each of whose bytes is the low byte of
+    /// the `memory.fill` operand `val`.
+    fn fill_pattern(&self, pos: &mut FuncCursor<'_>, val: ir::Value, ty: ir::Type) -> ir::Value {
+        if ty.is_vector() {
+            let byte = pos.ins().ireduce(I8, val);
+            return pos.ins().splat(ty, byte);
+        }
+        if ty == I8 {
+            return pos.ins().ireduce(I8, val);
+        }
+        let byte = pos.ins().band_imm(val, 0xff);
+        let byte = pos.ins().uextend(I64, byte);
+        let pattern = pos.ins().imul_imm(byte, 0x0101_0101_0101_0101);
+        if ty == I64 {
+            pattern
+        } else {
+            pos.ins().ireduce(ty, pattern)
+        }
+    }
+
     fn get_or_init_funcref_table_elem(
         &mut self,
         builder: &mut FunctionBuilder,
@@ -761,6 +1491,22 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
     ) -> ir::Value {
         let pointer_type = self.pointer_type();
 
+        // The elements of shared tables are the offsets of their anyfuncs
+        // within the `VMContext`, or zero for null, and are never lazily
+        // initialized.
+        if self.module.is_shared_table(table_index) {
+            let table_entry_addr = builder.ins().table_addr(pointer_type, table, index, 0);
+            let mut mem_flags = ir::MemFlags::trusted();
+            mem_flags.set_readonly();
+            let offset = builder
+                .ins()
+                .load(pointer_type, mem_flags, table_entry_addr, 0);
+            let vmctx = self.vmctx(builder.func);
+            let base = builder.ins().global_value(pointer_type, vmctx);
+            let anyfunc = builder.ins().iadd(base, offset);
+            return builder.ins().select(offset, anyfunc, offset);
+        }
+
         // To support lazy initialization of table
         // contents, we check for a null entry here, and
         // if null, we take a slow-path that invokes a
@@ -804,6 +1550,496 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         builder.switch_to_block(continuation_block);
         result_param
     }
+
+    /// Returns the anyfunc at index `callee` of `table_index` for a
+    /// `call_indirect` of the type `ty_index`, trapping if it's null or of
+    /// another type.
+    fn get_checked_anyfunc(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        table_index: TableIndex,
+        table: ir::Table,
+        ty_index: TypeIndex,
+        callee: ir::Value,
+    ) -> ir::Value {
+        let pointer_type = self.pointer_type();
+
+        // Get the anyfunc pointer (the funcref) from the table.
+        let anyfunc_ptr = self.get_or_init_funcref_table_elem(builder, table_index, table, callee);
+
+        // Check for whether the table element is null, and trap if so.
+        builder
+            .ins()
+            .trapz(anyfunc_ptr, ir::TrapCode::IndirectCallToNull);
+
+        // If necessary, check the signature.
+        match self.module.table_plans[table_index].style {
+            TableStyle::CallerChecksSignature => {
+                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
+                let sig_id_type = Type::int(u16::from(sig_id_size) * 8).unwrap();
+                let vmctx = self.vmctx(builder.func);
+                let base = builder.ins().global_value(pointer_type, vmctx);
+
+                // Load the caller ID. This requires loading the
+                // `*mut VMCallerCheckedAnyfunc` base pointer from `VMContext`
+                // and then loading, based on `SignatureIndex`, the
+                // corresponding entry.
+                let mut mem_flags = ir::MemFlags::trusted();
+                mem_flags.set_readonly();
+                let signatures = builder.ins().load(
+                    pointer_type,
+                    mem_flags,
+                    base,
+                    i32::try_from(self.offsets.vmctx_signature_ids_array()).unwrap(),
+                );
+                let sig_index = self.module.types[ty_index].unwrap_function();
+                let offset =
+                    i32::try_from(sig_index.as_u32().checked_mul(sig_id_type.bytes()).unwrap())
+                        .unwrap();
+                let caller_sig_id = builder
+                    .ins()
+                    .load(sig_id_type, mem_flags, signatures, offset);
+
+                // Load the callee ID.
+                let mem_flags = ir::MemFlags::trusted();
+                let callee_sig_id = builder.ins().load(
+                    sig_id_type,
+                    mem_flags,
+                    anyfunc_ptr,
+                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
+                );
+
+                // Check that they match.
+                let cmp = builder
+                    .ins()
+                    .icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
+                builder.ins().trapz(cmp, ir::TrapCode::BadSignature);
+            }
+        }
+
+        anyfunc_ptr
+    }
+
+    /// Returns the anyfunc at index `callee` of `table_index` for a
+    /// `call_indirect` whose last callee is kept in the cache `cache`, which
+    /// is used instead of the table when `callee` is the same index again.
+    ///
+    /// The table is never modified, so the element at that index is still
+    /// the one which passed the checks of `get_checked_anyfunc` last time.
+    /// Before the first call the cached element is null, which is treated as
+    /// a miss so that calling the cache's initial index still traps.
+    fn get_cached_anyfunc(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        cache: u32,
+        table_index: TableIndex,
+        table: ir::Table,
+        ty_index: TypeIndex,
+        callee: ir::Value,
+    ) -> ir::Value {
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(builder.func);
+        let base = builder.ins().global_value(pointer_type, vmctx);
+        let cache = self.offsets.vmctx_call_indirect_cache(cache);
+        let index_offset = cache + u32::from(self.offsets.vmcall_indirect_cache_index());
+        let index_offset = i32::try_from(index_offset).unwrap();
+        let anyfunc_offset = cache + u32::from(self.offsets.vmcall_indirect_cache_anyfunc());
+        let anyfunc_offset = i32::try_from(anyfunc_offset).unwrap();
+        let mem_flags = ir::MemFlags::trusted();
+
+        let hit_block = builder.create_block();
+        let miss_block = builder.create_block();
+        let continuation_block = builder.create_block();
+        let result_param = builder.append_block_param(continuation_block, pointer_type);
+
+        let cached_index = builder.ins().load(I32, mem_flags, base, index_offset);
+        let hit = builder.ins().icmp(IntCC::Equal, cached_index, callee);
+        builder.ins().brz(hit, miss_block, &[]);
+        builder.ins().jump(hit_block, &[]);
+        builder.seal_block(hit_block);
+
+        builder.switch_to_block(hit_block);
+        let cached = builder
+            .ins()
+            .load(pointer_type, mem_flags, base, anyfunc_offset);
+        builder.ins().brz(cached, miss_block, &[]);
+        builder.ins().jump(continuation_block, &[cached]);
+        builder.seal_block(miss_block);
+
+        builder.switch_to_block(miss_block);
+        let anyfunc_ptr = self.get_checked_anyfunc(builder, table_index, table, ty_index, callee);
+        builder.ins().store(mem_flags, callee, base, index_offset);
+        builder
+            .ins()
+            .store(mem_flags, anyfunc_ptr, base, anyfunc_offset);
+        builder.ins().jump(continuation_block, &[anyfunc_ptr]);
+        builder.seal_block(continuation_block);
+
+        builder.switch_to_block(continuation_block);
+        result_param
+    }
+
+    /// Translates the body of the stub which stands in for the function being
+    /// compiled in a lazily compiled module.
+    ///
+    /// The stub loads the function's compiled code from the slot at `code`,
+    /// calling the `lazy_compile` builtin to compile it first if the slot is
+    /// still null, and then forwards its arguments to the compiled code.
+    pub fn translate_lazy_stub(&mut self, builder: &mut FunctionBuilder<'_>, code: usize) {
+        let pointer_type = self.pointer_type();
+        let entry = builder.create_block();
+        builder.append_block_params_for_function_params(entry);
+        builder.switch_to_block(entry);
+        builder.seal_block(entry);
+        let params = builder.func.dfg.block_params(entry).to_vec();
+
+        let compile_block = builder.create_block();
+        let call_block = builder.create_block();
+        builder.append_block_param(call_block, pointer_type);
+
+        let slot = builder.ins().iconst(pointer_type, code as i64);
+        let func_addr = builder
+            .ins()
+            .load(pointer_type, ir::MemFlags::trusted(), slot, 0);
+        builder.ins().brz(func_addr, compile_block, &[]);
+        builder.ins().jump(call_block, &[func_addr]);
+
+        builder.switch_to_block(compile_block);
+        builder.seal_block(compile_block);
+        let index = self.func_index.as_u32();
+        let index = builder.ins().iconst(I32, i64::from(index));
+        let lazy_compile_sig = self.builtin_function_signatures.lazy_compile(builder.func);
+        let (vmctx, lazy_compile) = self.translate_load_builtin_function_address(
+            &mut builder.cursor(),
+            BuiltinFunctionIndex::lazy_compile(),
+        );
+        let call = builder
+            .ins()
+            .call_indirect(lazy_compile_sig, lazy_compile, &[vmctx, index]);
+        let compiled = builder.func.dfg.first_result(call);
+        builder.ins().jump(call_block, &[compiled]);
+
+        builder.switch_to_block(call_block);
+        builder.seal_block(call_block);
+        let func_addr = builder.func.dfg.block_params(call_block)[0];
+        let sig_ref = builder.import_signature(builder.func.signature.clone());
+        let call = builder.ins().call_indirect(sig_ref, func_addr, &params);
+        let results = builder.func.dfg.inst_results(call).to_vec();
+        builder.ins().return_(&results);
+        builder.finalize();
+    }
+
+    /// Creates the stack slot whose address `insert_stack_check` compares
+    /// against the stack limit.
+    ///
+    /// This must be the first stack slot created in `func`. Cranelift lays out
+    /// stack slots in order starting at the bottom of the frame, so the first
+    /// slot's address is the stack pointer once the prologue has allocated the
+    /// whole frame, spill slots and all.
+    pub fn create_stack_check_slot(&self, func: &mut Function) -> ir::StackSlot {
+        assert!(func.stack_slots.is_empty());
+        func.create_stack_slot(ir::StackSlotData::new(
+            ir::StackSlotKind::ExplicitSlot,
+            self.pointer_type().bytes(),
+        ))
+    }
+
+    /// Inserts a stack overflow check at the start of `func`, comparing the
+    /// address of `slot`, created by `create_stack_check_slot`, to
+    /// `stack_limit`.
+    ///
+    /// This replaces Cranelift's check in the function prologue when
+    /// signals-based traps are disabled, since that check always raises its
+    /// trap with an illegal instruction. Like the prologue check this accounts
+    /// for the function's whole frame, since `slot` is at its bottom.
+    pub fn insert_stack_check(
+        &mut self,
+        func: &mut Function,
+        slot: ir::StackSlot,
+        stack_limit: ir::GlobalValue,
+    ) {
+        let pointer_type = self.pointer_type();
+        let entry = func.layout.entry_block().unwrap();
+        let mut pos = FuncCursor::new(func).at_first_insertion_point(entry);
+        let frame = pos.ins().stack_addr(pointer_type, slot, 0);
+        let limit = pos.ins().global_value(pointer_type, stack_limit);
+        let overflow = pos.ins().icmp(IntCC::UnsignedLessThan, frame, limit);
+        pos.ins().trapnz(overflow, ir::TrapCode::StackOverflow);
+    }
+
+    /// Rewrites `func`, which must already be legalized, such that none of
+    /// its traps are raised with signals.
+    ///
+    /// Trap instructions are replaced with branches to a block, one per trap
+    /// code, which calls the `raise_trap` builtin. Integer division and
+    /// float-to-int conversions, which Cranelift's backends lower to their own
+    /// trapping sequences, are instead preceded by explicit checks of their
+    /// operands so that the backend's traps are never reached.
+    ///
+    /// Conditional traps on flags are rewritten by recomputing their condition
+    /// from the instruction which produced the flags, and an error is returned
+    /// if that's an instruction whose condition can't be recomputed.
+    pub fn convert_traps_to_calls(&mut self, func: &mut Function) -> WasmResult<()> {
+        let mut trap_blocks = HashMap::new();
+        let mut insts = Vec::new();
+        let mut pos = FuncCursor::new(func);
+        while pos.next_block().is_some() {
+            while let Some(inst) = pos.next_inst() {
+                insts.push(inst);
+            }
+        }
+
+        for inst in insts {
+            pos.goto_inst(inst);
+            pos.use_srcloc(inst);
+            match pos.func.dfg[inst] {
+                ir::InstructionData::Trap {
+                    opcode: ir::Opcode::Trap,
+                    code,
+                } => {
+                    let srcloc = pos.srcloc();
+                    let block = self.trap_block(pos.func, &mut trap_blocks, code, srcloc);
+                    pos.func.dfg.replace(inst).jump(block, &[]);
+                }
+                ir::InstructionData::IntCondTrap {
+                    opcode: ir::Opcode::Trapif,
+                    arg,
+                    cond,
+                    code,
+                } => {
+                    // Backends can only branch on flags in limited cases, so
+                    // recompute the condition as a boolean from the
+                    // instruction which produced the flags.
+                    let flags_inst = pos.func.dfg.value_def(arg).unwrap_inst();
+                    let trapping = match pos.func.dfg[flags_inst] {
+                        ir::InstructionData::Binary {
+                            opcode: ir::Opcode::Ifcmp,
+                            args: [x, y],
+                        } => pos.ins().icmp(cond, x, y),
+                        // This is only used by heap legalization to check
+                        // whether adding the access size to an offset carries.
+                        ir::InstructionData::Binary {
+                            opcode: ir::Opcode::IaddIfcout,
+                            args: [x, _],
+                        } => {
+                            debug_assert_eq!(cond, self.isa.unsigned_add_overflow_condition());
+                            let sum = pos.func.dfg.first_result(flags_inst);
+                            pos.ins().icmp(IntCC::UnsignedLessThan, sum, x)
+                        }
+                        _ => {
+                            return Err(WasmError::Unsupported(format!(
+                                "trapif on flags from {} without signals-based traps",
+                                pos.func.dfg[flags_inst].opcode()
+                            )))
+                        }
+                    };
+                    self.trap_if(&mut pos, &mut trap_blocks, trapping, code);
+                    pos.remove_inst();
+                }
+                ir::InstructionData::FloatCondTrap {
+                    opcode: ir::Opcode::Trapff,
+                    arg,
+                    cond,
+                    code,
+                } => {
+                    let flags_inst = pos.func.dfg.value_def(arg).unwrap_inst();
+                    let trapping = match pos.func.dfg[flags_inst] {
+                        ir::InstructionData::Binary {
+                            opcode: ir::Opcode::Ffcmp,
+                            args: [x, y],
+                        } => pos.ins().fcmp(cond, x, y),
+                        _ => {
+                            return Err(WasmError::Unsupported(format!(
+                                "trapff on flags from {} without signals-based traps",
+                                pos.func.dfg[flags_inst].opcode()
+                            )))
+                        }
+                    };
+                    self.trap_if(&mut pos, &mut trap_blocks, trapping, code);
+                    pos.remove_inst();
+                }
+                ir::InstructionData::Binary {
+                    opcode:
+                        opcode @ (ir::Opcode::Udiv
+                        | ir::Opcode::Urem
+                        | ir::Opcode::Sdiv
+                        | ir::Opcode::Srem),
+                    args: [x, y],
+                } => {
+                    let is_zero = pos.ins().icmp_imm(IntCC::Equal, y, 0);
+                    self.trap_if(
+                        &mut pos,
+                        &mut trap_blocks,
+                        is_zero,
+                        ir::TrapCode::IntegerDivisionByZero,
+                    );
+                    if opcode == ir::Opcode::Sdiv {
+                        // `x / y` overflows only if `x` is the minimum value
+                        // and `y` is -1, or if `(x ^ min) | (y ^ -1)` is zero.
+                        let ty = pos.func.dfg.value_type(x);
+                        let min = if ty == I32 {
+                            i64::from(i32::MIN)
+                        } else {
+                            i64::MIN
+                        };
+                        let x_is_min = pos.ins().bxor_imm(x, min);
+                        let y_is_neg_one = pos.ins().bxor_imm(y, -1);
+                        let both = pos.ins().bor(x_is_min, y_is_neg_one);
+                        let overflow = pos.ins().icmp_imm(IntCC::Equal, both, 0);
+                        self.trap_if(
+                            &mut pos,
+                            &mut trap_blocks,
+                            overflow,
+                            ir::TrapCode::IntegerOverflow,
+                        );
+                    }
+                }
+                ir::InstructionData::Unary {
+                    opcode: opcode @ (ir::Opcode::FcvtToSint | ir::Opcode::FcvtToUint),
+                    arg: x,
+                } => {
+                    let float_ty = pos.func.dfg.value_type(x);
+                    let int_bits = pos.func.dfg.ctrl_typevar(inst).bits() as i32;
+
+                    let is_nan = pos.ins().fcmp(FloatCC::Unordered, x, x);
+                    self.trap_if(
+                        &mut pos,
+                        &mut trap_blocks,
+                        is_nan,
+                        ir::TrapCode::BadConversionToInteger,
+                    );
+
+                    // The conversion overflows if `x` is at or beyond these
+                    // bounds, which are picked to be exactly representable in
+                    // the float type.
+                    let (lower_cc, lower, upper) = if opcode == ir::Opcode::FcvtToSint {
+                        let min = -(2f64.powi(int_bits - 1));
+                        if float_ty == F64 && int_bits == 32 {
+                            (FloatCC::LessThanOrEqual, min - 1.0, -min)
+                        } else {
+                            (FloatCC::LessThan, min, -min)
+                        }
+                    } else {
+                        (FloatCC::LessThanOrEqual, -1.0, 2f64.powi(int_bits))
+                    };
+                    for (cc, bound) in [(lower_cc, lower), (FloatCC::GreaterThanOrEqual, upper)] {
+                        let bound = if float_ty == F32 {
+                            pos.ins().f32const(Ieee32::with_float(bound as f32))
+                        } else {
+                            pos.ins().f64const(Ieee64::with_float(bound))
+                        };
+                        let overflow = pos.ins().fcmp(cc, x, bound);
+                        self.trap_if(
+                            &mut pos,
+                            &mut trap_blocks,
+                            overflow,
+                            ir::TrapCode::IntegerOverflow,
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Branches to the trap block for `code` if `cond` is true, continuing at
+    /// the cursor's current instruction otherwise.
+    fn trap_if(
+        &mut self,
+        pos: &mut FuncCursor<'_>,
+        trap_blocks: &mut HashMap<(ir::TrapCode, u32), ir::Block>,
+        cond: ir::Value,
+        code: ir::TrapCode,
+    ) {
+        let srcloc = pos.srcloc();
+        let block = self.trap_block(pos.func, trap_blocks, code, srcloc);
+        pos.ins().brnz(cond, block, &[]);
+        split_block_at_cursor(pos);
+    }
+
+    /// Returns the cold block which raises a trap with `code` on behalf of the
+    /// wasm instruction at `srcloc`, creating it if this is the first such
+    /// trap in `func`.
+    ///
+    /// Blocks are shared only between traps of the same instruction so that
+    /// the call to `raise_trap` maps back to the instruction which trapped.
+    fn trap_block(
+        &mut self,
+        func: &mut Function,
+        trap_blocks: &mut HashMap<(ir::TrapCode, u32), ir::Block>,
+        code: ir::TrapCode,
+        srcloc: ir::SourceLoc,
+    ) -> ir::Block {
+        if let Some(block) = trap_blocks.get(&(code, srcloc.bits())) {
+            return *block;
+        }
+        let block = func.dfg.make_block();
+        func.layout.append_block(block);
+        func.layout.set_cold(block);
+        let sig = self.builtin_function_signatures.raise_trap(func);
+        let mut pos = FuncCursor::new(func).at_bottom(block);
+        pos.set_srcloc(srcloc);
+        let (vmctx, raise_trap) = self
+            .translate_load_builtin_function_address(&mut pos, BuiltinFunctionIndex::raise_trap());
+        let trap_code = pos
+            .ins()
+            .iconst(I32, i64::from(crate::wasmtime_trap_code(code) as u8));
+        pos.ins()
+            .call_indirect(sig, raise_trap, &[vmctx, trap_code]);
+        // `raise_trap` never returns, but the block still needs a terminator.
+        pos.ins().trap(code);
+        trap_blocks.insert((code, srcloc.bits()), block);
+        block
+    }
+}
+
+/// Ends the cursor's block with a jump to a new block starting at the cursor's
+/// current instruction.
+fn split_block_at_cursor(pos: &mut FuncCursor<'_>) {
+    let resume = pos.func.dfg.make_block();
+    pos.ins().jump(resume, &[]);
+    pos.insert_block(resume);
+}
+
+/// Returns the operation of each instruction checked when checking for
+/// integer overflow.
+fn overflowing_op(op: &Operator<'_>) -> Option<OverflowingOp> {
+    Some(match op {
+        Operator::I32Add | Operator::I64Add => OverflowingOp::Add,
+        Operator::I32Sub | Operator::I64Sub => OverflowingOp::Sub,
+        Operator::I32Mul | Operator::I64Mul => OverflowingOp::Mul,
+        _ => return None,
+    })
+}
+
+/// Returns the memory immediate, the size in bytes of the access and whether
+/// it's a store for each load and store traced when tracing memory accesses.
+fn traced_memory_access(op: &Operator<'_>) -> Option<(MemoryImmediate, u32, bool)> {
+    Some(match *op {
+        Operator::I32Load8S { memarg }
+        | Operator::I32Load8U { memarg }
+        | Operator::I64Load8S { memarg }
+        | Operator::I64Load8U { memarg } => (memarg, 1, false),
+        Operator::I32Load16S { memarg }
+        | Operator::I32Load16U { memarg }
+        | Operator::I64Load16S { memarg }
+        | Operator::I64Load16U { memarg } => (memarg, 2, false),
+        Operator::I32Load { memarg }
+        | Operator::F32Load { memarg }
+        | Operator::I64Load32S { memarg }
+        | Operator::I64Load32U { memarg } => (memarg, 4, false),
+        Operator::I64Load { memarg } | Operator::F64Load { memarg } => (memarg, 8, false),
+        Operator::V128Load { memarg } => (memarg, 16, false),
+        Operator::I32Store8 { memarg } | Operator::I64Store8 { memarg } => (memarg, 1, true),
+        Operator::I32Store16 { memarg } | Operator::I64Store16 { memarg } => (memarg, 2, true),
+        Operator::I32Store { memarg }
+        | Operator::F32Store { memarg }
+        | Operator::I64Store32 { memarg } => (memarg, 4, true),
+        Operator::I64Store { memarg } | Operator::F64Store { memarg } => (memarg, 8, true),
+        Operator::V128Store { memarg } => (memarg, 16, true),
+        _ => return None,
+    })
 }
 
 impl<'module_environment> TargetEnvironment for FuncEnvironment<'module_environment> {
@@ -1495,7 +2731,7 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         func: &mut ir::Function,
         index: FuncIndex,
     ) -> WasmResult<ir::FuncRef> {
-        let sig = crate::func_signature(self.isa, self.translation, self.types, index);
+        let sig = crate::func_signature(self.isa, self.module, self.types, index);
         let signature = func.import_signature(sig);
         let name = get_func_name(index);
         Ok(func.import_function(ir::ExtFuncData {
@@ -1515,8 +2751,11 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             // module there's relative calls encoded. All calls external to a
             // wasm module (e.g. imports or libcalls) are either encoded through
             // the `VMContext` as relative jumps (hence no relocations) or
-            // they're libcalls with absolute relocations.
-            colocated: self.module.defined_func_index(index).is_some(),
+            // they're libcalls with absolute relocations. Imports called
+            // through their thunks are also local, since the thunks are in
+            // the module's text section.
+            colocated: self.module.defined_func_index(index).is_some()
+                || self.calls_imports_through_thunks(),
         }))
     }
 
@@ -1532,13 +2771,12 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     ) -> WasmResult<ir::Inst> {
         let pointer_type = self.pointer_type();
 
-        // Get the anyfunc pointer (the funcref) from the table.
-        let anyfunc_ptr = self.get_or_init_funcref_table_elem(builder, table_index, table, callee);
-
-        // Check for whether the table element is null, and trap if so.
-        builder
-            .ins()
-            .trapz(anyfunc_ptr, ir::TrapCode::IndirectCallToNull);
+        let anyfunc_ptr = match self.module.call_indirect_cache(builder.srcloc().bits()) {
+            Some(cache) => {
+                self.get_cached_anyfunc(builder, cache, table_index, table, ty_index, callee)
+            }
+            None => self.get_checked_anyfunc(builder, table_index, table, ty_index, callee),
+        };
 
         // Dereference anyfunc pointer to get the function address.
         let mem_flags = ir::MemFlags::trusted();
@@ -1549,51 +2787,6 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             i32::from(self.offsets.vmcaller_checked_anyfunc_func_ptr()),
         );
 
-        // If necessary, check the signature.
-        match self.module.table_plans[table_index].style {
-            TableStyle::CallerChecksSignature => {
-                let sig_id_size = self.offsets.size_of_vmshared_signature_index();
-                let sig_id_type = Type::int(u16::from(sig_id_size) * 8).unwrap();
-                let vmctx = self.vmctx(builder.func);
-                let base = builder.ins().global_value(pointer_type, vmctx);
-
-                // Load the caller ID. This requires loading the
-                // `*mut VMCallerCheckedAnyfunc` base pointer from `VMContext`
-                // and then loading, based on `SignatureIndex`, the
-                // corresponding entry.
-                let mut mem_flags = ir::MemFlags::trusted();
-                mem_flags.set_readonly();
-                let signatures = builder.ins().load(
-                    pointer_type,
-                    mem_flags,
-                    base,
-                    i32::try_from(self.offsets.vmctx_signature_ids_array()).unwrap(),
-                );
-                let sig_index = self.module.types[ty_index].unwrap_function();
-                let offset =
-                    i32::try_from(sig_index.as_u32().checked_mul(sig_id_type.bytes()).unwrap())
-                        .unwrap();
-                let caller_sig_id = builder
-                    .ins()
-                    .load(sig_id_type, mem_flags, signatures, offset);
-
-                // Load the callee ID.
-                let mem_flags = ir::MemFlags::trusted();
-                let callee_sig_id = builder.ins().load(
-                    sig_id_type,
-                    mem_flags,
-                    anyfunc_ptr,
-                    i32::from(self.offsets.vmcaller_checked_anyfunc_type_index()),
-                );
-
-                // Check that they match.
-                let cmp = builder
-                    .ins()
-                    .icmp(IntCC::Equal, callee_sig_id, caller_sig_id);
-                builder.ins().trapz(cmp, ir::TrapCode::BadSignature);
-            }
-        }
-
         let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
         let caller_vmctx = builder
             .func
@@ -1628,6 +2821,30 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         let mut real_call_args = Vec::with_capacity(call_args.len() + 2);
         let caller_vmctx = pos.func.special_param(ArgumentPurpose::VMContext).unwrap();
 
+        // Functions compiled lazily are placed in their own text section, so
+        // they call other locally-defined functions through the module's
+        // table of compiled code, falling back to the callee's stub if it
+        // hasn't been compiled yet.
+        if let (Some(table), Some(defined)) = (
+            self.lazy_table,
+            self.module.defined_func_index(callee_index),
+        ) {
+            let pointer_type = self.pointer_type();
+            let sig_ref = pos.func.dfg.ext_funcs[callee].signature;
+            let slot = table.code + defined.index() * pointer_type.bytes() as usize;
+            let slot = pos.ins().iconst(pointer_type, slot as i64);
+            let code = pos
+                .ins()
+                .load(pointer_type, ir::MemFlags::trusted(), slot, 0);
+            let stub = pos.ins().iconst(pointer_type, table.stubs[defined] as i64);
+            let func_addr = pos.ins().select(code, code, stub);
+
+            real_call_args.push(caller_vmctx);
+            real_call_args.push(caller_vmctx);
+            real_call_args.extend_from_slice(call_args);
+            return Ok(pos.ins().call_indirect(sig_ref, func_addr, &real_call_args));
+        }
+
         // Handle direct calls to locally-defined functions.
         if !self.module.is_imported_function(callee_index) {
             // First append the callee vmctx address, which is the same as the caller vmctx in
@@ -1643,6 +2860,15 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
             return Ok(pos.ins().call(callee, &real_call_args));
         }
 
+        // Calls to imported functions through their thunks pass the caller's
+        // vmctx in place of the callee's, which the thunk loads.
+        if self.calls_imports_through_thunks() {
+            real_call_args.push(caller_vmctx);
+            real_call_args.push(caller_vmctx);
+            real_call_args.extend_from_slice(call_args);
+            return Ok(pos.ins().call(callee, &real_call_args));
+        }
+
         // Handle direct calls to imported functions. We use an indirect call
         // so that we don't have to patch the code at runtime.
         let pointer_type = self.pointer_type();
@@ -1739,13 +2965,35 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         &mut self,
         mut pos: FuncCursor,
         src_index: MemoryIndex,
-        _src_heap: ir::Heap,
+        src_heap: ir::Heap,
         dst_index: MemoryIndex,
-        _dst_heap: ir::Heap,
+        dst_heap: ir::Heap,
         dst: ir::Value,
         src: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
+        // Short copies of a constant length, such as those of structs, are
+        // performed inline. The whole source is loaded before anything is
+        // stored so that overlapping copies work, and the destination is
+        // stored from its end so that a copy running off the end of the
+        // memory traps before writing any of it.
+        if let Some(len) = self.inline_bulk_memory_len(pos.func, len) {
+            let pointer_type = self.pointer_type();
+            let dst_addr = pos.ins().heap_addr(pointer_type, dst_heap, dst, len);
+            let src_addr = pos.ins().heap_addr(pointer_type, src_heap, src, len);
+            let mut flags = ir::MemFlags::new();
+            flags.set_endianness(ir::Endianness::Little);
+            let chunks = self.bulk_memory_chunks(len);
+            let values = chunks
+                .iter()
+                .map(|&(offset, ty)| pos.ins().load(ty, flags, src_addr, offset as i32))
+                .collect::<Vec<_>>();
+            for (&(offset, _), value) in chunks.iter().zip(values).rev() {
+                pos.ins().store(flags, value, dst_addr, offset as i32);
+            }
+            return Ok(());
+        }
+
         let (vmctx, func_addr) = self
             .translate_load_builtin_function_address(&mut pos, BuiltinFunctionIndex::memory_copy());
 
@@ -1779,11 +3027,29 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         &mut self,
         mut pos: FuncCursor,
         memory_index: MemoryIndex,
-        _heap: ir::Heap,
+        heap: ir::Heap,
         dst: ir::Value,
         val: ir::Value,
         len: ir::Value,
     ) -> WasmResult<()> {
+        // Short fills of a constant length are performed inline, storing
+        // from the end as copies do.
+        if let Some(len) = self.inline_bulk_memory_len(pos.func, len) {
+            let addr = pos.ins().heap_addr(self.pointer_type(), heap, dst, len);
+            let mut flags = ir::MemFlags::new();
+            flags.set_endianness(ir::Endianness::Little);
+            let mut pattern: Option<(ir::Type, ir::Value)> = None;
+            for (offset, ty) in self.bulk_memory_chunks(len).into_iter().rev() {
+                let value = match pattern {
+                    Some((pattern_ty, value)) if pattern_ty == ty => value,
+                    _ => self.fill_pattern(&mut pos, val, ty),
+                };
+                pattern = Some((ty, value));
+                pos.ins().store(flags, value, addr, offset as i32);
+            }
+            return Ok(());
+        }
+
         let func_sig = self.builtin_function_signatures.memory_fill(&mut pos.func);
         let dst = self.cast_memory_index_to_i64(&mut pos, dst, memory_index);
         let len = self.cast_memory_index_to_i64(&mut pos, len, memory_index);
@@ -2000,6 +3266,24 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         if self.tunables.consume_fuel {
             self.fuel_before_op(op, builder, state.reachable());
         }
+        if self.tunables.count_instructions {
+            self.count_before_op(op, builder, state.reachable());
+        }
+        if self.profile_counters.is_some() || self.profile.is_some() {
+            self.profile_before_op(op, builder, state);
+        }
+        if self.tunables.guest_debug && state.reachable() {
+            self.debug_before_op(builder);
+        }
+        if self.tunables.coredump_frames {
+            self.coredump_before_op(op, builder, state.reachable());
+        }
+        if self.tunables.trace_memory_accesses && state.reachable() {
+            self.trace_before_op(op, state);
+        }
+        if self.check_integer_overflow && state.reachable() {
+            self.overflow_before_op(op, state);
+        }
         Ok(())
     }
 
@@ -2012,6 +3296,15 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         if self.tunables.consume_fuel && state.reachable() {
             self.fuel_after_op(op, builder);
         }
+        if self.tunables.trace_memory_accesses {
+            self.trace_after_op(op, builder, state);
+        }
+        if self.check_integer_overflow {
+            self.overflow_after_op(op, builder, state);
+        }
+        if self.profile.is_some() {
+            self.profile_after_op(op, builder, state);
+        }
         Ok(())
     }
 
@@ -2022,7 +3315,10 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
     ) -> WasmResult<()> {
         // If the `vmruntime_limits_ptr` variable will get used then we initialize
         // it here.
-        if self.tunables.consume_fuel || self.tunables.epoch_interruption {
+        if self.tunables.consume_fuel
+            || self.tunables.epoch_interruption
+            || self.tunables.coredump_frames
+        {
             self.declare_vmruntime_limits_ptr(builder);
         }
         // Additionally we initialize `fuel_var` if it will get used.
@@ -2033,6 +3329,15 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         if self.tunables.epoch_interruption {
             self.epoch_function_entry(builder);
         }
+        if self.tunables.guest_debug {
+            self.debug_function_entry(builder);
+        }
+        if self.tunables.coredump_frames {
+            self.coredump_function_entry(builder);
+        }
+        if let Some(counters) = self.profile_counters {
+            self.profile_count(builder, counters, None);
+        }
         Ok(())
     }
 
@@ -2044,6 +3349,9 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         if self.tunables.consume_fuel && state.reachable() {
             self.fuel_function_exit(builder);
         }
+        if self.tunables.coredump_frames && state.reachable() {
+            self.coredump_function_exit(builder);
+        }
         Ok(())
     }
 
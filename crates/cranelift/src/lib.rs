@@ -43,6 +43,13 @@
 // For more information about the tricky bits of managing the reserved stack
 // size of wasm, see the implementation in `traphandlers.rs` in the
 // `update_stack_limit` function.
+//
+// The one exception to the above is when signals-based traps are disabled.
+// Cranelift's prologue check raises its trap with an illegal instruction, so
+// instead a check of the stack limit is inserted at the start of each
+// function as regular IR, see `FuncEnvironment::insert_stack_check`, and it
+// raises its trap through a call to the runtime like all other traps in that
+// mode.
 
 use cranelift_codegen::binemit;
 use cranelift_codegen::ir;
@@ -51,11 +58,12 @@ use cranelift_entity::PrimaryMap;
 use cranelift_wasm::{DefinedFuncIndex, FuncIndex, WasmFuncType, WasmType};
 use target_lexicon::CallingConvention;
 use wasmtime_environ::{
-    FilePos, FunctionInfo, InstructionAddressMap, ModuleTranslation, TrapInformation, TypeTables,
+    FilePos, FunctionInfo, InstructionAddressMap, Module, TrapCode, TrapInformation, TypeTables,
 };
 
 pub use builder::builder;
 
+mod baseline;
 mod builder;
 mod compiler;
 mod debug;
@@ -73,6 +81,10 @@ pub struct CompiledFunction {
     /// The unwind information.
     unwind_info: Option<UnwindInfo>,
 
+    /// Whether the function was compiled by the baseline compiler and needs
+    /// the unwind information of its fixed frame layout.
+    baseline_unwind_info: bool,
+
     /// Information used to translate from binary offsets back to the original
     /// location found in the wasm input.
     address_map: FunctionAddressMap,
@@ -207,12 +219,12 @@ fn indirect_signature(isa: &dyn TargetIsa, wasm: &WasmFuncType) -> ir::Signature
 /// use a custom theoretically faster calling convention instead of the default.
 fn func_signature(
     isa: &dyn TargetIsa,
-    translation: &ModuleTranslation,
+    module: &Module,
     types: &TypeTables,
     index: FuncIndex,
 ) -> ir::Signature {
-    let func = &translation.module.functions[index];
-    let call_conv = match translation.module.defined_func_index(index) {
+    let func = &module.functions[index];
+    let call_conv = match module.defined_func_index(index) {
         // If this is a defined function in the module and it doesn't escape
         // then we can optimize this function to use the fastest calling
         // convention since it's purely an internal implementation detail of
@@ -229,6 +241,26 @@ fn func_signature(
     return sig;
 }
 
+/// Converts a Cranelift trap code to the trap code recorded by Wasmtime.
+fn wasmtime_trap_code(code: ir::TrapCode) -> TrapCode {
+    match code {
+        ir::TrapCode::StackOverflow => TrapCode::StackOverflow,
+        ir::TrapCode::HeapOutOfBounds => TrapCode::HeapOutOfBounds,
+        ir::TrapCode::HeapMisaligned => TrapCode::HeapMisaligned,
+        ir::TrapCode::TableOutOfBounds => TrapCode::TableOutOfBounds,
+        ir::TrapCode::IndirectCallToNull => TrapCode::IndirectCallToNull,
+        ir::TrapCode::BadSignature => TrapCode::BadSignature,
+        ir::TrapCode::IntegerOverflow => TrapCode::IntegerOverflow,
+        ir::TrapCode::IntegerDivisionByZero => TrapCode::IntegerDivisionByZero,
+        ir::TrapCode::BadConversionToInteger => TrapCode::BadConversionToInteger,
+        ir::TrapCode::UnreachableCodeReached => TrapCode::UnreachableCodeReached,
+        ir::TrapCode::Interrupt => TrapCode::Interrupt,
+
+        // these should never be emitted by wasmtime-cranelift
+        ir::TrapCode::User(_) => unreachable!(),
+    }
+}
+
 /// Returns the reference type to use for the provided wasm type.
 fn reference_type(wasm_ty: cranelift_wasm::WasmType, pointer_type: ir::Type) -> ir::Type {
     match wasm_ty {
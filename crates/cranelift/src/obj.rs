@@ -28,15 +28,16 @@ use object::write::{
     SymbolSection,
 };
 use object::{
-    Architecture, RelocationEncoding, RelocationKind, SectionKind, SymbolFlags, SymbolKind,
-    SymbolScope,
+    elf, Architecture, BinaryFormat, RelocationEncoding, RelocationKind, SectionKind, SymbolFlags,
+    SymbolKind, SymbolScope,
 };
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::ops::Range;
 use wasmtime_environ::obj;
 use wasmtime_environ::{
-    DefinedFuncIndex, EntityRef, FuncIndex, Module, PrimaryMap, SignatureIndex, Trampoline,
+    import_thunk, DefinedFuncIndex, EntityRef, FuncIndex, Module, PrimaryMap, SignatureIndex,
+    Trampoline, VMOffsets,
 };
 
 const TEXT_SECTION_NAME: &[u8] = b".text";
@@ -93,7 +94,7 @@ pub struct ObjectBuilder<'a> {
 
     /// Pending unwinding information for DWARF-based platforms. This is used to
     /// build a `.eh_frame` lookalike at the very end of object building.
-    systemv_unwind_info: Vec<(u64, &'a systemv::UnwindInfo)>,
+    systemv_unwind_info: Vec<(u64, SystemVUnwindInfo<'a>)>,
 
     /// The corresponding symbol for each function, inserted as they're defined.
     ///
@@ -111,6 +112,10 @@ pub struct ObjectBuilder<'a> {
     /// instructions are encoded to rely on this placement. We use this `bool`
     /// for debug assertions to ensure that we get the ordering correct.
     added_unwind_info: bool,
+
+    /// Whether calls to imported functions go through their thunks, which
+    /// are labeled after the module's functions in the text section.
+    import_thunks: bool,
 }
 
 // This is a mirror of `RUNTIME_FUNCTION` in the Windows API, but defined here
@@ -125,8 +130,21 @@ struct RUNTIME_FUNCTION {
     unwind_address: u32,
 }
 
+/// The unwinding information of a function on DWARF-based platforms.
+enum SystemVUnwindInfo<'a> {
+    Cranelift(&'a systemv::UnwindInfo),
+    /// A function of the given length compiled by the baseline compiler, whose
+    /// frames all have the same layout.
+    Baseline(u32),
+}
+
 impl<'a> ObjectBuilder<'a> {
-    pub fn new(obj: &'a mut Object<'static>, module: &'a Module, isa: &'a dyn TargetIsa) -> Self {
+    pub fn new(
+        obj: &'a mut Object<'static>,
+        module: &'a Module,
+        isa: &'a dyn TargetIsa,
+        import_thunks: bool,
+    ) -> Self {
         // Entire code (functions and trampolines) will be placed
         // in the ".text" section.
         let text_section = obj.add_section(
@@ -153,6 +171,10 @@ impl<'a> ObjectBuilder<'a> {
             func_symbols.push(symbol_id);
         }
 
+        let mut labels = module.functions.len() - module.num_imported_funcs;
+        if import_thunks {
+            labels += module.num_imported_funcs;
+        }
         Self {
             isa,
             obj,
@@ -163,9 +185,9 @@ impl<'a> ObjectBuilder<'a> {
             windows_unwind_info: Vec::new(),
             systemv_unwind_info_id: None,
             systemv_unwind_info: Vec::new(),
-            text: isa
-                .text_section_builder((module.functions.len() - module.num_imported_funcs) as u32),
+            text: isa.text_section_builder(labels as u32),
             added_unwind_info: false,
+            import_thunks,
         }
     }
 
@@ -217,10 +239,16 @@ impl<'a> ObjectBuilder<'a> {
             // System-V is different enough that we just record the unwinding
             // information to get processed at a later time.
             Some(UnwindInfo::SystemV(info)) => {
-                self.systemv_unwind_info.push((off, info));
+                self.systemv_unwind_info
+                    .push((off, SystemVUnwindInfo::Cranelift(info)));
             }
 
             Some(_) => panic!("some unwind info isn't handled here"),
+            None if func.baseline_unwind_info => {
+                let len = u32::try_from(body_len).unwrap();
+                self.systemv_unwind_info
+                    .push((off, SystemVUnwindInfo::Baseline(len)));
+            }
             None => {}
         }
 
@@ -233,13 +261,19 @@ impl<'a> ObjectBuilder<'a> {
                 // file, but if it can't handle it then we pass through the
                 // relocation.
                 RelocationTarget::UserFunc(index) => {
-                    let defined_index = self.module.defined_func_index(index).unwrap();
-                    if self.text.resolve_reloc(
-                        off + u64::from(r.offset),
-                        r.reloc,
-                        r.addend,
-                        defined_index.as_u32(),
-                    ) {
+                    let label = match self.module.defined_func_index(index) {
+                        Some(defined_index) => defined_index.as_u32(),
+                        None => {
+                            assert!(self.import_thunks);
+                            let num_defined =
+                                self.module.functions.len() - self.module.num_imported_funcs;
+                            (num_defined + index.index()) as u32
+                        }
+                    };
+                    if self
+                        .text
+                        .resolve_reloc(off + u64::from(r.offset), r.reloc, r.addend, label)
+                    {
                         continue;
                     }
 
@@ -280,6 +314,34 @@ impl<'a> ObjectBuilder<'a> {
         range
     }
 
+    /// Appends the function `index` of a module, which was compiled lazily
+    /// after the rest of the module, to this object file.
+    ///
+    /// The object file is expected to be created for an empty module, since
+    /// the function is placed on its own.
+    pub fn lazy_func(&mut self, index: FuncIndex, func: &'a CompiledFunction) -> Range<u64> {
+        assert!(!self.added_unwind_info);
+        let name = obj::func_symbol_name(index);
+        let (_, range) = self.append_func(false, name.into_bytes(), func);
+        range
+    }
+
+    /// Appends the thunk of each of the module's imported functions, which
+    /// must come just after its functions, returning their offsets in the
+    /// text section.
+    pub fn import_thunks(&mut self) -> Vec<u64> {
+        assert!(self.import_thunks && !self.added_unwind_info);
+        let offsets = VMOffsets::new(self.isa.pointer_bytes(), self.module);
+        (0..self.module.num_imported_funcs)
+            .map(|i| {
+                let import = offsets.vmctx_vmfunction_import(FuncIndex::new(i));
+                let thunk = import_thunk(self.isa.triple(), import)
+                    .expect("import thunks aren't supported on this target");
+                self.text.append(true, &thunk, None)
+            })
+            .collect()
+    }
+
     pub fn trampoline(&mut self, sig: SignatureIndex, func: &'a CompiledFunction) -> Trampoline {
         assert!(!self.added_unwind_info);
         let name = obj::trampoline_symbol_name(sig);
@@ -288,9 +350,23 @@ impl<'a> ObjectBuilder<'a> {
             signature: sig,
             start: range.start,
             length: u32::try_from(range.end - range.start).unwrap(),
+            native: None,
         }
     }
 
+    /// Appends the trampoline for `sig` which takes its arguments natively,
+    /// returning its offset in the text section and size.
+    pub fn native_trampoline(
+        &mut self,
+        sig: SignatureIndex,
+        func: &'a CompiledFunction,
+    ) -> (u64, u32) {
+        assert!(!self.added_unwind_info);
+        let name = obj::native_trampoline_symbol_name(sig);
+        let (_, range) = self.append_func(false, name.into_bytes(), func);
+        (range.start, u32::try_from(range.end - range.start).unwrap())
+    }
+
     pub fn dwarf_sections(&mut self, sections: &[DwarfSection]) -> Result<()> {
         assert!(
             self.added_unwind_info,
@@ -379,10 +455,66 @@ impl<'a> ObjectBuilder<'a> {
         if self.systemv_unwind_info.len() > 0 {
             self.append_systemv_unwind_info();
         }
+        if self.isa.is_branch_protection_enabled() {
+            self.append_gnu_property_note();
+        }
 
         Ok(())
     }
 
+    /// This function appends a `.note.gnu.property` section marking the code
+    /// as compatible with the branch protection it was compiled with, like
+    /// the sections linkers use to decide whether it's enforced for an
+    /// executable.
+    fn append_gnu_property_note(&mut self) {
+        const GNU_PROPERTY_X86_FEATURE_1_AND: u32 = 0xc000_0002;
+        const GNU_PROPERTY_X86_FEATURE_1_IBT: u32 = 1 << 0;
+        const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc000_0000;
+        const GNU_PROPERTY_AARCH64_FEATURE_1_BTI: u32 = 1 << 0;
+        const GNU_PROPERTY_AARCH64_FEATURE_1_PAC: u32 = 1 << 1;
+
+        if self.obj.format() != BinaryFormat::Elf {
+            return;
+        }
+        let (pr_type, pr_data) = match self.isa.triple().architecture {
+            target_lexicon::Architecture::X86_64 => (
+                GNU_PROPERTY_X86_FEATURE_1_AND,
+                GNU_PROPERTY_X86_FEATURE_1_IBT,
+            ),
+            target_lexicon::Architecture::Aarch64(_) => {
+                let signed = self.isa.isa_flags().iter().any(|value| {
+                    value.name == "sign_return_address" && value.as_bool() == Some(true)
+                });
+                let pac = if signed {
+                    GNU_PROPERTY_AARCH64_FEATURE_1_PAC
+                } else {
+                    0
+                };
+                (
+                    GNU_PROPERTY_AARCH64_FEATURE_1_AND,
+                    GNU_PROPERTY_AARCH64_FEATURE_1_BTI | pac,
+                )
+            }
+            _ => return,
+        };
+
+        // A single note named "GNU" with one property, padded to 8 bytes.
+        let mut note = Vec::new();
+        for word in [4, 16, elf::NT_GNU_PROPERTY_TYPE_0] {
+            note.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        note.extend_from_slice(b"GNU\0");
+        for word in [pr_type, 4, pr_data, 0] {
+            note.extend_from_slice(&u32::to_le_bytes(word));
+        }
+        let section = self.obj.add_section(
+            Vec::new(),
+            b".note.gnu.property".to_vec(),
+            SectionKind::Note,
+        );
+        self.obj.section_mut(section).set_data(note, 8);
+    }
+
     /// This function appends a nonstandard section to the object which is only
     /// used during `CodeMemory::allocate_for_object`.
     ///
@@ -473,7 +605,13 @@ impl<'a> ObjectBuilder<'a> {
             // unwinders just use this constant for a relative addition with the
             // address of the FDE, which means that the sign doesn't actually
             // matter.
-            let fde = unwind_info.to_fde(Address::Constant(actual_offset as u64));
+            let address = Address::Constant(actual_offset as u64);
+            let fde = match unwind_info {
+                SystemVUnwindInfo::Cranelift(info) => info.to_fde(address),
+                SystemVUnwindInfo::Baseline(len) => {
+                    crate::baseline::frame_description(self.isa, address, *len)
+                }
+            };
             table.add_fde(cie_id, fde);
         }
         let endian = match self.isa.triple().endianness().unwrap() {
@@ -483,12 +621,16 @@ impl<'a> ObjectBuilder<'a> {
         let mut eh_frame = EhFrame(MyVec(EndianVec::new(endian)));
         table.write_eh_frame(&mut eh_frame).unwrap();
 
+        let mut eh_frame = (eh_frame.0).0.into_vec();
+        let augmentation = self.isa.systemv_cie_augmentation();
+        if !augmentation.is_empty() {
+            extend_cie_augmentation(&mut eh_frame, endian, augmentation);
+        }
+
         // Some unwinding implementations expect a terminating "empty" length so
         // a 0 is written at the end of the table for those implementations.
-        let mut endian_vec = (eh_frame.0).0;
-        endian_vec.write_u32(0).unwrap();
-        self.obj
-            .append_section_data(section_id, endian_vec.slice(), 1);
+        eh_frame.extend_from_slice(&[0; 4]);
+        self.obj.append_section_data(section_id, &eh_frame, 1);
 
         use gimli::constants;
         use gimli::write::Error;
@@ -537,3 +679,39 @@ impl<'a> ObjectBuilder<'a> {
         }
     }
 }
+
+/// Adds `augmentation` to the augmentation string of the CIE at the start of
+/// `eh_frame`, for the characters which gimli can't write itself.
+///
+/// The characters take the place of some of the `DW_CFA_nop`s which pad the
+/// CIE to a multiple of its address size, so that nothing after them moves.
+fn extend_cie_augmentation(eh_frame: &mut [u8], endian: RunTimeEndian, augmentation: &[u8]) {
+    use gimli::UnwindSection;
+
+    let (end, padding) = {
+        let bases = gimli::BaseAddresses::default();
+        let section = gimli::EhFrame::new(eh_frame, endian);
+        let cie = section
+            .cie_from_offset(&bases, gimli::EhFrameOffset(0))
+            .expect("failed to parse the CIE");
+        let mut padding = 0;
+        let mut instructions = cie.instructions(&section, &bases);
+        while let Some(instruction) = instructions.next().unwrap() {
+            padding = match instruction {
+                gimli::CallFrameInstruction::Nop => padding + 1,
+                _ => 0,
+            };
+        }
+        (4 + cie.entry_len(), padding)
+    };
+    assert!(
+        padding >= augmentation.len(),
+        "no room in the CIE for its augmentation"
+    );
+
+    // The augmentation string follows the length, the CIE id and the version.
+    let start = 9;
+    let nul = start + eh_frame[start..].iter().position(|b| *b == 0).unwrap();
+    eh_frame.copy_within(nul..end - augmentation.len(), nul + augmentation.len());
+    eh_frame[nul..nul + augmentation.len()].copy_from_slice(augmentation);
+}
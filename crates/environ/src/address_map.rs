@@ -129,6 +129,20 @@ impl AddressMapSection {
         self.last_offset = func_end;
     }
 
+    /// Appends the instruction mappings of `other` to this section.
+    ///
+    /// Like [`TrapEncodingBuilder::extend`](crate::TrapEncodingBuilder::extend)
+    /// this allows functions to be encoded in parallel. All of the functions
+    /// of `other` must come after those already pushed.
+    pub fn extend(&mut self, other: AddressMapSection) {
+        if let Some(first) = other.offsets.first() {
+            assert!(first.get(LittleEndian) >= self.last_offset);
+        }
+        self.offsets.extend(other.offsets);
+        self.positions.extend(other.positions);
+        self.last_offset = self.last_offset.max(other.last_offset);
+    }
+
     /// Finishes encoding this section into the `Object` provided.
     pub fn append_to(self, obj: &mut Object) {
         let section = obj.add_section(
@@ -156,15 +170,7 @@ impl AddressMapSection {
 /// section of the pc that is being looked up. If `offset` is out of range or
 /// doesn't correspond to anything in this file then `None` is returned.
 pub fn lookup_file_pos(section: &[u8], offset: usize) -> Option<FilePos> {
-    let mut section = Bytes(section);
-    // NB: this matches the encoding written by `append_to` above.
-    let count = section.read::<U32Bytes<LittleEndian>>().ok()?;
-    let count = usize::try_from(count.get(LittleEndian)).ok()?;
-    let (offsets, section) =
-        object::slice_from_bytes::<U32Bytes<LittleEndian>>(section.0, count).ok()?;
-    let (positions, section) =
-        object::slice_from_bytes::<U32Bytes<LittleEndian>>(section, count).ok()?;
-    debug_assert!(section.is_empty());
+    let (offsets, positions) = parse_address_map(section)?;
 
     // First perform a binary search on the `offsets` array. This is a sorted
     // array of offsets within the text section, which is conveniently what our
@@ -189,3 +195,36 @@ pub fn lookup_file_pos(section: &[u8], offset: usize) -> Option<FilePos> {
     let pos = positions.get(index)?;
     Some(FilePos(pos.get(LittleEndian)))
 }
+
+/// Iterates over all the entries of an encoded address map section, yielding
+/// the offset within the text section at which each entry starts along with
+/// the `FilePos` it maps to.
+///
+/// Each entry covers the text up to the start of the next one. The `section`
+/// must have been created with `AddressMapSection` above, as with
+/// `lookup_file_pos`, and `None` is returned if it's malformed.
+pub fn iterate_address_map(section: &[u8]) -> Option<impl Iterator<Item = (u32, FilePos)> + '_> {
+    let (offsets, positions) = parse_address_map(section)?;
+    Some(
+        offsets
+            .iter()
+            .zip(positions)
+            .map(|(offset, pos)| (offset.get(LittleEndian), FilePos(pos.get(LittleEndian)))),
+    )
+}
+
+/// A little-endian 32-bit integer of an encoded address map section.
+type U32 = U32Bytes<LittleEndian>;
+
+/// Splits an encoded address map section into its arrays of text offsets and
+/// file positions.
+fn parse_address_map(section: &[u8]) -> Option<(&[U32], &[U32])> {
+    let mut section = Bytes(section);
+    // NB: this matches the encoding written by `append_to` above.
+    let count = section.read::<U32>().ok()?;
+    let count = usize::try_from(count.get(LittleEndian)).ok()?;
+    let (offsets, section) = object::slice_from_bytes::<U32>(section.0, count).ok()?;
+    let (positions, section) = object::slice_from_bytes::<U32>(section, count).ok()?;
+    debug_assert!(section.is_empty());
+    Some((offsets, positions))
+}
@@ -51,6 +51,25 @@ macro_rules! foreach_builtin_function {
             out_of_gas(vmctx) -> ();
             /// Invoked when we reach a new epoch.
             new_epoch(vmctx) -> (i64);
+            /// Invoked to raise a trap when signals-based traps are disabled.
+            raise_trap(vmctx, i32) -> ();
+            /// Invoked before each instruction when guest debugging is enabled.
+            debug_hook(vmctx, i32, i32, pointer, i32) -> ();
+            /// Invoked at the end of each straight-line run of instructions when
+            /// instruction counting is enabled.
+            count_instructions(vmctx, i32, i32, i32) -> ();
+            /// Invoked after each load or store when memory access tracing is
+            /// enabled.
+            trace_memory_access(vmctx, i32, i32, i64, i32, i64, i64) -> ();
+            /// Invoked when integer arithmetic overflows when integer overflow
+            /// checks are enabled.
+            integer_overflow(vmctx, i32, i32, i32, i64, i64) -> ();
+            /// Invoked the first time a function of a module compiled lazily is
+            /// called, returning the address of its compiled code.
+            lazy_compile(vmctx, i32) -> (pointer);
+            /// Invoked when a function compiled with tiered compilation becomes
+            /// hot, to recompile it with the optimizing tier.
+            tier_up(vmctx, i32) -> ();
         }
     };
 }
@@ -125,3 +144,29 @@ macro_rules! declare_indexes {
 }
 
 foreach_builtin_function!(declare_indexes);
+
+/// An integer arithmetic operation which is checked for overflow, as passed
+/// to the `integer_overflow` builtin.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum OverflowingOp {
+    /// `i32.add` or `i64.add`.
+    Add,
+    /// `i32.sub` or `i64.sub`.
+    Sub,
+    /// `i32.mul` or `i64.mul`.
+    Mul,
+}
+
+impl OverflowingOp {
+    /// Converts an operation passed to the `integer_overflow` builtin back to
+    /// an `OverflowingOp`.
+    pub fn from_u8(op: u8) -> Option<Self> {
+        match op {
+            0 => Some(Self::Add),
+            1 => Some(Self::Sub),
+            2 => Some(Self::Mul),
+            _ => None,
+        }
+    }
+}
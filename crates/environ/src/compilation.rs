@@ -2,8 +2,9 @@
 //! module.
 
 use crate::{
-    DefinedFuncIndex, FilePos, FunctionBodyData, ModuleTranslation, PrimaryMap, SignatureIndex,
-    StackMap, Tunables, TypeTables, WasmError, WasmFuncType,
+    DefinedFuncIndex, FilePos, FuncIndex, FunctionBodyData, InstructionAddressMap, Module,
+    ModuleTranslation, PrimaryMap, SignatureIndex, StackMap, TrapInformation, Tunables, TypeTables,
+    WasmError, WasmFuncType,
 };
 use anyhow::Result;
 use object::write::Object;
@@ -13,6 +14,8 @@ use std::any::Any;
 use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
 /// Information about a function, such as trap information, address map,
@@ -27,6 +30,19 @@ pub struct FunctionInfo {
     pub start: u64,
     /// The size of the compiled function, in bytes.
     pub length: u32,
+
+    /// The size of the function's stack frame, in bytes, not counting its
+    /// return address and the registers it saves.
+    pub frame_size: u32,
+    /// The size of the function's body in the wasm module, in bytes.
+    pub wasm_size: u32,
+    /// How long it took to compile the function, which is filled in by the
+    /// runtime when the whole module is compiled up front.
+    ///
+    /// This isn't serialized, so that serialized modules don't depend on how
+    /// long they took to compile.
+    #[serde(skip)]
+    pub compile_time: Option<Duration>,
 }
 
 /// Information about a compiled trampoline which the host can call to enter
@@ -41,6 +57,30 @@ pub struct Trampoline {
     pub start: u64,
     /// The size of the compiled function, in bytes.
     pub length: u32,
+
+    /// The offset in the text section and size of the trampoline which takes
+    /// the signature's arguments natively, if one was compiled.
+    pub native: Option<(u64, u32)>,
+}
+
+/// Where everything compiled for a module ended up in the object emitted by
+/// [`Compiler::emit_obj`].
+pub struct EmittedCode {
+    /// The information about each of the module's functions.
+    pub funcs: PrimaryMap<DefinedFuncIndex, FunctionInfo>,
+    /// The trampolines of each of the module's exported signatures.
+    pub trampolines: Vec<Trampoline>,
+    /// The offset in the text section of the [import thunk](crate::import_thunk())
+    /// of each of the module's imported functions, if
+    /// `Tunables::devirtualize_imports` is set, or nothing otherwise.
+    pub import_thunks: Vec<u64>,
+    /// The traps of each of the module's functions, relative to the start of
+    /// the function, which are encoded into the object by the runtime.
+    pub traps: PrimaryMap<DefinedFuncIndex, Vec<TrapInformation>>,
+    /// The address map of each of the module's functions, relative to the
+    /// start of the function, which is encoded into the object by the runtime
+    /// if `Tunables::generate_address_map` is set.
+    pub address_maps: PrimaryMap<DefinedFuncIndex, Vec<InstructionAddressMap>>,
 }
 
 /// The offset within a function of a GC safepoint, and its associated stack
@@ -74,7 +114,9 @@ pub enum CompileError {
 /// Abstract trait representing the ability to create a `Compiler` below.
 ///
 /// This is used in Wasmtime to separate compiler implementations, currently
-/// mostly used to separate Cranelift from Wasmtime itself.
+/// mostly used to separate Cranelift from Wasmtime itself. Embedders can
+/// compile with their own implementation through
+/// `wasmtime::Config::custom_compiler`.
 pub trait CompilerBuilder: Send + Sync + fmt::Debug {
     /// Like the `Clone` trait, but for the boxed trait object.
     fn clone(&self) -> Box<dyn CompilerBuilder>;
@@ -103,10 +145,41 @@ pub trait CompilerBuilder: Send + Sync + fmt::Debug {
     /// [`CompilerBuilder::set`] and [`CompilerBuilder::enable`].
     fn settings(&self) -> Vec<Setting>;
 
+    /// Reports what the compiler generates for the functions `hook` asks for
+    /// to it as they're compiled.
+    fn function_hook(&mut self, hook: Arc<dyn FunctionHook>);
+
     /// Builds a new [`Compiler`] object from this configuration.
     fn build(&self) -> Result<Box<dyn Compiler>>;
 }
 
+/// Receives what a [`Compiler`] generated for some of the functions it
+/// compiles, as configured with [`CompilerBuilder::function_hook`].
+pub trait FunctionHook: Send + Sync {
+    /// Returns whether the function `index`, which is named `name` by the
+    /// module's name section, is passed to [`FunctionHook::compiled`] once
+    /// it's compiled.
+    fn wants(&self, index: FuncIndex, name: Option<&str>) -> bool;
+
+    /// Receives a function which [`FunctionHook::wants`] asked for.
+    fn compiled(&self, function: &FunctionListing<'_>);
+}
+
+/// What a compiler generated for one function, for
+/// [`FunctionHook::compiled`].
+pub struct FunctionListing<'a> {
+    /// The index of the function within its module.
+    pub index: FuncIndex,
+    /// The function's name from the module's name section, if it has one.
+    pub name: Option<&'a str>,
+    /// The function's IR once it's been optimized, if the compiler has one.
+    pub ir: Option<&'a str>,
+    /// The function's machine code, before it's relocated.
+    pub code: &'a [u8],
+    /// The compiler's disassembly of the machine code, if it has one.
+    pub disassembly: Option<&'a str>,
+}
+
 /// Description of compiler settings returned by [`CompilerBuilder::settings`].
 #[derive(Clone, Copy, Debug)]
 pub struct Setting {
@@ -151,12 +224,24 @@ pub trait Compiler: Send + Sync {
         types: &TypeTables,
     ) -> Result<Box<dyn Any + Send>, CompileError>;
 
+    /// Compiles the trampoline used by the host to call a wasm function with
+    /// the signature `ty`.
+    ///
+    /// Like `compile_function` this is called in parallel, once for each of
+    /// a module's exported signatures, and the results are passed to
+    /// `emit_obj`.
+    fn compile_host_to_wasm_trampoline(
+        &self,
+        ty: &WasmFuncType,
+    ) -> Result<Box<dyn Any + Send>, CompileError>;
+
     /// Collects the results of compilation into an in-memory object.
     ///
     /// This function will receive the same `Box<dyn Ayn>` produced as part of
-    /// `compile_function`, as well as the general compilation environment with
-    /// the translation/types. This method is expected to populate information
-    /// in the object file such as:
+    /// `compile_function` and `compile_host_to_wasm_trampoline`, in the order
+    /// of `module.exported_signatures` for the latter, as well as the general
+    /// compilation environment with the translation/types. This method is
+    /// expected to populate information in the object file such as:
     ///
     /// * Compiled code in a `.text` section
     /// * Unwind information in Wasmtime-specific sections
@@ -165,15 +250,75 @@ pub trait Compiler: Send + Sync {
     /// * Relocations, if necessary, for the text section
     ///
     /// The final result of compilation will contain more sections inserted by
-    /// the compiler-agnostic runtime.
+    /// the compiler-agnostic runtime. This includes the sections mapping code
+    /// offsets to trap codes and to wasm offsets, which are built from the
+    /// traps and address maps returned in [`EmittedCode`].
     fn emit_obj(
         &self,
         module: &ModuleTranslation,
         types: &TypeTables,
         funcs: PrimaryMap<DefinedFuncIndex, Box<dyn Any + Send>>,
+        trampolines: Vec<Box<dyn Any + Send>>,
         tunables: &Tunables,
         obj: &mut Object<'static>,
-    ) -> Result<(PrimaryMap<DefinedFuncIndex, FunctionInfo>, Vec<Trampoline>)>;
+    ) -> Result<EmittedCode>;
+
+    /// Compiles a stub for the function `index` within `translation`, for
+    /// modules whose functions are compiled lazily.
+    ///
+    /// The stub has the same signature as the function itself. It loads the
+    /// address of the function's compiled code from the pointer-sized slot at
+    /// the address `code`, compiling the function with the `lazy_compile`
+    /// builtin first if the slot is still null, and then calls it.
+    fn compile_lazy_stub(
+        &self,
+        translation: &ModuleTranslation<'_>,
+        index: DefinedFuncIndex,
+        tunables: &Tunables,
+        types: &TypeTables,
+        code: usize,
+    ) -> Result<Box<dyn Any + Send>, CompileError>;
+
+    /// Compiles the function `index` within `module` on its first call, for
+    /// modules whose functions are compiled lazily. Its `name` is what the
+    /// module's name section calls it, if anything.
+    ///
+    /// This is the same as [`Compiler::compile_function`] except that calls to
+    /// other functions defined in `module` go through `table`, since the
+    /// function's code isn't placed in the same text section as theirs.
+    ///
+    /// If `table` has hotness counters then the function is compiled with the
+    /// compiler's baseline tier where possible, unless `optimize` is set
+    /// because the function became hot, in which case it's compiled with the
+    /// compiler's optimizing tier.
+    #[allow(clippy::too_many_arguments)]
+    fn compile_lazy_function(
+        &self,
+        module: &Module,
+        index: DefinedFuncIndex,
+        name: Option<&str>,
+        data: FunctionBodyData<'_>,
+        tunables: &Tunables,
+        types: &TypeTables,
+        table: &LazyFunctionTable,
+        optimize: bool,
+    ) -> Result<Box<dyn Any + Send>, CompileError>;
+
+    /// Collects the result of [`Compiler::compile_lazy_function`] into an
+    /// in-memory object of its own.
+    ///
+    /// The object contains the function's code in a `.text` section, followed
+    /// by its unwind information, along with its trap information and, if
+    /// enabled in `tunables`, its address map. The returned description of the
+    /// function is relative to the start of this text section.
+    fn emit_lazy_function_obj(
+        &self,
+        module: &Module,
+        index: DefinedFuncIndex,
+        func: Box<dyn Any + Send>,
+        tunables: &Tunables,
+        obj: &mut Object<'static>,
+    ) -> Result<FunctionInfo>;
 
     /// Inserts two functions for host-to-wasm and wasm-to-host trampolines into
     /// the `obj` provided.
@@ -232,6 +377,37 @@ pub trait Compiler: Send + Sync {
 
     /// Same as [`Compiler::flags`], but ISA-specific (a cranelift-ism)
     fn isa_flags(&self) -> BTreeMap<String, FlagValue>;
+
+    /// Returns whether functions are compiled with Wasmtime's single-pass
+    /// baseline compiler rather than with optimizations.
+    ///
+    /// This isn't reflected in [`Compiler::flags`] but changes the code
+    /// generated, so it's part of the key of cached artifacts.
+    fn is_baseline(&self) -> bool {
+        false
+    }
+}
+
+/// Where the code of each function of a lazily compiled module is found at
+/// runtime, used by [`Compiler::compile_lazy_function`].
+#[derive(Debug)]
+pub struct LazyFunctionTable {
+    /// The address of an array of pointer-sized slots, indexed by
+    /// `DefinedFuncIndex`, which each hold the address of a function's compiled
+    /// code, or null if the function hasn't been compiled yet.
+    pub code: usize,
+    /// The address of each function's stub, which compiles it when called.
+    pub stubs: PrimaryMap<DefinedFuncIndex, usize>,
+    /// The address of an array of `u32` counters, indexed by
+    /// `DefinedFuncIndex`, if functions are compiled with tiered compilation.
+    ///
+    /// Code compiled with the baseline tier decrements its function's counter
+    /// on entry and on each iteration of its loops, and calls the `tier_up`
+    /// builtin when the counter reaches zero.
+    pub counters: Option<usize>,
+    /// The address of the array of `u64` counters of each function, if a
+    /// profile is collected, laid out as described by [`FunctionProfile`].
+    pub profile: Option<PrimaryMap<DefinedFuncIndex, usize>>,
 }
 
 /// Value of a configured setting for a [`Compiler`]
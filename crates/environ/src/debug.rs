@@ -0,0 +1,88 @@
+//! Encoding of the locals which code compiled with guest debugging passes to
+//! the `debug_hook` builtin, and which code compiled to capture core dumps
+//! records with its frames.
+//!
+//! Before each instruction the current values of the function's locals are
+//! spilled to a stack slot, one 16-byte `ValRaw` per local, followed by one tag
+//! byte per local which records its type. The slot's address and the number of
+//! locals are then passed to the runtime.
+//!
+//! Code compiled to capture core dumps spills its locals in the same format,
+//! within a record of its frame described by `CoreDumpFrameOffsets`.
+
+use crate::WasmType;
+
+/// Returns the tag byte recorded for a local of type `ty`.
+pub fn debug_local_tag(ty: WasmType) -> u8 {
+    match ty {
+        WasmType::I32 => 0,
+        WasmType::I64 => 1,
+        WasmType::F32 => 2,
+        WasmType::F64 => 3,
+        WasmType::V128 => 4,
+        WasmType::FuncRef => 5,
+        WasmType::ExternRef => 6,
+    }
+}
+
+/// Returns the type of a local from the tag byte recorded for it by
+/// `debug_local_tag`.
+pub fn debug_local_type(tag: u8) -> Option<WasmType> {
+    Some(match tag {
+        0 => WasmType::I32,
+        1 => WasmType::I64,
+        2 => WasmType::F32,
+        3 => WasmType::F64,
+        4 => WasmType::V128,
+        5 => WasmType::FuncRef,
+        6 => WasmType::ExternRef,
+        _ => return None,
+    })
+}
+
+/// The layout of the record which each frame of code compiled to capture core
+/// dumps keeps in a stack slot.
+///
+/// The records of the frames on the stack form a list, most recent first,
+/// whose head is the `last_coredump_frame` field of `VMRuntimeLimits`. A
+/// function pushes its record on entry and pops it when returning, and before
+/// each instruction it records the instruction's offset and spills its locals
+/// to the record. A trap leaves the records of the frames it unwinds on the
+/// list, so the runtime restores its head when wasm returns to the host.
+#[derive(Debug, Copy, Clone)]
+pub struct CoreDumpFrameOffsets {
+    /// The offset of the pointer to the record of the calling frame, which is
+    /// null for the oldest frame.
+    pub prev: u32,
+    /// The offset of the frame's `*mut VMContext`.
+    pub vmctx: u32,
+    /// The offset of the index of the frame's function, as a `u32`.
+    pub func_index: u32,
+    /// The offset of the `u32` offset of the instruction the frame is
+    /// executing, from the start of its function's body in the wasm module.
+    pub func_offset: u32,
+    /// The offset of the number of the function's locals, as a `u32`.
+    pub count: u32,
+    /// The offset of the locals, which are encoded as for `debug_hook`.
+    pub locals: u32,
+}
+
+impl CoreDumpFrameOffsets {
+    /// Returns the layout of records for pointers of `pointer_size` bytes.
+    pub fn new(pointer_size: u8) -> CoreDumpFrameOffsets {
+        let pointer_size = u32::from(pointer_size);
+        CoreDumpFrameOffsets {
+            prev: 0,
+            vmctx: pointer_size,
+            func_index: 2 * pointer_size,
+            func_offset: 2 * pointer_size + 4,
+            count: 2 * pointer_size + 8,
+            locals: 2 * pointer_size + 16,
+        }
+    }
+
+    /// Returns the size of the record of a function with `count` locals.
+    pub fn size(&self, count: u32) -> u32 {
+        self.locals + count * 17
+    }
+}
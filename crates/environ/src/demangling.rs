@@ -0,0 +1,59 @@
+//! Helpers for demangling function names, and for the names which identify
+//! wasm functions wherever they're shown outside of Wasmtime.
+
+/// Demangles a single function name into a user-readable form.
+///
+/// Currently supported: Rust/C/C++ function names.
+pub fn demangle_function_name(writer: &mut impl std::fmt::Write, name: &str) -> std::fmt::Result {
+    if let Ok(demangled) = rustc_demangle::try_demangle(name) {
+        write!(writer, "{}", demangled)
+    } else if let Ok(demangled) = cpp_demangle::Symbol::new(name) {
+        write!(writer, "{}", demangled)
+    } else {
+        write!(writer, "{}", name)
+    }
+}
+
+/// Demangles a function name if it's provided, or returns a unified representation based on the
+/// function index otherwise.
+pub fn demangle_function_name_or_index(
+    writer: &mut impl std::fmt::Write,
+    name: Option<&str>,
+    func_id: usize,
+) -> std::fmt::Result {
+    match name {
+        Some(name) => demangle_function_name(writer, name),
+        None => write!(writer, "<wasm function {}>", func_id),
+    }
+}
+
+/// Writes the symbol name of a wasm function, which is how the function is
+/// named in trap backtraces, in the profiles of every profiler Wasmtime
+/// supports, in the debug information registered with GDB and by `wasmtime
+/// explore`, so that the same function can be found in all of them.
+///
+/// The symbol name is `module!function`, where `module` is `module_name`,
+/// the name of the module in its name section, or `<unknown>`, and
+/// `function` is the demangled `func_name` from the name section, or
+/// `<wasm function N>` with the function's index.
+pub fn write_symbol_name(
+    writer: &mut impl std::fmt::Write,
+    module_name: Option<&str>,
+    func_name: Option<&str>,
+    func_index: usize,
+) -> std::fmt::Result {
+    write!(writer, "{}!", module_name.unwrap_or("<unknown>"))?;
+    demangle_function_name_or_index(writer, func_name, func_index)
+}
+
+/// Returns the symbol name of a wasm function, as written by
+/// [`write_symbol_name`].
+pub fn symbol_name(
+    module_name: Option<&str>,
+    func_name: Option<&str>,
+    func_index: usize,
+) -> String {
+    let mut name = String::new();
+    write_symbol_name(&mut name, module_name, func_name, func_index).unwrap();
+    name
+}
@@ -0,0 +1,153 @@
+//! The thunks through which compiled code can call its module's imported
+//! functions, so that they can be patched to call a known import directly.
+//!
+//! A thunk is called with the same arguments as the import, but with the
+//! caller's `VMContext` in place of the callee's. The thunks compiled into a
+//! module load the import's `VMFunctionImport` from the caller's `VMContext`
+//! and jump to it, which works for any instance of the module. Once the
+//! imports of an instance are known ahead of its instantiation a copy of the
+//! module's code can have them replaced with thunks which jump straight to
+//! the import's code with its `VMContext`, without loading either from
+//! memory.
+//!
+//! Thunks don't touch the stack, so they don't need unwind information.
+
+use target_lexicon::{Architecture, OperatingSystem, Triple};
+
+/// The size in bytes of every thunk, which fits both kinds of thunk on every
+/// architecture supporting them.
+pub const IMPORT_THUNK_SIZE: usize = 40;
+
+/// Returns whether calls to imports can go through thunks on `triple`.
+pub fn import_thunks_supported(triple: &Triple) -> bool {
+    match triple.architecture {
+        Architecture::X86_64 => true,
+        Architecture::Aarch64(_) => triple.operating_system != OperatingSystem::Windows,
+        _ => false,
+    }
+}
+
+/// Returns the thunk which loads the import of the caller's `VMContext`
+/// whose `VMFunctionImport` is at `import_offset` in it and jumps to it.
+///
+/// Returns `None` if thunks aren't supported on `triple`.
+pub fn import_thunk(triple: &Triple, import_offset: u32) -> Option<[u8; IMPORT_THUNK_SIZE]> {
+    if !import_thunks_supported(triple) {
+        return None;
+    }
+    let body = import_offset.to_le_bytes();
+    let vmctx = (import_offset + 8).to_le_bytes();
+    let mut thunk = Thunk::new(triple);
+    match triple.architecture {
+        Architecture::X86_64 => {
+            // mov callee_vmctx, [caller_vmctx + vmctx]
+            // jmp [caller_vmctx + body]
+            match triple.operating_system {
+                OperatingSystem::Windows => thunk.bytes(&[0x48, 0x8b, 0x8a]),
+                _ => thunk.bytes(&[0x48, 0x8b, 0xbe]),
+            }
+            thunk.bytes(&vmctx);
+            match triple.operating_system {
+                OperatingSystem::Windows => thunk.bytes(&[0xff, 0xa2]),
+                _ => thunk.bytes(&[0xff, 0xa6]),
+            }
+            thunk.bytes(&body);
+        }
+        _ => {
+            // mov x17, #import_offset
+            // add x17, x1, x17
+            // ldp x16, x0, [x17]
+            // br x16
+            thunk.aarch64_mov(17, u64::from(import_offset), 2);
+            thunk.aarch64(0x8b11_0031);
+            thunk.aarch64(0xa940_0230);
+            thunk.aarch64(0xd61f_0200);
+        }
+    }
+    Some(thunk.finish())
+}
+
+/// Returns the thunk which calls the function whose code is at `body` with
+/// the `VMContext` `vmctx`.
+///
+/// Returns `None` if thunks aren't supported on `triple`.
+pub fn direct_import_thunk(
+    triple: &Triple,
+    body: u64,
+    vmctx: u64,
+) -> Option<[u8; IMPORT_THUNK_SIZE]> {
+    if !import_thunks_supported(triple) {
+        return None;
+    }
+    let mut thunk = Thunk::new(triple);
+    match triple.architecture {
+        Architecture::X86_64 => {
+            // movabs callee_vmctx, vmctx
+            // movabs rax, body
+            // jmp rax
+            match triple.operating_system {
+                OperatingSystem::Windows => thunk.bytes(&[0x48, 0xb9]),
+                _ => thunk.bytes(&[0x48, 0xbf]),
+            }
+            thunk.bytes(&vmctx.to_le_bytes());
+            thunk.bytes(&[0x48, 0xb8]);
+            thunk.bytes(&body.to_le_bytes());
+            thunk.bytes(&[0xff, 0xe0]);
+        }
+        _ => {
+            // mov x0, #vmctx
+            // mov x16, #body
+            // br x16
+            thunk.aarch64_mov(0, vmctx, 4);
+            thunk.aarch64_mov(16, body, 4);
+            thunk.aarch64(0xd61f_0200);
+        }
+    }
+    Some(thunk.finish())
+}
+
+/// A thunk being encoded, which is padded with traps.
+struct Thunk {
+    code: [u8; IMPORT_THUNK_SIZE],
+    len: usize,
+}
+
+impl Thunk {
+    fn new(triple: &Triple) -> Self {
+        let mut code = [0; IMPORT_THUNK_SIZE];
+        match triple.architecture {
+            // int3
+            Architecture::X86_64 => code.fill(0xcc),
+            // brk #0
+            _ => {
+                for word in code.chunks_mut(4) {
+                    word.copy_from_slice(&0xd420_0000u32.to_le_bytes());
+                }
+            }
+        }
+        Self { code, len: 0 }
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code[self.len..][..bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+    }
+
+    fn aarch64(&mut self, inst: u32) {
+        self.bytes(&inst.to_le_bytes());
+    }
+
+    /// Moves `value` into register `rd` with a `movz` followed by a `movk`
+    /// for each of the next `chunks - 1` sixteen bits of it.
+    fn aarch64_mov(&mut self, rd: u32, value: u64, chunks: u32) {
+        for hw in 0..chunks {
+            let imm16 = ((value >> (16 * hw)) & 0xffff) as u32;
+            let opcode = if hw == 0 { 0xd280_0000 } else { 0xf280_0000 };
+            self.aarch64(opcode | (hw << 21) | (imm16 << 5) | rd);
+        }
+    }
+
+    fn finish(self) -> [u8; IMPORT_THUNK_SIZE] {
+        self.code
+    }
+}
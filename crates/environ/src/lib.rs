@@ -26,9 +26,14 @@
 mod address_map;
 mod builtin;
 mod compilation;
+mod debug;
+mod demangling;
+mod import_thunks;
 mod module;
 mod module_environ;
 pub mod obj;
+mod profile;
+mod reachability;
 mod ref_bits;
 mod stack_map;
 mod trap_encoding;
@@ -38,8 +43,13 @@ mod vmoffsets;
 pub use crate::address_map::*;
 pub use crate::builtin::*;
 pub use crate::compilation::*;
+pub use crate::debug::*;
+pub use crate::demangling::*;
+pub use crate::import_thunks::*;
 pub use crate::module::*;
 pub use crate::module_environ::*;
+pub use crate::profile::*;
+pub use crate::reachability::*;
 pub use crate::ref_bits::*;
 pub use crate::stack_map::StackMap;
 pub use crate::trap_encoding::*;
@@ -1,6 +1,6 @@
 //! Data structures for representing decoded wasm modules.
 
-use crate::{ModuleTranslation, PrimaryMap, Tunables, WASM_PAGE_SIZE};
+use crate::{EntitySet, ModuleTranslation, PrimaryMap, Tunables, WASM_PAGE_SIZE};
 use cranelift_entity::{packed_option::ReservedValue, EntityRef};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
@@ -102,6 +102,8 @@ impl MemoryPlan {
 /// A WebAssembly linear memory initializer.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct MemoryInitializer {
+    /// The index of the data segment this initializer was declared as.
+    pub data_index: DataIndex,
     /// The index of a linear memory to initialize.
     pub memory_index: MemoryIndex,
     /// Optionally, a global variable giving a base index.
@@ -412,50 +414,23 @@ impl ModuleTranslation<'_> {
         let mut tables = PrimaryMap::with_capacity(self.module.table_plans.len());
         // Keep the "leftovers" for eager init.
         let mut leftovers = vec![];
+        // The tables which have had a segment left over.
+        let mut leftover_tables = EntitySet::with_capacity(self.module.table_plans.len());
 
         for segment in segments {
-            // Skip imported tables: we can't provide a preconstructed
-            // table for them, because their values depend on the
-            // imported table overlaid with whatever segments we have.
-            if self
-                .module
-                .defined_table_index(segment.table_index)
-                .is_none()
-            {
-                leftovers.push(segment.clone());
-                continue;
-            }
-
-            // If this is not a funcref table, then we can't support a
-            // pre-computed table of function indices.
-            if self.module.table_plans[segment.table_index].table.wasm_ty != WasmType::FuncRef {
-                leftovers.push(segment.clone());
-                continue;
-            }
-
-            // If the base of this segment is dynamic, then we can't
-            // include it in the statically-built array of initial
-            // contents.
-            if segment.base.is_some() {
-                leftovers.push(segment.clone());
-                continue;
-            }
-
-            // Get the end of this segment. If out-of-bounds, or too
-            // large for our dense table representation, then skip the
-            // segment.
-            let top = match segment.offset.checked_add(segment.elements.len() as u32) {
-                Some(top) => top,
-                None => {
+            let top = match self.func_table_segment_top(segment, MAX_FUNC_TABLE_SIZE) {
+                // Leftovers are applied after the table's pre-computed
+                // contents, so once a segment of a table is left over all of
+                // the later segments of that table must be too, so that they
+                // still overwrite the earlier ones.
+                Some(top) if !leftover_tables.contains(segment.table_index) => top,
+                _ => {
+                    leftover_tables.insert(segment.table_index);
                     leftovers.push(segment.clone());
                     continue;
                 }
             };
             let table_size = self.module.table_plans[segment.table_index].table.minimum;
-            if top > table_size || top > MAX_FUNC_TABLE_SIZE {
-                leftovers.push(segment.clone());
-                continue;
-            }
 
             // We can now incorporate this segment into the initializers array.
             while tables.len() <= segment.table_index.index() {
@@ -475,6 +450,38 @@ impl ModuleTranslation<'_> {
             segments: leftovers,
         };
     }
+
+    /// Returns the end of `segment` in its table if it can be included in the
+    /// table's pre-computed contents for `try_func_table_init`.
+    fn func_table_segment_top(&self, segment: &TableInitializer, max_size: u32) -> Option<u32> {
+        // Skip imported tables: we can't provide a preconstructed
+        // table for them, because their values depend on the
+        // imported table overlaid with whatever segments we have.
+        self.module.defined_table_index(segment.table_index)?;
+
+        // If this is not a funcref table, then we can't support a
+        // pre-computed table of function indices.
+        if self.module.table_plans[segment.table_index].table.wasm_ty != WasmType::FuncRef {
+            return None;
+        }
+
+        // If the base of this segment is dynamic, then we can't
+        // include it in the statically-built array of initial
+        // contents.
+        if segment.base.is_some() {
+            return None;
+        }
+
+        // Get the end of this segment. If out-of-bounds, or too
+        // large for our dense table representation, then skip the
+        // segment.
+        let top = segment.offset.checked_add(segment.elements.len() as u32)?;
+        let table_size = self.module.table_plans[segment.table_index].table.minimum;
+        if top > table_size || top > max_size {
+            return None;
+        }
+        Some(top)
+    }
 }
 
 impl Default for MemoryInitialization {
@@ -559,6 +566,7 @@ impl MemoryInitialization {
                 base,
                 offset,
                 ref data,
+                ..
             } = *initializer;
 
             // First up determine the start/end range and verify that they're
@@ -679,9 +687,42 @@ impl TablePlan {
     }
 }
 
+/// An active data or element segment, as it's declared in a module.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ActiveSegment {
+    /// A data segment, which is written to a linear memory.
+    Data {
+        /// The index of the data segment.
+        index: DataIndex,
+        /// The index of the linear memory it's written to.
+        memory_index: MemoryIndex,
+        /// Optionally, a global variable giving a base offset.
+        base: Option<GlobalIndex>,
+        /// The offset to add to the base.
+        offset: u64,
+        /// The number of bytes it contains.
+        len: u32,
+    },
+    /// An element segment, which is written to a table.
+    Elem {
+        /// The index of the element segment.
+        index: ElemIndex,
+        /// The index of the table it's written to.
+        table_index: TableIndex,
+        /// Optionally, a global variable giving a base index.
+        base: Option<GlobalIndex>,
+        /// The index to add to the base.
+        offset: u32,
+        /// The number of elements it contains.
+        len: u32,
+    },
+}
+
 /// A WebAssembly table initializer segment.
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TableInitializer {
+    /// The index of the element segment this initializer was declared as.
+    pub elem_index: ElemIndex,
     /// The index of a table to initialize.
     pub table_index: TableIndex,
     /// Optionally, a global variable giving a base index.
@@ -784,6 +825,11 @@ pub struct Module {
     /// WebAssembly linear memory initializer.
     pub memory_initialization: MemoryInitialization,
 
+    /// The active data and element segments as the module declares them,
+    /// which is kept for introspection since `table_initialization` and
+    /// `memory_initialization` may no longer have them one by one.
+    pub active_segments: Vec<ActiveSegment>,
+
     /// WebAssembly passive elements.
     pub passive_elements: Vec<Box<[FuncIndex]>>,
 
@@ -826,6 +872,40 @@ pub struct Module {
 
     /// WebAssembly global variables.
     pub globals: PrimaryMap<GlobalIndex, Global>,
+
+    /// The name and contents of each custom section in the module, in the
+    /// order they appear, except for the `name` section and DWARF sections
+    /// (`.debug_*`), which are decoded into the rest of the module instead.
+    pub custom_sections: Vec<(String, Vec<u8>)>,
+
+    /// The offsets in the wasm file of the `call_indirect` instructions which
+    /// cache their last callee in the `VMContext`, in ascending order. The
+    /// index of an instruction's offset is the index of its cache.
+    pub call_indirect_caches: Vec<u32>,
+
+    /// The defined globals which are immutable and initialized with a
+    /// constant, in ascending order. Their values are the same in all
+    /// instances, so they're kept in a single read-only block which all
+    /// instances share rather than in each `VMContext`.
+    pub shared_globals: Vec<DefinedGlobalIndex>,
+
+    /// The defined funcref tables which are never modified and whose elements
+    /// are all written by segments at constant offsets, in ascending order.
+    /// They hold the same functions in all instances, so their elements are
+    /// kept in a single read-only block which all instances share, with each
+    /// element being the offset of its anyfunc within the `VMContext` rather
+    /// than a pointer to it.
+    pub shared_tables: Vec<DefinedTableIndex>,
+}
+
+/// Where the value of a global defined by a module is kept, see
+/// `Module::defined_global_slot`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GlobalSlot {
+    /// In the `VMContext` of each instance, at this index of its globals.
+    VMContext(u32),
+    /// In the block of globals shared by all instances, at this index.
+    Shared(u32),
 }
 
 /// Initialization routines for creating an instance, encompassing imports,
@@ -850,6 +930,13 @@ impl Module {
         Module::default()
     }
 
+    /// Returns the index of the cache of the `call_indirect` instruction at
+    /// `offset` in the wasm file, if it has one.
+    pub fn call_indirect_cache(&self, offset: u32) -> Option<u32> {
+        let index = self.call_indirect_caches.binary_search(&offset).ok()?;
+        Some(u32::try_from(index).unwrap())
+    }
+
     /// Convert a `DefinedFuncIndex` into a `FuncIndex`.
     #[inline]
     pub fn func_index(&self, defined_func: DefinedFuncIndex) -> FuncIndex {
@@ -900,6 +987,15 @@ impl Module {
         index.index() < self.num_imported_tables
     }
 
+    /// Test whether the given table index is for a table whose elements are
+    /// shared by all instances, see `Module::shared_tables`.
+    pub fn is_shared_table(&self, index: TableIndex) -> bool {
+        match self.defined_table_index(index) {
+            Some(index) => self.shared_tables.binary_search(&index).is_ok(),
+            None => false,
+        }
+    }
+
     /// Convert a `DefinedMemoryIndex` into a `MemoryIndex`.
     #[inline]
     pub fn memory_index(&self, defined_memory: DefinedMemoryIndex) -> MemoryIndex {
@@ -944,6 +1040,16 @@ impl Module {
         }
     }
 
+    /// Returns where the value of the defined global `index` is kept.
+    pub fn defined_global_slot(&self, index: DefinedGlobalIndex) -> GlobalSlot {
+        match self.shared_globals.binary_search(&index) {
+            Ok(shared) => GlobalSlot::Shared(u32::try_from(shared).unwrap()),
+            Err(preceding) => {
+                GlobalSlot::VMContext(index.as_u32() - u32::try_from(preceding).unwrap())
+            }
+        }
+    }
+
     /// Test whether the given global index is for an imported global.
     #[inline]
     pub fn is_imported_global(&self, index: GlobalIndex) -> bool {
@@ -994,7 +1100,7 @@ impl Module {
 ///
 /// Note that this is shared amongst all modules coming out of a translation
 /// in the case of nested modules and the module linking proposal.
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub struct TypeTables {
     pub(crate) wasm_signatures: PrimaryMap<SignatureIndex, WasmFuncType>,
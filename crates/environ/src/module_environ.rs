@@ -1,10 +1,10 @@
 use crate::module::{
-    AnyfuncIndex, Initializer, MemoryInitialization, MemoryInitializer, MemoryPlan, Module,
-    ModuleType, TableInitializer, TablePlan, TypeTables,
+    ActiveSegment, AnyfuncIndex, Initializer, MemoryInitialization, MemoryInitializer, MemoryPlan,
+    Module, ModuleType, TableInitializer, TablePlan, TypeTables,
 };
 use crate::{
-    DataIndex, DefinedFuncIndex, ElemIndex, EntityIndex, EntityType, FuncIndex, Global,
-    GlobalIndex, GlobalInit, MemoryIndex, PrimaryMap, SignatureIndex, TableIndex,
+    DataIndex, DefinedFuncIndex, ElemIndex, EntityIndex, EntityType, FuncIndex, FunctionProfile,
+    Global, GlobalIndex, GlobalInit, MemoryIndex, PrimaryMap, SignatureIndex, TableIndex,
     TableInitialization, Tunables, TypeIndex, WasmError, WasmFuncType, WasmResult,
 };
 use cranelift_entity::packed_option::ReservedValue;
@@ -83,6 +83,10 @@ pub struct ModuleTranslation<'data> {
     /// Total size of all passive data pushed into `passive_data` so far.
     total_passive_data: u32,
 
+    /// The profile of each function from earlier executions of the module,
+    /// which guides how it's compiled, or empty if there isn't a profile.
+    pub profile: PrimaryMap<DefinedFuncIndex, FunctionProfile>,
+
     /// When we're parsing the code section this will be incremented so we know
     /// which function is currently being defined.
     code_index: u32,
@@ -178,6 +182,29 @@ impl<'data> ModuleEnvironment<'data> {
         Ok((self.result, self.types))
     }
 
+    /// Translates the sections of a wasm module which come before its code
+    /// section, which are all of `data`, validating them with `validator`.
+    ///
+    /// This is used to compile the functions of a module while its code
+    /// section is still being streamed in: the returned translation has
+    /// everything about the module which compiling its functions needs, and
+    /// `validator` can go on to validate the code section. Function names
+    /// aren't known yet as the name section comes after the code section.
+    pub fn translate_prefix(
+        mut self,
+        data: &'data [u8],
+        validator: &mut Validator,
+    ) -> WasmResult<(ModuleTranslation<'data>, TypeTables)> {
+        for payload in Parser::new(0).parse_all(data) {
+            match payload? {
+                Payload::End(_) => break,
+                payload => self.translate_payload(validator, payload)?,
+            }
+        }
+
+        Ok((self.result, self.types))
+    }
+
     fn translate_payload(
         &mut self,
         validator: &mut Validator,
@@ -213,6 +240,12 @@ impl<'data> ModuleEnvironment<'data> {
                     .collect();
                 self.result.exported_signatures.sort_unstable();
                 self.result.exported_signatures.dedup();
+
+                let immutable = self.find_immutable_tables()?;
+                self.find_shared_tables(&immutable);
+                if self.tunables.cache_call_indirects {
+                    self.find_call_indirect_caches(&immutable)?;
+                }
             }
 
             Payload::TypeSection(types) => {
@@ -353,7 +386,22 @@ impl<'data> ModuleEnvironment<'data> {
                         }
                     };
                     let ty = Global::new(ty, initializer)?;
-                    self.result.module.globals.push(ty);
+                    let shared = !ty.mutability
+                        && matches!(
+                            ty.initializer,
+                            GlobalInit::I32Const(_)
+                                | GlobalInit::I64Const(_)
+                                | GlobalInit::F32Const(_)
+                                | GlobalInit::F64Const(_)
+                                | GlobalInit::V128Const(_)
+                                | GlobalInit::RefNullConst
+                        );
+                    let module = &mut self.result.module;
+                    let index = module.globals.push(ty);
+                    if shared {
+                        let index = module.defined_global_index(index).unwrap();
+                        module.shared_globals.push(index);
+                    }
                 }
             }
 
@@ -464,7 +512,19 @@ impl<'data> ModuleEnvironment<'data> {
                                 TableInitialization::Segments { segments } => segments,
                                 TableInitialization::FuncTable { .. } => unreachable!(),
                             };
+                            let elem_index = ElemIndex::from_u32(index as u32);
+                            self.result
+                                .module
+                                .active_segments
+                                .push(ActiveSegment::Elem {
+                                    index: elem_index,
+                                    table_index,
+                                    base,
+                                    offset,
+                                    len: elements.len() as u32,
+                                });
                             table_segments.push(TableInitializer {
+                                elem_index,
                                 table_index,
                                 base,
                                 offset,
@@ -579,7 +639,19 @@ impl<'data> ModuleEnvironment<'data> {
                                 }
                             };
 
+                            let data_index = DataIndex::from_u32(index as u32);
+                            self.result
+                                .module
+                                .active_segments
+                                .push(ActiveSegment::Data {
+                                    index: data_index,
+                                    memory_index,
+                                    base,
+                                    offset,
+                                    len: range.end - range.start,
+                                });
                             initializers.push(MemoryInitializer {
+                                data_index,
                                 memory_index,
                                 base,
                                 offset,
@@ -650,6 +722,7 @@ and for re-adding support for interface types you can see this issue:
 
             Payload::CustomSection { name, data, .. } => {
                 self.register_dwarf_section(name, data);
+                self.register_custom_section(name, data);
             }
 
             // It's expected that validation will probably reject other
@@ -664,6 +737,18 @@ and for re-adding support for interface types you can see this issue:
         Ok(())
     }
 
+    fn register_custom_section(&mut self, name: &str, data: &'data [u8]) {
+        // DWARF is translated on its own, if at all, and tends to be large, so
+        // it isn't kept around in its raw form.
+        if name.starts_with(".debug_") {
+            return;
+        }
+        self.result
+            .module
+            .custom_sections
+            .push((name.to_string(), data.to_vec()));
+    }
+
     fn register_dwarf_section(&mut self, name: &str, data: &'data [u8]) {
         if !name.starts_with(".debug_") {
             return;
@@ -749,6 +834,97 @@ and for re-adding support for interface types you can see this issue:
         }
     }
 
+    /// Finds the tables which only element segments initialize, returning
+    /// whether each table of the module is one of them. Such a table is
+    /// defined by the module, isn't exported and isn't modified by any of the
+    /// module's code, so it holds the same function at an index for as long
+    /// as its instance exists.
+    fn find_immutable_tables(&self) -> WasmResult<Vec<bool>> {
+        let module = &self.result.module;
+        let mut immutable = module
+            .table_plans
+            .keys()
+            .map(|table| !module.is_imported_table(table))
+            .collect::<Vec<_>>();
+        for export in module.exports.values() {
+            if let EntityIndex::Table(table) = export {
+                immutable[table.as_u32() as usize] = false;
+            }
+        }
+        if !immutable.contains(&true) {
+            return Ok(immutable);
+        }
+        for (_, input) in &self.result.function_body_inputs {
+            let mut reader = input.body.get_operators_reader()?;
+            while !reader.eof() {
+                match reader.read()? {
+                    Operator::TableSet { table }
+                    | Operator::TableGrow { table }
+                    | Operator::TableFill { table }
+                    | Operator::TableInit { table, .. }
+                    | Operator::TableCopy {
+                        dst_table: table, ..
+                    } => immutable[table as usize] = false,
+                    _ => {}
+                }
+            }
+        }
+        Ok(immutable)
+    }
+
+    /// Finds the tables whose elements all instances of the module can
+    /// share, which are the `immutable` funcref tables whose segments all
+    /// have constant offsets and fit within the table's initial size. Every
+    /// instance writes the same functions into them, and nothing writes to
+    /// them after that.
+    fn find_shared_tables(&mut self, immutable: &[bool]) {
+        let module = &mut self.result.module;
+        let mut shared = immutable.to_vec();
+        for (table, plan) in &module.table_plans {
+            if plan.table.wasm_ty != crate::WasmType::FuncRef || plan.table.minimum == 0 {
+                shared[table.index()] = false;
+            }
+        }
+        let segments = match &module.table_initialization {
+            TableInitialization::Segments { segments } => segments,
+            TableInitialization::FuncTable { .. } => unreachable!(),
+        };
+        for segment in segments {
+            let minimum = module.table_plans[segment.table_index].table.minimum;
+            let top = u64::from(segment.offset) + segment.elements.len() as u64;
+            if segment.base.is_some() || top > u64::from(minimum) {
+                shared[segment.table_index.index()] = false;
+            }
+        }
+
+        module.shared_tables = shared
+            .iter()
+            .enumerate()
+            .filter(|(_, shared)| **shared)
+            .map(|(table, _)| module.defined_table_index(TableIndex::new(table)).unwrap())
+            .collect();
+    }
+
+    /// Finds the `call_indirect` instructions which can cache their last
+    /// callee, which are those calling through one of the `immutable`
+    /// tables, see `find_immutable_tables`.
+    fn find_call_indirect_caches(&mut self, immutable: &[bool]) -> WasmResult<()> {
+        let module = &mut self.result.module;
+        for (_, input) in &self.result.function_body_inputs {
+            let mut reader = input.body.get_operators_reader()?;
+            while !reader.eof() {
+                if let (Operator::CallIndirect { table_index, .. }, offset) =
+                    reader.read_with_offset()?
+                {
+                    if immutable[table_index as usize] {
+                        module.call_indirect_caches.push(offset as u32);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
     fn flag_func_escaped(&mut self, func: FuncIndex) {
         let ty = &mut self.result.module.functions[func];
         // If this was already assigned an anyfunc index no need to re-assign it.
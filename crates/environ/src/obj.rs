@@ -5,6 +5,7 @@ use crate::{EntityRef, FuncIndex, SignatureIndex};
 
 const FUNCTION_PREFIX: &str = "_wasm_function_";
 const TRAMPOLINE_PREFIX: &str = "_trampoline_";
+const NATIVE_TRAMPOLINE_PREFIX: &str = "_native_trampoline_";
 
 /// Returns the symbol name in an object file for the corresponding wasm
 /// function index in a module.
@@ -25,6 +26,12 @@ pub fn trampoline_symbol_name(index: SignatureIndex) -> String {
     format!("{}{}", TRAMPOLINE_PREFIX, index.index())
 }
 
+/// Returns the symbol name in an object file for the corresponding trampoline
+/// which takes its arguments natively for the given signature in a module.
+pub fn native_trampoline_symbol_name(index: SignatureIndex) -> String {
+    format!("{}{}", NATIVE_TRAMPOLINE_PREFIX, index.index())
+}
+
 /// Attempts to extract the corresponding signature index from a symbol
 /// possibly produced by `trampoline_symbol_name`.
 pub fn try_parse_trampoline_name(name: &str) -> Option<SignatureIndex> {
@@ -0,0 +1,80 @@
+//! Profiles of earlier executions of a module's functions, which guide how
+//! they're compiled.
+
+use crate::WasmResult;
+use serde::{Deserialize, Serialize};
+use wasmparser::{FunctionBody, Operator};
+
+/// What was recorded about one function while collecting a profile.
+///
+/// While a profile is collected each function has an array of `u64`
+/// counters: how many times it was called, followed by the
+/// [`BranchProfile`] of each of its branches, as the number of times it was
+/// executed followed by the number of those times it was taken.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionProfile {
+    /// How many times the function was called.
+    pub calls: u64,
+    /// The counts of each `if` and `br_if` in the function, in the order they
+    /// appear in its body.
+    pub branches: Vec<BranchProfile>,
+}
+
+/// How often one of a function's `if` or `br_if` instructions went either way.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BranchProfile {
+    /// How many times the instruction was executed.
+    pub executed: u64,
+    /// How many of those times its condition was nonzero, so that a `br_if`
+    /// branched or an `if` ran its consequent.
+    pub taken: u64,
+}
+
+impl BranchProfile {
+    /// Returns whether the branch was taken rarely enough for the path it
+    /// takes to be laid out as cold.
+    pub fn taken_is_cold(&self) -> bool {
+        self.executed > 0 && self.taken.saturating_mul(100) <= self.executed
+    }
+
+    /// Returns whether the branch was taken often enough for the path it
+    /// falls through to be laid out as cold.
+    pub fn not_taken_is_cold(&self) -> bool {
+        self.executed > 0 && (self.executed - self.taken).saturating_mul(100) <= self.executed
+    }
+}
+
+impl FunctionProfile {
+    /// Returns the number of `u64` counters a function with `branches`
+    /// branches has while collecting a profile.
+    pub fn num_counters(branches: usize) -> usize {
+        1 + 2 * branches
+    }
+
+    /// Reads a function's profile back from its `counters`.
+    pub fn from_counters(counters: &[u64]) -> Self {
+        Self {
+            calls: counters[0],
+            branches: counters[1..]
+                .chunks(2)
+                .map(|pair| BranchProfile {
+                    executed: pair[0],
+                    taken: pair[1],
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Returns the number of branches of `body` which are profiled, which are
+/// all of its `if` and `br_if` instructions.
+pub fn count_profiled_branches(body: &FunctionBody<'_>) -> WasmResult<usize> {
+    let mut reader = body.get_operators_reader()?;
+    let mut branches = 0;
+    while !reader.eof() {
+        if let Operator::If { .. } | Operator::BrIf { .. } = reader.read()? {
+            branches += 1;
+        }
+    }
+    Ok(branches)
+}
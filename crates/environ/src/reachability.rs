@@ -0,0 +1,53 @@
+//! Finding the functions of a module which can never be called, so that
+//! compiling them can be skipped.
+
+use crate::{DefinedFuncIndex, EntitySet, FuncIndex, ModuleTranslation, WasmResult};
+use wasmparser::Operator;
+
+/// Returns the defined functions of `translation` which can't be called
+/// however the module is used.
+///
+/// Functions which escape the module, by being exported, being its start
+/// function, appearing in an element segment or being referenced by
+/// `ref.func`, can be called from outside of the code of the module, so they
+/// and every function they directly call are reachable. Functions which only
+/// escape through `ref.func`, which must be declared by an element segment,
+/// are therefore covered by the element segments, and the functions of a
+/// table are all reachable whether or not anything calls through it.
+pub fn unreachable_functions(
+    translation: &ModuleTranslation<'_>,
+) -> WasmResult<Vec<DefinedFuncIndex>> {
+    let module = &translation.module;
+    let mut reachable = EntitySet::with_capacity(translation.function_body_inputs.len());
+    let mut worklist = module
+        .functions
+        .iter()
+        .filter(|(_, f)| f.is_escaping())
+        .filter_map(|(index, _)| module.defined_func_index(index))
+        .collect::<Vec<_>>();
+    while let Some(index) = worklist.pop() {
+        if !reachable.insert(index) {
+            continue;
+        }
+        let body = &translation.function_body_inputs[index].body;
+        let mut reader = body.get_operators_reader()?;
+        while !reader.eof() {
+            let callee = match reader.read()? {
+                Operator::Call { function_index } | Operator::ReturnCall { function_index } => {
+                    function_index
+                }
+                _ => continue,
+            };
+            if let Some(callee) = module.defined_func_index(FuncIndex::from_u32(callee)) {
+                if !reachable.contains(callee) {
+                    worklist.push(callee);
+                }
+            }
+        }
+    }
+    Ok(translation
+        .function_body_inputs
+        .keys()
+        .filter(|index| !reachable.contains(*index))
+        .collect())
+}
@@ -101,6 +101,38 @@ pub enum TrapCode {
     // if adding a variant here be sure to update the `check!` macro below
 }
 
+impl TrapCode {
+    /// Converts the byte representation of a trap code, `code as u8`, back
+    /// into a `TrapCode`.
+    pub fn from_u8(trap: u8) -> Option<TrapCode> {
+        // FIXME: this could use some sort of derive-like thing to avoid having to
+        // deduplicate the names here.
+        //
+        // This simply converts from the `trap`, a `u8`, to the `TrapCode` enum.
+        macro_rules! check {
+            ($($name:ident)*) => ($(if trap == TrapCode::$name as u8 {
+                return Some(TrapCode::$name);
+            })*);
+        }
+
+        check! {
+            StackOverflow
+            HeapOutOfBounds
+            HeapMisaligned
+            TableOutOfBounds
+            IndirectCallToNull
+            BadSignature
+            IntegerOverflow
+            IntegerDivisionByZero
+            BadConversionToInteger
+            UnreachableCodeReached
+            Interrupt
+        }
+
+        None
+    }
+}
+
 impl TrapEncodingBuilder {
     /// Appends trap information about a function into this section.
     ///
@@ -136,6 +168,20 @@ impl TrapEncodingBuilder {
         self.last_offset = func_end;
     }
 
+    /// Appends the traps of `other` to this section.
+    ///
+    /// This allows the traps of functions to be encoded in parallel, each
+    /// into its own builder, which are then concatenated in order. All of
+    /// the functions of `other` must come after those already pushed.
+    pub fn extend(&mut self, other: TrapEncodingBuilder) {
+        if let Some(first) = other.offsets.first() {
+            assert!(first.get(LittleEndian) >= self.last_offset);
+        }
+        self.offsets.extend(other.offsets);
+        self.traps.extend(other.traps);
+        self.last_offset = self.last_offset.max(other.last_offset);
+    }
+
     /// Encodes this section into the object provided.
     pub fn append_to(self, obj: &mut Object) {
         let section = obj.add_section(
@@ -183,33 +229,9 @@ pub fn lookup_trap_code(section: &[u8], offset: usize) -> Option<TrapCode> {
     debug_assert!(index < traps.len());
     let trap = *traps.get(index)?;
 
-    // FIXME: this could use some sort of derive-like thing to avoid having to
-    // deduplicate the names here.
-    //
-    // This simply converts from the `trap`, a `u8`, to the `TrapCode` enum.
-    macro_rules! check {
-        ($($name:ident)*) => ($(if trap == TrapCode::$name as u8 {
-            return Some(TrapCode::$name);
-        })*);
-    }
-
-    check! {
-        StackOverflow
-        HeapOutOfBounds
-        HeapMisaligned
-        TableOutOfBounds
-        IndirectCallToNull
-        BadSignature
-        IntegerOverflow
-        IntegerDivisionByZero
-        BadConversionToInteger
-        UnreachableCodeReached
-        Interrupt
-    }
-
-    if cfg!(debug_assertions) {
-        panic!("missing mapping for {}", trap);
-    } else {
-        None
+    match TrapCode::from_u8(trap) {
+        Some(code) => Some(code),
+        None if cfg!(debug_assertions) => panic!("missing mapping for {}", trap),
+        None => None,
     }
 }
@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
 
 /// Tunable parameters for WebAssembly compilation.
 #[derive(Clone, Hash, Serialize, Deserialize)]
@@ -41,6 +42,73 @@ pub struct Tunables {
     /// Indicates whether an address map from compiled native code back to wasm
     /// offsets in the original file is generated.
     pub generate_address_map: bool,
+
+    /// Whether or not generated code may rely on signals, such as faults on
+    /// guard pages or illegal instructions, to raise traps. When disabled all
+    /// traps are raised by calling into the runtime instead.
+    pub signals_based_traps: bool,
+
+    /// Whether or not generated code calls into the runtime before each
+    /// instruction, passing it the values of the function's locals, so that
+    /// execution can be paused at breakpoints or stepped through.
+    pub guest_debug: bool,
+
+    /// Whether or not generated code keeps a record of each of its frames,
+    /// with the values of the function's locals, where the runtime can find it
+    /// when a trap happens, so that core dumps can be captured.
+    pub coredump_frames: bool,
+
+    /// Whether or not generated code reports to the runtime how many
+    /// instructions it executed in each function.
+    pub count_instructions: bool,
+
+    /// Whether or not generated code reports each load from and store to
+    /// linear memory to the runtime.
+    pub trace_memory_accesses: bool,
+
+    /// Whether or not generated code checks integer additions, subtractions
+    /// and multiplications for signed overflow, calling into the runtime when
+    /// they overflow.
+    pub check_integer_overflow: bool,
+
+    /// The names of functions whose integer arithmetic isn't checked for
+    /// overflow when `check_integer_overflow` is enabled.
+    pub integer_overflow_allowlist: BTreeSet<String>,
+
+    /// Whether or not functions are compiled lazily, the first time they're
+    /// called, instead of when their module is created.
+    pub lazy_compilation: bool,
+
+    /// Whether or not functions compiled lazily count their calls and which
+    /// way their branches go, collecting a profile of the module.
+    pub collect_profile: bool,
+
+    /// Whether or not functions are first compiled with a compiler's baseline
+    /// tier, and recompiled with its optimizing tier once they're hot.
+    pub tiered_compilation: bool,
+
+    /// Whether or not the trampolines entering wasm keep the store's
+    /// `VMRuntimeLimits` pointer in the pinned register, which generated code
+    /// reads instead of loading it from its `VMContext`.
+    pub pinned_context_register: bool,
+
+    /// Whether or not functions which can't be called are compiled to a stub
+    /// which traps instead of from their bodies, when compiling up front.
+    pub skip_unreachable_functions: bool,
+
+    /// Whether or not calls to imported functions go through a thunk in the
+    /// module's text section, which can be patched to call a known import
+    /// directly.
+    pub devirtualize_imports: bool,
+
+    /// The largest constant length, in bytes, of a `memory.copy` or
+    /// `memory.fill` which generated code performs inline rather than by
+    /// calling into the runtime.
+    pub bulk_memory_inline_threshold: u32,
+
+    /// Whether or not `call_indirect` instructions calling through a table
+    /// which is never modified cache their last callee in the `VMContext`.
+    pub cache_call_indirects: bool,
 }
 
 impl Default for Tunables {
@@ -86,6 +154,21 @@ impl Default for Tunables {
             static_memory_bound_is_maximum: false,
             guard_before_linear_memory: true,
             generate_address_map: true,
+            signals_based_traps: true,
+            guest_debug: false,
+            coredump_frames: false,
+            count_instructions: false,
+            trace_memory_accesses: false,
+            check_integer_overflow: false,
+            integer_overflow_allowlist: BTreeSet::new(),
+            lazy_compilation: false,
+            collect_profile: false,
+            tiered_compilation: false,
+            pinned_context_register: false,
+            skip_unreachable_functions: false,
+            devirtualize_imports: false,
+            bulk_memory_inline_threshold: 64,
+            cache_call_indirects: false,
         }
     }
 }
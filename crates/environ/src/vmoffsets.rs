@@ -9,19 +9,21 @@
 //      store: *mut dyn Store,
 //      builtins: *mut VMBuiltinFunctionsArray,
 //      signature_ids: *const VMSharedSignatureIndex,
+//      shared_globals: *const VMGlobalDefinition,
 //      imported_functions: [VMFunctionImport; module.num_imported_functions],
 //      imported_tables: [VMTableImport; module.num_imported_tables],
 //      imported_memories: [VMMemoryImport; module.num_imported_memories],
 //      imported_globals: [VMGlobalImport; module.num_imported_globals],
 //      tables: [VMTableDefinition; module.num_defined_tables],
 //      memories: [VMMemoryDefinition; module.num_defined_memories],
-//      globals: [VMGlobalDefinition; module.num_defined_globals],
+//      globals: [VMGlobalDefinition; module.num_defined_globals - module.shared_globals.len()],
 //      anyfuncs: [VMCallerCheckedAnyfunc; module.num_escaped_funcs],
+//      call_indirect_caches: [VMCallIndirectCache; module.call_indirect_caches.len()],
 // }
 
 use crate::{
-    AnyfuncIndex, DefinedGlobalIndex, DefinedMemoryIndex, DefinedTableIndex, FuncIndex,
-    GlobalIndex, MemoryIndex, Module, TableIndex,
+    AnyfuncIndex, DefinedMemoryIndex, DefinedTableIndex, FuncIndex, GlobalIndex, MemoryIndex,
+    Module, TableIndex,
 };
 use cranelift_entity::packed_option::ReservedValue;
 use more_asserts::assert_lt;
@@ -67,11 +69,15 @@ pub struct VMOffsets<P> {
     pub num_defined_tables: u32,
     /// The number of defined memories in the module.
     pub num_defined_memories: u32,
-    /// The number of defined globals in the module.
+    /// The number of defined globals in the module which are kept in the
+    /// `VMContext`, rather than shared by all instances.
     pub num_defined_globals: u32,
     /// The number of escaped functions in the module, the size of the anyfuncs
     /// array.
     pub num_escaped_funcs: u32,
+    /// The number of `call_indirect` instructions in the module which cache
+    /// their last callee.
+    pub num_call_indirect_caches: u32,
 
     // precalculated offsets of various member fields
     runtime_limits: u32,
@@ -80,6 +86,7 @@ pub struct VMOffsets<P> {
     store: u32,
     builtin_functions: u32,
     signature_ids: u32,
+    shared_globals: u32,
     imported_functions: u32,
     imported_tables: u32,
     imported_memories: u32,
@@ -88,6 +95,7 @@ pub struct VMOffsets<P> {
     defined_memories: u32,
     defined_globals: u32,
     defined_anyfuncs: u32,
+    call_indirect_caches: u32,
     size: u32,
 }
 
@@ -133,11 +141,15 @@ pub struct VMOffsetsFields<P> {
     pub num_defined_tables: u32,
     /// The number of defined memories in the module.
     pub num_defined_memories: u32,
-    /// The number of defined globals in the module.
+    /// The number of defined globals in the module which are kept in the
+    /// `VMContext`, rather than shared by all instances.
     pub num_defined_globals: u32,
     /// The numbe of escaped functions in the module, the size of the anyfunc
     /// array.
     pub num_escaped_funcs: u32,
+    /// The number of `call_indirect` instructions in the module which cache
+    /// their last callee.
+    pub num_call_indirect_caches: u32,
 }
 
 impl<P: PtrSize> VMOffsets<P> {
@@ -152,8 +164,9 @@ impl<P: PtrSize> VMOffsets<P> {
             num_defined_functions: cast_to_u32(module.functions.len()),
             num_defined_tables: cast_to_u32(module.table_plans.len()),
             num_defined_memories: cast_to_u32(module.memory_plans.len()),
-            num_defined_globals: cast_to_u32(module.globals.len()),
+            num_defined_globals: cast_to_u32(module.globals.len() - module.shared_globals.len()),
             num_escaped_funcs: cast_to_u32(module.num_escaped_funcs),
+            num_call_indirect_caches: cast_to_u32(module.call_indirect_caches.len()),
         })
     }
 
@@ -183,6 +196,7 @@ impl<P: PtrSize> VMOffsets<P> {
                     num_defined_memories: _,
                     num_defined_functions: _,
                     num_escaped_funcs: _,
+                    num_call_indirect_caches: _,
 
                     // used as the initial size below
                     size,
@@ -208,6 +222,7 @@ impl<P: PtrSize> VMOffsets<P> {
         }
 
         calculate_sizes! {
+            call_indirect_caches: "call_indirect caches",
             defined_anyfuncs: "module functions",
             defined_globals: "defined globals",
             defined_memories: "defined memories",
@@ -216,6 +231,7 @@ impl<P: PtrSize> VMOffsets<P> {
             imported_memories: "imported memories",
             imported_tables: "imported tables",
             imported_functions: "imported functions",
+            shared_globals: "module shared globals",
             signature_ids: "module types",
             builtin_functions: "jit builtin functions state",
             store: "jit store state",
@@ -239,12 +255,14 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             num_defined_memories: fields.num_defined_memories,
             num_defined_globals: fields.num_defined_globals,
             num_escaped_funcs: fields.num_escaped_funcs,
+            num_call_indirect_caches: fields.num_call_indirect_caches,
             runtime_limits: 0,
             epoch_ptr: 0,
             externref_activations_table: 0,
             store: 0,
             builtin_functions: 0,
             signature_ids: 0,
+            shared_globals: 0,
             imported_functions: 0,
             imported_tables: 0,
             imported_memories: 0,
@@ -253,6 +271,7 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             defined_memories: 0,
             defined_globals: 0,
             defined_anyfuncs: 0,
+            call_indirect_caches: 0,
             size: 0,
         };
 
@@ -292,6 +311,7 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
             size(store) = ret.ptr.size() * 2,
             size(builtin_functions) = ret.pointer_size(),
             size(signature_ids) = ret.ptr.size(),
+            size(shared_globals) = ret.ptr.size(),
             size(imported_functions)
                 = cmul(ret.num_imported_functions, ret.size_of_vmfunction_import()),
             size(imported_tables)
@@ -311,6 +331,10 @@ impl<P: PtrSize> From<VMOffsetsFields<P>> for VMOffsets<P> {
                 ret.num_escaped_funcs,
                 ret.size_of_vmcaller_checked_anyfunc(),
             ),
+            size(call_indirect_caches) = cmul(
+                ret.num_call_indirect_caches,
+                ret.size_of_vmcall_indirect_cache(),
+            ),
         }
 
         ret.size = next_field_offset;
@@ -502,6 +526,13 @@ impl<P: PtrSize> VMOffsets<P> {
     pub fn vmruntime_limits_epoch_deadline(&self) -> u8 {
         self.pointer_size() + 8 // `stack_limit` is a pointer; `fuel_consumed` is an `i64`
     }
+
+    /// Return the offset of the `last_coredump_frame` field of
+    /// `VMRuntimeLimits`
+    #[inline]
+    pub fn vmruntime_limits_last_coredump_frame(&self) -> u8 {
+        self.pointer_size() + 16 // `epoch_deadline` is a `u64`
+    }
 }
 
 /// Offsets for `VMCallerCheckedAnyfunc`.
@@ -533,6 +564,27 @@ impl<P: PtrSize> VMOffsets<P> {
     }
 }
 
+/// Offsets for `VMCallIndirectCache`.
+impl<P: PtrSize> VMOffsets<P> {
+    /// The offset of the `index` field.
+    #[inline]
+    pub fn vmcall_indirect_cache_index(&self) -> u8 {
+        0
+    }
+
+    /// The offset of the `anyfunc` field.
+    #[inline]
+    pub fn vmcall_indirect_cache_anyfunc(&self) -> u8 {
+        self.pointer_size()
+    }
+
+    /// Return the size of `VMCallIndirectCache`.
+    #[inline]
+    pub fn size_of_vmcall_indirect_cache(&self) -> u8 {
+        2 * self.pointer_size()
+    }
+}
+
 /// Offsets for `VMContext`.
 impl<P: PtrSize> VMOffsets<P> {
     /// Return the offset to the `VMRuntimeLimits` structure
@@ -566,6 +618,12 @@ impl<P: PtrSize> VMOffsets<P> {
         self.signature_ids
     }
 
+    /// The offset of the pointer to the globals shared by all instances.
+    #[inline]
+    pub fn vmctx_shared_globals(&self) -> u32 {
+        self.shared_globals
+    }
+
     /// The offset of the `tables` array.
     #[allow(clippy::erasing_op)]
     #[inline]
@@ -616,6 +674,12 @@ impl<P: PtrSize> VMOffsets<P> {
         self.defined_anyfuncs
     }
 
+    /// The offset of the `call_indirect_caches` array.
+    #[inline]
+    pub fn vmctx_call_indirect_caches_begin(&self) -> u32 {
+        self.call_indirect_caches
+    }
+
     /// The offset of the builtin functions array.
     #[inline]
     pub fn vmctx_builtin_functions(&self) -> u32 {
@@ -674,11 +738,20 @@ impl<P: PtrSize> VMOffsets<P> {
         self.vmctx_memories_begin() + index.as_u32() * u32::from(self.size_of_vmmemory_definition())
     }
 
-    /// Return the offset to the `VMGlobalDefinition` index `index`.
+    /// Return the offset to the `VMGlobalDefinition` index `index` of those
+    /// kept in the `VMContext`, see `GlobalSlot::VMContext`.
+    #[inline]
+    pub fn vmctx_vmglobal_definition(&self, index: u32) -> u32 {
+        assert_lt!(index, self.num_defined_globals);
+        self.vmctx_globals_begin() + index * u32::from(self.size_of_vmglobal_definition())
+    }
+
+    /// Return the offset to the `VMGlobalDefinition` index `index` of those
+    /// shared by all instances from the pointer to them, see
+    /// `GlobalSlot::Shared`.
     #[inline]
-    pub fn vmctx_vmglobal_definition(&self, index: DefinedGlobalIndex) -> u32 {
-        assert_lt!(index.as_u32(), self.num_defined_globals);
-        self.vmctx_globals_begin() + index.as_u32() * u32::from(self.size_of_vmglobal_definition())
+    pub fn shared_vmglobal_definition(&self, index: u32) -> u32 {
+        index * u32::from(self.size_of_vmglobal_definition())
     }
 
     /// Return the offset to the `VMCallerCheckedAnyfunc` for the given function
@@ -691,6 +764,14 @@ impl<P: PtrSize> VMOffsets<P> {
             + index.as_u32() * u32::from(self.size_of_vmcaller_checked_anyfunc())
     }
 
+    /// Return the offset to the `VMCallIndirectCache` index `index`.
+    #[inline]
+    pub fn vmctx_call_indirect_cache(&self, index: u32) -> u32 {
+        assert_lt!(index, self.num_call_indirect_caches);
+        self.vmctx_call_indirect_caches_begin()
+            + index * u32::from(self.size_of_vmcall_indirect_cache())
+    }
+
     /// Return the offset to the `body` field in `*const VMFunctionBody` index `index`.
     #[inline]
     pub fn vmctx_vmfunction_import_body(&self, index: FuncIndex) -> u32 {
@@ -691,14 +691,18 @@ pub fn table_ops(mut fuzz_config: generators::Config, ops: generators::table_ops
     }
 }
 
-/// Perform differential execution between Cranelift and wasmi, diffing the
-/// resulting memory image when execution terminates. This relies on the
-/// module-under-test to be instrumented to bound the execution time. Invoke
-/// with a module generated by `wasm-smith` using the
-/// `SingleFunctionModuleConfig` configuration type for best results.
+/// Perform differential execution between Cranelift and wasmi, calling each
+/// exported function in turn and diffing what it returns, or how it traps,
+/// followed by the final contents of each exported memory and the values of
+/// each exported global. This relies on the module-under-test to be
+/// instrumented to bound the execution time. Invoke with a module generated by
+/// `wasm-smith` using the `SingleFunctionModuleConfig` configuration type for
+/// best results.
 ///
 /// May return `None` if we early-out due to a rejected fuzz config; these
-/// should be rare if modules are generated appropriately.
+/// should be rare if modules are generated appropriately. Since the engines'
+/// stack limits differ, the comparison also stops once either of them
+/// overflows its stack.
 pub fn differential_wasmi_execution(wasm: &[u8], config: &generators::Config) -> Option<()> {
     crate::init_fuzzing();
     log_wasm(wasm);
@@ -714,78 +718,166 @@ pub fn differential_wasmi_execution(wasm: &[u8], config: &generators::Config) ->
     let wasmtime_module = wasmtime_module?;
     let wasmtime_instance = Instance::new(&mut wasmtime_store, &wasmtime_module, &[])
         .expect("Wasmtime can instantiate module");
+    first_exported_function(&wasmtime_module)?;
+
+    // Each function is called with the same dummy arguments on both sides, and
+    // sees the state that the functions called before it left behind.
+    for export in wasmtime_module.exports() {
+        let ty = match export.ty() {
+            ExternType::Func(ty) => ty,
+            _ => continue,
+        };
+        let name = export.name();
+        let params = dummy::dummy_values(ty.params());
+        let wasmi_params = params.iter().map(wasmi_value).collect::<Option<Vec<_>>>()?;
+
+        let wasmi_export = wasmi_instance.export_by_name(name).unwrap();
+        let wasmi_func = wasmi_export.as_func().unwrap();
+        let wasmi_val =
+            wasmi::FuncInstance::invoke(wasmi_func, &wasmi_params, &mut wasmi::NopExternals);
+
+        let wasmtime_func = wasmtime_instance
+            .get_func(&mut wasmtime_store, name)
+            .expect("function export is present");
+        let mut wasmtime_results = vec![Val::I32(0); ty.results().len()];
+        let wasmtime_val = wasmtime_func
+            .call(&mut wasmtime_store, &params, &mut wasmtime_results)
+            .map(|()| wasmtime_results.get(0).cloned());
 
-    // Introspect wasmtime module to find name of an exported function and of an
-    // exported memory.
-    let (func_name, ty) = first_exported_function(&wasmtime_module)?;
-    let memory_name = first_exported_memory(&wasmtime_module)?;
-
-    let wasmi_mem_export = wasmi_instance.export_by_name(memory_name).unwrap();
-    let wasmi_mem = wasmi_mem_export.as_memory().unwrap();
-    let wasmi_main_export = wasmi_instance.export_by_name(func_name).unwrap();
-    let wasmi_main = wasmi_main_export.as_func().unwrap();
-    let wasmi_val = wasmi::FuncInstance::invoke(&wasmi_main, &[], &mut wasmi::NopExternals);
-
-    let wasmtime_mem = wasmtime_instance
-        .get_memory(&mut wasmtime_store, memory_name)
-        .expect("memory export is present");
-    let wasmtime_main = wasmtime_instance
-        .get_func(&mut wasmtime_store, func_name)
-        .expect("function export is present");
-    let mut wasmtime_results = vec![Val::I32(0); ty.results().len()];
-    let wasmtime_val = wasmtime_main
-        .call(&mut wasmtime_store, &[], &mut wasmtime_results)
-        .map(|()| wasmtime_results.get(0).cloned());
-
-    debug!(
-        "Successful execution: wasmi returned {:?}, wasmtime returned {:?}",
-        wasmi_val, wasmtime_val
-    );
-
-    match (&wasmi_val, &wasmtime_val) {
-        (&Ok(Some(wasmi::RuntimeValue::I32(a))), &Ok(Some(Val::I32(b)))) if a == b => {}
-        (&Ok(Some(wasmi::RuntimeValue::F32(a))), &Ok(Some(Val::F32(b))))
-            if f32_equal(a.to_bits(), b) => {}
-        (&Ok(Some(wasmi::RuntimeValue::I64(a))), &Ok(Some(Val::I64(b)))) if a == b => {}
-        (&Ok(Some(wasmi::RuntimeValue::F64(a))), &Ok(Some(Val::F64(b))))
-            if f64_equal(a.to_bits(), b) => {}
-        (&Ok(None), &Ok(None)) => {}
-        (&Err(_), &Err(_)) => {}
-        _ => {
-            panic!(
-                "Values do not match: wasmi returned {:?}; wasmtime returned {:?}",
-                wasmi_val, wasmtime_val
-            );
+        debug!(
+            "Called `{}`: wasmi returned {:?}, wasmtime returned {:?}",
+            name, wasmi_val, wasmtime_val
+        );
+
+        let wasmtime_trap = match &wasmtime_val {
+            Ok(_) => None,
+            Err(e) => Some(e.downcast_ref::<Trap>().and_then(|t| t.trap_code())),
+        };
+        let wasmi_overflowed =
+            matches!(&wasmi_val, Err(t) if matches!(t.kind(), wasmi::TrapKind::StackOverflow));
+        if wasmi_overflowed || wasmtime_trap == Some(Some(TrapCode::StackOverflow)) {
+            return None;
         }
-    }
 
-    if wasmi_mem.current_size().0 != wasmtime_mem.size(&wasmtime_store) as usize {
-        panic!("resulting memories are not the same size");
+        match (&wasmi_val, &wasmtime_val) {
+            (Ok(Some(a)), Ok(Some(b))) if wasmi_matches(a, b) => {}
+            (Ok(None), Ok(None)) => {}
+            (Err(a), Err(_)) if wasmi_trap_matches(a.kind(), wasmtime_trap.unwrap()) => {}
+            _ => {
+                panic!(
+                    "Results of `{}` do not match: wasmi returned {:?}; wasmtime returned {:?}",
+                    name, wasmi_val, wasmtime_val
+                );
+            }
+        }
     }
 
-    // Wasmi memory may be stored non-contiguously; copy it out to a contiguous chunk.
-    let mut wasmi_buf: Vec<u8> = vec![0; wasmtime_mem.data_size(&wasmtime_store)];
-    wasmi_mem
-        .get_into(0, &mut wasmi_buf[..])
-        .expect("can access wasmi memory");
+    for export in wasmtime_module.exports() {
+        let name = export.name();
+        let wasmi_export = wasmi_instance.export_by_name(name).unwrap();
+        match export.ty() {
+            ExternType::Memory(_) => {
+                let wasmi_mem = wasmi_export.as_memory().unwrap();
+                let wasmtime_mem = wasmtime_instance
+                    .get_memory(&mut wasmtime_store, name)
+                    .expect("memory export is present");
+                if wasmi_mem.current_size().0 != wasmtime_mem.size(&wasmtime_store) as usize {
+                    panic!("resulting memories `{}` are not the same size", name);
+                }
 
-    let wasmtime_slice = wasmtime_mem.data(&wasmtime_store);
+                // Wasmi memory may be stored non-contiguously; copy it out to a contiguous chunk.
+                let mut wasmi_buf: Vec<u8> = vec![0; wasmtime_mem.data_size(&wasmtime_store)];
+                wasmi_mem
+                    .get_into(0, &mut wasmi_buf[..])
+                    .expect("can access wasmi memory");
 
-    if wasmi_buf.len() >= 64 {
-        debug!("-> First 64 bytes of wasmi heap: {:?}", &wasmi_buf[0..64]);
-        debug!(
-            "-> First 64 bytes of Wasmtime heap: {:?}",
-            &wasmtime_slice[0..64]
-        );
-    }
+                let wasmtime_slice = wasmtime_mem.data(&wasmtime_store);
 
-    if &wasmi_buf[..] != &wasmtime_slice[..] {
-        panic!("memory contents are not equal");
+                if wasmi_buf.len() >= 64 {
+                    debug!("-> First 64 bytes of wasmi heap: {:?}", &wasmi_buf[0..64]);
+                    debug!(
+                        "-> First 64 bytes of Wasmtime heap: {:?}",
+                        &wasmtime_slice[0..64]
+                    );
+                }
+
+                if &wasmi_buf[..] != &wasmtime_slice[..] {
+                    panic!("contents of memories `{}` are not equal", name);
+                }
+            }
+            ExternType::Global(_) => {
+                let wasmi_val = wasmi_export.as_global().unwrap().get();
+                let wasmtime_val = wasmtime_instance
+                    .get_global(&mut wasmtime_store, name)
+                    .expect("global export is present")
+                    .get(&mut wasmtime_store);
+                if !wasmi_matches(&wasmi_val, &wasmtime_val) {
+                    panic!(
+                        "Values of global `{}` do not match: wasmi has {:?}; wasmtime has {:?}",
+                        name, wasmi_val, wasmtime_val
+                    );
+                }
+            }
+            _ => {}
+        }
     }
 
     Some(())
 }
 
+/// Converts a wasmtime argument to a wasmi one, if wasmi supports its type.
+fn wasmi_value(val: &Val) -> Option<wasmi::RuntimeValue> {
+    use wasmi::nan_preserving_float::{F32, F64};
+    Some(match *val {
+        Val::I32(i) => wasmi::RuntimeValue::I32(i),
+        Val::I64(i) => wasmi::RuntimeValue::I64(i),
+        Val::F32(bits) => wasmi::RuntimeValue::F32(F32::from_bits(bits)),
+        Val::F64(bits) => wasmi::RuntimeValue::F64(F64::from_bits(bits)),
+        _ => return None,
+    })
+}
+
+fn wasmi_matches(wasmi_val: &wasmi::RuntimeValue, wasmtime_val: &Val) -> bool {
+    match (wasmi_val, wasmtime_val) {
+        (wasmi::RuntimeValue::I32(a), Val::I32(b)) => a == b,
+        (wasmi::RuntimeValue::I64(a), Val::I64(b)) => a == b,
+        (wasmi::RuntimeValue::F32(a), Val::F32(b)) => f32_equal(a.to_bits(), *b),
+        (wasmi::RuntimeValue::F64(a), Val::F64(b)) => f64_equal(a.to_bits(), *b),
+        _ => false,
+    }
+}
+
+/// Returns whether wasmi trapping with `kind` matches wasmtime trapping with
+/// `code`. Wasmi reports both integer overflow and invalid conversions to
+/// integers the same way.
+fn wasmi_trap_matches(kind: &wasmi::TrapKind, code: Option<TrapCode>) -> bool {
+    use wasmi::TrapKind;
+    matches!(
+        (kind, code),
+        (
+            TrapKind::Unreachable,
+            Some(TrapCode::UnreachableCodeReached)
+        ) | (
+            TrapKind::MemoryAccessOutOfBounds,
+            Some(TrapCode::MemoryOutOfBounds)
+        ) | (
+            TrapKind::TableAccessOutOfBounds,
+            Some(TrapCode::TableOutOfBounds)
+        ) | (
+            TrapKind::ElemUninitialized,
+            Some(TrapCode::IndirectCallToNull)
+        ) | (TrapKind::UnexpectedSignature, Some(TrapCode::BadSignature))
+            | (
+                TrapKind::DivisionByZero,
+                Some(TrapCode::IntegerDivisionByZero)
+            )
+            | (
+                TrapKind::InvalidConversionToInt,
+                Some(TrapCode::IntegerOverflow | TrapCode::BadConversionToInteger)
+            )
+    )
+}
+
 /// Perform differential execution between Wasmtime and the official WebAssembly
 /// specification interpreter.
 ///
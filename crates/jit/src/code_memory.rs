@@ -3,6 +3,7 @@
 use crate::unwind::UnwindRegistration;
 use anyhow::{bail, Context, Result};
 use object::read::{File, Object, ObjectSection};
+use object::Architecture;
 use std::mem::ManuallyDrop;
 use wasmtime_runtime::MmapVec;
 
@@ -157,9 +158,10 @@ impl CodeMemory {
 
             // Switch the executable portion from read/write to
             // read/execute, notably not using read/write/execute to prevent
-            // modifications.
+            // modifications. Code compiled with landing pads for indirect
+            // branches can additionally have them enforced.
             self.mmap
-                .make_executable(text_range.clone())
+                .make_executable(text_range.clone(), has_bti_landing_pads(&ret.obj))
                 .expect("unable to make memory executable");
 
             #[cfg(all(target_arch = "aarch64", target_os = "linux"))]
@@ -182,6 +184,32 @@ impl CodeMemory {
     }
 }
 
+/// Returns whether the `.note.gnu.property` section of `obj` marks its code as
+/// compatible with AArch64's branch target identification.
+fn has_bti_landing_pads(obj: &File) -> bool {
+    const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc000_0000;
+    const GNU_PROPERTY_AARCH64_FEATURE_1_BTI: u32 = 1 << 0;
+
+    if obj.architecture() != Architecture::Aarch64 {
+        return false;
+    }
+    let note = match obj
+        .section_by_name(".note.gnu.property")
+        .and_then(|s| s.data().ok())
+    {
+        Some(note) => note,
+        None => return false,
+    };
+    // The note written during compilation has the four-byte name "GNU"
+    // followed by a single property.
+    let word = |i: usize| {
+        note.get(i * 4..i * 4 + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    word(4) == Some(GNU_PROPERTY_AARCH64_FEATURE_1_AND)
+        && word(6).map_or(false, |data| data & GNU_PROPERTY_AARCH64_FEATURE_1_BTI != 0)
+}
+
 unsafe fn register_unwind_info(obj: &File, text: &[u8]) -> Result<Option<UnwindRegistration>> {
     let unwind_info = match obj
         .section_by_name(UnwindRegistration::section_name())
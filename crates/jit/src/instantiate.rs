@@ -12,17 +12,18 @@ use object::{File, Object as _, ObjectSection, SectionKind};
 use serde::{Deserialize, Serialize};
 use std::convert::TryFrom;
 use std::ops::Range;
+use std::ptr::NonNull;
 use std::str;
 use std::sync::Arc;
 use thiserror::Error;
 use wasmtime_environ::{
-    CompileError, DefinedFuncIndex, FuncIndex, FunctionInfo, Module, ModuleTranslation, PrimaryMap,
-    SignatureIndex, StackMapInformation, Trampoline, Tunables, ELF_WASMTIME_ADDRMAP,
-    ELF_WASMTIME_TRAPS,
+    CompileError, DefinedFuncIndex, EmittedCode, FuncIndex, FunctionInfo, Module,
+    ModuleTranslation, PrimaryMap, SignatureIndex, StackMapInformation, Trampoline, Tunables,
+    ELF_WASMTIME_ADDRMAP, ELF_WASMTIME_TRAPS, IMPORT_THUNK_SIZE,
 };
 use wasmtime_runtime::{
     CompiledModuleId, CompiledModuleIdAllocator, GdbJitImageRegistration, InstantiationError,
-    MmapVec, VMFunctionBody, VMTrampoline,
+    MmapVec, VMFunctionBody, VMNativeTrampoline, VMTrampoline,
 };
 
 /// This is the name of the section in the final ELF image which contains
@@ -107,6 +108,10 @@ pub struct CompiledModuleInfo {
     /// relative to the start of the text section.
     trampolines: Vec<Trampoline>,
 
+    /// The offset in the text section of the import thunk of each imported
+    /// function, if calls to imports go through thunks.
+    import_thunks: Vec<u64>,
+
     /// General compilation metadata.
     meta: Metadata,
 }
@@ -157,10 +162,15 @@ struct Metadata {
 pub fn finish_compile(
     translation: ModuleTranslation<'_>,
     mut obj: Object,
-    funcs: PrimaryMap<DefinedFuncIndex, FunctionInfo>,
-    trampolines: Vec<Trampoline>,
+    code: EmittedCode,
     tunables: &Tunables,
 ) -> Result<(MmapVec, CompiledModuleInfo)> {
+    let EmittedCode {
+        funcs,
+        trampolines,
+        import_thunks,
+        ..
+    } = code;
     let ModuleTranslation {
         mut module,
         debuginfo,
@@ -259,6 +269,7 @@ pub fn finish_compile(
         module,
         funcs,
         trampolines,
+        import_thunks,
         func_names,
         meta: Metadata {
             native_debug_info_present: tunables.generate_native_debuginfo,
@@ -365,6 +376,7 @@ pub struct CompiledModule {
     module: Arc<Module>,
     funcs: PrimaryMap<DefinedFuncIndex, FunctionInfo>,
     trampolines: Vec<Trampoline>,
+    import_thunks: Vec<u64>,
     meta: Metadata,
     code: Range<usize>,
     code_memory: CodeMemory,
@@ -435,6 +447,7 @@ impl CompiledModule {
             module: Arc::new(info.module),
             funcs: info.funcs,
             trampolines: info.trampolines,
+            import_thunks: info.import_thunks,
             wasm_data: subslice_range(section(ELF_WASM_DATA)?, code.mmap),
             address_map_data: code
                 .obj
@@ -512,6 +525,25 @@ impl CompiledModule {
         &self.mmap()[self.code.clone()]
     }
 
+    /// Returns the range of [`CompiledModule::mmap`] holding the import thunk
+    /// of each of the module's imported functions, or nothing if calls to
+    /// imports don't go through thunks.
+    pub fn import_thunks(&self) -> impl ExactSizeIterator<Item = Range<usize>> + '_ {
+        self.import_thunks.iter().map(move |offset| {
+            let start = self.code.start + *offset as usize;
+            start..start + IMPORT_THUNK_SIZE
+        })
+    }
+
+    /// Advises the OS to back the text section with transparent huge pages,
+    /// to reduce the iTLB misses of executing it.
+    ///
+    /// The text section's pages are already populated, so on Linux they're
+    /// only replaced by huge pages as `khugepaged` gets to them.
+    pub fn advise_huge_pages(&self) {
+        self.code_memory.mmap().advise_huge_pages(self.code.clone());
+    }
+
     /// Return a reference-counting pointer to a module.
     pub fn module(&self) -> &Arc<Module> {
         &self.module
@@ -567,6 +599,20 @@ impl CompiledModule {
         })
     }
 
+    /// Returns the trampolines taking their signature's arguments natively,
+    /// which are only compiled when the trampolines entering wasm pin the
+    /// store's context.
+    pub fn native_trampolines(
+        &self,
+    ) -> impl Iterator<Item = (SignatureIndex, VMNativeTrampoline)> + '_ {
+        let code = self.code();
+        self.trampolines.iter().filter_map(move |info| {
+            let (start, _) = info.native?;
+            let ptr = NonNull::from(&code[start as usize]).cast();
+            Some((info.signature, unsafe { VMNativeTrampoline::new(ptr) }))
+        })
+    }
+
     /// Returns the stack map information for all functions defined in this
     /// module.
     ///
@@ -22,7 +22,6 @@
 
 mod code_memory;
 mod debug;
-mod demangling;
 mod instantiate;
 mod profiling;
 mod unwind;
@@ -32,8 +31,10 @@ pub use crate::instantiate::{
     finish_compile, mmap_vec_from_obj, subslice_range, CompiledModule, CompiledModuleInfo,
     SetupError, SymbolizeContext,
 };
-pub use demangling::*;
 pub use profiling::*;
+pub use wasmtime_environ::{
+    demangle_function_name, demangle_function_name_or_index, symbol_name, write_symbol_name,
+};
 
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,4 +1,15 @@
-use crate::{demangling::demangle_function_name_or_index, CompiledModule};
+use crate::CompiledModule;
+#[cfg(any(
+    all(feature = "jitdump", target_os = "linux"),
+    all(feature = "vtune", target_arch = "x86_64")
+))]
+use anyhow::Result;
+#[cfg(any(
+    all(feature = "jitdump", target_os = "linux"),
+    all(feature = "vtune", target_arch = "x86_64")
+))]
+use object::{Object, ObjectSection};
+#[cfg(any(target_os = "linux", all(feature = "vtune", target_arch = "x86_64")))]
 use wasmtime_environ::{DefinedFuncIndex, EntityRef};
 
 cfg_if::cfg_if! {
@@ -11,6 +22,16 @@ cfg_if::cfg_if! {
     }
 }
 
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        #[path = "profiling/perfmap_linux.rs"]
+        mod perfmap;
+    } else {
+        #[path = "profiling/perfmap_disabled.rs"]
+        mod perfmap;
+    }
+}
+
 cfg_if::cfg_if! {
     if #[cfg(all(feature = "vtune", target_arch = "x86_64"))] {
         #[path = "profiling/vtune.rs"]
@@ -22,6 +43,7 @@ cfg_if::cfg_if! {
 }
 
 pub use jitdump::JitDumpAgent;
+pub use perfmap::PerfMapAgent;
 pub use vtune::VTuneAgent;
 
 /// Common interface for profiling tools.
@@ -51,11 +73,100 @@ impl ProfilingAgent for NullProfilerAgent {
     }
 }
 
-#[allow(dead_code)]
+#[cfg(any(target_os = "linux", all(feature = "vtune", target_arch = "x86_64")))]
 fn debug_name(module: &CompiledModule, index: DefinedFuncIndex) -> String {
     let index = module.module().func_index(index);
-    let mut debug_name = String::new();
-    demangle_function_name_or_index(&mut debug_name, module.func_name(index), index.index())
-        .unwrap();
-    debug_name
+    crate::symbol_name(
+        module.module().name.as_deref(),
+        module.func_name(index),
+        index.index(),
+    )
+}
+
+// The DWARF of modules is only read by the jitdump and VTune agents.
+#[cfg(any(
+    all(feature = "jitdump", target_os = "linux"),
+    all(feature = "vtune", target_arch = "x86_64")
+))]
+type Dwarf<'a> = gimli::Dwarf<gimli::EndianSlice<'a, gimli::RunTimeEndian>>;
+
+/// Loads the DWARF sections of the in-memory object `dbg_image`.
+#[cfg(any(
+    all(feature = "jitdump", target_os = "linux"),
+    all(feature = "vtune", target_arch = "x86_64")
+))]
+fn load_dwarf(dbg_image: &[u8]) -> Result<Dwarf<'_>> {
+    let file = object::File::parse(dbg_image)?;
+    let endian = if file.is_little_endian() {
+        gimli::RunTimeEndian::Little
+    } else {
+        gimli::RunTimeEndian::Big
+    };
+    let dwarf = gimli::Dwarf::load(|id| -> Result<_> {
+        let data = match file.section_by_name(id.name()) {
+            Some(section) => section.data()?,
+            None => &[],
+        };
+        Ok(gimli::EndianSlice::new(data, endian))
+    })?;
+    Ok(dwarf)
+}
+
+/// The source location of a machine instruction, as described by a row of a
+/// DWARF line program.
+#[cfg(any(
+    all(feature = "jitdump", target_os = "linux"),
+    all(feature = "vtune", target_arch = "x86_64")
+))]
+struct LineRow {
+    address: u64,
+    file: String,
+    line: u32,
+    // Only the jitdump agent records columns.
+    #[cfg_attr(not(all(feature = "jitdump", target_os = "linux")), allow(dead_code))]
+    column: u32,
+}
+
+/// Returns the rows of all line programs in `dwarf` which describe
+/// instructions within the `size` bytes of code at `address`, in order of
+/// appearance.
+#[cfg(any(
+    all(feature = "jitdump", target_os = "linux"),
+    all(feature = "vtune", target_arch = "x86_64")
+))]
+fn line_rows(dwarf: &Dwarf<'_>, address: u64, size: u64) -> Result<Vec<LineRow>> {
+    let mut result = Vec::new();
+    let mut units = dwarf.units();
+    while let Some(header) = units.next()? {
+        let unit = dwarf.unit(header)?;
+        let program = match unit.line_program.clone() {
+            Some(program) => program,
+            None => continue,
+        };
+        let mut rows = program.rows();
+        while let Some((header, row)) = rows.next_row()? {
+            if row.address() < address || row.address() >= address + size {
+                continue;
+            }
+            let file = match row.file(header) {
+                Some(file) => dwarf
+                    .attr_string(&unit, file.path_name())?
+                    .to_string_lossy()
+                    .into_owned(),
+                None => continue,
+            };
+            let line = row.line().map_or(0, |nonzero| nonzero.get());
+            let column = match row.column() {
+                gimli::ColumnType::Column(column) => column.get(),
+                gimli::ColumnType::LeftEdge => 0,
+            };
+            result.push(LineRow {
+                address: row.address(),
+                file,
+                line: line as u32,
+                column: column as u32,
+            });
+        }
+    }
+    Ok(result)
 }
@@ -0,0 +1,84 @@
+//! Support for perf map files which can be used by perf for symbolizing jitted
+//! code without the need for `perf inject`.
+//! The format is described here:
+//! <https://git.kernel.org/pub/scm/linux/kernel/git/torvalds/linux.git/tree/tools/perf/Documentation/jit-interface.txt>
+//!
+//! Usage Example:
+//!     Record
+//!         perf record -g target/debug/wasmtime --perfmap test.wasm
+//!     Report
+//!         perf report
+
+use crate::{CompiledModule, ProfilingAgent};
+use anyhow::Result;
+use std::fmt::Write as _;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::process;
+use wasmtime_environ::EntityRef;
+
+/// Interface for driving the creation of perf map files
+pub struct PerfMapAgent {
+    // The file is opened in append mode and each batch of lines is written
+    // with a single call, so agents on multiple threads, or of multiple
+    // engines within this process, may share the file without a lock.
+    file: File,
+}
+
+impl PerfMapAgent {
+    /// Intialize a PerfMapAgent, creating the perf map file of this process if
+    /// it doesn't exist yet
+    pub fn new() -> Result<Self> {
+        let filename = format!("/tmp/perf-{}.map", process::id());
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(filename)?;
+        Ok(PerfMapAgent { file })
+    }
+
+    fn write_lines(&self, lines: &str) {
+        if let Err(err) = (&self.file).write_all(lines.as_bytes()) {
+            println!("PerfMap: write failed: {:?}\n", err);
+        }
+    }
+}
+
+/// Appends the perf map line for the code at `addr` to `lines`.
+fn push_line(lines: &mut String, name: &str, addr: *const u8, len: usize) {
+    // Names are the last field of a line, so they may contain spaces, but
+    // wasm names can hold any character and line breaks would corrupt the
+    // file.
+    let name = name.replace(&['\n', '\r'][..], "_");
+    writeln!(lines, "{:x} {:x} {}", addr as usize, len, name).unwrap();
+}
+
+impl ProfilingAgent for PerfMapAgent {
+    fn module_load(&self, module: &CompiledModule, _dbg_image: Option<&[u8]>) {
+        let mut lines = String::new();
+        for (idx, func) in module.finished_functions() {
+            let (addr, len) = unsafe { ((*func).as_ptr().cast::<u8>(), (*func).len()) };
+            push_line(&mut lines, &super::debug_name(module, idx), addr, len);
+        }
+
+        // Note: these are the trampolines into exported functions.
+        for (idx, func, len) in module.trampolines() {
+            let name = format!("wasm::trampoline[{}]", idx.index());
+            push_line(&mut lines, &name, func as usize as *const u8, len);
+        }
+        self.write_lines(&lines);
+    }
+
+    fn load_single_trampoline(
+        &self,
+        name: &str,
+        addr: *const u8,
+        size: usize,
+        _pid: u32,
+        _tid: u32,
+    ) {
+        let mut lines = String::new();
+        push_line(&mut lines, name, addr, size);
+        self.write_lines(&lines);
+    }
+}
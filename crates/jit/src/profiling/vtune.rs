@@ -60,23 +60,27 @@ impl State {
         method_name: &str,
         addr: *const u8,
         len: usize,
+        source: &mut SourceLines,
     ) -> () {
+        // VTune copies these strings while notified of the event, so they
+        // only need to live until the end of this function.
+        let method_name = CString::new(method_name).expect("CString::new failed");
+        let class_file_name = CString::new(module_name).expect("CString::new failed");
+        let source_file_name = CString::new(&source.file[..]).expect("CString::new failed");
         let mut jmethod = _iJIT_Method_Load {
             method_id,
-            method_name: CString::new(method_name)
-                .expect("CString::new failed")
-                .into_raw(),
+            method_name: method_name.as_ptr() as *mut _,
             method_load_address: addr as *mut ::std::os::raw::c_void,
             method_size: len as u32,
-            line_number_size: 0,
-            line_number_table: ptr::null_mut(),
+            line_number_size: source.lines.len() as u32,
+            line_number_table: if source.lines.is_empty() {
+                ptr::null_mut()
+            } else {
+                source.lines.as_mut_ptr()
+            },
             class_id: 0,
-            class_file_name: CString::new(module_name)
-                .expect("CString::new failed")
-                .into_raw(),
-            source_file_name: CString::new("<unknown wasm filename>")
-                .expect("CString::new failed")
-                .into_raw(),
+            class_file_name: class_file_name.as_ptr() as *mut _,
+            source_file_name: source_file_name.as_ptr() as *mut _,
         };
         let jmethod_ptr = &mut jmethod as *mut _ as *mut _;
         unsafe {
@@ -113,7 +117,7 @@ impl ProfilingAgent for VTuneAgent {
 }
 
 impl State {
-    fn module_load(&mut self, module: &CompiledModule, _dbg_image: Option<&[u8]>) -> () {
+    fn module_load(&mut self, module: &CompiledModule, dbg_image: Option<&[u8]>) -> () {
         // Global counter for module ids.
         static MODULE_ID: atomic::AtomicUsize = atomic::AtomicUsize::new(0);
         let global_module_id = MODULE_ID.fetch_add(1, atomic::Ordering::SeqCst);
@@ -125,8 +129,22 @@ impl State {
             .cloned()
             .unwrap_or_else(|| format!("wasm_module_{}", global_module_id));
 
+        // If the module was compiled with DWARF then its line programs are used
+        // to map each function back to its source.
+        let dwarf = dbg_image.and_then(|img| match super::load_dwarf(img) {
+            Ok(dwarf) => Some(dwarf),
+            Err(err) => {
+                log::warn!("failed to parse debug image for VTune: {:?}", err);
+                None
+            }
+        });
+
         for (idx, func) in module.finished_functions() {
             let (addr, len) = unsafe { ((*func).as_ptr().cast::<u8>(), (*func).len()) };
+            let mut source = match &dwarf {
+                Some(dwarf) => SourceLines::new(dwarf, addr, len),
+                None => SourceLines::default(),
+            };
             let method_name = super::debug_name(module, idx);
             let method_id = self.get_method_id();
             log::trace!(
@@ -136,7 +154,14 @@ impl State {
                 method_name,
                 addr
             );
-            self.event_load(method_id, &module_name, &method_name, addr, len);
+            self.event_load(
+                method_id,
+                &module_name,
+                &method_name,
+                addr,
+                len,
+                &mut source,
+            );
         }
 
         // Note: these are the trampolines into exported functions.
@@ -151,7 +176,14 @@ impl State {
                 idx,
                 addr
             );
-            self.event_load(method_id, &module_name, &method_name, addr, len);
+            self.event_load(
+                method_id,
+                &module_name,
+                &method_name,
+                addr,
+                len,
+                &mut SourceLines::default(),
+            );
         }
     }
 
@@ -164,6 +196,59 @@ impl State {
         _tid: u32,
     ) {
         let method_id = self.get_method_id();
-        self.event_load(method_id, "wasm trampoline for Func::new", name, addr, size);
+        self.event_load(
+            method_id,
+            "wasm trampoline for Func::new",
+            name,
+            addr,
+            size,
+            &mut SourceLines::default(),
+        );
+    }
+}
+
+/// The source file of a code region, along with the line of that file for
+/// each described offset in the region.
+struct SourceLines {
+    file: String,
+    lines: Vec<LineNumberInfo>,
+}
+
+impl Default for SourceLines {
+    fn default() -> SourceLines {
+        SourceLines {
+            file: "<unknown wasm filename>".to_string(),
+            lines: Vec::new(),
+        }
+    }
+}
+
+impl SourceLines {
+    /// Looks up the source of the function compiled to the `len` bytes at
+    /// `addr` in the line programs of `dwarf`.
+    ///
+    /// VTune only supports a single source file per method, so lines of any
+    /// other files, such as those of inlined functions, are skipped.
+    fn new(dwarf: &super::Dwarf<'_>, addr: *const u8, len: usize) -> SourceLines {
+        let rows = match super::line_rows(dwarf, addr as u64, len as u64) {
+            Ok(rows) => rows,
+            Err(err) => {
+                log::warn!("failed to read line programs for VTune: {:?}", err);
+                Vec::new()
+            }
+        };
+        let file = match rows.first() {
+            Some(row) => row.file.clone(),
+            None => return SourceLines::default(),
+        };
+        let lines = rows
+            .iter()
+            .filter(|row| row.file == file)
+            .map(|row| LineNumberInfo {
+                Offset: (row.address - addr as u64) as u32,
+                LineNumber: row.line,
+            })
+            .collect();
+        SourceLines { file, lines }
     }
 }
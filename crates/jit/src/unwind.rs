@@ -9,6 +9,7 @@ cfg_if::cfg_if! {
         mod systemv;
         pub use self::systemv::*;
     } else {
-        compile_error!("unsupported target platform for unwind");
+        mod none;
+        pub use self::none::*;
     }
 }
@@ -0,0 +1,24 @@
+//! Unwind information isn't registered on platforms without a known unwinder,
+//! such as custom platforms.
+
+use anyhow::Result;
+
+/// Represents a registration of function unwind information, which is a no-op
+/// on this platform.
+pub struct UnwindRegistration {}
+
+impl UnwindRegistration {
+    /// Does nothing, since there's no unwinder to register the information
+    /// with.
+    pub unsafe fn new(
+        _base_address: *const u8,
+        _unwind_info: *const u8,
+        _unwind_len: usize,
+    ) -> Result<UnwindRegistration> {
+        Ok(UnwindRegistration {})
+    }
+
+    pub fn section_name() -> &'static str {
+        ".eh_frame"
+    }
+}
@@ -2,11 +2,53 @@
 
 use anyhow::{bail, Result};
 use std::mem;
+use winapi::shared::basetsd::ULONG_PTR;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::ntdef::PVOID;
+use winapi::um::libloaderapi::{GetModuleHandleA, GetProcAddress};
 use winapi::um::winnt;
 
 /// Represents a registry of function unwind information for Windows x64 ABI.
 pub struct UnwindRegistration {
-    functions: usize,
+    table: FunctionTable,
+}
+
+/// How the function table of some code was registered.
+enum FunctionTable {
+    /// A table registered with `RtlAddGrowableFunctionTable`, identified by
+    /// the handle it returned.
+    Growable(usize),
+    /// A table registered with `RtlAddFunctionTable`, identified by the
+    /// address of its first entry.
+    Static(usize),
+}
+
+type RtlAddGrowableFunctionTable = unsafe extern "system" fn(
+    *mut PVOID,
+    winnt::PRUNTIME_FUNCTION,
+    DWORD,
+    DWORD,
+    ULONG_PTR,
+    ULONG_PTR,
+) -> DWORD;
+type RtlDeleteGrowableFunctionTable = unsafe extern "system" fn(PVOID);
+
+/// Returns the address of the export `name` of `ntdll.dll`, if it has one.
+///
+/// Growable function tables only exist since Windows 8, so they're looked up
+/// rather than linked against.
+unsafe fn ntdll_export(name: &[u8]) -> Option<usize> {
+    debug_assert_eq!(name.last(), Some(&0));
+    let ntdll = GetModuleHandleA(b"ntdll.dll\0".as_ptr().cast());
+    if ntdll.is_null() {
+        return None;
+    }
+    let export = GetProcAddress(ntdll, name.as_ptr().cast());
+    if export.is_null() {
+        None
+    } else {
+        Some(export as usize)
+    }
 }
 
 impl UnwindRegistration {
@@ -18,17 +60,41 @@ impl UnwindRegistration {
         assert!(unwind_info as usize % 4 == 0);
         let unit_len = mem::size_of::<winnt::RUNTIME_FUNCTION>();
         assert!(unwind_len % unit_len == 0);
-        if winnt::RtlAddFunctionTable(
-            unwind_info as *mut _,
-            (unwind_len / unit_len) as u32,
-            base_address as u64,
-        ) == 0
-        {
+        let functions = unwind_info as *mut winnt::RUNTIME_FUNCTION;
+        let count = (unwind_len / unit_len) as u32;
+
+        // Tables registered with `RtlAddFunctionTable` are only found by
+        // unwinding within the process, while growable ones are also found by
+        // the kernel when it walks the stack, so they're preferred to have
+        // ETW traces and crash reports walk through the code. A growable
+        // table covers a range of addresses, which ends with the last
+        // function since the entries are sorted.
+        if let Some(add) = ntdll_export(b"RtlAddGrowableFunctionTable\0") {
+            let add: RtlAddGrowableFunctionTable = mem::transmute(add);
+            let end = (*functions.add(count as usize - 1)).EndAddress;
+            let mut table = std::ptr::null_mut();
+            if add(
+                &mut table,
+                functions,
+                count,
+                count,
+                base_address as ULONG_PTR,
+                base_address as ULONG_PTR + end as ULONG_PTR,
+            ) != 0
+            {
+                bail!("failed to register growable function table");
+            }
+            return Ok(UnwindRegistration {
+                table: FunctionTable::Growable(table as usize),
+            });
+        }
+
+        if winnt::RtlAddFunctionTable(functions, count, base_address as u64) == 0 {
             bail!("failed to register function table");
         }
 
         Ok(UnwindRegistration {
-            functions: unwind_info as usize,
+            table: FunctionTable::Static(functions as usize),
         })
     }
 
@@ -40,7 +106,17 @@ impl UnwindRegistration {
 impl Drop for UnwindRegistration {
     fn drop(&mut self) {
         unsafe {
-            winnt::RtlDeleteFunctionTable(self.functions as _);
+            match self.table {
+                FunctionTable::Growable(table) => {
+                    // This is exported along with `RtlAddGrowableFunctionTable`.
+                    let delete = ntdll_export(b"RtlDeleteGrowableFunctionTable\0").unwrap();
+                    let delete: RtlDeleteGrowableFunctionTable = mem::transmute(delete);
+                    delete(table as PVOID);
+                }
+                FunctionTable::Static(functions) => {
+                    winnt::RtlDeleteFunctionTable(functions as _);
+                }
+            }
         }
     }
 }
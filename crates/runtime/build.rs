@@ -11,12 +11,22 @@ fn main() {
         .file("src/helpers.c")
         .compile("wasmtime-helpers");
 
+    // Targets which are neither Unix nor Windows use the platform interface
+    // in `include/wasmtime-platform.h`, which the embedder implements, and
+    // enable the `wasmtime_custom_platform` rustc cfg for it. It can also be
+    // enabled on any target with `RUSTFLAGS="--cfg wasmtime_custom_platform"`.
+    let family = env::var("CARGO_CFG_TARGET_FAMILY").unwrap_or_default();
+    let custom_platform = env::var_os("CARGO_CFG_WASMTIME_CUSTOM_PLATFORM").is_some()
+        || (family != "unix" && family != "windows");
+    if custom_platform {
+        println!("cargo:rustc-cfg=wasmtime_custom_platform");
+    }
+
     // Check to see if we are on Unix and the `memory-init-cow` feature is
     // active. If so, enable the `memory_init_cow` rustc cfg so
     // `#[cfg(memory_init_cow)]` will work.
-    let family = env::var("CARGO_CFG_TARGET_FAMILY").unwrap();
     let memory_init_cow = env::var("CARGO_FEATURE_MEMORY_INIT_COW").is_ok();
-    if &family == "unix" && memory_init_cow {
+    if &family == "unix" && memory_init_cow && !custom_platform {
         println!("cargo:rustc-cfg=memory_init_cow");
     }
 }
@@ -0,0 +1,123 @@
+/**
+ * \file wasmtime-platform.h
+ *
+ * \brief The platform interface Wasmtime's runtime is built on when targeting
+ * something other than Unix or Windows.
+ *
+ * For targets such as bare-metal systems or RTOSes there's no operating
+ * system interface Wasmtime knows about, so instead the embedder implements
+ * the functions declared here, and they're linked into the final binary
+ * alongside Wasmtime. The same platform can be used on Unix for testing by
+ * building with `RUSTFLAGS="--cfg wasmtime_custom_platform"`.
+ *
+ * Only a subset of Wasmtime is supported on custom platforms:
+ *
+ * * Modules are best precompiled on another host with
+ *   `Engine::precompile_module` and loaded with `Module::deserialize`, so the
+ *   compiler can be left out with `default-features = false`.
+ * * Guard pages require virtual memory. Platforms without an MMU should
+ *   compile modules with `Config::static_memory_maximum_size(0)` and
+ *   `Config::static_memory_guard_size(0)` so that every memory access is
+ *   explicitly bounds-checked instead.
+ * * The pooling allocator, copy-on-write memory initialization, memory
+ *   protection keys, async support and mapping modules from files aren't
+ *   available.
+ *
+ * All functions returning `int` return 0 on success and a platform-specific
+ * nonzero error code on failure, which is included in the error Wasmtime
+ * reports.
+ */
+
+#ifndef _WASMTIME_PLATFORM_H
+#define _WASMTIME_PLATFORM_H
+
+#include <stdbool.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/// The memory can be read.
+#define WASMTIME_PROT_READ (1 << 0)
+/// The memory can be written.
+#define WASMTIME_PROT_WRITE (1 << 1)
+/// The memory can be executed.
+#define WASMTIME_PROT_EXEC (1 << 2)
+
+/**
+ * \brief Allocates `size` bytes of page-aligned memory, zero-filled, with the
+ * `WASMTIME_PROT_*` protections in `prot_flags`.
+ *
+ * `size` is always a multiple of `wasmtime_page_size`. Memory allocated with no
+ * protections at all is a reservation which later has parts made accessible
+ * with `wasmtime_mprotect`; platforms without virtual memory can allocate it
+ * ahead of time.
+ *
+ * The start of the allocated memory is written to `ret`.
+ */
+int wasmtime_mmap_new(uintptr_t size, uint32_t prot_flags, uint8_t **ret);
+
+/**
+ * \brief Changes the protections of the `size` bytes at `ptr`, which are
+ * within memory allocated by `wasmtime_mmap_new`, to `prot_flags`.
+ *
+ * Both `ptr` and `size` are multiples of `wasmtime_page_size`. Platforms
+ * without memory protection can ignore this and return 0.
+ */
+int wasmtime_mprotect(uint8_t *ptr, uintptr_t size, uint32_t prot_flags);
+
+/**
+ * \brief Frees the `size` bytes at `ptr` previously allocated in full by
+ * `wasmtime_mmap_new`.
+ */
+int wasmtime_munmap(uint8_t *ptr, uintptr_t size);
+
+/**
+ * \brief Returns the granularity of `wasmtime_mmap_new` and
+ * `wasmtime_mprotect`, a power of two.
+ */
+uintptr_t wasmtime_page_size(void);
+
+/**
+ * \brief The handler Wasmtime passes to `wasmtime_init_traps`.
+ *
+ * The platform calls this when a hardware trap such as an illegal instruction,
+ * a division by zero or an invalid memory access happens, with the program
+ * counter of the faulting instruction and, for invalid memory accesses, the
+ * faulting address.
+ *
+ * If the trap was raised by WebAssembly this doesn't return, and instead
+ * unwinds straight to where WebAssembly was entered, so it must be called from
+ * a context where that's possible, such as the faulting thread's exception
+ * handler. Otherwise it returns false and the platform must handle the trap
+ * itself.
+ */
+typedef bool (*wasmtime_trap_handler_t)(uintptr_t pc, bool has_faulting_addr,
+                                        uintptr_t faulting_addr);
+
+/**
+ * \brief Installs `handler` as the handler for hardware traps.
+ *
+ * This is called at most once, when the first `Engine` using signals-based
+ * traps is created.
+ */
+int wasmtime_init_traps(wasmtime_trap_handler_t handler);
+
+/**
+ * \brief Returns the current thread's value of a thread-local pointer Wasmtime
+ * uses to track WebAssembly on the stack, or null if it's never been set.
+ */
+uint8_t *wasmtime_tls_get(void);
+
+/**
+ * \brief Sets the current thread's value of the pointer returned by
+ * `wasmtime_tls_get`.
+ */
+void wasmtime_tls_set(uint8_t *ptr);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif
+
+#endif // _WASMTIME_PLATFORM_H
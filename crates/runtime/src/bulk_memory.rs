@@ -0,0 +1,79 @@
+//! Copying and filling the bytes of linear memories for the bulk memory
+//! instructions which aren't performed inline by compiled code.
+//!
+//! Compiled code performs short operations of a constant length itself, so
+//! those reaching the runtime are usually long. On x86_64 hosts with enhanced
+//! `rep movsb` (ERMS) long copies and fills use `rep movsb` and `rep stosb`,
+//! which move whole cache lines at a time, and everything else goes through
+//! the standard library.
+
+use std::ptr;
+
+/// The length from which `rep movsb` and `rep stosb` are used when they're
+/// fast, below which their startup cost outweighs their throughput.
+#[cfg(target_arch = "x86_64")]
+const REP_THRESHOLD: usize = 512;
+
+/// Copies `len` bytes from `src` to `dst`, which may overlap, like
+/// `ptr::copy`.
+///
+/// # Safety
+///
+/// Both ranges must be valid for `len` bytes.
+pub unsafe fn copy(src: *const u8, dst: *mut u8, len: usize) {
+    // `rep movsb` copies forwards, which is only right if `dst` doesn't start
+    // within `src`.
+    #[cfg(target_arch = "x86_64")]
+    if len >= REP_THRESHOLD && (dst as usize).wrapping_sub(src as usize) >= len && has_erms() {
+        std::arch::asm!(
+            "rep movsb",
+            inout("rcx") len => _,
+            inout("rsi") src => _,
+            inout("rdi") dst => _,
+            options(nostack, preserves_flags),
+        );
+        return;
+    }
+    ptr::copy(src, dst, len);
+}
+
+/// Sets the `len` bytes at `dst` to `val`, like `ptr::write_bytes`.
+///
+/// # Safety
+///
+/// The range must be valid for `len` bytes.
+pub unsafe fn fill(dst: *mut u8, val: u8, len: usize) {
+    #[cfg(target_arch = "x86_64")]
+    if len >= REP_THRESHOLD && has_erms() {
+        std::arch::asm!(
+            "rep stosb",
+            inout("rcx") len => _,
+            inout("rdi") dst => _,
+            in("al") val,
+            options(nostack, preserves_flags),
+        );
+        return;
+    }
+    ptr::write_bytes(dst, val, len);
+}
+
+/// Returns whether the host supports enhanced `rep movsb` and `rep stosb`.
+#[cfg(target_arch = "x86_64")]
+fn has_erms() -> bool {
+    use std::arch::x86_64::{__cpuid, __cpuid_count};
+    use std::sync::atomic::{AtomicU8, Ordering};
+
+    const UNKNOWN: u8 = 0;
+    const ABSENT: u8 = 1;
+    const PRESENT: u8 = 2;
+    static ERMS: AtomicU8 = AtomicU8::new(UNKNOWN);
+
+    match ERMS.load(Ordering::Relaxed) {
+        UNKNOWN => {}
+        state => return state == PRESENT,
+    }
+    // ERMS is reported in bit 9 of `ebx` of the extended features leaf.
+    let present = unsafe { __cpuid(0).eax >= 7 && __cpuid_count(7, 0).ebx & (1 << 9) != 0 };
+    ERMS.store(if present { PRESENT } else { ABSENT }, Ordering::Relaxed);
+    present
+}
@@ -0,0 +1,54 @@
+//! Reading the records which frames of code compiled to capture core dumps
+//! keep of themselves.
+
+use crate::vmcontext::{VMContext, ValRaw};
+use std::ptr;
+use wasmtime_environ::{debug_local_type, CoreDumpFrameOffsets, WasmType};
+
+/// A frame of wasm code compiled to capture core dumps, as recorded when a
+/// trap happened.
+pub struct CoreDumpStackFrame {
+    /// The `VMContext` of the instance which was executing the frame.
+    pub vmctx: *mut VMContext,
+    /// The index of the frame's function within its module.
+    pub func_index: u32,
+    /// The offset of the instruction the frame was executing from the start
+    /// of its function's body in the wasm module.
+    pub func_offset: u32,
+    /// The types and values of the function's locals, parameters first.
+    pub locals: Vec<(WasmType, ValRaw)>,
+}
+
+/// Copies the records of all frames in the list starting at `record`, most
+/// recent first.
+///
+/// # Safety
+///
+/// `record` must be the head of the list of frame records of a store, read
+/// while the wasm frames which the list describes are still on the stack.
+pub(crate) unsafe fn capture_coredump_stack(mut record: *const u8) -> Vec<CoreDumpStackFrame> {
+    // Records live in stack slots, which aren't necessarily aligned, so
+    // everything is read unaligned.
+    let offsets = CoreDumpFrameOffsets::new(std::mem::size_of::<usize>() as u8);
+    let field = |record: *const u8, offset: u32| record.add(offset as usize);
+    let mut frames = Vec::new();
+    while !record.is_null() {
+        let count = ptr::read_unaligned(field(record, offsets.count).cast::<u32>()) as usize;
+        let values = field(record, offsets.locals).cast::<ValRaw>();
+        let tags = values.add(count).cast::<u8>();
+        let locals = (0..count)
+            .map(|i| {
+                let ty = debug_local_type(*tags.add(i)).expect("invalid local type tag");
+                (ty, ptr::read_unaligned(values.add(i)))
+            })
+            .collect();
+        frames.push(CoreDumpStackFrame {
+            vmctx: ptr::read_unaligned(field(record, offsets.vmctx).cast()),
+            func_index: ptr::read_unaligned(field(record, offsets.func_index).cast()),
+            func_offset: ptr::read_unaligned(field(record, offsets.func_offset).cast()),
+            locals,
+        });
+        record = ptr::read_unaligned(field(record, offsets.prev).cast());
+    }
+    frames
+}
@@ -3,6 +3,7 @@
 
 use crate::InstantiationError;
 use crate::MmapVec;
+use crate::ProtectionKey;
 use anyhow::Result;
 use libc::c_void;
 use rustix::fd::AsRawFd;
@@ -303,6 +304,10 @@ pub struct MemoryImageSlot {
     /// specific to this slot) in place when it is dropped. Default
     /// on, unless the caller knows what they are doing.
     clear_on_drop: bool,
+    /// The protection key that the whole slot is tagged with, if the pooling
+    /// allocator stripes memories across keys. New mappings are tagged with
+    /// the default key, so this is reapplied after each `mmap`.
+    pkey: Option<ProtectionKey>,
 }
 
 impl MemoryImageSlot {
@@ -318,9 +323,15 @@ impl MemoryImageSlot {
             image: None,
             dirty: false,
             clear_on_drop: true,
+            pkey: None,
         }
     }
 
+    /// Tag the slot, which must still be inaccessible, with `pkey`.
+    pub(crate) fn set_protection_key(&mut self, pkey: ProtectionKey) {
+        self.pkey = Some(pkey);
+    }
+
     /// Inform the MemoryImageSlot that it should *not* clear the underlying
     /// address space when dropped. This should be used only when the
     /// caller will clear or reuse the address space in some other
@@ -454,6 +465,10 @@ impl MemoryImageSlot {
                     )
                     .map_err(|e| InstantiationError::Resource(e.into()))?;
                     assert_eq!(ptr as usize, self.base + image.linear_memory_offset);
+                    if let Some(pkey) = self.pkey {
+                        pkey.protect(ptr.cast(), image.len, true)
+                            .map_err(InstantiationError::Resource)?;
+                    }
                 }
             }
         }
@@ -523,6 +538,15 @@ impl MemoryImageSlot {
         self.image.is_some()
     }
 
+    /// Returns whether this slot keeps an image mapped which nothing else
+    /// uses, because the module it belongs to was dropped.
+    #[allow(dead_code)] // ignore warnings as this is only used in some cfgs
+    pub(crate) fn has_unused_image(&self) -> bool {
+        self.image
+            .as_ref()
+            .map_or(false, |image| Arc::strong_count(image) == 1)
+    }
+
     #[allow(dead_code)] // ignore warnings as this is only used in some cfgs
     pub(crate) fn is_dirty(&self) -> bool {
         self.dirty
@@ -539,6 +563,9 @@ impl MemoryImageSlot {
                 rustix::io::MapFlags::PRIVATE | rustix::io::MapFlags::FIXED,
             )?;
             assert_eq!(ptr as usize, self.base);
+            if let Some(pkey) = self.pkey {
+                pkey.protect(ptr.cast(), self.static_size, false)?;
+            }
         }
         Ok(())
     }
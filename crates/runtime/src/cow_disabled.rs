@@ -2,7 +2,7 @@
 //! not included. Enables unconditional use of the type and its methods
 //! throughout higher-level code.
 
-use crate::{InstantiationError, MmapVec};
+use crate::{InstantiationError, MmapVec, ProtectionKey};
 use anyhow::Result;
 use std::sync::Arc;
 use wasmtime_environ::{DefinedMemoryIndex, Module};
@@ -55,6 +55,10 @@ impl MemoryImageSlot {
         match *self {}
     }
 
+    pub(crate) fn set_protection_key(&mut self, _: ProtectionKey) {
+        match *self {}
+    }
+
     pub(crate) fn clear_and_remain_ready(&mut self) -> Result<()> {
         match *self {}
     }
@@ -63,6 +67,10 @@ impl MemoryImageSlot {
         match *self {}
     }
 
+    pub(crate) fn has_unused_image(&self) -> bool {
+        match *self {}
+    }
+
     pub(crate) fn is_dirty(&self) -> bool {
         match *self {}
     }
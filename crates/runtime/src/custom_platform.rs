@@ -0,0 +1,64 @@
+//! The platform interface used on targets other than Unix and Windows, which
+//! is implemented by the embedder. Each function is documented in
+//! `include/wasmtime-platform.h`.
+
+use anyhow::{bail, Result};
+
+pub const WASMTIME_PROT_READ: u32 = 1 << 0;
+pub const WASMTIME_PROT_WRITE: u32 = 1 << 1;
+pub const WASMTIME_PROT_EXEC: u32 = 1 << 2;
+
+pub type TrapHandler = extern "C" fn(usize, bool, usize) -> bool;
+
+extern "C" {
+    fn wasmtime_mmap_new(size: usize, prot_flags: u32, ret: &mut *mut u8) -> i32;
+    fn wasmtime_mprotect(ptr: *mut u8, size: usize, prot_flags: u32) -> i32;
+    fn wasmtime_munmap(ptr: *mut u8, size: usize) -> i32;
+    fn wasmtime_page_size() -> usize;
+    fn wasmtime_init_traps(handler: TrapHandler) -> i32;
+    pub fn wasmtime_tls_get() -> *mut u8;
+    pub fn wasmtime_tls_set(ptr: *mut u8);
+}
+
+/// Allocates `size` bytes of zeroed memory with the protections in
+/// `prot_flags`.
+pub fn mmap_new(size: usize, prot_flags: u32) -> Result<*mut u8> {
+    let mut ret = std::ptr::null_mut();
+    let code = unsafe { wasmtime_mmap_new(size, prot_flags, &mut ret) };
+    if code != 0 {
+        bail!("failed to allocate {:#x} bytes: error {}", size, code);
+    }
+    Ok(ret)
+}
+
+/// Changes the protections of the `size` bytes at `ptr` to `prot_flags`.
+pub unsafe fn mprotect(ptr: *mut u8, size: usize, prot_flags: u32) -> Result<()> {
+    let code = wasmtime_mprotect(ptr, size, prot_flags);
+    if code != 0 {
+        bail!("failed to change memory protections: error {}", code);
+    }
+    Ok(())
+}
+
+/// Frees the `size` bytes at `ptr` allocated by `mmap_new`.
+pub unsafe fn munmap(ptr: *mut u8, size: usize) -> Result<()> {
+    let code = wasmtime_munmap(ptr, size);
+    if code != 0 {
+        bail!("failed to free memory: error {}", code);
+    }
+    Ok(())
+}
+
+/// Returns the platform's page size.
+pub fn page_size() -> usize {
+    unsafe { wasmtime_page_size() }
+}
+
+/// Installs `handler` as the platform's handler for hardware traps.
+pub fn init_traps(handler: TrapHandler) -> Result<()> {
+    let code = unsafe { wasmtime_init_traps(handler) };
+    if code != 0 {
+        bail!("failed to install the trap handler: error {}", code);
+    }
+    Ok(())
+}
@@ -109,6 +109,7 @@ use std::mem;
 use std::ops::Deref;
 use std::ptr::{self, NonNull};
 use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 use wasmtime_environ::StackMap;
 
 /// An external reference to some opaque data.
@@ -523,12 +524,33 @@ pub struct VMExternRefActivationsTable {
     /// those missed roots, and use after free.
     stack_canary: Option<usize>,
 
+    /// The number of slots of the bump chunk, which is how many references
+    /// can be inserted between two collections before the next collection
+    /// is triggered by an insertion.
+    gc_threshold: usize,
+
+    /// Statistics about the collections of this table.
+    gc_stats: VMGcStats,
+
     /// A debug-only field for asserting that we are in a region of code where
     /// GC is okay to preform.
     #[cfg(debug_assertions)]
     gc_okay: bool,
 }
 
+/// Statistics about the garbage collections of a
+/// `VMExternRefActivationsTable`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct VMGcStats {
+    /// The number of collections performed.
+    pub collections: u64,
+    /// The time all of the collections took together.
+    pub total_duration: Duration,
+    /// The time the last collection took, and the number of references it
+    /// released from the table.
+    pub last: Option<(Duration, usize)>,
+}
+
 #[repr(C)] // This is accessed from JIT code.
 struct VMExternRefTableAlloc {
     /// Bump-allocation finger within the `chunk`.
@@ -584,16 +606,36 @@ impl VMExternRefActivationsTable {
             over_approximated_stack_roots: HashSet::new(),
             precise_stack_roots: HashSet::new(),
             stack_canary: None,
+            gc_threshold: Self::CHUNK_SIZE,
+            gc_stats: VMGcStats::default(),
             #[cfg(debug_assertions)]
             gc_okay: true,
         }
     }
 
     fn new_chunk(size: usize) -> Box<[UnsafeCell<Option<VMExternRef>>]> {
-        assert!(size >= Self::CHUNK_SIZE);
+        assert!(size > 0);
         (0..size).map(|_| UnsafeCell::new(None)).collect()
     }
 
+    /// Configures how many references can be inserted after a collection
+    /// before inserting another one triggers the next collection, which
+    /// takes effect from the next collection on.
+    pub fn set_gc_threshold(&mut self, threshold: usize) {
+        self.gc_threshold = threshold.max(1);
+    }
+
+    /// Returns the statistics about the collections of this table.
+    pub fn gc_stats(&self) -> VMGcStats {
+        self.gc_stats
+    }
+
+    /// Returns the number of references this table holds, counting those
+    /// inserted more than once since the last collection once per insertion.
+    pub fn num_refs(&self) -> usize {
+        self.over_approximated_stack_roots.len() + self.num_filled_in_bump_chunk()
+    }
+
     /// Get the available capacity in the bump allocation chunk.
     #[inline]
     pub fn bump_capacity_remaining(&self) -> usize {
@@ -689,7 +731,8 @@ impl VMExternRefActivationsTable {
         self.alloc.chunk.len().saturating_sub(slots_unused)
     }
 
-    fn elements(&self, mut f: impl FnMut(&VMExternRef)) {
+    /// Calls `f` with each reference this table holds.
+    pub fn elements(&self, mut f: impl FnMut(&VMExternRef)) {
         for elem in self.over_approximated_stack_roots.iter() {
             f(&elem.0);
         }
@@ -736,8 +779,9 @@ impl VMExternRefActivationsTable {
 
         // If this is the first instance of gc then the initial chunk is empty,
         // so we lazily allocate space for fast bump-allocation in the future.
-        if self.alloc.chunk.is_empty() {
-            self.alloc.chunk = Self::new_chunk(Self::CHUNK_SIZE);
+        // The chunk is also replaced when the threshold has changed.
+        if self.alloc.chunk.len() != self.gc_threshold {
+            self.alloc.chunk = Self::new_chunk(self.gc_threshold);
             self.alloc.end =
                 NonNull::new(unsafe { self.alloc.chunk.as_mut_ptr().add(self.alloc.chunk.len()) })
                     .unwrap();
@@ -867,10 +911,26 @@ impl<T> std::ops::DerefMut for DebugOnly<T> {
 ///
 /// Additionally, you must have registered the stack maps for every Wasm module
 /// that has frames on the stack with the given `stack_maps_registry`.
-#[cfg_attr(not(feature = "wasm-backtrace"), allow(unused_mut, unused_variables))]
 pub unsafe fn gc(
     module_info_lookup: &dyn ModuleInfoLookup,
     externref_activations_table: &mut VMExternRefActivationsTable,
+) {
+    let start = Instant::now();
+    let before = externref_activations_table.num_refs();
+    collect(module_info_lookup, externref_activations_table);
+    let released = before.saturating_sub(externref_activations_table.num_refs());
+
+    let duration = start.elapsed();
+    let stats = &mut externref_activations_table.gc_stats;
+    stats.collections += 1;
+    stats.total_duration += duration;
+    stats.last = Some((duration, released));
+}
+
+#[cfg_attr(not(feature = "wasm-backtrace"), allow(unused_mut, unused_variables))]
+unsafe fn collect(
+    module_info_lookup: &dyn ModuleInfoLookup,
+    externref_activations_table: &mut VMExternRefActivationsTable,
 ) {
     log::debug!("start GC");
 
@@ -1050,6 +1110,7 @@ mod tests {
             num_defined_memories: 0,
             num_defined_globals: 0,
             num_escaped_funcs: 0,
+            num_call_indirect_caches: 0,
         });
         assert_eq!(
             offsets.vm_extern_data_ref_count(),
@@ -1077,6 +1138,7 @@ mod tests {
             num_defined_memories: 0,
             num_defined_globals: 0,
             num_escaped_funcs: 0,
+            num_call_indirect_caches: 0,
         });
         assert_eq!(
             offsets.vm_extern_ref_activation_table_next() as usize,
@@ -1104,6 +1166,7 @@ mod tests {
             num_defined_memories: 0,
             num_defined_globals: 0,
             num_escaped_funcs: 0,
+            num_call_indirect_caches: 0,
         });
         assert_eq!(
             offsets.vm_extern_ref_activation_table_end() as usize,
@@ -3,19 +3,20 @@
 //! `InstanceHandle` is a reference-counting handle for an `Instance`.
 
 use crate::export::Export;
-use crate::externref::VMExternRefActivationsTable;
+use crate::externref::{VMExternRef, VMExternRefActivationsTable};
 use crate::memory::{Memory, RuntimeMemoryCreator};
 use crate::table::{Table, TableElement, TableElementType};
 use crate::traphandlers::Trap;
 use crate::vmcontext::{
-    VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionImport,
-    VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition, VMMemoryImport, VMRuntimeLimits,
-    VMTableDefinition, VMTableImport,
+    VMBuiltinFunctionsArray, VMCallIndirectCache, VMCallerCheckedAnyfunc, VMContext,
+    VMFunctionImport, VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition, VMMemoryImport,
+    VMRuntimeLimits, VMTableDefinition, VMTableImport,
 };
 use crate::{
-    ExportFunction, ExportGlobal, ExportMemory, ExportTable, Imports, ModuleRuntimeInfo, Store,
+    CompiledModuleId, ExportFunction, ExportGlobal, ExportMemory, ExportTable, Imports,
+    ModuleRuntimeInfo, Store,
 };
-use anyhow::Error;
+use anyhow::{bail, Error};
 use memoffset::offset_of;
 use more_asserts::assert_lt;
 use std::alloc::Layout;
@@ -28,10 +29,10 @@ use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::{mem, ptr, slice};
 use wasmtime_environ::{
-    packed_option::ReservedValue, DataIndex, DefinedGlobalIndex, DefinedMemoryIndex,
-    DefinedTableIndex, ElemIndex, EntityIndex, EntityRef, EntitySet, FuncIndex, GlobalIndex,
-    GlobalInit, HostPtr, MemoryIndex, Module, PrimaryMap, SignatureIndex, TableIndex,
-    TableInitialization, TrapCode, VMOffsets, WasmType,
+    packed_option::ReservedValue, DataIndex, DefinedFuncIndex, DefinedGlobalIndex,
+    DefinedMemoryIndex, DefinedTableIndex, ElemIndex, EntityIndex, EntityRef, EntitySet, FuncIndex,
+    GlobalIndex, GlobalInit, GlobalSlot, HostPtr, MemoryIndex, Module, PrimaryMap, SignatureIndex,
+    TableIndex, TableInitialization, TrapCode, VMOffsets, WasmType,
 };
 
 mod allocator;
@@ -85,6 +86,12 @@ pub(crate) struct Instance {
     /// If the index is present in the set, the segment has been dropped.
     dropped_data: EntitySet<DataIndex>,
 
+    /// The leftover segments of `TableInitialization::FuncTable`, in order,
+    /// which were written to defined funcref tables lazily rather than
+    /// during instantiation, because their bases depend on globals or they
+    /// come after such a segment. See `get_table_with_lazy_init`.
+    lazy_table_segments: Vec<LazyTableSegment>,
+
     /// Hosts can store arbitrary per-instance information here.
     ///
     /// Most of the time from Wasmtime this is `Box::new(())`, a noop
@@ -97,6 +104,16 @@ pub(crate) struct Instance {
     vmctx: VMContext,
 }
 
+/// A leftover table segment which is written to a table lazily.
+struct LazyTableSegment {
+    /// The table the segment is written to.
+    table: DefinedTableIndex,
+    /// The index of the segment within the leftover segments.
+    segment: usize,
+    /// The index of the table element the segment is written at.
+    start: u32,
+}
+
 #[allow(clippy::cast_ptr_alignment)]
 impl Instance {
     /// Create an instance at the given memory address.
@@ -127,6 +144,7 @@ impl Instance {
                 tables,
                 dropped_elements,
                 dropped_data,
+                lazy_table_segments: Vec::new(),
                 host_state: req.host_state,
                 vmctx: VMContext {
                     _marker: std::marker::PhantomPinned,
@@ -149,6 +167,24 @@ impl Instance {
         self.runtime_info.module()
     }
 
+    /// Returns the unique ID of the module this instance was created from, if
+    /// it has one.
+    pub(crate) fn module_id(&self) -> Option<CompiledModuleId> {
+        self.runtime_info.unique_id()
+    }
+
+    /// Compiles the locally-defined function `index` of this instance's
+    /// module, returning the address of its code.
+    pub(crate) fn compile_lazily(&self, index: DefinedFuncIndex) -> anyhow::Result<usize> {
+        self.runtime_info.compile_lazily(index)
+    }
+
+    /// Starts recompiling the hot locally-defined function `index` of this
+    /// instance's module with the optimizing tier.
+    pub(crate) fn tier_up(&self, index: DefinedFuncIndex) {
+        self.runtime_info.clone().tier_up(index)
+    }
+
     /// Return the indexed `VMFunctionImport`.
     fn imported_function(&self, index: FuncIndex) -> &VMFunctionImport {
         unsafe { &*self.vmctx_plus_offset(self.offsets.vmctx_vmfunction_import(index)) }
@@ -220,8 +256,18 @@ impl Instance {
     }
 
     /// Return the indexed `VMGlobalDefinition`.
+    ///
+    /// The definitions of globals shared by all instances are read-only.
     fn global_ptr(&self, index: DefinedGlobalIndex) -> *mut VMGlobalDefinition {
-        unsafe { self.vmctx_plus_offset(self.offsets.vmctx_vmglobal_definition(index)) }
+        match self.module().defined_global_slot(index) {
+            GlobalSlot::VMContext(slot) => unsafe {
+                self.vmctx_plus_offset(self.offsets.vmctx_vmglobal_definition(slot))
+            },
+            GlobalSlot::Shared(slot) => unsafe {
+                let globals = self.runtime_info.shared_globals() as *mut VMGlobalDefinition;
+                globals.add(slot as usize)
+            },
+        }
     }
 
     /// Get a raw pointer to the global at the given index regardless whether it
@@ -271,7 +317,12 @@ impl Instance {
         ptr
     }
 
+    /// Sets the store of this instance, along with the pointers into it which
+    /// compiled code uses.
     pub unsafe fn set_store(&mut self, store: *mut dyn Store) {
+        *self.runtime_limits() = (*store).vmruntime_limits();
+        *self.epoch_ptr() = (*store).epoch_ptr();
+        *self.externref_activations_table() = (*store).externref_activations_table().0;
         *self.vmctx_plus_offset(self.offsets.vmctx_store()) = store;
     }
 
@@ -386,7 +437,8 @@ impl Instance {
     ///
     /// Returns `None` if memory can't be grown by the specified amount
     /// of pages. Returns `Some` with the old size in bytes if growth was
-    /// successful.
+    /// successful. Returns an error if the memory may only be grown by the
+    /// host.
     pub(crate) fn memory_grow(
         &mut self,
         index: MemoryIndex,
@@ -405,6 +457,9 @@ impl Instance {
         };
         let store = unsafe { &mut *instance.store() };
         let memory = &mut instance.memories[idx];
+        if delta != 0 && !memory.guest_growable() {
+            bail!("memory.grow is disabled for a memory whose growth is managed by the host");
+        }
 
         let result = unsafe { memory.grow(delta, store) };
         let vmmemory = memory.vmmemory();
@@ -663,13 +718,14 @@ impl Instance {
 
         let src = self.validate_inbounds(src_mem.current_length, src, len)?;
         let dst = self.validate_inbounds(dst_mem.current_length, dst, len)?;
+        self.validate_writable(dst_index, dst, len as usize)?;
 
         // Bounds and casts are checked above, by this point we know that
         // everything is safe.
         unsafe {
             let dst = dst_mem.base.add(dst);
             let src = src_mem.base.add(src);
-            ptr::copy(src, dst, len as usize);
+            crate::bulk_memory::copy(src, dst, len as usize);
         }
 
         Ok(())
@@ -688,6 +744,26 @@ impl Instance {
         }
     }
 
+    /// Returns a trap if any of the `len` bytes at `dst` in the memory
+    /// `index`, which are known to be in bounds, were made read-only.
+    fn validate_writable(&self, index: MemoryIndex, dst: usize, len: usize) -> Result<(), Trap> {
+        let memory = if let Some(idx) = self.module().defined_memory_index(index) {
+            &self.memories[idx]
+        } else {
+            let import = self.imported_memory(index);
+            unsafe {
+                let foreign_instance = (*import.vmctx).instance();
+                let foreign_memory_index = foreign_instance.memory_index(&*import.from);
+                &foreign_instance.memories[foreign_memory_index]
+            }
+        };
+        if memory.is_writable(dst..dst + len) {
+            Ok(())
+        } else {
+            Err(Trap::wasm(TrapCode::HeapOutOfBounds))
+        }
+    }
+
     /// Perform the `memory.fill` operation on a locally defined memory.
     ///
     /// # Errors
@@ -702,12 +778,13 @@ impl Instance {
     ) -> Result<(), Trap> {
         let memory = self.get_memory(memory_index);
         let dst = self.validate_inbounds(memory.current_length, dst, len)?;
+        self.validate_writable(memory_index, dst, len as usize)?;
 
         // Bounds and casts are checked above, by this point we know that
         // everything is safe.
         unsafe {
             let dst = memory.base.add(dst);
-            ptr::write_bytes(dst, val, len as usize);
+            crate::bulk_memory::fill(dst, val, len as usize);
         }
 
         Ok(())
@@ -754,6 +831,7 @@ impl Instance {
         let dst = self.validate_inbounds(memory.current_length, dst, len.into())?;
         let src = self.validate_inbounds(data.len(), src.into(), len.into())?;
         let len = len as usize;
+        self.validate_writable(memory_index, dst, len)?;
 
         let src_slice = &data[src..(src + len)];
 
@@ -805,16 +883,32 @@ impl Instance {
                     }
                 };
                 if value.is_uninit() {
-                    let table_init = match &instance.module().table_initialization {
+                    let (tables, segments) = match &instance.module().table_initialization {
                         // We unfortunately can't borrow `tables`
                         // outside the loop because we need to call
                         // `get_caller_checked_anyfunc` (a `&mut`
                         // method) below; so unwrap it dynamically
                         // here.
-                        TableInitialization::FuncTable { tables, .. } => tables,
+                        TableInitialization::FuncTable { tables, segments } => (tables, segments),
                         _ => break,
-                    }
-                    .get(table_index);
+                    };
+                    let table_init = tables.get(table_index);
+
+                    // Segments written lazily come after the table's
+                    // pre-computed contents, so the last of them which
+                    // covers this element takes precedence.
+                    let lazy_index = instance
+                        .lazy_table_segments
+                        .iter()
+                        .rev()
+                        .filter(|lazy| lazy.table == idx)
+                        .find_map(|lazy| {
+                            let offset = i.checked_sub(lazy.start)?;
+                            segments[lazy.segment]
+                                .elements
+                                .get(offset as usize)
+                                .cloned()
+                        });
 
                     // The TableInitialization::FuncTable elements table may
                     // be smaller than the current size of the table: it
@@ -824,8 +918,9 @@ impl Instance {
                     // if there is no initializer. We do a checked `get()` on
                     // the initializer table below and unwrap to a null if
                     // we're past its end.
-                    let func_index =
-                        table_init.and_then(|indices| indices.get(i as usize).cloned());
+                    let func_index = lazy_index.or_else(|| {
+                        table_init.and_then(|indices| indices.get(i as usize).cloned())
+                    });
                     let anyfunc = func_index
                         .and_then(|func_index| instance.get_caller_checked_anyfunc(func_index))
                         .unwrap_or(std::ptr::null_mut());
@@ -880,9 +975,6 @@ impl Instance {
         assert!(std::ptr::eq(module, self.module().as_ref()));
 
         if let Some(store) = store.as_raw() {
-            *self.runtime_limits() = (*store).vmruntime_limits();
-            *self.epoch_ptr() = (*store).epoch_ptr();
-            *self.externref_activations_table() = (*store).externref_activations_table().0;
             self.set_store(store);
         }
 
@@ -890,6 +982,10 @@ impl Instance {
         let signatures = self.runtime_info.signature_ids();
         *self.vmctx_plus_offset(self.offsets.vmctx_signature_ids_array()) = signatures.as_ptr();
 
+        // Initialize the pointer to the globals shared by all instances
+        *self.vmctx_plus_offset(self.offsets.vmctx_shared_globals()) =
+            self.runtime_info.shared_globals();
+
         // Initialize the built-in functions
         *self.vmctx_plus_offset(self.offsets.vmctx_builtin_functions()) =
             &VMBuiltinFunctionsArray::INIT;
@@ -924,12 +1020,23 @@ impl Instance {
         // we eagerly construct each element in it whenever asked for a
         // reference to that element. In other words, there is no state
         // needed to track the lazy-init, so we don't need to initialize
-        // any state now.
+        // any state now. The exception are the anyfuncs which the elements
+        // of shared tables refer to, since compiled code reads those
+        // without asking for them.
+        let runtime_info = self.runtime_info.clone();
+        if let Some(shared) = runtime_info.shared_tables() {
+            for func in shared.funcs() {
+                self.get_caller_checked_anyfunc(*func);
+            }
+        }
 
         // Initialize the defined tables
+        let vmctx = self.vmctx_ptr();
         let mut ptr = self.vmctx_plus_offset(self.offsets.vmctx_tables_begin());
         for i in 0..module.table_plans.len() - module.num_imported_tables {
-            ptr::write(ptr, self.tables[DefinedTableIndex::new(i)].vmtable());
+            let table = &mut self.tables[DefinedTableIndex::new(i)];
+            table.set_vmctx(vmctx);
+            ptr::write(ptr, table.vmtable());
             ptr = ptr.add(1);
         }
 
@@ -942,12 +1049,30 @@ impl Instance {
 
         // Initialize the defined globals
         self.initialize_vmctx_globals(module);
+
+        // Initialize the `call_indirect` caches
+        self.reset_call_indirect_caches();
+    }
+
+    /// Empties all of the `call_indirect` caches, so that each of them looks
+    /// up its callee in its table on its next call.
+    fn reset_call_indirect_caches(&mut self) {
+        unsafe {
+            let mut ptr = self.vmctx_plus_offset(self.offsets.vmctx_call_indirect_caches_begin());
+            for _ in 0..self.module().call_indirect_caches.len() {
+                ptr::write(ptr, VMCallIndirectCache::EMPTY);
+                ptr = ptr.add(1);
+            }
+        }
     }
 
     unsafe fn initialize_vmctx_globals(&mut self, module: &Module) {
         let num_imports = module.num_imported_globals;
         for (index, global) in module.globals.iter().skip(num_imports) {
             let def_index = module.defined_global_index(index).unwrap();
+            if let GlobalSlot::Shared(_) = module.defined_global_slot(def_index) {
+                continue;
+            }
             let to = self.global_ptr(def_index);
 
             // Initialize the global before writing to it
@@ -1000,10 +1125,14 @@ impl Drop for Instance {
                 None => continue,
             };
             match global.wasm_ty {
-                // For now only externref globals need to get destroyed
+                // For now only externref globals need to get destroyed, and
+                // shared ones are always null.
                 WasmType::ExternRef => {}
                 _ => continue,
             }
+            if let GlobalSlot::Shared(_) = self.module().defined_global_slot(idx) {
+                continue;
+            }
             unsafe {
                 drop((*self.global_ptr(idx)).as_externref_mut().take());
             }
@@ -1102,6 +1231,32 @@ impl InstanceHandle {
         self.instance().host_state()
     }
 
+    /// Returns the element segments which have been dropped with `elem.drop`.
+    pub fn dropped_elements(&self) -> impl Iterator<Item = ElemIndex> + '_ {
+        self.instance().dropped_elements.keys()
+    }
+
+    /// Returns the data segments which have been dropped with `data.drop`.
+    pub fn dropped_data(&self) -> impl Iterator<Item = DataIndex> + '_ {
+        self.instance().dropped_data.keys()
+    }
+
+    /// Drops the element segment `elem_index`, as `elem.drop` does.
+    pub fn elem_drop(&mut self, elem_index: ElemIndex) {
+        self.instance_mut().elem_drop(elem_index)
+    }
+
+    /// Drops the data segment `data_index`, as `data.drop` does.
+    pub fn data_drop(&mut self, data_index: DataIndex) {
+        self.instance_mut().data_drop(data_index)
+    }
+
+    /// Empties the `call_indirect` caches of the instance, which must be done
+    /// whenever the elements of the tables they call through are changed.
+    pub fn reset_call_indirect_caches(&mut self) {
+        self.instance_mut().reset_call_indirect_caches()
+    }
+
     /// Return the memory index for the given `VMMemoryDefinition` in this instance.
     pub unsafe fn memory_index(&self, memory: &VMMemoryDefinition) -> DefinedMemoryIndex {
         self.instance().memory_index(memory)
@@ -1133,6 +1288,26 @@ impl InstanceHandle {
         self.instance_mut().get_table_with_lazy_init(index, range)
     }
 
+    /// Returns the tables defined locally within this module.
+    pub fn defined_tables(&self) -> impl ExactSizeIterator<Item = &Table> + '_ {
+        self.instance().tables.values()
+    }
+
+    /// Returns the `externref`s held by the globals defined locally within
+    /// this module.
+    pub fn defined_externref_globals(&self) -> impl Iterator<Item = &VMExternRef> + '_ {
+        let instance = self.instance();
+        let module = instance.module();
+        module
+            .globals
+            .iter()
+            .filter(|(_, global)| global.wasm_ty == WasmType::ExternRef)
+            .filter_map(move |(index, _)| {
+                let index = module.defined_global_index(index)?;
+                unsafe { instance.global(index).as_externref().as_ref() }
+            })
+    }
+
     /// Return a reference to the contained `Instance`.
     #[inline]
     pub(crate) fn instance(&self) -> &Instance {
@@ -1,10 +1,9 @@
 use crate::imports::Imports;
-use crate::instance::{Instance, InstanceHandle, RuntimeMemoryCreator};
+use crate::instance::{Instance, InstanceHandle, LazyTableSegment, RuntimeMemoryCreator};
 use crate::memory::{DefaultMemoryCreator, Memory};
 use crate::table::Table;
 use crate::traphandlers::Trap;
-use crate::ModuleRuntimeInfo;
-use crate::Store;
+use crate::{ModuleRuntimeInfo, ProtectionKey, Store};
 use anyhow::Result;
 use std::alloc;
 use std::any::Any;
@@ -14,9 +13,9 @@ use std::slice;
 use std::sync::Arc;
 use thiserror::Error;
 use wasmtime_environ::{
-    DefinedMemoryIndex, DefinedTableIndex, HostPtr, InitMemory, MemoryInitialization,
-    MemoryInitializer, Module, PrimaryMap, TableInitialization, TableInitializer, TrapCode,
-    VMOffsets, WasmType, WASM_PAGE_SIZE,
+    DataIndex, DefinedMemoryIndex, DefinedTableIndex, ElemIndex, EntitySet, HostPtr, InitMemory,
+    MemoryInitialization, MemoryInitializer, Module, PrimaryMap, TableInitialization,
+    TableInitializer, TrapCode, VMOffsets, WasmType, WASM_PAGE_SIZE,
 };
 
 #[cfg(feature = "pooling-allocator")]
@@ -55,6 +54,10 @@ pub struct InstanceAllocationRequest<'a> {
     /// We use a number of `PhantomPinned` declarations to indicate this to the
     /// compiler. More info on this in `wasmtime/src/store.rs`
     pub store: StorePtr,
+
+    /// The memory protection key of the store this instance belongs to, if
+    /// any, from [`InstanceAllocator::next_available_pkey`].
+    pub pkey: Option<ProtectionKey>,
 }
 
 /// A pointer to a Store. This Option<*mut dyn Store> is wrapped in a struct
@@ -109,6 +112,42 @@ pub enum InstantiationError {
     /// A limit on how many instances are supported has been reached.
     #[error("Limit of {0} concurrent instances has been reached")]
     Limit(u32),
+
+    /// A data or element segment didn't fit in the memory or table it
+    /// initializes, which failed instantiation with the `Link` or `Trap`
+    /// error alongside it.
+    #[error("Segment out of bounds")]
+    Segment(SegmentOutOfBounds, Box<InstantiationError>),
+}
+
+/// A data or element segment which didn't fit in the memory or table it
+/// initializes.
+#[derive(Debug, Clone)]
+pub struct SegmentOutOfBounds {
+    /// The segment which didn't fit.
+    pub segment: SegmentIndex,
+    /// The offset of the memory, or index of the table, it was written at.
+    pub start: u64,
+    /// The number of bytes or elements in the segment.
+    pub len: u64,
+    /// The size of the memory in bytes, or of the table in elements.
+    pub size: u64,
+}
+
+/// The index of either a data segment or an element segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentIndex {
+    /// A data segment.
+    Data(DataIndex),
+    /// An element segment.
+    Elem(ElemIndex),
+}
+
+impl SegmentOutOfBounds {
+    /// Wraps `error`, which is what initializing this segment failed with.
+    fn into_error(self, error: InstantiationError) -> InstantiationError {
+        InstantiationError::Segment(self, Box::new(error))
+    }
 }
 
 /// An error while creating a fiber stack.
@@ -178,6 +217,24 @@ pub unsafe trait InstanceAllocator: Send + Sync {
     /// Use extreme care when deallocating an instance so that there are no dangling instance pointers.
     unsafe fn deallocate(&self, handle: &InstanceHandle);
 
+    /// Returns the number of instance slots in use and the total number of
+    /// instance slots, for allocators which allocate instances from a
+    /// fixed-size pool.
+    fn instance_slots(&self) -> Option<(usize, usize)> {
+        None
+    }
+
+    /// Releases the memory images which the allocator keeps for modules that
+    /// are no longer alive, for allocators which keep the images of the
+    /// instances they deallocate mapped for reuse.
+    fn purge_unused_images(&self) {}
+
+    /// Returns the memory protection key for a new store to allocate its
+    /// instances with, for allocators which stripe memories across keys.
+    fn next_available_pkey(&self) -> Option<ProtectionKey> {
+        None
+    }
+
     /// Allocates a fiber stack for calling async functions on.
     #[cfg(feature = "async")]
     fn allocate_fiber_stack(&self) -> Result<wasmtime_fiber::FiberStack, FiberStackError>;
@@ -215,6 +272,27 @@ fn get_table_init_start(
     }
 }
 
+/// Returns which of `segment`'s elements don't fit in its table, if any
+/// don't, given that it's written at `start`.
+fn table_segment_out_of_bounds(
+    instance: &mut Instance,
+    segment: &TableInitializer,
+    start: u32,
+) -> Option<SegmentOutOfBounds> {
+    let table = unsafe { &*instance.get_table(segment.table_index) };
+    let len = segment.elements.len() as u64;
+    let size = u64::from(table.size());
+    if u64::from(start) + len <= size {
+        return None;
+    }
+    Some(SegmentOutOfBounds {
+        segment: SegmentIndex::Elem(segment.elem_index),
+        start: u64::from(start),
+        len,
+        size,
+    })
+}
+
 fn check_table_init_bounds(
     instance: &mut Instance,
     module: &Module,
@@ -223,20 +301,11 @@ fn check_table_init_bounds(
         TableInitialization::FuncTable { segments, .. }
         | TableInitialization::Segments { segments } => {
             for segment in segments {
-                let table = unsafe { &*instance.get_table(segment.table_index) };
                 let start = get_table_init_start(segment, instance)?;
-                let start = usize::try_from(start).unwrap();
-                let end = start.checked_add(segment.elements.len());
-
-                match end {
-                    Some(end) if end <= table.size() as usize => {
-                        // Initializer is in bounds
-                    }
-                    _ => {
-                        return Err(InstantiationError::Link(LinkError(
-                            "table out of bounds: elements segment does not fit".to_owned(),
-                        )))
-                    }
+                if let Some(oob) = table_segment_out_of_bounds(instance, segment, start) {
+                    return Err(oob.into_error(InstantiationError::Link(LinkError(
+                        "table out of bounds: elements segment does not fit".to_owned(),
+                    ))));
                 }
             }
         }
@@ -245,6 +314,9 @@ fn check_table_init_bounds(
     Ok(())
 }
 
+/// The most leftover table segments an instance writes lazily.
+const MAX_LAZY_TABLE_SEGMENTS: usize = 16;
+
 fn initialize_tables(instance: &mut Instance, module: &Module) -> Result<(), InstantiationError> {
     // Note: if the module's table initializer state is in
     // FuncTable mode, we will lazily initialize tables based on
@@ -253,19 +325,63 @@ fn initialize_tables(instance: &mut Instance, module: &Module) -> Result<(), Ins
     // incorporated. So we have a unified handler here that
     // iterates over all segments (Segments mode) or leftover
     // segments (FuncTable mode) to initialize.
+    let lazy = match module.table_initialization {
+        TableInitialization::FuncTable { .. } => true,
+        TableInitialization::Segments { .. } => false,
+    };
+    // The tables which have had a segment written eagerly, after which all
+    // of their later segments must be too, so that they still overwrite the
+    // earlier ones.
+    let mut eager_tables = EntitySet::with_capacity(module.table_plans.len());
+
     match &module.table_initialization {
         TableInitialization::FuncTable { segments, .. }
         | TableInitialization::Segments { segments } => {
-            for segment in segments {
+            for (i, segment) in segments.iter().enumerate() {
+                // The elements of shared tables are already in place.
+                if module.is_shared_table(segment.table_index) {
+                    continue;
+                }
+
+                let start = get_table_init_start(segment, instance)?;
+
+                // In FuncTable mode leftover segments of defined funcref
+                // tables which fit in them are written lazily as well,
+                // unless there are so many of them that looking through them
+                // for each element would be slow.
+                if let Some(table) = module.defined_table_index(segment.table_index) {
+                    if lazy
+                        && !eager_tables.contains(segment.table_index)
+                        && module.table_plans[segment.table_index].table.wasm_ty
+                            == WasmType::FuncRef
+                        && instance.lazy_table_segments.len() < MAX_LAZY_TABLE_SEGMENTS
+                        && table_segment_out_of_bounds(instance, segment, start).is_none()
+                    {
+                        instance.lazy_table_segments.push(LazyTableSegment {
+                            table,
+                            segment: i,
+                            start,
+                        });
+                        continue;
+                    }
+                }
+                eager_tables.insert(segment.table_index);
+
                 instance
                     .table_init_segment(
                         segment.table_index,
                         &segment.elements,
-                        get_table_init_start(segment, instance)?,
+                        start,
                         0,
                         segment.elements.len() as u32,
                     )
-                    .map_err(InstantiationError::Trap)?;
+                    .map_err(|trap| {
+                        let error = InstantiationError::Trap(trap);
+                        match table_segment_out_of_bounds(instance, segment, start) {
+                            Some(oob) => oob.into_error(error),
+                            None => error,
+                        }
+                    })?;
             }
         }
     }
@@ -303,26 +419,37 @@ fn get_memory_init_start(
     }
 }
 
+/// Returns which of `init`'s bytes don't fit in its memory, if any don't,
+/// given that it's written at `start`.
+fn memory_segment_out_of_bounds(
+    instance: &Instance,
+    init: &MemoryInitializer,
+    start: u64,
+) -> Option<SegmentOutOfBounds> {
+    let memory = instance.get_memory(init.memory_index);
+    let len = u64::from(init.data.end - init.data.start);
+    let size = memory.current_length as u64;
+    match start.checked_add(len) {
+        Some(end) if end <= size => None,
+        _ => Some(SegmentOutOfBounds {
+            segment: SegmentIndex::Data(init.data_index),
+            start,
+            len,
+            size,
+        }),
+    }
+}
+
 fn check_memory_init_bounds(
     instance: &Instance,
     initializers: &[MemoryInitializer],
 ) -> Result<(), InstantiationError> {
     for init in initializers {
-        let memory = instance.get_memory(init.memory_index);
         let start = get_memory_init_start(init, instance)?;
-        let end = usize::try_from(start)
-            .ok()
-            .and_then(|start| start.checked_add(init.data.len()));
-
-        match end {
-            Some(end) if end <= memory.current_length => {
-                // Initializer is in bounds
-            }
-            _ => {
-                return Err(InstantiationError::Link(LinkError(
-                    "memory out of bounds: data segment does not fit".into(),
-                )))
-            }
+        if let Some(oob) = memory_segment_out_of_bounds(instance, init, start) {
+            return Err(oob.into_error(InstantiationError::Link(LinkError(
+                "memory out of bounds: data segment does not fit".into(),
+            ))));
         }
     }
 
@@ -380,9 +507,22 @@ fn initialize_memories(instance: &mut Instance, module: &Module) -> Result<(), I
         },
     );
     if !ok {
-        return Err(InstantiationError::Trap(Trap::wasm(
-            TrapCode::HeapOutOfBounds,
-        )));
+        let error = InstantiationError::Trap(Trap::wasm(TrapCode::HeapOutOfBounds));
+        // Initialization stopped at the first segment which didn't fit, which
+        // is found again to say which it was. Only segmented initialization
+        // can fail, since static initialization was validated up front.
+        if let MemoryInitialization::Segmented(initializers) = &module.memory_initialization {
+            for init in initializers {
+                let start = match get_memory_init_start(init, instance) {
+                    Ok(start) => start,
+                    Err(_) => break,
+                };
+                if let Some(oob) = memory_segment_out_of_bounds(instance, init, start) {
+                    return Err(oob.into_error(error));
+                }
+            }
+        }
+        return Err(error);
     }
 
     Ok(())
@@ -451,15 +591,22 @@ impl OnDemandInstanceAllocator {
         let num_imports = module.num_imported_tables;
         let mut tables: PrimaryMap<DefinedTableIndex, _> =
             PrimaryMap::with_capacity(module.table_plans.len() - num_imports);
-        for (_, table) in module.table_plans.iter().skip(num_imports) {
-            tables.push(
-                Table::new_dynamic(table, unsafe {
-                    store
-                        .get()
-                        .expect("if module has table plans, store is not empty")
-                })
-                .map_err(InstantiationError::Resource)?,
-            );
+        for (index, table) in module.table_plans.iter().skip(num_imports) {
+            let store = unsafe {
+                store
+                    .get()
+                    .expect("if module has table plans, store is not empty")
+            };
+            let shared = runtime_info
+                .shared_tables()
+                .and_then(|shared| shared.elements(module.defined_table_index(index).unwrap()));
+            let table = match shared {
+                // The module which owns the shared elements outlives its
+                // instances.
+                Some(data) => unsafe { Table::new_shared(table, data, store) },
+                None => Table::new_dynamic(table, store),
+            };
+            tables.push(table.map_err(InstantiationError::Resource)?);
         }
         Ok(tables)
     }
@@ -470,10 +617,8 @@ impl OnDemandInstanceAllocator {
         runtime_info: &Arc<dyn ModuleRuntimeInfo>,
     ) -> Result<PrimaryMap<DefinedMemoryIndex, Memory>, InstantiationError> {
         let module = runtime_info.module();
-        let creator = self
-            .mem_creator
-            .as_deref()
-            .unwrap_or_else(|| &DefaultMemoryCreator);
+        let default_creator = DefaultMemoryCreator::default();
+        let creator = self.mem_creator.as_deref().unwrap_or(&default_creator);
         let num_imports = module.num_imported_memories;
         let mut memories: PrimaryMap<DefinedMemoryIndex, _> =
             PrimaryMap::with_capacity(module.memory_plans.len() - num_imports);
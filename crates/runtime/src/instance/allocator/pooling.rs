@@ -12,11 +12,12 @@ use super::{
     InstantiationError,
 };
 use crate::{instance::Instance, Memory, Mmap, Table};
-use crate::{MemoryImageSlot, ModuleRuntimeInfo, Store};
+use crate::{MemoryImageSlot, ModuleRuntimeInfo, ProtectionKey, ProtectionMask, Store};
 use anyhow::{anyhow, bail, Context, Result};
 use libc::c_void;
 use std::convert::TryFrom;
 use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Mutex;
 use wasmtime_environ::{
     DefinedMemoryIndex, DefinedTableIndex, HostPtr, Module, PrimaryMap, Tunables, VMOffsets,
@@ -201,21 +202,35 @@ impl Default for PoolingAllocationStrategy {
 /// structure depending on the limits used to create the pool.
 ///
 /// The pool maintains a free list for fast instance allocation.
+///
+/// When memories are striped across memory protection keys, the instances
+/// are divided into one stripe per key: instance `i` belongs to stripe
+/// `i % stripes.len()`, and each stripe has its own free list.
 #[derive(Debug)]
 struct InstancePool {
     mapping: Mmap,
     instance_size: usize,
     max_instances: usize,
-    index_allocator: Mutex<PoolingAllocationState>,
+    stripes: Vec<Stripe>,
     memories: MemoryPool,
     tables: TablePool,
 }
 
+/// The instances whose memories are protected with one protection key.
+#[derive(Debug)]
+struct Stripe {
+    pkey: Option<ProtectionKey>,
+    /// The free list of this stripe's instances, indexed by `i /
+    /// stripes.len()` for instance `i`.
+    index_allocator: Mutex<PoolingAllocationState>,
+}
+
 impl InstancePool {
     fn new(
         strategy: PoolingAllocationStrategy,
         instance_limits: &InstanceLimits,
         tunables: &Tunables,
+        memory_protection_keys: usize,
     ) -> Result<Self> {
         let page_size = region::page::size();
 
@@ -233,12 +248,29 @@ impl InstancePool {
         let mapping = Mmap::accessible_reserved(allocation_size, allocation_size)
             .context("failed to create instance pool mapping")?;
 
+        // Striping only packs memories more densely with at least two keys,
+        // and every stripe needs at least one instance.
+        let mut pkeys = ProtectionKey::allocate(memory_protection_keys.min(max_instances));
+        if pkeys.len() < 2 {
+            pkeys.clear();
+        }
+        let num_stripes = pkeys.len().max(1);
+        let stripes = (0..num_stripes)
+            .map(|i| Stripe {
+                pkey: pkeys.get(i).copied(),
+                index_allocator: Mutex::new(PoolingAllocationState::new(
+                    strategy,
+                    (max_instances + num_stripes - 1 - i) / num_stripes,
+                )),
+            })
+            .collect();
+
         let pool = Self {
             mapping,
             instance_size,
             max_instances,
-            index_allocator: Mutex::new(PoolingAllocationState::new(strategy, max_instances)),
-            memories: MemoryPool::new(instance_limits, tunables)?,
+            stripes,
+            memories: MemoryPool::new(instance_limits, tunables, pkeys)?,
             tables: TablePool::new(instance_limits)?,
         };
 
@@ -305,26 +337,53 @@ impl InstancePool {
         &self,
         req: InstanceAllocationRequest,
     ) -> Result<InstanceHandle, InstantiationError> {
+        let stripe = self
+            .stripes
+            .iter()
+            .position(|s| s.pkey == req.pkey)
+            .unwrap_or(0);
         let index = {
-            let mut alloc = self.index_allocator.lock().unwrap();
+            let mut alloc = self.stripes[stripe].index_allocator.lock().unwrap();
             if alloc.is_empty() {
                 return Err(InstantiationError::Limit(self.max_instances as u32));
             }
-            alloc.alloc(req.runtime_info.unique_id()).index()
+            alloc.alloc(req.runtime_info.unique_id()).index() * self.stripes.len() + stripe
         };
 
+        // Initializing the instance writes to its memories, which the current
+        // thread may not have been given access to if they've got a key.
+        if req.pkey.is_some() {
+            ProtectionMask::all().allow();
+        }
+
         match unsafe { self.initialize_instance(index, req) } {
             Ok(handle) => Ok(handle),
             Err(e) => {
                 // If we failed to initialize the instance, there's no need to drop
                 // it as it was never "allocated", but we still need to free the
                 // instance's slot.
-                self.index_allocator.lock().unwrap().free(SlotId(index));
+                self.free(index);
                 Err(e)
             }
         }
     }
 
+    fn free(&self, index: usize) {
+        let stripe = &self.stripes[index % self.stripes.len()];
+        stripe
+            .index_allocator
+            .lock()
+            .unwrap()
+            .free(SlotId(index / self.stripes.len()));
+    }
+
+    fn free_slots(&self) -> usize {
+        self.stripes
+            .iter()
+            .map(|s| s.index_allocator.lock().unwrap().free_slots())
+            .sum()
+    }
+
     fn deallocate(&self, handle: &InstanceHandle) {
         let addr = handle.instance as usize;
         let base = self.mapping.as_ptr() as usize;
@@ -351,7 +410,7 @@ impl InstancePool {
         // touched again until we write a fresh Instance in-place with
         // std::ptr::write in allocate() above.
 
-        self.index_allocator.lock().unwrap().free(SlotId(index));
+        self.free(index);
     }
 
     fn allocate_instance_resources(
@@ -481,9 +540,23 @@ impl InstancePool {
             .map_err(InstantiationError::Resource)?;
 
         let mut bases = self.tables.get(instance_index);
-        for (_, plan) in module.table_plans.iter().skip(module.num_imported_tables) {
+        for (index, plan) in module.table_plans.iter().skip(module.num_imported_tables) {
             let base = bases.next().unwrap() as _;
 
+            // Shared tables leave their slot unused.
+            let shared = runtime_info
+                .shared_tables()
+                .and_then(|shared| shared.elements(module.defined_table_index(index).unwrap()));
+            if let Some(data) = shared {
+                // The module which owns the shared elements outlives its
+                // instances.
+                tables.push(
+                    unsafe { Table::new_shared(plan, data, &mut *store.unwrap()) }
+                        .map_err(InstantiationError::Resource)?,
+                );
+                continue;
+            }
+
             commit_table_pages(
                 base as *mut u8,
                 self.tables.max_elements as usize * mem::size_of::<*mut u8>(),
@@ -513,6 +586,9 @@ impl InstancePool {
         // Decommit any tables that were used
         for (table, base) in tables.values_mut().zip(self.tables.get(instance_index)) {
             let table = mem::take(table);
+            if table.is_shared() {
+                continue;
+            }
             assert!(table.is_static());
 
             let size = round_up_to_pow2(
@@ -641,6 +717,13 @@ impl InstancePool {
 ///
 /// Each instance index into the pool returns an iterator over the base addresses
 /// of the instance's linear memories.
+///
+/// If memories are striped across protection keys, the slots of consecutive
+/// stripes are interleaved so that the slots following any one slot, up to its
+/// whole reservation and guard region, either belong to another key or are
+/// inaccessible. Wasm running with only its own key enabled can't access any
+/// of them, so each slot only needs to be `1 / pkeys.len()` of the
+/// reservation apart.
 #[derive(Debug)]
 struct MemoryPool {
     mapping: Mmap,
@@ -648,9 +731,12 @@ struct MemoryPool {
     // dynamically transfer ownership of a slot to a Memory when in
     // use.
     image_slots: Vec<Mutex<Option<MemoryImageSlot>>>,
-    // The size, in bytes, of each linear memory's reservation plus the guard
-    // region allocated for it.
-    memory_reservation_size: usize,
+    // The distance, in bytes, between consecutive slots. This is the size of
+    // each linear memory's reservation plus the guard region allocated for it,
+    // unless memories are striped across protection keys.
+    memory_stride: usize,
+    // The protection keys that memories are striped across, if any.
+    pkeys: Vec<ProtectionKey>,
     // The maximum size, in bytes, of each linear memory. Guaranteed to be a
     // whole number of wasm pages.
     max_memory_size: usize,
@@ -663,7 +749,11 @@ struct MemoryPool {
 }
 
 impl MemoryPool {
-    fn new(instance_limits: &InstanceLimits, tunables: &Tunables) -> Result<Self> {
+    fn new(
+        instance_limits: &InstanceLimits,
+        tunables: &Tunables,
+        pkeys: Vec<ProtectionKey>,
+    ) -> Result<Self> {
         // The maximum module memory page count cannot exceed 65536 pages
         if instance_limits.memory_pages > 0x10000 {
             bail!(
@@ -699,6 +789,13 @@ impl MemoryPool {
 
         let max_instances = instance_limits.count as usize;
         let max_memories = instance_limits.memories as usize;
+        let max_memory_size = (instance_limits.memory_pages as usize) * (WASM_PAGE_SIZE as usize);
+        let num_stripes = pkeys.len().max(1);
+        let memory_stride = round_up_to_pow2(
+            max_memory_size.max((memory_size + num_stripes - 1) / num_stripes),
+            region::page::size(),
+        )
+        .min(memory_size);
         let initial_memory_offset = if tunables.guard_before_linear_memory {
             usize::try_from(tunables.static_memory_offset_guard_size).unwrap()
         } else {
@@ -718,9 +815,15 @@ impl MemoryPool {
         // `initial_memory_offset` variable here. If guards aren't specified
         // before linear memories this is set to `0`, otherwise it's set to
         // the same size as guard regions for other memories.
-        let allocation_size = memory_size
-            .checked_mul(max_memories)
-            .and_then(|c| c.checked_mul(max_instances))
+        //
+        // With striping, slots are allocated for whole groups of instances,
+        // one from each stripe, and the last slot is followed by the rest of
+        // its reservation.
+        let num_slots =
+            (max_instances + num_stripes - 1) / num_stripes * num_stripes * max_memories;
+        let allocation_size = memory_stride
+            .checked_mul(num_slots)
+            .and_then(|c| c.checked_add(memory_size - memory_stride))
             .and_then(|c| c.checked_add(initial_memory_offset))
             .ok_or_else(|| {
                 anyhow!("total size of memory reservation exceeds addressable memory")
@@ -730,11 +833,20 @@ impl MemoryPool {
         let mapping = Mmap::accessible_reserved(0, allocation_size)
             .context("failed to create memory pool mapping")?;
 
-        let num_image_slots = if cfg!(memory_init_cow) {
-            max_instances * max_memories
-        } else {
-            0
-        };
+        // Tag each striped slot with its key. The slot stays inaccessible
+        // until it's committed, which preserves the key.
+        if !pkeys.is_empty() && memory_stride > 0 {
+            for slot in 0..num_slots {
+                let offset = initial_memory_offset + slot * memory_stride;
+                pkeys[slot % pkeys.len()].protect(
+                    unsafe { mapping.as_mut_ptr().add(offset) },
+                    memory_stride,
+                    false,
+                )?;
+            }
+        }
+
+        let num_image_slots = if cfg!(memory_init_cow) { num_slots } else { 0 };
         let image_slots: Vec<_> = std::iter::repeat_with(|| Mutex::new(None))
             .take(num_image_slots)
             .collect();
@@ -742,22 +854,32 @@ impl MemoryPool {
         let pool = Self {
             mapping,
             image_slots,
-            memory_reservation_size: memory_size,
+            memory_stride,
+            pkeys,
             initial_memory_offset,
             max_memories,
             max_instances,
-            max_memory_size: (instance_limits.memory_pages as usize) * (WASM_PAGE_SIZE as usize),
+            max_memory_size,
         };
 
         Ok(pool)
     }
 
-    fn get_base(&self, instance_index: usize, memory_index: DefinedMemoryIndex) -> *mut u8 {
+    /// Returns the index of the slot for the given memory, which is protected
+    /// with key `slot % pkeys.len()` when striping.
+    fn slot(&self, instance_index: usize, memory_index: DefinedMemoryIndex) -> usize {
         assert!(instance_index < self.max_instances);
         let memory_index = memory_index.as_u32() as usize;
         assert!(memory_index < self.max_memories);
-        let idx = instance_index * self.max_memories + memory_index;
-        let offset = self.initial_memory_offset + idx * self.memory_reservation_size;
+        let num_stripes = self.pkeys.len().max(1);
+        let group = instance_index / num_stripes;
+        let stripe = instance_index % num_stripes;
+        (group * self.max_memories + memory_index) * num_stripes + stripe
+    }
+
+    fn get_base(&self, instance_index: usize, memory_index: DefinedMemoryIndex) -> *mut u8 {
+        let offset = self.initial_memory_offset
+            + self.slot(instance_index, memory_index) * self.memory_stride;
         unsafe { self.mapping.as_mut_ptr().offset(offset as isize) }
     }
 
@@ -773,18 +895,36 @@ impl MemoryPool {
         instance_index: usize,
         memory_index: DefinedMemoryIndex,
     ) -> MemoryImageSlot {
-        let idx = instance_index * self.max_memories + (memory_index.as_u32() as usize);
+        let idx = self.slot(instance_index, memory_index);
         let maybe_slot = self.image_slots[idx].lock().unwrap().take();
 
         maybe_slot.unwrap_or_else(|| {
-            MemoryImageSlot::create(
+            let mut slot = MemoryImageSlot::create(
                 self.get_base(instance_index, memory_index) as *mut c_void,
                 0,
                 self.max_memory_size,
-            )
+            );
+            if !self.pkeys.is_empty() {
+                slot.set_protection_key(self.pkeys[idx % self.pkeys.len()]);
+            }
+            slot
         })
     }
 
+    /// Resets the free slots whose memory image, which is kept mapped for the
+    /// next instance of the same module, belongs to a module which was
+    /// dropped, releasing the image.
+    fn purge_unused_images(&self) {
+        for slot in &self.image_slots {
+            let mut slot = slot.lock().unwrap();
+            if slot.as_ref().map_or(false, |s| s.has_unused_image()) {
+                // Dropping the slot maps anonymous memory over it, and the
+                // next instance in it creates a new slot.
+                *slot = None;
+            }
+        }
+    }
+
     /// Return ownership of the given image slot.
     fn return_memory_image_slot(
         &self,
@@ -793,7 +933,7 @@ impl MemoryPool {
         slot: MemoryImageSlot,
     ) {
         assert!(!slot.is_dirty());
-        let idx = instance_index * self.max_memories + (memory_index.as_u32() as usize);
+        let idx = self.slot(instance_index, memory_index);
         *self.image_slots[idx].lock().unwrap() = Some(slot);
     }
 }
@@ -1012,6 +1152,7 @@ impl StackPool {
 #[derive(Debug)]
 pub struct PoolingInstanceAllocator {
     instances: InstancePool,
+    next_stripe: AtomicUsize,
     #[cfg(all(feature = "async", unix))]
     stacks: StackPool,
     #[cfg(all(feature = "async", windows))]
@@ -1020,22 +1161,39 @@ pub struct PoolingInstanceAllocator {
 
 impl PoolingInstanceAllocator {
     /// Creates a new pooling instance allocator with the given strategy and limits.
+    ///
+    /// If `huge_pages` is set the memory pool is advised to be backed by
+    /// transparent huge pages, see [`Mmap::advise_huge_pages`].
+    ///
+    /// If `memory_protection_keys` is at least two, and that many keys can be
+    /// allocated on this host, memories are striped across up to that many
+    /// protection keys, see [`ProtectionKey`]. Wasm must then only run with
+    /// the key of its store enabled, see
+    /// [`PoolingInstanceAllocator::next_available_pkey`].
     pub fn new(
         strategy: PoolingAllocationStrategy,
         instance_limits: InstanceLimits,
         stack_size: usize,
         tunables: &Tunables,
+        huge_pages: bool,
+        memory_protection_keys: usize,
     ) -> Result<Self> {
         if instance_limits.count == 0 {
             bail!("the instance count limit cannot be zero");
         }
 
-        let instances = InstancePool::new(strategy, &instance_limits, tunables)?;
+        let instances =
+            InstancePool::new(strategy, &instance_limits, tunables, memory_protection_keys)?;
+        if huge_pages {
+            let mapping = &instances.memories.mapping;
+            mapping.advise_huge_pages(0..mapping.len());
+        }
 
         drop(stack_size); // suppress unused warnings w/o async feature
 
         Ok(Self {
             instances: instances,
+            next_stripe: AtomicUsize::new(0),
             #[cfg(all(feature = "async", unix))]
             stacks: StackPool::new(&instance_limits, stack_size)?,
             #[cfg(all(feature = "async", windows))]
@@ -1088,6 +1246,24 @@ unsafe impl InstanceAllocator for PoolingInstanceAllocator {
         self.instances.deallocate(handle);
     }
 
+    fn instance_slots(&self) -> Option<(usize, usize)> {
+        let free = self.instances.free_slots();
+        let total = self.instances.max_instances;
+        Some((total - free, total))
+    }
+
+    fn purge_unused_images(&self) {
+        self.instances.memories.purge_unused_images();
+    }
+
+    fn next_available_pkey(&self) -> Option<ProtectionKey> {
+        // Hand out the keys round-robin so stores are spread evenly across
+        // the stripes.
+        let stripes = &self.instances.stripes;
+        let next = self.next_stripe.fetch_add(1, Ordering::Relaxed);
+        stripes[next % stripes.len()].pkey
+    }
+
     #[cfg(all(feature = "async", unix))]
     fn allocate_fiber_stack(&self) -> Result<wasmtime_fiber::FiberStack, FiberStackError> {
         self.stacks.allocate()
@@ -1118,7 +1294,10 @@ unsafe impl InstanceAllocator for PoolingInstanceAllocator {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{CompiledModuleId, Imports, MemoryImage, StorePtr, VMSharedSignatureIndex};
+    use crate::{
+        CompiledModuleId, Imports, MemoryImage, SharedTables, StorePtr, VMGlobalDefinition,
+        VMSharedSignatureIndex,
+    };
     use std::sync::Arc;
     use wasmtime_environ::{DefinedFuncIndex, DefinedMemoryIndex, FunctionInfo, SignatureIndex};
 
@@ -1156,6 +1335,12 @@ mod test {
             fn signature_ids(&self) -> &[VMSharedSignatureIndex] {
                 &[]
             }
+            fn shared_globals(&self) -> *const VMGlobalDefinition {
+                std::ptr::null()
+            }
+            fn shared_tables(&self) -> Option<&SharedTables> {
+                None
+            }
         }
 
         Arc::new(RuntimeInfo(module))
@@ -1181,13 +1366,18 @@ mod test {
                 static_memory_bound: 1,
                 ..Tunables::default()
             },
+            0,
         )?;
 
         assert_eq!(instances.instance_size, 1008); // round 1000 up to alignment
         assert_eq!(instances.max_instances, 3);
 
         assert_eq!(
-            instances.index_allocator.lock().unwrap().testing_freelist(),
+            instances.stripes[0]
+                .index_allocator
+                .lock()
+                .unwrap()
+                .testing_freelist(),
             &[SlotId(0), SlotId(1), SlotId(2)]
         );
 
@@ -1207,13 +1397,18 @@ mod test {
                         },
                         host_state: Box::new(()),
                         store: StorePtr::empty(),
+                        pkey: None,
                     })
                     .expect("allocation should succeed"),
             );
         }
 
         assert_eq!(
-            instances.index_allocator.lock().unwrap().testing_freelist(),
+            instances.stripes[0]
+                .index_allocator
+                .lock()
+                .unwrap()
+                .testing_freelist(),
             &[]
         );
 
@@ -1227,6 +1422,7 @@ mod test {
             },
             host_state: Box::new(()),
             store: StorePtr::empty(),
+            pkey: None,
         }) {
             Err(InstantiationError::Limit(3)) => {}
             _ => panic!("unexpected error"),
@@ -1237,7 +1433,11 @@ mod test {
         }
 
         assert_eq!(
-            instances.index_allocator.lock().unwrap().testing_freelist(),
+            instances.stripes[0]
+                .index_allocator
+                .lock()
+                .unwrap()
+                .testing_freelist(),
             &[SlotId(2), SlotId(1), SlotId(0)]
         );
 
@@ -1261,9 +1461,10 @@ mod test {
                 static_memory_offset_guard_size: 0,
                 ..Tunables::default()
             },
+            Vec::new(),
         )?;
 
-        assert_eq!(pool.memory_reservation_size, WASM_PAGE_SIZE as usize);
+        assert_eq!(pool.memory_stride, WASM_PAGE_SIZE as usize);
         assert_eq!(pool.max_memories, 3);
         assert_eq!(pool.max_instances, 5);
         assert_eq!(pool.max_memory_size, WASM_PAGE_SIZE as usize);
@@ -1276,7 +1477,7 @@ mod test {
             for j in 0..3 {
                 assert_eq!(
                     iter.next().unwrap() as usize - base,
-                    ((i * 3) + j) * pool.memory_reservation_size
+                    ((i * 3) + j) * pool.memory_stride
                 );
             }
 
@@ -1286,6 +1487,54 @@ mod test {
         Ok(())
     }
 
+    #[cfg(all(target_arch = "x86_64", target_os = "linux"))]
+    #[test]
+    fn test_memory_pool_striped() -> Result<()> {
+        let pkeys = ProtectionKey::allocate(2);
+        if pkeys.len() < 2 {
+            return Ok(());
+        }
+        let pool = MemoryPool::new(
+            &InstanceLimits {
+                count: 5,
+                tables: 0,
+                memories: 2,
+                table_elements: 0,
+                memory_pages: 1,
+                ..Default::default()
+            },
+            &Tunables {
+                static_memory_bound: 4,
+                static_memory_offset_guard_size: 0,
+                ..Tunables::default()
+            },
+            pkeys,
+        )?;
+
+        // Each memory needs 4 pages of reservation, so 2 pages apart is
+        // enough to interleave two stripes.
+        let page = WASM_PAGE_SIZE as usize;
+        assert_eq!(pool.memory_stride, 2 * page);
+        assert_eq!(pool.mapping.len(), 12 * 2 * page + 2 * page);
+
+        let base = pool.mapping.as_ptr() as usize;
+        let offsets = (0..5)
+            .map(|i| pool.get(i).map(|p| p as usize - base).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            offsets,
+            [
+                [0, 4 * page],
+                [2 * page, 6 * page],
+                [8 * page, 12 * page],
+                [10 * page, 14 * page],
+                [16 * page, 20 * page],
+            ]
+        );
+
+        Ok(())
+    }
+
     #[cfg(target_pointer_width = "64")]
     #[test]
     fn test_table_pool() -> Result<()> {
@@ -1409,6 +1658,8 @@ mod test {
                 },
                 4096,
                 &Tunables::default(),
+                false,
+                0,
             )
             .map_err(|e| e.to_string())
             .expect_err("expected a failure constructing instance allocator"),
@@ -1431,6 +1682,8 @@ mod test {
                     static_memory_bound: 1,
                     ..Tunables::default()
                 },
+                false,
+                0,
             )
             .map_err(|e| e.to_string())
             .expect_err("expected a failure constructing instance allocator"),
@@ -1454,6 +1707,8 @@ mod test {
                     static_memory_offset_guard_size: 0,
                     ..Tunables::default()
                 },
+                false,
+                0,
             )
             .map_err(|e| e.to_string())
             .expect_err("expected a failure constructing instance allocator"),
@@ -1476,6 +1731,8 @@ mod test {
             },
             4096,
             &Tunables::default(),
+            false,
+            0,
         )?;
 
         unsafe {
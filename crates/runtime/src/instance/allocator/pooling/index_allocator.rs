@@ -276,6 +276,15 @@ impl PoolingAllocationState {
         }
     }
 
+    /// Returns the number of slots which are free to be allocated.
+    pub(crate) fn free_slots(&self) -> usize {
+        match self {
+            &PoolingAllocationState::NextAvailable(ref free_list)
+            | &PoolingAllocationState::Random(ref free_list) => free_list.len(),
+            &PoolingAllocationState::ReuseAffinity { ref free_list, .. } => free_list.len(),
+        }
+    }
+
     /// Allocate a new slot.
     pub(crate) fn alloc(&mut self, id: Option<CompiledModuleId>) -> SlotId {
         match self {
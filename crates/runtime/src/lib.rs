@@ -28,8 +28,12 @@ use anyhow::Error;
 use wasmtime_environ::DefinedFuncIndex;
 use wasmtime_environ::DefinedMemoryIndex;
 use wasmtime_environ::FunctionInfo;
+use wasmtime_environ::MemoryIndex;
+use wasmtime_environ::OverflowingOp;
 use wasmtime_environ::SignatureIndex;
 
+mod bulk_memory;
+mod coredump;
 mod export;
 mod externref;
 mod imports;
@@ -37,6 +41,8 @@ mod instance;
 mod memory;
 mod mmap;
 mod mmap_vec;
+mod shared_globals;
+mod shared_tables;
 mod table;
 mod traphandlers;
 mod vmcontext;
@@ -46,27 +52,36 @@ pub mod libcalls;
 
 pub use wasmtime_jit_debug::gdb_jit_int::GdbJitImageRegistration;
 
+pub use crate::coredump::CoreDumpStackFrame;
 pub use crate::export::*;
 pub use crate::externref::*;
 pub use crate::imports::Imports;
 pub use crate::instance::{
     InstanceAllocationRequest, InstanceAllocator, InstanceHandle, InstantiationError, LinkError,
-    OnDemandInstanceAllocator, StorePtr,
+    OnDemandInstanceAllocator, SegmentIndex, SegmentOutOfBounds, StorePtr,
 };
 #[cfg(feature = "pooling-allocator")]
 pub use crate::instance::{InstanceLimits, PoolingAllocationStrategy, PoolingInstanceAllocator};
 pub use crate::memory::{DefaultMemoryCreator, Memory, RuntimeLinearMemory, RuntimeMemoryCreator};
-pub use crate::mmap::Mmap;
+pub use crate::mmap::{page_size, Mmap};
 pub use crate::mmap_vec::MmapVec;
-pub use crate::table::{Table, TableElement};
+pub use crate::shared_globals::SharedGlobals;
+pub use crate::shared_tables::SharedTables;
+pub use crate::table::{Table, TableElement, TableElementType};
+#[cfg(any(
+    wasmtime_custom_platform,
+    not(all(target_os = "macos", not(feature = "posix-signals-on-macos")))
+))]
+pub use crate::traphandlers::handle_trap;
 pub use crate::traphandlers::{
     catch_traps, init_traps, raise_lib_trap, raise_user_trap, resume_panic, tls_eager_initialize,
     Backtrace, SignalHandler, TlsRestore, Trap,
 };
 pub use crate::vmcontext::{
     VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport, VMGlobalDefinition,
-    VMGlobalImport, VMInvokeArgument, VMMemoryDefinition, VMMemoryImport, VMRuntimeLimits,
-    VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline, ValRaw,
+    VMGlobalImport, VMInvokeArgument, VMMemoryDefinition, VMMemoryImport, VMNativeTrampoline,
+    VMRuntimeLimits, VMSharedSignatureIndex, VMTableDefinition, VMTableImport, VMTrampoline,
+    ValRaw,
 };
 
 mod module_id;
@@ -82,6 +97,40 @@ mod cow_disabled;
 #[cfg(not(memory_init_cow))]
 pub use crate::cow_disabled::{MemoryImage, MemoryImageSlot, ModuleMemoryImages};
 
+#[cfg(all(
+    target_arch = "x86_64",
+    target_os = "linux",
+    not(wasmtime_custom_platform)
+))]
+mod mpk;
+#[cfg(all(
+    target_arch = "x86_64",
+    target_os = "linux",
+    not(wasmtime_custom_platform)
+))]
+pub use crate::mpk::{ProtectionKey, ProtectionMask};
+
+#[cfg(not(all(
+    target_arch = "x86_64",
+    target_os = "linux",
+    not(wasmtime_custom_platform)
+)))]
+mod mpk_disabled;
+#[cfg(not(all(
+    target_arch = "x86_64",
+    target_os = "linux",
+    not(wasmtime_custom_platform)
+)))]
+pub use crate::mpk_disabled::{ProtectionKey, ProtectionMask};
+
+#[cfg(wasmtime_custom_platform)]
+mod custom_platform;
+
+#[cfg(all(wasmtime_custom_platform, feature = "pooling-allocator"))]
+compile_error!("the pooling allocator isn't supported on custom platforms");
+#[cfg(all(wasmtime_custom_platform, feature = "async"))]
+compile_error!("async support isn't supported on custom platforms");
+
 /// Version number of this crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -130,6 +179,21 @@ pub unsafe trait Store {
     /// Callback invoked to notify the store's resource limiter that a memory
     /// grow operation has failed.
     fn memory_grow_failed(&mut self, error: &Error);
+    /// Callback invoked to notify the store's resource limiter that a memory
+    /// has grown from `old` to `new` bytes.
+    fn memory_grown(&mut self, old: usize, new: usize);
+    /// Callback invoked when growing a memory from `current` to `desired`
+    /// bytes failed, either because the store's resource limiter rejected it
+    /// or because the host failed to allocate it, in which case `error` is
+    /// the reason. Returns whether to try growing the memory again, or an
+    /// error to raise as a trap; the grow operation returns failure to wasm
+    /// otherwise.
+    fn memory_grow_failure(
+        &mut self,
+        current: usize,
+        desired: usize,
+        error: Option<Error>,
+    ) -> Result<bool, Error>;
     /// Callback invoked to allow the store's resource limiter to reject a
     /// table grow operation.
     fn table_growing(
@@ -141,6 +205,9 @@ pub unsafe trait Store {
     /// Callback invoked to notify the store's resource limiter that a table
     /// grow operation has failed.
     fn table_grow_failed(&mut self, error: &Error);
+    /// Callback invoked to notify the store's resource limiter that a table
+    /// has grown from `old` to `new` elements.
+    fn table_grown(&mut self, old: u32, new: u32);
     /// Callback invoked whenever fuel runs out by a wasm instance. If an error
     /// is returned that's raised as a trap. Otherwise wasm execution will
     /// continue as normal.
@@ -149,6 +216,59 @@ pub unsafe trait Store {
     /// number. Cannot fail; cooperative epoch-based yielding is
     /// completely semantically transparent. Returns the new deadline.
     fn new_epoch(&mut self) -> Result<u64, Error>;
+    /// Callback invoked before each instruction executed by code compiled
+    /// with guest debugging.
+    ///
+    /// The instruction is at `offset` within the module identified by
+    /// `module`, in the function `func_index`. The current values of the
+    /// function's locals are in `locals`, whose types are described by the
+    /// tags in `types` (see `wasmtime_environ::debug_local_type`). If an error
+    /// is returned it's raised as a trap.
+    fn debug_hook(
+        &mut self,
+        module: Option<CompiledModuleId>,
+        func_index: u32,
+        offset: u32,
+        locals: &[ValRaw],
+        types: &[u8],
+    ) -> Result<(), Error>;
+    /// Callback invoked by code compiled with instruction counting when it
+    /// has executed `count` instructions in a straight-line run starting at
+    /// `offset` within the module identified by `module`, in the function
+    /// `func_index`.
+    fn instructions_executed(
+        &mut self,
+        module: Option<CompiledModuleId>,
+        func_index: u32,
+        offset: u32,
+        count: u32,
+    );
+    /// Callback invoked by code compiled with memory access tracing after it
+    /// loads `size` bytes at `address` from, or stores them to, the memory
+    /// with index `memory` in the accessing instance. The bytes are the low
+    /// `size` bytes of `value`. If an error is returned it's raised as a trap.
+    fn memory_accessed(
+        &mut self,
+        memory: MemoryIndex,
+        is_store: bool,
+        address: u64,
+        size: u32,
+        value: u128,
+    ) -> Result<(), Error>;
+    /// Callback invoked by code compiled with integer overflow checks when
+    /// `op` on `bits`-bit integers `lhs` and `rhs`, sign-extended to 64 bits,
+    /// overflows in the function `func_index`. Returns whether the overflow
+    /// was handled, in which case execution continues with the wrapped
+    /// result, or `false` if the overflow should trap. If an error is
+    /// returned it's raised as a trap.
+    fn integer_overflowed(
+        &mut self,
+        func_index: u32,
+        op: OverflowingOp,
+        bits: u32,
+        lhs: i64,
+        rhs: i64,
+    ) -> Result<bool, Error>;
 }
 
 /// Functionality required by this crate for a particular module. This
@@ -194,4 +314,31 @@ pub trait ModuleRuntimeInfo: Send + Sync + 'static {
     /// Returns an array, indexed by `SignatureIndex` of all
     /// `VMSharedSignatureIndex` entries corresponding to the `SignatureIndex`.
     fn signature_ids(&self) -> &[VMSharedSignatureIndex];
+
+    /// Returns a pointer to the globals shared by all instances of the
+    /// module, see `SharedGlobals`, or null if the module has none.
+    fn shared_globals(&self) -> *const VMGlobalDefinition;
+
+    /// Returns the tables shared by all instances of the module, see
+    /// `SharedTables`, if it has any.
+    fn shared_tables(&self) -> Option<&SharedTables>;
+
+    /// Compiles the function `index` of a module whose functions are compiled
+    /// lazily, returning the address of its code.
+    ///
+    /// This is called by the function's stub on its first call, and fails for
+    /// modules which were compiled up front.
+    fn compile_lazily(&self, index: DefinedFuncIndex) -> anyhow::Result<usize> {
+        anyhow::bail!("function {} isn't compiled lazily", index.as_u32())
+    }
+
+    /// Starts recompiling the function `index`, which became hot, with the
+    /// optimizing tier, for modules compiled with tiered compilation.
+    ///
+    /// This is called by code compiled with the baseline tier, and returns
+    /// without waiting for the function to be recompiled. It does nothing for
+    /// other modules.
+    fn tier_up(self: Arc<Self>, index: DefinedFuncIndex) {
+        let _ = index;
+    }
 }
@@ -60,11 +60,11 @@ use crate::externref::VMExternRef;
 use crate::instance::Instance;
 use crate::table::{Table, TableElementType};
 use crate::traphandlers::{raise_lib_trap, resume_panic, Trap};
-use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext, ValRaw};
 use std::mem;
 use std::ptr::{self, NonNull};
 use wasmtime_environ::{
-    DataIndex, ElemIndex, FuncIndex, GlobalIndex, MemoryIndex, TableIndex, TrapCode,
+    DataIndex, ElemIndex, FuncIndex, GlobalIndex, MemoryIndex, OverflowingOp, TableIndex, TrapCode,
 };
 
 const TOINT_32: f32 = 1.0 / f32::EPSILON;
@@ -607,3 +607,129 @@ pub unsafe extern "C" fn new_epoch(vmctx: *mut VMContext) -> u64 {
         Err(err) => crate::traphandlers::raise_user_trap(err),
     }
 }
+
+/// Hook invoked before each instruction of wasm code compiled with guest
+/// debugging.
+///
+/// The `count` locals of the function are spilled at `locals` as `ValRaw`s,
+/// followed by a tag byte for each local's type. The spill slot isn't
+/// necessarily aligned for `ValRaw` so the values are copied out first.
+pub unsafe extern "C" fn debug_hook(
+    vmctx: *mut VMContext,
+    func_index: u32,
+    offset: u32,
+    locals: *mut u8,
+    count: u32,
+) {
+    let instance = (*vmctx).instance();
+    let count = usize::try_from(count).unwrap();
+    let mut values = Vec::with_capacity(count);
+    let mut types: &[u8] = &[];
+    if count > 0 {
+        let raw = locals.cast::<ValRaw>();
+        values.extend((0..count).map(|i| ptr::read_unaligned(raw.add(i))));
+        types = std::slice::from_raw_parts(raw.add(count).cast::<u8>(), count);
+    }
+    let result =
+        (*instance.store()).debug_hook(instance.module_id(), func_index, offset, &values, types);
+    match result {
+        Ok(()) => {}
+        Err(err) => crate::traphandlers::raise_user_trap(err),
+    }
+}
+
+/// Hook invoked by wasm code compiled with instruction counting at the end of
+/// each straight-line run of `count` instructions starting at `offset`.
+pub unsafe extern "C" fn count_instructions(
+    vmctx: *mut VMContext,
+    func_index: u32,
+    offset: u32,
+    count: u32,
+) {
+    let instance = (*vmctx).instance();
+    (*instance.store()).instructions_executed(instance.module_id(), func_index, offset, count);
+}
+
+/// Hook invoked by wasm code compiled with memory access tracing after each
+/// load or store, where the value accessed is split into two 64-bit halves.
+pub unsafe extern "C" fn trace_memory_access(
+    vmctx: *mut VMContext,
+    memory: u32,
+    is_store: u32,
+    address: u64,
+    size: u32,
+    low: u64,
+    high: u64,
+) {
+    let instance = (*vmctx).instance();
+    let mut value = u128::from(low) | (u128::from(high) << 64);
+    if size < 16 {
+        value &= (1 << (size * 8)) - 1;
+    }
+    let result = (*instance.store()).memory_accessed(
+        MemoryIndex::from_u32(memory),
+        is_store != 0,
+        address,
+        size,
+        value,
+    );
+    match result {
+        Ok(()) => {}
+        Err(err) => crate::traphandlers::raise_user_trap(err),
+    }
+}
+
+/// Hook invoked by wasm code compiled with integer overflow checks when an
+/// `op` on `bits`-bit integers `lhs` and `rhs` in the function `func_index`
+/// overflows, where the operands are sign-extended to 64 bits.
+pub unsafe extern "C" fn integer_overflow(
+    vmctx: *mut VMContext,
+    func_index: u32,
+    op: u32,
+    bits: u32,
+    lhs: u64,
+    rhs: u64,
+) {
+    let instance = (*vmctx).instance();
+    let op = OverflowingOp::from_u8(op as u8).unwrap();
+    let result =
+        (*instance.store()).integer_overflowed(func_index, op, bits, lhs as i64, rhs as i64);
+    match result {
+        Ok(true) => {}
+        // Without a callback to handle it the overflow traps.
+        Ok(false) => raise_lib_trap(Trap::wasm(TrapCode::IntegerOverflow)),
+        Err(err) => crate::traphandlers::raise_user_trap(err),
+    }
+}
+
+/// Compiles a function of a lazily compiled module on its first call, returning
+/// the address of its code.
+pub unsafe extern "C" fn lazy_compile(vmctx: *mut VMContext, func_index: u32) -> *mut u8 {
+    let instance = (*vmctx).instance();
+    let index = instance
+        .module()
+        .defined_func_index(FuncIndex::from_u32(func_index))
+        .unwrap();
+    match instance.compile_lazily(index) {
+        Ok(code) => code as *mut u8,
+        Err(err) => crate::traphandlers::raise_user_trap(err),
+    }
+}
+
+/// Recompiles a hot function of a module compiled with tiered compilation
+/// with the optimizing tier, in the background.
+pub unsafe extern "C" fn tier_up(vmctx: *mut VMContext, func_index: u32) {
+    let instance = (*vmctx).instance();
+    let index = instance
+        .module()
+        .defined_func_index(FuncIndex::from_u32(func_index))
+        .unwrap();
+    instance.tier_up(index);
+}
+
+/// Raises a trap on behalf of wasm code compiled without signals-based traps,
+/// where `code` is the byte representation of a `TrapCode`.
+pub unsafe extern "C" fn raise_trap(_vmctx: *mut VMContext, code: u32) {
+    let code = TrapCode::from_u8(code as u8).unwrap_or(TrapCode::UnreachableCodeReached);
+    raise_lib_trap(Trap::wasm(code))
+}
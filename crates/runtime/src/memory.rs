@@ -11,6 +11,7 @@ use anyhow::Error;
 use anyhow::{bail, format_err, Result};
 use more_asserts::{assert_ge, assert_le};
 use std::convert::TryFrom;
+use std::ops::Range;
 use std::sync::Arc;
 use wasmtime_environ::{MemoryPlan, MemoryStyle, WASM32_MAX_PAGES, WASM64_MAX_PAGES};
 
@@ -31,7 +32,12 @@ pub trait RuntimeMemoryCreator: Send + Sync {
 }
 
 /// A default memory allocator used by Wasmtime
-pub struct DefaultMemoryCreator;
+#[derive(Default)]
+pub struct DefaultMemoryCreator {
+    /// Whether the memories created are backed by transparent huge pages,
+    /// see [`Mmap::advise_huge_pages`].
+    pub huge_pages: bool,
+}
 
 impl RuntimeMemoryCreator for DefaultMemoryCreator {
     /// Create new MmapMemory
@@ -47,6 +53,7 @@ impl RuntimeMemoryCreator for DefaultMemoryCreator {
             minimum,
             maximum,
             memory_image,
+            self.huge_pages,
         )?))
     }
 }
@@ -111,6 +118,9 @@ pub struct MmapMemory {
     // An optional CoW mapping that provides the initial content of this
     // MmapMemory, if mapped.
     memory_image: Option<MemoryImageSlot>,
+
+    // Whether `mmap` is advised to be backed by transparent huge pages.
+    huge_pages: bool,
 }
 
 impl MmapMemory {
@@ -120,6 +130,7 @@ impl MmapMemory {
         minimum: usize,
         mut maximum: Option<usize>,
         memory_image: Option<&Arc<MemoryImage>>,
+        huge_pages: bool,
     ) -> Result<Self> {
         // It's a programmer error for these two configuration values to exceed
         // the host available address space, so panic if such a configuration is
@@ -152,6 +163,9 @@ impl MmapMemory {
             .ok_or_else(|| format_err!("cannot allocate {} with guard regions", minimum))?;
 
         let mut mmap = Mmap::accessible_reserved(0, request_bytes)?;
+        if huge_pages {
+            mmap.advise_huge_pages(pre_guard_bytes..request_bytes - offset_guard_bytes);
+        }
         if minimum > 0 {
             mmap.make_accessible(pre_guard_bytes, minimum)?;
         }
@@ -184,6 +198,7 @@ impl MmapMemory {
             offset_guard_size: offset_guard_bytes,
             extra_to_reserve_on_growth,
             memory_image,
+            huge_pages,
         })
     }
 }
@@ -211,6 +226,10 @@ impl RuntimeLinearMemory for MmapMemory {
                 .ok_or_else(|| format_err!("overflow calculating size of memory allocation"))?;
 
             let mut new_mmap = Mmap::accessible_reserved(0, request_bytes)?;
+            if self.huge_pages {
+                new_mmap
+                    .advise_huge_pages(self.pre_guard_size..request_bytes - self.offset_guard_size);
+            }
             new_mmap.make_accessible(self.pre_guard_size, new_size)?;
 
             new_mmap.as_mut_slice()[self.pre_guard_size..][..self.accessible]
@@ -381,7 +400,14 @@ impl RuntimeLinearMemory for ExternalMemory {
 }
 
 /// Representation of a runtime wasm linear memory.
-pub struct Memory(Box<dyn RuntimeLinearMemory>);
+pub struct Memory {
+    inner: Box<dyn RuntimeLinearMemory>,
+    /// The ranges of this memory, in bytes, which have been made read-only
+    /// with `protect_read_only`.
+    read_only: Vec<Range<usize>>,
+    /// Whether wasm code may grow this memory with `memory.grow`.
+    guest_growable: bool,
+}
 
 impl Memory {
     /// Create a new dynamic (movable) memory instance for the specified plan.
@@ -392,12 +418,9 @@ impl Memory {
         memory_image: Option<&Arc<MemoryImage>>,
     ) -> Result<Self> {
         let (minimum, maximum) = Self::limit_new(plan, store)?;
-        Ok(Memory(creator.new_memory(
-            plan,
-            minimum,
-            maximum,
-            memory_image,
-        )?))
+        let memory = creator.new_memory(plan, minimum, maximum, memory_image)?;
+        Self::created(minimum, store);
+        Ok(Self::from_inner(memory))
     }
 
     /// Create a new static (immovable) memory instance for the specified plan.
@@ -411,7 +434,24 @@ impl Memory {
         let (minimum, maximum) = Self::limit_new(plan, store)?;
         let pooled_memory =
             ExternalMemory::new(base, minimum, maximum, make_accessible, memory_image)?;
-        Ok(Memory(Box::new(pooled_memory)))
+        Self::created(minimum, store);
+        Ok(Self::from_inner(Box::new(pooled_memory)))
+    }
+
+    fn from_inner(inner: Box<dyn RuntimeLinearMemory>) -> Self {
+        Self {
+            inner,
+            read_only: Vec::new(),
+            guest_growable: true,
+        }
+    }
+
+    /// Informs the `store`'s limiter that a memory of `minimum` bytes, which
+    /// it permitted in `limit_new`, was successfully allocated.
+    fn created(minimum: usize, store: &mut dyn Store) {
+        if minimum > 0 {
+            store.memory_grown(0, minimum);
+        }
     }
 
     /// Calls the `store`'s limiter to optionally prevent a memory from being allocated.
@@ -499,7 +539,7 @@ impl Memory {
 
     /// Returns the number of allocated wasm pages.
     pub fn byte_size(&self) -> usize {
-        self.0.byte_size()
+        self.inner.byte_size()
     }
 
     /// Returns the maximum number of pages the memory can grow to at runtime.
@@ -509,14 +549,14 @@ impl Memory {
     /// The runtime maximum may not be equal to the maximum from the linear memory's
     /// Wasm type when it is being constrained by an instance allocator.
     pub fn maximum_byte_size(&self) -> Option<usize> {
-        self.0.maximum_byte_size()
+        self.inner.maximum_byte_size()
     }
 
     /// Returns whether or not this memory needs initialization. It
     /// may not if it already has initial content thanks to a CoW
     /// mechanism.
     pub(crate) fn needs_init(&self) -> bool {
-        self.0.needs_init()
+        self.inner.needs_init()
     }
 
     /// Grow memory by the specified amount of wasm pages.
@@ -565,38 +605,130 @@ impl Memory {
         };
 
         let maximum = self.maximum_byte_size();
-        // Store limiter gets first chance to reject memory_growing.
-        if !store.memory_growing(old_byte_size, new_byte_size, maximum)? {
-            return Ok(None);
-        }
+        let exceeds_maximum = maximum.map_or(false, |max| new_byte_size > max);
+        loop {
+            // Store limiter gets first chance to reject memory_growing.
+            if !store.memory_growing(old_byte_size, new_byte_size, maximum)? {
+                // Growing past the maximum fails regardless of the limiter,
+                // so it isn't reported as a failure.
+                if exceeds_maximum
+                    || !store.memory_grow_failure(old_byte_size, new_byte_size, None)?
+                {
+                    return Ok(None);
+                }
+                continue;
+            }
 
-        // Never exceed maximum, even if limiter permitted it.
-        if let Some(max) = maximum {
-            if new_byte_size > max {
+            // Never exceed maximum, even if limiter permitted it.
+            if exceeds_maximum {
                 store.memory_grow_failed(&format_err!("Memory maximum size exceeded"));
                 return Ok(None);
             }
-        }
 
-        match self.0.grow_to(new_byte_size) {
-            Ok(_) => Ok(Some(old_byte_size)),
-            Err(e) => {
-                store.memory_grow_failed(&e);
-                Ok(None)
+            let old_base = self.inner.vmmemory().base;
+            match self.inner.grow_to(new_byte_size) {
+                Ok(_) => {
+                    // Growing a dynamic memory may have moved it to a new
+                    // mapping which is entirely writable.
+                    if self.inner.vmmemory().base != old_base {
+                        for range in self.read_only.clone() {
+                            self.protect(&range, region::Protection::READ)?;
+                        }
+                    }
+                    store.memory_grown(old_byte_size, new_byte_size);
+                    return Ok(Some(old_byte_size));
+                }
+                Err(e) => {
+                    store.memory_grow_failed(&e);
+                    if store.memory_grow_failure(old_byte_size, new_byte_size, Some(e))? {
+                        continue;
+                    }
+                    return Ok(None);
+                }
             }
         }
     }
 
     /// Return a `VMMemoryDefinition` for exposing the memory to compiled wasm code.
     pub fn vmmemory(&mut self) -> VMMemoryDefinition {
-        self.0.vmmemory()
+        self.inner.vmmemory()
+    }
+
+    /// Makes the bytes of `range` of this memory read-only, so that wasm code
+    /// which stores to them faults.
+    ///
+    /// The range must be aligned to the host's page size and within the
+    /// current size of the memory. The range stays read-only if the memory
+    /// grows, including when a dynamic memory is moved by growing.
+    ///
+    /// The host must not write to the range through raw pointers into the
+    /// memory from now on; the runtime's own bulk memory operations check
+    /// `is_writable` first and trap instead.
+    pub fn protect_read_only(&mut self, range: Range<usize>) -> Result<()> {
+        let page_size = crate::page_size();
+        if range.start % page_size != 0 || range.end % page_size != 0 {
+            bail!(
+                "range {:#x}..{:#x} is not aligned to the host page size of {:#x} bytes",
+                range.start,
+                range.end,
+                page_size
+            );
+        }
+        if range.start > range.end || range.end > self.byte_size() {
+            bail!(
+                "range {:#x}..{:#x} is out of bounds of a memory of {:#x} bytes",
+                range.start,
+                range.end,
+                self.byte_size()
+            );
+        }
+        if range.is_empty() {
+            return Ok(());
+        }
+        self.protect(&range, region::Protection::READ)?;
+        self.read_only.push(range);
+        Ok(())
+    }
+
+    /// Returns the ranges of this memory which were made read-only with
+    /// `protect_read_only`, in the order they were protected.
+    pub fn read_only_ranges(&self) -> &[Range<usize>] {
+        &self.read_only
+    }
+
+    /// Returns whether none of the bytes of `range` are read-only.
+    pub fn is_writable(&self, range: Range<usize>) -> bool {
+        range.is_empty()
+            || self
+                .read_only
+                .iter()
+                .all(|r| range.end <= r.start || r.end <= range.start)
+    }
+
+    /// Returns whether wasm code may grow this memory with `memory.grow`.
+    pub fn guest_growable(&self) -> bool {
+        self.guest_growable
+    }
+
+    /// Configures whether wasm code may grow this memory with `memory.grow`,
+    /// which traps if it may not. The memory can still be grown with `grow`.
+    pub fn set_guest_growable(&mut self, growable: bool) {
+        self.guest_growable = growable;
+    }
+
+    fn protect(&mut self, range: &Range<usize>, protection: region::Protection) -> Result<()> {
+        let base = self.inner.vmmemory().base;
+        unsafe {
+            region::protect(base.add(range.start), range.len(), protection)?;
+        }
+        Ok(())
     }
 
     /// Check if the inner implementation of [`Memory`] is a memory created with
     /// [`Memory::new_static()`].
     #[cfg(feature = "pooling-allocator")]
     pub fn is_static(&mut self) -> bool {
-        let as_any = self.0.as_any_mut();
+        let as_any = self.inner.as_any_mut();
         as_any.downcast_ref::<ExternalMemory>().is_some()
     }
 
@@ -605,7 +737,7 @@ impl Memory {
     /// [`Memory::new_static()`].
     #[cfg(feature = "pooling-allocator")]
     pub fn unwrap_static_image(mut self) -> Option<MemoryImageSlot> {
-        let as_any = self.0.as_any_mut();
+        let as_any = self.inner.as_any_mut();
         if let Some(m) = as_any.downcast_mut::<ExternalMemory>() {
             std::mem::take(&mut m.memory_image)
         } else {
@@ -613,3 +745,13 @@ impl Memory {
         }
     }
 }
+
+impl Drop for Memory {
+    fn drop(&mut self) {
+        // Static memories are reused by later instances, so their pages are
+        // made writable again before they're handed back.
+        for range in std::mem::take(&mut self.read_only) {
+            let _ = self.protect(&range, region::Protection::READ_WRITE);
+        }
+    }
+}
@@ -1,6 +1,9 @@
 //! Low-level abstraction for allocating and managing zero-filled pages
 //! of memory.
 
+// Most of the imports are only used by the Unix and Windows implementations.
+#![cfg_attr(wasmtime_custom_platform, allow(unused_imports))]
+
 use anyhow::anyhow;
 use anyhow::{Context, Result};
 use more_asserts::assert_le;
@@ -41,7 +44,8 @@ impl Mmap {
 
     /// Create a new `Mmap` pointing to at least `size` bytes of page-aligned accessible memory.
     pub fn with_at_least(size: usize) -> Result<Self> {
-        let rounded_size = region::page::ceil(size);
+        let page_size = page_size();
+        let rounded_size = (size + page_size - 1) & !(page_size - 1);
         Self::accessible_reserved(rounded_size, rounded_size)
     }
 
@@ -55,7 +59,13 @@ impl Mmap {
     /// The memory mapping and the length of the file within the mapping are
     /// returned.
     pub fn from_file(path: &Path) -> Result<Self> {
-        #[cfg(unix)]
+        #[cfg(wasmtime_custom_platform)]
+        {
+            let _ = path;
+            anyhow::bail!("mapping files isn't supported on custom platforms")
+        }
+
+        #[cfg(all(unix, not(wasmtime_custom_platform)))]
         {
             let file = File::open(path).context("failed to open file")?;
             let len = file
@@ -155,9 +165,9 @@ impl Mmap {
     /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
     /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
     /// must be native page-size multiples.
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(not(any(target_os = "windows", wasmtime_custom_platform)))]
     pub fn accessible_reserved(accessible_size: usize, mapping_size: usize) -> Result<Self> {
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_le!(accessible_size, mapping_size);
         assert_eq!(mapping_size & (page_size - 1), 0);
         assert_eq!(accessible_size & (page_size - 1), 0);
@@ -226,7 +236,7 @@ impl Mmap {
             return Ok(Self::new());
         }
 
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_le!(accessible_size, mapping_size);
         assert_eq!(mapping_size & (page_size - 1), 0);
         assert_eq!(accessible_size & (page_size - 1), 0);
@@ -276,9 +286,9 @@ impl Mmap {
     /// Make the memory starting at `start` and extending for `len` bytes accessible.
     /// `start` and `len` must be native page-size multiples and describe a range within
     /// `self`'s reserved memory.
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(not(any(target_os = "windows", wasmtime_custom_platform)))]
     pub fn make_accessible(&mut self, start: usize, len: usize) -> Result<()> {
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_eq!(start & (page_size - 1), 0);
         assert_eq!(len & (page_size - 1), 0);
         assert_le!(len, self.len);
@@ -303,7 +313,7 @@ impl Mmap {
         use winapi::ctypes::c_void;
         use winapi::um::memoryapi::VirtualAlloc;
         use winapi::um::winnt::{MEM_COMMIT, PAGE_READWRITE};
-        let page_size = region::page::size();
+        let page_size = page_size();
         assert_eq!(start & (page_size - 1), 0);
         assert_eq!(len & (page_size - 1), 0);
         assert_le!(len, self.len);
@@ -327,6 +337,62 @@ impl Mmap {
         Ok(())
     }
 
+    /// Create a new `Mmap` pointing to `accessible_size` bytes of page-aligned accessible memory,
+    /// within a reserved mapping of `mapping_size` bytes. `accessible_size` and `mapping_size`
+    /// must be native page-size multiples.
+    #[cfg(wasmtime_custom_platform)]
+    pub fn accessible_reserved(accessible_size: usize, mapping_size: usize) -> Result<Self> {
+        use crate::custom_platform::*;
+
+        let page_size = page_size();
+        assert_le!(accessible_size, mapping_size);
+        assert_eq!(mapping_size & (page_size - 1), 0);
+        assert_eq!(accessible_size & (page_size - 1), 0);
+
+        if mapping_size == 0 {
+            return Ok(Self::new());
+        }
+
+        let prot = if accessible_size == mapping_size {
+            WASMTIME_PROT_READ | WASMTIME_PROT_WRITE
+        } else {
+            0
+        };
+        let ptr = mmap_new(mapping_size, prot)?;
+        let mut result = Self {
+            ptr: ptr as usize,
+            len: mapping_size,
+            file: None,
+        };
+        if accessible_size != 0 && accessible_size != mapping_size {
+            result.make_accessible(0, accessible_size)?;
+        }
+        Ok(result)
+    }
+
+    /// Make the memory starting at `start` and extending for `len` bytes accessible.
+    /// `start` and `len` must be native page-size multiples and describe a range within
+    /// `self`'s reserved memory.
+    #[cfg(wasmtime_custom_platform)]
+    pub fn make_accessible(&mut self, start: usize, len: usize) -> Result<()> {
+        use crate::custom_platform::*;
+
+        let page_size = page_size();
+        assert_eq!(start & (page_size - 1), 0);
+        assert_eq!(len & (page_size - 1), 0);
+        assert_le!(len, self.len);
+        assert_le!(start, self.len - len);
+
+        let ptr = self.ptr as *mut u8;
+        unsafe {
+            mprotect(
+                ptr.add(start),
+                len,
+                WASMTIME_PROT_READ | WASMTIME_PROT_WRITE,
+            )
+        }
+    }
+
     /// Return the allocated memory as a slice of u8.
     pub fn as_slice(&self) -> &[u8] {
         unsafe { slice::from_raw_parts(self.ptr as *const u8, self.len) }
@@ -370,7 +436,7 @@ impl Mmap {
         assert!(range.end <= self.len());
         assert!(range.start <= range.end);
         assert!(
-            range.start % region::page::size() == 0,
+            range.start % page_size() == 0,
             "changing of protections isn't page-aligned",
         );
 
@@ -396,28 +462,141 @@ impl Mmap {
             }
         }
 
+        #[cfg(wasmtime_custom_platform)]
+        {
+            use crate::custom_platform::*;
+            mprotect(
+                base as *mut u8,
+                len,
+                WASMTIME_PROT_READ | WASMTIME_PROT_WRITE,
+            )
+        }
+
         // If we're not on Windows or if we're on Windows with an anonymous
         // mapping then we can use the `region` crate.
-        region::protect(base, len, region::Protection::READ_WRITE)?;
-        Ok(())
+        #[cfg(not(wasmtime_custom_platform))]
+        {
+            region::protect(base, len, region::Protection::READ_WRITE)?;
+            Ok(())
+        }
+    }
+
+    /// Makes the specified `range` within this `Mmap` to be read-only.
+    pub unsafe fn make_readonly(&self, range: Range<usize>) -> Result<()> {
+        assert!(range.start <= self.len());
+        assert!(range.end <= self.len());
+        assert!(range.start <= range.end);
+        assert!(
+            range.start % page_size() == 0,
+            "changing of protections isn't page-aligned",
+        );
+        let base = self.as_ptr().add(range.start);
+        let len = range.end - range.start;
+
+        #[cfg(wasmtime_custom_platform)]
+        {
+            use crate::custom_platform::*;
+            mprotect(base as *mut u8, len, WASMTIME_PROT_READ)
+        }
+
+        #[cfg(not(wasmtime_custom_platform))]
+        {
+            region::protect(base, len, region::Protection::READ)?;
+            Ok(())
+        }
     }
 
     /// Makes the specified `range` within this `Mmap` to be read/execute.
-    pub unsafe fn make_executable(&self, range: Range<usize>) -> Result<()> {
+    ///
+    /// With `enable_branch_protection` the pages are also made guarded pages
+    /// on AArch64 Linux, where indirect branches must land on `bti`
+    /// instructions, if the kernel supports it.
+    pub unsafe fn make_executable(
+        &self,
+        range: Range<usize>,
+        enable_branch_protection: bool,
+    ) -> Result<()> {
         assert!(range.start <= self.len());
         assert!(range.end <= self.len());
         assert!(range.start <= range.end);
         assert!(
-            range.start % region::page::size() == 0,
+            range.start % page_size() == 0,
             "changing of protections isn't page-aligned",
         );
+        let base = self.as_ptr().add(range.start);
+        let len = range.end - range.start;
 
-        region::protect(
-            self.as_ptr().add(range.start),
-            range.end - range.start,
-            region::Protection::READ_EXECUTE,
-        )?;
-        Ok(())
+        #[cfg(wasmtime_custom_platform)]
+        {
+            use crate::custom_platform::*;
+            let _ = enable_branch_protection;
+            mprotect(
+                base as *mut u8,
+                len,
+                WASMTIME_PROT_READ | WASMTIME_PROT_EXEC,
+            )
+        }
+
+        #[cfg(all(
+            target_arch = "aarch64",
+            target_os = "linux",
+            not(wasmtime_custom_platform)
+        ))]
+        if enable_branch_protection {
+            const PROT_BTI: libc::c_int = 0x10;
+            let prot = libc::PROT_READ | libc::PROT_EXEC | PROT_BTI;
+            if libc::mprotect(base as *mut _, len, prot) == 0 {
+                return Ok(());
+            }
+            // Kernels without BTI support reject `PROT_BTI`, in which case
+            // the code still runs fine without being guarded.
+            let err = std::io::Error::last_os_error();
+            if err.raw_os_error() != Some(libc::EINVAL) {
+                return Err(err).context("failed to make memory executable");
+            }
+        }
+        #[cfg(not(any(
+            all(target_arch = "aarch64", target_os = "linux"),
+            wasmtime_custom_platform
+        )))]
+        let _ = enable_branch_protection;
+
+        #[cfg(not(wasmtime_custom_platform))]
+        {
+            region::protect(base, len, region::Protection::READ_EXECUTE)?;
+            Ok(())
+        }
+    }
+
+    /// Advises the OS to back the specified `range` within this `Mmap` with
+    /// transparent huge pages.
+    ///
+    /// This is only a hint: it's implemented with `madvise(MADV_HUGEPAGE)` on
+    /// Linux and does nothing on other platforms, and failures are ignored
+    /// since the memory works the same either way. Only the parts of `range`
+    /// which cover whole, aligned huge pages can be backed by them.
+    pub fn advise_huge_pages(&self, range: Range<usize>) {
+        assert!(range.start <= range.end);
+        assert!(range.end <= self.len());
+
+        #[cfg(all(target_os = "linux", not(wasmtime_custom_platform)))]
+        {
+            let page_size = page_size();
+            let start = range.start & !(page_size - 1);
+            if range.end == start {
+                return;
+            }
+            let result = unsafe {
+                rustix::io::madvise(
+                    self.as_ptr().add(start) as *mut _,
+                    range.end - start,
+                    rustix::io::Advice::LinuxHugepage,
+                )
+            };
+            if let Err(e) = result {
+                log::debug!("failed to advise the use of huge pages: {}", e);
+            }
+        }
     }
 
     /// Returns the underlying file that this mmap is mapping, if present.
@@ -427,7 +606,15 @@ impl Mmap {
 }
 
 impl Drop for Mmap {
-    #[cfg(not(target_os = "windows"))]
+    #[cfg(wasmtime_custom_platform)]
+    fn drop(&mut self) {
+        if self.len != 0 {
+            unsafe { crate::custom_platform::munmap(self.ptr as *mut u8, self.len) }
+                .expect("munmap failed");
+        }
+    }
+
+    #[cfg(not(any(target_os = "windows", wasmtime_custom_platform)))]
     fn drop(&mut self) {
         if self.len != 0 {
             unsafe { rustix::io::munmap(self.ptr as *mut std::ffi::c_void, self.len) }
@@ -452,6 +639,14 @@ impl Drop for Mmap {
     }
 }
 
+/// Returns the host's page size.
+pub fn page_size() -> usize {
+    #[cfg(wasmtime_custom_platform)]
+    return crate::custom_platform::page_size();
+    #[cfg(not(wasmtime_custom_platform))]
+    return region::page::size();
+}
+
 fn _assert() {
     fn _assert_send_sync<T: Send + Sync>() {}
     _assert_send_sync::<Mmap>();
@@ -112,10 +112,24 @@ impl MmapVec {
             .make_writable(range.start + self.range.start..range.end + self.range.start)
     }
 
-    /// Makes the specified `range` within this `mmap` to be read/execute.
-    pub unsafe fn make_executable(&self, range: Range<usize>) -> Result<()> {
+    /// Makes the specified `range` within this `mmap` to be read/execute, see
+    /// [`Mmap::make_executable`].
+    pub unsafe fn make_executable(
+        &self,
+        range: Range<usize>,
+        enable_branch_protection: bool,
+    ) -> Result<()> {
+        self.mmap.make_executable(
+            range.start + self.range.start..range.end + self.range.start,
+            enable_branch_protection,
+        )
+    }
+
+    /// Advises the OS to back the specified `range` within this `mmap` with
+    /// transparent huge pages, see [`Mmap::advise_huge_pages`].
+    pub fn advise_huge_pages(&self, range: Range<usize>) {
         self.mmap
-            .make_executable(range.start + self.range.start..range.end + self.range.start)
+            .advise_huge_pages(range.start + self.range.start..range.end + self.range.start)
     }
 
     /// Returns the underlying file that this mmap is mapping, if present.
@@ -0,0 +1,156 @@
+//! Memory protection keys (MPK, also known as "pkeys") for x86_64 Linux.
+//!
+//! Every page of memory is tagged with one of 16 protection keys, and each
+//! thread's `PKRU` register has bits disabling access to the pages of each
+//! key. The pooling allocator uses this to stripe the memory slots of
+//! different stores across keys: a store's wasm only runs with its own key
+//! enabled, so the neighbouring slots of other stores are as inaccessible to
+//! it as guard pages would be, and slots can be packed much more densely.
+
+use anyhow::{bail, Result};
+use std::sync::Once;
+
+/// A protection key allocated for this process with `pkey_alloc`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtectionKey(u32);
+
+static ALLOCATE_KEYS: Once = Once::new();
+static mut KEYS: Vec<ProtectionKey> = Vec::new();
+
+impl ProtectionKey {
+    /// Returns whether this host supports memory protection keys, which
+    /// requires both the CPU and the kernel to support them and for there to
+    /// be keys left to allocate.
+    pub fn is_supported() -> bool {
+        !Self::all().is_empty()
+    }
+
+    /// Returns up to `max` of the keys allocated for this process.
+    ///
+    /// All of the keys this process can allocate are allocated the first time
+    /// this is called, and shared by all pooling allocators.
+    pub fn allocate(max: usize) -> Vec<ProtectionKey> {
+        let keys = Self::all();
+        keys[..max.min(keys.len())].to_vec()
+    }
+
+    fn all() -> &'static [ProtectionKey] {
+        ALLOCATE_KEYS.call_once(|| {
+            if !cpu_supports_pkeys() {
+                return;
+            }
+            let mut keys = Vec::new();
+            loop {
+                // Allocate keys which the calling thread can access, just like
+                // the default key, since host code doesn't restrict itself.
+                let key = unsafe { libc::syscall(libc::SYS_pkey_alloc, 0, 0) };
+                if key < 0 {
+                    break;
+                }
+                keys.push(ProtectionKey(key as u32));
+            }
+            unsafe {
+                KEYS = keys;
+            }
+        });
+        unsafe { &KEYS }
+    }
+
+    /// Makes the pages at `addr..addr + len` read/write if `accessible` is
+    /// set, or inaccessible otherwise, and tags them with this key.
+    ///
+    /// Pages mapped over later on lose their key, so this needs to be
+    /// reapplied afterwards.
+    pub fn protect(&self, addr: *mut u8, len: usize, accessible: bool) -> Result<()> {
+        if len == 0 {
+            return Ok(());
+        }
+        let prot = if accessible {
+            libc::PROT_READ | libc::PROT_WRITE
+        } else {
+            libc::PROT_NONE
+        };
+        let result = unsafe {
+            libc::syscall(
+                libc::SYS_pkey_mprotect,
+                addr,
+                len,
+                prot,
+                self.0 as libc::c_int,
+            )
+        };
+        if result != 0 {
+            bail!(
+                "failed to protect memory with protection key {}: {}",
+                self.0,
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+}
+
+fn cpu_supports_pkeys() -> bool {
+    use std::arch::x86_64::{__cpuid_count, __get_cpuid_max};
+    unsafe {
+        if __get_cpuid_max(0).0 < 7 {
+            return false;
+        }
+        // `PKU` means the CPU supports protection keys, and `OSPKE` that the
+        // kernel has enabled them.
+        let ecx = __cpuid_count(7, 0).ecx;
+        ecx & (1 << 3) != 0 && ecx & (1 << 4) != 0
+    }
+}
+
+/// The set of protection keys whose pages the current thread can access,
+/// which is the state of its `PKRU` register.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtectionMask(u32);
+
+impl ProtectionMask {
+    /// A mask with every key enabled.
+    pub fn all() -> ProtectionMask {
+        ProtectionMask(0)
+    }
+
+    /// A mask with only the default key, 0, enabled, which all memory outside
+    /// of the pooling allocator's memory slots is tagged with.
+    pub fn zero() -> ProtectionMask {
+        ProtectionMask(!0b11)
+    }
+
+    /// Returns this mask, additionally enabling `key`.
+    pub fn or(self, key: ProtectionKey) -> ProtectionMask {
+        // Each key has an access-disable and a write-disable bit.
+        ProtectionMask(self.0 & !(0b11 << (key.0 * 2)))
+    }
+
+    /// Returns the current thread's mask.
+    pub fn current() -> ProtectionMask {
+        let pkru: u32;
+        unsafe {
+            std::arch::asm!(
+                "rdpkru",
+                in("ecx") 0,
+                out("eax") pkru,
+                out("edx") _,
+                options(nomem, nostack, preserves_flags),
+            );
+        }
+        ProtectionMask(pkru)
+    }
+
+    /// Makes this mask the current thread's mask.
+    pub fn allow(self) {
+        unsafe {
+            std::arch::asm!(
+                "wrpkru",
+                in("eax") self.0,
+                in("ecx") 0,
+                in("edx") 0,
+                options(nostack, preserves_flags),
+            );
+        }
+    }
+}
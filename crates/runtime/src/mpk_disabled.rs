@@ -0,0 +1,54 @@
+//! Shims for memory protection keys on hosts which don't support them.
+//! Enables unconditional use of the types throughout higher-level code.
+
+use anyhow::Result;
+
+/// A shim for a protection key, which can't be allocated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProtectionKey {}
+
+impl ProtectionKey {
+    /// Memory protection keys are never supported on this host.
+    pub fn is_supported() -> bool {
+        false
+    }
+
+    /// No keys can be allocated on this host.
+    pub fn allocate(_max: usize) -> Vec<ProtectionKey> {
+        Vec::new()
+    }
+
+    /// Protection keys can't exist on this host.
+    pub fn protect(&self, _addr: *mut u8, _len: usize, _accessible: bool) -> Result<()> {
+        match *self {}
+    }
+}
+
+/// A shim for the set of protection keys the current thread can access.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtectionMask;
+
+impl ProtectionMask {
+    /// All memory is always accessible on this host.
+    pub fn all() -> ProtectionMask {
+        ProtectionMask
+    }
+
+    /// All memory is always accessible on this host.
+    pub fn zero() -> ProtectionMask {
+        ProtectionMask
+    }
+
+    /// Protection keys can't exist on this host.
+    pub fn or(self, key: ProtectionKey) -> ProtectionMask {
+        match key {}
+    }
+
+    /// All memory is always accessible on this host.
+    pub fn current() -> ProtectionMask {
+        ProtectionMask
+    }
+
+    /// Does nothing, since all memory is always accessible on this host.
+    pub fn allow(self) {}
+}
@@ -0,0 +1,55 @@
+//! The globals which all instances of a module share.
+
+use crate::mmap::Mmap;
+use crate::vmcontext::VMGlobalDefinition;
+use anyhow::Result;
+use std::mem;
+use std::ptr;
+use wasmtime_environ::{GlobalInit, Module};
+
+/// The values of a module's `shared_globals`, which are the same in all of
+/// its instances, in memory which is made read-only once they're written.
+///
+/// Each instance of the module points to these rather than holding a copy
+/// of them in its `VMContext`.
+#[derive(Debug)]
+pub struct SharedGlobals {
+    mmap: Mmap,
+}
+
+impl SharedGlobals {
+    /// Creates the shared globals of `module`, or returns `None` if it
+    /// doesn't have any.
+    pub fn new(module: &Module) -> Result<Option<Self>> {
+        if module.shared_globals.is_empty() {
+            return Ok(None);
+        }
+        let size = module.shared_globals.len() * mem::size_of::<VMGlobalDefinition>();
+        let mmap = Mmap::with_at_least(size)?;
+        let globals = mmap.as_mut_ptr().cast::<VMGlobalDefinition>();
+        for (slot, index) in module.shared_globals.iter().enumerate() {
+            let global = &module.globals[module.global_index(*index)];
+            unsafe {
+                let to = globals.add(slot);
+                ptr::write(to, VMGlobalDefinition::new());
+                match global.initializer {
+                    GlobalInit::I32Const(x) => *(*to).as_i32_mut() = x,
+                    GlobalInit::I64Const(x) => *(*to).as_i64_mut() = x,
+                    GlobalInit::F32Const(x) => *(*to).as_f32_bits_mut() = x,
+                    GlobalInit::F64Const(x) => *(*to).as_f64_bits_mut() = x,
+                    GlobalInit::V128Const(x) => *(*to).as_u128_mut() = x,
+                    // `VMGlobalDefinition::new()` already zeroed out the bits
+                    GlobalInit::RefNullConst => {}
+                    init => panic!("global initialized with {:?} can't be shared", init),
+                }
+            }
+        }
+        unsafe { mmap.make_readonly(0..mmap.len())? };
+        Ok(Some(Self { mmap }))
+    }
+
+    /// Returns a pointer to the first of the shared globals.
+    pub fn as_ptr(&self) -> *const VMGlobalDefinition {
+        self.mmap.as_ptr().cast()
+    }
+}
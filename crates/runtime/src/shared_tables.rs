@@ -0,0 +1,104 @@
+//! The tables which all instances of a module share.
+
+use crate::mmap::Mmap;
+use anyhow::Result;
+use std::mem;
+use std::ops::Range;
+use wasmtime_environ::{
+    packed_option::ReservedValue, DefinedTableIndex, FuncIndex, HostPtr, Module,
+    TableInitialization, VMOffsets,
+};
+
+/// The elements of a module's `shared_tables`, which are the same in all of
+/// its instances, in memory which is made read-only once they're written.
+///
+/// Each element is the offset of the anyfunc it refers to within the
+/// `VMContext` of an instance, or zero for null, so that every instance can
+/// find its own anyfunc from it. Each instance constructs the anyfuncs of
+/// `funcs` when it's created, and its shared tables point to these rather
+/// than to a copy of them.
+#[derive(Debug)]
+pub struct SharedTables {
+    mmap: Mmap,
+    tables: Vec<(DefinedTableIndex, Range<usize>)>,
+    funcs: Vec<FuncIndex>,
+}
+
+impl SharedTables {
+    /// Creates the shared tables of `module`, or returns `None` if it
+    /// doesn't have any.
+    pub fn new(module: &Module) -> Result<Option<Self>> {
+        if module.shared_tables.is_empty() {
+            return Ok(None);
+        }
+        let offsets = VMOffsets::new(HostPtr, module);
+        let (images, segments) = match &module.table_initialization {
+            TableInitialization::FuncTable { tables, segments } => (Some(tables), segments),
+            TableInitialization::Segments { segments } => (None, segments),
+        };
+
+        let mut tables = Vec::with_capacity(module.shared_tables.len());
+        let mut len = 0;
+        for index in &module.shared_tables {
+            let size = module.table_plans[module.table_index(*index)].table.minimum as usize;
+            tables.push((*index, len..len + size));
+            len += size;
+        }
+        let mmap = Mmap::with_at_least(len * mem::size_of::<usize>())?;
+        let elements =
+            unsafe { std::slice::from_raw_parts_mut(mmap.as_mut_ptr().cast::<usize>(), len) };
+
+        let mut funcs = Vec::new();
+        for (index, range) in &tables {
+            let table = module.table_index(*index);
+
+            // Segments left over from the table's pre-computed contents
+            // overwrite them, as they do when they're written to a table
+            // which isn't shared.
+            let mut indices = vec![FuncIndex::reserved_value(); range.len()];
+            if let Some(image) = images.and_then(|images| images.get(table)) {
+                indices[..image.len()].copy_from_slice(image);
+            }
+            for segment in segments.iter().filter(|s| s.table_index == table) {
+                let offset = segment.offset as usize;
+                indices[offset..offset + segment.elements.len()].copy_from_slice(&segment.elements);
+            }
+
+            for (element, func) in elements[range.clone()].iter_mut().zip(indices) {
+                *element = if func.is_reserved_value() {
+                    0
+                } else {
+                    funcs.push(func);
+                    offsets.vmctx_anyfunc(module.functions[func].anyfunc) as usize
+                };
+            }
+        }
+        unsafe { mmap.make_readonly(0..mmap.len())? };
+
+        funcs.sort_unstable();
+        funcs.dedup();
+        Ok(Some(Self {
+            mmap,
+            tables,
+            funcs,
+        }))
+    }
+
+    /// Returns the elements of the shared table `index`, or `None` if it
+    /// isn't shared.
+    pub fn elements(&self, index: DefinedTableIndex) -> Option<&[usize]> {
+        let i = self
+            .tables
+            .binary_search_by_key(&index, |(index, _)| *index)
+            .ok()?;
+        let range = self.tables[i].1.clone();
+        let elements = self.mmap.as_ptr().cast::<usize>();
+        Some(unsafe { std::slice::from_raw_parts(elements.add(range.start), range.len()) })
+    }
+
+    /// Returns the functions which the elements of the shared tables refer
+    /// to.
+    pub fn funcs(&self) -> &[FuncIndex] {
+        &self.funcs
+    }
+}
@@ -2,7 +2,7 @@
 //!
 //! `Table` is to WebAssembly tables what `LinearMemory` is to WebAssembly linear memories.
 
-use crate::vmcontext::{VMCallerCheckedAnyfunc, VMTableDefinition};
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMContext, VMTableDefinition};
 use crate::{Store, Trap, VMExternRef};
 use anyhow::{bail, format_err, Error, Result};
 use std::convert::{TryFrom, TryInto};
@@ -26,9 +26,12 @@ pub enum TableElement {
     UninitFunc,
 }
 
+/// The type of the elements of a table.
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub enum TableElementType {
+    /// The table holds `funcref`s.
     Func,
+    /// The table holds `externref`s.
     Extern,
 }
 
@@ -161,6 +164,15 @@ pub enum Table {
         /// Maximum size that `elements` can grow to.
         maximum: Option<u32>,
     },
+    /// A funcref table whose elements are shared by all instances of its
+    /// module, see `SharedTables`, and which can't be modified.
+    Shared {
+        /// The shared elements, which the module owns, and which are the
+        /// offsets of anyfuncs within `vmctx`, or zero for null.
+        data: &'static [usize],
+        /// The `VMContext` of the instance the table belongs to.
+        vmctx: *mut VMContext,
+    },
 }
 
 fn wasm_to_table_type(ty: WasmType) -> Result<TableElementType> {
@@ -178,6 +190,7 @@ impl Table {
         let elements = vec![0; plan.table.minimum as usize];
         let ty = wasm_to_table_type(plan.table.wasm_ty)?;
         let maximum = plan.table.maximum;
+        Self::created(plan, store);
 
         Ok(Table::Dynamic {
             elements,
@@ -207,10 +220,44 @@ impl Table {
             Some(max) if (max as usize) < data.len() => &mut data[..max as usize],
             _ => data,
         };
+        Self::created(plan, store);
 
         Ok(Table::Static { data, size, ty })
     }
 
+    /// Create a new shared table instance for the specified table plan, whose
+    /// elements are `data`.
+    ///
+    /// The table's `VMContext` must be set with `set_vmctx` before its
+    /// elements are read.
+    ///
+    /// # Unsafety
+    ///
+    /// `data` must outlive the table, which holds on to it.
+    pub unsafe fn new_shared(
+        plan: &TablePlan,
+        data: &[usize],
+        store: &mut dyn Store,
+    ) -> Result<Self> {
+        Self::limit_new(plan, store)?;
+        debug_assert_eq!(plan.table.wasm_ty, WasmType::FuncRef);
+        debug_assert_eq!(data.len(), plan.table.minimum as usize);
+        Self::created(plan, store);
+
+        Ok(Table::Shared {
+            data: &*(data as *const [usize]),
+            vmctx: ptr::null_mut(),
+        })
+    }
+
+    /// Sets the `VMContext` which the elements of a shared table are relative
+    /// to. This does nothing for other tables.
+    pub(crate) fn set_vmctx(&mut self, vmctx: *mut VMContext) {
+        if let Table::Shared { vmctx: v, .. } = self {
+            *v = vmctx;
+        }
+    }
+
     fn limit_new(plan: &TablePlan, store: &mut dyn Store) -> Result<()> {
         if !store.table_growing(0, plan.table.minimum, plan.table.maximum)? {
             bail!(
@@ -221,11 +268,20 @@ impl Table {
         Ok(())
     }
 
+    /// Informs the `store`'s limiter that a table, which it permitted in
+    /// `limit_new`, was successfully created.
+    fn created(plan: &TablePlan, store: &mut dyn Store) {
+        if plan.table.minimum > 0 {
+            store.table_grown(0, plan.table.minimum);
+        }
+    }
+
     /// Returns the type of the elements in this table.
     pub fn element_type(&self) -> TableElementType {
         match self {
             Table::Static { ty, .. } => *ty,
             Table::Dynamic { ty, .. } => *ty,
+            Table::Shared { .. } => TableElementType::Func,
         }
     }
 
@@ -239,11 +295,23 @@ impl Table {
         }
     }
 
+    /// Returns whether or not the elements of the table are shared by all
+    /// instances of its module.
+    #[cfg(feature = "pooling-allocator")]
+    pub(crate) fn is_shared(&self) -> bool {
+        if let Table::Shared { .. } = self {
+            true
+        } else {
+            false
+        }
+    }
+
     /// Returns the number of allocated elements.
     pub fn size(&self) -> u32 {
         match self {
             Table::Static { size, .. } => *size,
             Table::Dynamic { elements, .. } => elements.len().try_into().unwrap(),
+            Table::Shared { data, .. } => data.len().try_into().unwrap(),
         }
     }
 
@@ -257,6 +325,7 @@ impl Table {
         match self {
             Table::Static { data, .. } => Some(data.len() as u32),
             Table::Dynamic { maximum, .. } => maximum.clone(),
+            Table::Shared { data, .. } => Some(data.len().try_into().unwrap()),
         }
     }
 
@@ -334,6 +403,11 @@ impl Table {
         init_value: TableElement,
         store: &mut dyn Store,
     ) -> Result<Option<u32>, Error> {
+        // Shared tables can't be modified
+        if let Table::Shared { .. } = self {
+            return Ok(None);
+        }
+
         let old_size = self.size();
         let new_size = match old_size.checked_add(delta) {
             Some(s) => s,
@@ -364,11 +438,15 @@ impl Table {
             Table::Dynamic { elements, .. } => {
                 elements.resize(new_size as usize, 0);
             }
+            Table::Shared { .. } => unreachable!(),
         }
 
         self.fill(old_size, init_value, delta)
             .expect("table should not be out of bounds");
 
+        if delta > 0 {
+            store.table_grown(old_size, new_size);
+        }
         Ok(Some(old_size))
     }
 
@@ -376,6 +454,14 @@ impl Table {
     ///
     /// Returns `None` if the index is out of bounds.
     pub fn get(&self, index: u32) -> Option<TableElement> {
+        if let Table::Shared { data, vmctx } = self {
+            let offset = *data.get(index as usize)?;
+            let anyfunc = match offset {
+                0 => ptr::null_mut(),
+                offset => unsafe { vmctx.cast::<u8>().add(offset).cast() },
+            };
+            return Some(TableElement::FuncRef(anyfunc));
+        }
         self.elements()
             .get(index as usize)
             .map(|p| unsafe { TableElement::clone_from_table_value(self.element_type(), *p) })
@@ -388,7 +474,7 @@ impl Table {
     /// Returns an error if `index` is out of bounds or if this table type does
     /// not match the element type.
     pub fn set(&mut self, index: u32, elem: TableElement) -> Result<(), ()> {
-        if !self.type_matches(&elem) {
+        if !self.type_matches(&elem) || matches!(self, Table::Shared { .. }) {
             return Err(());
         }
 
@@ -452,6 +538,10 @@ impl Table {
                 base: elements.as_mut_ptr().cast(),
                 current_elements: elements.len().try_into().unwrap(),
             },
+            Table::Shared { data, .. } => VMTableDefinition {
+                base: data.as_ptr() as *mut u8,
+                current_elements: data.len().try_into().unwrap(),
+            },
         }
     }
 
@@ -467,6 +557,7 @@ impl Table {
         match self {
             Table::Static { data, size, .. } => &data[..*size as usize],
             Table::Dynamic { elements, .. } => &elements[..],
+            Table::Shared { data, .. } => data,
         }
     }
 
@@ -474,6 +565,7 @@ impl Table {
         match self {
             Table::Static { data, size, .. } => &mut data[..*size as usize],
             Table::Dynamic { elements, .. } => &mut elements[..],
+            Table::Shared { .. } => panic!("shared tables can't be modified"),
         }
     }
 
@@ -500,9 +592,19 @@ impl Table {
 
         match ty {
             TableElementType::Func => {
-                // `funcref` are `Copy`, so just do a mempcy
-                dst_table.elements_mut()[dst_range]
-                    .copy_from_slice(&src_table.elements()[src_range]);
+                if let Table::Shared { .. } = src_table {
+                    // The elements of shared tables aren't pointers, so each
+                    // is read through `get` instead
+                    let dst = dst_table.elements_mut();
+                    for (s, d) in src_range.zip(dst_range) {
+                        let elem = src_table.get(s as u32).unwrap();
+                        Self::set_raw(ty, &mut dst[d], elem);
+                    }
+                } else {
+                    // `funcref` are `Copy`, so just do a mempcy
+                    dst_table.elements_mut()[dst_range]
+                        .copy_from_slice(&src_table.elements()[src_range]);
+                }
             }
             TableElementType::Extern => {
                 // We need to clone each `externref`
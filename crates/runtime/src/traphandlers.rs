@@ -1,7 +1,8 @@
 //! WebAssembly trap handling, which is built on top of the lower-level
 //! signalhandling mechanisms.
 
-use crate::VMContext;
+use crate::coredump::{capture_coredump_stack, CoreDumpStackFrame};
+use crate::{VMContext, VMRuntimeLimits};
 use anyhow::Error;
 use std::any::Any;
 use std::cell::{Cell, UnsafeCell};
@@ -25,15 +26,21 @@ extern "C" {
 }
 
 cfg_if::cfg_if! {
-    if #[cfg(all(target_os = "macos", not(feature = "posix-signals-on-macos")))] {
+    if #[cfg(wasmtime_custom_platform)] {
+        mod custom;
+        use custom as sys;
+        pub use custom::handle_trap;
+    } else if #[cfg(all(target_os = "macos", not(feature = "posix-signals-on-macos")))] {
         mod macos;
         use macos as sys;
     } else if #[cfg(unix)] {
         mod unix;
         use unix as sys;
+        pub use unix::handle_trap;
     } else if #[cfg(target_os = "windows")] {
         mod windows;
         use windows as sys;
+        pub use windows::handle_trap;
     }
 }
 
@@ -46,6 +53,13 @@ pub use sys::SignalHandler;
 /// `wasmtime` currently.
 static mut IS_WASM_PC: fn(usize) -> bool = |_| false;
 
+/// Whether `init_traps` has run, and threads entering WebAssembly need to
+/// perform their per-thread initialization for handling traps.
+static INIT: Once = Once::new();
+
+/// Whether the platform's signal handlers have been installed by `init_traps`.
+static HANDLERS: Once = Once::new();
+
 /// This function is required to be called before any WebAssembly is entered.
 /// This will configure global state such as signal handlers to prepare the
 /// process to receive wasm traps.
@@ -55,16 +69,35 @@ static mut IS_WASM_PC: fn(usize) -> bool = |_| false;
 /// WebAssembly. Currently in wasmtime's integration this function is called on
 /// creation of a `Engine`.
 ///
+/// Code compiled without signals-based traps never relies on these handlers,
+/// so if this is never called then the process's signal handlers are left
+/// untouched and threads skip their per-thread initialization as well.
+///
 /// The `is_wasm_pc` argument is used when a trap happens to determine if a
 /// program counter is the pc of an actual wasm trap or not. This is then used
 /// to disambiguate faults that happen due to wasm and faults that happen due to
 /// bugs in Rust or elsewhere.
-pub fn init_traps(is_wasm_pc: fn(usize) -> bool) {
-    static INIT: Once = Once::new();
+///
+/// If `install_handlers` is `false` then the platform's signal handlers are not
+/// installed, and the embedder is instead responsible for forwarding faults to
+/// `handle_trap`. Handlers are installed the first time this is called with
+/// `install_handlers` set. On macOS with Mach ports handlers are always
+/// installed since they don't conflict with signal handlers.
+pub fn init_traps(is_wasm_pc: fn(usize) -> bool, install_handlers: bool) {
     INIT.call_once(|| unsafe {
         IS_WASM_PC = is_wasm_pc;
-        sys::platform_init();
     });
+    let install_handlers = install_handlers
+        || cfg!(all(
+            target_os = "macos",
+            not(feature = "posix-signals-on-macos"),
+            not(wasmtime_custom_platform)
+        ));
+    if install_handlers {
+        HANDLERS.call_once(|| unsafe {
+            sys::platform_init();
+        });
+    }
 }
 
 /// Raises a user-defined trap immediately.
@@ -190,16 +223,26 @@ impl Backtrace {
 /// Catches any wasm traps that happen within the execution of `closure`,
 /// returning them as a `Result`.
 ///
+/// When `coredump_stack` is given and a trap happens, the records of the
+/// frames of code compiled to capture core dumps which are on the stack are
+/// copied to it, starting with the most recent frame found in the
+/// `VMRuntimeLimits` of `callee`.
+///
 /// Highly unsafe since `closure` won't have any dtors run.
 pub unsafe fn catch_traps<'a, F>(
     signal_handler: Option<*const SignalHandler<'static>>,
     callee: *mut VMContext,
+    coredump_stack: Option<&mut Vec<CoreDumpStackFrame>>,
     mut closure: F,
 ) -> Result<(), Box<Trap>>
 where
     F: FnMut(*mut VMContext),
 {
-    return CallThreadState::new(signal_handler).with(|cx| {
+    let coredump_stack = coredump_stack.map(|stack| {
+        let limits = *(*callee).instance().runtime_limits();
+        (limits, stack as *mut _)
+    });
+    return CallThreadState::new(signal_handler, coredump_stack).with(|cx| {
         wasmtime_setjmp(
             cx.jmp_buf.as_ptr(),
             call_closure::<F>,
@@ -223,6 +266,7 @@ pub struct CallThreadState {
     jmp_buf: Cell<*const u8>,
     handling_trap: Cell<bool>,
     signal_handler: Option<*const SignalHandler<'static>>,
+    coredump_stack: Option<(*const VMRuntimeLimits, *mut Vec<CoreDumpStackFrame>)>,
     prev: Cell<tls::Ptr>,
 }
 
@@ -235,12 +279,16 @@ enum UnwindReason {
 
 impl CallThreadState {
     #[inline]
-    fn new(signal_handler: Option<*const SignalHandler<'static>>) -> CallThreadState {
+    fn new(
+        signal_handler: Option<*const SignalHandler<'static>>,
+        coredump_stack: Option<(*const VMRuntimeLimits, *mut Vec<CoreDumpStackFrame>)>,
+    ) -> CallThreadState {
         CallThreadState {
             unwind: UnsafeCell::new(MaybeUninit::uninit()),
             jmp_buf: Cell::new(ptr::null()),
             handling_trap: Cell::new(false),
             signal_handler,
+            coredump_stack,
             prev: Cell::new(ptr::null()),
         }
     }
@@ -265,6 +313,9 @@ impl CallThreadState {
     }
 
     fn unwind_with(&self, reason: UnwindReason) -> ! {
+        if !matches!(reason, UnwindReason::Panic(_)) {
+            self.capture_coredump_stack();
+        }
         unsafe {
             (*self.unwind.get()).as_mut_ptr().write(reason);
             wasmtime_longjmp(self.jmp_buf.get());
@@ -327,6 +378,7 @@ impl CallThreadState {
     }
 
     fn capture_backtrace(&self, pc: *const u8) {
+        self.capture_coredump_stack();
         let backtrace = Backtrace::new();
         unsafe {
             (*self.unwind.get())
@@ -337,6 +389,16 @@ impl CallThreadState {
                 });
         }
     }
+
+    /// Copies the records of the frames on the stack to `coredump_stack`,
+    /// while the frames are still there.
+    fn capture_coredump_stack(&self) {
+        if let Some((limits, stack)) = self.coredump_stack {
+            unsafe {
+                *stack = capture_coredump_stack(*(*limits).last_coredump_frame.get());
+            }
+        }
+    }
 }
 
 struct ResetCell<'a, T: Copy>(&'a Cell<T>, T);
@@ -374,6 +436,7 @@ mod tls {
     //
     // Note, though, that if async support is disabled at compile time then
     // these functions are free to be inlined.
+    #[cfg(not(wasmtime_custom_platform))]
     mod raw {
         use super::CallThreadState;
         use crate::Trap;
@@ -396,11 +459,12 @@ mod tls {
                 // When a new value is configured that means that we may be
                 // entering WebAssembly so check to see if this thread has
                 // performed per-thread initialization for traps.
-                let (prev, initialized) = p.get();
-                if !initialized {
+                let (prev, mut initialized) = p.get();
+                if !initialized && super::super::INIT.is_completed() {
                     super::super::sys::lazy_per_thread_init()?;
+                    initialized = true;
                 }
-                p.set((val, true));
+                p.set((val, initialized));
                 Ok(prev)
             })
         }
@@ -412,7 +476,7 @@ mod tls {
         pub fn initialize() -> Result<(), Box<Trap>> {
             PTR.with(|p| {
                 let (state, initialized) = p.get();
-                if initialized {
+                if initialized || !super::super::INIT.is_completed() {
                     return Ok(());
                 }
                 super::super::sys::lazy_per_thread_init()?;
@@ -428,6 +492,33 @@ mod tls {
         }
     }
 
+    // On custom platforms the pointer lives in the embedder's thread-local
+    // storage, and there's no per-thread initialization to track.
+    #[cfg(wasmtime_custom_platform)]
+    mod raw {
+        use super::CallThreadState;
+        use crate::custom_platform::{wasmtime_tls_get, wasmtime_tls_set};
+        use crate::Trap;
+
+        pub type Ptr = *const CallThreadState;
+
+        pub fn replace(val: Ptr) -> Result<Ptr, Box<Trap>> {
+            let prev = get();
+            unsafe { wasmtime_tls_set(val as *mut u8) };
+            Ok(prev)
+        }
+
+        /// Eagerly initialize thread-local runtime functionality, which
+        /// custom platforms don't have.
+        pub fn initialize() -> Result<(), Box<Trap>> {
+            Ok(())
+        }
+
+        pub fn get() -> Ptr {
+            unsafe { wasmtime_tls_get() as Ptr }
+        }
+    }
+
     pub use raw::initialize as tls_eager_initialize;
 
     /// Opaque state used to help control TLS state across stack switches for
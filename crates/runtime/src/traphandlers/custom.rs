@@ -0,0 +1,55 @@
+use crate::custom_platform;
+use crate::traphandlers::{tls, wasmtime_longjmp};
+
+/// Function which may handle custom signals while processing traps.
+///
+/// It's given the program counter of the faulting instruction and, for
+/// invalid memory accesses, the faulting address.
+pub type SignalHandler<'a> = dyn Fn(usize, Option<usize>) -> bool + Send + Sync + 'a;
+
+pub unsafe fn platform_init() {
+    if let Err(e) = custom_platform::init_traps(trap_handler) {
+        panic!("{:#}", e);
+    }
+}
+
+extern "C" fn trap_handler(pc: usize, has_faulting_addr: bool, faulting_addr: usize) -> bool {
+    let faulting_addr = if has_faulting_addr {
+        Some(faulting_addr)
+    } else {
+        None
+    };
+    unsafe { handle_trap(pc, faulting_addr) }
+}
+
+/// Handles a hardware trap raised by WebAssembly code on behalf of a trap
+/// handler installed by the embedder rather than the one passed to
+/// `wasmtime_init_traps`.
+///
+/// Returns `false` if the trap wasn't raised by wasm, in which case it's up to
+/// the caller to handle it. Otherwise this either unwinds straight to the point
+/// where wasm was entered, never returning, or returns `true` if a custom
+/// signal handler handled the trap and execution should continue.
+///
+/// # Safety
+///
+/// This must only be called from the context of the trap, on the thread which
+/// raised it, with its faulting program counter.
+pub unsafe fn handle_trap(pc: usize, faulting_addr: Option<usize>) -> bool {
+    tls::with(|info| {
+        let info = match info {
+            Some(info) => info,
+            None => return false,
+        };
+        let pc = pc as *const u8;
+        let jmp_buf = info.jmp_buf_if_trap(pc, |handler| handler(pc as usize, faulting_addr));
+        if jmp_buf.is_null() {
+            false
+        } else if jmp_buf as usize == 1 {
+            true
+        } else {
+            info.capture_backtrace(pc);
+            wasmtime_longjmp(jmp_buf)
+        }
+    })
+}
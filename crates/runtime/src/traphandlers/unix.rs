@@ -71,7 +71,54 @@ unsafe extern "C" fn trap_handler(
         libc::SIGILL => &PREV_SIGILL,
         _ => panic!("unknown signal: {}", signum),
     };
-    let handled = tls::with(|info| {
+    if handle_trap(signum, siginfo, context) {
+        return;
+    }
+
+    // This signal is not for any compiled wasm code we expect, so we
+    // need to forward the signal to the next handler. If there is no
+    // next handler (SIG_IGN or SIG_DFL), then it's time to crash. To do
+    // this, we set the signal back to its original disposition and
+    // return. This will cause the faulting op to be re-executed which
+    // will crash in the normal way. If there is a next handler, call
+    // it. It will either crash synchronously, fix up the instruction
+    // so that execution can continue and return, or trigger a crash by
+    // returning the signal to it's original disposition and returning.
+    let previous = &*previous.as_ptr();
+    if previous.sa_flags & libc::SA_SIGINFO != 0 {
+        mem::transmute::<usize, extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)>(
+            previous.sa_sigaction,
+        )(signum, siginfo, context)
+    } else if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+        libc::sigaction(signum, previous, ptr::null_mut());
+    } else {
+        mem::transmute::<usize, extern "C" fn(libc::c_int)>(previous.sa_sigaction)(signum)
+    }
+}
+
+/// Handles a signal raised by WebAssembly code on behalf of a signal handler
+/// installed by the embedder rather than by wasmtime itself.
+///
+/// Returns `false` if the signal wasn't raised by wasm, in which case it's up
+/// to the caller to handle it. Otherwise this either unwinds straight to the
+/// point where wasm was entered, never returning, or returns `true` after
+/// updating `context` such that returning from the signal handler resumes
+/// execution appropriately.
+///
+/// # Safety
+///
+/// This must only be called from within a signal handler for `signum`, with
+/// the `siginfo` and `context` arguments that the handler received.
+pub unsafe fn handle_trap(
+    signum: libc::c_int,
+    siginfo: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) -> bool {
+    match signum {
+        libc::SIGSEGV | libc::SIGBUS | libc::SIGFPE | libc::SIGILL => {}
+        _ => return false,
+    }
+    tls::with(|info| {
         // If no wasm code is executing, we don't handle this as a wasm
         // trap.
         let info = match info {
@@ -137,31 +184,7 @@ unsafe extern "C" fn trap_handler(
             return true;
         }
         wasmtime_longjmp(jmp_buf)
-    });
-
-    if handled {
-        return;
-    }
-
-    // This signal is not for any compiled wasm code we expect, so we
-    // need to forward the signal to the next handler. If there is no
-    // next handler (SIG_IGN or SIG_DFL), then it's time to crash. To do
-    // this, we set the signal back to its original disposition and
-    // return. This will cause the faulting op to be re-executed which
-    // will crash in the normal way. If there is a next handler, call
-    // it. It will either crash synchronously, fix up the instruction
-    // so that execution can continue and return, or trigger a crash by
-    // returning the signal to it's original disposition and returning.
-    let previous = &*previous.as_ptr();
-    if previous.sa_flags & libc::SA_SIGINFO != 0 {
-        mem::transmute::<usize, extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)>(
-            previous.sa_sigaction,
-        )(signum, siginfo, context)
-    } else if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
-        libc::sigaction(signum, previous, ptr::null_mut());
-    } else {
-        mem::transmute::<usize, extern "C" fn(libc::c_int)>(previous.sa_sigaction)(signum)
-    }
+    })
 }
 
 unsafe fn get_pc(cx: *mut libc::c_void, _signum: libc::c_int) -> *const u8 {
@@ -22,6 +22,26 @@ pub unsafe fn platform_init() {
 }
 
 unsafe extern "system" fn exception_handler(exception_info: PEXCEPTION_POINTERS) -> LONG {
+    if handle_trap(exception_info) {
+        EXCEPTION_CONTINUE_EXECUTION
+    } else {
+        EXCEPTION_CONTINUE_SEARCH
+    }
+}
+
+/// Handles an exception raised by WebAssembly code on behalf of an exception
+/// handler installed by the embedder rather than by wasmtime itself.
+///
+/// Returns `false` if the exception wasn't raised by wasm, in which case it's
+/// up to the caller to handle it. Otherwise this either unwinds straight to the
+/// point where wasm was entered, never returning, or returns `true` if
+/// execution should continue with `EXCEPTION_CONTINUE_EXECUTION`.
+///
+/// # Safety
+///
+/// This must only be called from within an exception handler with the
+/// exception information that the handler received.
+pub unsafe fn handle_trap(exception_info: PEXCEPTION_POINTERS) -> bool {
     // Check the kind of exception, since we only handle a subset within
     // wasm code. If anything else happens we want to defer to whatever
     // the rest of the system wants to do for this exception.
@@ -31,7 +51,7 @@ unsafe extern "system" fn exception_handler(exception_info: PEXCEPTION_POINTERS)
         && record.ExceptionCode != EXCEPTION_INT_DIVIDE_BY_ZERO
         && record.ExceptionCode != EXCEPTION_INT_OVERFLOW
     {
-        return EXCEPTION_CONTINUE_SEARCH;
+        return false;
     }
 
     // FIXME: this is what the previous C++ did to make sure that TLS
@@ -51,7 +71,7 @@ unsafe extern "system" fn exception_handler(exception_info: PEXCEPTION_POINTERS)
     tls::with(|info| {
         let info = match info {
             Some(info) => info,
-            None => return EXCEPTION_CONTINUE_SEARCH,
+            None => return false,
         };
         cfg_if::cfg_if! {
             if #[cfg(target_arch = "x86_64")] {
@@ -64,9 +84,9 @@ unsafe extern "system" fn exception_handler(exception_info: PEXCEPTION_POINTERS)
         }
         let jmp_buf = info.jmp_buf_if_trap(ip, |handler| handler(exception_info));
         if jmp_buf.is_null() {
-            EXCEPTION_CONTINUE_SEARCH
+            false
         } else if jmp_buf as usize == 1 {
-            EXCEPTION_CONTINUE_EXECUTION
+            true
         } else {
             info.capture_backtrace(ip);
             wasmtime_longjmp(jmp_buf)
@@ -6,7 +6,7 @@ use crate::instance::Instance;
 use std::any::Any;
 use std::cell::UnsafeCell;
 use std::marker;
-use std::ptr::NonNull;
+use std::ptr::{self, NonNull};
 use std::u32;
 
 /// An imported function.
@@ -584,6 +584,55 @@ mod test_vmcaller_checked_anyfunc {
     }
 }
 
+/// The cache of a `call_indirect` instruction which calls through a table that
+/// is never modified, holding the index and element of its last callee.
+#[derive(Debug)]
+#[repr(C)]
+pub struct VMCallIndirectCache {
+    /// The index of the last callee in the table, or `u32::MAX` before the
+    /// first call.
+    pub index: u32,
+    /// The element at `index`, which is of the instruction's signature, or
+    /// null before the first call. The cache is only used when this is
+    /// non-null, since `index` can still be called before then.
+    pub anyfunc: *mut VMCallerCheckedAnyfunc,
+    // If more elements are added here, remember to add offset_of tests below!
+}
+
+impl VMCallIndirectCache {
+    /// The cache of an instruction which has made no calls yet.
+    pub const EMPTY: Self = Self {
+        index: u32::MAX,
+        anyfunc: std::ptr::null_mut(),
+    };
+}
+
+#[cfg(test)]
+mod test_vmcall_indirect_cache {
+    use super::VMCallIndirectCache;
+    use memoffset::offset_of;
+    use std::mem::size_of;
+    use wasmtime_environ::{Module, VMOffsets};
+
+    #[test]
+    fn check_vmcall_indirect_cache_offsets() {
+        let module = Module::new();
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module);
+        assert_eq!(
+            size_of::<VMCallIndirectCache>(),
+            usize::from(offsets.size_of_vmcall_indirect_cache())
+        );
+        assert_eq!(
+            offset_of!(VMCallIndirectCache, index),
+            usize::from(offsets.vmcall_indirect_cache_index())
+        );
+        assert_eq!(
+            offset_of!(VMCallIndirectCache, anyfunc),
+            usize::from(offsets.vmcall_indirect_cache_anyfunc())
+        );
+    }
+}
+
 macro_rules! define_builtin_array {
     (
         $(
@@ -679,12 +728,18 @@ pub struct VMRuntimeLimits {
     /// observed to reach or exceed this value, the guest code will
     /// yield if running asynchronously.
     pub epoch_deadline: UnsafeCell<u64>,
+
+    /// The record of the most recent frame of wasm compiled to capture core
+    /// dumps, or null if there isn't any.
+    ///
+    /// For more information see `CoreDumpFrameOffsets` in `wasmtime-environ`.
+    pub last_coredump_frame: UnsafeCell<*const u8>,
 }
 
 // The `VMRuntimeLimits` type is a pod-type with no destructor, and we don't
 // access any fields from other threads, so add in these trait impls which are
-// otherwise not available due to the `fuel_consumed` and `epoch_deadline`
-// variables in `VMRuntimeLimits`.
+// otherwise not available due to the `fuel_consumed`, `epoch_deadline` and
+// `last_coredump_frame` variables in `VMRuntimeLimits`.
 unsafe impl Send for VMRuntimeLimits {}
 unsafe impl Sync for VMRuntimeLimits {}
 
@@ -694,6 +749,7 @@ impl Default for VMRuntimeLimits {
             stack_limit: UnsafeCell::new(usize::max_value()),
             fuel_consumed: UnsafeCell::new(0),
             epoch_deadline: UnsafeCell::new(0),
+            last_coredump_frame: UnsafeCell::new(ptr::null()),
         }
     }
 }
@@ -721,6 +777,10 @@ mod test_vmruntime_limits {
             offset_of!(VMRuntimeLimits, epoch_deadline),
             usize::from(offsets.vmruntime_limits_epoch_deadline())
         );
+        assert_eq!(
+            offset_of!(VMRuntimeLimits, last_coredump_frame),
+            usize::from(offsets.vmruntime_limits_last_coredump_frame())
+        );
     }
 }
 
@@ -863,3 +923,34 @@ pub type VMTrampoline = unsafe extern "C" fn(
     *const VMFunctionBody, // function we're actually calling
     *mut ValRaw,           // space for arguments and return values
 );
+
+/// A trampoline which the host calls to enter wasm with the callee's arguments
+/// passed natively.
+///
+/// It takes the same arguments as the callee, with the callee's code pointer
+/// inserted after its two `VMContext` arguments, and returns its results. Its
+/// function type depends on the callee's signature, so it's transmuted to
+/// that type before being called.
+#[derive(Copy, Clone, Debug)]
+pub struct VMNativeTrampoline(NonNull<VMFunctionBody>);
+
+// The trampoline is code which is never mutated.
+unsafe impl Send for VMNativeTrampoline {}
+unsafe impl Sync for VMNativeTrampoline {}
+
+impl VMNativeTrampoline {
+    /// Creates a trampoline from the start of its code.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to code compiled as a native trampoline which lives
+    /// for as long as the returned value is used.
+    pub unsafe fn new(ptr: NonNull<VMFunctionBody>) -> Self {
+        Self(ptr)
+    }
+
+    /// Returns the start of the trampoline's code.
+    pub fn as_ptr(&self) -> *const VMFunctionBody {
+        self.0.as_ptr()
+    }
+}
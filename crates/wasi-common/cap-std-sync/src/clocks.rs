@@ -1,6 +1,7 @@
 use cap_std::time::{Duration, Instant, SystemTime};
 use cap_std::{ambient_authority, AmbientAuthority};
 use cap_time_ext::{MonotonicClockExt, SystemClockExt};
+use std::sync::atomic::{AtomicU32, Ordering};
 use wasi_common::clocks::{WasiClocks, WasiMonotonicClock, WasiSystemClock};
 
 pub struct SystemClock(cap_std::time::SystemClock);
@@ -45,3 +46,61 @@ pub fn clocks_ctx() -> WasiClocks {
         creation_time,
     }
 }
+
+/// How far the deterministic clocks advance each time they're read.
+const DETERMINISTIC_TICK: Duration = Duration::from_millis(1);
+
+/// A clock which starts at a fixed time and advances by `DETERMINISTIC_TICK`
+/// every time it's read, so that guests waiting for time to pass still make
+/// progress.
+struct DeterministicClock {
+    ticks: AtomicU32,
+}
+
+impl DeterministicClock {
+    fn new() -> Self {
+        DeterministicClock {
+            ticks: AtomicU32::new(0),
+        }
+    }
+    fn elapsed(&self) -> Duration {
+        DETERMINISTIC_TICK * self.ticks.fetch_add(1, Ordering::Relaxed)
+    }
+}
+
+struct DeterministicSystemClock(DeterministicClock);
+impl WasiSystemClock for DeterministicSystemClock {
+    fn resolution(&self) -> Duration {
+        DETERMINISTIC_TICK
+    }
+    fn now(&self, _precision: Duration) -> SystemTime {
+        SystemTime::from_std(std::time::SystemTime::UNIX_EPOCH + self.0.elapsed())
+    }
+}
+
+struct DeterministicMonotonicClock {
+    start: Instant,
+    clock: DeterministicClock,
+}
+impl WasiMonotonicClock for DeterministicMonotonicClock {
+    fn resolution(&self) -> Duration {
+        DETERMINISTIC_TICK
+    }
+    fn now(&self, _precision: Duration) -> Instant {
+        self.start + self.clock.elapsed()
+    }
+}
+
+/// Clocks whose readings don't depend on the host: the system clock starts at
+/// the unix epoch and both clocks advance by a fixed amount on every read.
+pub fn deterministic_clocks_ctx() -> WasiClocks {
+    let creation_time = cap_std::time::MonotonicClock::new(ambient_authority()).now();
+    WasiClocks {
+        system: Box::new(DeterministicSystemClock(DeterministicClock::new())),
+        monotonic: Box::new(DeterministicMonotonicClock {
+            start: creation_time,
+            clock: DeterministicClock::new(),
+        }),
+        creation_time,
+    }
+}
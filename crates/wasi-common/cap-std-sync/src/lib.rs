@@ -42,14 +42,21 @@ pub mod stdio;
 
 pub use cap_std::ambient_authority;
 pub use cap_std::fs::Dir;
-pub use cap_std::net::TcpListener;
-pub use clocks::clocks_ctx;
+pub use cap_std::net::{TcpListener, TcpStream};
+pub use clocks::{clocks_ctx, deterministic_clocks_ctx};
 pub use sched::sched_ctx;
 
+use crate::clocks::{MonotonicClock, SystemClock};
 use crate::net::Socket;
-use cap_rand::RngCore;
+use cap_rand::{RngCore, SeedableRng};
+use cap_std::time::Duration;
+use std::any::Any;
+use std::io::{Read, Write};
 use std::path::Path;
-use wasi_common::{file::FileCaps, table::Table, Error, WasiCtx, WasiFile};
+use wasi_common::clocks::{CoarseMonotonicClock, CoarseSystemClock};
+use wasi_common::pipe::{ReadPipe, WritePipe};
+use wasi_common::{dir::DirCaps, file::FileCaps, table::Table, Error, WasiCtx, WasiDir, WasiFile};
+use wasi_common::{WasiMonotonicClock, WasiSystemClock};
 
 pub struct WasiCtxBuilder(WasiCtx);
 
@@ -106,6 +113,18 @@ impl WasiCtxBuilder {
         self.0.set_stderr(f);
         self
     }
+    /// Makes the guest's stdin read from `r`.
+    pub fn stdin_reader(self, r: impl Read + Any + Send + Sync) -> Self {
+        self.stdin(Box::new(ReadPipe::new(r)))
+    }
+    /// Makes the guest's stdout write to `w`.
+    pub fn stdout_writer(self, w: impl Write + Any + Send + Sync) -> Self {
+        self.stdout(Box::new(WritePipe::new(w)))
+    }
+    /// Makes the guest's stderr write to `w`.
+    pub fn stderr_writer(self, w: impl Write + Any + Send + Sync) -> Self {
+        self.stderr(Box::new(WritePipe::new(w)))
+    }
     pub fn inherit_stdin(self) -> Self {
         self.stdin(Box::new(crate::stdio::stdin()))
     }
@@ -123,18 +142,92 @@ impl WasiCtxBuilder {
         self.0.push_preopened_dir(dir, guest_path)?;
         Ok(self)
     }
+    /// Preopens `dir` at `guest_path` like `preopened_dir`, but only with the
+    /// capabilities `caps` for it and its subdirectories, and `file_caps` for
+    /// the files opened in them. `DirCaps::read_only` and
+    /// `FileCaps::read_only` make the directory read-only.
+    pub fn preopened_dir_with_caps(
+        mut self,
+        dir: Dir,
+        guest_path: impl AsRef<Path>,
+        caps: DirCaps,
+        file_caps: FileCaps,
+    ) -> Result<Self, Error> {
+        let dir = Box::new(crate::dir::Dir::from_cap_std(dir));
+        self.0
+            .push_preopened_dir_with_caps(dir, guest_path, caps, file_caps)?;
+        Ok(self)
+    }
+    /// Preopens a directory implemented by the embedder, rather than one on
+    /// the host filesystem, at `guest_path`.
+    pub fn preopened_custom_dir(
+        mut self,
+        dir: Box<dyn WasiDir>,
+        guest_path: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        self.0.push_preopened_dir(dir, guest_path)?;
+        Ok(self)
+    }
+    /// Grants the guest `socket` as the file descriptor `fd`.
+    ///
+    /// This is the only way a guest gets a socket, other than accepting a
+    /// connection on a listener granted this way, since WASI has no way to
+    /// open one. Access is therefore granted socket by socket, rather than by
+    /// address or port.
     pub fn preopened_socket(mut self, fd: u32, socket: impl Into<Socket>) -> Result<Self, Error> {
         let socket: Socket = socket.into();
-        let file: Box<dyn WasiFile> = socket.into();
-
-        let caps = FileCaps::FDSTAT_SET_FLAGS
+        let mut caps = FileCaps::FDSTAT_SET_FLAGS
             | FileCaps::FILESTAT_GET
             | FileCaps::READ
             | FileCaps::POLL_READWRITE;
+        // Connected streams can be written to, unlike listeners which can
+        // only accept new connections.
+        if socket.is_stream() {
+            caps |= FileCaps::WRITE;
+        }
+        let file: Box<dyn WasiFile> = socket.into();
 
         self.0.insert_file(fd, file, caps);
         Ok(self)
     }
+    /// Replaces the clocks and random number generator with ones which
+    /// produce the same values on every run, with random numbers derived from
+    /// `seed`.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.0.random = deterministic_random_ctx(seed);
+        self.0.clocks = deterministic_clocks_ctx();
+        self
+    }
+    /// Replaces the system clock, which the guest reads the wall-clock time
+    /// from.
+    pub fn system_clock(mut self, clock: impl WasiSystemClock + 'static) -> Self {
+        self.0.clocks.system = Box::new(clock);
+        self
+    }
+    /// Replaces the monotonic clock, which the guest measures time with and
+    /// waits on in `poll_oneoff`.
+    pub fn monotonic_clock(mut self, clock: impl WasiMonotonicClock + 'static) -> Self {
+        self.0.clocks.creation_time = clock.now(clock.resolution());
+        self.0.clocks.monotonic = Box::new(clock);
+        self
+    }
+    /// Replaces the source of the guest's random numbers.
+    pub fn random(mut self, random: impl RngCore + Send + Sync + 'static) -> Self {
+        self.0.random = Box::new(random);
+        self
+    }
+    /// Makes the host clocks only advance in steps of `resolution`, so that
+    /// guests can't use them as high-resolution timers.
+    pub fn coarse_clocks(self, resolution: Duration) -> Self {
+        self.system_clock(CoarseSystemClock::new(
+            SystemClock::new(ambient_authority()),
+            resolution,
+        ))
+        .monotonic_clock(CoarseMonotonicClock::new(
+            MonotonicClock::new(ambient_authority()),
+            resolution,
+        ))
+    }
     pub fn build(self) -> WasiCtx {
         self.0
     }
@@ -143,3 +236,7 @@ impl WasiCtxBuilder {
 pub fn random_ctx() -> Box<dyn RngCore + Send + Sync> {
     Box::new(cap_rand::rngs::OsRng::default(ambient_authority()))
 }
+
+pub fn deterministic_random_ctx(seed: u64) -> Box<dyn RngCore + Send + Sync> {
+    Box::new(cap_rand::rngs::StdRng::seed_from_u64(seed))
+}
@@ -17,7 +17,7 @@ use system_interface::fs::GetSetFdFlags;
 use system_interface::io::IsReadWrite;
 use system_interface::io::ReadReady;
 use wasi_common::{
-    file::{FdFlags, FileType, WasiFile},
+    file::{FdFlags, FileType, RiFlags, RoFlags, SdFlags, SiFlags, WasiFile},
     Error, ErrorExt,
 };
 
@@ -30,6 +30,18 @@ pub enum Socket {
     UnixListener(cap_std::os::unix::net::UnixListener),
 }
 
+impl Socket {
+    /// Returns whether this is a connected stream, rather than a listener.
+    pub fn is_stream(&self) -> bool {
+        match self {
+            Socket::TcpStream(_) => true,
+            #[cfg(unix)]
+            Socket::UnixStream(_) => true,
+            _ => false,
+        }
+    }
+}
+
 impl From<cap_std::net::TcpListener> for Socket {
     fn from(listener: cap_std::net::TcpListener) -> Self {
         Self::TcpListener(listener)
@@ -225,6 +237,73 @@ macro_rules! wasi_stream_write_impl {
                 let n = self.0.peek(buf)?;
                 Ok(n.try_into()?)
             }
+            async fn sock_recv<'a>(
+                &mut self,
+                ri_data: &mut [io::IoSliceMut<'a>],
+                ri_flags: RiFlags,
+            ) -> Result<(u64, RoFlags), Error> {
+                use std::io::Read;
+                if ri_flags.contains(RiFlags::RECV_PEEK) {
+                    // Peeking only looks at the front of the socket's buffer,
+                    // so only the first non-empty buffer is filled.
+                    let n = match ri_data.iter_mut().find(|buf| !buf.is_empty()) {
+                        Some(buf) => self.0.peek(buf)?,
+                        None => 0,
+                    };
+                    return Ok((n.try_into()?, RoFlags::empty()));
+                }
+                let mut socket = self.as_socketlike_view::<$std_ty>();
+                let n = if ri_flags.contains(RiFlags::RECV_WAITALL) {
+                    // A non-blocking socket may run out of data before the
+                    // buffers are full, which only fails if nothing was read,
+                    // as the data which was read can't be put back.
+                    let mut n = 0;
+                    'bufs: for buf in ri_data.iter_mut() {
+                        let mut filled = 0;
+                        while filled < buf.len() {
+                            match Read::read(&mut *socket, &mut buf[filled..]) {
+                                Ok(0) => break 'bufs,
+                                Ok(read) => filled += read,
+                                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                                Err(e)
+                                    if e.kind() == io::ErrorKind::WouldBlock && n + filled > 0 =>
+                                {
+                                    n += filled;
+                                    break 'bufs;
+                                }
+                                Err(e) => return Err(e.into()),
+                            }
+                        }
+                        n += filled;
+                    }
+                    n
+                } else {
+                    Read::read_vectored(&mut *socket, ri_data)?
+                };
+                Ok((n.try_into()?, RoFlags::empty()))
+            }
+            async fn sock_send<'a>(
+                &mut self,
+                si_data: &[io::IoSlice<'a>],
+                _si_flags: SiFlags,
+            ) -> Result<u64, Error> {
+                use std::io::Write;
+                let n = Write::write_vectored(&mut *self.as_socketlike_view::<$std_ty>(), si_data)?;
+                Ok(n.try_into()?)
+            }
+            async fn sock_shutdown(&mut self, how: SdFlags) -> Result<(), Error> {
+                let how = if how == SdFlags::RD | SdFlags::WR {
+                    std::net::Shutdown::Both
+                } else if how == SdFlags::RD {
+                    std::net::Shutdown::Read
+                } else if how == SdFlags::WR {
+                    std::net::Shutdown::Write
+                } else {
+                    return Err(Error::invalid_argument());
+                };
+                self.0.shutdown(how)?;
+                Ok(())
+            }
             async fn num_ready_bytes(&self) -> Result<u64, Error> {
                 let val = self.as_socketlike_view::<$std_ty>().num_ready_bytes()?;
                 Ok(val)
@@ -20,3 +20,108 @@ pub struct WasiClocks {
     pub monotonic: Box<dyn WasiMonotonicClock>,
     pub creation_time: cap_std::time::Instant,
 }
+
+/// Rounds `duration` down to a multiple of `resolution`.
+fn round_down(duration: Duration, resolution: Duration) -> Duration {
+    let resolution = resolution.as_nanos();
+    if resolution == 0 {
+        return duration;
+    }
+    let nanos = duration.as_nanos();
+    Duration::from_nanos((nanos - nanos % resolution) as u64)
+}
+
+/// A system clock which only advances in steps of a fixed resolution, to
+/// keep guests from using it as a high-resolution timer.
+pub struct CoarseSystemClock<C> {
+    clock: C,
+    resolution: Duration,
+}
+
+impl<C: WasiSystemClock> CoarseSystemClock<C> {
+    pub fn new(clock: C, resolution: Duration) -> Self {
+        CoarseSystemClock { clock, resolution }
+    }
+}
+
+impl<C: WasiSystemClock> WasiSystemClock for CoarseSystemClock<C> {
+    fn resolution(&self) -> Duration {
+        self.clock.resolution().max(self.resolution)
+    }
+    fn now(&self, precision: Duration) -> SystemTime {
+        let epoch = SystemTime::from_std(std::time::SystemTime::UNIX_EPOCH);
+        let since_epoch = self
+            .clock
+            .now(precision)
+            .duration_since(epoch)
+            .unwrap_or_default();
+        epoch + round_down(since_epoch, self.resolution)
+    }
+}
+
+/// A monotonic clock which only advances in steps of a fixed resolution,
+/// counted from when it was created, to keep guests from using it as a
+/// high-resolution timer.
+pub struct CoarseMonotonicClock<C> {
+    clock: C,
+    start: Instant,
+    resolution: Duration,
+}
+
+impl<C: WasiMonotonicClock> CoarseMonotonicClock<C> {
+    pub fn new(clock: C, resolution: Duration) -> Self {
+        let start = clock.now(resolution);
+        CoarseMonotonicClock {
+            clock,
+            start,
+            resolution,
+        }
+    }
+}
+
+impl<C: WasiMonotonicClock> WasiMonotonicClock for CoarseMonotonicClock<C> {
+    fn resolution(&self) -> Duration {
+        self.clock.resolution().max(self.resolution)
+    }
+    fn now(&self, precision: Duration) -> Instant {
+        let elapsed = self.clock.now(precision).duration_since(self.start);
+        self.start + round_down(elapsed, self.resolution)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// A system clock which reads whatever time it's been set to.
+    struct VirtualClock(Mutex<SystemTime>);
+
+    impl WasiSystemClock for VirtualClock {
+        fn resolution(&self) -> Duration {
+            Duration::from_nanos(1)
+        }
+        fn now(&self, _precision: Duration) -> SystemTime {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    #[test]
+    fn coarse_system_clock() {
+        let epoch = SystemTime::from_std(std::time::SystemTime::UNIX_EPOCH);
+        let clock = CoarseSystemClock::new(
+            VirtualClock(Mutex::new(epoch + Duration::from_micros(1500))),
+            Duration::from_millis(1),
+        );
+        assert_eq!(clock.resolution(), Duration::from_millis(1));
+        assert_eq!(
+            clock.now(Duration::from_nanos(1)),
+            epoch + Duration::from_millis(1)
+        );
+        *clock.clock.0.lock().unwrap() = epoch + Duration::from_micros(2999);
+        assert_eq!(
+            clock.now(Duration::from_nanos(1)),
+            epoch + Duration::from_millis(2)
+        );
+    }
+}
@@ -6,6 +6,7 @@ use crate::string_array::{StringArray, StringArrayError};
 use crate::table::Table;
 use crate::Error;
 use cap_rand::RngCore;
+use std::mem;
 use std::path::{Path, PathBuf};
 
 pub struct WasiCtx {
@@ -70,6 +71,83 @@ impl WasiCtx {
         Ok(())
     }
 
+    /// Replaces the guest's arguments with `args`, which it sees from its
+    /// next call of `args_get`.
+    pub fn set_args(&mut self, args: &[String]) -> Result<(), StringArrayError> {
+        self.args = string_array(args.iter().cloned())?;
+        Ok(())
+    }
+
+    /// Replaces the guest's environment variables with `env`, which it sees
+    /// from its next call of `environ_get`.
+    pub fn set_envs(&mut self, env: &[(String, String)]) -> Result<(), StringArrayError> {
+        self.env = string_array(env.iter().map(|(k, v)| format!("{}={}", k, v)))?;
+        Ok(())
+    }
+
+    /// Replaces the guest's current working directory, the directory
+    /// preopened at `.` which wasi-libc resolves relative paths in, with
+    /// `dir`, preopening it there if nothing was.
+    ///
+    /// A directory which replaces another keeps its fd, so guests which have
+    /// already looked up their preopens see it, but guests built with
+    /// wasi-libc only look for preopens when they start, so one started
+    /// before a directory was added won't see it. Files and directories the
+    /// guest already opened in the previous directory stay open.
+    pub fn set_cwd(&mut self, dir: Box<dyn WasiDir>) -> Result<(), Error> {
+        self.replace_cwd(None, Some(cwd_entry(dir)))?;
+        Ok(())
+    }
+
+    /// Applies `overrides` to this context, such as for a single call of an
+    /// instance which serves many requests, returning the overrides which
+    /// restore what they replaced.
+    ///
+    /// Nothing is replaced if this fails.
+    pub fn apply_overrides(&mut self, overrides: WasiOverrides) -> Result<WasiOverrides, Error> {
+        let mut previous = WasiOverrides::new();
+        if let Some((fd, cwd)) = overrides.cwd {
+            previous.cwd = Some(self.replace_cwd(fd, cwd)?);
+        }
+        if let Some(args) = overrides.args {
+            previous.args = Some(mem::replace(&mut self.args, args));
+        }
+        if let Some(env) = overrides.env {
+            previous.env = Some(mem::replace(&mut self.env, env));
+        }
+        Ok(previous)
+    }
+
+    /// Replaces the directory preopened at `.` with `cwd`, removing it if
+    /// that's `None`, and returns the previous one along with the fd which
+    /// the directory is or was preopened as. A directory added where there
+    /// wasn't one is preopened as `fd` if that's given and free, so that
+    /// restoring one which was removed puts it back where it was.
+    fn replace_cwd(
+        &mut self,
+        fd: Option<u32>,
+        cwd: Option<DirEntry>,
+    ) -> Result<(Option<u32>, Option<DirEntry>), Error> {
+        let table = self.table();
+        let existing = table.keys().find(|fd| {
+            table.get::<DirEntry>(*fd).map_or(false, |dir| {
+                dir.preopen_path().as_deref() == Some(Path::new("."))
+            })
+        });
+        let previous = existing
+            .and_then(|fd| table.delete(fd))
+            .map(|dir| *dir.downcast::<DirEntry>().unwrap());
+        let fd = match (existing.or(fd), cwd) {
+            (Some(fd), Some(cwd)) if existing.is_some() || !table.contains_key(fd) => {
+                table.insert_at(fd, Box::new(cwd));
+                Some(fd)
+            }
+            (_, Some(cwd)) => Some(table.push(Box::new(cwd))?),
+            (fd, None) => fd,
+        };
+        Ok((fd, previous))
+    }
+
     pub fn set_stdin(&mut self, mut f: Box<dyn WasiFile>) {
         let rights = Self::stdio_rights(&mut *f);
         self.insert_file(0, f, rights);
@@ -103,8 +181,19 @@ impl WasiCtx {
         dir: Box<dyn WasiDir>,
         path: impl AsRef<Path>,
     ) -> Result<(), Error> {
-        let caps = DirCaps::all();
-        let file_caps = FileCaps::all();
+        self.push_preopened_dir_with_caps(dir, path, DirCaps::all(), FileCaps::all())
+    }
+
+    /// Preopens `dir` at `path`, with only the capabilities `caps` for the
+    /// directory and its subdirectories, and `file_caps` for the files
+    /// opened in them.
+    pub fn push_preopened_dir_with_caps(
+        &mut self,
+        dir: Box<dyn WasiDir>,
+        path: impl AsRef<Path>,
+        caps: DirCaps,
+        file_caps: FileCaps,
+    ) -> Result<(), Error> {
         self.table().push(Box::new(DirEntry::new(
             caps,
             file_caps,
@@ -114,3 +203,65 @@ impl WasiCtx {
         Ok(())
     }
 }
+
+/// Arguments, environment variables and a current working directory to give
+/// the guest in place of those of its [`WasiCtx`], applied with
+/// [`WasiCtx::apply_overrides`].
+///
+/// This lets an instance kept around to serve many requests, such as one
+/// from a pool, see each request's parameters without its context being
+/// rebuilt, which would mean instantiating it again. Applying them returns
+/// the overrides which undo them, so a call can be made with a request's
+/// parameters by applying its overrides before the call and the returned
+/// ones after it.
+#[derive(Default)]
+pub struct WasiOverrides {
+    args: Option<StringArray>,
+    env: Option<StringArray>,
+    cwd: Option<(Option<u32>, Option<DirEntry>)>,
+}
+
+impl WasiOverrides {
+    /// Creates overrides which don't replace anything.
+    pub fn new() -> Self {
+        WasiOverrides::default()
+    }
+
+    /// Replaces the guest's arguments with `args`.
+    pub fn args(mut self, args: &[String]) -> Result<Self, StringArrayError> {
+        self.args = Some(string_array(args.iter().cloned())?);
+        Ok(self)
+    }
+
+    /// Replaces the guest's environment variables with `env`.
+    pub fn envs(mut self, env: &[(String, String)]) -> Result<Self, StringArrayError> {
+        self.env = Some(string_array(
+            env.iter().map(|(k, v)| format!("{}={}", k, v)),
+        )?);
+        Ok(self)
+    }
+
+    /// Replaces the guest's current working directory with `dir`, as
+    /// [`WasiCtx::set_cwd`] does.
+    pub fn cwd(mut self, dir: Box<dyn WasiDir>) -> Self {
+        self.cwd = Some((None, Some(cwd_entry(dir))));
+        self
+    }
+}
+
+fn string_array(elems: impl Iterator<Item = String>) -> Result<StringArray, StringArrayError> {
+    let mut array = StringArray::new();
+    for elem in elems {
+        array.push(elem)?;
+    }
+    Ok(array)
+}
+
+fn cwd_entry(dir: Box<dyn WasiDir>) -> DirEntry {
+    DirEntry::new(
+        DirCaps::all(),
+        FileCaps::all(),
+        Some(PathBuf::from(".")),
+        dir,
+    )
+}
@@ -57,6 +57,12 @@ pub enum ErrorKind {
     /// Errno::Nametoolong: Filename too long
     #[error("Nametoolong: Filename too long")]
     Nametoolong,
+    /// Errno::Isdir: Is a directory
+    #[error("Isdir: Is a directory")]
+    Isdir,
+    /// Errno::Notempty: Directory not empty
+    #[error("Notempty: Directory not empty")]
+    Notempty,
     /// Errno::Notdir: Not a directory or a symbolic link to a directory.
     #[error("Notdir: Not a directory or a symbolic link to a directory")]
     Notdir,
@@ -66,6 +72,9 @@ pub enum ErrorKind {
     /// Errno::Overflow: Value too large to be stored in data type.
     #[error("Overflow: Value too large to be stored in data type")]
     Overflow,
+    /// Errno::Pipe: Broken pipe
+    #[error("Pipe: Broken pipe")]
+    Pipe,
     /// Errno::Range: Result too large
     #[error("Range: Result too large")]
     Range,
@@ -88,8 +97,11 @@ pub trait ErrorExt {
     fn io() -> Self;
     fn name_too_long() -> Self;
     fn not_dir() -> Self;
+    fn is_dir() -> Self;
+    fn not_empty() -> Self;
     fn not_supported() -> Self;
     fn overflow() -> Self;
+    fn broken_pipe() -> Self;
     fn range() -> Self;
     fn seek_pipe() -> Self;
     fn not_capable() -> Self;
@@ -126,12 +138,21 @@ impl ErrorExt for Error {
     fn not_dir() -> Self {
         ErrorKind::Notdir.into()
     }
+    fn is_dir() -> Self {
+        ErrorKind::Isdir.into()
+    }
+    fn not_empty() -> Self {
+        ErrorKind::Notempty.into()
+    }
     fn not_supported() -> Self {
         ErrorKind::Notsup.into()
     }
     fn overflow() -> Self {
         ErrorKind::Overflow.into()
     }
+    fn broken_pipe() -> Self {
+        ErrorKind::Pipe.into()
+    }
     fn range() -> Self {
         ErrorKind::Range.into()
     }
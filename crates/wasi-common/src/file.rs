@@ -25,6 +25,26 @@ pub trait WasiFile: Send + Sync {
         Err(Error::badf())
     }
 
+    async fn sock_recv<'a>(
+        &mut self,
+        _ri_data: &mut [std::io::IoSliceMut<'a>],
+        _ri_flags: RiFlags,
+    ) -> Result<(u64, RoFlags), Error> {
+        Err(Error::badf())
+    }
+
+    async fn sock_send<'a>(
+        &mut self,
+        _si_data: &[std::io::IoSlice<'a>],
+        _si_flags: SiFlags,
+    ) -> Result<u64, Error> {
+        Err(Error::badf())
+    }
+
+    async fn sock_shutdown(&mut self, _how: SdFlags) -> Result<(), Error> {
+        Err(Error::badf())
+    }
+
     async fn datasync(&mut self) -> Result<(), Error> {
         Ok(())
     }
@@ -145,6 +165,31 @@ bitflags! {
     }
 }
 
+bitflags! {
+    pub struct RiFlags: u32 {
+        const RECV_PEEK    = 0b1;
+        const RECV_WAITALL = 0b10;
+    }
+}
+
+bitflags! {
+    pub struct RoFlags: u32 {
+        const RECV_DATA_TRUNCATED = 0b1;
+    }
+}
+
+bitflags! {
+    pub struct SiFlags: u32 {
+    }
+}
+
+bitflags! {
+    pub struct SdFlags: u32 {
+        const RD = 0b1;
+        const WR = 0b10;
+    }
+}
+
 bitflags! {
     pub struct OFlags: u32 {
         const CREATE    = 0b1;
@@ -247,6 +292,18 @@ bitflags! {
     }
 }
 
+impl FileCaps {
+    /// The capabilities which don't allow a file to be modified.
+    pub fn read_only() -> FileCaps {
+        FileCaps::READ
+            | FileCaps::SEEK
+            | FileCaps::TELL
+            | FileCaps::ADVISE
+            | FileCaps::FILESTAT_GET
+            | FileCaps::POLL_READWRITE
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct FdStat {
     pub filetype: FileType,
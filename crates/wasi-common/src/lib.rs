@@ -61,10 +61,11 @@ pub mod sched;
 pub mod snapshots;
 mod string_array;
 pub mod table;
+pub mod vfs;
 
 pub use cap_rand::RngCore;
 pub use clocks::{SystemTimeSpec, WasiClocks, WasiMonotonicClock, WasiSystemClock};
-pub use ctx::WasiCtx;
+pub use ctx::{WasiCtx, WasiOverrides};
 pub use dir::WasiDir;
 pub use error::{Context, Error, ErrorExt, ErrorKind};
 pub use file::WasiFile;
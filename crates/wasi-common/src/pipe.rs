@@ -9,12 +9,16 @@
 //! Some convenience constructors are included for common backing types like `Vec<u8>` and `String`,
 //! but the virtual pipes can be instantiated with any `Read` or `Write` type.
 //!
+//! [`bounded`] creates a pipe with a fixed capacity instead, whose two ends can be used by the
+//! host and the guest at the same time, to stream data between them while the guest runs.
+//!
 use crate::file::{FdFlags, FileType, WasiFile};
-use crate::Error;
+use crate::{Error, ErrorExt};
 use std::any::Any;
+use std::collections::VecDeque;
 use std::convert::TryInto;
 use std::io::{self, Read, Write};
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, RwLock};
 
 /// A virtual pipe read end.
 ///
@@ -182,6 +186,12 @@ impl WritePipe<io::Cursor<Vec<u8>>> {
     pub fn new_in_memory() -> Self {
         Self::new(io::Cursor::new(vec![]))
     }
+
+    /// Returns a copy of everything written to this pipe so far, which can be called while the
+    /// guest still holds the other end.
+    pub fn contents(&self) -> Vec<u8> {
+        self.borrow().get_ref().clone()
+    }
 }
 
 #[wiggle::async_trait]
@@ -200,3 +210,191 @@ impl<W: Write + Any + Send + Sync> WasiFile for WritePipe<W> {
         Ok(n.try_into()?)
     }
 }
+
+/// Creates a pipe which holds at most `capacity` bytes at a time.
+///
+/// Writes block while the pipe is full, until the reader has made room, and reads block while it
+/// is empty. Once the writer is dropped, reads return whatever is left and then end-of-file; once
+/// the reader is dropped, writes fail with a broken pipe error. Both ends can be given to a guest,
+/// as a `WasiFile`, or used by the host, through `Read` and `Write`:
+///
+/// ```no_run
+/// use std::io::Read;
+/// use wasi_common::{pipe, WasiCtx};
+/// # fn run(ctx: &mut WasiCtx) {
+/// let (stdout, mut output) = pipe::bounded(4096);
+/// ctx.set_stdout(Box::new(stdout));
+/// // Run the guest on another thread, and read its output while it runs:
+/// let mut line = [0; 80];
+/// let n = output.read(&mut line).unwrap();
+/// # }
+/// ```
+pub fn bounded(capacity: usize) -> (PipeWriter, PipeReader) {
+    assert!(capacity > 0, "a pipe needs room for at least one byte");
+    let shared = Arc::new(Shared {
+        state: Mutex::new(State {
+            buf: VecDeque::with_capacity(capacity),
+            reader_closed: false,
+            writer_closed: false,
+        }),
+        changed: Condvar::new(),
+        capacity,
+    });
+    (
+        PipeWriter {
+            shared: shared.clone(),
+        },
+        PipeReader { shared },
+    )
+}
+
+struct Shared {
+    state: Mutex<State>,
+    /// Notified whenever data is read or written, or either end is dropped.
+    changed: Condvar,
+    capacity: usize,
+}
+
+struct State {
+    buf: VecDeque<u8>,
+    reader_closed: bool,
+    writer_closed: bool,
+}
+
+impl Shared {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    fn wait<'a>(&self, state: MutexGuard<'a, State>) -> MutexGuard<'a, State> {
+        self.changed.wait(state).unwrap()
+    }
+}
+
+/// The read end of a pipe created by [`bounded`].
+pub struct PipeReader {
+    shared: Arc<Shared>,
+}
+
+impl PipeReader {
+    /// Returns how many bytes can be read without blocking.
+    pub fn available(&self) -> usize {
+        self.shared.lock().buf.len()
+    }
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Read::read_vectored(self, &mut [io::IoSliceMut::new(buf)])
+    }
+
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        if bufs.iter().all(|buf| buf.is_empty()) {
+            return Ok(0);
+        }
+        let mut state = self.shared.lock();
+        while state.buf.is_empty() && !state.writer_closed {
+            state = self.shared.wait(state);
+        }
+        let mut n = 0;
+        for buf in bufs {
+            let len = buf.len().min(state.buf.len());
+            for (dst, src) in buf.iter_mut().zip(state.buf.drain(..len)) {
+                *dst = src;
+            }
+            n += len;
+        }
+        self.shared.changed.notify_all();
+        Ok(n)
+    }
+}
+
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.shared.lock().reader_closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiFile for PipeReader {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn get_filetype(&mut self) -> Result<FileType, Error> {
+        Ok(FileType::Pipe)
+    }
+    async fn read_vectored<'a>(&mut self, bufs: &mut [io::IoSliceMut<'a>]) -> Result<u64, Error> {
+        let n = Read::read_vectored(self, bufs)?;
+        Ok(n.try_into()?)
+    }
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        Ok(self.available().try_into()?)
+    }
+}
+
+/// The write end of a pipe created by [`bounded`].
+pub struct PipeWriter {
+    shared: Arc<Shared>,
+}
+
+impl Write for PipeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Write::write_vectored(self, &[io::IoSlice::new(buf)])
+    }
+
+    /// Writes all of `bufs`, waiting for the reader to make room as needed.
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let mut state = self.shared.lock();
+        let mut n = 0;
+        for mut buf in bufs.iter().map(|buf| &buf[..]) {
+            while !buf.is_empty() {
+                if state.reader_closed {
+                    return Err(io::ErrorKind::BrokenPipe.into());
+                }
+                let room = self.shared.capacity - state.buf.len();
+                if room == 0 {
+                    state = self.shared.wait(state);
+                    continue;
+                }
+                let len = room.min(buf.len());
+                state.buf.extend(&buf[..len]);
+                buf = &buf[len..];
+                n += len;
+                self.shared.changed.notify_all();
+            }
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.shared.lock().writer_closed = true;
+        self.shared.changed.notify_all();
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiFile for PipeWriter {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+    async fn get_filetype(&mut self) -> Result<FileType, Error> {
+        Ok(FileType::Pipe)
+    }
+    async fn get_fdflags(&mut self) -> Result<FdFlags, Error> {
+        Ok(FdFlags::APPEND)
+    }
+    async fn write_vectored<'a>(&mut self, bufs: &[io::IoSlice<'a>]) -> Result<u64, Error> {
+        match Write::write_vectored(self, bufs) {
+            Ok(n) => Ok(n.try_into()?),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Err(Error::broken_pipe()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
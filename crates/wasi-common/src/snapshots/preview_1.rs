@@ -2,7 +2,7 @@ use crate::{
     dir::{DirCaps, DirEntry, DirEntryExt, DirFdStat, ReaddirCursor, ReaddirEntity, TableDirExt},
     file::{
         Advice, FdFlags, FdStat, FileCaps, FileEntry, FileEntryExt, FileType, Filestat, OFlags,
-        TableFileExt, WasiFile,
+        RiFlags, RoFlags, SdFlags, SiFlags, TableFileExt, WasiFile,
     },
     sched::{
         subscription::{RwEventFlags, SubscriptionResult},
@@ -78,9 +78,12 @@ impl From<ErrorKind> for types::Errno {
             ErrorKind::Inval => Errno::Inval,
             ErrorKind::Io => Errno::Io,
             ErrorKind::Nametoolong => Errno::Nametoolong,
+            ErrorKind::Isdir => Errno::Isdir,
+            ErrorKind::Notempty => Errno::Notempty,
             ErrorKind::Notdir => Errno::Notdir,
             ErrorKind::Notsup => Errno::Notsup,
             ErrorKind::Overflow => Errno::Overflow,
+            ErrorKind::Pipe => Errno::Pipe,
             ErrorKind::Range => Errno::Range,
             ErrorKind::Spipe => Errno::Spipe,
             ErrorKind::NotCapable => Errno::Notcapable,
@@ -98,6 +101,7 @@ impl From<wiggle::GuestError> for types::Errno {
             PtrOutOfBounds { .. } => Self::Fault,
             PtrNotAligned { .. } => Self::Inval,
             PtrBorrowed { .. } => Self::Fault,
+            PtrReadOnly { .. } => Self::Fault,
             InvalidUtf8 { .. } => Self::Ilseq,
             TryFromIntError { .. } => Self::Overflow,
             InFunc { err, .. } => types::Errno::from(*err),
@@ -838,6 +842,11 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
             if oflags.contains(OFlags::CREATE) {
                 required_caps = required_caps | DirCaps::CREATE_FILE;
             }
+            // Truncating modifies the file even if it's never written to,
+            // so it needs the capability to resize files.
+            if oflags.contains(OFlags::TRUNCATE) {
+                dir_entry.capable_of_file(FileCaps::FILESTAT_SET_SIZE)?;
+            }
 
             let file_caps = dir_entry.child_file_caps(FileCaps::from(&fs_rights_base));
             let dir = dir_entry.get_cap(required_caps)?;
@@ -1164,24 +1173,72 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
 
     async fn sock_recv<'a>(
         &mut self,
-        _fd: types::Fd,
-        _ri_data: &types::IovecArray<'a>,
-        _ri_flags: types::Riflags,
+        fd: types::Fd,
+        ri_data: &types::IovecArray<'a>,
+        ri_flags: types::Riflags,
     ) -> Result<(types::Size, types::Roflags), Error> {
-        Err(Error::trap("sock_recv unsupported"))
+        let f = self
+            .table()
+            .get_file_mut(u32::from(fd))?
+            .get_cap_mut(FileCaps::READ)?;
+
+        let mut guest_slices: Vec<wiggle::GuestSliceMut<u8>> = ri_data
+            .iter()
+            .map(|iov_ptr| {
+                let iov_ptr = iov_ptr?;
+                let iov: types::Iovec = iov_ptr.read()?;
+                Ok(iov.buf.as_array(iov.buf_len).as_slice_mut()?)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let mut ioslices: Vec<IoSliceMut> = guest_slices
+            .iter_mut()
+            .map(|s| IoSliceMut::new(&mut *s))
+            .collect();
+
+        let (bytes_read, ro_flags) = f.sock_recv(&mut ioslices, RiFlags::from(ri_flags)).await?;
+        Ok((
+            types::Size::try_from(bytes_read)?,
+            types::Roflags::from(ro_flags),
+        ))
     }
 
     async fn sock_send<'a>(
         &mut self,
-        _fd: types::Fd,
-        _si_data: &types::CiovecArray<'a>,
+        fd: types::Fd,
+        si_data: &types::CiovecArray<'a>,
         _si_flags: types::Siflags,
     ) -> Result<types::Size, Error> {
-        Err(Error::trap("sock_send unsupported"))
+        let f = self
+            .table()
+            .get_file_mut(u32::from(fd))?
+            .get_cap_mut(FileCaps::WRITE)?;
+
+        let guest_slices: Vec<wiggle::GuestSlice<u8>> = si_data
+            .iter()
+            .map(|iov_ptr| {
+                let iov_ptr = iov_ptr?;
+                let iov: types::Ciovec = iov_ptr.read()?;
+                Ok(iov.buf.as_array(iov.buf_len).as_slice()?)
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let ioslices: Vec<IoSlice> = guest_slices
+            .iter()
+            .map(|s| IoSlice::new(s.deref()))
+            .collect();
+        let bytes_written = f.sock_send(&ioslices, SiFlags::empty()).await?;
+
+        Ok(types::Size::try_from(bytes_written)?)
     }
 
-    async fn sock_shutdown(&mut self, _fd: types::Fd, _how: types::Sdflags) -> Result<(), Error> {
-        Err(Error::trap("sock_shutdown unsupported"))
+    async fn sock_shutdown(&mut self, fd: types::Fd, how: types::Sdflags) -> Result<(), Error> {
+        let f = self
+            .table()
+            .get_file_mut(u32::from(fd))?
+            .get_cap_mut(FileCaps::empty())?;
+
+        f.sock_shutdown(SdFlags::from(how)).await
     }
 }
 
@@ -1476,6 +1533,9 @@ convert_flags_bidirectional!(
     RSYNC,
     SYNC
 );
+convert_flags!(types::Riflags, RiFlags, RECV_PEEK, RECV_WAITALL);
+convert_flags!(RoFlags, types::Roflags, RECV_DATA_TRUNCATED);
+convert_flags!(types::Sdflags, SdFlags, RD, WR);
 
 impl From<&types::Oflags> for OFlags {
     fn from(oflags: &types::Oflags) -> OFlags {
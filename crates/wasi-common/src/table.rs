@@ -84,6 +84,11 @@ impl Table {
         }
     }
 
+    /// Iterate over the indices of the resources in the table, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = u32> + '_ {
+        self.map.keys().copied()
+    }
+
     /// Remove a resource at a given index from the table. Returns the resource
     /// if it was present.
     pub fn delete(&mut self, key: u32) -> Option<Box<dyn Any + Send + Sync>> {
@@ -0,0 +1,1345 @@
+//! An in-memory virtual filesystem.
+//!
+//! [`MemDir`] is a directory tree held entirely in memory, which can be
+//! preopened to give a guest a scratch directory, or configuration files,
+//! without any access to the host filesystem. [`Overlay`] layers a `MemDir`
+//! over another directory, such as a real preopened one: the guest sees that
+//! directory's contents, but everything it changes is copied into memory
+//! first, and the directory underneath is never modified.
+//!
+//! Neither supports symbolic links, or keeps track of timestamps. The size
+//! of the files in a `MemDir` can be limited with [`MemDir::with_limits`], so
+//! that a guest can't use up the host's memory by writing to them.
+//!
+//! ```no_run
+//! use wasi_common::{vfs::MemDir, WasiCtx};
+//! # fn preopen(ctx: &mut WasiCtx) -> Result<(), wasi_common::Error> {
+//! let etc = MemDir::new();
+//! etc.add_file("app/config.toml", "verbose = true")?;
+//! ctx.push_preopened_dir(Box::new(etc), "/etc")?;
+//! ctx.push_preopened_dir(Box::new(MemDir::new()), "/tmp")?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::dir::{ReaddirCursor, ReaddirEntity, WasiDir};
+use crate::file::{FdFlags, FileType, Filestat, OFlags, WasiFile};
+use crate::{Error, ErrorExt};
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, IoSlice, IoSliceMut, SeekFrom};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// A directory held in memory.
+///
+/// Clones of a `MemDir` refer to the same directory, so the host can keep
+/// one to look at what the guest has written.
+#[derive(Clone, Default)]
+pub struct MemDir {
+    entries: Arc<RwLock<BTreeMap<String, Node>>>,
+    /// The limits shared by every directory in the tree this one was created
+    /// in.
+    limits: Arc<Limits>,
+    /// Whether this directory hides the lower directory of an [`Overlay`]
+    /// with the same path, rather than merging with it, which is the case for
+    /// directories created in place of one which was removed.
+    opaque: bool,
+}
+
+/// The limits on the size of the files in a tree of [`MemDir`]s.
+struct Limits {
+    max_file_size: u64,
+    max_total_size: u64,
+    /// The total size of the files in the tree, including those which have
+    /// been removed but are still open.
+    total_size: AtomicU64,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_file_size: u64::MAX,
+            max_total_size: u64::MAX,
+            total_size: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Limits {
+    /// Accounts for a file growing by `by` bytes to `size` bytes, failing if
+    /// that's more than the limits allow.
+    fn grow(&self, size: u64, by: u64) -> Result<(), Error> {
+        if size > self.max_file_size {
+            return Err(Error::too_big().context("file size limit exceeded"));
+        }
+        self.total_size
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |total| {
+                total
+                    .checked_add(by)
+                    .filter(|total| *total <= self.max_total_size)
+            })
+            .map_err(|_| Error::too_big().context("total file size limit exceeded"))?;
+        Ok(())
+    }
+
+    fn shrink(&self, by: u64) {
+        self.total_size.fetch_sub(by, Ordering::Relaxed);
+    }
+}
+
+/// The contents of a file, which count towards the limits of the tree it was
+/// created in for as long as they're around.
+struct FileData {
+    bytes: Vec<u8>,
+    limits: Arc<Limits>,
+}
+
+impl FileData {
+    fn new(limits: &Arc<Limits>, bytes: Vec<u8>) -> Result<Arc<RwLock<FileData>>, Error> {
+        let size = bytes.len() as u64;
+        limits.grow(size, size)?;
+        Ok(Arc::new(RwLock::new(FileData {
+            bytes,
+            limits: limits.clone(),
+        })))
+    }
+
+    fn len(&self) -> u64 {
+        self.bytes.len() as u64
+    }
+
+    fn resize(&mut self, size: u64) -> Result<(), Error> {
+        let new_len = usize::try_from(size).map_err(|_| Error::too_big())?;
+        let len = self.len();
+        if size > len {
+            self.limits.grow(size, size - len)?;
+        } else {
+            self.limits.shrink(len - size);
+        }
+        self.bytes.resize(new_len, 0);
+        Ok(())
+    }
+}
+
+impl Drop for FileData {
+    fn drop(&mut self) {
+        self.limits.shrink(self.len());
+    }
+}
+
+#[derive(Clone)]
+enum Node {
+    File(Arc<RwLock<FileData>>),
+    Dir(MemDir),
+    /// Hides the entry with the same name in the lower directory of an
+    /// [`Overlay`]. Only ever found in an overlay's upper directory, and
+    /// treated as if there were no entry at all by `MemDir` itself.
+    Whiteout,
+}
+
+impl Node {
+    fn filestat(&self) -> Filestat {
+        let (filetype, inode, size) = match self {
+            Node::File(data) => (
+                FileType::RegularFile,
+                Arc::as_ptr(data) as usize,
+                data.read().unwrap().len(),
+            ),
+            Node::Dir(dir) => (FileType::Directory, dir.inode(), 0),
+            Node::Whiteout => unreachable!(),
+        };
+        Filestat {
+            device_id: 0,
+            inode: inode as u64,
+            filetype,
+            nlink: 1,
+            size,
+            atim: None,
+            mtim: None,
+            ctim: None,
+        }
+    }
+
+    fn filetype(&self) -> FileType {
+        match self {
+            Node::File(_) => FileType::RegularFile,
+            Node::Dir(_) => FileType::Directory,
+            Node::Whiteout => unreachable!(),
+        }
+    }
+}
+
+/// Splits `path` into its components, resolving `.` and `..`, which can't
+/// lead outside of the directory it's relative to.
+fn components(path: &str) -> Result<Vec<&str>, Error> {
+    if path.starts_with('/') {
+        return Err(Error::not_capable().context("absolute path"));
+    }
+    let mut components = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => {
+                if components.pop().is_none() {
+                    return Err(Error::not_capable().context("path escapes its directory"));
+                }
+            }
+            component => components.push(component),
+        }
+    }
+    Ok(components)
+}
+
+/// Splits `path` into the path of its parent directory and its name.
+fn split_parent(path: &str) -> Result<(Vec<&str>, &str), Error> {
+    let mut components = components(path)?;
+    match components.pop() {
+        Some(name) => Ok((components, name)),
+        None => Err(Error::invalid_argument().context("path has no file name")),
+    }
+}
+
+fn readdir_entities(
+    inode: usize,
+    entries: impl IntoIterator<Item = (String, FileType, u64)>,
+    cursor: ReaddirCursor,
+) -> Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send> {
+    let dots = [".", ".."]
+        .into_iter()
+        .map(move |name| (name.to_string(), FileType::Directory, inode as u64));
+    let entities = dots
+        .chain(entries)
+        .enumerate()
+        .map(|(ix, (name, filetype, inode))| {
+            Ok(ReaddirEntity {
+                next: ReaddirCursor::from(ix as u64 + 1),
+                inode,
+                name,
+                filetype,
+            })
+        })
+        .skip(u64::from(cursor) as usize)
+        .collect::<Vec<_>>();
+    Box::new(entities.into_iter())
+}
+
+impl MemDir {
+    /// Creates an empty directory.
+    pub fn new() -> MemDir {
+        MemDir::default()
+    }
+
+    /// Creates an empty directory in which no file can be larger than
+    /// `max_file_size` bytes, and the files in it and its subdirectories
+    /// can't be larger than `max_total_size` bytes altogether.
+    ///
+    /// Writing to a file, or resizing it, fails with `E2BIG` instead of going
+    /// beyond the limits. Files which are removed while they're still open
+    /// count towards the total until they're closed.
+    pub fn with_limits(max_file_size: u64, max_total_size: u64) -> MemDir {
+        MemDir {
+            limits: Arc::new(Limits {
+                max_file_size,
+                max_total_size,
+                ..Limits::default()
+            }),
+            ..MemDir::default()
+        }
+    }
+
+    /// Creates an empty directory to go in this one, which shares its limits.
+    fn new_subdir(&self, opaque: bool) -> MemDir {
+        MemDir {
+            entries: Default::default(),
+            limits: self.limits.clone(),
+            opaque,
+        }
+    }
+
+    /// Creates a file at `path` with `contents`, along with any of its parent
+    /// directories which don't exist yet, replacing any file already there.
+    pub fn add_file(&self, path: &str, contents: impl Into<Vec<u8>>) -> Result<(), Error> {
+        let (parent, name) = split_parent(path)?;
+        let dir = self.create_dirs(&parent)?;
+        let mut entries = dir.entries.write().unwrap();
+        if let Some(Node::Dir(_)) = entries.get(name) {
+            return Err(Error::is_dir());
+        }
+        let data = FileData::new(&self.limits, contents.into())?;
+        entries.insert(name.to_string(), Node::File(data));
+        Ok(())
+    }
+
+    /// Creates a directory at `path`, along with any of its parents which
+    /// don't exist yet.
+    pub fn create_dir_all(&self, path: &str) -> Result<(), Error> {
+        self.create_dirs(&components(path)?)?;
+        Ok(())
+    }
+
+    /// Returns the contents of the file at `path`.
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>, Error> {
+        match self.lookup(&components(path)?)? {
+            Node::File(data) => Ok(data.read().unwrap().bytes.clone()),
+            _ => Err(Error::is_dir()),
+        }
+    }
+
+    fn inode(&self) -> usize {
+        Arc::as_ptr(&self.entries) as usize
+    }
+
+    fn create_dirs(&self, components: &[&str]) -> Result<MemDir, Error> {
+        let mut dir = self.clone();
+        for component in components {
+            let next = {
+                let mut entries = dir.entries.write().unwrap();
+                match entries.get(*component) {
+                    Some(Node::Dir(next)) => next.clone(),
+                    Some(Node::File(_)) => return Err(Error::not_dir()),
+                    Some(Node::Whiteout) | None => {
+                        let next = dir.new_subdir(false);
+                        entries.insert(component.to_string(), Node::Dir(next.clone()));
+                        next
+                    }
+                }
+            };
+            dir = next;
+        }
+        Ok(dir)
+    }
+
+    /// Returns the entry named `name` in this directory, which may be a
+    /// whiteout.
+    fn entry(&self, name: &str) -> Option<Node> {
+        self.entries.read().unwrap().get(name).cloned()
+    }
+
+    fn subdir(&self, components: &[&str]) -> Result<MemDir, Error> {
+        match self.lookup(components)? {
+            Node::Dir(dir) => Ok(dir),
+            _ => Err(Error::not_dir()),
+        }
+    }
+
+    fn lookup(&self, components: &[&str]) -> Result<Node, Error> {
+        let mut node = Node::Dir(self.clone());
+        for component in components {
+            let dir = match node {
+                Node::Dir(dir) => dir,
+                _ => return Err(Error::not_dir()),
+            };
+            node = match dir.entry(component) {
+                Some(Node::Whiteout) | None => return Err(Error::not_found()),
+                Some(node) => node,
+            };
+        }
+        Ok(node)
+    }
+
+    /// Returns the directory containing `path`, and the name of `path` in
+    /// it.
+    fn parent<'a>(&self, path: &'a str) -> Result<(MemDir, &'a str), Error> {
+        let (parent, name) = split_parent(path)?;
+        Ok((self.subdir(&parent)?, name))
+    }
+
+    /// Returns whether `dir` is this directory or one inside of it.
+    fn contains(&self, dir: &MemDir) -> bool {
+        Arc::ptr_eq(&self.entries, &dir.entries)
+            || self
+                .entries
+                .read()
+                .unwrap()
+                .values()
+                .any(|node| match node {
+                    Node::Dir(child) => child.contains(dir),
+                    _ => false,
+                })
+    }
+
+    fn open(
+        &self,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<MemFile, Error> {
+        let (dir, name) = match self.parent(path) {
+            Ok(parent) => parent,
+            // The path refers to this directory itself.
+            Err(_) if components(path)?.is_empty() => return Err(Error::is_dir()),
+            Err(e) => return Err(e),
+        };
+        let mut entries = dir.entries.write().unwrap();
+        let data = match entries.get(name) {
+            Some(Node::File(_)) if oflags.contains(OFlags::CREATE | OFlags::EXCLUSIVE) => {
+                return Err(Error::exist())
+            }
+            Some(Node::File(data)) => {
+                if oflags.contains(OFlags::TRUNCATE) {
+                    data.write().unwrap().resize(0)?;
+                }
+                data.clone()
+            }
+            Some(Node::Dir(_)) => return Err(Error::is_dir()),
+            Some(Node::Whiteout) | None => {
+                if !oflags.contains(OFlags::CREATE) {
+                    return Err(Error::not_found());
+                }
+                let data = FileData::new(&dir.limits, Vec::new())?;
+                entries.insert(name.to_string(), Node::File(data.clone()));
+                data
+            }
+        };
+        Ok(MemFile {
+            data,
+            pos: 0,
+            read,
+            write,
+            append: fdflags.contains(FdFlags::APPEND),
+        })
+    }
+
+    fn insert(&self, name: &str, node: Node) -> Result<(), Error> {
+        let mut entries = self.entries.write().unwrap();
+        match entries.get(name) {
+            Some(Node::Whiteout) | None => {
+                entries.insert(name.to_string(), node);
+                Ok(())
+            }
+            Some(_) => Err(Error::exist()),
+        }
+    }
+
+    fn remove(&self, path: &str, dir: bool) -> Result<(), Error> {
+        let (parent, name) = self.parent(path)?;
+        let mut entries = parent.entries.write().unwrap();
+        match (entries.get(name), dir) {
+            (Some(Node::File(_)), false) => {}
+            (Some(Node::Dir(child)), true) => {
+                let empty = child
+                    .entries
+                    .read()
+                    .unwrap()
+                    .values()
+                    .all(|node| matches!(node, Node::Whiteout));
+                if !empty {
+                    return Err(Error::not_empty());
+                }
+            }
+            (Some(Node::File(_)), true) => return Err(Error::not_dir()),
+            (Some(Node::Dir(_)), false) => return Err(Error::is_dir()),
+            (Some(Node::Whiteout) | None, _) => return Err(Error::not_found()),
+        }
+        entries.remove(name);
+        Ok(())
+    }
+
+    fn rename_to(&self, path: &str, dest_dir: &MemDir, dest_path: &str) -> Result<(), Error> {
+        let (src_parent, src_name) = self.parent(path)?;
+        let (dest_parent, dest_name) = dest_dir.parent(dest_path)?;
+        let node = match src_parent.entry(src_name) {
+            Some(Node::Whiteout) | None => return Err(Error::not_found()),
+            Some(node) => node,
+        };
+        match (&node, dest_parent.entry(dest_name)) {
+            (Node::File(_), Some(Node::Dir(_))) => return Err(Error::is_dir()),
+            (Node::Dir(_), Some(Node::File(_))) => return Err(Error::not_dir()),
+            (Node::Dir(dir), _) if dir.contains(&dest_parent) => {
+                return Err(Error::invalid_argument().context("rename into itself"))
+            }
+            (Node::Dir(_), Some(Node::Dir(dest))) => {
+                if dest
+                    .entries
+                    .read()
+                    .unwrap()
+                    .values()
+                    .any(|n| !matches!(n, Node::Whiteout))
+                {
+                    return Err(Error::not_empty());
+                }
+            }
+            _ => {}
+        }
+        src_parent.entries.write().unwrap().remove(src_name);
+        dest_parent
+            .entries
+            .write()
+            .unwrap()
+            .insert(dest_name.to_string(), node);
+        Ok(())
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiDir for MemDir {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn open_file(
+        &self,
+        _symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        Ok(Box::new(self.open(path, oflags, read, write, fdflags)?))
+    }
+
+    async fn open_dir(&self, _symlink_follow: bool, path: &str) -> Result<Box<dyn WasiDir>, Error> {
+        Ok(Box::new(self.subdir(&components(path)?)?))
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        let (parent, name) = self.parent(path)?;
+        parent.insert(name, Node::Dir(parent.new_subdir(false)))
+    }
+
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        let entries = self
+            .entries
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, node)| !matches!(node, Node::Whiteout))
+            .map(|(name, node)| (name.clone(), node.filetype(), node.filestat().inode))
+            .collect::<Vec<_>>();
+        Ok(readdir_entities(self.inode(), entries, cursor))
+    }
+
+    async fn remove_dir(&self, path: &str) -> Result<(), Error> {
+        self.remove(path, true)
+    }
+
+    async fn unlink_file(&self, path: &str) -> Result<(), Error> {
+        self.remove(path, false)
+    }
+
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        // Nothing here is a symbolic link.
+        self.lookup(&components(path)?)?;
+        Err(Error::invalid_argument())
+    }
+
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        Ok(Node::Dir(self.clone()).filestat())
+    }
+
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        _follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        Ok(self.lookup(&components(path)?)?.filestat())
+    }
+
+    async fn rename(
+        &self,
+        path: &str,
+        dest_dir: &dyn WasiDir,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        let dest_dir = dest_dir
+            .as_any()
+            .downcast_ref::<MemDir>()
+            .ok_or_else(|| Error::not_supported().context("rename to another filesystem"))?;
+        self.rename_to(path, dest_dir, dest_path)
+    }
+
+    async fn hard_link(
+        &self,
+        path: &str,
+        target_dir: &dyn WasiDir,
+        target_path: &str,
+    ) -> Result<(), Error> {
+        let target_dir = target_dir
+            .as_any()
+            .downcast_ref::<MemDir>()
+            .ok_or_else(|| Error::not_supported().context("link to another filesystem"))?;
+        let node = self.lookup(&components(path)?)?;
+        if let Node::Dir(_) = node {
+            return Err(Error::not_supported().context("hard link to a directory"));
+        }
+        let (parent, name) = target_dir.parent(target_path)?;
+        parent.insert(name, node)
+    }
+}
+
+/// A file held in memory, opened from a [`MemDir`].
+pub struct MemFile {
+    data: Arc<RwLock<FileData>>,
+    pos: u64,
+    read: bool,
+    write: bool,
+    append: bool,
+}
+
+impl MemFile {
+    fn read_at(&self, bufs: &mut [IoSliceMut<'_>], offset: u64) -> Result<u64, Error> {
+        if !self.read {
+            return Err(Error::badf());
+        }
+        let data = &self.data.read().unwrap().bytes;
+        let mut pos = offset.min(data.len() as u64) as usize;
+        let start = pos;
+        for buf in bufs {
+            let n = buf.len().min(data.len() - pos);
+            buf[..n].copy_from_slice(&data[pos..][..n]);
+            pos += n;
+        }
+        Ok((pos - start) as u64)
+    }
+
+    fn write_at(&self, bufs: &[IoSlice<'_>], offset: u64) -> Result<u64, Error> {
+        if !self.write {
+            return Err(Error::badf());
+        }
+        let mut data = self.data.write().unwrap();
+        let mut pos = offset;
+        for buf in bufs {
+            let end = pos
+                .checked_add(buf.len() as u64)
+                .ok_or_else(Error::too_big)?;
+            if data.len() < end {
+                data.resize(end)?;
+            }
+            // Both ends fit in a `usize` now that they're within the file.
+            data.bytes[pos as usize..end as usize].copy_from_slice(buf);
+            pos = end;
+        }
+        Ok(pos - offset)
+    }
+
+    fn len(&self) -> u64 {
+        self.data.read().unwrap().len()
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiFile for MemFile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn get_filetype(&mut self) -> Result<FileType, Error> {
+        Ok(FileType::RegularFile)
+    }
+
+    async fn get_fdflags(&mut self) -> Result<FdFlags, Error> {
+        if self.append {
+            Ok(FdFlags::APPEND)
+        } else {
+            Ok(FdFlags::empty())
+        }
+    }
+
+    async fn set_fdflags(&mut self, fdflags: FdFlags) -> Result<(), Error> {
+        if !(FdFlags::APPEND | FdFlags::NONBLOCK).contains(fdflags) {
+            return Err(Error::invalid_argument().context("cannot set anything but APPEND"));
+        }
+        self.append = fdflags.contains(FdFlags::APPEND);
+        Ok(())
+    }
+
+    async fn get_filestat(&mut self) -> Result<Filestat, Error> {
+        Ok(Node::File(self.data.clone()).filestat())
+    }
+
+    async fn set_filestat_size(&mut self, size: u64) -> Result<(), Error> {
+        if !self.write {
+            return Err(Error::badf());
+        }
+        self.data.write().unwrap().resize(size)
+    }
+
+    async fn read_vectored<'a>(&mut self, bufs: &mut [IoSliceMut<'a>]) -> Result<u64, Error> {
+        let n = self.read_at(bufs, self.pos)?;
+        self.pos += n;
+        Ok(n)
+    }
+
+    async fn read_vectored_at<'a>(
+        &mut self,
+        bufs: &mut [IoSliceMut<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        self.read_at(bufs, offset)
+    }
+
+    async fn write_vectored<'a>(&mut self, bufs: &[IoSlice<'a>]) -> Result<u64, Error> {
+        if self.append {
+            self.pos = self.len();
+        }
+        let n = self.write_at(bufs, self.pos)?;
+        self.pos += n;
+        Ok(n)
+    }
+
+    async fn write_vectored_at<'a>(
+        &mut self,
+        bufs: &[IoSlice<'a>],
+        offset: u64,
+    ) -> Result<u64, Error> {
+        self.write_at(bufs, offset)
+    }
+
+    async fn seek(&mut self, pos: SeekFrom) -> Result<u64, Error> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i64),
+            SeekFrom::Current(offset) => (self.pos, offset),
+            SeekFrom::End(offset) => (self.len(), offset),
+        };
+        self.pos = i64::try_from(base)?
+            .checked_add(offset)
+            .and_then(|pos| u64::try_from(pos).ok())
+            .ok_or_else(|| Error::invalid_argument().context("seek to a negative offset"))?;
+        Ok(self.pos)
+    }
+
+    async fn peek(&mut self, buf: &mut [u8]) -> Result<u64, Error> {
+        self.read_at(&mut [IoSliceMut::new(buf)], self.pos)
+    }
+
+    async fn num_ready_bytes(&self) -> Result<u64, Error> {
+        Ok(self.len().saturating_sub(self.pos))
+    }
+
+    async fn readable(&self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn writable(&self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// A copy-on-write overlay of an in-memory directory over another directory.
+///
+/// Reading something the guest hasn't changed reads it from the lower
+/// directory, while creating, writing to, renaming or removing anything only
+/// changes the in-memory upper directory, copying files up from the lower
+/// one first when needed. Renaming directories from the lower directory isn't
+/// supported.
+pub struct Overlay {
+    upper: MemDir,
+    lower: Option<Box<dyn WasiDir>>,
+}
+
+/// Where an entry of an [`Overlay`] is.
+enum Found {
+    Upper(Node),
+    Lower(Filestat),
+    Missing,
+}
+
+impl Overlay {
+    /// Creates an overlay of an empty in-memory directory over `lower`.
+    pub fn new(lower: Box<dyn WasiDir>) -> Overlay {
+        Overlay::with_upper(MemDir::new(), lower)
+    }
+
+    /// Creates an overlay of `upper` over `lower`. Whatever is in `upper`
+    /// hides the entries of `lower` with the same path.
+    pub fn with_upper(upper: MemDir, lower: Box<dyn WasiDir>) -> Overlay {
+        Overlay {
+            upper,
+            lower: Some(lower),
+        }
+    }
+
+    /// Returns the in-memory directory holding everything which has been
+    /// changed.
+    pub fn upper(&self) -> &MemDir {
+        &self.upper
+    }
+
+    async fn find(&self, name: &str) -> Result<Found, Error> {
+        match self.upper.entry(name) {
+            Some(Node::Whiteout) => return Ok(Found::Missing),
+            Some(node) => return Ok(Found::Upper(node)),
+            None => {}
+        }
+        let lower = match &self.lower {
+            Some(lower) => lower,
+            None => return Ok(Found::Missing),
+        };
+        match lower.get_path_filestat(name, false).await {
+            Ok(stat) => Ok(Found::Lower(stat)),
+            Err(e) => match e.downcast_ref::<std::io::Error>().map(|e| e.kind()) {
+                Some(io::ErrorKind::NotFound) => Ok(Found::Missing),
+                _ if matches!(e.downcast_ref(), Some(crate::ErrorKind::Noent)) => {
+                    Ok(Found::Missing)
+                }
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Returns the overlay of the directory `name` in this one.
+    async fn child(&self, name: &str) -> Result<Overlay, Error> {
+        match self.find(name).await? {
+            Found::Upper(Node::Dir(upper)) => {
+                // Directories created by the guest hide what was in the lower
+                // directory, but directories which were only copied up to
+                // hold changes to it merge with it.
+                let lower = match &self.lower {
+                    Some(lower) if !upper.opaque => lower.open_dir(false, name).await.ok(),
+                    _ => None,
+                };
+                Ok(Overlay { upper, lower })
+            }
+            Found::Upper(_) => Err(Error::not_dir()),
+            Found::Lower(stat) if stat.filetype == FileType::Directory => {
+                let lower = self.lower.as_ref().unwrap().open_dir(false, name).await?;
+                let upper = self.upper.create_dirs(&[name])?;
+                Ok(Overlay {
+                    upper,
+                    lower: Some(lower),
+                })
+            }
+            Found::Lower(_) => Err(Error::not_dir()),
+            Found::Missing => Err(Error::not_found()),
+        }
+    }
+
+    async fn subdir(&self, components: &[&str]) -> Result<Overlay, Error> {
+        let (first, rest) = match components.split_first() {
+            Some(split) => split,
+            None => {
+                let lower = match &self.lower {
+                    Some(lower) => Some(lower.open_dir(false, ".").await?),
+                    None => None,
+                };
+                return Ok(Overlay {
+                    upper: self.upper.clone(),
+                    lower,
+                });
+            }
+        };
+        let mut dir = self.child(first).await?;
+        for component in rest {
+            dir = dir.child(component).await?;
+        }
+        Ok(dir)
+    }
+
+    /// Returns the overlay of the directory containing `path`, and the name
+    /// of `path` in it. Only returns a new overlay if `path` has more than
+    /// one component.
+    async fn parent<'a>(&self, path: &'a str) -> Result<(Option<Overlay>, &'a str), Error> {
+        let (parent, name) = split_parent(path)?;
+        if parent.is_empty() {
+            Ok((None, name))
+        } else {
+            Ok((Some(self.subdir(&parent).await?), name))
+        }
+    }
+
+    /// Copies the file `name` from the lower directory into the upper one.
+    async fn copy_up(&self, name: &str) -> Result<(), Error> {
+        let lower = self.lower.as_ref().unwrap();
+        let mut file = lower
+            .open_file(false, name, OFlags::empty(), true, false, FdFlags::empty())
+            .await?;
+        let mut contents = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = file.read_vectored(&mut [IoSliceMut::new(&mut buf)]).await? as usize;
+            if n == 0 {
+                break;
+            }
+            contents.extend_from_slice(&buf[..n]);
+        }
+        let data = FileData::new(&self.upper.limits, contents)?;
+        self.upper
+            .entries
+            .write()
+            .unwrap()
+            .insert(name.to_string(), Node::File(data));
+        Ok(())
+    }
+
+    fn hide(&self, name: &str) {
+        self.upper
+            .entries
+            .write()
+            .unwrap()
+            .insert(name.to_string(), Node::Whiteout);
+    }
+
+    async fn open_file_here(
+        &self,
+        name: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        match self.find(name).await? {
+            Found::Lower(_) if oflags.contains(OFlags::CREATE | OFlags::EXCLUSIVE) => {
+                Err(Error::exist())
+            }
+            Found::Lower(stat) if stat.filetype == FileType::Directory => Err(Error::is_dir()),
+            Found::Lower(_) if !write && !oflags.contains(OFlags::TRUNCATE) => {
+                self.lower
+                    .as_ref()
+                    .unwrap()
+                    .open_file(false, name, OFlags::empty(), read, false, fdflags)
+                    .await
+            }
+            Found::Lower(_) => {
+                self.copy_up(name).await?;
+                Ok(Box::new(
+                    self.upper.open(name, oflags, read, write, fdflags)?,
+                ))
+            }
+            Found::Upper(_) | Found::Missing => Ok(Box::new(
+                self.upper.open(name, oflags, read, write, fdflags)?,
+            )),
+        }
+    }
+
+    async fn remove_here(&self, name: &str, dir: bool) -> Result<(), Error> {
+        match self.find(name).await? {
+            Found::Upper(_) => {
+                let lower_has_it = match &self.lower {
+                    Some(lower) => lower.get_path_filestat(name, false).await.is_ok(),
+                    None => false,
+                };
+                if dir && lower_has_it {
+                    // The merged directory has to be empty, not just the
+                    // part of it which has been copied up.
+                    let child = self.child(name).await?;
+                    if child
+                        .readdir(ReaddirCursor::from(2))
+                        .await?
+                        .next()
+                        .is_some()
+                    {
+                        return Err(Error::not_empty());
+                    }
+                    self.hide(name);
+                    return Ok(());
+                }
+                self.upper.remove(name, dir)?;
+                if lower_has_it {
+                    self.hide(name);
+                }
+                Ok(())
+            }
+            Found::Lower(stat) => {
+                match (stat.filetype == FileType::Directory, dir) {
+                    (true, false) => return Err(Error::is_dir()),
+                    (false, true) => return Err(Error::not_dir()),
+                    (true, true) => {
+                        let child = self.child(name).await?;
+                        if child
+                            .readdir(ReaddirCursor::from(2))
+                            .await?
+                            .next()
+                            .is_some()
+                        {
+                            return Err(Error::not_empty());
+                        }
+                    }
+                    (false, false) => {}
+                }
+                self.hide(name);
+                Ok(())
+            }
+            Found::Missing => Err(Error::not_found()),
+        }
+    }
+
+    /// Makes sure the file `name` is in the upper directory, for it to be
+    /// renamed or linked there.
+    async fn copy_up_file(&self, name: &str) -> Result<bool, Error> {
+        match self.find(name).await? {
+            Found::Upper(_) => Ok(false),
+            Found::Lower(stat) if stat.filetype == FileType::Directory => {
+                Err(Error::not_supported().context("renaming a directory in the lower directory"))
+            }
+            Found::Lower(_) => {
+                self.copy_up(name).await?;
+                Ok(true)
+            }
+            Found::Missing => Err(Error::not_found()),
+        }
+    }
+}
+
+#[wiggle::async_trait]
+impl WasiDir for Overlay {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn open_file(
+        &self,
+        _symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        read: bool,
+        write: bool,
+        fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        if components(path)?.is_empty() {
+            return Err(Error::is_dir());
+        }
+        match self.parent(path).await? {
+            (Some(parent), name) => {
+                parent
+                    .open_file_here(name, oflags, read, write, fdflags)
+                    .await
+            }
+            (None, name) => {
+                self.open_file_here(name, oflags, read, write, fdflags)
+                    .await
+            }
+        }
+    }
+
+    async fn open_dir(&self, _symlink_follow: bool, path: &str) -> Result<Box<dyn WasiDir>, Error> {
+        Ok(Box::new(self.subdir(&components(path)?).await?))
+    }
+
+    async fn create_dir(&self, path: &str) -> Result<(), Error> {
+        let (parent, name) = self.parent(path).await?;
+        let parent = parent.as_ref().unwrap_or(self);
+        match parent.find(name).await? {
+            Found::Missing => {
+                // A directory replacing one which was removed mustn't show
+                // what that one contained, so it's opaque, keeping the lower
+                // directory out of its view.
+                let dir = parent.upper.new_subdir(true);
+                parent.upper.insert(name, Node::Dir(dir))
+            }
+            _ => Err(Error::exist()),
+        }
+    }
+
+    async fn readdir(
+        &self,
+        cursor: ReaddirCursor,
+    ) -> Result<Box<dyn Iterator<Item = Result<ReaddirEntity, Error>> + Send>, Error> {
+        let mut entries = BTreeMap::new();
+        let mut hidden = BTreeSet::new();
+        for (name, node) in self.upper.entries.read().unwrap().iter() {
+            match node {
+                Node::Whiteout => {
+                    hidden.insert(name.clone());
+                }
+                node => {
+                    entries.insert(name.clone(), (node.filetype(), node.filestat().inode));
+                }
+            }
+        }
+        if let Some(lower) = &self.lower {
+            for entity in lower.readdir(ReaddirCursor::from(0)).await? {
+                let entity = entity?;
+                if entity.name == "." || entity.name == ".." || hidden.contains(&entity.name) {
+                    continue;
+                }
+                entries
+                    .entry(entity.name)
+                    .or_insert((entity.filetype, entity.inode));
+            }
+        }
+        let entries = entries
+            .into_iter()
+            .map(|(name, (filetype, inode))| (name, filetype, inode));
+        Ok(readdir_entities(self.upper.inode(), entries, cursor))
+    }
+
+    async fn remove_dir(&self, path: &str) -> Result<(), Error> {
+        let (parent, name) = self.parent(path).await?;
+        parent
+            .as_ref()
+            .unwrap_or(self)
+            .remove_here(name, true)
+            .await
+    }
+
+    async fn unlink_file(&self, path: &str) -> Result<(), Error> {
+        let (parent, name) = self.parent(path).await?;
+        parent
+            .as_ref()
+            .unwrap_or(self)
+            .remove_here(name, false)
+            .await
+    }
+
+    async fn read_link(&self, path: &str) -> Result<PathBuf, Error> {
+        let (parent, name) = self.parent(path).await?;
+        let parent = parent.as_ref().unwrap_or(self);
+        match parent.find(name).await? {
+            Found::Lower(_) => parent.lower.as_ref().unwrap().read_link(name).await,
+            Found::Upper(_) => Err(Error::invalid_argument()),
+            Found::Missing => Err(Error::not_found()),
+        }
+    }
+
+    async fn get_filestat(&self) -> Result<Filestat, Error> {
+        self.upper.get_filestat().await
+    }
+
+    async fn get_path_filestat(
+        &self,
+        path: &str,
+        _follow_symlinks: bool,
+    ) -> Result<Filestat, Error> {
+        if components(path)?.is_empty() {
+            return self.get_filestat().await;
+        }
+        let (parent, name) = self.parent(path).await?;
+        match parent.as_ref().unwrap_or(self).find(name).await? {
+            Found::Upper(node) => Ok(node.filestat()),
+            Found::Lower(stat) => Ok(stat),
+            Found::Missing => Err(Error::not_found()),
+        }
+    }
+
+    async fn rename(
+        &self,
+        path: &str,
+        dest_dir: &dyn WasiDir,
+        dest_path: &str,
+    ) -> Result<(), Error> {
+        let dest_dir = dest_dir
+            .as_any()
+            .downcast_ref::<Overlay>()
+            .ok_or_else(|| Error::not_supported().context("rename to another filesystem"))?;
+        let (src_parent, src_name) = self.parent(path).await?;
+        let src_parent = src_parent.as_ref().unwrap_or(self);
+        let (dest_parent, dest_name) = dest_dir.parent(dest_path).await?;
+        let dest_parent = dest_parent.as_ref().unwrap_or(dest_dir);
+        let copied = src_parent.copy_up_file(src_name).await?;
+        let lower_had_it = copied
+            || match &src_parent.lower {
+                Some(lower) => lower.get_path_filestat(src_name, false).await.is_ok(),
+                None => false,
+            };
+        if let Found::Lower(stat) = dest_parent.find(dest_name).await? {
+            // Whatever the rename replaces has to be hidden just like it
+            // would be in the upper directory.
+            if stat.filetype == FileType::Directory {
+                return Err(Error::not_supported().context("replacing a lower directory"));
+            }
+            dest_parent.hide(dest_name);
+        }
+        src_parent
+            .upper
+            .rename_to(src_name, &dest_parent.upper, dest_name)?;
+        if lower_had_it {
+            src_parent.hide(src_name);
+        }
+        Ok(())
+    }
+
+    async fn hard_link(
+        &self,
+        path: &str,
+        target_dir: &dyn WasiDir,
+        target_path: &str,
+    ) -> Result<(), Error> {
+        let target_dir = target_dir
+            .as_any()
+            .downcast_ref::<Overlay>()
+            .ok_or_else(|| Error::not_supported().context("link to another filesystem"))?;
+        let (src_parent, src_name) = self.parent(path).await?;
+        let src_parent = src_parent.as_ref().unwrap_or(self);
+        let (dest_parent, dest_name) = target_dir.parent(target_path).await?;
+        let dest_parent = dest_parent.as_ref().unwrap_or(target_dir);
+        src_parent.copy_up_file(src_name).await?;
+        if !matches!(dest_parent.find(dest_name).await?, Found::Missing) {
+            return Err(Error::exist());
+        }
+        let node = src_parent.upper.lookup(&[src_name])?;
+        if let Node::Dir(_) = node {
+            return Err(Error::not_supported().context("hard link to a directory"));
+        }
+        dest_parent
+            .upper
+            .entries
+            .write()
+            .unwrap()
+            .insert(dest_name.to_string(), node);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn names(dir: &dyn WasiDir) -> Vec<String> {
+        run(dir.readdir(ReaddirCursor::from(0)))
+            .expect("readdir succeeds")
+            .map(|entity| entity.expect("readdir entry is valid").name)
+            .collect()
+    }
+
+    fn write(dir: &dyn WasiDir, path: &str, contents: &[u8]) {
+        let mut file = run(dir.open_file(
+            false,
+            path,
+            OFlags::CREATE | OFlags::TRUNCATE,
+            false,
+            true,
+            FdFlags::empty(),
+        ))
+        .expect("open file for writing");
+        run(file.write_vectored(&[IoSlice::new(contents)])).expect("write file");
+    }
+
+    fn read(dir: &dyn WasiDir, path: &str) -> Result<Vec<u8>, Error> {
+        let mut file =
+            run(dir.open_file(false, path, OFlags::empty(), true, false, FdFlags::empty()))?;
+        let mut buf = [0; 64];
+        let n = run(file.read_vectored(&mut [IoSliceMut::new(&mut buf)]))?;
+        Ok(buf[..n as usize].to_vec())
+    }
+
+    #[test]
+    fn mem_dir() {
+        let dir = MemDir::new();
+        dir.add_file("a/b.txt", "hello").expect("add file");
+        assert_eq!(names(&dir), [".", "..", "a"]);
+        assert_eq!(read(&dir, "a/./b.txt").unwrap(), b"hello");
+        assert!(read(&dir, "a/../../b.txt").is_err());
+
+        write(&dir, "a/c.txt", b"world");
+        assert_eq!(dir.read_file("a/c.txt").unwrap(), b"world");
+        assert_eq!(names(&dir), [".", "..", "a"]);
+
+        let err = run(dir.remove_dir("a")).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(crate::ErrorKind::Notempty)
+        ));
+        run(dir.rename("a/c.txt", &dir, "c.txt")).expect("rename");
+        run(dir.unlink_file("a/b.txt")).expect("unlink");
+        run(dir.remove_dir("a")).expect("remove empty dir");
+        assert_eq!(names(&dir), [".", "..", "c.txt"]);
+    }
+
+    #[test]
+    fn overlay() {
+        let lower = MemDir::new();
+        lower
+            .add_file("config/app.toml", "lower")
+            .expect("add file");
+        lower.add_file("data.txt", "data").expect("add file");
+        let overlay = Overlay::new(Box::new(lower.clone()));
+
+        assert_eq!(read(&overlay, "config/app.toml").unwrap(), b"lower");
+        write(&overlay, "config/app.toml", b"upper");
+        write(&overlay, "new.txt", b"new");
+        assert_eq!(read(&overlay, "config/app.toml").unwrap(), b"upper");
+        assert_eq!(lower.read_file("config/app.toml").unwrap(), b"lower");
+
+        run(overlay.unlink_file("data.txt")).expect("unlink");
+        assert!(read(&overlay, "data.txt").is_err());
+        assert_eq!(lower.read_file("data.txt").unwrap(), b"data");
+        assert_eq!(names(&overlay), [".", "..", "config", "new.txt"]);
+
+        run(overlay.rename("config/app.toml", &overlay, "app.toml")).expect("rename");
+        run(overlay.remove_dir("config")).expect("remove emptied dir");
+        assert_eq!(names(&overlay), [".", "..", "app.toml", "new.txt"]);
+        assert_eq!(names(&lower), [".", "..", "config", "data.txt"]);
+        assert_eq!(overlay.upper().read_file("app.toml").unwrap(), b"upper");
+    }
+
+    #[test]
+    fn overlay_recreated_dir() {
+        let lower = MemDir::new();
+        lower.add_file("dir/old.txt", "old").expect("add file");
+        let overlay = Overlay::new(Box::new(lower));
+
+        run(overlay.unlink_file("dir/old.txt")).expect("unlink");
+        run(overlay.remove_dir("dir")).expect("remove emptied dir");
+        run(overlay.create_dir("dir")).expect("recreate dir");
+        write(&overlay, "dir/new.txt", b"new");
+
+        // Nothing in the removed directory shows through the new one.
+        let dir = run(overlay.open_dir(false, "dir")).expect("open recreated dir");
+        assert_eq!(names(&*dir), [".", "..", "new.txt"]);
+        assert!(read(&overlay, "dir/old.txt").is_err());
+    }
+
+    #[test]
+    fn limits() {
+        fn too_big(result: Result<impl Sized, Error>) -> bool {
+            match result {
+                Err(e) => matches!(e.downcast_ref(), Some(crate::ErrorKind::TooBig)),
+                Ok(_) => false,
+            }
+        }
+
+        let dir = MemDir::with_limits(8, 12);
+        write(&dir, "a", b"12345678");
+        let mut a = run(dir.open_file(false, "a", OFlags::empty(), true, true, FdFlags::APPEND))
+            .expect("open file");
+        assert!(too_big(run(a.write_vectored(&[IoSlice::new(b"9")]))));
+        assert!(too_big(run(a.set_filestat_size(9))));
+        assert!(too_big(run(
+            a.write_vectored_at(&[IoSlice::new(b"9")], u64::MAX)
+        )));
+        assert_eq!(dir.read_file("a").unwrap(), b"12345678");
+
+        // The limit on the total applies across subdirectories.
+        dir.add_file("sub/b", "1234").expect("add file");
+        assert!(too_big(dir.add_file("sub/c", "5")));
+
+        // Removed files stop counting once they're closed.
+        run(dir.unlink_file("a")).expect("unlink");
+        assert!(too_big(dir.add_file("sub/c", "5")));
+        drop(a);
+        dir.add_file("sub/c", "5").expect("add file");
+    }
+
+    fn run<F: std::future::Future>(future: F) -> F::Output {
+        use std::pin::Pin;
+        use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        let mut f = Pin::from(Box::new(future));
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        match f.as_mut().poll(&mut cx) {
+            Poll::Ready(val) => return val,
+            Poll::Pending => panic!("in-memory filesystem operations never block"),
+        }
+
+        fn dummy_waker() -> Waker {
+            return unsafe { Waker::from_raw(clone(5 as *const _)) };
+
+            unsafe fn clone(ptr: *const ()) -> RawWaker {
+                assert_eq!(ptr as usize, 5);
+                const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+                RawWaker::new(ptr, &VTABLE)
+            }
+
+            unsafe fn wake(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+
+            unsafe fn wake_by_ref(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+
+            unsafe fn drop(ptr: *const ()) {
+                assert_eq!(ptr as usize, 5);
+            }
+        }
+    }
+}
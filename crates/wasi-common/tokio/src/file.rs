@@ -6,7 +6,7 @@ use io_lifetimes::AsFd;
 use std::any::Any;
 use std::io;
 use wasi_common::{
-    file::{Advice, FdFlags, FileType, Filestat, WasiFile},
+    file::{Advice, FdFlags, FileType, Filestat, RiFlags, RoFlags, SdFlags, SiFlags, WasiFile},
     Error,
 };
 
@@ -224,6 +224,23 @@ macro_rules! wasi_file_impl {
             async fn sock_accept(&mut self, fdflags: FdFlags) -> Result<Box<dyn WasiFile>, Error> {
                 block_on_dummy_executor(|| self.0.sock_accept(fdflags))
             }
+            async fn sock_recv<'a>(
+                &mut self,
+                ri_data: &mut [io::IoSliceMut<'a>],
+                ri_flags: RiFlags,
+            ) -> Result<(u64, RoFlags), Error> {
+                block_on_dummy_executor(move || self.0.sock_recv(ri_data, ri_flags))
+            }
+            async fn sock_send<'a>(
+                &mut self,
+                si_data: &[io::IoSlice<'a>],
+                si_flags: SiFlags,
+            ) -> Result<u64, Error> {
+                block_on_dummy_executor(move || self.0.sock_send(si_data, si_flags))
+            }
+            async fn sock_shutdown(&mut self, how: SdFlags) -> Result<(), Error> {
+                block_on_dummy_executor(|| self.0.sock_shutdown(how))
+            }
         }
         #[cfg(windows)]
         impl AsRawHandleOrSocket for $ty {
@@ -6,15 +6,27 @@ pub mod net;
 pub mod sched;
 pub mod stdio;
 
+use cap_std::time::Duration;
+use std::any::Any;
 use std::future::Future;
+use std::io::{Read, Write};
 use std::path::Path;
-pub use wasi_cap_std_sync::{clocks_ctx, random_ctx};
-use wasi_common::{Error, Table, WasiCtx, WasiFile};
+use wasi_cap_std_sync::ambient_authority;
+use wasi_cap_std_sync::clocks::{MonotonicClock, SystemClock};
+pub use wasi_cap_std_sync::{
+    clocks_ctx, deterministic_clocks_ctx, deterministic_random_ctx, random_ctx,
+};
+use wasi_common::clocks::{CoarseMonotonicClock, CoarseSystemClock};
+use wasi_common::pipe::{ReadPipe, WritePipe};
+use wasi_common::{
+    Error, RngCore, Table, WasiCtx, WasiDir, WasiFile, WasiMonotonicClock, WasiSystemClock,
+};
 
 pub use dir::Dir;
 pub use file::File;
 pub use net::*;
 use wasi_cap_std_sync::net::Socket;
+use wasi_common::dir::DirCaps;
 use wasi_common::file::FileCaps;
 
 use crate::sched::sched_ctx;
@@ -74,6 +86,18 @@ impl WasiCtxBuilder {
         self.0.set_stderr(f);
         self
     }
+    /// Makes the guest's stdin read from `r`.
+    pub fn stdin_reader(self, r: impl Read + Any + Send + Sync) -> Self {
+        self.stdin(Box::new(ReadPipe::new(r)))
+    }
+    /// Makes the guest's stdout write to `w`.
+    pub fn stdout_writer(self, w: impl Write + Any + Send + Sync) -> Self {
+        self.stdout(Box::new(WritePipe::new(w)))
+    }
+    /// Makes the guest's stderr write to `w`.
+    pub fn stderr_writer(self, w: impl Write + Any + Send + Sync) -> Self {
+        self.stderr(Box::new(WritePipe::new(w)))
+    }
     pub fn inherit_stdin(self) -> Self {
         self.stdin(Box::new(crate::stdio::stdin()))
     }
@@ -95,19 +119,94 @@ impl WasiCtxBuilder {
         self.0.push_preopened_dir(dir, guest_path)?;
         Ok(self)
     }
+    /// Preopens `dir` at `guest_path` like `preopened_dir`, but only with the
+    /// capabilities `caps` for it and its subdirectories, and `file_caps` for
+    /// the files opened in them. `DirCaps::read_only` and
+    /// `FileCaps::read_only` make the directory read-only.
+    pub fn preopened_dir_with_caps(
+        mut self,
+        dir: cap_std::fs::Dir,
+        guest_path: impl AsRef<Path>,
+        caps: DirCaps,
+        file_caps: FileCaps,
+    ) -> Result<Self, Error> {
+        let dir = Box::new(crate::dir::Dir::from_cap_std(dir));
+        self.0
+            .push_preopened_dir_with_caps(dir, guest_path, caps, file_caps)?;
+        Ok(self)
+    }
+    /// Preopens a directory implemented by the embedder, rather than one on
+    /// the host filesystem, at `guest_path`.
+    pub fn preopened_custom_dir(
+        mut self,
+        dir: Box<dyn WasiDir>,
+        guest_path: impl AsRef<Path>,
+    ) -> Result<Self, Error> {
+        self.0.push_preopened_dir(dir, guest_path)?;
+        Ok(self)
+    }
+    /// Grants the guest `socket` as the file descriptor `fd`, like
+    /// `wasi_cap_std_sync::WasiCtxBuilder::preopened_socket`.
+    ///
+    /// Reading from and writing to sockets waits for them in the same way as
+    /// files do, by blocking the executor's thread in `block_in_place`,
+    /// rather than by registering them with the tokio reactor. Only
+    /// `poll_oneoff` waits for them asynchronously, on Unix.
     pub fn preopened_socket(mut self, fd: u32, socket: impl Into<Socket>) -> Result<Self, Error> {
         let socket: Socket = socket.into();
-        let file: Box<dyn WasiFile> = socket.into();
-
-        let caps = FileCaps::FDSTAT_SET_FLAGS
+        let mut caps = FileCaps::FDSTAT_SET_FLAGS
             | FileCaps::FILESTAT_GET
             | FileCaps::READ
             | FileCaps::POLL_READWRITE;
+        // Connected streams can be written to, unlike listeners which can
+        // only accept new connections.
+        if socket.is_stream() {
+            caps |= FileCaps::WRITE;
+        }
+        let file: Box<dyn WasiFile> = socket.into();
 
         self.0.insert_file(fd, file, caps);
         Ok(self)
     }
 
+    /// Replaces the clocks and random number generator with ones which
+    /// produce the same values on every run, with random numbers derived from
+    /// `seed`.
+    pub fn deterministic(mut self, seed: u64) -> Self {
+        self.0.random = deterministic_random_ctx(seed);
+        self.0.clocks = deterministic_clocks_ctx();
+        self
+    }
+    /// Replaces the system clock, which the guest reads the wall-clock time
+    /// from.
+    pub fn system_clock(mut self, clock: impl WasiSystemClock + 'static) -> Self {
+        self.0.clocks.system = Box::new(clock);
+        self
+    }
+    /// Replaces the monotonic clock, which the guest measures time with and
+    /// waits on in `poll_oneoff`.
+    pub fn monotonic_clock(mut self, clock: impl WasiMonotonicClock + 'static) -> Self {
+        self.0.clocks.creation_time = clock.now(clock.resolution());
+        self.0.clocks.monotonic = Box::new(clock);
+        self
+    }
+    /// Replaces the source of the guest's random numbers.
+    pub fn random(mut self, random: impl RngCore + Send + Sync + 'static) -> Self {
+        self.0.random = Box::new(random);
+        self
+    }
+    /// Makes the host clocks only advance in steps of `resolution`, so that
+    /// guests can't use them as high-resolution timers.
+    pub fn coarse_clocks(self, resolution: Duration) -> Self {
+        self.system_clock(CoarseSystemClock::new(
+            SystemClock::new(ambient_authority()),
+            resolution,
+        ))
+        .monotonic_clock(CoarseMonotonicClock::new(
+            MonotonicClock::new(ambient_authority()),
+            resolution,
+        ))
+    }
     pub fn build(self) -> WasiCtx {
         self.0
     }
@@ -18,11 +18,13 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         options_handle: &guest_types::OptOptions,
     ) -> Result<guest_types::Keypair, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let options_handle = match *options_handle {
             guest_types::OptOptions::Some(options_handle) => Some(options_handle),
             guest_types::OptOptions::None => None,
         };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_generate_managed(
                 secrets_manager_handle.into(),
                 alg_type.into(),
@@ -40,7 +42,7 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         kp_id_max_len: guest_types::Size,
     ) -> Result<(), guest_types::CryptoErrno> {
         let key_id_buf = &mut *kp_id_ptr.as_array(kp_id_max_len).as_slice_mut()?;
-        Ok((&*self).keypair_store_managed(
+        Ok(self.ctx.keypair_store_managed(
             secrets_manager_handle.into(),
             kp_handle.into(),
             key_id_buf,
@@ -53,7 +55,8 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         kp_old_handle: guest_types::Keypair,
         kp_new_handle: guest_types::Keypair,
     ) -> Result<guest_types::Version, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_replace_managed(
                 secrets_manager_handle.into(),
                 kp_old_handle.into(),
@@ -70,7 +73,8 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         kp_version: guest_types::Version,
     ) -> Result<guest_types::Keypair, guest_types::CryptoErrno> {
         let kp_id = &*kp_id_ptr.as_array(kp_id_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_from_id(secrets_manager_handle.into(), kp_id, Version(kp_version))?
             .into())
     }
@@ -84,11 +88,13 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         options_handle: &guest_types::OptOptions,
     ) -> Result<guest_types::Keypair, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let options_handle = match *options_handle {
             guest_types::OptOptions::Some(options_handle) => Some(options_handle),
             guest_types::OptOptions::None => None,
         };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_generate(alg_type.into(), alg_str, options_handle.map(Into::into))?
             .into())
     }
@@ -102,8 +108,10 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         encoding: guest_types::KeypairEncoding,
     ) -> Result<guest_types::Keypair, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let encoded = &*encoded_ptr.as_array(encoded_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_import(alg_type.into(), alg_str, encoded, encoding.into())?
             .into())
     }
@@ -115,7 +123,7 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         kp_id_max_len: guest_types::Size,
     ) -> Result<(guest_types::Size, guest_types::Version), guest_types::CryptoErrno> {
         let kp_id_buf = &mut *kp_id_ptr.as_array(kp_id_max_len as _).as_slice_mut()?;
-        let (kp_id, version) = (&*self).keypair_id(kp_handle.into())?;
+        let (kp_id, version) = self.ctx.keypair_id(kp_handle.into())?;
         ensure!(kp_id.len() <= kp_id_buf.len(), CryptoError::Overflow.into());
         kp_id_buf.copy_from_slice(&kp_id);
         Ok((kp_id.len().try_into()?, version.0))
@@ -126,7 +134,8 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         kp_handle: guest_types::Keypair,
         encoding: guest_types::KeypairEncoding,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_export(kp_handle.into(), encoding.into())?
             .into())
     }
@@ -135,14 +144,14 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         &mut self,
         kp_handle: guest_types::Keypair,
     ) -> Result<guest_types::Publickey, guest_types::CryptoErrno> {
-        Ok((&*self).keypair_publickey(kp_handle.into())?.into())
+        Ok(self.ctx.keypair_publickey(kp_handle.into())?.into())
     }
 
     fn keypair_close(
         &mut self,
         kp_handle: guest_types::Keypair,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).keypair_close(kp_handle.into())?)
+        Ok(self.ctx.keypair_close(kp_handle.into())?)
     }
 
     // --- publickey
@@ -156,8 +165,10 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         encoding: guest_types::PublickeyEncoding,
     ) -> Result<guest_types::Publickey, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let encoded = &*encoded_ptr.as_array(encoded_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .publickey_import(alg_type.into(), alg_str, encoded, encoding.into())?
             .into())
     }
@@ -167,7 +178,8 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         pk_handle: guest_types::Publickey,
         encoding: guest_types::PublickeyEncoding,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .publickey_export(pk_handle.into(), encoding.into())?
             .into())
     }
@@ -176,21 +188,21 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         &mut self,
         sk_handle: guest_types::Secretkey,
     ) -> Result<guest_types::Publickey, guest_types::CryptoErrno> {
-        Ok((&*self).keypair_publickey(sk_handle.into())?.into())
+        Ok(self.ctx.keypair_publickey(sk_handle.into())?.into())
     }
 
     fn publickey_verify(
         &mut self,
         pk_handle: guest_types::Publickey,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).publickey_verify(pk_handle.into())?)
+        Ok(self.ctx.publickey_verify(pk_handle.into())?)
     }
 
     fn publickey_close(
         &mut self,
         pk_handle: guest_types::Publickey,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).publickey_close(pk_handle.into())?)
+        Ok(self.ctx.publickey_close(pk_handle.into())?)
     }
 
     // --- secretkey
@@ -204,8 +216,10 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         encoding: guest_types::SecretkeyEncoding,
     ) -> Result<guest_types::Secretkey, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let encoded = &*encoded_ptr.as_array(encoded_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .secretkey_import(alg_type.into(), alg_str, encoded, encoding.into())?
             .into())
     }
@@ -215,7 +229,8 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         sk_handle: guest_types::Secretkey,
         encoding: guest_types::SecretkeyEncoding,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .secretkey_export(sk_handle.into(), encoding.into())?
             .into())
     }
@@ -224,7 +239,7 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         &mut self,
         sk_handle: guest_types::Secretkey,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).secretkey_close(sk_handle.into())?)
+        Ok(self.ctx.secretkey_close(sk_handle.into())?)
     }
 
     fn keypair_from_pk_and_sk(
@@ -232,7 +247,8 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         pk_handle: guest_types::Publickey,
         sk_handle: guest_types::Secretkey,
     ) -> Result<guest_types::Keypair, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .keypair_from_pk_and_sk(pk_handle.into(), sk_handle.into())?
             .into())
     }
@@ -241,7 +257,7 @@ impl super::wasi_ephemeral_crypto_asymmetric_common::WasiEphemeralCryptoAsymmetr
         &mut self,
         kp_handle: guest_types::Keypair,
     ) -> Result<guest_types::Secretkey, guest_types::CryptoErrno> {
-        Ok((&*self).keypair_secretkey(kp_handle.into())?.into())
+        Ok(self.ctx.keypair_secretkey(kp_handle.into())?.into())
     }
 }
 
@@ -10,14 +10,14 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         &mut self,
         options_type: guest_types::AlgorithmType,
     ) -> Result<guest_types::Options, guest_types::CryptoErrno> {
-        Ok((&*self).options_open(options_type.into())?.into())
+        Ok(self.ctx.options_open(options_type.into())?.into())
     }
 
     fn options_close(
         &mut self,
         options_handle: guest_types::Options,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).options_close(options_handle.into())?)
+        Ok(self.ctx.options_close(options_handle.into())?)
     }
 
     fn options_set(
@@ -29,7 +29,9 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
     ) -> Result<(), guest_types::CryptoErrno> {
         let name_str: &str = &*name_str.as_str()?;
         let value: &[u8] = { &*value_ptr.as_array(value_len).as_slice()? };
-        Ok((&*self).options_set(options_handle.into(), name_str, value)?)
+        Ok(self
+            .ctx
+            .options_set(options_handle.into(), name_str, value)?)
     }
 
     fn options_set_guest_buffer(
@@ -42,7 +44,9 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         let name_str: &str = &*name_str.as_str()?;
         let buffer: &'static mut [u8] =
             unsafe { std::mem::transmute(&mut *buffer_ptr.as_array(buffer_len).as_slice_mut()?) };
-        Ok((&*self).options_set_guest_buffer(options_handle.into(), name_str, buffer)?)
+        Ok(self
+            .ctx
+            .options_set_guest_buffer(options_handle.into(), name_str, buffer)?)
     }
 
     fn options_set_u64(
@@ -52,7 +56,9 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         value: u64,
     ) -> Result<(), guest_types::CryptoErrno> {
         let name_str: &str = &*name_str.as_str()?;
-        Ok((&*self).options_set_u64(options_handle.into(), name_str, value)?)
+        Ok(self
+            .ctx
+            .options_set_u64(options_handle.into(), name_str, value)?)
     }
 
     // --- array
@@ -61,7 +67,8 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         &mut self,
         array_output_handle: guest_types::ArrayOutput,
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .array_output_len(array_output_handle.into())?
             .try_into()?)
     }
@@ -73,7 +80,8 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         buf_len: guest_types::Size,
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
         let buf: &mut [u8] = { &mut *buf_ptr.as_array(buf_len).as_slice_mut()? };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .array_output_pull(array_output_handle.into(), buf)?
             .try_into()?)
     }
@@ -88,7 +96,8 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
             guest_types::OptOptions::Some(options_handle) => Some(options_handle),
             guest_types::OptOptions::None => None,
         };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .secrets_manager_open(options_handle.map(Into::into))?
             .into())
     }
@@ -97,7 +106,9 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         &mut self,
         secrets_manager_handle: guest_types::SecretsManager,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).secrets_manager_close(secrets_manager_handle.into())?)
+        Ok(self
+            .ctx
+            .secrets_manager_close(secrets_manager_handle.into())?)
     }
 
     fn secrets_manager_invalidate(
@@ -108,7 +119,7 @@ impl super::wasi_ephemeral_crypto_common::WasiEphemeralCryptoCommon for WasiCryp
         key_version: guest_types::Version,
     ) -> Result<(), guest_types::CryptoErrno> {
         let key_id: &[u8] = { &*key_id_ptr.as_array(key_id_len).as_slice()? };
-        Ok((&*self).secrets_manager_invalidate(
+        Ok(self.ctx.secrets_manager_invalidate(
             secrets_manager_handle.into(),
             key_id,
             Version(key_version),
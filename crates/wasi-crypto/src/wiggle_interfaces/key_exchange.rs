@@ -8,7 +8,7 @@ impl super::wasi_ephemeral_crypto_kx::WasiEphemeralCryptoKx for WasiCryptoCtx {
         pk_handle: guest_types::Publickey,
         sk_handle: guest_types::Secretkey,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self).kx_dh(pk_handle.into(), sk_handle.into())?.into())
+        Ok(self.ctx.kx_dh(pk_handle.into(), sk_handle.into())?.into())
     }
 
     // --- Key encapsulation
@@ -19,7 +19,7 @@ impl super::wasi_ephemeral_crypto_kx::WasiEphemeralCryptoKx for WasiCryptoCtx {
     ) -> Result<(guest_types::ArrayOutput, guest_types::ArrayOutput), guest_types::CryptoErrno>
     {
         let (secret_handle, encapsulated_secret_handle) =
-            (&*self).kx_encapsulate(pk_handle.into())?;
+            self.ctx.kx_encapsulate(pk_handle.into())?;
         Ok((secret_handle.into(), encapsulated_secret_handle.into()))
     }
 
@@ -32,7 +32,8 @@ impl super::wasi_ephemeral_crypto_kx::WasiEphemeralCryptoKx for WasiCryptoCtx {
         let encapsulated_secret = &*encapsulated_secret_ptr
             .as_array(encapsulated_secret_len)
             .as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .kx_decapsulate(sk_handle.into(), encapsulated_secret)?
             .into())
     }
@@ -1,4 +1,54 @@
-pub use wasi_crypto::CryptoCtx as WasiCryptoCtx;
+use std::collections::HashSet;
+use wasi_crypto::{CryptoCtx, CryptoError};
+
+/// The state of the wasi-crypto API for one guest: its keys and the other
+/// objects it has handles to, and which algorithms it's allowed to use.
+pub struct WasiCryptoCtx {
+    ctx: CryptoCtx,
+    algorithms: Option<HashSet<String>>,
+}
+
+impl WasiCryptoCtx {
+    /// Creates a context allowing every algorithm the implementation
+    /// supports.
+    pub fn new() -> Self {
+        WasiCryptoCtx {
+            ctx: CryptoCtx::new(),
+            algorithms: None,
+        }
+    }
+
+    /// Only lets the guest use the algorithms named in `algorithms`, such as
+    /// `"Ed25519"` or `"AES-256-GCM"`. Generating or importing keys,
+    /// signatures or symmetric states for any other algorithm fails with
+    /// `unsupported_algorithm`. Names are matched ignoring ASCII case.
+    pub fn allow_algorithms<S: Into<String>>(
+        mut self,
+        algorithms: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.algorithms = Some(algorithms.into_iter().map(Into::into).collect());
+        self
+    }
+
+    fn check_algorithm(&self, alg_str: &str) -> Result<(), CryptoError> {
+        match &self.algorithms {
+            Some(algorithms)
+                if !algorithms
+                    .iter()
+                    .any(|algorithm| algorithm.eq_ignore_ascii_case(alg_str)) =>
+            {
+                Err(CryptoError::UnsupportedAlgorithm)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Default for WasiCryptoCtx {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 wiggle::from_witx!({
     witx: ["$CARGO_MANIFEST_DIR/spec/witx/wasi_ephemeral_crypto.witx"],
@@ -20,3 +70,31 @@ mod error;
 mod key_exchange;
 mod signatures;
 mod symmetric;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_algorithm() {
+        let ctx = WasiCryptoCtx::new();
+        assert!(ctx.check_algorithm("Ed25519").is_ok());
+        assert!(ctx.check_algorithm("AES-256-GCM").is_ok());
+
+        let ctx = WasiCryptoCtx::new().allow_algorithms(["Ed25519", "aes-256-gcm"]);
+        assert!(ctx.check_algorithm("Ed25519").is_ok());
+        assert!(ctx.check_algorithm("ED25519").is_ok());
+        assert!(ctx.check_algorithm("AES-256-GCM").is_ok());
+        assert!(matches!(
+            ctx.check_algorithm("X25519"),
+            Err(CryptoError::UnsupportedAlgorithm)
+        ));
+        assert!(matches!(
+            ctx.check_algorithm("Ed25519ph"),
+            Err(CryptoError::UnsupportedAlgorithm)
+        ));
+
+        let ctx = WasiCryptoCtx::new().allow_algorithms(Vec::<String>::new());
+        assert!(ctx.check_algorithm("Ed25519").is_err());
+    }
+}
@@ -10,7 +10,8 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         signature_handle: guest_types::Signature,
         encoding: guest_types::SignatureEncoding,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .signature_export(signature_handle.into(), encoding.into())?
             .into())
     }
@@ -23,8 +24,10 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         encoding: guest_types::SignatureEncoding,
     ) -> Result<guest_types::Signature, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let encoded = &*encoded_ptr.as_array(encoded_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .signature_import(alg_str, encoded, encoding.into())?
             .into())
     }
@@ -33,7 +36,7 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         &mut self,
         kp_handle: guest_types::Keypair,
     ) -> Result<guest_types::SignatureState, guest_types::CryptoErrno> {
-        Ok((&*self).signature_state_open(kp_handle.into())?.into())
+        Ok(self.ctx.signature_state_open(kp_handle.into())?.into())
     }
 
     fn signature_state_update(
@@ -43,14 +46,17 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         input_len: guest_types::Size,
     ) -> Result<(), guest_types::CryptoErrno> {
         let input = &*input_ptr.as_array(input_len).as_slice()?;
-        Ok((&*self).signature_state_update(state_handle.into(), input)?)
+        Ok(self
+            .ctx
+            .signature_state_update(state_handle.into(), input)?)
     }
 
     fn signature_state_sign(
         &mut self,
         signature_state_handle: guest_types::SignatureState,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .signature_state_sign(signature_state_handle.into())?
             .into())
     }
@@ -59,14 +65,17 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         &mut self,
         signature_state_handle: guest_types::SignatureState,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).signature_state_close(signature_state_handle.into())?)
+        Ok(self
+            .ctx
+            .signature_state_close(signature_state_handle.into())?)
     }
 
     fn signature_verification_state_open(
         &mut self,
         pk_handle: guest_types::Publickey,
     ) -> Result<guest_types::SignatureVerificationState, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .signature_verification_state_open(pk_handle.into())?
             .into())
     }
@@ -78,10 +87,9 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         input_len: guest_types::Size,
     ) -> Result<(), guest_types::CryptoErrno> {
         let input: &[u8] = &*input_ptr.as_array(input_len).as_slice()?;
-        Ok(
-            (&*self)
-                .signature_verification_state_update(verification_state_handle.into(), input)?,
-        )
+        Ok(self
+            .ctx
+            .signature_verification_state_update(verification_state_handle.into(), input)?)
     }
 
     fn signature_verification_state_verify(
@@ -89,7 +97,7 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         verification_state_handle: guest_types::SignatureVerificationState,
         signature_handle: guest_types::Signature,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).signature_verification_state_verify(
+        Ok(self.ctx.signature_verification_state_verify(
             verification_state_handle.into(),
             signature_handle.into(),
         )?)
@@ -99,14 +107,16 @@ impl super::wasi_ephemeral_crypto_signatures::WasiEphemeralCryptoSignatures for
         &mut self,
         verification_state_handle: guest_types::SignatureVerificationState,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).signature_verification_state_close(verification_state_handle.into())?)
+        Ok(self
+            .ctx
+            .signature_verification_state_close(verification_state_handle.into())?)
     }
 
     fn signature_close(
         &mut self,
         signature_handle: guest_types::Signature,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).signature_close(signature_handle.into())?)
+        Ok(self.ctx.signature_close(signature_handle.into())?)
     }
 }
 
@@ -13,11 +13,13 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         options_handle: &guest_types::OptOptions,
     ) -> Result<guest_types::SymmetricKey, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let options_handle = match *options_handle {
             guest_types::OptOptions::Some(options_handle) => Some(options_handle),
             guest_types::OptOptions::None => None,
         };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_key_generate_managed(
                 secrets_manager_handle.into(),
                 alg_str,
@@ -36,7 +38,7 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         let key_id_buf = &mut *symmetric_key_id_ptr
             .as_array(symmetric_key_id_max_len)
             .as_slice_mut()?;
-        Ok((&*self).symmetric_key_store_managed(
+        Ok(self.ctx.symmetric_key_store_managed(
             secrets_manager_handle.into(),
             symmetric_key_handle.into(),
             key_id_buf,
@@ -49,7 +51,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         symmetric_key_old_handle: guest_types::SymmetricKey,
         symmetric_key_new_handle: guest_types::SymmetricKey,
     ) -> Result<guest_types::Version, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_key_replace_managed(
                 secrets_manager_handle.into(),
                 symmetric_key_old_handle.into(),
@@ -68,7 +71,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         let symmetric_key_id = &*symmetric_key_id_ptr
             .as_array(symmetric_key_id_len)
             .as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_key_from_id(
                 secrets_manager_handle.into(),
                 symmetric_key_id,
@@ -85,11 +89,13 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         options_handle: &guest_types::OptOptions,
     ) -> Result<guest_types::SymmetricKey, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let options_handle = match *options_handle {
             guest_types::OptOptions::Some(options_handle) => Some(options_handle),
             guest_types::OptOptions::None => None,
         };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_key_generate(alg_str, options_handle.map(Into::into))?
             .into())
     }
@@ -101,15 +107,17 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         raw_len: guest_types::Size,
     ) -> Result<guest_types::SymmetricKey, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let raw = &*raw_ptr.as_array(raw_len).as_slice()?;
-        Ok((&*self).symmetric_key_import(alg_str, raw)?.into())
+        Ok(self.ctx.symmetric_key_import(alg_str, raw)?.into())
     }
 
     fn symmetric_key_export(
         &mut self,
         symmetric_key_handle: guest_types::SymmetricKey,
     ) -> Result<guest_types::ArrayOutput, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_key_export(symmetric_key_handle.into())?
             .into())
     }
@@ -123,7 +131,7 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         let key_id_buf = &mut *symmetric_key_id_ptr
             .as_array(symmetric_key_id_max_len)
             .as_slice_mut()?;
-        let (key_id, version) = (&*self).symmetric_key_id(symmetric_key_handle.into())?;
+        let (key_id, version) = self.ctx.symmetric_key_id(symmetric_key_handle.into())?;
         ensure!(
             key_id.len() <= key_id_buf.len(),
             CryptoError::Overflow.into()
@@ -136,7 +144,7 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         &mut self,
         key_handle: guest_types::SymmetricKey,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).symmetric_key_close(key_handle.into())?)
+        Ok(self.ctx.symmetric_key_close(key_handle.into())?)
     }
 
     // --- state
@@ -148,6 +156,7 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         options_handle: &guest_types::OptOptions,
     ) -> Result<guest_types::SymmetricState, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
+        self.check_algorithm(alg_str)?;
         let key_handle = match *key_handle {
             guest_types::OptSymmetricKey::Some(key_handle) => Some(key_handle),
             guest_types::OptSymmetricKey::None => None,
@@ -156,7 +165,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
             guest_types::OptOptions::Some(options_handle) => Some(options_handle),
             guest_types::OptOptions::None => None,
         };
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_open(
                 alg_str,
                 key_handle.map(Into::into),
@@ -174,7 +184,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
         let name_str: &str = &*name_str.as_str()?;
         let value = &mut *value_ptr.as_array(value_max_len).as_slice_mut()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .options_get(symmetric_state_handle.into(), name_str, value)?
             .try_into()?)
     }
@@ -185,14 +196,18 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         name_str: &wiggle::GuestPtr<'_, str>,
     ) -> Result<u64, guest_types::CryptoErrno> {
         let name_str: &str = &*name_str.as_str()?;
-        Ok((&*self).options_get_u64(symmetric_state_handle.into(), name_str)?)
+        Ok(self
+            .ctx
+            .options_get_u64(symmetric_state_handle.into(), name_str)?)
     }
 
     fn symmetric_state_close(
         &mut self,
         symmetric_state_handle: guest_types::SymmetricState,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).symmetric_state_close(symmetric_state_handle.into())?)
+        Ok(self
+            .ctx
+            .symmetric_state_close(symmetric_state_handle.into())?)
     }
 
     fn symmetric_state_absorb(
@@ -202,7 +217,9 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         data_len: guest_types::Size,
     ) -> Result<(), guest_types::CryptoErrno> {
         let data = &*data_ptr.as_array(data_len).as_slice()?;
-        Ok((&*self).symmetric_state_absorb(symmetric_state_handle.into(), data)?)
+        Ok(self
+            .ctx
+            .symmetric_state_absorb(symmetric_state_handle.into(), data)?)
     }
 
     fn symmetric_state_squeeze(
@@ -212,14 +229,17 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         out_len: guest_types::Size,
     ) -> Result<(), guest_types::CryptoErrno> {
         let out = &mut *out_ptr.as_array(out_len).as_slice_mut()?;
-        Ok((&*self).symmetric_state_squeeze(symmetric_state_handle.into(), out)?)
+        Ok(self
+            .ctx
+            .symmetric_state_squeeze(symmetric_state_handle.into(), out)?)
     }
 
     fn symmetric_state_squeeze_tag(
         &mut self,
         symmetric_state_handle: guest_types::SymmetricState,
     ) -> Result<guest_types::SymmetricTag, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_squeeze_tag(symmetric_state_handle.into())?
             .into())
     }
@@ -230,7 +250,9 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         alg_str: &wiggle::GuestPtr<'_, str>,
     ) -> Result<guest_types::SymmetricKey, guest_types::CryptoErrno> {
         let alg_str = &*alg_str.as_str()?;
-        Ok((&*self)
+        self.check_algorithm(alg_str)?;
+        Ok(self
+            .ctx
             .symmetric_state_squeeze_key(symmetric_state_handle.into(), alg_str)?
             .into())
     }
@@ -239,7 +261,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         &mut self,
         symmetric_state_handle: guest_types::SymmetricState,
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_max_tag_len(symmetric_state_handle.into())?
             .try_into()?)
     }
@@ -254,7 +277,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
         let out = &mut *out_ptr.as_array(out_len).as_slice_mut()?;
         let data = &*data_ptr.as_array(data_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_encrypt(symmetric_state_handle.into(), out, data)?
             .try_into()?)
     }
@@ -269,7 +293,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
     ) -> Result<guest_types::SymmetricTag, guest_types::CryptoErrno> {
         let out = &mut *out_ptr.as_array(out_len).as_slice_mut()?;
         let data = &*data_ptr.as_array(data_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_encrypt_detached(symmetric_state_handle.into(), out, data)?
             .into())
     }
@@ -284,7 +309,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
         let out = &mut *out_ptr.as_array(out_len).as_slice_mut()?;
         let data = &*data_ptr.as_array(data_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_decrypt(symmetric_state_handle.into(), out, data)?
             .try_into()?)
     }
@@ -302,7 +328,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         let out = &mut *out_ptr.as_array(out_len).as_slice_mut()?;
         let data = &*data_ptr.as_array(data_len).as_slice()?;
         let raw_tag: &[u8] = &*raw_tag_ptr.as_array(raw_tag_len).as_slice()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_state_decrypt_detached(symmetric_state_handle.into(), out, data, raw_tag)?
             .try_into()?)
     }
@@ -311,7 +338,9 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         &mut self,
         symmetric_state_handle: guest_types::SymmetricState,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).symmetric_state_ratchet(symmetric_state_handle.into())?)
+        Ok(self
+            .ctx
+            .symmetric_state_ratchet(symmetric_state_handle.into())?)
     }
 
     // --- tag
@@ -320,7 +349,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         &mut self,
         symmetric_tag_handle: guest_types::SymmetricTag,
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_tag_len(symmetric_tag_handle.into())?
             .try_into()?)
     }
@@ -332,7 +362,8 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         buf_len: guest_types::Size,
     ) -> Result<guest_types::Size, guest_types::CryptoErrno> {
         let buf = &mut *buf_ptr.as_array(buf_len).as_slice_mut()?;
-        Ok((&*self)
+        Ok(self
+            .ctx
             .symmetric_tag_pull(symmetric_tag_handle.into(), buf)?
             .try_into()?)
     }
@@ -344,13 +375,15 @@ impl super::wasi_ephemeral_crypto_symmetric::WasiEphemeralCryptoSymmetric for Wa
         expected_raw_len: guest_types::Size,
     ) -> Result<(), guest_types::CryptoErrno> {
         let expected_raw = &*expected_raw_ptr.as_array(expected_raw_len).as_slice()?;
-        Ok((&*self).symmetric_tag_verify(symmetric_tag_handle.into(), expected_raw)?)
+        Ok(self
+            .ctx
+            .symmetric_tag_verify(symmetric_tag_handle.into(), expected_raw)?)
     }
 
     fn symmetric_tag_close(
         &mut self,
         symmetric_tag_handle: guest_types::SymmetricTag,
     ) -> Result<(), guest_types::CryptoErrno> {
-        Ok((&*self).symmetric_tag_close(symmetric_tag_handle.into())?)
+        Ok(self.ctx.symmetric_tag_close(symmetric_tag_handle.into())?)
     }
 }
@@ -1,35 +1,52 @@
 //! Define the Rust interface a backend must implement in order to be used by
 //! this crate. the `Box<dyn ...>` types returned by these interfaces allow
 //! implementations to maintain backend-specific state between calls.
+//!
+//! Besides the backends built into this crate, embedders can implement these
+//! traits themselves and register their backend with
+//! [crate::WasiNnCtx::with_backends]. Backends never see guest memory: they are
+//! handed copies of the guest's buffers as plain slices.
 
-use crate::witx::types::{ExecutionTarget, GraphBuilderArray, Tensor};
+use crate::witx::types::{ExecutionTarget, TensorType};
 use thiserror::Error;
 use wiggle::GuestError;
 
 /// A [Backend] contains the necessary state to load [BackendGraph]s.
-pub(crate) trait Backend {
+pub trait Backend {
     fn name(&self) -> &str;
+
+    /// Loads a graph from the buffers the guest passed to `load`, whose
+    /// number and meaning are up to the backend, to execute on `target`.
     fn load(
         &mut self,
-        builders: &GraphBuilderArray<'_>,
+        builders: &[&[u8]],
         target: ExecutionTarget,
     ) -> Result<Box<dyn BackendGraph>, BackendError>;
 }
 
 /// A [BackendGraph] can create [BackendExecutionContext]s; this is the backing
 /// implementation for a [crate::witx::types::Graph].
-pub(crate) trait BackendGraph {
+pub trait BackendGraph {
     fn init_execution_context(&mut self) -> Result<Box<dyn BackendExecutionContext>, BackendError>;
 }
 
 /// A [BackendExecutionContext] performs the actual inference; this is the
 /// backing implementation for a [crate::witx::types::GraphExecutionContext].
-pub(crate) trait BackendExecutionContext {
+pub trait BackendExecutionContext {
     fn set_input(&mut self, index: u32, tensor: &Tensor<'_>) -> Result<(), BackendError>;
     fn compute(&mut self) -> Result<(), BackendError>;
+    /// Copies the output at `index` into `destination`, returning how many
+    /// bytes it takes up.
     fn get_output(&mut self, index: u32, destination: &mut [u8]) -> Result<u32, BackendError>;
 }
 
+/// A tensor passed to [BackendExecutionContext::set_input].
+pub struct Tensor<'a> {
+    pub dimensions: &'a [u32],
+    pub tensor_type: TensorType,
+    pub data: &'a [u8],
+}
+
 /// Errors returned by a backend; [BackendError::BackendAccess] is a catch-all
 /// for failures interacting with the ML library.
 #[derive(Debug, Error)]
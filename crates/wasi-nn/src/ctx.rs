@@ -1,6 +1,7 @@
 //! Implements the base structure (i.e. [WasiNnCtx]) that will provide the
 //! implementation of the wasi-nn API.
 use crate::api::{Backend, BackendError, BackendExecutionContext, BackendGraph};
+#[cfg(feature = "openvino")]
 use crate::openvino::OpenvinoBackend;
 use crate::r#impl::UsageError;
 use crate::witx::types::{Graph, GraphEncoding, GraphExecutionContext};
@@ -20,21 +21,36 @@ pub struct Ctx {
 impl Ctx {
     /// Make a new context from the default state.
     pub fn new() -> WasiNnResult<Self> {
-        let mut backends = HashMap::new();
-        backends.insert(
-            // This is necessary because Wiggle's variant types do not derive
-            // `Hash` and `Eq`.
-            GraphEncoding::Openvino.into(),
-            Box::new(OpenvinoBackend::default()) as Box<dyn Backend>,
-        );
-        Ok(Self {
-            backends,
+        Ok(Self::with_backends(default_backends()))
+    }
+
+    fn with_backends(
+        backends: impl IntoIterator<Item = (GraphEncoding, Box<dyn Backend>)>,
+    ) -> Self {
+        Self {
+            backends: backends
+                .into_iter()
+                // This is necessary because Wiggle's variant types do not
+                // derive `Hash` and `Eq`.
+                .map(|(encoding, backend)| (encoding.into(), backend))
+                .collect(),
             graphs: Table::default(),
             executions: Table::default(),
-        })
+        }
     }
 }
 
+/// The backends built into this crate, for the encodings they handle.
+fn default_backends() -> Vec<(GraphEncoding, Box<dyn Backend>)> {
+    vec![
+        #[cfg(feature = "openvino")]
+        (
+            GraphEncoding::Openvino,
+            Box::<OpenvinoBackend>::default() as Box<dyn Backend>,
+        ),
+    ]
+}
+
 /// This struct solely wraps [Ctx] in a `RefCell`.
 pub struct WasiNnCtx {
     pub(crate) ctx: RefCell<Ctx>,
@@ -47,6 +63,17 @@ impl WasiNnCtx {
             ctx: RefCell::new(Ctx::new()?),
         })
     }
+
+    /// Make a new `WasiNnCtx` which loads graphs of each encoding with the
+    /// given backend, instead of the ones built into this crate. Graphs of
+    /// any other encoding fail to load.
+    pub fn with_backends(
+        backends: impl IntoIterator<Item = (GraphEncoding, Box<dyn Backend>)>,
+    ) -> Self {
+        Self {
+            ctx: RefCell::new(Ctx::with_backends(backends)),
+        }
+    }
 }
 
 /// Possible errors while interacting with [WasiNnCtx].
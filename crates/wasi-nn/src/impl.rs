@@ -1,4 +1,5 @@
 //! Implements the wasi-nn API.
+use crate::api;
 use crate::ctx::WasiNnResult as Result;
 use crate::witx::types::{
     ExecutionTarget, Graph, GraphBuilderArray, GraphEncoding, GraphExecutionContext, Tensor,
@@ -12,7 +13,7 @@ use wiggle::GuestPtr;
 pub enum UsageError {
     #[error("Invalid context; has the load function been called?")]
     InvalidContext,
-    #[error("Only OpenVINO's IR is currently supported, passed encoding: {0:?}")]
+    #[error("No backend is registered for the encoding: {0:?}")]
     InvalidEncoding(GraphEncoding),
     #[error("OpenVINO expects only two buffers (i.e. [ir, weights]), passed: {0}")]
     InvalidNumberOfBuilders(u32),
@@ -32,8 +33,13 @@ impl<'a> WasiEphemeralNn for WasiNnCtx {
         target: ExecutionTarget,
     ) -> Result<Graph> {
         let encoding_id: u8 = encoding.into();
+        let builders = builders
+            .iter()
+            .map(|builder| builder?.read()?.as_slice())
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        let builders = builders.iter().map(|b| &**b).collect::<Vec<_>>();
         let graph = if let Some(backend) = self.ctx.borrow_mut().backends.get_mut(&encoding_id) {
-            backend.load(builders, target)?
+            backend.load(&builders, target)?
         } else {
             return Err(UsageError::InvalidEncoding(encoding).into());
         };
@@ -58,8 +64,15 @@ impl<'a> WasiEphemeralNn for WasiNnCtx {
         index: u32,
         tensor: &Tensor<'b>,
     ) -> Result<()> {
+        let dimensions = tensor.dimensions.as_slice()?;
+        let data = tensor.data.as_slice()?;
+        let tensor = api::Tensor {
+            dimensions: &dimensions,
+            tensor_type: tensor.type_,
+            data: &data,
+        };
         if let Some(exec_context) = self.ctx.borrow_mut().executions.get_mut(exec_context_id) {
-            Ok(exec_context.set_input(index, tensor)?)
+            Ok(exec_context.set_input(index, &tensor)?)
         } else {
             Err(UsageError::InvalidGraphHandle.into())
         }
@@ -1,8 +1,11 @@
 mod api;
 mod ctx;
 mod r#impl;
+#[cfg(feature = "openvino")]
 mod openvino;
 mod witx;
 
+pub use api::{Backend, BackendError, BackendExecutionContext, BackendGraph, Tensor};
 pub use ctx::WasiNnCtx;
+pub use witx::types::{ExecutionTarget, GraphEncoding, TensorType};
 pub use witx::wasi_ephemeral_nn::add_to_linker;
@@ -1,6 +1,6 @@
 //! Implements the wasi-nn API.
-use crate::api::{Backend, BackendError, BackendExecutionContext, BackendGraph};
-use crate::witx::types::{ExecutionTarget, GraphBuilderArray, Tensor, TensorType};
+use crate::api::{Backend, BackendError, BackendExecutionContext, BackendGraph, Tensor};
+use crate::witx::types::{ExecutionTarget, TensorType};
 use openvino::{InferenceError, Layout, Precision, SetupError, TensorDesc};
 use std::sync::Arc;
 
@@ -14,12 +14,18 @@ impl Backend for OpenvinoBackend {
 
     fn load(
         &mut self,
-        builders: &GraphBuilderArray<'_>,
+        builders: &[&[u8]],
         target: ExecutionTarget,
     ) -> Result<Box<dyn BackendGraph>, BackendError> {
-        if builders.len() != 2 {
-            return Err(BackendError::InvalidNumberOfBuilders(2, builders.len()).into());
-        }
+        let (xml, weights) = match builders {
+            [xml, weights] => (xml, weights),
+            _ => {
+                return Err(BackendError::InvalidNumberOfBuilders(
+                    2,
+                    builders.len() as u32,
+                ))
+            }
+        };
 
         // Construct the context if none is present; this is done lazily (i.e.
         // upon actually loading a model) because it may fail to find and load
@@ -29,18 +35,13 @@ impl Backend for OpenvinoBackend {
             self.0.replace(openvino::Core::new(None)?);
         }
 
-        // Read the guest array.
-        let builders = builders.as_ptr();
-        let xml = builders.read()?.as_slice()?;
-        let weights = builders.add(1)?.read()?.as_slice()?;
-
         // Construct OpenVINO graph structures: `cnn_network` contains the graph
         // structure, `exec_network` can perform inference.
         let core = self
             .0
             .as_mut()
             .expect("openvino::Core was previously constructed");
-        let mut cnn_network = core.read_network_from_buffer(&xml, &weights)?;
+        let mut cnn_network = core.read_network_from_buffer(xml, weights)?;
 
         // TODO this is a temporary workaround. We need a more eligant way to specify the layout in the long run.
         // However, without this newer versions of OpenVINO will fail due to parameter mismatch.
@@ -77,17 +78,15 @@ impl BackendExecutionContext for OpenvinoExecutionContext {
         // Construct the blob structure.
         let dimensions = tensor
             .dimensions
-            .as_slice()?
             .iter()
             .map(|d| *d as usize)
             .collect::<Vec<_>>();
-        let precision = map_tensor_type_to_precision(tensor.type_);
+        let precision = map_tensor_type_to_precision(tensor.tensor_type);
 
         // TODO There must be some good way to discover the layout here; this
         // should not have to default to NHWC.
         let desc = TensorDesc::new(Layout::NHWC, &dimensions, precision);
-        let data = tensor.data.as_slice()?;
-        let blob = openvino::Blob::new(desc, &data)?;
+        let blob = openvino::Blob::new(desc, tensor.data)?;
 
         // Actually assign the blob to the request.
         self.1.set_blob(&input_name, blob)?;
@@ -1,4 +1,5 @@
 //! Contains the macro-generated implementation of wasi-nn from the its witx definition file.
+use crate::api::BackendError;
 use crate::ctx::WasiNnCtx;
 use crate::ctx::WasiNnError;
 
@@ -14,9 +15,14 @@ impl<'a> types::UserErrorConversion for WasiNnCtx {
     fn nn_errno_from_wasi_nn_error(&mut self, e: WasiNnError) -> Result<NnErrno, wiggle::Trap> {
         eprintln!("Host error: {:?}", e);
         match e {
-            WasiNnError::BackendError(_) => unimplemented!(),
-            WasiNnError::GuestError(_) => unimplemented!(),
-            WasiNnError::UsageError(_) => unimplemented!(),
+            // Failing to use the ML library at all is the host's problem, not
+            // something the guest can recover from.
+            WasiNnError::BackendError(BackendError::BackendAccess(e)) => Err(wiggle::Trap::String(
+                format!("wasi-nn backend failed: {:?}", e),
+            )),
+            WasiNnError::BackendError(_)
+            | WasiNnError::GuestError(_)
+            | WasiNnError::UsageError(_) => Ok(NnErrno::InvalidArgument),
         }
     }
 }
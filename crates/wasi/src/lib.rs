@@ -7,7 +7,25 @@
 //! Individual snapshots are available through
 //! `wasmtime_wasi::snapshots::preview_{0, 1}::Wasi::new(&Store, Rc<RefCell<WasiCtx>>)`.
 
-pub use wasi_common::{Error, WasiCtx, WasiDir, WasiFile};
+pub use wasi_common::clocks::{WasiMonotonicClock, WasiSystemClock};
+pub use wasi_common::{pipe, Error, WasiCtx, WasiDir, WasiFile, WasiOverrides};
+
+#[cfg(feature = "sync")]
+mod policy;
+#[cfg(feature = "sync")]
+pub use policy::{SandboxPolicy, SandboxPolicyBuilder};
+
+/// The types needed to implement [`WasiFile`] and [`WasiDir`], using the
+/// `async-trait` crate, for files and directories which aren't on the host
+/// filesystem. Such directories can be preopened with
+/// `WasiCtxBuilder::preopened_custom_dir`. Also the capabilities which
+/// preopened directories can be restricted to.
+pub mod fs {
+    pub use wasi_common::dir::{DirCaps, ReaddirCursor, ReaddirEntity};
+    pub use wasi_common::file::{FdFlags, FileCaps, FileType, Filestat, OFlags};
+    pub use wasi_common::vfs::{MemDir, Overlay};
+    pub use wasi_common::{ErrorExt, SystemTimeSpec};
+}
 
 /// Re-export the commonly used wasi-cap-std-sync crate here. This saves
 /// consumers of this library from having to keep additional dependencies
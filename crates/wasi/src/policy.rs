@@ -0,0 +1,471 @@
+//! A single policy for everything that limits what a guest may do.
+
+use crate::fs::{DirCaps, FileCaps};
+use crate::sync::{ambient_authority, Dir, WasiCtxBuilder};
+use crate::WasiCtx;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use wasmtime::{Config, ResourceLimiter, Store, StoreLimits, StoreLimitsBuilder};
+
+/// The WebAssembly features a policy can enable, by the names of the
+/// `--wasm-features` option of the `wasmtime` command, along with the
+/// [`Config`] method enabling each.
+const FEATURES: &[(&str, EnableFeature)] = &[
+    ("bulk-memory", Config::wasm_bulk_memory),
+    ("memory64", Config::wasm_memory64),
+    ("multi-memory", Config::wasm_multi_memory),
+    ("multi-value", Config::wasm_multi_value),
+    ("mutable-global", Config::wasm_mutable_global),
+    ("reference-types", wasm_reference_types),
+    ("sat-float-to-int", Config::wasm_sat_float_to_int),
+    ("sign-extension", Config::wasm_sign_extension),
+    ("simd", Config::wasm_simd),
+    ("threads", Config::wasm_threads),
+];
+
+type EnableFeature = fn(&mut Config, bool) -> &mut Config;
+
+/// The features a `Config` enables by default.
+const DEFAULT_FEATURES: &[&str] = &[
+    "bulk-memory",
+    "multi-value",
+    "mutable-global",
+    #[cfg(feature = "wasm-backtrace")]
+    "reference-types",
+    "sat-float-to-int",
+    "sign-extension",
+    "simd",
+];
+
+/// Enables reference types, which Wasmtime only supports along with its
+/// `wasm-backtrace` feature.
+fn wasm_reference_types(config: &mut Config, enable: bool) -> &mut Config {
+    #[cfg(feature = "wasm-backtrace")]
+    config.wasm_reference_types(enable);
+    let _ = enable; // suppress unused warnings
+    config
+}
+
+/// Everything which limits what a guest may do, in one place: the resources
+/// it may use, the WebAssembly features it may use, how long it may execute
+/// for and what it may access through WASI.
+///
+/// A policy is built with a [`SandboxPolicyBuilder`], or deserialized with
+/// [`serde`] from a file kept along with the rest of a host's configuration,
+/// and serializing it records exactly what a guest is allowed to do for
+/// review. It's then applied to each of the places these settings live:
+///
+/// * [`SandboxPolicy::configure`] enables exactly the policy's WebAssembly
+///   features in the [`Config`] of an [`Engine`](wasmtime::Engine), and fuel
+///   consumption or epoch interruption if the policy limits execution with
+///   them.
+/// * [`SandboxPolicy::configure_store`] installs the policy's resource
+///   limits in a [`Store`], along with its fuel and epoch deadline.
+/// * [`SandboxPolicy::wasi_ctx`] creates a [`WasiCtx`] granting only the
+///   policy's arguments, environment variables, stdio and directories.
+///
+/// Anything a policy doesn't mention is denied, except for the WebAssembly
+/// features enabled by default, which a policy enables unless it lists the
+/// features to enable itself.
+///
+/// # Examples
+///
+/// In TOML, a policy looks like this:
+///
+/// ```toml
+/// wasm_features = ["bulk-memory", "multi-value", "reference-types"]
+/// fuel = 10000000
+///
+/// [limits]
+/// memory_size = 67108864
+/// instances = 1
+///
+/// [wasi]
+/// args = ["guest.wasm", "--verbose"]
+/// inherit_stdout = true
+///
+/// [wasi.env]
+/// LANG = "C"
+///
+/// [[wasi.dirs]]
+/// host = "/srv/guest/data"
+/// guest = "/data"
+/// read_only = true
+/// ```
+///
+/// which is applied like this:
+///
+/// ```
+/// use wasmtime::*;
+/// use wasmtime_wasi::{SandboxPolicy, SandboxPolicyBuilder, WasiCtx};
+///
+/// struct Host {
+///     wasi: WasiCtx,
+///     limits: StoreLimits,
+/// }
+///
+/// # fn main() -> anyhow::Result<()> {
+/// let policy: SandboxPolicy = SandboxPolicyBuilder::new()
+///     .memory_size(64 << 20)
+///     .fuel(10_000_000)
+///     .arg("guest.wasm")
+///     .build();
+///
+/// let mut config = Config::new();
+/// policy.configure(&mut config)?;
+/// let engine = Engine::new(&config)?;
+///
+/// let host = Host {
+///     wasi: policy.wasi_ctx()?,
+///     limits: policy.store_limits(),
+/// };
+/// let mut store = Store::new(&engine, host);
+/// policy.configure_store(&mut store, |host| &mut host.limits)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SandboxPolicy {
+    wasm_features: Vec<String>,
+    fuel: Option<u64>,
+    epoch_deadline: Option<u64>,
+    limits: Limits,
+    wasi: WasiGrants,
+}
+
+/// The resource limits of a policy, see [`StoreLimitsBuilder`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Limits {
+    memory_size: Option<usize>,
+    total_memory_size: Option<usize>,
+    table_elements: Option<u32>,
+    instances: usize,
+    tables: usize,
+    memories: usize,
+    memory_grow_failure_trap: bool,
+}
+
+/// What a policy grants access to through WASI.
+///
+/// The tables come last, as formats like TOML can't serialize plain values
+/// after them.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WasiGrants {
+    args: Vec<String>,
+    inherit_env: bool,
+    inherit_stdin: bool,
+    inherit_stdout: bool,
+    inherit_stderr: bool,
+    env: BTreeMap<String, String>,
+    dirs: Vec<DirGrant>,
+}
+
+/// A directory of the host which a policy preopens for the guest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DirGrant {
+    host: PathBuf,
+    guest: String,
+    #[serde(default)]
+    read_only: bool,
+}
+
+impl Default for SandboxPolicy {
+    fn default() -> SandboxPolicy {
+        SandboxPolicy {
+            wasm_features: DEFAULT_FEATURES.iter().map(|f| f.to_string()).collect(),
+            fuel: None,
+            epoch_deadline: None,
+            limits: Limits::default(),
+            wasi: WasiGrants::default(),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            memory_size: None,
+            total_memory_size: None,
+            table_elements: None,
+            instances: wasmtime::DEFAULT_INSTANCE_LIMIT,
+            tables: wasmtime::DEFAULT_TABLE_LIMIT,
+            memories: wasmtime::DEFAULT_MEMORY_LIMIT,
+            memory_grow_failure_trap: false,
+        }
+    }
+}
+
+impl SandboxPolicy {
+    /// Configures `config` to enable exactly the WebAssembly features of this
+    /// policy, and the fuel consumption or epoch interruption this policy's
+    /// fuel or epoch deadline needs.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the policy names a feature which doesn't exist,
+    /// or reference types without the `wasm-backtrace` feature of this
+    /// crate.
+    pub fn configure(&self, config: &mut Config) -> Result<()> {
+        for feature in &self.wasm_features {
+            if !FEATURES.iter().any(|(name, _)| name == feature) {
+                bail!(
+                    "unknown WebAssembly feature `{}` in sandbox policy",
+                    feature
+                );
+            }
+            if cfg!(not(feature = "wasm-backtrace")) && feature == "reference-types" {
+                bail!("reference types need the `wasm-backtrace` feature of `wasmtime-wasi`");
+            }
+        }
+        for (name, enable) in FEATURES {
+            enable(config, self.wasm_features.iter().any(|f| f == name));
+        }
+        config.consume_fuel(self.fuel.is_some());
+        config.epoch_interruption(self.epoch_deadline.is_some());
+        Ok(())
+    }
+
+    /// Returns the resource limits of this policy, to be kept in the data of
+    /// a [`Store`] for [`SandboxPolicy::configure_store`].
+    pub fn store_limits(&self) -> StoreLimits {
+        let limits = &self.limits;
+        let mut builder = StoreLimitsBuilder::new()
+            .instances(limits.instances)
+            .tables(limits.tables)
+            .memories(limits.memories);
+        if let Some(limit) = limits.memory_size {
+            builder = builder.memory_size(limit);
+        }
+        if let Some(limit) = limits.total_memory_size {
+            builder = builder.total_memory_size(limit);
+        }
+        if let Some(limit) = limits.table_elements {
+            builder = builder.table_elements(limit);
+        }
+        builder.build()
+    }
+
+    /// Installs the resource limits of this policy in `store`, which are
+    /// found in the store's data with `limits`, and gives the store this
+    /// policy's fuel and epoch deadline.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the store's engine wasn't configured with
+    /// [`SandboxPolicy::configure`] to consume the policy's fuel.
+    pub fn configure_store<T>(
+        &self,
+        store: &mut Store<T>,
+        limits: impl FnMut(&mut T) -> &mut StoreLimits + Send + Sync + 'static,
+    ) -> Result<()> {
+        let mut limits = limits;
+        store.limiter(move |data| limits(data) as &mut dyn ResourceLimiter);
+        if self.limits.memory_grow_failure_trap {
+            store.memory_grow_failure_trap();
+        }
+        if let Some(fuel) = self.fuel {
+            store
+                .add_fuel(fuel)
+                .context("sandbox policy limits fuel, which isn't enabled")?;
+        }
+        if let Some(ticks) = self.epoch_deadline {
+            store.set_epoch_deadline(ticks);
+            store.epoch_deadline_trap();
+        }
+        Ok(())
+    }
+
+    /// Creates a [`WasiCtx`] granting the guest only the arguments,
+    /// environment variables, stdio and directories of this policy.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if one of the policy's directories can't be opened.
+    pub fn wasi_ctx(&self) -> Result<WasiCtx> {
+        let wasi = &self.wasi;
+        let mut builder = WasiCtxBuilder::new().args(&wasi.args)?;
+        if wasi.inherit_env {
+            builder = builder.inherit_env()?;
+        }
+        for (name, value) in &wasi.env {
+            builder = builder.env(name, value)?;
+        }
+        if wasi.inherit_stdin {
+            builder = builder.inherit_stdin();
+        }
+        if wasi.inherit_stdout {
+            builder = builder.inherit_stdout();
+        }
+        if wasi.inherit_stderr {
+            builder = builder.inherit_stderr();
+        }
+        for grant in &wasi.dirs {
+            let dir =
+                Dir::open_ambient_dir(&grant.host, ambient_authority()).with_context(|| {
+                    format!(
+                        "failed to open directory `{}` of sandbox policy",
+                        grant.host.display()
+                    )
+                })?;
+            builder = if grant.read_only {
+                builder.preopened_dir_with_caps(
+                    dir,
+                    &grant.guest,
+                    DirCaps::read_only(),
+                    FileCaps::read_only(),
+                )?
+            } else {
+                builder.preopened_dir(dir, &grant.guest)?
+            };
+        }
+        Ok(builder.build())
+    }
+}
+
+/// Used to build a [`SandboxPolicy`].
+///
+/// A new builder denies everything a guest could be granted, while limiting
+/// resources and enabling WebAssembly features the same way as a default
+/// [`Config`] and [`StoreLimits`].
+#[derive(Default)]
+pub struct SandboxPolicyBuilder(SandboxPolicy);
+
+impl SandboxPolicyBuilder {
+    /// Creates a new [`SandboxPolicyBuilder`].
+    pub fn new() -> Self {
+        Self(SandboxPolicy::default())
+    }
+
+    /// Enables or disables the WebAssembly feature `name`, which is the name
+    /// of the feature for the `--wasm-features` option of the `wasmtime`
+    /// command, such as `simd` or `reference-types`.
+    ///
+    /// Unknown features are reported by [`SandboxPolicy::configure`].
+    pub fn wasm_feature(mut self, name: &str, enable: bool) -> Self {
+        let features = &mut self.0.wasm_features;
+        features.retain(|f| f != name);
+        if enable {
+            features.push(name.to_string());
+            features.sort();
+        }
+        self
+    }
+
+    /// The fuel a store is given, see
+    /// [`Config::consume_fuel`](wasmtime::Config::consume_fuel).
+    ///
+    /// By default, execution isn't limited by fuel.
+    pub fn fuel(mut self, fuel: u64) -> Self {
+        self.0.fuel = Some(fuel);
+        self
+    }
+
+    /// The number of epoch ticks after which execution in a store traps, see
+    /// [`Config::epoch_interruption`](wasmtime::Config::epoch_interruption).
+    ///
+    /// By default, execution isn't limited by epochs.
+    pub fn epoch_deadline(mut self, ticks: u64) -> Self {
+        self.0.epoch_deadline = Some(ticks);
+        self
+    }
+
+    /// The maximum number of bytes a linear memory can grow to, see
+    /// [`StoreLimitsBuilder::memory_size`].
+    pub fn memory_size(mut self, limit: usize) -> Self {
+        self.0.limits.memory_size = Some(limit);
+        self
+    }
+
+    /// The maximum number of bytes the linear memories of a store can grow
+    /// to combined, see [`StoreLimitsBuilder::total_memory_size`].
+    pub fn total_memory_size(mut self, limit: usize) -> Self {
+        self.0.limits.total_memory_size = Some(limit);
+        self
+    }
+
+    /// The maximum number of elements in a table, see
+    /// [`StoreLimitsBuilder::table_elements`].
+    pub fn table_elements(mut self, limit: u32) -> Self {
+        self.0.limits.table_elements = Some(limit);
+        self
+    }
+
+    /// The maximum number of instances of a store, see
+    /// [`StoreLimitsBuilder::instances`].
+    pub fn instances(mut self, limit: usize) -> Self {
+        self.0.limits.instances = limit;
+        self
+    }
+
+    /// The maximum number of tables of a store, see
+    /// [`StoreLimitsBuilder::tables`].
+    pub fn tables(mut self, limit: usize) -> Self {
+        self.0.limits.tables = limit;
+        self
+    }
+
+    /// The maximum number of linear memories of a store, see
+    /// [`StoreLimitsBuilder::memories`].
+    pub fn memories(mut self, limit: usize) -> Self {
+        self.0.limits.memories = limit;
+        self
+    }
+
+    /// Whether a `memory.grow` beyond the limits traps rather than returning
+    /// -1, see [`Store::memory_grow_failure_trap`].
+    pub fn memory_grow_failure_trap(mut self, enable: bool) -> Self {
+        self.0.limits.memory_grow_failure_trap = enable;
+        self
+    }
+
+    /// Adds an argument passed to the guest.
+    pub fn arg(mut self, arg: &str) -> Self {
+        self.0.wasi.args.push(arg.to_string());
+        self
+    }
+
+    /// Adds an environment variable passed to the guest.
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.0.wasi.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Whether the guest is passed the environment variables of the host
+    /// process, along with those added with [`SandboxPolicyBuilder::env`].
+    pub fn inherit_env(mut self, enable: bool) -> Self {
+        self.0.wasi.inherit_env = enable;
+        self
+    }
+
+    /// Whether the guest can use the stdin, stdout and stderr of the host
+    /// process. By default, none of them are available.
+    pub fn inherit_stdio(mut self, stdin: bool, stdout: bool, stderr: bool) -> Self {
+        let wasi = &mut self.0.wasi;
+        wasi.inherit_stdin = stdin;
+        wasi.inherit_stdout = stdout;
+        wasi.inherit_stderr = stderr;
+        self
+    }
+
+    /// Preopens the directory `host` of the host at `guest` for the guest,
+    /// only for reading and not for modifying it if `read_only` is set.
+    pub fn preopened_dir(mut self, host: impl Into<PathBuf>, guest: &str, read_only: bool) -> Self {
+        self.0.wasi.dirs.push(DirGrant {
+            host: host.into(),
+            guest: guest.to_string(),
+            read_only,
+        });
+        self
+    }
+
+    /// Consumes this builder and returns the [`SandboxPolicy`].
+    pub fn build(self) -> SandboxPolicy {
+        self.0
+    }
+}
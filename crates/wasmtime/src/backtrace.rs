@@ -0,0 +1,185 @@
+//! Capturing the native stack of the current thread with its WebAssembly
+//! frames resolved, for embedders to include guest context in their own
+//! diagnostics.
+
+use crate::module::GlobalModuleRegistry;
+use crate::store::AsContext;
+use crate::FrameInfo;
+use std::ffi::c_void;
+use std::fmt;
+
+/// A backtrace of the native stack of the current thread, with the frames of
+/// WebAssembly functions interleaved with those of the host.
+///
+/// Backtraces are captured by walking the stack with the system unwinder,
+/// which walks through the code Wasmtime compiles using the unwind
+/// information registered for it (SystemV-style `.eh_frame` information or,
+/// on Windows, function tables). Each frame whose program counter is within a
+/// WebAssembly function is resolved to that function's index and the offset
+/// of the instruction within its module, while every other frame is reported
+/// as a host frame.
+///
+/// Unlike [`Trap::trace`](crate::Trap::trace), which is only available once
+/// WebAssembly has trapped, this can be captured at any point, such as within
+/// a host function to record how the guest called it.
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let mut store = Store::<()>::default();
+/// let report = Func::wrap(&mut store, |caller: Caller<'_, ()>| {
+///     let backtrace = Backtrace::capture(&caller);
+///     let names = backtrace
+///         .wasm_frames()
+///         .map(|frame| frame.func_name().unwrap().to_string())
+///         .collect::<Vec<_>>();
+///     assert_eq!(names, ["inner", "outer"]);
+/// });
+/// let module = Module::new(
+///     store.engine(),
+///     r#"
+///         (module
+///             (import "" "report" (func $report))
+///             (func $outer (export "run") call $inner)
+///             (func $inner call $report))
+///     "#,
+/// )?;
+/// let instance = Instance::new(&mut store, &module, &[report.into()])?;
+/// let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+/// run.call(&mut store, ())?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Backtrace {
+    frames: Vec<BacktraceFrame>,
+}
+
+/// A frame of a [`Backtrace`], which is either the frame of a WebAssembly
+/// function or a host frame.
+#[derive(Debug)]
+pub struct BacktraceFrame {
+    pc: usize,
+    wasm: Option<FrameInfo>,
+}
+
+impl Backtrace {
+    /// Captures the stack of the current thread, resolving the frames of the
+    /// WebAssembly functions of modules instantiated within `store`.
+    ///
+    /// Frames of WebAssembly functions belonging to other stores are reported
+    /// as host frames.
+    pub fn capture(store: impl AsContext) -> Backtrace {
+        let store = store.as_context();
+        let modules = store.0.modules();
+        Backtrace::capture_with(|pc| modules.lookup_frame_info(pc).map(|(info, _)| info))
+    }
+
+    /// Captures the stack of the current thread, resolving the frames of the
+    /// WebAssembly functions of any module which is currently loaded.
+    ///
+    /// This doesn't need access to a store, so it can be used where none is
+    /// available, such as within a panic hook. Resolving frames takes a lock
+    /// shared with loading and dropping modules, so a panic raised while a
+    /// module is being loaded or dropped may deadlock here.
+    pub fn capture_all() -> Backtrace {
+        Backtrace::capture_with(|pc| {
+            GlobalModuleRegistry::with(|modules| modules.lookup_frame_info(pc))
+                .map(|(info, _, _)| info)
+        })
+    }
+
+    fn capture_with(lookup: impl Fn(usize) -> Option<FrameInfo>) -> Backtrace {
+        let native_trace = wasmtime_runtime::Backtrace::new();
+        let frames = native_trace
+            .frames()
+            .iter()
+            .map(|frame| frame.ip() as usize)
+            .filter(|pc| *pc != 0)
+            .map(|pc| BacktraceFrame {
+                pc,
+                // Program counters on the stack point after the call
+                // instruction, so the instruction before it is looked up.
+                wasm: lookup(pc - 1),
+            })
+            .collect();
+        Backtrace { frames }
+    }
+
+    /// Returns all of the frames of this backtrace, starting with the most
+    /// recently called, which is within Wasmtime itself.
+    pub fn frames(&self) -> &[BacktraceFrame] {
+        &self.frames
+    }
+
+    /// Returns the WebAssembly frames of this backtrace, starting with the
+    /// most recently called.
+    pub fn wasm_frames(&self) -> impl Iterator<Item = &FrameInfo> + '_ {
+        self.frames.iter().filter_map(|frame| frame.wasm())
+    }
+}
+
+impl BacktraceFrame {
+    /// Returns the program counter of this frame, which is the address
+    /// execution returns to in it.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    /// Returns the WebAssembly function this frame belongs to and the
+    /// instruction within it, or `None` if this is a host frame.
+    pub fn wasm(&self) -> Option<&FrameInfo> {
+        self.wasm.as_ref()
+    }
+
+    /// Returns whether this is a host frame rather than a WebAssembly one.
+    ///
+    /// Host frames include those of Wasmtime's trampolines between host and
+    /// WebAssembly code, which aren't WebAssembly functions.
+    pub fn is_host(&self) -> bool {
+        self.wasm.is_none()
+    }
+
+    /// Returns the name of the host symbol this frame is within, as reported
+    /// by the system's symbol information, or `None` if this is a WebAssembly
+    /// frame or the symbol isn't known.
+    ///
+    /// Resolving symbols reads the debug information of the process and is
+    /// relatively slow, so this is done on demand.
+    pub fn host_symbol(&self) -> Option<String> {
+        if self.wasm.is_some() {
+            return None;
+        }
+        let mut name = None;
+        backtrace::resolve((self.pc - 1) as *mut c_void, |symbol| {
+            if name.is_none() {
+                name = symbol.name().map(|n| n.to_string());
+            }
+        });
+        name
+    }
+}
+
+impl fmt::Display for Backtrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, frame) in self.frames.iter().enumerate() {
+            write!(f, "  {:>3}: ", i)?;
+            match &frame.wasm {
+                Some(info) => {
+                    if let Some(offset) = info.module_offset() {
+                        write!(f, "{:#6x} - ", offset)?;
+                    }
+                    write!(f, "{}", info.symbol_name())?;
+                }
+                None => {
+                    write!(f, "{:#x} - <host>", frame.pc)?;
+                    if let Some(name) = frame.host_symbol() {
+                        write!(f, " {}", name)?;
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,88 @@
+//! Hooks which are shown the code generated for functions as they're
+//! compiled.
+
+use wasmtime_environ::{FuncIndex, FunctionHook};
+
+/// A hook which is shown what the compiler generated for each function it
+/// compiles, configured with
+/// [`Config::compilation_hook`](crate::Config::compilation_hook).
+///
+/// This can be used to log or diff the code generated for particular
+/// functions, for example across upgrades of Wasmtime. Rendering a function's
+/// IR and disassembly takes time, so it's only done for the functions
+/// [`CompilationHook::filter`] accepts.
+///
+/// Functions are reported when they're compiled, which for modules created
+/// with [`Module::new`](crate::Module::new) and similar is while the module
+/// is created, possibly on several threads at once, and with
+/// [`Config::lazy_compilation`](crate::Config::lazy_compilation) is when
+/// they're first called. Modules which are deserialized or loaded from the
+/// compilation cache aren't compiled, so their functions aren't reported.
+pub trait CompilationHook: Send + Sync {
+    /// Returns whether the function with index `index` within its module,
+    /// which is named `name` by the module's name section, is reported to
+    /// [`CompilationHook::function_compiled`].
+    ///
+    /// All functions are reported by default.
+    fn filter(&self, index: u32, name: Option<&str>) -> bool {
+        let _ = (index, name);
+        true
+    }
+
+    /// Receives what was generated for a function which
+    /// [`CompilationHook::filter`] accepted.
+    fn function_compiled(&self, function: &FunctionListing<'_>);
+}
+
+/// What the compiler generated for a function, which is passed to
+/// [`CompilationHook::function_compiled`].
+pub struct FunctionListing<'a> {
+    inner: &'a wasmtime_environ::FunctionListing<'a>,
+}
+
+impl<'a> FunctionListing<'a> {
+    /// Returns the index of the function within its module.
+    pub fn index(&self) -> u32 {
+        self.inner.index.as_u32()
+    }
+
+    /// Returns the name of the function from its module's name section, if it
+    /// has one.
+    pub fn name(&self) -> Option<&'a str> {
+        self.inner.name
+    }
+
+    /// Returns the Cranelift IR of the function once it's been optimized, or
+    /// `None` if it was compiled by the
+    /// [baseline compiler](crate::Strategy::Baseline).
+    pub fn clif(&self) -> Option<&'a str> {
+        self.inner.ir
+    }
+
+    /// Returns the function's machine code, before any of its calls or other
+    /// references to code outside of it are relocated.
+    pub fn code(&self) -> &'a [u8] {
+        self.inner.code
+    }
+
+    /// Returns the compiler's listing of the function's final instructions,
+    /// with their registers allocated, or `None` if it was compiled by the
+    /// [baseline compiler](crate::Strategy::Baseline).
+    pub fn disassembly(&self) -> Option<&'a str> {
+        self.inner.disassembly
+    }
+}
+
+/// Adapts a [`CompilationHook`] to the hook the compiler reports to.
+pub(crate) struct HookAdapter<T>(pub(crate) T);
+
+impl<T: CompilationHook> FunctionHook for HookAdapter<T> {
+    fn wants(&self, index: FuncIndex, name: Option<&str>) -> bool {
+        self.0.filter(index.as_u32(), name)
+    }
+
+    fn compiled(&self, function: &wasmtime_environ::FunctionListing<'_>) {
+        self.0
+            .function_compiled(&FunctionListing { inner: function });
+    }
+}
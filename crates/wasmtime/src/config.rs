@@ -1,5 +1,9 @@
 use crate::memory::MemoryCreator;
 use crate::trampoline::MemoryCreatorProxy;
+#[cfg(compiler)]
+use crate::FuncType;
+#[cfg(compiler)]
+use anyhow::Context;
 use anyhow::{bail, Result};
 use serde::{Deserialize, Serialize};
 use std::cmp;
@@ -10,9 +14,13 @@ use std::sync::Arc;
 use wasmparser::WasmFeatures;
 #[cfg(feature = "cache")]
 use wasmtime_cache::CacheConfig;
+#[cfg(compiler)]
+use wasmtime_environ::{Compiler, FunctionHook};
 use wasmtime_environ::{CompilerBuilder, Tunables};
-use wasmtime_jit::{JitDumpAgent, NullProfilerAgent, ProfilingAgent, VTuneAgent};
-use wasmtime_runtime::{InstanceAllocator, OnDemandInstanceAllocator, RuntimeMemoryCreator};
+use wasmtime_jit::{JitDumpAgent, NullProfilerAgent, PerfMapAgent, ProfilingAgent, VTuneAgent};
+use wasmtime_runtime::{
+    DefaultMemoryCreator, InstanceAllocator, OnDemandInstanceAllocator, RuntimeMemoryCreator,
+};
 
 #[cfg(feature = "pooling-allocator")]
 pub use wasmtime_runtime::{InstanceLimits, PoolingAllocationStrategy};
@@ -82,7 +90,9 @@ impl Default for ModuleVersionStrategy {
 /// [`Engine::new()`](crate::Engine::new)
 pub struct Config {
     #[cfg(compiler)]
-    pub(crate) compiler: Box<dyn CompilerBuilder>,
+    pub(crate) compiler: CompilerConfig,
+    #[cfg(compiler)]
+    pub(crate) host_trampoline_signatures: Option<Vec<FuncType>>,
     pub(crate) tunables: Tunables,
     #[cfg(feature = "cache")]
     pub(crate) cache_config: CacheConfig,
@@ -91,15 +101,23 @@ pub struct Config {
     pub(crate) allocation_strategy: InstanceAllocationStrategy,
     pub(crate) max_wasm_stack: usize,
     pub(crate) features: WasmFeatures,
+    pub(crate) deterministic: bool,
     pub(crate) wasm_backtrace_details_env_used: bool,
     #[cfg(feature = "async")]
     pub(crate) async_stack_size: usize,
     pub(crate) async_support: bool,
     pub(crate) module_version: ModuleVersionStrategy,
     pub(crate) parallel_compilation: bool,
+    pub(crate) parallel_compilation_threads: Option<usize>,
+    pub(crate) huge_pages_for_code: bool,
+    pub(crate) huge_pages_for_memories: bool,
+    pub(crate) memory_protection_keys: MpkEnabled,
+    pub(crate) max_memory_protection_keys: usize,
+    pub(crate) tier_up_threshold: u32,
     pub(crate) memory_init_cow: bool,
     pub(crate) memory_guaranteed_dense_image_size: u64,
     pub(crate) force_memory_init_memfd: bool,
+    pub(crate) install_signal_handlers: bool,
 }
 
 impl Config {
@@ -109,7 +127,9 @@ impl Config {
         let mut ret = Self {
             tunables: Tunables::default(),
             #[cfg(compiler)]
-            compiler: compiler_builder(Strategy::Auto).unwrap(),
+            compiler: compiler_builder().unwrap(),
+            #[cfg(compiler)]
+            host_trampoline_signatures: None,
             #[cfg(feature = "cache")]
             cache_config: CacheConfig::new_cache_disabled(),
             profiler: Arc::new(NullProfilerAgent),
@@ -126,14 +146,22 @@ impl Config {
             max_wasm_stack: 512 * 1024,
             wasm_backtrace_details_env_used: false,
             features: WasmFeatures::default(),
+            deterministic: false,
             #[cfg(feature = "async")]
             async_stack_size: 2 << 20,
             async_support: false,
             module_version: ModuleVersionStrategy::default(),
             parallel_compilation: true,
+            parallel_compilation_threads: None,
+            huge_pages_for_code: false,
+            huge_pages_for_memories: false,
+            memory_protection_keys: MpkEnabled::Disable,
+            max_memory_protection_keys: 16,
+            tier_up_threshold: 10_000,
             memory_init_cow: true,
             memory_guaranteed_dense_image_size: 16 << 20,
             force_memory_init_memfd: false,
+            install_signal_handlers: true,
         };
         #[cfg(compiler)]
         {
@@ -305,6 +333,35 @@ impl Config {
         self
     }
 
+    /// Configures whether a core dump of the guest's state is captured when a
+    /// trap happens.
+    ///
+    /// When enabled, any [`Trap`](crate::Trap) which is raised by, or unwinds
+    /// through, WebAssembly code will carry a
+    /// [`WasmCoreDump`](crate::WasmCoreDump) available through
+    /// [`Trap::coredump`](crate::Trap::coredump). The core dump records the
+    /// wasm stack along with the contents of every linear memory and global in
+    /// the store, and it can be serialized to the standard wasm core dump
+    /// format to debug crashes offline.
+    ///
+    /// Each frame records the instance and function executing it, the
+    /// instruction it was executing and the values of the function's locals.
+    /// The operand stack isn't recorded. To find them, compiled code keeps a
+    /// record of each frame on the stack and spills the function's locals to
+    /// it before each instruction, which slows down execution considerably,
+    /// and modules compiled with and without this option are incompatible.
+    ///
+    /// Note that capturing a core dump copies all linear memories in the store,
+    /// which can be expensive for large memories.
+    ///
+    /// By default this option is `false`.
+    #[cfg(feature = "coredump")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "coredump")))]
+    pub fn coredump_on_trap(&mut self, enable: bool) -> &mut Self {
+        self.tunables.coredump_frames = enable;
+        self
+    }
+
     /// Configures whether execution of WebAssembly will "consume fuel" to
     /// either halt or yield execution as desired.
     ///
@@ -410,6 +467,36 @@ impl Config {
         self
     }
 
+    /// Configures whether a pointer to the store's fuel and epoch deadline is
+    /// kept in a register reserved for it while wasm runs.
+    ///
+    /// Code checking [fuel](Config::consume_fuel) or
+    /// [epochs](Config::epoch_interruption) otherwise loads this pointer from
+    /// the instance at the start of each function. When enabled, the
+    /// trampolines which enter wasm from the host put it in a register which
+    /// generated code doesn't otherwise use (`r15` on x86_64 and `x21` on
+    /// aarch64) and restore the register's previous value on the way out. To
+    /// do so calls through [`TypedFunc`](crate::TypedFunc), and of start
+    /// functions, go through a trampoline which takes the function's
+    /// arguments natively, rather than calling the function directly, while
+    /// [`Func::call`](crate::Func::call) keeps using the trampoline which
+    /// passes them through an array.
+    ///
+    /// Modules compiled with and without this option can't be loaded into
+    /// the same engine, since calls between them would see the wrong value
+    /// in the register.
+    ///
+    /// # Errors
+    ///
+    /// [`Engine::new`](crate::Engine::new) fails if this is enabled for a
+    /// target other than x86_64 or aarch64.
+    ///
+    /// By default this option is `false`.
+    pub fn pinned_context_register(&mut self, enable: bool) -> &mut Self {
+        self.tunables.pinned_context_register = enable;
+        self
+    }
+
     /// Configures the maximum amount of stack space available for
     /// executing WebAssembly code.
     ///
@@ -429,6 +516,9 @@ impl Config {
     /// on stack overflow, a host function that overflows the stack will
     /// abort the process.
     ///
+    /// Individual stores can be given a different limit with
+    /// [`Store::max_wasm_stack`](crate::Store::max_wasm_stack).
+    ///
     /// By default this option is 512 KiB.
     pub fn max_wasm_stack(&mut self, size: usize) -> Result<&mut Self> {
         #[cfg(feature = "async")]
@@ -571,6 +661,34 @@ impl Config {
         self
     }
 
+    /// Configures the largest length, in bytes, of a `memory.copy` or
+    /// `memory.fill` instruction which is compiled to inline loads and
+    /// stores.
+    ///
+    /// Bulk memory instructions are otherwise performed by calling into
+    /// Wasmtime, which is quick for long copies but has a fixed overhead
+    /// that dominates short ones. Compilers targeting WebAssembly emit lots
+    /// of short copies of a constant length, such as for copying structs,
+    /// and those no longer than this threshold are compiled to a bounds
+    /// check and a few loads and stores, using 128-bit vectors on x86_64 and
+    /// aarch64 when SIMD is enabled. Instructions whose length isn't a
+    /// constant always call into Wasmtime. A threshold of 0 disables this.
+    ///
+    /// The threshold can be at most 256 bytes, since inline copies keep all
+    /// of their bytes in registers at once.
+    ///
+    /// An inline instruction traps before writing anything if it runs off
+    /// the end of its memory, but one whose destination starts in a range
+    /// made read-only with
+    /// [`Memory::protect_read_only`](crate::Memory::protect_read_only) may
+    /// write the rest of its destination before it traps.
+    ///
+    /// By default this is 64 bytes.
+    pub fn bulk_memory_inline_threshold(&mut self, bytes: u32) -> &mut Self {
+        self.tunables.bulk_memory_inline_threshold = bytes;
+        self
+    }
+
     /// Configures whether the WebAssembly multi-value [proposal] will
     /// be enabled for compilation.
     ///
@@ -585,6 +703,49 @@ impl Config {
         self
     }
 
+    /// Configures whether the WebAssembly non-trapping float-to-int
+    /// conversions [proposal] will be enabled for compilation.
+    ///
+    /// This feature gates the `trunc_sat` instructions, such as
+    /// `i32.trunc_sat_f32_s`, being in a module. These convert floats to
+    /// integers without trapping, saturating values which are out of range of
+    /// the integer type and converting NaN to zero.
+    ///
+    /// This is `true` by default.
+    ///
+    /// [proposal]: https://github.com/webassembly/nontrapping-float-to-int-conversions
+    pub fn wasm_sat_float_to_int(&mut self, enable: bool) -> &mut Self {
+        self.features.saturating_float_to_int = enable;
+        self
+    }
+
+    /// Configures whether the WebAssembly sign-extension operators [proposal]
+    /// will be enabled for compilation.
+    ///
+    /// This feature gates instructions such as `i32.extend8_s` and
+    /// `i64.extend32_s` being in a module.
+    ///
+    /// This is `true` by default.
+    ///
+    /// [proposal]: https://github.com/webassembly/sign-extension-ops
+    pub fn wasm_sign_extension(&mut self, enable: bool) -> &mut Self {
+        self.features.sign_extension = enable;
+        self
+    }
+
+    /// Configures whether the WebAssembly import/export of mutable globals
+    /// [proposal] will be enabled for compilation.
+    ///
+    /// This feature gates modules importing or exporting mutable globals.
+    ///
+    /// This is `true` by default.
+    ///
+    /// [proposal]: https://github.com/webassembly/mutable-global
+    pub fn wasm_mutable_global(&mut self, enable: bool) -> &mut Self {
+        self.features.mutable_global = enable;
+        self
+    }
+
     /// Configures whether the WebAssembly multi-memory [proposal] will
     /// be enabled for compilation.
     ///
@@ -630,10 +791,48 @@ impl Config {
     #[cfg(compiler)]
     #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
     pub fn strategy(&mut self, strategy: Strategy) -> Result<&mut Self> {
-        self.compiler = compiler_builder(strategy)?;
+        let baseline = match strategy {
+            Strategy::Auto | Strategy::Cranelift => false,
+            Strategy::Baseline => true,
+        };
+        self.compiler
+            .set("wasmtime_baseline", if baseline { "true" } else { "false" })?;
         Ok(self)
     }
 
+    /// Compiles wasm modules with the compiler built by `builder` instead of
+    /// Cranelift.
+    ///
+    /// This allows experimenting with other code generators, or testing the
+    /// runtime with a compiler which wraps Cranelift, without changing
+    /// Wasmtime itself. The compiler implements
+    /// [`wasmtime_environ::Compiler`], and must generate code which follows
+    /// the same conventions as Cranelift's: the layout of the `VMContext`, how
+    /// traps are reported and so on.
+    ///
+    /// Every setting of the compiler made with this `Config`, whether before
+    /// or after this method is called, is applied to `builder` when an
+    /// [`Engine`](crate::Engine) is created: the target of
+    /// [`Config::target`], the flags of [`Config::cranelift_opt_level`],
+    /// [`Config::cranelift_flag_set`] and the like, the hook of
+    /// [`Config::compilation_hook`], and the settings Wasmtime itself relies
+    /// on, such as whether to emit unwind information. They're applied in the
+    /// order they were made, with
+    /// [`CompilerBuilder::set`](wasmtime_environ::CompilerBuilder::set) and
+    /// its siblings, and are still checked by Cranelift when they're made.
+    /// [`Engine::new`](crate::Engine::new) fails if `builder` rejects any of
+    /// them. The settings a compiler reports with
+    /// [`Compiler::flags`](wasmtime_environ::Compiler::flags) are also
+    /// checked to be compatible with Wasmtime before compiling anything.
+    ///
+    /// Calling this again replaces `builder`.
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn custom_compiler(&mut self, builder: Box<dyn CompilerBuilder>) -> &mut Self {
+        self.compiler.custom = Some(builder);
+        self
+    }
+
     /// Creates a default profiler based on the profiling strategy chosen.
     ///
     /// Profiler creation calls the type's default initializer where the purpose is
@@ -641,12 +840,26 @@ impl Config {
     pub fn profiler(&mut self, profile: ProfilingStrategy) -> Result<&mut Self> {
         self.profiler = match profile {
             ProfilingStrategy::JitDump => Arc::new(JitDumpAgent::new()?) as Arc<dyn ProfilingAgent>,
+            ProfilingStrategy::PerfMap => Arc::new(PerfMapAgent::new()?) as Arc<dyn ProfilingAgent>,
             ProfilingStrategy::VTune => Arc::new(VTuneAgent::new()?) as Arc<dyn ProfilingAgent>,
             ProfilingStrategy::None => Arc::new(NullProfilerAgent),
         };
         Ok(self)
     }
 
+    /// Shows `hook` what the compiler generates for each function it compiles:
+    /// its optimized Cranelift IR, its machine code and a listing of its
+    /// instructions.
+    ///
+    /// See [`CompilationHook`](crate::CompilationHook) for more information.
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn compilation_hook(&mut self, hook: impl crate::CompilationHook + 'static) -> &mut Self {
+        self.compiler
+            .function_hook(Arc::new(crate::compilation_hook::HookAdapter(hook)));
+        self
+    }
+
     /// Configures whether the debug verifier of Cranelift is enabled or not.
     ///
     /// When Cranelift is used as a code generation backend this will configure
@@ -704,6 +917,41 @@ impl Config {
         self
     }
 
+    /// Configures whether WebAssembly execution is guaranteed to produce the
+    /// same results on every host.
+    ///
+    /// WebAssembly is almost entirely deterministic, but a few behaviors are
+    /// left up to the host. When this option is enabled:
+    ///
+    /// * NaN-canonicalization is performed by Cranelift, as with
+    ///   [`Config::cranelift_nan_canonicalization`], so the bit pattern of NaN
+    ///   results of floating point operations doesn't depend on the host CPU.
+    ///
+    /// * The threads proposal, whose shared memories may be raced on by many
+    ///   threads, can't be enabled. Creating an [`Engine`](crate::Engine)
+    ///   with this option and [`Config::wasm_threads`] both enabled fails.
+    ///
+    /// * A failure of the host to allocate memory while growing a linear
+    ///   memory raises a trap instead of returning -1 from `memory.grow`. The
+    ///   memory's maximum size, and the limits of a
+    ///   [`ResourceLimiter`](crate::ResourceLimiter), are still reported to the
+    ///   guest as a failed `memory.grow` since they don't depend on the host.
+    ///
+    /// Other sources of nondeterminism are up to the embedder. Notably, host
+    /// functions must be deterministic themselves, fuel rather than wall-clock
+    /// time should be used to limit execution, and WASI's clocks and random
+    /// numbers should be stubbed out, for example with
+    /// `WasiCtxBuilder::deterministic` from the `wasmtime-wasi` crate. Stack
+    /// overflow happens at the same point for a given module and
+    /// [`Config::max_wasm_stack`], as long as the same version of Wasmtime
+    /// compiles the module for the same target.
+    ///
+    /// By default this option is `false`.
+    pub fn deterministic(&mut self, enable: bool) -> &mut Self {
+        self.deterministic = enable;
+        self
+    }
+
     /// Allows setting a Cranelift boolean flag or preset. This allows
     /// fine-tuning of Cranelift settings.
     ///
@@ -734,6 +982,10 @@ impl Config {
     /// Note that this is marked as unsafe, because setting the wrong flag might break invariants,
     /// resulting in execution hazards.
     ///
+    /// Target-specific flags, such as `has_avx` on x86-64, can also be set here. The flags of the
+    /// features detected on the host are enabled by default unless [`Config::target`] is used, and
+    /// modules compiled with a feature the host lacks fail to load on it.
+    ///
     /// # Errors
     ///
     /// This method can fail if the flag's name does not exist, or the value is not appropriate for
@@ -819,6 +1071,67 @@ impl Config {
         self
     }
 
+    /// Configures whether the pooling allocator stripes linear memories
+    /// across memory protection keys (MPK).
+    ///
+    /// Memory protection keys are supported by some x86_64 CPUs on Linux.
+    /// Each page of memory is tagged with a key, and access to the pages of
+    /// each key can be disabled per-thread without a system call. With this
+    /// enabled each [`Store`](crate::Store) is assigned one of the keys, and
+    /// its wasm only runs with access to the memories tagged with it. The
+    /// memory slots of stores with other keys are then as inaccessible to it
+    /// as guard pages, so the slots of the pooling allocator are interleaved
+    /// to be a fraction of [`Config::static_memory_maximum_size`] plus
+    /// [`Config::static_memory_guard_size`] apart, and many more fit into the
+    /// same amount of virtual address space. The bounds checks of generated
+    /// code are unaffected.
+    ///
+    /// When a store enters wasm the current thread's access is restricted,
+    /// and when wasm calls a host function or returns it's restored, so
+    /// host code is unaffected as well. Host code called by wasm through
+    /// other means, such as a [`ResourceLimiter`](crate::ResourceLimiter),
+    /// runs with access only to its own store's memories. Threads which
+    /// haven't entered wasm and weren't spawned after a store was created may
+    /// not have access to the memories of stores using a key, and should
+    /// only access them after creating a store or calling into wasm.
+    ///
+    /// Each stripe has its own share of the pool's instance slots, so a
+    /// store's instantiation may fail due to the instance limit before all
+    /// of the pool's slots are allocated. Stores are assigned keys
+    /// round-robin.
+    ///
+    /// This has no effect unless the pooling allocator is used, see
+    /// [`Config::allocation_strategy`]. With [`MpkEnabled::Enable`] creating
+    /// an [`Engine`](crate::Engine) fails if the host doesn't support memory
+    /// protection keys or [`Config::async_support`] is enabled, since
+    /// suspended wasm would leave the thread's access restricted. With
+    /// [`MpkEnabled::Auto`] they're used only if they can be.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to [`MpkEnabled::Disable`].
+    #[cfg(feature = "pooling-allocator")]
+    pub fn memory_protection_keys(&mut self, enable: MpkEnabled) -> &mut Self {
+        self.memory_protection_keys = enable;
+        self
+    }
+
+    /// Configures the maximum number of memory protection keys that the
+    /// pooling allocator stripes linear memories across, see
+    /// [`Config::memory_protection_keys`].
+    ///
+    /// Hosts have at most 15 keys to allocate, which are shared by all of the
+    /// engines in a process, and striping needs at least 2.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to 16, which allocates all available keys.
+    #[cfg(feature = "pooling-allocator")]
+    pub fn max_memory_protection_keys(&mut self, max: usize) -> &mut Self {
+        self.max_memory_protection_keys = max;
+        self
+    }
+
     /// Configures the maximum size, in bytes, where a linear memory is
     /// considered static, above which it'll be considered dynamic.
     ///
@@ -1081,6 +1394,404 @@ impl Config {
         self
     }
 
+    /// Configures whether the JIT code of modules is backed by transparent
+    /// huge pages.
+    ///
+    /// Backing code with huge pages reduces the iTLB misses of executing it,
+    /// which matters most for modules with lots of code. On Linux this advises
+    /// the kernel with `madvise(MADV_HUGEPAGE)` to use huge pages for each
+    /// module's text section, which it does in the background as `khugepaged`
+    /// gets to it. This requires transparent huge pages to be enabled in
+    /// either `always` or `madvise` mode, and only the parts of a text section
+    /// covering whole, aligned huge pages are affected. Functions compiled
+    /// with [`Config::lazy_compilation`] each have their own small allocation
+    /// and aren't affected.
+    ///
+    /// Huge pages explicitly reserved through `hugetlbfs` aren't used, since
+    /// Wasmtime changes the protection of code and memories at the granularity
+    /// of native pages. On platforms other than Linux this setting does
+    /// nothing.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to `false`.
+    pub fn huge_pages_for_code(&mut self, enable: bool) -> &mut Self {
+        self.huge_pages_for_code = enable;
+        self
+    }
+
+    /// Configures whether linear memories are backed by transparent huge
+    /// pages.
+    ///
+    /// On Linux this advises the kernel with `madvise(MADV_HUGEPAGE)` to use
+    /// huge pages for the whole reservation of each linear memory, or of the
+    /// pooling allocator's memory pool, so pages are faulted in as huge
+    /// pages when their memory is first accessed. This trades more resident
+    /// memory for fewer TLB misses when accessing linear memory. Pages mapped
+    /// copy-on-write from a module's initialization image, as configured by
+    /// [`Config::memory_init_cow`], aren't affected.
+    ///
+    /// Memories created by a host-defined [`MemoryCreator`] aren't affected
+    /// either. As with [`Config::huge_pages_for_code`] this does nothing on
+    /// platforms other than Linux.
+    ///
+    /// ## Default
+    ///
+    /// This value defaults to `false`.
+    pub fn huge_pages_for_memories(&mut self, enable: bool) -> &mut Self {
+        self.huge_pages_for_memories = enable;
+        self
+    }
+
+    /// Configures whether generated code may rely on signals to raise traps.
+    ///
+    /// By default Wasmtime installs signal handlers (or, on Windows, a
+    /// vectored exception handler) when the first [`Engine`](crate::Engine) is
+    /// created. Out-of-bounds accesses to linear memory then fault on guard
+    /// pages rather than being checked explicitly, and other traps, such as
+    /// `unreachable` or stack overflow, are raised by illegal instructions.
+    /// This is the fastest way to run WebAssembly but it requires Wasmtime's
+    /// handlers to remain installed, which may conflict with hosts that own
+    /// the process's signal handling themselves.
+    ///
+    /// When this option is disabled:
+    ///
+    /// * Every access to linear memory is bounds-checked explicitly. The
+    ///   [`Config::static_memory_maximum_size`],
+    ///   [`Config::static_memory_guard_size`],
+    ///   [`Config::dynamic_memory_guard_size`] and
+    ///   [`Config::guard_before_linear_memory`] options are ignored and all
+    ///   memories are dynamic memories without guard regions.
+    ///
+    /// * All traps, including stack overflow, are raised by a call from
+    ///   compiled code into Wasmtime, which never relies on a signal being
+    ///   delivered.
+    ///
+    /// * Creating an [`Engine`](crate::Engine) doesn't install any signal
+    ///   handlers. If every engine in the process has this option disabled
+    ///   then Wasmtime never installs them.
+    ///
+    /// The stack overflow check in this mode happens on entry to each
+    /// function, once its frame has been allocated, and accounts for the
+    /// whole of that frame. As with signals-based traps, overflowing the
+    /// host's stack itself aborts the process.
+    ///
+    /// Modules compiled with one setting of this option can't be deserialized
+    /// into an engine with the other. This option also can't be disabled when
+    /// using the pooling instance allocator, which requires static memories.
+    ///
+    /// By default this option is `true`.
+    pub fn signals_based_traps(&mut self, enable: bool) -> &mut Self {
+        self.tunables.signals_based_traps = enable;
+        self
+    }
+
+    /// Configures whether Wasmtime installs its own signal handlers for
+    /// catching traps raised by WebAssembly code.
+    ///
+    /// When disabled, creating an [`Engine`](crate::Engine) with
+    /// signals-based traps (see [`Config::signals_based_traps`]) no longer
+    /// installs Wasmtime's signal handlers (or, on Windows, its vectored
+    /// exception handler). Compiled code still relies on faults to trap,
+    /// though, so the embedder must install its own handlers and forward
+    /// faults to Wasmtime with `handle_trap` from the [`unix`](crate::unix)
+    /// or [`windows`](crate::windows) module. Faults which Wasmtime doesn't
+    /// recognize as wasm traps are left to the embedder to handle however it
+    /// sees fit.
+    ///
+    /// If any engine in the process is created with this option enabled then
+    /// Wasmtime's handlers are installed regardless. On macOS, unless the
+    /// `posix-signals-on-macos` feature is enabled, traps are caught with
+    /// Mach exception ports which don't interfere with signal handlers, so
+    /// this option has no effect there.
+    ///
+    /// By default this option is `true`.
+    pub fn install_signal_handlers(&mut self, enable: bool) -> &mut Self {
+        self.install_signal_handlers = enable;
+        self
+    }
+
+    /// Configures whether compiled code is instrumented so that it can be
+    /// paused at breakpoints and single-stepped by a debugger embedded in the
+    /// host.
+    ///
+    /// When enabled, compiled code calls into Wasmtime before executing each
+    /// instruction, passing it the current values of the function's locals.
+    /// A store's debug handler (see
+    /// [`Store::debug_handler`](crate::Store::debug_handler)) is then called
+    /// whenever execution reaches a breakpoint set with
+    /// [`Store::set_breakpoint`](crate::Store::set_breakpoint) or while
+    /// single-stepping, and may inspect the paused function's locals, the call
+    /// stack and the rest of the store.
+    ///
+    /// This instrumentation makes compiled code much slower and is intended
+    /// only for debugging. Unlike
+    /// [`Config::debug_info`](crate::Config::debug_info) it doesn't require a
+    /// native debugger attached to the process. Modules compiled with one
+    /// setting of this option can't be deserialized into an engine with the
+    /// other.
+    ///
+    /// By default this option is `false`.
+    pub fn guest_debug(&mut self, enable: bool) -> &mut Self {
+        self.tunables.guest_debug = enable;
+        self
+    }
+
+    /// Configures whether compiled code counts the WebAssembly instructions it
+    /// executes in each function.
+    ///
+    /// When enabled, each straight-line run of instructions reports how many
+    /// instructions it executed to Wasmtime once it finishes. Counts are
+    /// collected per module, function and basic block within each
+    /// [`Store`](crate::Store), and retrieved with
+    /// [`Store::instruction_counts`](crate::Store::instruction_counts). This
+    /// may be used to measure the cost of a call deterministically per
+    /// function, or as coverage information when generating test inputs for a
+    /// module.
+    ///
+    /// Unlike [`Config::consume_fuel`] the count doesn't limit execution and
+    /// includes every instruction executed. The instrumentation calls into
+    /// Wasmtime at the end of every basic block, which has a significant
+    /// runtime cost. Modules compiled with one setting of this option can't
+    /// be deserialized into an engine with the other.
+    ///
+    /// By default this option is `false`.
+    pub fn count_instructions(&mut self, enable: bool) -> &mut Self {
+        self.tunables.count_instructions = enable;
+        self
+    }
+
+    /// Configures whether compiled code reports each of its loads from and
+    /// stores to linear memory.
+    ///
+    /// When enabled, compiled code calls into Wasmtime after each load or
+    /// store instruction with the address accessed and the bytes loaded or
+    /// stored, which are passed to the callback configured with
+    /// [`Store::memory_access_callback`](crate::Store::memory_access_callback).
+    /// This is intended for tools such as record-and-replay debuggers and
+    /// dynamic taint analyses.
+    ///
+    /// Scalar and `v128` loads and stores are reported. Atomic instructions,
+    /// bulk memory instructions such as `memory.copy`, the other SIMD memory
+    /// instructions, and accesses made by the host aren't. Accesses which trap
+    /// aren't reported either.
+    ///
+    /// The instrumentation has a significant runtime cost. Modules compiled
+    /// with one setting of this option can't be deserialized into an engine
+    /// with the other.
+    ///
+    /// By default this option is `false`.
+    pub fn trace_memory_accesses(&mut self, enable: bool) -> &mut Self {
+        self.tunables.trace_memory_accesses = enable;
+        self
+    }
+
+    /// Configures whether compiled code checks its integer arithmetic for
+    /// overflow.
+    ///
+    /// When enabled, each `i32.add`, `i32.sub`, `i32.mul`, `i64.add`,
+    /// `i64.sub` and `i64.mul` is followed by a check of whether its result
+    /// overflowed when its operands are interpreted as signed integers. An
+    /// overflow is reported to the callback configured with
+    /// [`Store::integer_overflow_callback`](crate::Store::integer_overflow_callback),
+    /// or traps with [`TrapCode::IntegerOverflow`](crate::TrapCode::IntegerOverflow)
+    /// if there isn't one. This is intended for testing guests, to find
+    /// arithmetic which unintentionally wraps around.
+    ///
+    /// Plenty of code wraps around on purpose, such as hash functions and
+    /// random number generators, so functions can be exempted from the checks
+    /// with [`Config::allow_integer_overflow`]. Arithmetic which the compiler
+    /// producing a module has lowered to wrapping operations, such as unsigned
+    /// arithmetic which happens to cross the signed boundary, is reported too.
+    ///
+    /// The checks have a runtime cost. Modules compiled with one setting of
+    /// this option can't be deserialized into an engine with the other.
+    ///
+    /// By default this option is `false`.
+    pub fn check_integer_overflow(&mut self, enable: bool) -> &mut Self {
+        self.tunables.check_integer_overflow = enable;
+        self
+    }
+
+    /// Exempts the functions named `name` from the checks enabled by
+    /// [`Config::check_integer_overflow`].
+    ///
+    /// Functions are matched by their name in the module's name section, so
+    /// that functions which overflow on purpose can be exempted whichever
+    /// module they're in. This can be called any number of times to exempt
+    /// several functions.
+    ///
+    /// Modules compiled with check exemptions can only be deserialized into
+    /// an engine with the same exemptions.
+    pub fn allow_integer_overflow(&mut self, name: impl Into<String>) -> &mut Self {
+        self.tunables.integer_overflow_allowlist.insert(name.into());
+        self
+    }
+
+    /// Configures whether the functions of a module are compiled the first
+    /// time they're called, rather than when the module is created.
+    ///
+    /// When enabled, [`Module::new`](crate::Module::new) and related
+    /// functions still validate the whole module, but only compile a small
+    /// stub for each of its functions. The first call of a function, whether
+    /// from the host, from another function, or through a table, compiles it
+    /// and updates the module so that later calls go straight to the compiled
+    /// code. This makes modules with many functions, of which only a few are
+    /// called, much quicker to create, at the cost of all calls to the
+    /// module's functions becoming indirect calls.
+    ///
+    /// If compiling a function fails then its call traps. Modules compiled
+    /// lazily can't be [serialized](crate::Module::serialize), and this option
+    /// can't be combined with [`Config::debug_info`]. Modules created with
+    /// [`Engine::precompile_module`](crate::Engine::precompile_module) or
+    /// [`Module::preinitialize`](crate::Module::preinitialize) are always
+    /// compiled up front.
+    ///
+    /// By default this option is `false`.
+    pub fn lazy_compilation(&mut self, enable: bool) -> &mut Self {
+        self.tunables.lazy_compilation = enable;
+        self
+    }
+
+    /// Configures whether functions compiled lazily collect a profile of how
+    /// they execute.
+    ///
+    /// When enabled, each function counts how many times it's called, and
+    /// each of its `if` and `br_if` instructions counts how many times it's
+    /// executed and how many of those times its condition was nonzero. The
+    /// counts of all instances of a module are added together, and retrieved
+    /// with [`Module::execution_profile`](crate::Module::execution_profile).
+    /// Long-running embeddings which create the same module again, for
+    /// example when it's redeployed, can pass the profile to
+    /// [`Module::new_with_profile`](crate::Module::new_with_profile) to
+    /// compile it for how it actually runs.
+    ///
+    /// The counters are plain memory updated without synchronization, which
+    /// keeps them cheap, but means that counts from concurrent calls may be
+    /// lost. Since counters belong to a function's lazily compiled code this
+    /// requires [`Config::lazy_compilation`] or
+    /// [`Config::tiered_compilation`], and functions collecting a profile are
+    /// always compiled with Cranelift.
+    ///
+    /// By default this option is `false`.
+    pub fn collect_execution_profile(&mut self, enable: bool) -> &mut Self {
+        self.tunables.collect_profile = enable;
+        self
+    }
+
+    /// Configures whether functions are first compiled with the baseline
+    /// compiler, and recompiled with Cranelift once they're hot.
+    ///
+    /// When enabled, the functions of a module are compiled on their first
+    /// call as with [`Config::lazy_compilation`], but with the
+    /// [baseline compiler](Strategy::Baseline), which compiles much faster
+    /// than Cranelift but generates slower code. Each function counts its
+    /// calls and loop iterations, and once the count reaches the
+    /// [threshold](Config::tier_up_threshold) it's recompiled with Cranelift
+    /// on a background thread of the [`Engine`](crate::Engine). Calls which
+    /// start after the recompiled code is ready use it, while calls which are
+    /// already running finish in the baseline code. This gives modules both a
+    /// fast startup and fast steady-state performance.
+    ///
+    /// Functions which the baseline compiler doesn't support, and all
+    /// functions on targets other than x86_64 System V ones, are compiled with
+    /// Cranelift straight away. The same restrictions as for
+    /// [`Config::lazy_compilation`] apply.
+    ///
+    /// By default this option is `false`.
+    pub fn tiered_compilation(&mut self, enable: bool) -> &mut Self {
+        self.tunables.tiered_compilation = enable;
+        self
+    }
+
+    /// Configures whether functions which can never be called are left out of
+    /// the compilation of modules.
+    ///
+    /// Toolchains often link whole libraries into a module of which only a
+    /// small part is used. When this is enabled, a module's exported
+    /// functions, its start function, and the functions of its element
+    /// segments, which are the only ones which can be called from outside of
+    /// it or through a table, are found along with every function they call
+    /// directly. All of the module's other functions are still validated but
+    /// compiled to a stub which traps, saving the time and memory it takes to
+    /// compile them.
+    ///
+    /// This has no effect with [`Config::lazy_compilation`] or
+    /// [`Config::tiered_compilation`], which only compile the functions that
+    /// are actually called, but applies to
+    /// [`Engine::precompile_module`](crate::Engine::precompile_module) and
+    /// [`Module::preinitialize`](crate::Module::preinitialize).
+    ///
+    /// By default this option is `false`.
+    pub fn skip_unreachable_functions(&mut self, enable: bool) -> &mut Self {
+        self.tunables.skip_unreachable_functions = enable;
+        self
+    }
+
+    /// Configures whether calls to imported functions can be turned into
+    /// direct calls once the functions are known.
+    ///
+    /// Wasm calls imported functions indirectly, loading the function's code
+    /// and `VMContext` from its caller's `VMContext`, since they're only
+    /// known at instantiation while a module's code is shared by all of its
+    /// instances. When this is enabled, calls to imported functions instead
+    /// go through a small thunk in the module's code, and
+    /// [`Linker::instantiate_pre`](crate::Linker::instantiate_pre) creates a
+    /// copy of the code, whose thunks jump straight to the imports it
+    /// resolved, for the returned [`InstancePre`](crate::InstancePre) to
+    /// instantiate. This speeds up calls to host functions and to the exports
+    /// of other instances, at the cost of copying the module's code each time
+    /// an `InstancePre` is created, so it's worth it when an `InstancePre` is
+    /// instantiated many times or its instances make many calls to imports.
+    /// Modules instantiated in any other way call their imports through the
+    /// thunks at about the cost of an indirect call.
+    ///
+    /// This is supported on x86_64, and on aarch64 other than on Windows. It
+    /// has no effect on functions compiled
+    /// [lazily](Config::lazy_compilation).
+    ///
+    /// By default this option is `false`.
+    pub fn devirtualize_imports(&mut self, enable: bool) -> &mut Self {
+        self.tunables.devirtualize_imports = enable;
+        self
+    }
+
+    /// Configures whether `call_indirect` instructions remember their last
+    /// callee, to skip looking it up in the table when it's called again.
+    ///
+    /// Each `call_indirect` normally bounds checks its index into the table,
+    /// loads the table element, initializing it first if it's still lazily
+    /// uninitialized, traps if it's null and compares its signature against
+    /// the expected one. When this is enabled, a `call_indirect` through a
+    /// table which the module defines, doesn't export and never modifies with
+    /// instructions such as `table.set` or `table.grow` instead keeps the
+    /// index and element of its last callee in the instance's `VMContext`,
+    /// and calls the element straight away when it's given the same index
+    /// again. Since such a table holds the same function at an index for as
+    /// long as its instance exists, this skips all of the checks the first
+    /// call already made. This speeds up guests which dispatch through their
+    /// function table from sites which mostly call the same function, such as
+    /// those of dynamic languages, at the cost of two pointers of `VMContext`
+    /// for each cached instruction and a comparison when the callee changes.
+    ///
+    /// By default this option is `false`.
+    pub fn cache_call_indirects(&mut self, enable: bool) -> &mut Self {
+        self.tunables.cache_call_indirects = enable;
+        self
+    }
+
+    /// Configures how many calls and loop iterations of a function compiled
+    /// with the baseline compiler it takes for it to be recompiled with
+    /// Cranelift, when [`Config::tiered_compilation`] is enabled.
+    ///
+    /// A threshold of zero is treated as one.
+    ///
+    /// By default this is 10,000.
+    pub fn tier_up_threshold(&mut self, threshold: u32) -> &mut Self {
+        self.tier_up_threshold = threshold;
+        self
+    }
+
     /// Configure the version information used in serialized and deserialzied [`crate::Module`]s.
     /// This effects the behavior of [`crate::Module::serialize()`], as well as
     /// [`crate::Module::deserialize()`] and related functions.
@@ -1112,6 +1823,62 @@ impl Config {
         self
     }
 
+    /// Configures how many threads are used to compile modules when
+    /// [`Config::parallel_compilation`] is enabled.
+    ///
+    /// When this is set the [`Engine`] owns a thread pool of `threads` threads
+    /// for compiling its modules, instead of sharing rayon's global thread
+    /// pool with the rest of the process. The threads are spawned when the
+    /// [`Engine`] is created, and `0` picks the number of CPUs.
+    ///
+    /// Functions and host-to-wasm trampolines are compiled on the pool, and
+    /// once the compiled code is linked into a single object the trap tables
+    /// and address maps of the functions are also built on the pool.
+    ///
+    /// By default rayon's global thread pool is used.
+    #[cfg(feature = "parallel-compilation")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "parallel-compilation")))]
+    pub fn parallel_compilation_threads(&mut self, threads: usize) -> &mut Self {
+        self.parallel_compilation_threads = Some(threads);
+        self
+    }
+
+    /// Declares the signatures of every host function defined with
+    /// [`Func::new`](crate::Func::new), [`Func::new_unchecked`] or the
+    /// equivalent methods of [`Linker`], whose trampolines are then compiled
+    /// when the [`Engine`](crate::Engine) is created.
+    ///
+    /// Each of those host functions has trampolines to enter it from
+    /// WebAssembly and to call WebAssembly through it, which are otherwise
+    /// compiled for each function as it's created and so delay it. Once the
+    /// signatures are declared every function with one of them shares the
+    /// trampolines the engine compiled for it, and creating a function with
+    /// any other signature fails with an [`UndeclaredSignatureError`], which
+    /// [`Func::try_new`](crate::Func::try_new) and the [`Linker`] methods
+    /// return and [`Func::new`](crate::Func::new) panics with. The
+    /// signatures can be enumerated with
+    /// [`Engine::host_trampoline_signatures`].
+    ///
+    /// Functions defined with [`Func::wrap`](crate::Func::wrap) don't need
+    /// trampolines to be compiled, and may have any signature.
+    ///
+    /// By default no signatures are declared and the trampolines of each
+    /// function are compiled when it's created.
+    ///
+    /// [`Func::new_unchecked`]: crate::Func::new_unchecked
+    /// [`Linker`]: crate::Linker
+    /// [`UndeclaredSignatureError`]: crate::UndeclaredSignatureError
+    /// [`Engine::host_trampoline_signatures`]: crate::Engine::host_trampoline_signatures
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn host_trampoline_signatures(
+        &mut self,
+        signatures: impl IntoIterator<Item = FuncType>,
+    ) -> &mut Self {
+        self.host_trampoline_signatures = Some(signatures.into_iter().collect());
+        self
+    }
+
     /// Configures whether compiled artifacts will contain information to map
     /// native program addresses back to the original wasm module.
     ///
@@ -1241,6 +2008,18 @@ impl Config {
         self
     }
 
+    /// Returns the creator of memories for on-demand allocation, which is the
+    /// host-provided one if there is one.
+    pub(crate) fn memory_creator(&self) -> Option<Arc<dyn RuntimeMemoryCreator>> {
+        match &self.mem_creator {
+            Some(creator) => Some(creator.clone()),
+            None if self.huge_pages_for_memories => {
+                Some(Arc::new(DefaultMemoryCreator { huge_pages: true }))
+            }
+            None => None,
+        }
+    }
+
     pub(crate) fn build_allocator(&self) -> Result<Box<dyn InstanceAllocator>> {
         #[cfg(feature = "async")]
         let stack_size = self.async_stack_size;
@@ -1250,10 +2029,14 @@ impl Config {
 
         match self.allocation_strategy {
             InstanceAllocationStrategy::OnDemand => Ok(Box::new(OnDemandInstanceAllocator::new(
-                self.mem_creator.clone(),
+                self.memory_creator(),
                 stack_size,
             ))),
             #[cfg(feature = "pooling-allocator")]
+            InstanceAllocationStrategy::Pooling { .. } if !self.tunables.signals_based_traps => {
+                bail!("the pooling instance allocator requires signals-based traps")
+            }
+            #[cfg(feature = "pooling-allocator")]
             InstanceAllocationStrategy::Pooling {
                 strategy,
                 instance_limits,
@@ -1262,17 +2045,40 @@ impl Config {
                 instance_limits,
                 stack_size,
                 &self.tunables,
+                self.huge_pages_for_memories,
+                self.num_memory_protection_keys()?,
             )?)),
         }
     }
+
+    /// Returns the maximum number of protection keys the pooling allocator
+    /// should stripe memories across.
+    #[cfg(feature = "pooling-allocator")]
+    fn num_memory_protection_keys(&self) -> Result<usize> {
+        use wasmtime_runtime::ProtectionKey;
+        match self.memory_protection_keys {
+            MpkEnabled::Disable => Ok(0),
+            MpkEnabled::Enable if !ProtectionKey::is_supported() => {
+                bail!("memory protection keys are not supported on this host")
+            }
+            MpkEnabled::Enable if self.async_support => {
+                bail!("memory protection keys cannot be used with async support")
+            }
+            MpkEnabled::Enable => Ok(self.max_memory_protection_keys),
+            MpkEnabled::Auto if self.async_support => Ok(0),
+            MpkEnabled::Auto => Ok(self.max_memory_protection_keys),
+        }
+    }
 }
 
 #[cfg(compiler)]
-fn compiler_builder(strategy: Strategy) -> Result<Box<dyn CompilerBuilder>> {
-    let mut builder = match strategy {
-        Strategy::Auto | Strategy::Cranelift => wasmtime_cranelift::builder(),
+fn compiler_builder() -> Result<CompilerConfig> {
+    let mut compiler = CompilerConfig {
+        builder: wasmtime_cranelift::builder(),
+        custom: None,
+        settings: Vec::new(),
     };
-    builder
+    compiler
         .set(
             "unwind_info",
             if cfg!(feature = "wasm-backtrace") {
@@ -1282,11 +2088,125 @@ fn compiler_builder(strategy: Strategy) -> Result<Box<dyn CompilerBuilder>> {
             },
         )
         .unwrap();
-    Ok(builder)
+    Ok(compiler)
+}
+
+/// The compiler of a [`Config`], which records every setting made to it so
+/// that they can be applied to the builder of [`Config::custom_compiler`]
+/// too, whenever it was set.
+#[cfg(compiler)]
+pub(crate) struct CompilerConfig {
+    /// The builder of the compiler which is used, which each setting is
+    /// applied to, and checked by, as it's made.
+    builder: Box<dyn CompilerBuilder>,
+    /// The builder of `Config::custom_compiler`, which replaces `builder`
+    /// once `Engine::new` has applied the settings to it.
+    custom: Option<Box<dyn CompilerBuilder>>,
+    /// The settings made so far, in order.
+    settings: Vec<CompilerSetting>,
+}
+
+#[cfg(compiler)]
+#[derive(Clone)]
+enum CompilerSetting {
+    Target(target_lexicon::Triple),
+    Set(String, String),
+    Enable(String),
+    FunctionHook(Arc<dyn FunctionHook>),
+}
+
+#[cfg(compiler)]
+impl CompilerConfig {
+    fn target(&mut self, target: target_lexicon::Triple) -> Result<()> {
+        self.builder.target(target.clone())?;
+        self.settings.push(CompilerSetting::Target(target));
+        Ok(())
+    }
+
+    pub(crate) fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        self.builder.set(name, value)?;
+        self.settings
+            .push(CompilerSetting::Set(name.to_string(), value.to_string()));
+        Ok(())
+    }
+
+    pub(crate) fn enable(&mut self, name: &str) -> Result<()> {
+        self.builder.enable(name)?;
+        self.settings
+            .push(CompilerSetting::Enable(name.to_string()));
+        Ok(())
+    }
+
+    fn function_hook(&mut self, hook: Arc<dyn FunctionHook>) {
+        self.builder.function_hook(hook.clone());
+        self.settings.push(CompilerSetting::FunctionHook(hook));
+    }
+
+    pub(crate) fn triple(&self) -> &target_lexicon::Triple {
+        self.builder.triple()
+    }
+
+    pub(crate) fn build(&self) -> Result<Box<dyn Compiler>> {
+        self.builder.build()
+    }
+
+    /// Applies the settings made so far to the builder of
+    /// `Config::custom_compiler`, if any, and uses it from now on.
+    pub(crate) fn use_custom(&mut self) -> Result<()> {
+        let mut custom = match self.custom.take() {
+            Some(custom) => custom,
+            None => return Ok(()),
+        };
+        for setting in &self.settings {
+            match setting {
+                CompilerSetting::Target(target) => custom
+                    .target(target.clone())
+                    .with_context(|| format!("the custom compiler rejected target `{}`", target))?,
+                CompilerSetting::Set(name, value) => {
+                    custom.set(name, value).with_context(|| {
+                        format!(
+                            "the custom compiler rejected setting `{}` to `{}`",
+                            name, value
+                        )
+                    })?
+                }
+                CompilerSetting::Enable(name) => custom
+                    .enable(name)
+                    .with_context(|| format!("the custom compiler rejected enabling `{}`", name))?,
+                CompilerSetting::FunctionHook(hook) => custom.function_hook(hook.clone()),
+            }
+        }
+        self.builder = custom;
+        Ok(())
+    }
+}
+
+#[cfg(compiler)]
+impl Clone for CompilerConfig {
+    fn clone(&self) -> CompilerConfig {
+        CompilerConfig {
+            builder: self.builder.clone(),
+            custom: self
+                .custom
+                .as_ref()
+                .map(|custom| CompilerBuilder::clone(&**custom)),
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+#[cfg(compiler)]
+impl fmt::Debug for CompilerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CompilerConfig")
+            .field("builder", &self.builder)
+            .field("custom", &self.custom)
+            .finish()
+    }
 }
 
 fn round_up_to_pages(val: u64) -> u64 {
-    let page_size = region::page::size() as u64;
+    let page_size = wasmtime_runtime::page_size() as u64;
     debug_assert!(page_size.is_power_of_two());
     val.checked_add(page_size - 1)
         .map(|val| val & !(page_size - 1))
@@ -1304,11 +2224,14 @@ impl Clone for Config {
         Config {
             #[cfg(compiler)]
             compiler: self.compiler.clone(),
+            #[cfg(compiler)]
+            host_trampoline_signatures: self.host_trampoline_signatures.clone(),
             tunables: self.tunables.clone(),
             #[cfg(feature = "cache")]
             cache_config: self.cache_config.clone(),
             profiler: self.profiler.clone(),
             features: self.features.clone(),
+            deterministic: self.deterministic,
             mem_creator: self.mem_creator.clone(),
             allocation_strategy: self.allocation_strategy.clone(),
             max_wasm_stack: self.max_wasm_stack,
@@ -1318,9 +2241,16 @@ impl Clone for Config {
             async_stack_size: self.async_stack_size,
             module_version: self.module_version.clone(),
             parallel_compilation: self.parallel_compilation,
+            parallel_compilation_threads: self.parallel_compilation_threads,
+            huge_pages_for_code: self.huge_pages_for_code,
+            huge_pages_for_memories: self.huge_pages_for_memories,
+            memory_protection_keys: self.memory_protection_keys,
+            max_memory_protection_keys: self.max_memory_protection_keys,
+            tier_up_threshold: self.tier_up_threshold,
             memory_init_cow: self.memory_init_cow,
             memory_guaranteed_dense_image_size: self.memory_guaranteed_dense_image_size,
             force_memory_init_memfd: self.force_memory_init_memfd,
+            install_signal_handlers: self.install_signal_handlers,
         }
     }
 }
@@ -1335,6 +2265,13 @@ impl fmt::Debug for Config {
             .field("wasm_bulk_memory", &self.features.bulk_memory)
             .field("wasm_simd", &self.features.simd)
             .field("wasm_multi_value", &self.features.multi_value)
+            .field(
+                "wasm_sat_float_to_int",
+                &self.features.saturating_float_to_int,
+            )
+            .field("wasm_sign_extension", &self.features.sign_extension)
+            .field("wasm_mutable_global", &self.features.mutable_global)
+            .field("deterministic", &self.deterministic)
             .field(
                 "static_memory_maximum_size",
                 &(u64::from(self.tunables.static_memory_bound)
@@ -1352,10 +2289,59 @@ impl fmt::Debug for Config {
                 "guard_before_linear_memory",
                 &self.tunables.guard_before_linear_memory,
             )
-            .field("parallel_compilation", &self.parallel_compilation);
+            .field("signals_based_traps", &self.tunables.signals_based_traps)
+            .field("guest_debug", &self.tunables.guest_debug)
+            .field("coredump_frames", &self.tunables.coredump_frames)
+            .field("count_instructions", &self.tunables.count_instructions)
+            .field(
+                "trace_memory_accesses",
+                &self.tunables.trace_memory_accesses,
+            )
+            .field(
+                "check_integer_overflow",
+                &self.tunables.check_integer_overflow,
+            )
+            .field(
+                "integer_overflow_allowlist",
+                &self.tunables.integer_overflow_allowlist,
+            )
+            .field("lazy_compilation", &self.tunables.lazy_compilation)
+            .field("tiered_compilation", &self.tunables.tiered_compilation)
+            .field(
+                "skip_unreachable_functions",
+                &self.tunables.skip_unreachable_functions,
+            )
+            .field("devirtualize_imports", &self.tunables.devirtualize_imports)
+            .field("cache_call_indirects", &self.tunables.cache_call_indirects)
+            .field(
+                "bulk_memory_inline_threshold",
+                &self.tunables.bulk_memory_inline_threshold,
+            )
+            .field("collect_profile", &self.tunables.collect_profile)
+            .field(
+                "pinned_context_register",
+                &self.tunables.pinned_context_register,
+            )
+            .field("tier_up_threshold", &self.tier_up_threshold)
+            .field("install_signal_handlers", &self.install_signal_handlers)
+            .field("huge_pages_for_code", &self.huge_pages_for_code)
+            .field("huge_pages_for_memories", &self.huge_pages_for_memories)
+            .field("memory_protection_keys", &self.memory_protection_keys)
+            .field(
+                "max_memory_protection_keys",
+                &self.max_memory_protection_keys,
+            )
+            .field("parallel_compilation", &self.parallel_compilation)
+            .field(
+                "parallel_compilation_threads",
+                &self.parallel_compilation_threads,
+            );
         #[cfg(compiler)]
         {
-            f.field("compiler", &self.compiler);
+            f.field("compiler", &self.compiler).field(
+                "host_trampoline_signatures",
+                &self.host_trampoline_signatures,
+            );
         }
         f.finish()
     }
@@ -1381,6 +2367,18 @@ pub enum Strategy {
     /// Currently the default backend, Cranelift aims to be a reasonably fast
     /// code generator which generates high quality machine code.
     Cranelift,
+
+    /// A single-pass compiler which translates wasm directly to machine code,
+    /// compiling much faster than Cranelift but generating slower code.
+    ///
+    /// This is useful for code which is only run briefly or where startup
+    /// latency matters more than throughput. Functions using features that the
+    /// baseline compiler doesn't support, such as SIMD or reference types, are
+    /// transparently compiled with Cranelift instead.
+    ///
+    /// This is currently only supported on x86_64 System V targets, and
+    /// creating an [`Engine`](crate::Engine) for other targets will fail.
+    Baseline,
 }
 
 /// Possible optimization levels for the Cranelift codegen backend.
@@ -1409,6 +2407,14 @@ pub enum ProfilingStrategy {
 
     /// Collect profiling info using the "ittapi", used with `VTune` on Linux.
     VTune,
+
+    /// Append the address, size and name of each compiled function to the
+    /// `/tmp/perf-<pid>.map` file, used with `perf` on Linux.
+    ///
+    /// This only lets `perf` symbolize samples of wasm code, but unlike
+    /// jitdump it doesn't require post-processing the profile with
+    /// `perf inject`.
+    PerfMap,
 }
 
 /// Select how wasm backtrace detailed information is handled.
@@ -1426,3 +2432,18 @@ pub enum WasmBacktraceDetails {
     /// `WASMTIME_BACKTRACE_DETAILS` environment variable.
     Environment,
 }
+
+/// Select whether the pooling allocator uses memory protection keys, see
+/// [`Config::memory_protection_keys`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpkEnabled {
+    /// Memory protection keys are used, and creating an engine fails if they
+    /// can't be.
+    Enable,
+
+    /// Memory protection keys aren't used.
+    Disable,
+
+    /// Memory protection keys are used if the host supports them.
+    Auto,
+}
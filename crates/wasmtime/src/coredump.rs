@@ -0,0 +1,384 @@
+//! Capturing guest state when a trap happens, and serializing it in the wasm
+//! core dump format.
+
+use crate::store::StoreOpaque;
+use crate::Val;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+use wasm_encoder::{encoders, Instruction};
+use wasmtime_environ::{WasmType, WASM_PAGE_SIZE};
+use wasmtime_runtime::{CoreDumpStackFrame, VMContext, VMGlobalDefinition, VMMemoryDefinition};
+
+/// A snapshot of the state of the WebAssembly guests within a
+/// [`Store`](crate::Store) at the time that a trap happened.
+///
+/// Core dumps are captured when
+/// [`Config::coredump_on_trap`](crate::Config::coredump_on_trap) is enabled
+/// and are retrieved with [`Trap::coredump`](crate::Trap::coredump). A core
+/// dump contains the stack of wasm frames which led to the trap as well as the
+/// contents of every linear memory and the value of every global of each
+/// instance in the store.
+///
+/// The main use of a core dump is [`WasmCoreDump::serialize`] which produces a
+/// file in the [wasm core dump format][format] which can be inspected offline
+/// with standard tooling.
+///
+/// Each frame records the values of its function's locals, but not the
+/// contents of its operand stack.
+///
+/// [format]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+pub struct WasmCoreDump {
+    modules: Vec<Option<String>>,
+    instances: Vec<CoreDumpInstance>,
+    memories: Vec<CoreDumpMemory>,
+    globals: Vec<CoreDumpGlobal>,
+    frames: Vec<CoreDumpFrame>,
+}
+
+struct CoreDumpInstance {
+    module: u32,
+    memories: Vec<u32>,
+    globals: Vec<u32>,
+}
+
+struct CoreDumpMemory {
+    maximum: Option<u64>,
+    memory64: bool,
+    data: Vec<u8>,
+}
+
+struct CoreDumpGlobal {
+    ty: WasmType,
+    mutable: bool,
+    bits: u128,
+}
+
+/// A frame of the wasm stack recorded within a [`WasmCoreDump`].
+///
+/// Frames record which instruction they were executing and the values of
+/// their function's locals, but not the contents of their operand stack.
+#[derive(Debug, Clone)]
+pub struct CoreDumpFrame {
+    instance: u32,
+    func_index: u32,
+    func_offset: u32,
+    locals: Vec<Option<Val>>,
+}
+
+impl CoreDumpFrame {
+    /// Returns the index, within the core dump, of the instance executing
+    /// this frame.
+    pub fn instance(&self) -> u32 {
+        self.instance
+    }
+
+    /// Returns the index of the function executing this frame in its module's
+    /// function index space.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the offset of this frame's instruction from the start of its
+    /// function in the original wasm module.
+    pub fn func_offset(&self) -> u32 {
+        self.func_offset
+    }
+
+    /// Returns the values of the locals of this frame's function, starting
+    /// with its parameters.
+    ///
+    /// References can't be meaningfully represented outside of the store, so
+    /// the values of `externref` and `funcref` locals are `None`.
+    pub fn locals(&self) -> &[Option<Val>] {
+        &self.locals
+    }
+}
+
+impl WasmCoreDump {
+    /// Captures the state of all instances within `store`, with the wasm
+    /// frames recorded in `stack`.
+    pub(crate) fn new(store: &StoreOpaque, stack: &[CoreDumpStackFrame]) -> WasmCoreDump {
+        let mut dump = WasmCoreDump {
+            modules: Vec::new(),
+            instances: Vec::new(),
+            memories: Vec::new(),
+            globals: Vec::new(),
+            frames: Vec::new(),
+        };
+
+        // Memories and globals may be imported into many instances, so they're
+        // deduplicated by the address of their definition.
+        let mut module_indices = HashMap::new();
+        let mut memory_indices = HashMap::<*mut VMMemoryDefinition, u32>::new();
+        let mut global_indices = HashMap::<*mut VMGlobalDefinition, u32>::new();
+        let mut instance_indices = HashMap::<*mut VMContext, u32>::new();
+
+        for handle in store.module_instances() {
+            // Safety: this handle is only used for the duration of this loop,
+            // during which the store keeps the instance alive.
+            let mut handle = unsafe { handle.clone() };
+            let module = handle.module().clone();
+            let module_index = *module_indices
+                .entry(Arc::as_ptr(&module))
+                .or_insert_with(|| {
+                    dump.modules.push(module.name.clone());
+                    (dump.modules.len() - 1) as u32
+                });
+
+            let mut memories = Vec::new();
+            for index in module.memory_plans.keys() {
+                let export = handle.get_exported_memory(index);
+                let index = *memory_indices.entry(export.definition).or_insert_with(|| {
+                    let definition = unsafe { &*export.definition };
+                    let data = unsafe {
+                        std::slice::from_raw_parts(definition.base, definition.current_length)
+                    };
+                    dump.memories.push(CoreDumpMemory {
+                        maximum: export.memory.memory.maximum,
+                        memory64: export.memory.memory.memory64,
+                        data: data.to_vec(),
+                    });
+                    (dump.memories.len() - 1) as u32
+                });
+                memories.push(index);
+            }
+
+            let mut globals = Vec::new();
+            for index in module.globals.keys() {
+                let export = handle.get_exported_global(index);
+                let index = *global_indices.entry(export.definition).or_insert_with(|| {
+                    let definition = unsafe { &*export.definition };
+                    let bits = match export.global.wasm_ty {
+                        WasmType::I32 | WasmType::F32 => {
+                            u128::from(unsafe { *definition.as_u32() })
+                        }
+                        WasmType::I64 | WasmType::F64 => {
+                            u128::from(unsafe { *definition.as_u64() })
+                        }
+                        WasmType::V128 => unsafe { *definition.as_u128() },
+                        // References can't be meaningfully represented outside
+                        // of the store, so they're always recorded as null.
+                        WasmType::FuncRef | WasmType::ExternRef => 0,
+                    };
+                    dump.globals.push(CoreDumpGlobal {
+                        ty: export.global.wasm_ty,
+                        mutable: export.global.mutability,
+                        bits,
+                    });
+                    (dump.globals.len() - 1) as u32
+                });
+                globals.push(index);
+            }
+
+            instance_indices.insert(handle.vmctx_ptr(), dump.instances.len() as u32);
+            dump.instances.push(CoreDumpInstance {
+                module: module_index,
+                memories,
+                globals,
+            });
+        }
+
+        for frame in stack {
+            // Frames are recorded with the `VMContext` of their instance, so
+            // each is attributed to the right one of the instances of its
+            // module.
+            let instance = match instance_indices.get(&frame.vmctx) {
+                Some(i) => *i,
+                None => continue,
+            };
+            let locals = frame
+                .locals
+                .iter()
+                .map(|(ty, raw)| unsafe {
+                    match ty {
+                        WasmType::I32 => Some(Val::I32(i32::from_le(raw.i32))),
+                        WasmType::I64 => Some(Val::I64(i64::from_le(raw.i64))),
+                        WasmType::F32 => Some(Val::F32(u32::from_le(raw.f32))),
+                        WasmType::F64 => Some(Val::F64(u64::from_le(raw.f64))),
+                        WasmType::V128 => Some(Val::V128(u128::from_le(raw.v128))),
+                        WasmType::FuncRef | WasmType::ExternRef => None,
+                    }
+                })
+                .collect();
+            dump.frames.push(CoreDumpFrame {
+                instance,
+                func_index: frame.func_index,
+                func_offset: frame.func_offset,
+                locals,
+            });
+        }
+
+        dump
+    }
+
+    /// Returns the wasm frames, starting with the most recently called, which
+    /// were on the stack when the trap happened.
+    pub fn frames(&self) -> &[CoreDumpFrame] {
+        &self.frames
+    }
+
+    /// Returns the contents of each linear memory in the store at the time of
+    /// the trap.
+    pub fn memories(&self) -> impl ExactSizeIterator<Item = &[u8]> + '_ {
+        self.memories.iter().map(|m| &m.data[..])
+    }
+
+    /// Serializes this core dump into the [wasm core dump format][format].
+    ///
+    /// The `name` is recorded as the name of the executable which trapped.
+    /// The returned bytes are a valid WebAssembly module whose memories and
+    /// globals hold the state of the guest at the time of the trap.
+    ///
+    /// [format]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+    pub fn serialize(&self, name: &str) -> Vec<u8> {
+        let mut module = wasm_encoder::Module::new();
+
+        let mut core = vec![0x00];
+        core.extend(encoders::str(name));
+        module.section(&wasm_encoder::CustomSection {
+            name: "core",
+            data: &core,
+        });
+
+        let mut coremodules = encoders::u32(self.modules.len() as u32).collect::<Vec<_>>();
+        for name in self.modules.iter() {
+            coremodules.push(0x00);
+            coremodules.extend(encoders::str(name.as_deref().unwrap_or("<unknown>")));
+        }
+        module.section(&wasm_encoder::CustomSection {
+            name: "coremodules",
+            data: &coremodules,
+        });
+
+        let mut coreinstances = encoders::u32(self.instances.len() as u32).collect::<Vec<_>>();
+        for instance in self.instances.iter() {
+            coreinstances.push(0x00);
+            coreinstances.extend(encoders::u32(instance.module));
+            for list in [&instance.memories, &instance.globals] {
+                coreinstances.extend(encoders::u32(list.len() as u32));
+                for index in list.iter() {
+                    coreinstances.extend(encoders::u32(*index));
+                }
+            }
+        }
+        module.section(&wasm_encoder::CustomSection {
+            name: "coreinstances",
+            data: &coreinstances,
+        });
+
+        let mut corestack = vec![0x00];
+        corestack.extend(encoders::str("main"));
+        corestack.extend(encoders::u32(self.frames.len() as u32));
+        for frame in self.frames.iter() {
+            corestack.push(0x00);
+            corestack.extend(encoders::u32(frame.instance));
+            corestack.extend(encoders::u32(frame.func_index));
+            corestack.extend(encoders::u32(frame.func_offset));
+            corestack.extend(encoders::u32(frame.locals.len() as u32));
+            for local in frame.locals.iter() {
+                match local {
+                    Some(Val::I32(i)) => {
+                        corestack.push(0x7f);
+                        corestack.extend(encoders::s32(*i));
+                    }
+                    Some(Val::I64(i)) => {
+                        corestack.push(0x7e);
+                        corestack.extend(encoders::s64(*i));
+                    }
+                    Some(Val::F32(bits)) => {
+                        corestack.push(0x7d);
+                        corestack.extend(bits.to_le_bytes());
+                    }
+                    Some(Val::F64(bits)) => {
+                        corestack.push(0x7c);
+                        corestack.extend(bits.to_le_bytes());
+                    }
+                    // The format has no encoding of vectors or references, so
+                    // they're recorded as missing.
+                    _ => corestack.push(0x01),
+                }
+            }
+            // The operand stack isn't recorded.
+            corestack.extend(encoders::u32(0));
+        }
+        module.section(&wasm_encoder::CustomSection {
+            name: "corestack",
+            data: &corestack,
+        });
+
+        let mut memories = wasm_encoder::MemorySection::new();
+        let mut data = wasm_encoder::DataSection::new();
+        for (i, memory) in self.memories.iter().enumerate() {
+            memories.memory(wasm_encoder::MemoryType {
+                minimum: memory.data.len() as u64 / u64::from(WASM_PAGE_SIZE),
+                maximum: memory.maximum,
+                memory64: memory.memory64,
+            });
+            let offset = if memory.memory64 {
+                Instruction::I64Const(0)
+            } else {
+                Instruction::I32Const(0)
+            };
+            data.active(i as u32, &offset, memory.data.iter().copied());
+        }
+
+        let mut globals = wasm_encoder::GlobalSection::new();
+        for global in self.globals.iter() {
+            let (val_type, init) = match global.ty {
+                WasmType::I32 => (
+                    wasm_encoder::ValType::I32,
+                    Instruction::I32Const(global.bits as i32),
+                ),
+                WasmType::I64 => (
+                    wasm_encoder::ValType::I64,
+                    Instruction::I64Const(global.bits as i64),
+                ),
+                WasmType::F32 => (
+                    wasm_encoder::ValType::F32,
+                    Instruction::F32Const(f32::from_bits(global.bits as u32)),
+                ),
+                WasmType::F64 => (
+                    wasm_encoder::ValType::F64,
+                    Instruction::F64Const(f64::from_bits(global.bits as u64)),
+                ),
+                WasmType::V128 => (
+                    wasm_encoder::ValType::V128,
+                    Instruction::V128Const(global.bits as i128),
+                ),
+                WasmType::FuncRef => (
+                    wasm_encoder::ValType::FuncRef,
+                    Instruction::RefNull(wasm_encoder::ValType::FuncRef),
+                ),
+                WasmType::ExternRef => (
+                    wasm_encoder::ValType::ExternRef,
+                    Instruction::RefNull(wasm_encoder::ValType::ExternRef),
+                ),
+            };
+            globals.global(
+                wasm_encoder::GlobalType {
+                    val_type,
+                    mutable: global.mutable,
+                },
+                &init,
+            );
+        }
+
+        module.section(&memories);
+        module.section(&globals);
+        module.section(&data);
+        module.finish()
+    }
+}
+
+impl fmt::Debug for WasmCoreDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WasmCoreDump")
+            .field("modules", &self.modules)
+            .field("instances", &self.instances.len())
+            .field("memories", &self.memories.len())
+            .field("globals", &self.globals.len())
+            .field("frames", &self.frames)
+            .finish()
+    }
+}
@@ -0,0 +1,170 @@
+//! Pausing WebAssembly compiled with guest debugging at breakpoints, and
+//! inspecting its state while paused.
+
+use crate::store::StoreContextMut;
+#[cfg(feature = "wasm-backtrace")]
+use crate::FrameInfo;
+use crate::{Module, Val, ValType};
+use anyhow::Result;
+use std::collections::HashSet;
+use std::fmt;
+use wasmtime_environ::debug_local_type;
+use wasmtime_runtime::{CompiledModuleId, ValRaw};
+
+/// What to do once a [`Store`](crate::Store)'s debug handler returns.
+///
+/// See [`Store::debug_handler`](crate::Store::debug_handler) for more
+/// information.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugAction {
+    /// Resume execution until the next breakpoint is reached.
+    Continue,
+    /// Resume execution and pause again before the next instruction, which may
+    /// be in a function called by, or returned to from, the current one.
+    Step,
+}
+
+/// The state of a WebAssembly function paused before one of its instructions.
+///
+/// This is passed to the debug handler of a [`Store`](crate::Store) each time
+/// execution pauses, whether due to a breakpoint or to single-stepping.
+pub struct DebugFrame {
+    func_index: u32,
+    module_offset: usize,
+    locals: Vec<Val>,
+    #[cfg(feature = "wasm-backtrace")]
+    backtrace: Vec<FrameInfo>,
+}
+
+impl DebugFrame {
+    /// Captures the state of the function paused before the instruction at
+    /// `module_offset`, whose locals are `locals` with types described by the
+    /// tags in `types`.
+    pub(crate) fn new<T>(
+        mut store: StoreContextMut<'_, T>,
+        func_index: u32,
+        module_offset: usize,
+        locals: &[ValRaw],
+        types: &[u8],
+    ) -> DebugFrame {
+        let locals = locals
+            .iter()
+            .zip(types)
+            .map(|(raw, tag)| {
+                let ty = debug_local_type(*tag).expect("invalid local type tag");
+                unsafe { Val::from_raw(&mut store, *raw, ValType::from_wasm_type(&ty)) }
+            })
+            .collect();
+
+        #[cfg(feature = "wasm-backtrace")]
+        let backtrace = {
+            let modules = store.0.modules();
+            let native_trace = wasmtime_runtime::Backtrace::new();
+            crate::trap::lookup_pcs(None, &native_trace)
+                .filter_map(|pc| modules.lookup_frame_info(pc))
+                .map(|(info, _module)| info)
+                .collect()
+        };
+
+        DebugFrame {
+            func_index,
+            module_offset,
+            locals,
+            #[cfg(feature = "wasm-backtrace")]
+            backtrace,
+        }
+    }
+
+    /// Returns the index, within its module, of the paused function.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the offset, within the original wasm module, of the instruction
+    /// which is about to execute.
+    pub fn module_offset(&self) -> usize {
+        self.module_offset
+    }
+
+    /// Returns the values of the paused function's locals, starting with its
+    /// parameters.
+    pub fn locals(&self) -> &[Val] {
+        &self.locals
+    }
+
+    /// Returns the value of the local with index `index`, or `None` if the
+    /// function has no such local.
+    pub fn local(&self, index: u32) -> Option<&Val> {
+        self.locals.get(index as usize)
+    }
+
+    /// Returns the WebAssembly call stack at the point execution paused.
+    ///
+    /// The first frame is the paused function itself, followed by the frames
+    /// of its callers.
+    #[cfg(feature = "wasm-backtrace")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "wasm-backtrace")))]
+    pub fn backtrace(&self) -> &[FrameInfo] {
+        &self.backtrace
+    }
+}
+
+impl fmt::Debug for DebugFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DebugFrame")
+            .field("func_index", &self.func_index)
+            .field("module_offset", &self.module_offset)
+            .field("locals", &self.locals)
+            .finish()
+    }
+}
+
+pub(crate) type DebugHandler<T> =
+    Box<dyn FnMut(StoreContextMut<'_, T>, &DebugFrame) -> Result<DebugAction> + Send + Sync>;
+
+/// The breakpoints and debug handler of a store.
+pub(crate) struct DebugState<T> {
+    pub(crate) handler: Option<DebugHandler<T>>,
+    breakpoints: HashSet<(CompiledModuleId, usize)>,
+    single_step: bool,
+}
+
+impl<T> DebugState<T> {
+    pub(crate) fn new() -> DebugState<T> {
+        DebugState {
+            handler: None,
+            breakpoints: HashSet::new(),
+            single_step: false,
+        }
+    }
+
+    pub(crate) fn set_breakpoint(&mut self, module: &Module, offset: usize) {
+        self.breakpoints
+            .insert((module.compiled_module().unique_id(), offset));
+    }
+
+    pub(crate) fn clear_breakpoint(&mut self, module: &Module, offset: usize) {
+        self.breakpoints
+            .remove(&(module.compiled_module().unique_id(), offset));
+    }
+
+    pub(crate) fn single_step(&mut self, enable: bool) {
+        self.single_step = enable;
+    }
+
+    /// Returns whether execution should pause before the instruction at
+    /// `offset` in the module `module`.
+    pub(crate) fn should_pause(&self, module: Option<CompiledModuleId>, offset: usize) -> bool {
+        if self.handler.is_none() {
+            return false;
+        }
+        self.single_step
+            || module.map_or(false, |module| self.breakpoints.contains(&(module, offset)))
+    }
+
+    /// Updates whether to single-step according to what the debug handler
+    /// asked to do next.
+    pub(crate) fn resume(&mut self, action: DebugAction) {
+        self.single_step = action == DebugAction::Step;
+    }
+}
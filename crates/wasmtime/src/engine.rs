@@ -1,6 +1,11 @@
+use crate::metrics::MetricsCounters;
 use crate::signatures::SignatureRegistry;
+#[cfg(compiler)]
+use crate::trampoline::HostTrampolines;
+#[cfg(compiler)]
+use crate::FuncType;
 use crate::{Config, Trap};
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use once_cell::sync::OnceCell;
 #[cfg(feature = "parallel-compilation")]
 use rayon::prelude::*;
@@ -11,6 +16,12 @@ use wasmtime_cache::CacheConfig;
 use wasmtime_environ::FlagValue;
 use wasmtime_runtime::{debug_builtins, CompiledModuleIdAllocator, InstanceAllocator};
 
+#[cfg(compiler)]
+mod background;
+
+#[cfg(compiler)]
+pub(crate) use background::BackgroundCompiler;
+
 /// An `Engine` which is a global context for compilation and management of wasm
 /// modules.
 ///
@@ -42,10 +53,17 @@ struct EngineInner {
     config: Config,
     #[cfg(compiler)]
     compiler: Box<dyn wasmtime_environ::Compiler>,
+    #[cfg(compiler)]
+    background: BackgroundCompiler,
+    #[cfg(compiler)]
+    host_trampolines: Option<HostTrampolines>,
+    #[cfg(feature = "parallel-compilation")]
+    thread_pool: Option<rayon::ThreadPool>,
     allocator: Box<dyn InstanceAllocator>,
     signatures: SignatureRegistry,
     epoch: AtomicU64,
     unique_id_allocator: CompiledModuleIdAllocator,
+    metrics: MetricsCounters,
 
     // One-time check of whether the compiler's settings, if present, are
     // compatible with the native host.
@@ -56,26 +74,107 @@ impl Engine {
     /// Creates a new [`Engine`] with the specified compilation and
     /// configuration settings.
     pub fn new(config: &Config) -> Result<Engine> {
-        // Ensure that wasmtime_runtime's signal handlers are configured. This
-        // is the per-program initialization required for handling traps, such
-        // as configuring signals, vectored exception handlers, etc.
-        wasmtime_runtime::init_traps(crate::module::GlobalModuleRegistry::is_wasm_trap_pc);
         debug_builtins::ensure_exported();
 
         let registry = SignatureRegistry::new();
         let mut config = config.clone();
+        if config.tunables.signals_based_traps {
+            // Ensure that wasmtime_runtime's signal handlers are configured.
+            // This is the per-program initialization required for handling
+            // traps, such as configuring signals, vectored exception handlers,
+            // etc.
+            wasmtime_runtime::init_traps(
+                crate::module::GlobalModuleRegistry::is_wasm_trap_pc,
+                config.install_signal_handlers,
+            );
+        } else {
+            // Without signal handlers there's nothing to catch accesses to
+            // guard regions, so all memories are bounds-checked explicitly.
+            config.tunables.static_memory_bound = 0;
+            config.tunables.static_memory_offset_guard_size = 0;
+            config.tunables.dynamic_memory_offset_guard_size = 0;
+            config.tunables.guard_before_linear_memory = false;
+        }
+        if config.tunables.lazy_compilation && config.tunables.generate_native_debuginfo {
+            bail!("lazy compilation cannot be enabled with native debug information");
+        }
+        if config.tunables.tiered_compilation && config.tunables.generate_native_debuginfo {
+            bail!("tiered compilation cannot be enabled with native debug information");
+        }
+        if config.tunables.bulk_memory_inline_threshold > 256 {
+            bail!("the bulk memory inline threshold cannot be more than 256 bytes");
+        }
+        if config.tunables.collect_profile
+            && !config.tunables.lazy_compilation
+            && !config.tunables.tiered_compilation
+        {
+            bail!("collecting an execution profile requires lazy compilation");
+        }
+        #[cfg(compiler)]
+        config.compiler.use_custom()?;
+        if config.deterministic {
+            if config.features.threads {
+                bail!("the wasm threads proposal cannot be enabled in deterministic mode");
+            }
+            #[cfg(compiler)]
+            config.compiler.set("enable_nan_canonicalization", "true")?;
+        }
+        #[cfg(compiler)]
+        if config.tunables.pinned_context_register {
+            use target_lexicon::Architecture;
+            match config.compiler.triple().architecture {
+                Architecture::X86_64 | Architecture::Aarch64(_) => {}
+                _ => bail!("a pinned context register is only supported on x86_64 and aarch64"),
+            }
+            config.compiler.enable("enable_pinned_reg")?;
+        }
+        #[cfg(compiler)]
+        if config.tunables.devirtualize_imports
+            && !wasmtime_environ::import_thunks_supported(config.compiler.triple())
+        {
+            bail!("devirtualizing imports isn't supported on this target");
+        }
         let allocator = config.build_allocator()?;
         allocator.adjust_tunables(&mut config.tunables);
 
+        #[cfg(feature = "parallel-compilation")]
+        let thread_pool = match config.parallel_compilation_threads {
+            Some(threads) => Some(
+                rayon::ThreadPoolBuilder::new()
+                    .num_threads(threads)
+                    .thread_name(|i| format!("wasmtime-parallel-compile-{}", i))
+                    .build()
+                    .context("failed to create the compilation thread pool")?,
+            ),
+            None => None,
+        };
+
+        #[cfg(compiler)]
+        let compiler = config.compiler.build()?;
+        #[cfg(compiler)]
+        let host_trampolines = config
+            .host_trampoline_signatures
+            .as_ref()
+            .map(|signatures| HostTrampolines::new(&*compiler, &*config.profiler, signatures))
+            .transpose()
+            .context("failed to compile the host trampolines")?;
+
         Ok(Engine {
             inner: Arc::new(EngineInner {
                 #[cfg(compiler)]
-                compiler: config.compiler.build()?,
+                compiler,
+                #[cfg(compiler)]
+                background: BackgroundCompiler::new(),
+                #[cfg(compiler)]
+                host_trampolines,
+                #[cfg(feature = "parallel-compilation")]
+                thread_pool,
                 config,
                 allocator,
                 signatures: registry,
                 epoch: AtomicU64::new(0),
                 unique_id_allocator: CompiledModuleIdAllocator::new(),
+                metrics: MetricsCounters::default(),
                 compatible_with_native_host: OnceCell::new(),
             }),
         })
@@ -113,6 +212,28 @@ impl Engine {
         &*self.inner.compiler
     }
 
+    #[cfg(compiler)]
+    pub(crate) fn background_compiler(&self) -> &BackgroundCompiler {
+        &self.inner.background
+    }
+
+    #[cfg(compiler)]
+    pub(crate) fn host_trampolines(&self) -> Option<&HostTrampolines> {
+        self.inner.host_trampolines.as_ref()
+    }
+
+    /// Returns the signatures declared with
+    /// [`Config::host_trampoline_signatures`], without duplicates, whose
+    /// trampolines this engine compiled when it was created.
+    ///
+    /// Returns `None` if no signatures were declared, in which case the
+    /// trampolines of each host function are compiled when it's created.
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn host_trampoline_signatures(&self) -> Option<&[FuncType]> {
+        self.host_trampolines().map(HostTrampolines::signatures)
+    }
+
     pub(crate) fn allocator(&self) -> &dyn InstanceAllocator {
         self.inner.allocator.as_ref()
     }
@@ -162,10 +283,37 @@ impl Engine {
         self.inner.epoch.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Releases what this engine still keeps for modules which were unloaded.
+    ///
+    /// The code of a [`Module`](crate::Module) is unloaded as soon as
+    /// nothing uses it anymore, as described by
+    /// [`Module::downgrade`](crate::Module::downgrade), but the
+    /// [pooling allocator](crate::InstanceAllocationStrategy::Pooling) keeps
+    /// the initial memory image of the last module instantiated in each of
+    /// its slots mapped, to instantiate the same module there again quickly.
+    /// This unmaps the images of modules which were unloaded from the
+    /// pooling allocator's free slots, so that a host which reloads modules
+    /// doesn't hold on to the images of the versions it replaced. It does
+    /// nothing for the on-demand allocator, which releases everything of an
+    /// instance along with it.
+    ///
+    /// Profilers are told about code as it's loaded but not as it's
+    /// unloaded, which the perf map file of
+    /// [`ProfilingStrategy::PerfMap`](crate::ProfilingStrategy::PerfMap) has
+    /// no way to express, so it keeps the entries of unloaded code even if
+    /// new code is loaded at the same addresses.
+    pub fn purge_unused_code(&self) {
+        self.allocator().purge_unused_images();
+    }
+
     pub(crate) fn unique_id_allocator(&self) -> &CompiledModuleIdAllocator {
         &self.inner.unique_id_allocator
     }
 
+    pub(crate) fn metrics_counters(&self) -> &MetricsCounters {
+        &self.inner.metrics
+    }
+
     /// Ahead-of-time (AOT) compiles a WebAssembly module.
     ///
     /// The `bytes` provided must be in one of two formats:
@@ -193,7 +341,7 @@ impl Engine {
     pub fn precompile_module(&self, bytes: &[u8]) -> Result<Vec<u8>> {
         #[cfg(feature = "wat")]
         let bytes = wat::parse_bytes(&bytes)?;
-        let (mmap, _, types) = crate::Module::build_artifacts(self, &bytes)?;
+        let (mmap, _, types) = crate::Module::build_artifacts(self, &bytes, None, None)?;
         crate::module::SerializedModule::from_artifacts(self, &mmap, &types)
             .to_bytes(&self.config().module_version)
     }
@@ -210,10 +358,18 @@ impl Engine {
     ) -> Result<Vec<B>, E> {
         if self.config().parallel_compilation {
             #[cfg(feature = "parallel-compilation")]
-            return input
-                .into_par_iter()
-                .map(|a| f(a))
-                .collect::<Result<Vec<B>, E>>();
+            {
+                let run = || {
+                    input
+                        .into_par_iter()
+                        .map(|a| f(a))
+                        .collect::<Result<Vec<B>, E>>()
+                };
+                return match &self.inner.thread_pool {
+                    Some(pool) => pool.install(run),
+                    None => run(),
+                };
+            }
         }
 
         // In case the parallel-compilation feature is disabled or the parallel_compilation config
@@ -224,6 +380,25 @@ impl Engine {
             .collect::<Result<Vec<B>, E>>()
     }
 
+    /// Runs `f` in the background on the threads used for parallel
+    /// compilation, or right away on this thread if compilation isn't
+    /// parallel.
+    #[cfg(compiler)]
+    pub(crate) fn spawn_maybe_parallel(&self, f: impl FnOnce() + Send + 'static) {
+        if self.config().parallel_compilation {
+            #[cfg(feature = "parallel-compilation")]
+            {
+                match &self.inner.thread_pool {
+                    Some(pool) => pool.spawn(f),
+                    None => rayon::spawn(f),
+                }
+                return;
+            }
+        }
+
+        f()
+    }
+
     /// Returns the target triple which this engine is compiling code for
     /// and/or running code for.
     pub(crate) fn target(&self) -> target_lexicon::Triple {
@@ -312,11 +487,16 @@ impl Engine {
             "baldrdash_prologue_words" => *value == FlagValue::Num(0),
             "enable_llvm_abi_extensions" => *value == FlagValue::Bool(false),
             "emit_all_ones_funcaddrs" => *value == FlagValue::Bool(false),
-            "enable_pinned_reg" => *value == FlagValue::Bool(false),
             "enable_probestack" => *value == FlagValue::Bool(false),
             "use_colocated_libcalls" => *value == FlagValue::Bool(false),
             "use_pinned_reg_as_heap_base" => *value == FlagValue::Bool(false),
 
+            // The pinned register is reserved exactly when the trampolines
+            // entering wasm keep the store's context in it.
+            "enable_pinned_reg" => {
+                *value == FlagValue::Bool(self.config().tunables.pinned_context_register)
+            }
+
             // If reference types are enabled this must be enabled, otherwise
             // this setting can have any value.
             "enable_safepoints" => {
@@ -397,6 +577,10 @@ impl Engine {
         {
             enabled = match flag {
                 "has_lse" => Some(std::arch::is_aarch64_feature_detected!("lse")),
+
+                // Pointer authentication and `bti` instructions are NOPs on
+                // CPUs without them.
+                "sign_return_address" | "sign_return_address_with_bkey" | "use_bti" => Some(true),
                 // fall through to the very bottom to indicate that support is
                 // not enabled to test whether this feature is enabled on the
                 // host.
@@ -441,6 +625,9 @@ impl Engine {
                 "has_avx512vbmi" => Some(std::is_x86_feature_detected!("avx512vbmi")),
                 "has_lzcnt" => Some(std::is_x86_feature_detected!("lzcnt")),
 
+                // `endbr64` is a NOP on CPUs without CET.
+                "use_ibt" => Some(true),
+
                 // fall through to the very bottom to indicate that support is
                 // not enabled to test whether this feature is enabled on the
                 // host.
@@ -542,4 +729,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn cache_shared_between_engines() -> Result<()> {
+        let td = TempDir::new()?;
+        let config_path = td.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            &format!(
+                "
+                    [cache]
+                    enabled = true
+                    directory = '{}'
+                ",
+                td.path().join("cache").display()
+            ),
+        )?;
+        let mut cfg = Config::new();
+        cfg.cache_config_load(&config_path)?;
+        let engine = Engine::new(&cfg)?;
+        Module::new(&engine, "(module (func))")?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+
+        // A fresh engine with the same settings reuses the artifacts of the
+        // first one, but only for the same module. Note that the statistics
+        // are shared by all engines created from `cfg`.
+        let engine = Engine::new(&cfg)?;
+        Module::new(&engine, "(module (func))")?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 1);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+        Module::new(&engine, "(module (func) (func))")?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 1);
+        assert_eq!(engine.config().cache_config.cache_misses(), 2);
+
+        // Enabled wasm features are part of the cache key.
+        let mut cfg = Config::new();
+        cfg.wasm_simd(false).cache_config_load(&config_path)?;
+        let engine = Engine::new(&cfg)?;
+        Module::new(&engine, "(module (func))")?;
+        assert_eq!(engine.config().cache_config.cache_hits(), 0);
+        assert_eq!(engine.config().cache_config.cache_misses(), 1);
+
+        Ok(())
+    }
 }
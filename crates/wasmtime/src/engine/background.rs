@@ -0,0 +1,62 @@
+//! Threads owned by an [`Engine`](crate::Engine) for compilation which happens
+//! in the background, such as recompiling hot functions with tiered
+//! compilation.
+
+use once_cell::sync::OnceCell;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// A pool of threads running compilation jobs in the background.
+///
+/// The threads are only spawned when the first job is queued, and exit once
+/// the pool is dropped and they've finished the jobs queued so far. Jobs
+/// shouldn't keep the engine alive while they're queued, since the pool
+/// belongs to it.
+pub(crate) struct BackgroundCompiler {
+    threads: usize,
+    queue: OnceCell<Mutex<Sender<Job>>>,
+}
+
+impl BackgroundCompiler {
+    pub(crate) fn new() -> BackgroundCompiler {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get().min(4));
+        BackgroundCompiler {
+            threads,
+            queue: OnceCell::new(),
+        }
+    }
+
+    /// Queues `job` to run on one of the pool's threads.
+    ///
+    /// The job never runs if none of the pool's threads could be spawned.
+    pub(crate) fn spawn(&self, job: impl FnOnce() + Send + 'static) {
+        let queue = self.queue.get_or_init(|| {
+            let (sender, receiver) = mpsc::channel();
+            let receiver = Arc::new(Mutex::new(receiver));
+            for i in 0..self.threads {
+                let receiver = receiver.clone();
+                let spawned = thread::Builder::new()
+                    .name(format!("wasmtime-compile-{}", i))
+                    .spawn(move || run(&receiver));
+                if let Err(e) = spawned {
+                    log::warn!("failed to spawn a background compilation thread: {}", e);
+                }
+            }
+            Mutex::new(sender)
+        });
+        let _ = queue.lock().unwrap().send(Box::new(job));
+    }
+}
+
+fn run(receiver: &Mutex<Receiver<Job>>) {
+    loop {
+        let job = receiver.lock().unwrap().recv();
+        match job {
+            Ok(job) => job(),
+            Err(_) => break,
+        }
+    }
+}
@@ -0,0 +1,130 @@
+//! Profiles of how a module's functions executed, which guide how they're
+//! compiled the next time the module is created.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+#[cfg(compiler)]
+use wasmtime_environ::{count_profiled_branches, ModuleTranslation};
+use wasmtime_environ::{DefinedFuncIndex, FunctionProfile, PrimaryMap};
+
+/// A profile of how the functions of a [`Module`](crate::Module) executed:
+/// how many times each was called, and which way each of its branches went.
+///
+/// Profiles are collected by modules compiled with
+/// [`Config::collect_execution_profile`](crate::Config::collect_execution_profile),
+/// retrieved with
+/// [`Module::execution_profile`](crate::Module::execution_profile), and fed
+/// back into the compilation of the same module with
+/// [`Module::new_with_profile`](crate::Module::new_with_profile). In between
+/// they can be [serialized](ExecutionProfile::serialize) to be kept alongside
+/// the module, and profiles of several runs can be
+/// [merged](ExecutionProfile::merge).
+///
+/// The branches of a function are its `if` and `br_if` instructions, in the
+/// order they appear in its body. A branch is taken when its condition is
+/// nonzero: a `br_if` branches, or an `if` runs its consequent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionProfile {
+    num_imported_funcs: u32,
+    functions: PrimaryMap<DefinedFuncIndex, FunctionProfile>,
+}
+
+impl ExecutionProfile {
+    pub(crate) fn new(
+        num_imported_funcs: usize,
+        functions: PrimaryMap<DefinedFuncIndex, FunctionProfile>,
+    ) -> ExecutionProfile {
+        ExecutionProfile {
+            num_imported_funcs: num_imported_funcs as u32,
+            functions,
+        }
+    }
+
+    fn function(&self, func_index: u32) -> Option<&FunctionProfile> {
+        let index = func_index.checked_sub(self.num_imported_funcs)?;
+        self.functions.get(DefinedFuncIndex::from_u32(index))
+    }
+
+    /// Returns how many times the function with index `func_index` within
+    /// the module was called, or zero if it's imported.
+    pub fn calls(&self, func_index: u32) -> u64 {
+        self.function(func_index).map_or(0, |f| f.calls)
+    }
+
+    /// Returns how many times each branch of the function with index
+    /// `func_index` was executed, along with how many times it was taken, in
+    /// order.
+    pub fn branches(&self, func_index: u32) -> impl Iterator<Item = (u64, u64)> + '_ {
+        self.function(func_index)
+            .into_iter()
+            .flat_map(|f| f.branches.iter().map(|b| (b.executed, b.taken)))
+    }
+
+    /// Adds the counts of `other`, a profile of the same module, to this
+    /// profile.
+    ///
+    /// # Errors
+    ///
+    /// Fails if `other` is a profile of a different module.
+    pub fn merge(&mut self, other: &ExecutionProfile) -> Result<()> {
+        self.check_shape(
+            other.num_imported_funcs,
+            other.functions.values().map(|f| f.branches.len()),
+        )?;
+        for (mine, theirs) in self.functions.values_mut().zip(other.functions.values()) {
+            mine.calls += theirs.calls;
+            for (mine, theirs) in mine.branches.iter_mut().zip(&theirs.branches) {
+                mine.executed += theirs.executed;
+                mine.taken += theirs.taken;
+            }
+        }
+        Ok(())
+    }
+
+    /// Serializes the profile into bytes, which
+    /// [`ExecutionProfile::deserialize`] turns back into a profile.
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+
+    /// Deserializes a profile previously serialized with
+    /// [`ExecutionProfile::serialize`].
+    pub fn deserialize(bytes: &[u8]) -> Result<ExecutionProfile> {
+        bincode::deserialize(bytes).context("failed to deserialize execution profile")
+    }
+
+    /// Checks that this is a profile of a module with `num_imported_funcs`
+    /// imported functions, whose functions have `branches` branches each.
+    fn check_shape(
+        &self,
+        num_imported_funcs: u32,
+        branches: impl ExactSizeIterator<Item = usize>,
+    ) -> Result<()> {
+        if num_imported_funcs != self.num_imported_funcs || branches.len() != self.functions.len() {
+            bail!("execution profile is of a module with different functions");
+        }
+        for (f, branches) in self.functions.values().zip(branches) {
+            if f.branches.len() != branches {
+                bail!("execution profile is of a module with different function bodies");
+            }
+        }
+        Ok(())
+    }
+
+    /// Gives the functions of `translation`, which must be of the module this
+    /// is a profile of, their profiles for their compilation.
+    #[cfg(compiler)]
+    pub(crate) fn apply(&self, translation: &mut ModuleTranslation<'_>) -> Result<()> {
+        let branches = translation
+            .function_body_inputs
+            .values()
+            .map(|f| count_profiled_branches(&f.body))
+            .collect::<Result<Vec<_>, _>>()?;
+        self.check_shape(
+            translation.module.num_imported_funcs as u32,
+            branches.into_iter(),
+        )?;
+        translation.profile = self.functions.clone();
+        Ok(())
+    }
+}
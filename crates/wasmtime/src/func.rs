@@ -4,17 +4,19 @@ use crate::{
     StoreContextMut, Trap, Val, ValRaw, ValType,
 };
 use anyhow::{bail, Context as _, Result};
+use std::fmt;
 use std::future::Future;
 use std::mem;
 use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
 use std::ptr::NonNull;
 use std::sync::Arc;
+use std::time::Duration;
 use wasmtime_environ::FuncIndex;
 use wasmtime_runtime::{
     raise_user_trap, ExportFunction, InstanceAllocator, InstanceHandle, OnDemandInstanceAllocator,
-    VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport, VMSharedSignatureIndex,
-    VMTrampoline,
+    ProtectionMask, VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport,
+    VMNativeTrampoline, VMSharedSignatureIndex, VMTrampoline,
 };
 
 /// A WebAssembly function which can be called.
@@ -203,6 +205,7 @@ enum FuncKind {
     /// function.
     StoreOwned {
         trampoline: VMTrampoline,
+        native_trampoline: Option<VMNativeTrampoline>,
         export: ExportFunction,
     },
 
@@ -266,6 +269,11 @@ macro_rules! for_each_function_signature {
 mod typed;
 pub use typed::*;
 
+#[cfg(feature = "async")]
+mod resumable;
+#[cfg(feature = "async")]
+pub use resumable::*;
+
 macro_rules! generate_wrap_async_func {
     ($num:tt $($args:ident)*) => (paste::paste!{
         /// Same as [`Func::wrap`], except the closure asynchronously produces
@@ -329,6 +337,14 @@ impl Func {
     ///
     /// For more information about `Send + Sync + 'static` requirements on the
     /// `func`, see [`Func::wrap`](#why-send--sync--static).
+    ///
+    /// # Panics
+    ///
+    /// This function will panic if the trampolines of `func` can't be
+    /// compiled, or if `ty` isn't one of the signatures declared with
+    /// [`Config::host_trampoline_signatures`](crate::Config::host_trampoline_signatures).
+    /// See [`Func::try_new`] for a version of this function which returns
+    /// those errors instead.
     #[cfg(compiler)]
     #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
     pub fn new<T>(
@@ -336,12 +352,29 @@ impl Func {
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
     ) -> Self {
-        let ty_clone = ty.clone();
-        unsafe {
-            Func::new_unchecked(store, ty, move |caller, values| {
-                Func::invoke(caller, &ty_clone, values, &func)
-            })
-        }
+        Func::try_new(store, ty, func).expect("failed to create function")
+    }
+
+    /// Same as [`Func::new`], except that it returns an error rather than
+    /// panic if the function can't be created.
+    ///
+    /// # Errors
+    ///
+    /// This function returns an [`UndeclaredSignatureError`] if the engine of
+    /// `store` compiled the trampolines of the signatures declared with
+    /// [`Config::host_trampoline_signatures`](crate::Config::host_trampoline_signatures)
+    /// and `ty` isn't one of them, or an error if the trampolines of `func`
+    /// can't be compiled otherwise.
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn try_new<T>(
+        mut store: impl AsContextMut<Data = T>,
+        ty: FuncType,
+        func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
+    ) -> Result<Self> {
+        let store = store.as_context_mut().0;
+        let host = HostFunc::new(store.engine(), ty, func)?;
+        unsafe { Ok(host.into_func(store)) }
     }
 
     /// Creates a new [`Func`] with the given arguments, although has fewer
@@ -362,6 +395,10 @@ impl Func {
     /// This function is not safe because it's not known at compile time that
     /// the `func` provided correctly interprets the argument types provided to
     /// it, or that the results it produces will be of the correct type.
+    ///
+    /// # Panics
+    ///
+    /// This function panics in the same cases as [`Func::new`].
     #[cfg(compiler)]
     #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
     pub unsafe fn new_unchecked<T>(
@@ -370,7 +407,8 @@ impl Func {
         func: impl Fn(Caller<'_, T>, *mut ValRaw) -> Result<(), Trap> + Send + Sync + 'static,
     ) -> Self {
         let store = store.as_context_mut().0;
-        let host = HostFunc::new_unchecked(store.engine(), ty, func);
+        let host =
+            HostFunc::new_unchecked(store.engine(), ty, func).expect("failed to create function");
         host.into_func(store)
     }
 
@@ -392,7 +430,8 @@ impl Func {
     /// # Panics
     ///
     /// This function will panic if `store` is not associated with an [async
-    /// config](crate::Config::async_support).
+    /// config](crate::Config::async_support), and in the same cases as
+    /// [`Func::new`].
     ///
     /// # Examples
     ///
@@ -991,7 +1030,15 @@ impl Func {
     ) -> Self {
         let anyfunc = export.anyfunc.as_ref();
         let trampoline = store.lookup_trampoline(&*anyfunc);
-        Func::from_func_kind(FuncKind::StoreOwned { trampoline, export }, store)
+        let native_trampoline = store.modules().lookup_native_trampoline(anyfunc);
+        Func::from_func_kind(
+            FuncKind::StoreOwned {
+                trampoline,
+                native_trampoline,
+                export,
+            },
+            store,
+        )
     }
 
     fn from_func_kind(kind: FuncKind, store: &mut StoreOpaque) -> Self {
@@ -1224,14 +1271,45 @@ pub(crate) fn invoke_wasm_and_catch_traps<T>(
             exit_wasm(store, exit);
             return Err(trap);
         }
+        // If this store's memories are protected with a key then wasm only
+        // runs with access to them, so it can't touch other stores' memories
+        // interleaved with them in the pooling allocator.
+        let previous_mask = store.0.pkey().map(|pkey| {
+            let previous = ProtectionMask::current();
+            ProtectionMask::zero().or(pkey).allow();
+            previous
+        });
+        // The records of the frames of code compiled to capture core dumps
+        // are copied when a trap happens, and the trap leaves the records of
+        // the frames it unwound in the list, so its head is restored after.
+        let mut coredump_stack = Vec::new();
+        let capture_coredump = store.engine().config().tunables.coredump_frames;
+        let last_coredump_frame = *store.0.runtime_limits().last_coredump_frame.get();
         let result = wasmtime_runtime::catch_traps(
             store.0.signal_handler(),
             store.0.default_callee(),
+            if capture_coredump {
+                Some(&mut coredump_stack)
+            } else {
+                None
+            },
             closure,
         );
+        *store.0.runtime_limits().last_coredump_frame.get() = last_coredump_frame;
+        if let Some(mask) = previous_mask {
+            mask.allow();
+        }
         exit_wasm(store, exit);
         store.0.call_hook(CallHook::ReturningFromWasm)?;
-        result.map_err(Trap::from_runtime_box)
+        result.map_err(|trap| {
+            store.engine().metrics_counters().trapped();
+            let trap = Trap::from_runtime_box(trap);
+            #[cfg(feature = "coredump")]
+            if capture_coredump {
+                return trap.with_coredump(store.0, &coredump_stack);
+            }
+            trap
+        })
     }
 }
 
@@ -1280,7 +1358,7 @@ fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Option<usize> {
     // immediately trap. This is checked on the entry to all wasm functions.
     //
     // Note that this isn't 100% precise. We are requested to give wasm
-    // `max_wasm_stack` bytes, but what we're actually doing is giving wasm
+    // `max_wasm_stack` bytes, the store's limit, but what we're actually doing is giving wasm
     // probably a little less than `max_wasm_stack` because we're
     // calculating the limit relative to this function's approximate stack
     // pointer. Wasm will be executed on a frame beneath this one (or next
@@ -1290,7 +1368,7 @@ fn enter_wasm<T>(store: &mut StoreContextMut<'_, T>) -> Option<usize> {
     //
     // After we've got the stack limit then we store it into the `stack_limit`
     // variable.
-    let wasm_stack_limit = stack_pointer - store.engine().config().max_wasm_stack;
+    let wasm_stack_limit = stack_pointer.saturating_sub(store.0.max_wasm_stack());
     let prev_stack = unsafe {
         mem::replace(
             &mut *store.0.runtime_limits().stack_limit.get(),
@@ -1759,6 +1837,14 @@ impl<T> Caller<'_, T> {
         self.store.fuel_consumed()
     }
 
+    /// Returns the CPU time consumed by this store's WebAssembly.
+    ///
+    /// For more information see
+    /// [`Store::cpu_time_consumed`](crate::Store::cpu_time_consumed)
+    pub fn cpu_time_consumed(&self) -> Option<Duration> {
+        self.store.cpu_time_consumed()
+    }
+
     /// Inject more fuel into this store to be consumed when executing wasm code.
     ///
     /// For more information see [`Store::add_fuel`](crate::Store::add_fuel)
@@ -1790,6 +1876,15 @@ impl<T> Caller<'_, T> {
         self.store
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
+
+    /// Configures this `Store` to yield while executing futures every
+    /// `interval` units of fuel.
+    ///
+    /// For more information see
+    /// [`Store::fuel_async_yield_interval`](crate::Store::fuel_async_yield_interval)
+    pub fn fuel_async_yield_interval(&mut self, interval: Option<u64>) -> Result<()> {
+        self.store.fuel_async_yield_interval(interval)
+    }
 }
 
 impl<T> AsContext for Caller<'_, T> {
@@ -1989,6 +2084,68 @@ macro_rules! impl_into_func {
 
 for_each_function_signature!(impl_into_func);
 
+/// The error of creating a host function with [`Func::try_new`] or a
+/// [`Linker`](crate::Linker) whose signature wasn't declared with
+/// [`Config::host_trampoline_signatures`](crate::Config::host_trampoline_signatures),
+/// which can be recovered from the returned [`anyhow::Error`] with
+/// `downcast_ref`.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let unary = FuncType::new([ValType::I32], [ValType::I32]);
+/// let mut config = Config::new();
+/// config.host_trampoline_signatures([unary.clone()]);
+/// let engine = Engine::new(&config)?;
+/// let mut store = Store::new(&engine, ());
+/// Func::try_new(&mut store, unary, |_, params, results| {
+///     results[0] = params[0].clone();
+///     Ok(())
+/// })?;
+///
+/// let nullary = FuncType::new([], []);
+/// let error = Func::try_new(&mut store, nullary.clone(), |_, _, _| Ok(())).unwrap_err();
+/// let undeclared = error.downcast_ref::<UndeclaredSignatureError>().unwrap();
+/// assert_eq!(undeclared.ty(), &nullary);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct UndeclaredSignatureError {
+    ty: FuncType,
+}
+
+impl UndeclaredSignatureError {
+    #[cfg(compiler)]
+    pub(crate) fn new(ty: FuncType) -> Self {
+        Self { ty }
+    }
+
+    /// Returns the signature of the function which couldn't be created.
+    pub fn ty(&self) -> &FuncType {
+        &self.ty
+    }
+}
+
+impl fmt::Display for UndeclaredSignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |tys: &mut dyn Iterator<Item = ValType>| {
+            tys.map(|ty| ty.to_string()).collect::<Vec<_>>().join(", ")
+        };
+        write!(
+            f,
+            "the signature `({}) -> ({})` of the host function is not one of those declared \
+             with `Config::host_trampoline_signatures`",
+            list(&mut self.ty.params()),
+            list(&mut self.ty.results())
+        )
+    }
+}
+
+impl std::error::Error for UndeclaredSignatureError {}
+
 /// Representation of a host-defined function.
 ///
 /// This is used for `Func::new` but also for `Linker`-defined functions. For
@@ -2015,13 +2172,13 @@ pub(crate) struct HostFunc {
 }
 
 impl HostFunc {
-    /// Analog of [`Func::new`]
+    /// Analog of [`Func::try_new`]
     #[cfg(compiler)]
     pub fn new<T>(
         engine: &Engine,
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
-    ) -> Self {
+    ) -> Result<Self> {
         let ty_clone = ty.clone();
         unsafe {
             HostFunc::new_unchecked(engine, ty, move |caller, values| {
@@ -2036,7 +2193,7 @@ impl HostFunc {
         engine: &Engine,
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, *mut ValRaw) -> Result<(), Trap> + Send + Sync + 'static,
-    ) -> Self {
+    ) -> Result<Self> {
         let func = move |caller_vmctx, values: *mut ValRaw| {
             Caller::<T>::with(caller_vmctx, |mut caller| {
                 caller.store.0.call_hook(CallHook::CallingHost)?;
@@ -2045,9 +2202,8 @@ impl HostFunc {
                 Ok(result)
             })
         };
-        let (instance, trampoline) = crate::trampoline::create_function(&ty, func, engine)
-            .expect("failed to create function");
-        HostFunc::_new(engine, instance, trampoline)
+        let (instance, trampoline) = crate::trampoline::create_function(&ty, func, engine)?;
+        Ok(HostFunc::_new(engine, instance, trampoline))
     }
 
     /// Analog of [`Func::wrap`]
@@ -2128,6 +2284,18 @@ impl HostFunc {
     pub(crate) fn sig_index(&self) -> VMSharedSignatureIndex {
         unsafe { self.export.anyfunc.as_ref().type_index }
     }
+
+    /// Returns how wasm calls this function, which is the same in every store
+    /// it's used in.
+    pub(crate) fn vmimport(&self) -> VMFunctionImport {
+        unsafe {
+            let f = self.export.anyfunc.as_ref();
+            VMFunctionImport {
+                body: f.func_ptr,
+                vmctx: f.vmctx,
+            }
+        }
+    }
 }
 
 impl Drop for HostFunc {
@@ -2155,6 +2323,22 @@ impl FuncData {
         }
     }
 
+    /// Returns the trampoline which calls to this function from the host with
+    /// statically known arguments go through, or `None` if they call it
+    /// directly.
+    ///
+    /// Only wasm functions have one, and only when the trampolines entering
+    /// wasm pin the store's context.
+    #[inline]
+    pub(crate) fn native_trampoline(&self) -> Option<VMNativeTrampoline> {
+        match &self.kind {
+            FuncKind::StoreOwned {
+                native_trampoline, ..
+            } => *native_trampoline,
+            _ => None,
+        }
+    }
+
     #[inline]
     fn export(&self) -> &ExportFunction {
         self.kind.export()
@@ -0,0 +1,159 @@
+use crate::{Caller, Func, StoreContextMut, Val};
+use anyhow::{anyhow, Result};
+use std::future::Future;
+use std::pin::Pin;
+use std::ptr;
+use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// The outcome of a call started with [`Func::call_resumable`] or continued
+/// with [`Resumable::resume`].
+pub enum ResumableCall<'a> {
+    /// The function returned, producing these results.
+    Finished(Vec<Val>),
+
+    /// A host function called by WebAssembly invoked [`Caller::suspend`], and
+    /// the call can be continued later with [`Resumable::resume`].
+    Suspended(Resumable<'a>),
+}
+
+/// A call into WebAssembly which has been suspended by a host function.
+///
+/// The WebAssembly stack of the call is kept alive on its own fiber until the
+/// call is resumed, and the store of the call stays borrowed for as long as
+/// this handle is alive. Dropping a `Resumable` without resuming it to
+/// completion cancels the call: the host function which suspended it sees an
+/// error returned from [`Caller::suspend`], which it should propagate to unwind
+/// the call's stack.
+pub struct Resumable<'a> {
+    call: Pin<Box<dyn Future<Output = Result<Vec<Val>>> + Send + 'a>>,
+}
+
+impl<'a> Resumable<'a> {
+    /// Continues executing the suspended call until it either returns or is
+    /// suspended again.
+    ///
+    /// Returns an error if the call traps, or if computing its results
+    /// otherwise fails.
+    pub fn resume(mut self) -> Result<ResumableCall<'a>> {
+        let waker = noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        match self.call.as_mut().poll(&mut cx) {
+            Poll::Ready(results) => Ok(ResumableCall::Finished(results?)),
+            Poll::Pending => Ok(ResumableCall::Suspended(self)),
+        }
+    }
+}
+
+impl Func {
+    /// Invokes this function with the `params` given, allowing host functions
+    /// it calls to suspend it with [`Caller::suspend`].
+    ///
+    /// This is a synchronous alternative to [`Func::call_async`] for embedders
+    /// which schedule WebAssembly themselves, for example running many guest
+    /// tasks as cooperative green threads. The call runs on its own fiber and
+    /// executes until either it returns, producing
+    /// [`ResumableCall::Finished`], or a host function suspends it, producing
+    /// [`ResumableCall::Suspended`] with a handle to continue it later.
+    ///
+    /// Asynchronous host functions may also be called, in which case the call
+    /// is suspended each time their future is pending and resuming it polls the
+    /// future again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the types of `params` don't match this function's
+    /// type, or if the call traps before it's first suspended.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this is called on a function in a synchronous store, since
+    /// fibers are only available with [asynchronous
+    /// configs](crate::Config::async_support). Also panics if `store` does not
+    /// own this function.
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub fn call_resumable<'a, T>(
+        &self,
+        mut store: StoreContextMut<'a, T>,
+        params: &[Val],
+    ) -> Result<ResumableCall<'a>>
+    where
+        T: Send,
+    {
+        assert!(
+            store.0.async_support(),
+            "cannot use `call_resumable` without enabling async support in the config",
+        );
+        let func = *self;
+        let params = params.to_vec();
+        let mut results = vec![Val::null(); self.ty(&store).results().len()];
+        let call = Resumable {
+            call: Box::pin(async move {
+                func.call_async(&mut store, &params, &mut results).await?;
+                Ok(results)
+            }),
+        };
+        call.resume()
+    }
+}
+
+impl<T> Caller<'_, T> {
+    /// Suspends the WebAssembly call which invoked this host function,
+    /// returning control to the embedder.
+    ///
+    /// When called during [`Func::call_resumable`] this causes the call to
+    /// return [`ResumableCall::Suspended`], and this function returns once the
+    /// embedder calls [`Resumable::resume`]. When called during an
+    /// asynchronous call such as [`Func::call_async`] the call's future instead
+    /// yields once, waking itself to be polled again.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this store isn't currently executing an asynchronous
+    /// or resumable call, or if the call was cancelled while suspended. Host
+    /// functions should propagate the latter error to let the call unwind.
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
+    pub fn suspend(&mut self) -> Result<()> {
+        let cx = if self.store.0.async_support() {
+            self.store.0.async_cx()
+        } else {
+            None
+        };
+        let cx = cx.ok_or_else(|| {
+            anyhow!("cannot suspend outside of an asynchronous or resumable call")
+        })?;
+        let mut yield_once = YieldOnce(false);
+        unsafe {
+            cx.block_on(Pin::new(&mut yield_once) as Pin<&mut (dyn Future<Output = ()> + Send)>)?;
+        }
+        Ok(())
+    }
+}
+
+/// A future which is pending the first time it's polled, waking itself, and
+/// ready afterwards.
+struct YieldOnce(bool);
+
+impl Future for YieldOnce {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+/// Returns a waker which does nothing, since resumable calls are only polled
+/// again when the embedder resumes them.
+fn noop_waker() -> Waker {
+    const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+    unsafe fn clone(_: *const ()) -> RawWaker {
+        RawWaker::new(ptr::null(), &VTABLE)
+    }
+    unsafe fn noop(_: *const ()) {}
+    unsafe { Waker::from_raw(RawWaker::new(ptr::null(), &VTABLE)) }
+}
@@ -5,7 +5,7 @@ use anyhow::{bail, Result};
 use std::marker;
 use std::mem::{self, MaybeUninit};
 use std::ptr;
-use wasmtime_runtime::{VMContext, VMFunctionBody};
+use wasmtime_runtime::{VMContext, VMFunctionBody, VMNativeTrampoline};
 
 /// A statically typed WebAssembly function.
 ///
@@ -152,24 +152,26 @@ where
         // the memory go away, so the size matters here for performance.
         let mut captures = (
             self.func.caller_checked_anyfunc(store.0),
+            store.0.store_data()[self.func.0].native_trampoline(),
             MaybeUninit::uninit(),
             params,
             false,
         );
 
         let result = invoke_wasm_and_catch_traps(store, |callee| {
-            let (anyfunc, ret, params, returned) = &mut captures;
+            let (anyfunc, trampoline, ret, params, returned) = &mut captures;
             let anyfunc = anyfunc.as_ref();
             let result = Params::invoke::<Results>(
                 anyfunc.func_ptr.as_ptr(),
                 anyfunc.vmctx,
                 callee,
+                *trampoline,
                 *params,
             );
             ptr::write(ret.as_mut_ptr(), result);
             *returned = true
         });
-        let (_, ret, _, returned) = captures;
+        let (_, _, ret, _, returned) = captures;
         debug_assert_eq!(result.is_ok(), returned);
         result?;
         Ok(Results::from_abi(store.0, ret.assume_init()))
@@ -448,6 +450,7 @@ pub unsafe trait WasmParams: Send {
         func: *const VMFunctionBody,
         vmctx1: *mut VMContext,
         vmctx2: *mut VMContext,
+        trampoline: Option<VMNativeTrampoline>,
         abi: Self::Abi,
     ) -> R::ResultAbi;
 }
@@ -478,9 +481,10 @@ where
         func: *const VMFunctionBody,
         vmctx1: *mut VMContext,
         vmctx2: *mut VMContext,
+        trampoline: Option<VMNativeTrampoline>,
         abi: Self::Abi,
     ) -> R::ResultAbi {
-        <(T,) as WasmParams>::invoke::<R>(func, vmctx1, vmctx2, abi)
+        <(T,) as WasmParams>::invoke::<R>(func, vmctx1, vmctx2, trampoline, abi)
     }
 }
 
@@ -538,8 +542,29 @@ macro_rules! impl_wasm_params {
                 func: *const VMFunctionBody,
                 vmctx1: *mut VMContext,
                 vmctx2: *mut VMContext,
+                trampoline: Option<VMNativeTrampoline>,
                 abi: Self::Abi,
             ) -> R::ResultAbi {
+                let ($($t,)*) = abi;
+
+                // A native trampoline takes the same arguments as `func`, with
+                // `func` itself inserted after the `VMContext`s.
+                if let Some(trampoline) = trampoline {
+                    let fnptr = mem::transmute::<
+                        *const VMFunctionBody,
+                        unsafe extern "C" fn(
+                            *mut VMContext,
+                            *mut VMContext,
+                            *const VMFunctionBody,
+                            $($t::Abi,)*
+                            <R::ResultAbi as HostAbi>::Retptr,
+                        ) -> <R::ResultAbi as HostAbi>::Abi,
+                    >(trampoline.as_ptr());
+                    return <R::ResultAbi as HostAbi>::call(|retptr| {
+                        fnptr(vmctx1, vmctx2, func, $($t,)* retptr)
+                    });
+                }
+
                 let fnptr = mem::transmute::<
                     *const VMFunctionBody,
                     unsafe extern "C" fn(
@@ -549,7 +574,6 @@ macro_rules! impl_wasm_params {
                         <R::ResultAbi as HostAbi>::Retptr,
                     ) -> <R::ResultAbi as HostAbi>::Abi,
                 >(func);
-                let ($($t,)*) = abi;
                 // Use the `call` function to acquire a `retptr` which we'll
                 // forward to the native function. Once we have it we also
                 // convert all our arguments to abi arguments to go to the raw
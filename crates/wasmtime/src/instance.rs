@@ -2,13 +2,15 @@ use crate::linker::Definition;
 use crate::store::{InstanceId, StoreOpaque, Stored};
 use crate::types::matching;
 use crate::{
-    AsContextMut, Engine, Export, Extern, Func, Global, Memory, Module, StoreContextMut, Table,
-    Trap, TypedFunc,
+    AsContextMut, Engine, Export, Extern, ExternType, Func, Global, Memory, Module, ModuleExport,
+    Snapshot, StoreContextMut, Table, Trap, TypedFunc, TypedModuleExport,
 };
 use anyhow::{anyhow, bail, Context, Error, Result};
 use std::mem;
 use std::sync::Arc;
-use wasmtime_environ::{EntityType, FuncIndex, GlobalIndex, MemoryIndex, PrimaryMap, TableIndex};
+use wasmtime_environ::{
+    EntityIndex, EntityType, FuncIndex, GlobalIndex, MemoryIndex, PrimaryMap, TableIndex,
+};
 use wasmtime_runtime::{
     Imports, InstanceAllocationRequest, InstantiationError, StorePtr, VMContext, VMFunctionBody,
     VMFunctionImport, VMGlobalImport, VMMemoryImport, VMTableImport,
@@ -264,6 +266,7 @@ impl Instance {
                     imports,
                     host_state: Box::new(Instance(instance_to_be)),
                     store: StorePtr::new(store.traitobj()),
+                    pkey: store.pkey(),
                 })?;
 
         // The instance still has lots of setup, for example
@@ -320,12 +323,7 @@ impl Instance {
                 compiled_module.module(),
                 store.engine().config().features.bulk_memory,
             )
-            .map_err(|e| -> Error {
-                match e {
-                    InstantiationError::Trap(trap) => Trap::from_runtime(trap).into(),
-                    other => other.into(),
-                }
-            })?;
+            .map_err(instantiation_error)?;
 
         Ok((instance, compiled_module.module().start_func))
     }
@@ -342,13 +340,19 @@ impl Instance {
         let f = instance.get_exported_func(start);
         let vmctx = instance.vmctx_ptr();
         unsafe {
-            super::func::invoke_wasm_and_catch_traps(store, |_default_callee| {
-                mem::transmute::<
+            let anyfunc = f.anyfunc.as_ref();
+            let trampoline = store.0.modules().lookup_native_trampoline(anyfunc);
+            super::func::invoke_wasm_and_catch_traps(store, |_default_callee| match trampoline {
+                Some(trampoline) => mem::transmute::<
+                    *const VMFunctionBody,
+                    unsafe extern "C" fn(*mut VMContext, *mut VMContext, *const VMFunctionBody),
+                >(trampoline.as_ptr())(
+                    anyfunc.vmctx, vmctx, anyfunc.func_ptr.as_ptr()
+                ),
+                None => mem::transmute::<
                     *const VMFunctionBody,
                     unsafe extern "C" fn(*mut VMContext, *mut VMContext),
-                >(f.anyfunc.as_ref().func_ptr.as_ptr())(
-                    f.anyfunc.as_ref().vmctx, vmctx
-                )
+                >(anyfunc.func_ptr.as_ptr())(anyfunc.vmctx, vmctx),
             })?;
         }
         Ok(())
@@ -389,6 +393,41 @@ impl Instance {
             .map(|((name, _), export)| Export::new(name, export.clone().unwrap()))
     }
 
+    /// Returns the list of exported items from this [`Instance`] along with
+    /// their types, such as the signatures of functions and the limits of
+    /// memories and tables.
+    ///
+    /// This is like [`Instance::exports`], whose exports borrow `store` so
+    /// that their [`Export::ty`] can't be looked up while they're iterated
+    /// over.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn exports_with_types<'a, T: 'a>(
+        &'a self,
+        store: impl Into<StoreContextMut<'a, T>>,
+    ) -> impl ExactSizeIterator<Item = (Export<'a>, ExternType)> + 'a {
+        let store = store.into();
+        let types = self
+            ._exports(store.0)
+            .map(|export| export.into_extern())
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|export| export.ty(&store))
+            .collect::<Vec<_>>();
+
+        let store: &'a StoreOpaque = store.0;
+        let data = &store.store_data()[self.0];
+        let module = store.instance(data.id).module();
+        module
+            .exports
+            .iter()
+            .zip(&data.exports)
+            .zip(types)
+            .map(|(((name, _), export), ty)| (Export::new(name, export.clone().unwrap()), ty))
+    }
+
     /// Looks up an exported [`Extern`] value by name.
     ///
     /// This method will search the module for an export named `name` and return
@@ -410,15 +449,82 @@ impl Instance {
         self._get_export(store.as_context_mut().0, name)
     }
 
-    fn _get_export(&self, store: &mut StoreOpaque, name: &str) -> Option<Extern> {
-        // Instantiated instances will lazily fill in exports, so we process
-        // all that lazy logic here.
+    /// Looks up an exported [`Extern`] value by a [`ModuleExport`] found
+    /// ahead of time with [`Module::get_export_index`].
+    ///
+    /// This is cheaper than [`Instance::get_export`] since the export's name
+    /// doesn't need to be searched for, which is useful when looking up the
+    /// same export of many instances.
+    ///
+    /// Returns `None` if `export` isn't an export of this instance's module.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn get_module_export(
+        &self,
+        mut store: impl AsContextMut,
+        export: &ModuleExport,
+    ) -> Option<Extern> {
+        let store = store.as_context_mut().0;
         let data = &store[self.0];
+        if !export.matches(store.instance(data.id).module()) {
+            return None;
+        }
+        Some(self._get_export_by_index(store, export.index, export.entity))
+    }
 
+    /// Looks up an exported function as a [`TypedFunc`] by a
+    /// [`TypedModuleExport`] found and type-checked ahead of time with
+    /// [`Module::get_typed_func_export`].
+    ///
+    /// This is cheaper than [`Instance::get_typed_func`] since neither the
+    /// function's name needs to be searched for nor its type checked again.
+    ///
+    /// Returns `None` if `export` isn't an export of this instance's module
+    /// with the same type.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn get_typed_module_export<Params, Results>(
+        &self,
+        mut store: impl AsContextMut,
+        export: &TypedModuleExport<Params, Results>,
+    ) -> Option<TypedFunc<Params, Results>>
+    where
+        Params: crate::WasmParams,
+        Results: crate::WasmResults,
+    {
+        let mut store = store.as_context_mut();
+        let func = self
+            .get_module_export(&mut store, &export.export)?
+            .into_func()?;
+        if func.sig_index(store.0.store_data()) != export.sig_index {
+            return None;
+        }
+        // The function has the type `export` was checked to have.
+        Some(unsafe { TypedFunc::new_unchecked(func) })
+    }
+
+    fn _get_export(&self, store: &mut StoreOpaque, name: &str) -> Option<Extern> {
+        let data = &store[self.0];
         let instance = store.instance(data.id);
         let (i, _, &index) = instance.module().exports.get_full(name)?;
+        Some(self._get_export_by_index(store, i, index))
+    }
+
+    fn _get_export_by_index(
+        &self,
+        store: &mut StoreOpaque,
+        i: usize,
+        index: EntityIndex,
+    ) -> Extern {
+        // Instantiated instances will lazily fill in exports, so we process
+        // all that lazy logic here.
+        let data = &store[self.0];
         if let Some(export) = &data.exports[i] {
-            return Some(export.clone());
+            return export.clone();
         }
 
         let id = data.id;
@@ -427,7 +533,7 @@ impl Instance {
             unsafe { Extern::from_wasmtime_export(instance.get_export_by_index(index), store) };
         let data = &mut store[self.0];
         data.exports[i] = Some(item.clone());
-        Some(item)
+        item
     }
 
     /// Looks up an exported [`Func`] value by name.
@@ -506,6 +612,50 @@ impl Instance {
     pub fn get_global(&self, store: impl AsContextMut, name: &str) -> Option<Global> {
         self.get_export(store, name)?.into_global()
     }
+
+    /// Captures the state of the memories, tables, and mutable globals which
+    /// this instance defines into a [`Snapshot`].
+    ///
+    /// The snapshot can later be applied to this instance, or to another
+    /// instance of the same module, with [`Instance::restore`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a table or global of this instance refers to a
+    /// function which isn't part of this instance's function index space, for
+    /// example a host function stored into a table with [`Table::set`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn snapshot(&self, mut store: impl AsContextMut) -> Result<Snapshot> {
+        Snapshot::capture(store.as_context_mut(), self)
+    }
+
+    /// Overwrites the state of this instance with the contents of `snapshot`.
+    ///
+    /// Memories and tables which are smaller than they were in the snapshot
+    /// are grown to their previous size, and function references are restored
+    /// to refer to this instance's functions.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `snapshot` was taken from an instance of a
+    /// different module, if a memory or table is larger than it was in the
+    /// snapshot, or if growing a memory or table fails. Nothing is
+    /// overwritten when an error is returned, but memories and tables may
+    /// have been grown if growing another one failed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `store` does not own this instance.
+    pub fn restore(&self, mut store: impl AsContextMut, snapshot: &Snapshot) -> Result<()> {
+        snapshot.restore(store.as_context_mut(), self)
+    }
+
+    pub(crate) fn id(&self, store: &StoreOpaque) -> InstanceId {
+        store[self.0].id
+    }
 }
 
 struct OwnedImports {
@@ -583,6 +733,11 @@ pub struct InstancePre<T> {
     /// preallocate space in a `Store` up front for all entries to be inserted.
     host_funcs: usize,
 
+    /// The copy of `module` whose calls to imported functions go straight to
+    /// `items`, which is what's instantiated, if it was compiled with thunks
+    /// for its imports.
+    devirtualized: Option<Module>,
+
     _marker: std::marker::PhantomData<fn() -> T>,
 }
 
@@ -593,6 +748,7 @@ impl<T> Clone for InstancePre<T> {
             module: self.module.clone(),
             items: self.items.clone(),
             host_funcs: self.host_funcs,
+            devirtualized: self.devirtualized.clone(),
             _marker: self._marker,
         }
     }
@@ -607,10 +763,14 @@ impl<T> InstancePre<T> {
     /// This method is unsafe as the `T` of the `InstancePre<T>` is not
     /// guaranteed to be the same as the `T` within the `Store`, the caller must
     /// verify that.
+    ///
+    /// Calls to imported functions are devirtualized if `devirtualize` is set
+    /// and `module` was compiled with thunks for them.
     pub(crate) unsafe fn new(
         store: &mut StoreOpaque,
         module: &Module,
         items: Vec<Definition>,
+        devirtualize: bool,
     ) -> Result<InstancePre<T>> {
         for import in items.iter() {
             if !import.comes_from_same_store(store) {
@@ -628,10 +788,25 @@ impl<T> InstancePre<T> {
                 _ => false,
             })
             .count();
+
+        // The functions in `items` are kept alive by the stores this is
+        // instantiated in, which can only be the one they're owned by if
+        // they're owned by a store, and are the only imports the copy of the
+        // module is instantiated with.
+        let devirtualized = if devirtualize && module.has_import_thunks() {
+            let imports = items
+                .iter()
+                .filter_map(|item| item.func_import(store))
+                .collect::<Vec<_>>();
+            Some(module.devirtualized(&imports)?)
+        } else {
+            None
+        };
         Ok(InstancePre {
             module: module.clone(),
             items: items.into(),
             host_funcs,
+            devirtualized,
             _marker: std::marker::PhantomData,
         })
     }
@@ -665,7 +840,7 @@ impl<T> InstancePre<T> {
         // This unsafety should be handled by the type-checking performed by the
         // constructor of `InstancePre` to assert that all the imports we're passing
         // in match the module we're instantiating.
-        unsafe { Instance::new_started(&mut store, &self.module, imports.as_ref()) }
+        unsafe { Instance::new_started(&mut store, self.code(), imports.as_ref()) }
     }
 
     /// Creates a new instance, running the start function asynchronously
@@ -694,7 +869,13 @@ impl<T> InstancePre<T> {
         // This unsafety should be handled by the type-checking performed by the
         // constructor of `InstancePre` to assert that all the imports we're passing
         // in match the module we're instantiating.
-        unsafe { Instance::new_started_async(&mut store, &self.module, imports.as_ref()).await }
+        unsafe { Instance::new_started_async(&mut store, self.code(), imports.as_ref()).await }
+    }
+
+    /// Returns the module which is instantiated, which is the copy whose
+    /// calls to imports are devirtualized, if there is one.
+    fn code(&self) -> &Module {
+        self.devirtualized.as_ref().unwrap_or(&self.module)
     }
 }
 
@@ -763,3 +944,15 @@ fn typecheck<I>(
     }
     Ok(())
 }
+
+/// Converts an error from initializing an instance, reporting a segment which
+/// didn't fit as the context of the trap or link error it caused.
+fn instantiation_error(e: InstantiationError) -> Error {
+    match e {
+        InstantiationError::Trap(trap) => Trap::from_runtime(trap).into(),
+        InstantiationError::Segment(oob, cause) => {
+            instantiation_error(*cause).context(crate::SegmentOutOfBounds::from_runtime(oob))
+        }
+        other => other.into(),
+    }
+}
@@ -0,0 +1,57 @@
+//! Counts of the instructions executed by WebAssembly compiled with
+//! instruction counting.
+
+use std::collections::BTreeMap;
+
+/// Counts of the WebAssembly instructions executed by one module within a
+/// [`Store`](crate::Store).
+///
+/// Counts are only collected for modules compiled with
+/// [`Config::count_instructions`](crate::Config::count_instructions), and are
+/// retrieved with
+/// [`Store::instruction_counts`](crate::Store::instruction_counts). Counts of
+/// all instances of the module in the store are added together.
+///
+/// Instructions are counted in basic blocks: straight-line runs of
+/// instructions which end at a branch, a call, or the start or end of a
+/// control construct. A block's instructions are counted once it finishes
+/// executing, so the instructions of a block interrupted by a trap aren't
+/// counted. Otherwise every instruction executed is counted, regardless of
+/// its cost, which makes the counts deterministic across hosts and runs.
+#[derive(Debug, Default, Clone)]
+pub struct InstructionCounts {
+    functions: BTreeMap<u32, u64>,
+    blocks: BTreeMap<usize, u64>,
+}
+
+impl InstructionCounts {
+    /// Returns the total number of instructions executed in all functions of
+    /// the module.
+    pub fn total(&self) -> u64 {
+        self.functions.values().sum()
+    }
+
+    /// Returns the number of instructions executed in the function with index
+    /// `func_index` within the module.
+    pub fn function(&self, func_index: u32) -> u64 {
+        self.functions.get(&func_index).copied().unwrap_or(0)
+    }
+
+    /// Returns the number of instructions executed in each function of the
+    /// module which executed any, in order of function index.
+    pub fn functions(&self) -> impl Iterator<Item = (u32, u64)> + '_ {
+        self.functions.iter().map(|(index, count)| (*index, *count))
+    }
+
+    /// Returns how many times each basic block which executed was run to
+    /// completion, identified by the offset of its first instruction within
+    /// the original wasm module, in order of offset.
+    pub fn block_hits(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.blocks.iter().map(|(offset, hits)| (*offset, *hits))
+    }
+
+    pub(crate) fn record(&mut self, func_index: u32, offset: usize, count: u32) {
+        *self.functions.entry(func_index).or_insert(0) += u64::from(count);
+        *self.blocks.entry(offset).or_insert(0) += 1;
+    }
+}
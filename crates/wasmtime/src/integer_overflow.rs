@@ -0,0 +1,68 @@
+//! Integer overflows reported by WebAssembly compiled with integer overflow
+//! checks.
+
+use crate::store::StoreContextMut;
+use crate::ValType;
+use anyhow::Result;
+
+/// The arithmetic operation of an [`IntegerOverflow`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IntegerOverflowKind {
+    /// An `i32.add` or `i64.add`.
+    Add,
+    /// An `i32.sub` or `i64.sub`.
+    Sub,
+    /// An `i32.mul` or `i64.mul`.
+    Mul,
+}
+
+/// An integer addition, subtraction or multiplication made by WebAssembly
+/// whose result overflowed when its operands are interpreted as signed
+/// integers.
+///
+/// These are reported to the callback configured with
+/// [`Store::integer_overflow_callback`](crate::Store::integer_overflow_callback)
+/// for modules compiled with
+/// [`Config::check_integer_overflow`](crate::Config::check_integer_overflow).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IntegerOverflow {
+    pub(crate) kind: IntegerOverflowKind,
+    pub(crate) ty: ValType,
+    pub(crate) func_index: u32,
+    pub(crate) lhs: i64,
+    pub(crate) rhs: i64,
+}
+
+impl IntegerOverflow {
+    /// Returns the operation which overflowed.
+    pub fn kind(&self) -> IntegerOverflowKind {
+        self.kind
+    }
+
+    /// Returns the type of the operation's operands, either [`ValType::I32`]
+    /// or [`ValType::I64`].
+    pub fn ty(&self) -> ValType {
+        self.ty.clone()
+    }
+
+    /// Returns the index of the function which made the operation within the
+    /// module of the instance executing it.
+    pub fn func_index(&self) -> u32 {
+        self.func_index
+    }
+
+    /// Returns the left-hand operand of the operation, sign-extended to 64
+    /// bits for `i32` operations.
+    pub fn lhs(&self) -> i64 {
+        self.lhs
+    }
+
+    /// Returns the right-hand operand of the operation, sign-extended to 64
+    /// bits for `i32` operations.
+    pub fn rhs(&self) -> i64 {
+        self.rhs
+    }
+}
+
+pub(crate) type IntegerOverflowCallback<T> =
+    Box<dyn FnMut(StoreContextMut<'_, T>, &IntegerOverflow) -> Result<()> + Send + Sync>;
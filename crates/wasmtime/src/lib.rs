@@ -288,9 +288,9 @@
 //!   generate backtraces at runtime for WebAssembly modules. This means that
 //!   unwinding information is compiled into wasm modules and necessary runtime
 //!   dependencies are enabled as well. If this is turned off then some methods
-//!   to look at trap frames will not be available. Additionally at this time
-//!   disabling this feature means that the reference types feature is always
-//!   disabled as well.
+//!   to look at trap frames, and [`Backtrace`], will not be available.
+//!   Additionally at this time disabling this feature means that the reference
+//!   types feature is always disabled as well.
 //!
 //! ## Examples
 //!
@@ -391,41 +391,81 @@
 #[macro_use]
 mod func;
 
+#[cfg(feature = "wasm-backtrace")]
+mod backtrace;
+#[cfg(compiler)]
+mod compilation_hook;
 mod config;
+#[cfg(feature = "coredump")]
+mod coredump;
+mod debug;
 mod engine;
+mod execution_profile;
 mod externals;
 mod instance;
+mod instruction_counts;
+mod integer_overflow;
 mod limits;
 mod linker;
 mod memory;
+mod memory_access;
+mod metrics;
 mod module;
+#[cfg(feature = "profiling")]
+mod profiling;
 mod r#ref;
 mod signatures;
+mod snapshot;
 mod store;
 mod trampoline;
 mod trap;
 mod types;
 mod values;
 
+#[cfg(feature = "wasm-backtrace")]
+pub use crate::backtrace::{Backtrace, BacktraceFrame};
+#[cfg(compiler)]
+pub use crate::compilation_hook::{CompilationHook, FunctionListing};
 pub use crate::config::*;
+#[cfg(feature = "coredump")]
+pub use crate::coredump::*;
+pub use crate::debug::{DebugAction, DebugFrame};
 pub use crate::engine::*;
+pub use crate::execution_profile::ExecutionProfile;
 pub use crate::externals::*;
 pub use crate::func::*;
 pub use crate::instance::{Instance, InstancePre};
+pub use crate::instruction_counts::InstructionCounts;
+pub use crate::integer_overflow::{IntegerOverflow, IntegerOverflowKind};
 pub use crate::limits::*;
 pub use crate::linker::*;
 pub use crate::memory::*;
-pub use crate::module::{FrameInfo, FrameSymbol, Module};
+pub use crate::memory_access::{MemoryAccess, MemoryAccessKind};
+pub use crate::metrics::EngineMetrics;
+#[cfg(compiler)]
+pub use crate::module::ModuleStream;
+pub use crate::module::{
+    AddressMap, CompilationReport, DisabledFeatureError, FrameInfo, FrameSymbol, FunctionReport,
+    IncompatibleModuleError, Module, ModuleExport, SegmentInitializer, SegmentKind,
+    SegmentOutOfBounds, TypedModuleExport, WeakModule,
+};
+#[cfg(feature = "profiling")]
+pub use crate::profiling::GuestProfiler;
 pub use crate::r#ref::ExternRef;
+pub use crate::snapshot::Snapshot;
 #[cfg(feature = "async")]
 pub use crate::store::CallHookHandler;
-pub use crate::store::{AsContext, AsContextMut, CallHook, Store, StoreContext, StoreContextMut};
+pub use crate::store::{
+    AsContext, AsContextMut, CallHook, GcStats, Store, StoreContext, StoreContextMut,
+};
 pub use crate::trap::*;
 pub use crate::types::*;
 pub use crate::values::*;
 
 cfg_if::cfg_if! {
-    if #[cfg(all(target_os = "macos", not(feature = "posix-signals-on-macos")))] {
+    if #[cfg(wasmtime_custom_platform)] {
+        // no extensions for custom platforms either, see `wasmtime-platform.h`
+    } else if #[cfg(all(target_os = "macos", not(feature = "posix-signals-on-macos")))] {
         // no extensions for macOS at this time
     } else if #[cfg(unix)] {
         pub mod unix;
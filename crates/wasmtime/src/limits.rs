@@ -38,6 +38,15 @@ pub trait ResourceLimiter {
     /// memory. In that case, `error` might be downcastable to a `std::io::Error`.
     fn memory_grow_failed(&mut self, _error: &anyhow::Error) {}
 
+    /// Notifies the resource limiter that a linear memory has grown from
+    /// `old` to `new` bytes, after a request permitted by `memory_growing`
+    /// succeeded. Creating a memory is reported as growing it from zero bytes.
+    ///
+    /// This is intended for keeping track of how much memory is in use, for
+    /// example to report metrics, without having to poll the size of every
+    /// memory.
+    fn memory_grown(&mut self, _old: usize, _new: usize) {}
+
     /// Notifies the resource limiter that an instance's table has been requested to grow.
     ///
     /// * `current` is the current number of elements in the table.
@@ -57,6 +66,12 @@ pub trait ResourceLimiter {
     /// `table_growing`. This could expand in the future.
     fn table_grow_failed(&mut self, _error: &anyhow::Error) {}
 
+    /// Notifies the resource limiter that a table has grown from `old` to
+    /// `new` elements, after a request permitted by `table_growing`
+    /// succeeded. Creating a table is reported as growing it from zero
+    /// elements.
+    fn table_grown(&mut self, _old: u32, _new: u32) {}
+
     /// The maximum number of instances that can be created for a `Store`.
     ///
     /// Module instantiation will fail if this limit is exceeded.
@@ -107,12 +122,18 @@ pub trait ResourceLimiterAsync {
     /// Identical to [`ResourceLimiter::memory_grow_failed`]
     fn memory_grow_failed(&mut self, _error: &anyhow::Error) {}
 
+    /// Identical to [`ResourceLimiter::memory_grown`]
+    fn memory_grown(&mut self, _old: usize, _new: usize) {}
+
     /// Asynchronous version of [`ResourceLimiter::table_growing`]
     async fn table_growing(&mut self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
 
     /// Identical to [`ResourceLimiter::table_grow_failed`]
     fn table_grow_failed(&mut self, _error: &anyhow::Error) {}
 
+    /// Identical to [`ResourceLimiter::table_grown`]
+    fn table_grown(&mut self, _old: u32, _new: u32) {}
+
     /// Identical to [`ResourceLimiter::instances`]`
     fn instances(&self) -> usize {
         DEFAULT_INSTANCE_LIMIT
@@ -129,6 +150,39 @@ pub trait ResourceLimiterAsync {
     }
 }
 
+/// A linear memory which failed to grow, reported to the callback configured
+/// with
+/// [`Store::memory_grow_failure_callback`](crate::Store::memory_grow_failure_callback).
+///
+/// Growing fails when the store's resource limiter rejects it, or when the
+/// host fails to allocate the memory, such as when the process is out of
+/// memory. Growing past the memory's maximum size isn't reported, as it
+/// always fails.
+#[derive(Debug)]
+pub struct MemoryGrowFailure<'a> {
+    pub(crate) current: usize,
+    pub(crate) desired: usize,
+    pub(crate) error: Option<&'a anyhow::Error>,
+}
+
+impl MemoryGrowFailure<'_> {
+    /// Returns the current size of the memory in bytes.
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// Returns the size in bytes which the memory failed to grow to.
+    pub fn desired(&self) -> usize {
+        self.desired
+    }
+
+    /// Returns why the host failed to allocate the memory, or `None` if the
+    /// store's resource limiter rejected growing it.
+    pub fn error(&self) -> Option<&anyhow::Error> {
+        self.error
+    }
+}
+
 /// Used to build [`StoreLimits`].
 pub struct StoreLimitsBuilder(StoreLimits);
 
@@ -148,6 +202,20 @@ impl StoreLimitsBuilder {
         self
     }
 
+    /// The maximum number of bytes that all the linear memories of a
+    /// [`Store`](crate::Store) can grow to combined.
+    ///
+    /// Creating or growing a linear memory which would take the total size of
+    /// the store's memories beyond this limit will fail. Only memories created
+    /// after the limits are installed with
+    /// [`Store::limiter`](crate::Store::limiter) are counted.
+    ///
+    /// By default, the combined size of linear memories will not be limited.
+    pub fn total_memory_size(mut self, limit: usize) -> Self {
+        self.0.total_memory_size = Some(limit);
+        self
+    }
+
     /// The maximum number of elements in a table.
     ///
     /// Growing a table beyond this limit will fail.
@@ -197,7 +265,14 @@ impl StoreLimitsBuilder {
 /// Provides limits for a [`Store`](crate::Store).
 pub struct StoreLimits {
     memory_size: Option<usize>,
+    total_memory_size: Option<usize>,
+    /// The combined size of the memories this limiter has seen created and
+    /// grown.
+    memory_allocated: usize,
     table_elements: Option<u32>,
+    /// The combined number of elements of the tables this limiter has seen
+    /// created and grown.
+    table_elements_allocated: usize,
     instances: usize,
     tables: usize,
     memories: usize,
@@ -207,7 +282,10 @@ impl Default for StoreLimits {
     fn default() -> Self {
         Self {
             memory_size: None,
+            total_memory_size: None,
+            memory_allocated: 0,
             table_elements: None,
+            table_elements_allocated: 0,
             instances: DEFAULT_INSTANCE_LIMIT,
             tables: DEFAULT_TABLE_LIMIT,
             memories: DEFAULT_MEMORY_LIMIT,
@@ -215,15 +293,49 @@ impl Default for StoreLimits {
     }
 }
 
+impl StoreLimits {
+    /// Returns the combined size, in bytes, of the linear memories created and
+    /// grown while these limits were installed in a [`Store`](crate::Store).
+    pub fn memory_allocated(&self) -> usize {
+        self.memory_allocated
+    }
+
+    /// Returns how many more bytes linear memories can be grown by in total
+    /// before reaching [`StoreLimitsBuilder::total_memory_size`].
+    ///
+    /// Returns `None` if the combined size of linear memories isn't limited.
+    pub fn memory_remaining(&self) -> Option<usize> {
+        self.total_memory_size
+            .map(|limit| limit.saturating_sub(self.memory_allocated))
+    }
+
+    /// Returns the combined number of elements of the tables created and grown
+    /// while these limits were installed in a [`Store`](crate::Store).
+    pub fn table_elements_allocated(&self) -> usize {
+        self.table_elements_allocated
+    }
+}
+
 #[cfg_attr(feature = "async", async_trait::async_trait)]
 impl ResourceLimiter for StoreLimits {
-    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> bool {
+    fn memory_growing(&mut self, current: usize, desired: usize, _maximum: Option<usize>) -> bool {
         match self.memory_size {
-            Some(limit) if desired > limit => false,
-            _ => true,
+            Some(limit) if desired > limit => return false,
+            _ => {}
+        }
+        match self.total_memory_size {
+            Some(limit) => {
+                let total = self.memory_allocated.saturating_sub(current);
+                total.saturating_add(desired) <= limit
+            }
+            None => true,
         }
     }
 
+    fn memory_grown(&mut self, old: usize, new: usize) {
+        self.memory_allocated = self.memory_allocated.saturating_add(new - old);
+    }
+
     fn table_growing(&mut self, _current: u32, desired: u32, _maximum: Option<u32>) -> bool {
         match self.table_elements {
             Some(limit) if desired > limit => false,
@@ -231,6 +343,12 @@ impl ResourceLimiter for StoreLimits {
         }
     }
 
+    fn table_grown(&mut self, old: u32, new: u32) {
+        self.table_elements_allocated = self
+            .table_elements_allocated
+            .saturating_add((new - old) as usize);
+    }
+
     fn instances(&self) -> usize {
         self.instances
     }
@@ -14,6 +14,7 @@ use std::marker;
 #[cfg(feature = "async")]
 use std::pin::Pin;
 use std::sync::Arc;
+use wasmtime_runtime::VMFunctionImport;
 
 /// Structure used to link wasm modules/instances together.
 ///
@@ -65,6 +66,13 @@ use std::sync::Arc;
 /// [`Linker`] value at program start up and use that continuously for each
 /// [`Store`] created over the lifetime of the program.
 ///
+/// Host functions are only created once, when they're defined in the
+/// [`Linker`], and each [`Store`] that instantiates a module merely refers to
+/// them rather than copying their closures. To also skip resolving imports by
+/// name and type-checking them on each instantiation use
+/// [`Linker::instantiate_pre`] once per module and then instantiate the
+/// resulting [`InstancePre`](crate::InstancePre) into each [`Store`].
+///
 /// Note that once [`Store`]-owned items, such as [`Global`], are defined witin
 /// a [`Linker`] then it is no longer compatible with any [`Store`]. At that
 /// point only the [`Store`] that owns the [`Global`] can be used to instantiate
@@ -88,6 +96,7 @@ pub struct Linker<T> {
     map: HashMap<ImportKey, Definition>,
     allow_shadowing: bool,
     allow_unknown_exports: bool,
+    resolver: Option<Arc<dyn ImportResolver<T>>>,
     _marker: marker::PhantomData<fn() -> T>,
 }
 
@@ -100,6 +109,7 @@ impl<T> Clone for Linker<T> {
             map: self.map.clone(),
             allow_shadowing: self.allow_shadowing,
             allow_unknown_exports: self.allow_unknown_exports,
+            resolver: self.resolver.clone(),
             _marker: self._marker,
         }
     }
@@ -171,6 +181,7 @@ impl<T> Linker<T> {
             strings: Vec::new(),
             allow_shadowing: false,
             allow_unknown_exports: false,
+            resolver: None,
             _marker: marker::PhantomData,
         }
     }
@@ -237,6 +248,43 @@ impl<T> Linker<T> {
         self
     }
 
+    /// Configures an [`ImportResolver`] which this [`Linker`] asks for the
+    /// imports of the modules it instantiates which it doesn't define itself.
+    ///
+    /// This replaces any resolver previously configured. Items which the
+    /// resolver returns aren't defined in this linker, so it's asked again
+    /// each time a module importing them is instantiated.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let mut linker = Linker::new(&engine);
+    /// linker.import_resolver(|mut store: StoreContextMut<'_, ()>, import: &ImportType<'_>| {
+    ///     if import.module() != "env" {
+    ///         return Ok(None);
+    ///     }
+    ///     let name = import.name().to_string();
+    ///     let ty = import.ty().func().unwrap().clone();
+    ///     let func = Func::new(&mut store, ty, move |_, _, _| {
+    ///         Err(Trap::new(format!("`{}` isn't implemented", name)))
+    ///     });
+    ///     Ok(Some(func.into()))
+    /// });
+    ///
+    /// let module = Module::new(&engine, r#"(module (import "env" "anything" (func)))"#)?;
+    /// let mut store = Store::new(&engine, ());
+    /// linker.instantiate(&mut store, &module)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn import_resolver(&mut self, resolver: impl ImportResolver<T> + 'static) -> &mut Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
     /// Defines a new item in this [`Linker`].
     ///
     /// This method will add a new definition, by name, to this instance of
@@ -308,7 +356,7 @@ impl<T> Linker<T> {
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, &[Val], &mut [Val]) -> Result<(), Trap> + Send + Sync + 'static,
     ) -> Result<&mut Self> {
-        let func = HostFunc::new(&self.engine, ty, func);
+        let func = HostFunc::new(&self.engine, ty, func)?;
         let key = self.import_key(module, Some(name));
         self.insert(key, Definition::HostFunc(Arc::new(func)))?;
         Ok(self)
@@ -326,7 +374,7 @@ impl<T> Linker<T> {
         ty: FuncType,
         func: impl Fn(Caller<'_, T>, *mut ValRaw) -> Result<(), Trap> + Send + Sync + 'static,
     ) -> Result<&mut Self> {
-        let func = HostFunc::new_unchecked(&self.engine, ty, func);
+        let func = HostFunc::new_unchecked(&self.engine, ty, func)?;
         let key = self.import_key(module, Some(name));
         self.insert(key, Definition::HostFunc(Arc::new(func)))?;
         Ok(self)
@@ -777,11 +825,14 @@ impl<T> Linker<T> {
         T: 'static,
     {
         let mut store = store.as_context_mut();
+        // All of the exports instantiate the module the same way, so they
+        // share one `InstancePre`, which also means the module's code is only
+        // patched once when its imports are devirtualized.
+        let instance_pre = self.instantiate_pre(&mut store, module)?;
         for export in module.exports() {
             if let Some(func_ty) = export.ty().func() {
-                let instance_pre = self.instantiate_pre(&mut store, module)?;
                 let export_name = export.name().to_owned();
-                let func = mk_func(&mut store, func_ty, export_name, instance_pre);
+                let func = mk_func(&mut store, func_ty, export_name, instance_pre.clone());
                 let key = self.import_key(module_name, Some(export.name()));
                 self.insert(key, Definition::Extern(func.into()))?;
             } else if export.name() == "memory" && export.ty().memory().is_some() {
@@ -966,7 +1017,8 @@ impl<T> Linker<T> {
         mut store: impl AsContextMut<Data = T>,
         module: &Module,
     ) -> Result<Instance> {
-        self.instantiate_pre(&mut store, module)?.instantiate(store)
+        self._instantiate_pre(&mut store, module, false)?
+            .instantiate(store)
     }
 
     /// Attempts to instantiate the `module` provided. This is the same as
@@ -981,7 +1033,7 @@ impl<T> Linker<T> {
     where
         T: Send,
     {
-        self.instantiate_pre(&mut store, module)?
+        self._instantiate_pre(&mut store, module, false)?
             .instantiate_async(store)
             .await
     }
@@ -995,6 +1047,11 @@ impl<T> Linker<T> {
     /// returned [`InstancePre`] represents a ready-to-be-instantiated module,
     /// which can also be instantiated multiple times if desired.
     ///
+    /// If `module` was compiled with
+    /// [`Config::devirtualize_imports`](crate::Config::devirtualize_imports)
+    /// then this also creates a copy of its code which calls its imported
+    /// functions directly, which the returned [`InstancePre`] instantiates.
+    ///
     /// # Panics
     ///
     /// This method will panic if any item defined in this linker used by
@@ -1037,16 +1094,28 @@ impl<T> Linker<T> {
     /// # }
     /// ```
     pub fn instantiate_pre(
+        &self,
+        store: impl AsContextMut<Data = T>,
+        module: &Module,
+    ) -> Result<InstancePre<T>> {
+        self._instantiate_pre(store, module, true)
+    }
+
+    /// Same as [`Linker::instantiate_pre`], except that the `InstancePre`
+    /// only devirtualizes calls to its imports if `devirtualize` is set,
+    /// which isn't worth it for a single instantiation.
+    fn _instantiate_pre(
         &self,
         mut store: impl AsContextMut<Data = T>,
         module: &Module,
+        devirtualize: bool,
     ) -> Result<InstancePre<T>> {
-        let store = store.as_context_mut().0;
+        let mut store = store.as_context_mut();
         let imports = module
             .imports()
-            .map(|import| self._get_by_import(&import))
+            .map(|import| self.resolve_import(&mut store, &import))
             .collect::<Result<_>>()?;
-        unsafe { InstancePre::new(store, module, imports) }
+        unsafe { InstancePre::new(store.0, module, imports, devirtualize) }
     }
 
     /// Returns an iterator over all items defined in this `Linker`, in
@@ -1121,12 +1190,19 @@ impl<T> Linker<T> {
         mut store: impl AsContextMut<Data = T>,
         import: &ImportType,
     ) -> Option<Extern> {
-        let store = store.as_context_mut().0;
+        let mut store = store.as_context_mut();
+        let item = self.resolve_import(&mut store, import).ok()?;
         // Should be safe since `T` is connecting the linker and store
-        Some(unsafe { self._get_by_import(import).ok()?.to_extern(store) })
+        Some(unsafe { item.to_extern(store.0) })
     }
 
-    fn _get_by_import(&self, import: &ImportType) -> anyhow::Result<Definition> {
+    /// Looks up `import` in this linker, or failing that asks its
+    /// [`ImportResolver`] for it.
+    fn resolve_import(
+        &self,
+        store: &mut StoreContextMut<'_, T>,
+        import: &ImportType,
+    ) -> anyhow::Result<Definition> {
         fn undef_err(missing_import: &str) -> anyhow::Error {
             anyhow!("unknown import: `{}` has not been defined", missing_import)
         }
@@ -1134,6 +1210,20 @@ impl<T> Linker<T> {
         if let Some(item) = self._get(import.module(), import.name()) {
             return Ok(item.clone());
         }
+        if let Some(resolver) = &self.resolver {
+            let item = resolver
+                .resolve(store.as_context_mut(), import)
+                .with_context(|| {
+                    format!(
+                        "failed to resolve import `{}::{}`",
+                        import.module(),
+                        import.name()
+                    )
+                })?;
+            if let Some(item) = item {
+                return Ok(Definition::Extern(item));
+            }
+        }
 
         Err(undef_err(&format!(
             "{}::{}",
@@ -1183,6 +1273,43 @@ impl<T> Default for Linker<T> {
     }
 }
 
+/// Resolves the imports of modules instantiated by a [`Linker`] which aren't
+/// defined in it, configured with [`Linker::import_resolver`].
+///
+/// This lets an embedder which exposes more items than it would like to
+/// define up front, or which decides which items each guest may import,
+/// look them up as modules importing them are instantiated.
+///
+/// This is implemented for closures taking the same arguments as
+/// [`ImportResolver::resolve`].
+pub trait ImportResolver<T>: Send + Sync {
+    /// Returns the item to satisfy `import` with, which is created within
+    /// or belongs to `store`, or `None` if it isn't known.
+    ///
+    /// The item is type-checked against `import` before the module is
+    /// instantiated. Returning `None` reports the import as undefined, while
+    /// returning an error, such as when a guest isn't allowed to import the
+    /// item, fails instantiation with that error.
+    fn resolve(
+        &self,
+        store: StoreContextMut<'_, T>,
+        import: &ImportType<'_>,
+    ) -> Result<Option<Extern>>;
+}
+
+impl<T, F> ImportResolver<T> for F
+where
+    F: Fn(StoreContextMut<'_, T>, &ImportType<'_>) -> Result<Option<Extern>> + Send + Sync,
+{
+    fn resolve(
+        &self,
+        store: StoreContextMut<'_, T>,
+        import: &ImportType<'_>,
+    ) -> Result<Option<Extern>> {
+        self(store, import)
+    }
+}
+
 impl Definition {
     /// Note the unsafety here is due to calling `HostFunc::to_func`. The
     /// requirement here is that the `T` that was originally used to create the
@@ -1209,6 +1336,15 @@ impl Definition {
             Definition::HostFunc(_func) => true,
         }
     }
+
+    /// Returns how wasm calls this definition, if it's a function.
+    pub(crate) fn func_import(&self, store: &mut StoreOpaque) -> Option<VMFunctionImport> {
+        match self {
+            Definition::Extern(Extern::Func(f)) => Some(f.vmimport(store)),
+            Definition::Extern(_) => None,
+            Definition::HostFunc(func) => Some(func.vmimport()),
+        }
+    }
 }
 
 /// Modules can be interpreted either as Commands or Reactors.
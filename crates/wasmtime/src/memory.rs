@@ -3,19 +3,25 @@ use crate::trampoline::generate_memory_export;
 use crate::{AsContext, AsContextMut, MemoryType, StoreContext, StoreContextMut};
 use anyhow::{bail, Result};
 use std::convert::TryFrom;
+use std::ops::Range;
 use std::slice;
 
-/// Error for out of bounds [`Memory`] access.
+/// Error for out of bounds [`Memory`] access, or for writes to memory which
+/// was made read-only with [`Memory::protect_read_only`].
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct MemoryAccessError {
     // Keep struct internals private for future extensibility.
-    _private: (),
+    read_only: bool,
 }
 
 impl std::fmt::Display for MemoryAccessError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "out of bounds memory access")
+        if self.read_only {
+            write!(f, "write to read-only memory")
+        } else {
+            write!(f, "out of bounds memory access")
+        }
     }
 }
 
@@ -310,14 +316,15 @@ impl Memory {
             .data(&store)
             .get(offset..)
             .and_then(|s| s.get(..buffer.len()))
-            .ok_or(MemoryAccessError { _private: () })?;
+            .ok_or(MemoryAccessError { read_only: false })?;
         buffer.copy_from_slice(slice);
         Ok(())
     }
 
     /// Safely writes contents of a buffer to this memory at the given offset.
     ///
-    /// If the `offset + buffer.len()` exceeds the current memory capacity, then
+    /// If the `offset + buffer.len()` exceeds the current memory capacity, or
+    /// any of it was made read-only with [`Memory::protect_read_only`], then
     /// none of the buffer is written to memory and a [`MemoryAccessError`] is
     /// returned.
     ///
@@ -330,11 +337,16 @@ impl Memory {
         offset: usize,
         buffer: &[u8],
     ) -> Result<(), MemoryAccessError> {
-        let mut context = store.as_context_mut();
-        self.data_mut(&mut context)
+        let context = store.as_context_mut();
+        let end = offset.saturating_add(buffer.len());
+        let mem = self.wasmtime_memory(context.0);
+        if unsafe { !(*mem).is_writable(offset..end) } {
+            return Err(MemoryAccessError { read_only: true });
+        }
+        self.data_mut_unchecked(context)
             .get_mut(offset..)
             .and_then(|s| s.get_mut(..buffer.len()))
-            .ok_or(MemoryAccessError { _private: () })?
+            .ok_or(MemoryAccessError { read_only: false })?
             .copy_from_slice(buffer);
         Ok(())
     }
@@ -362,8 +374,41 @@ impl Memory {
     ///
     /// # Panics
     ///
-    /// Panics if this memory doesn't belong to `store`.
+    /// Panics if this memory doesn't belong to `store`, or if any of it was
+    /// made read-only with [`Memory::protect_read_only`], as writing through
+    /// the slice to those bytes would crash the process. See
+    /// [`Memory::try_data_mut`] and [`Memory::write`] for alternatives which
+    /// return an error instead.
     pub fn data_mut<'a, T: 'a>(&self, store: impl Into<StoreContextMut<'a, T>>) -> &'a mut [u8] {
+        self.try_data_mut(store)
+            .expect("memory has read-only ranges and can't be borrowed mutably")
+    }
+
+    /// Same as [`Memory::data_mut`], but returns a [`MemoryAccessError`]
+    /// rather than panicking if any of this memory was made read-only with
+    /// [`Memory::protect_read_only`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn try_data_mut<'a, T: 'a>(
+        &self,
+        store: impl Into<StoreContextMut<'a, T>>,
+    ) -> Result<&'a mut [u8], MemoryAccessError> {
+        let store = store.into();
+        let mem = self.wasmtime_memory(store.0);
+        if unsafe { !(*mem).read_only_ranges().is_empty() } {
+            return Err(MemoryAccessError { read_only: true });
+        }
+        Ok(self.data_mut_unchecked(store))
+    }
+
+    /// Returns this memory as a mutable slice, whether or not any of it is
+    /// read-only.
+    fn data_mut_unchecked<'a, T: 'a>(
+        &self,
+        store: impl Into<StoreContextMut<'a, T>>,
+    ) -> &'a mut [u8] {
         unsafe {
             let store = store.into();
             let definition = *store[self.0].definition;
@@ -380,6 +425,14 @@ impl Memory {
     /// this method allows the Rust compiler to see that the borrow of this
     /// memory and the borrow of `T` are disjoint.
     ///
+    /// Unlike [`Memory::data_mut`] this doesn't check whether any of this
+    /// memory was made read-only with [`Memory::protect_read_only`], so that
+    /// host functions can keep reading the memory of guests which protect
+    /// some of it. It's up to the caller not to write to the ranges returned
+    /// by [`Memory::read_only_ranges`], which crashes the process; this is
+    /// what the `GuestMemory` of `wiggle`, which WASI is implemented with,
+    /// does.
+    ///
     /// # Panics
     ///
     /// Panics if this memory doesn't belong to `store`.
@@ -400,7 +453,7 @@ impl Memory {
         unsafe {
             let mut store = store.into();
             let data = &mut *(store.data_mut() as *mut T);
-            (self.data_mut(store), data)
+            (self.data_mut_unchecked(store), data)
         }
     }
 
@@ -533,6 +586,170 @@ impl Memory {
         );
         store.on_fiber(|store| self.grow(store, delta)).await?
     }
+
+    /// Makes the bytes of `range` of this memory read-only, so that
+    /// WebAssembly which writes to them traps.
+    ///
+    /// This is meant for data which is loaded into a memory once, such as
+    /// large static assets, and must not change afterwards. The range is
+    /// protected with the host's virtual memory, so reading from it and
+    /// writing to the rest of the memory costs nothing extra. Stores to the
+    /// range, and `memory.fill`, `memory.copy` and `memory.init` into it, trap
+    /// with [`TrapCode::MemoryOutOfBounds`](crate::TrapCode::MemoryOutOfBounds),
+    /// and [`Memory::write`] returns an error. The range stays read-only for
+    /// the rest of the memory's life, including when it grows.
+    ///
+    /// Both ends of `range` must be multiples of the host's page size, which a
+    /// WebAssembly page of 64 KiB always is, and `range` must be within the
+    /// current size of the memory.
+    ///
+    /// Once any of the memory is read-only, the host can't borrow it mutably
+    /// either: [`Memory::data_mut`] panics and [`Memory::try_data_mut`]
+    /// returns an error. Host functions implemented with `wiggle`, such as
+    /// those of WASI, fail when asked to write into a read-only range, in the
+    /// same way as for an out of bounds one. Only writes through
+    /// [`Memory::data_and_store_mut`] and [`Memory::data_ptr`], which aren't
+    /// checked, crash the process.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if signals-based traps are disabled with
+    /// [`Config::signals_based_traps`](crate::Config::signals_based_traps),
+    /// as the protection relies on them to catch the guest's writes, or if
+    /// `range` isn't page-aligned or is out of bounds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    ///
+    /// # Examples
+    ///
+    /// A guest can be allowed to protect its own memory once it has
+    /// initialized it with a host function:
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let mut linker = Linker::new(&engine);
+    /// linker.func_wrap(
+    ///     "env",
+    ///     "protect",
+    ///     |mut caller: Caller<'_, ()>, start: u32, len: u32| -> Result<(), Trap> {
+    ///         let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+    ///         let (start, len) = (start as usize, len as usize);
+    ///         memory
+    ///             .protect_read_only(&mut caller, start..start + len)
+    ///             .map_err(|e| Trap::new(e.to_string()))
+    ///     },
+    /// )?;
+    /// let module = Module::new(
+    ///     &engine,
+    ///     r#"
+    ///         (module
+    ///             (import "env" "protect" (func $protect (param i32 i32)))
+    ///             (memory (export "memory") 2)
+    ///             (func (export "run")
+    ///                 (i32.store (i32.const 0) (i32.const 42))
+    ///                 (call $protect (i32.const 0) (i32.const 65536))
+    ///                 (i32.store (i32.const 0) (i32.const 43))))
+    ///     "#,
+    /// )?;
+    /// let mut store = Store::new(&engine, ());
+    /// let instance = linker.instantiate(&mut store, &module)?;
+    /// let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    /// let trap = run.call(&mut store, ()).unwrap_err();
+    /// assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+    /// let memory = instance.get_memory(&mut store, "memory").unwrap();
+    /// assert_eq!(memory.data(&store)[0], 42);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn protect_read_only(
+        &self,
+        mut store: impl AsContextMut,
+        range: Range<usize>,
+    ) -> Result<()> {
+        let store = store.as_context_mut().0;
+        if !store.engine().config().tunables.signals_based_traps {
+            bail!("read-only memory ranges require signals-based traps to be enabled");
+        }
+        let mem = self.wasmtime_memory(store);
+        unsafe { (*mem).protect_read_only(range) }
+    }
+
+    /// Returns the ranges of this memory which were made read-only with
+    /// [`Memory::protect_read_only`], in the order they were protected.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn read_only_ranges(&self, mut store: impl AsContextMut) -> Vec<Range<usize>> {
+        let mem = self.wasmtime_memory(store.as_context_mut().0);
+        unsafe { (*mem).read_only_ranges().to_vec() }
+    }
+
+    /// Configures whether WebAssembly may grow this memory with
+    /// `memory.grow`, which is allowed by default.
+    ///
+    /// This is meant for memories whose growth is managed by the host, such
+    /// as a single buffer created with [`Memory::new`] and imported by a
+    /// pipeline of instances which exchange data through it instead of
+    /// copying it between their own memories. Once guest growth is disabled
+    /// any `memory.grow` of this memory by a nonzero number of pages traps,
+    /// rather than returning -1, while growing it by zero pages still returns
+    /// its size. The host can still grow it with [`Memory::grow`], after
+    /// which every instance importing it sees its new size.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let mut store = Store::new(&engine, ());
+    /// let memory = Memory::new(&mut store, MemoryType::new(1, None))?;
+    /// memory.set_guest_growable(&mut store, false);
+    ///
+    /// let module = Module::new(
+    ///     &engine,
+    ///     r#"
+    ///         (module
+    ///             (import "" "memory" (memory 1))
+    ///             (func (export "size") (result i32) memory.size)
+    ///             (func (export "grow") (result i32) (memory.grow (i32.const 1))))
+    ///     "#,
+    /// )?;
+    /// let instance = Instance::new(&mut store, &module, &[memory.into()])?;
+    /// let size = instance.get_typed_func::<(), i32, _>(&mut store, "size")?;
+    /// let grow = instance.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    /// assert!(grow.call(&mut store, ()).is_err());
+    ///
+    /// memory.grow(&mut store, 1)?;
+    /// assert_eq!(size.call(&mut store, ())?, 2);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn set_guest_growable(&self, mut store: impl AsContextMut, growable: bool) {
+        let mem = self.wasmtime_memory(store.as_context_mut().0);
+        unsafe { (*mem).set_guest_growable(growable) }
+    }
+
+    /// Returns whether WebAssembly may grow this memory with `memory.grow`,
+    /// as configured with [`Memory::set_guest_growable`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if this memory doesn't belong to `store`.
+    pub fn guest_growable(&self, mut store: impl AsContextMut) -> bool {
+        let mem = self.wasmtime_memory(store.as_context_mut().0);
+        unsafe { (*mem).guest_growable() }
+    }
+
     fn wasmtime_memory(&self, store: &mut StoreOpaque) -> *mut wasmtime_runtime::Memory {
         unsafe {
             let export = &store[self.0];
@@ -0,0 +1,66 @@
+//! Loads and stores reported by WebAssembly compiled with memory access
+//! tracing.
+
+use crate::store::StoreContextMut;
+use anyhow::Result;
+
+/// Whether a [`MemoryAccess`] read or wrote memory.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MemoryAccessKind {
+    /// The access was a load, such as `i32.load`.
+    Load,
+    /// The access was a store, such as `i32.store`.
+    Store,
+}
+
+/// A load from or store to linear memory made by WebAssembly.
+///
+/// These are reported to the callback configured with
+/// [`Store::memory_access_callback`](crate::Store::memory_access_callback)
+/// for modules compiled with
+/// [`Config::trace_memory_accesses`](crate::Config::trace_memory_accesses).
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct MemoryAccess {
+    pub(crate) kind: MemoryAccessKind,
+    pub(crate) memory_index: u32,
+    pub(crate) address: u64,
+    pub(crate) size: u32,
+    pub(crate) value: u128,
+}
+
+impl MemoryAccess {
+    /// Returns whether this access was a load or a store.
+    pub fn kind(&self) -> MemoryAccessKind {
+        self.kind
+    }
+
+    /// Returns the index of the accessed memory within the module of the
+    /// instance which accessed it.
+    pub fn memory_index(&self) -> u32 {
+        self.memory_index
+    }
+
+    /// Returns the address of the first byte accessed, including the static
+    /// offset of the instruction.
+    pub fn address(&self) -> u64 {
+        self.address
+    }
+
+    /// Returns the number of bytes accessed, between 1 and 16.
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    /// Returns the bytes which were loaded or stored, as a little-endian
+    /// integer.
+    ///
+    /// Only the low [`MemoryAccess::size`] bytes can be non-zero, so for
+    /// example the value of an `i32.load8_s` of `0xff` is `0xff`, rather than
+    /// the sign-extended result of the instruction.
+    pub fn value(&self) -> u128 {
+        self.value
+    }
+}
+
+pub(crate) type MemoryAccessCallback<T> =
+    Box<dyn FnMut(StoreContextMut<'_, T>, &MemoryAccess) -> Result<()> + Send + Sync>;
@@ -0,0 +1,186 @@
+//! Counters and gauges describing the activity of an [`Engine`].
+
+use crate::Engine;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+/// A snapshot of runtime metrics for an [`Engine`], returned by
+/// [`Engine::metrics`].
+///
+/// Counters such as [`EngineMetrics::traps`] only ever increase over the
+/// lifetime of an engine, while gauges such as
+/// [`EngineMetrics::live_instances`] describe the engine at the moment the
+/// snapshot was taken. Metrics are updated with relaxed atomic operations, so
+/// a snapshot taken while other threads are using the engine may not be
+/// consistent across metrics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EngineMetrics {
+    code_bytes: usize,
+    live_modules: usize,
+    live_instances: usize,
+    instance_slots: Option<(usize, usize)>,
+    cache_hits: u64,
+    cache_misses: u64,
+    fuel_consumed: u64,
+    traps: u64,
+    tier_ups: u64,
+}
+
+impl EngineMetrics {
+    /// Returns the number of bytes of compiled machine code held by the live
+    /// [`Module`](crate::Module)s of this engine.
+    pub fn code_bytes(&self) -> usize {
+        self.code_bytes
+    }
+
+    /// Returns the number of [`Module`](crate::Module)s of this engine which
+    /// are still alive.
+    pub fn live_modules(&self) -> usize {
+        self.live_modules
+    }
+
+    /// Returns the number of WebAssembly instances of this engine which are
+    /// still alive, that is which belong to a [`Store`](crate::Store) which
+    /// hasn't been dropped yet.
+    pub fn live_instances(&self) -> usize {
+        self.live_instances
+    }
+
+    /// Returns the number of instance slots in use, and the total number of
+    /// instance slots, when using the
+    /// [pooling allocator](crate::InstanceAllocationStrategy::Pooling).
+    ///
+    /// Returns `None` with other allocation strategies, which don't limit the
+    /// number of instances.
+    pub fn instance_slots(&self) -> Option<(usize, usize)> {
+        self.instance_slots
+    }
+
+    /// Returns the number of modules whose compiled artifacts were found in
+    /// the [compilation cache](crate::Config::cache_config_load).
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits
+    }
+
+    /// Returns the number of modules which were compiled while the
+    /// [compilation cache](crate::Config::cache_config_load) was enabled
+    /// because their artifacts weren't found in it.
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses
+    }
+
+    /// Returns the fraction of cache lookups which were hits, or `None` if the
+    /// cache has never been consulted.
+    pub fn cache_hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits + self.cache_misses;
+        if lookups == 0 {
+            return None;
+        }
+        Some(self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// Returns the total fuel consumed by [`Store`](crate::Store)s of this
+    /// engine which have been dropped.
+    ///
+    /// Fuel is only consumed when
+    /// [`Config::consume_fuel`](crate::Config::consume_fuel) is enabled, and
+    /// is added to this total when the store consuming it is dropped. Use
+    /// [`Store::fuel_consumed`](crate::Store::fuel_consumed) for the fuel
+    /// consumed so far by a store which is still alive.
+    pub fn fuel_consumed(&self) -> u64 {
+        self.fuel_consumed
+    }
+
+    /// Returns the number of calls into WebAssembly which have returned a
+    /// [`Trap`](crate::Trap), including traps raised by host functions.
+    pub fn traps(&self) -> u64 {
+        self.traps
+    }
+
+    /// Returns the number of functions which were recompiled with Cranelift
+    /// after becoming hot, with
+    /// [`Config::tiered_compilation`](crate::Config::tiered_compilation).
+    pub fn tier_ups(&self) -> u64 {
+        self.tier_ups
+    }
+}
+
+/// The counters behind [`EngineMetrics`], updated as the engine is used.
+#[derive(Default)]
+pub(crate) struct MetricsCounters {
+    code_bytes: AtomicUsize,
+    live_modules: AtomicUsize,
+    live_instances: AtomicUsize,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+    fuel_consumed: AtomicU64,
+    traps: AtomicU64,
+    tier_ups: AtomicU64,
+}
+
+impl MetricsCounters {
+    pub(crate) fn module_created(&self, code_bytes: usize) {
+        self.live_modules.fetch_add(1, Ordering::Relaxed);
+        self.code_bytes.fetch_add(code_bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn module_dropped(&self, code_bytes: usize) {
+        self.live_modules.fetch_sub(1, Ordering::Relaxed);
+        self.code_bytes.fetch_sub(code_bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn code_compiled(&self, code_bytes: usize) {
+        self.code_bytes.fetch_add(code_bytes, Ordering::Relaxed);
+    }
+
+    pub(crate) fn instances_created(&self, count: usize) {
+        self.live_instances.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn instances_dropped(&self, count: usize) {
+        self.live_instances.fetch_sub(count, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "cache")]
+    pub(crate) fn cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[cfg(feature = "cache")]
+    pub(crate) fn cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn fuel_consumed(&self, fuel: u64) {
+        self.fuel_consumed.fetch_add(fuel, Ordering::Relaxed);
+    }
+
+    pub(crate) fn trapped(&self) {
+        self.traps.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn function_tiered_up(&self) {
+        self.tier_ups.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Engine {
+    /// Returns a snapshot of this engine's runtime metrics.
+    ///
+    /// This can be called from any thread at any time, for example
+    /// periodically to export the metrics to a monitoring system. See
+    /// [`EngineMetrics`] for the available metrics.
+    pub fn metrics(&self) -> EngineMetrics {
+        let counters = self.metrics_counters();
+        EngineMetrics {
+            code_bytes: counters.code_bytes.load(Ordering::Relaxed),
+            live_modules: counters.live_modules.load(Ordering::Relaxed),
+            live_instances: counters.live_instances.load(Ordering::Relaxed),
+            instance_slots: self.allocator().instance_slots(),
+            cache_hits: counters.cache_hits.load(Ordering::Relaxed),
+            cache_misses: counters.cache_misses.load(Ordering::Relaxed),
+            fuel_consumed: counters.fuel_consumed.load(Ordering::Relaxed),
+            traps: counters.traps.load(Ordering::Relaxed),
+            tier_ups: counters.tier_ups.load(Ordering::Relaxed),
+        }
+    }
+}
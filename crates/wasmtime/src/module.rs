@@ -3,28 +3,46 @@ use crate::{
     signatures::SignatureCollection,
     types::{ExportType, ExternType, ImportType},
 };
-use anyhow::{bail, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use once_cell::sync::OnceCell;
 use std::fs;
+use std::marker;
 use std::mem;
 use std::ops::Range;
 use std::path::Path;
 use std::sync::Arc;
 use wasmparser::{Parser, ValidPayload, Validator};
 use wasmtime_environ::{
-    DefinedFuncIndex, DefinedMemoryIndex, FunctionInfo, ModuleEnvironment, PrimaryMap,
-    SignatureIndex, TypeTables,
+    direct_import_thunk, DefinedFuncIndex, DefinedMemoryIndex, EntityIndex, FunctionInfo,
+    ModuleEnvironment, PrimaryMap, SignatureIndex, TypeTables,
 };
 use wasmtime_jit::{CompiledModule, CompiledModuleInfo};
 use wasmtime_runtime::{
-    CompiledModuleId, MemoryImage, MmapVec, ModuleMemoryImages, VMSharedSignatureIndex,
+    CompiledModuleId, MemoryImage, MmapVec, ModuleMemoryImages, SharedGlobals, SharedTables,
+    VMFunctionImport, VMGlobalDefinition, VMSharedSignatureIndex,
 };
 
+mod address_map;
+mod features;
+mod lazy;
 mod registry;
+mod report;
+mod segments;
 mod serialization;
+#[cfg(compiler)]
+mod stream;
 
+use lazy::LazyFunctions;
+
+pub use address_map::AddressMap;
+pub(crate) use features::name_disabled_feature;
+pub use features::DisabledFeatureError;
 pub use registry::{FrameInfo, FrameSymbol, GlobalModuleRegistry, ModuleRegistry};
-pub use serialization::SerializedModule;
+pub use report::{CompilationReport, FunctionReport};
+pub use segments::{SegmentInitializer, SegmentKind, SegmentOutOfBounds};
+pub use serialization::{IncompatibleModuleError, SerializedModule};
+#[cfg(compiler)]
+pub use stream::ModuleStream;
 
 /// A compiled WebAssembly module, ready to be instantiated.
 ///
@@ -116,6 +134,12 @@ struct ModuleInner {
     /// improves memory usage for modules that are created but may not ever be
     /// instantiated.
     memory_images: OnceCell<Option<ModuleMemoryImages>>,
+    /// The functions of this module, if they're compiled lazily.
+    lazy: Option<LazyFunctions>,
+    /// The globals shared by all instances of this module, if it has any.
+    shared_globals: Option<SharedGlobals>,
+    /// The tables shared by all instances of this module, if it has any.
+    shared_tables: Option<SharedTables>,
 }
 
 impl Module {
@@ -243,6 +267,47 @@ impl Module {
         }
     }
 
+    /// Creates a new WebAssembly `Module` from the bytes read from `reader`,
+    /// compiling its functions while the rest of it is still being read.
+    ///
+    /// This is useful when reading a module is slow, such as when it's
+    /// downloaded, since compiling the module overlaps with reading it rather
+    /// than waiting for all of it. See [`ModuleStream`] for how modules are
+    /// compiled as they're read, and for compiling modules from asynchronous
+    /// sources.
+    ///
+    /// # Errors
+    ///
+    /// Along with the reasons [`Module::new`] can fail, this fails if reading
+    /// from `reader` fails.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let file = std::fs::File::open("./path/to/foo.wasm")?;
+    /// let module = Module::from_reader(&engine, file)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn from_reader(engine: &Engine, mut reader: impl std::io::Read) -> Result<Module> {
+        let mut stream = ModuleStream::new(engine)?;
+        let mut buf = vec![0; 64 * 1024];
+        loop {
+            match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => stream.push(&buf[..n])?,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e).context("failed to read WebAssembly module"),
+            }
+        }
+        stream.finish()
+    }
+
     /// Creates a new WebAssembly `Module` from the given in-memory `binary`
     /// data.
     ///
@@ -281,6 +346,13 @@ impl Module {
             .check_compatible_with_native_host()
             .context("compilation settings are not compatible with the native host")?;
 
+        // Lazily compiled modules don't have complete artifacts to cache.
+        let tunables = &engine.config().tunables;
+        if tunables.lazy_compilation || tunables.tiered_compilation {
+            let (mmap, info, types, lazy) = Module::build_lazy_artifacts(engine, binary)?;
+            return Self::from_parts(engine, mmap, Some(info), types, Some(lazy));
+        }
+
         cfg_if::cfg_if! {
             if #[cfg(feature = "cache")] {
                 let state = (HashedEngineCompileEnv(engine), binary);
@@ -292,7 +364,12 @@ impl Module {
                     &state,
 
                     // Cache miss, compute the actual artifacts
-                    |(engine, wasm)| Module::build_artifacts(engine.0, wasm),
+                    |(engine, wasm)| {
+                        if engine.0.cache_config().enabled() {
+                            engine.0.metrics_counters().cache_miss();
+                        }
+                        Module::build_artifacts(engine.0, wasm, None, None)
+                    },
 
                     // Implementation of how to serialize artifacts
                     |(engine, _wasm), (mmap, _info, types)| {
@@ -305,18 +382,173 @@ impl Module {
 
                     // Cache hit, deserialize the provided artifacts
                     |(engine, _wasm), serialized_bytes| {
-                        SerializedModule::from_bytes(&serialized_bytes, &engine.0.config().module_version)
+                        let artifacts = SerializedModule::from_bytes(&serialized_bytes, &engine.0.config().module_version)
                             .ok()?
                             .into_parts(engine.0)
-                            .ok()
+                            .ok()?;
+                        engine.0.metrics_counters().cache_hit();
+                        Some(artifacts)
                     },
                 )?;
             } else {
-                let (mmap, info, types) = Module::build_artifacts(engine, binary)?;
+                let (mmap, info, types) = Module::build_artifacts(engine, binary, None, None)?;
             }
         };
 
-        Self::from_parts(engine, mmap, info, types)
+        Self::from_parts(engine, mmap, info, types, None)
+    }
+
+    /// Creates a new `Module` which starts out in the state that an instance
+    /// of `bytes` is in after calling its `init` export.
+    ///
+    /// Modules often spend a while setting up their state each time they're
+    /// instantiated, for example by running constructors or parsing
+    /// configuration built into them. When that state doesn't depend on the
+    /// host it can instead be computed once, ahead of time: this function
+    /// instantiates `bytes` into a new [`Store`](crate::Store), calls the
+    /// `init` function it exports, which must take no parameters and return
+    /// no results, and then compiles `bytes` again with the contents of the
+    /// instance's memories, tables, and mutable globals as their initial
+    /// values. The returned module can be serialized with
+    /// [`Module::serialize`] to do all this offline.
+    ///
+    /// To keep the host out of the initial state the module may not have any
+    /// imports, and it can't store `externref` values in its tables or
+    /// globals. Instances of the returned module don't run the start
+    /// function, since it already ran before `init`, but the `init` export
+    /// itself remains.
+    ///
+    /// # Errors
+    ///
+    /// Along with the reasons [`Module::new`] can fail, this fails if the
+    /// module has imports, if `init` isn't an exported function of the
+    /// expected type, or if instantiating the module or calling `init`
+    /// traps. Engines configured with
+    /// [`Config::async_support`](crate::Config::async_support) can't
+    /// pre-initialize modules.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::preinitialize(
+    ///     &engine,
+    ///     r#"
+    ///         (module
+    ///             (global (export "answer") (mut i32) (i32.const 0))
+    ///             (func (export "init") (global.set 0 (i32.const 42))))
+    ///     "#,
+    ///     "init",
+    /// )?;
+    /// let mut store = Store::new(&engine, ());
+    /// let instance = Instance::new(&mut store, &module, &[])?;
+    /// let answer = instance.get_global(&mut store, "answer").unwrap();
+    /// assert_eq!(answer.get(&mut store).i32(), Some(42));
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn preinitialize(engine: &Engine, bytes: impl AsRef<[u8]>, init: &str) -> Result<Module> {
+        let bytes = bytes.as_ref();
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes)?;
+        if engine.config().async_support {
+            bail!("cannot pre-initialize modules with an engine configured for async");
+        }
+
+        let module = Module::from_binary(engine, &bytes)?;
+        if let Some(import) = module.imports().next() {
+            bail!(
+                "cannot pre-initialize a module with imports, found import `{}::{}`",
+                import.module(),
+                import.name()
+            );
+        }
+        let mut store = crate::Store::new(engine, ());
+        let instance = crate::Instance::new(&mut store, &module, &[])?;
+        instance
+            .get_typed_func::<(), (), _>(&mut store, init)?
+            .call(&mut store, ())
+            .with_context(|| format!("failed to run initialization function `{}`", init))?;
+        let snapshot = instance.snapshot(&mut store)?;
+
+        let (mmap, info, types) = Module::build_artifacts(engine, &bytes, Some(&snapshot), None)?;
+        Self::from_parts(engine, mmap, info, types, None)
+    }
+
+    /// Creates a new `Module` from `bytes` like [`Module::new`], compiling it
+    /// with the guidance of `profile`, a profile of earlier executions of the
+    /// same module.
+    ///
+    /// Profiles are collected with
+    /// [`Config::collect_execution_profile`](crate::Config::collect_execution_profile).
+    /// Cranelift uses the profile to lay out the blocks of each function: the
+    /// paths which the profile shows to be rarely taken, such as the
+    /// fallthrough of a `br_if` which nearly always branches, are placed
+    /// after the rest of the function's code so that the hot paths are
+    /// straight-line code. Cranelift doesn't inline calls, so the profile's
+    /// call counts don't affect compilation. The baseline compiler ignores
+    /// profiles.
+    ///
+    /// Modules created this way can be [serialized](Module::serialize), but
+    /// aren't cached, since their code depends on the profile.
+    ///
+    /// # Errors
+    ///
+    /// Along with the reasons [`Module::new`] can fail, this fails if
+    /// `profile` isn't a profile of `bytes`, or if the engine compiles modules
+    /// lazily, which doesn't support profiles.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let wat = r#"
+    ///     (module
+    ///         (func (export "abs") (param i32) (result i32)
+    ///             (if (result i32) (i32.lt_s (local.get 0) (i32.const 0))
+    ///                 (then (i32.sub (i32.const 0) (local.get 0)))
+    ///                 (else (local.get 0)))))
+    /// "#;
+    ///
+    /// let mut config = Config::new();
+    /// config.lazy_compilation(true).collect_execution_profile(true);
+    /// let engine = Engine::new(&config)?;
+    /// let module = Module::new(&engine, wat)?;
+    /// let mut store = Store::new(&engine, ());
+    /// let instance = Instance::new(&mut store, &module, &[])?;
+    /// let abs = instance.get_typed_func::<i32, i32, _>(&mut store, "abs")?;
+    /// abs.call(&mut store, 5)?;
+    /// let profile = module.execution_profile().unwrap();
+    /// assert_eq!(profile.calls(0), 1);
+    ///
+    /// let module = Module::new_with_profile(&Engine::default(), wat, &profile)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(compiler)]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
+    pub fn new_with_profile(
+        engine: &Engine,
+        bytes: impl AsRef<[u8]>,
+        profile: &crate::ExecutionProfile,
+    ) -> Result<Module> {
+        let bytes = bytes.as_ref();
+        #[cfg(feature = "wat")]
+        let bytes = wat::parse_bytes(bytes)?;
+        let tunables = &engine.config().tunables;
+        if tunables.lazy_compilation || tunables.tiered_compilation {
+            bail!("cannot compile a module with a profile in an engine which compiles lazily");
+        }
+        engine
+            .check_compatible_with_native_host()
+            .context("compilation settings are not compatible with the native host")?;
+        let (mmap, info, types) = Module::build_artifacts(engine, &bytes, None, Some(profile))?;
+        Self::from_parts(engine, mmap, info, types, None)
     }
 
     /// Converts an input binary-encoded WebAssembly module to compilation
@@ -336,10 +568,16 @@ impl Module {
     /// * Type information about all the modules returned. All returned modules
     ///   have local type information with indices that refer to these returned
     ///   tables.
+    ///
+    /// If a `snapshot` is provided then it replaces the initial state of the
+    /// module, see [`Module::preinitialize`], and if a `profile` is provided
+    /// then it guides compilation, see [`Module::new_with_profile`].
     #[cfg(compiler)]
     pub(crate) fn build_artifacts(
         engine: &Engine,
         wasm: &[u8],
+        snapshot: Option<&crate::Snapshot>,
+        profile: Option<&crate::ExecutionProfile>,
     ) -> Result<(MmapVec, Option<CompiledModuleInfo>, TypeTables)> {
         let tunables = &engine.config().tunables;
 
@@ -349,27 +587,169 @@ impl Module {
         // this module.
         let (mut translation, types) = ModuleEnvironment::new(tunables, &engine.config().features)
             .translate(wasm)
+            .map_err(name_disabled_feature)
             .context("failed to parse WebAssembly module")?;
 
+        // A pre-initialized module starts out in the state captured by the
+        // snapshot, which must be known before compiling since the sizes of
+        // memories and tables affect how they're implemented.
+        if let Some(snapshot) = snapshot {
+            snapshot.bake(&mut translation, tunables)?;
+        }
+        if let Some(profile) = profile {
+            profile.apply(&mut translation)?;
+        }
+
+        // Functions which can never be called are still validated, along with
+        // the rest of the module, but then compiled from a body which only
+        // traps: no locals, `unreachable` and `end`.
+        if tunables.skip_unreachable_functions {
+            Module::validate(engine, wasm)?;
+            for index in wasmtime_environ::unreachable_functions(&translation)? {
+                let body = &mut translation.function_body_inputs[index].body;
+                *body = wasmparser::FunctionBody::new(body.range().start, &[0x00, 0x00, 0x0b]);
+            }
+        }
+
         // Next compile all functions in parallel using rayon. This will perform
         // the actual validation of all the function bodies.
         let functions = mem::take(&mut translation.function_body_inputs);
         let functions = functions.into_iter().collect::<Vec<_>>();
-        let funcs = engine
+        let (funcs, compile_times): (Vec<_>, Vec<_>) = engine
             .run_maybe_parallel(functions, |(index, func)| {
-                engine
-                    .compiler()
-                    .compile_function(&translation, index, func, tunables, &types)
+                let start = std::time::Instant::now();
+                let func = engine.compiler().compile_function(
+                    &translation,
+                    index,
+                    func,
+                    tunables,
+                    &types,
+                )?;
+                Ok::<_, wasmtime_environ::CompileError>((func, start.elapsed()))
+            })
+            .map_err(name_disabled_feature)?
+            .into_iter()
+            .unzip();
+
+        let funcs = funcs.into_iter().collect();
+        let (mmap, info) =
+            Module::emit_artifacts(engine, translation, &types, funcs, Some(&compile_times))?;
+        Ok((mmap, Some(info), types))
+    }
+
+    /// Same as [`Module::build_artifacts`], except that the artifacts only
+    /// contain a stub for each function, which compiles it on its first call
+    /// using the returned [`LazyFunctions`].
+    ///
+    /// The whole module is still validated up front.
+    #[cfg(compiler)]
+    fn build_lazy_artifacts(
+        engine: &Engine,
+        wasm: &[u8],
+    ) -> Result<(MmapVec, CompiledModuleInfo, TypeTables, LazyFunctions)> {
+        let tunables = &engine.config().tunables;
+        Module::validate(engine, wasm)?;
+        let (mut translation, types) = ModuleEnvironment::new(tunables, &engine.config().features)
+            .translate(wasm)
+            .map_err(name_disabled_feature)
+            .context("failed to parse WebAssembly module")?;
+
+        let functions = mem::take(&mut translation.function_body_inputs);
+        let indices = functions.keys().collect::<Vec<_>>();
+        let tier_up_threshold = match tunables.tiered_compilation {
+            true => Some(engine.config().tier_up_threshold),
+            false => None,
+        };
+        let lazy = LazyFunctions::new(
+            wasm,
+            functions,
+            engine.config().features.memory64,
+            tier_up_threshold,
+            tunables.collect_profile,
+        )?;
+        let stubs = engine
+            .run_maybe_parallel(indices, |index| {
+                engine.compiler().compile_lazy_stub(
+                    &translation,
+                    index,
+                    tunables,
+                    &types,
+                    lazy.code_slot(index),
+                )
             })?
             .into_iter()
             .collect();
 
+        let (mmap, info) = Module::emit_artifacts(engine, translation, &types, stubs, None)?;
+        Ok((mmap, info, types, lazy))
+    }
+
+    /// Collects the compiled functions of `translation` into the final
+    /// artifacts of a module.
+    #[cfg(compiler)]
+    fn emit_artifacts(
+        engine: &Engine,
+        mut translation: wasmtime_environ::ModuleTranslation<'_>,
+        types: &TypeTables,
+        funcs: PrimaryMap<DefinedFuncIndex, Box<dyn std::any::Any + Send>>,
+        compile_times: Option<&[std::time::Duration]>,
+    ) -> Result<(MmapVec, CompiledModuleInfo)> {
+        let tunables = &engine.config().tunables;
+
+        // Compile a trampoline for each signature the host can call wasm
+        // functions with, also in parallel.
+        let signatures = translation.exported_signatures.clone();
+        let trampolines = engine.run_maybe_parallel(signatures, |i| {
+            engine.compiler().compile_host_to_wasm_trampoline(&types[i])
+        })?;
+
         // Collect all the function results into a final ELF object.
         let mut obj = engine.compiler().object()?;
-        let (funcs, trampolines) =
-            engine
-                .compiler()
-                .emit_obj(&translation, &types, funcs, tunables, &mut obj)?;
+        let mut code = engine.compiler().emit_obj(
+            &translation,
+            types,
+            funcs,
+            trampolines,
+            tunables,
+            &mut obj,
+        )?;
+        for (info, time) in code.funcs.values_mut().zip(compile_times.unwrap_or(&[])) {
+            info.compile_time = Some(*time);
+        }
+
+        // Now that the functions have their places in the text section, encode
+        // their trap tables and address maps in parallel too, and then
+        // concatenate them in order.
+        let func_traps = mem::take(&mut code.traps).into_iter().map(|(_, t)| t);
+        let func_addrs = mem::take(&mut code.address_maps)
+            .into_iter()
+            .map(|(_, a)| a);
+        let tables = code
+            .funcs
+            .values()
+            .map(|info| info.start..info.start + u64::from(info.length))
+            .zip(func_traps)
+            .zip(func_addrs)
+            .collect::<Vec<_>>();
+        let tables = engine.run_maybe_parallel(tables, |((range, traps), addrs)| {
+            let mut trap_table = wasmtime_environ::TrapEncodingBuilder::default();
+            trap_table.push(range.clone(), &traps);
+            let mut address_map = wasmtime_environ::AddressMapSection::default();
+            if tunables.generate_address_map {
+                address_map.push(range, &addrs);
+            }
+            Ok::<_, anyhow::Error>((trap_table, address_map))
+        })?;
+        let mut traps = wasmtime_environ::TrapEncodingBuilder::default();
+        let mut addrs = wasmtime_environ::AddressMapSection::default();
+        for (trap_table, address_map) in tables {
+            traps.extend(trap_table);
+            addrs.extend(address_map);
+        }
+        if tunables.generate_address_map {
+            addrs.append_to(&mut obj);
+        }
+        traps.append_to(&mut obj);
 
         // If configured attempt to use static memory initialization which
         // can either at runtime be implemented as a single memcpy to
@@ -386,10 +766,7 @@ impl Module {
         // table lazy init.
         translation.try_func_table_init();
 
-        let (mmap, info) =
-            wasmtime_jit::finish_compile(translation, obj, funcs, trampolines, tunables)?;
-
-        Ok((mmap, Some(info), types))
+        wasmtime_jit::finish_compile(translation, obj, code, tunables)
     }
 
     /// Deserializes an in-memory compiled module previously created with
@@ -434,11 +811,49 @@ impl Module {
     /// those defined by any version of wasmtime. (this means that if you cache
     /// blobs across versions of wasmtime you can be safely guaranteed that
     /// future versions of wasmtime will reject old cache entries).
+    ///
+    /// # Errors
+    ///
+    /// Modules are checked to have been compiled by the same version of
+    /// Wasmtime (according to [`Config::module_version`]), for the same target
+    /// and for CPU features available on the host, and with the same
+    /// compilation settings and WebAssembly features as `engine`. If any of
+    /// these don't match then the returned error is an
+    /// [`IncompatibleModuleError`].
+    ///
+    /// [`Config::module_version`]: crate::Config::module_version
     pub unsafe fn deserialize(engine: &Engine, bytes: impl AsRef<[u8]>) -> Result<Module> {
         let module = SerializedModule::from_bytes(bytes.as_ref(), &engine.config().module_version)?;
         module.into_module(engine)
     }
 
+    /// Same as [`deserialize`], except that the module's compilation settings
+    /// aren't checked for compatibility with `engine`.
+    ///
+    /// [`deserialize`]: Module::deserialize
+    ///
+    /// # Unsafety
+    ///
+    /// All of the reasons that [`deserialize`] is `unsafe` applies to this
+    /// function as well. Additionally the caller must guarantee that the module
+    /// was compiled for the host with settings compatible with `engine`, which
+    /// [`deserialize`] would otherwise check. Running code compiled with
+    /// different settings, for example without the bounds checks that the
+    /// engine's memory configuration relies on, is undefined behavior.
+    ///
+    /// The version of Wasmtime the module was compiled with is still checked
+    /// according to [`Config::module_version`] since the format of serialized
+    /// modules isn't stable across versions.
+    ///
+    /// [`Config::module_version`]: crate::Config::module_version
+    pub unsafe fn deserialize_unchecked(
+        engine: &Engine,
+        bytes: impl AsRef<[u8]>,
+    ) -> Result<Module> {
+        let module = SerializedModule::from_bytes(bytes.as_ref(), &engine.config().module_version)?;
+        module.into_module_unchecked(engine)
+    }
+
     /// Same as [`deserialize`], except that the contents of `path` are read to
     /// deserialize into a [`Module`].
     ///
@@ -472,13 +887,18 @@ impl Module {
         mmap: MmapVec,
         info: Option<CompiledModuleInfo>,
         types: TypeTables,
+        lazy: Option<LazyFunctions>,
     ) -> Result<Self> {
-        let module = Arc::new(CompiledModule::from_artifacts(
+        let module = CompiledModule::from_artifacts(
             mmap,
             info,
             &*engine.config().profiler,
             engine.unique_id_allocator(),
-        )?);
+        )?;
+        if engine.config().huge_pages_for_code {
+            module.advise_huge_pages();
+        }
+        let module = Arc::new(module);
 
         // Validate the module can be used with the current allocator
         engine.allocator().validate(module.module())?;
@@ -487,13 +907,20 @@ impl Module {
             engine.signatures(),
             &types,
             module.trampolines().map(|(idx, f, _)| (idx, f)),
+            module.native_trampolines(),
         );
 
         // We're about to create a `Module` for real now so enter this module
         // into the global registry of modules so we can resolve traps
         // appropriately. Note that the corresponding `unregister` happens below
         // in `Drop for ModuleInner`.
-        registry::register(engine, &module);
+        registry::register(engine, &module, lazy.is_some());
+        engine
+            .metrics_counters()
+            .module_created(module.code().len());
+
+        let shared_globals = SharedGlobals::new(module.module())?;
+        let shared_tables = SharedTables::new(module.module())?;
 
         Ok(Self {
             inner: Arc::new(ModuleInner {
@@ -502,6 +929,9 @@ impl Module {
                 signatures,
                 memory_images: OnceCell::new(),
                 module,
+                lazy,
+                shared_globals,
+                shared_tables,
             }),
         })
     }
@@ -530,12 +960,17 @@ impl Module {
 
         let mut functions = Vec::new();
         for payload in Parser::new(0).parse_all(binary) {
-            if let ValidPayload::Func(a, b) = validator.payload(&payload?)? {
+            let payload = validator
+                .payload(&payload?)
+                .map_err(name_disabled_feature)?;
+            if let ValidPayload::Func(a, b) = payload {
                 functions.push((a, b));
             }
         }
 
-        engine.run_maybe_parallel(functions, |(mut validator, body)| validator.validate(&body))?;
+        engine
+            .run_maybe_parallel(functions, |(mut validator, body)| validator.validate(&body))
+            .map_err(name_disabled_feature)?;
         Ok(())
     }
 
@@ -551,6 +986,9 @@ impl Module {
     #[cfg(compiler)]
     #[cfg_attr(nightlydoc, doc(cfg(feature = "cranelift")))] // see build.rs
     pub fn serialize(&self) -> Result<Vec<u8>> {
+        if self.inner.lazy.is_some() {
+            bail!("cannot serialize a module whose functions are compiled lazily");
+        }
         SerializedModule::new(self).to_bytes(&self.inner.engine.config().module_version)
     }
 
@@ -558,6 +996,36 @@ impl Module {
         &self.inner.module
     }
 
+    /// Returns whether calls to this module's imported functions go through
+    /// thunks, which [`Module::devirtualized`] can patch.
+    pub(crate) fn has_import_thunks(&self) -> bool {
+        self.inner.lazy.is_none() && self.compiled_module().import_thunks().len() > 0
+    }
+
+    /// Returns a copy of this module whose calls to its imported functions go
+    /// straight to `imports`, which has one entry for each of them.
+    ///
+    /// The copy has its own code, in which the thunks through which calls to
+    /// imports go are replaced with thunks which jump to `imports`.
+    ///
+    /// # Safety
+    ///
+    /// The copy must only be instantiated with `imports` as its imported
+    /// functions, which must outlive its instances.
+    pub(crate) unsafe fn devirtualized(&self, imports: &[VMFunctionImport]) -> Result<Module> {
+        let module = self.compiled_module();
+        assert_eq!(module.import_thunks().len(), imports.len());
+        let target = self.engine().target();
+        let mut mmap = MmapVec::from_slice(module.mmap())?;
+        for (range, import) in module.import_thunks().zip(imports) {
+            let body = import.body.as_ptr() as u64;
+            let thunk = direct_import_thunk(&target, body, import.vmctx as u64)
+                .expect("modules are only compiled with import thunks where they're supported");
+            mmap[range].copy_from_slice(&thunk);
+        }
+        Module::from_parts(self.engine(), mmap, None, self.types().clone(), None)
+    }
+
     pub(crate) fn env_module(&self) -> &wasmtime_environ::Module {
         self.compiled_module().module()
     }
@@ -773,6 +1241,251 @@ impl Module {
         ))
     }
 
+    /// Looks up an export in this [`Module`] by name, returning a
+    /// [`ModuleExport`] which can be used to look up the export within
+    /// instances of this module with
+    /// [`Instance::get_module_export`](crate::Instance::get_module_export)
+    /// without searching for its name each time.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::new(&engine, r#"(module (func (export "run")))"#)?;
+    /// let run = module.get_export_index("run").unwrap();
+    /// for _ in 0..3 {
+    ///     let mut store = Store::new(&engine, ());
+    ///     let instance = Instance::new(&mut store, &module, &[])?;
+    ///     let func = instance.get_module_export(&mut store, &run).unwrap();
+    ///     func.into_func().unwrap().typed::<(), (), _>(&store)?.call(&mut store, ())?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_export_index(&self, name: &str) -> Option<ModuleExport> {
+        let module = self.compiled_module().module();
+        let (index, name, entity) = module.exports.get_full(name)?;
+        Some(ModuleExport {
+            name: name.as_str().into(),
+            index,
+            entity: *entity,
+        })
+    }
+
+    /// Looks up a function export in this [`Module`] by name and checks that
+    /// it has the type `Params -> Results`, returning a [`TypedModuleExport`]
+    /// which can be used to look up the function within instances of this
+    /// module as a [`TypedFunc`](crate::TypedFunc) with
+    /// [`Instance::get_typed_module_export`](crate::Instance::get_typed_module_export).
+    ///
+    /// This is like [`Instance::get_typed_func`](crate::Instance::get_typed_func),
+    /// except that the lookup and type check are done once, ahead of time,
+    /// rather than for every instance.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if there's no function export named `name` or if its
+    /// type isn't `Params -> Results`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::new(&engine, r#"(module (func (export "run")))"#)?;
+    /// let run = module.get_typed_func_export::<(), ()>("run")?;
+    /// for _ in 0..3 {
+    ///     let mut store = Store::new(&engine, ());
+    ///     let instance = Instance::new(&mut store, &module, &[])?;
+    ///     let func = instance.get_typed_module_export(&mut store, &run).unwrap();
+    ///     func.call(&mut store, ())?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_typed_func_export<Params, Results>(
+        &self,
+        name: &str,
+    ) -> Result<TypedModuleExport<Params, Results>>
+    where
+        Params: crate::WasmParams,
+        Results: crate::WasmResults,
+    {
+        let export = self
+            .get_export_index(name)
+            .ok_or_else(|| anyhow!("failed to find function export `{}`", name))?;
+        let func_index = match export.entity {
+            EntityIndex::Function(index) => index,
+            _ => bail!("failed to find function export `{}`", name),
+        };
+        let signature = self.env_module().functions[func_index].signature;
+        let ty = crate::FuncType::from_wasm_func_type(self.types()[signature].clone());
+        (|| -> Result<()> {
+            Params::typecheck(ty.params()).context("type mismatch with parameters")?;
+            Results::typecheck(ty.results()).context("type mismatch with results")?;
+            Ok(())
+        })()
+        .with_context(|| format!("failed to convert function `{}` to given type", name))?;
+        Ok(TypedModuleExport {
+            export,
+            sig_index: self
+                .signatures()
+                .shared_signature(signature)
+                .expect("signature should be registered"),
+            _module: self.clone(),
+            _ty: marker::PhantomData,
+        })
+    }
+
+    /// Returns the contents of each custom section named `name` in this
+    /// [`Module`], in the order they appear in the original wasm binary.
+    ///
+    /// Custom sections are kept when a module is serialized, so they're
+    /// available on modules created with [`Module::deserialize`] too. The
+    /// `name` section, and DWARF sections, whose names start with `.debug_`,
+    /// are not retained, since they're decoded into the module's names and
+    /// debug information when it's compiled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::new(&engine, "(module (@custom \"abi\" \"v2\"))")?;
+    /// let abi = module.custom_sections("abi").collect::<Vec<_>>();
+    /// assert_eq!(abi, [b"v2"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn custom_sections<'module>(
+        &'module self,
+        name: &'module str,
+    ) -> impl Iterator<Item = &'module [u8]> + 'module {
+        self.all_custom_sections()
+            .filter(move |(n, _)| *n == name)
+            .map(|(_, data)| data)
+    }
+
+    /// Returns the name and contents of every custom section in this
+    /// [`Module`], in the order they appear in the original wasm binary.
+    ///
+    /// See [`Module::custom_sections`] for more information.
+    pub fn all_custom_sections(&self) -> impl ExactSizeIterator<Item = (&str, &[u8])> {
+        self.compiled_module()
+            .module()
+            .custom_sections
+            .iter()
+            .map(|(name, data)| (name.as_str(), data.as_slice()))
+    }
+
+    /// Returns the active data and element segments of this [`Module`], in
+    /// the order they're written to memories and tables when it's
+    /// instantiated.
+    ///
+    /// With bulk memory enabled, instantiation writes segments until one
+    /// doesn't fit and then fails with a [`SegmentOutOfBounds`], leaving the
+    /// earlier segments written to any imported memories and tables. Checking
+    /// the segments beforehand lets an embedder reject such a module without
+    /// any of those side effects.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::new(&engine, r#"
+    ///     (module
+    ///         (memory 1)
+    ///         (data (i32.const 65535) "oops"))
+    /// "#)?;
+    /// let segment = module.initializers().next().unwrap();
+    /// assert_eq!(segment.kind(), SegmentKind::Data);
+    /// assert_eq!(segment.fits_minimum(), Some(false));
+    /// assert!(segment.fits(0, 2 * 65536));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn initializers(&self) -> impl ExactSizeIterator<Item = SegmentInitializer> + '_ {
+        let module = self.env_module();
+        module
+            .active_segments
+            .iter()
+            .map(move |segment| SegmentInitializer::from_wasmtime(module, segment))
+    }
+
+    /// Returns the profile of how this module's functions have executed so
+    /// far, in all of its instances, if it was compiled with
+    /// [`Config::collect_execution_profile`](crate::Config::collect_execution_profile).
+    ///
+    /// The profile can be passed to [`Module::new_with_profile`] to compile
+    /// the module again with its guidance.
+    pub fn execution_profile(&self) -> Option<crate::ExecutionProfile> {
+        let functions = self.inner.lazy.as_ref()?.profile()?;
+        let num_imported_funcs = self.env_module().num_imported_funcs;
+        Some(crate::ExecutionProfile::new(num_imported_funcs, functions))
+    }
+
+    /// Returns a report of the size of each function of this module, in wasm
+    /// and once compiled, along with the size of its stack frame and how long
+    /// it took to compile.
+    ///
+    /// The sizes are saved along with the compiled module, so modules loaded
+    /// with [`Module::deserialize`] or from the compilation cache report them
+    /// too, but not how long their functions took to compile. This returns
+    /// `None` for modules which are compiled lazily, with
+    /// [`Config::lazy_compilation`](crate::Config::lazy_compilation) or
+    /// [`Config::tiered_compilation`](crate::Config::tiered_compilation).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let engine = Engine::default();
+    /// let module = Module::new(
+    ///     &engine,
+    ///     r#"(module (func $small) (func $large (local i64) (local.set 0 (i64.const 1))))"#,
+    /// )?;
+    /// let report = module.compilation_report().unwrap();
+    /// let largest = report
+    ///     .functions()
+    ///     .iter()
+    ///     .max_by_key(|f| f.code_size())
+    ///     .unwrap();
+    /// assert_eq!(largest.name(), Some("large"));
+    /// println!("{}", report);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn compilation_report(&self) -> Option<CompilationReport> {
+        if self.inner.lazy.is_some() {
+            return None;
+        }
+        Some(CompilationReport::new(self.compiled_module()))
+    }
+
+    /// Returns the map between this module's compiled code and the offsets of
+    /// the WebAssembly instructions it was compiled from.
+    ///
+    /// This returns `None` if the module was compiled without an address map,
+    /// with [`Config::generate_address_map`](crate::Config::generate_address_map)
+    /// disabled, or if it's compiled lazily, with
+    /// [`Config::lazy_compilation`](crate::Config::lazy_compilation) or
+    /// [`Config::tiered_compilation`](crate::Config::tiered_compilation), in
+    /// which case [`FrameInfo::from_pc`] still resolves the code of the
+    /// functions compiled so far.
+    pub fn address_map(&self) -> Option<AddressMap<'_>> {
+        if self.inner.lazy.is_some() {
+            return None;
+        }
+        AddressMap::new(self.compiled_module())
+    }
+
     /// Returns the [`Engine`] that this [`Module`] was compiled by.
     pub fn engine(&self) -> &Engine {
         &self.inner.engine
@@ -790,6 +1503,62 @@ impl Module {
         &*self.inner
     }
 
+    /// Returns the number of bytes of machine code this module holds in
+    /// memory, including the code of functions compiled
+    /// [lazily](crate::Config::lazy_compilation) so far.
+    ///
+    /// A module's code is mapped into memory once, when it's created, and is
+    /// shared by all of its instances in every [`Store`](crate::Store) as well
+    /// as by clones of the `Module`, so instantiating the module doesn't add
+    /// to its code size. An [`Engine`] doesn't deduplicate identical code,
+    /// though: modules created separately from the same wasm are each
+    /// compiled and hold a copy of their code, so clone a module to share it
+    /// instead. The total code size of the live modules of an engine is
+    /// reported by [`EngineMetrics::code_bytes`](crate::EngineMetrics::code_bytes).
+    pub fn code_size(&self) -> usize {
+        self.inner.code_bytes()
+    }
+
+    /// Returns a handle to this module which doesn't keep it alive.
+    ///
+    /// A module's code is unloaded once nothing uses the module anymore: once
+    /// every `Module` handle, [`InstancePre`](crate::InstancePre) and
+    /// [`Store`](crate::Store) with an instance of it is dropped. Its code is
+    /// unmapped then, along with its unwind information, the image registered
+    /// with GDB for [native debugging](crate::Config::debug_info) and the code
+    /// of functions compiled [lazily](crate::Config::lazy_compilation).
+    /// Hosts which reload modules, such as plugins, can keep a `WeakModule`
+    /// of each version to find out when its code is gone, or to find the
+    /// module while it's still in use, without keeping superseded versions
+    /// loaded themselves. See also [`Engine::purge_unused_code`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let engine = Engine::default();
+    /// let module = Module::new(&engine, "(module (func))")?;
+    /// let weak = module.downgrade();
+    ///
+    /// let mut store = Store::new(&engine, ());
+    /// Instance::new(&mut store, &module, &[])?;
+    /// drop(module);
+    /// assert!(weak.upgrade().is_some());
+    ///
+    /// // Once the store with its instance is gone the code is unloaded.
+    /// drop(store);
+    /// assert!(weak.upgrade().is_none());
+    /// assert_eq!(engine.metrics().code_bytes(), 0);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn downgrade(&self) -> WeakModule {
+        WeakModule {
+            inner: Arc::downgrade(&self.inner),
+        }
+    }
+
     /// Returns the range of bytes in memory where this module's compilation
     /// image resides.
     ///
@@ -831,6 +1600,65 @@ impl Module {
     }
 }
 
+/// An export of a [`Module`] which has been looked up by name with
+/// [`Module::get_export_index`], with which the export can be found within
+/// instances of the module with
+/// [`Instance::get_module_export`](crate::Instance::get_module_export).
+#[derive(Clone, Debug)]
+pub struct ModuleExport {
+    name: Box<str>,
+    /// The index of the export within the module's exports.
+    pub(crate) index: usize,
+    pub(crate) entity: EntityIndex,
+}
+
+impl ModuleExport {
+    /// Returns the name of this export.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns whether this is the export at `index` within `module`.
+    pub(crate) fn matches(&self, module: &wasmtime_environ::Module) -> bool {
+        match module.exports.get_index(self.index) {
+            Some((name, entity)) => *entity == self.entity && **name == *self.name,
+            None => false,
+        }
+    }
+}
+
+/// A function export of a [`Module`] which has been looked up by name and
+/// type-checked with [`Module::get_typed_func_export`], with which the
+/// function can be found within instances of the module as a
+/// [`TypedFunc`](crate::TypedFunc) with
+/// [`Instance::get_typed_module_export`](crate::Instance::get_typed_module_export).
+pub struct TypedModuleExport<Params, Results> {
+    pub(crate) export: ModuleExport,
+    /// The function's signature, which stays registered with the engine as
+    /// long as the module it was found in is alive.
+    pub(crate) sig_index: VMSharedSignatureIndex,
+    _module: Module,
+    _ty: marker::PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params, Results> TypedModuleExport<Params, Results> {
+    /// Returns the name of this export.
+    pub fn name(&self) -> &str {
+        self.export.name()
+    }
+}
+
+impl<Params, Results> Clone for TypedModuleExport<Params, Results> {
+    fn clone(&self) -> Self {
+        TypedModuleExport {
+            export: self.export.clone(),
+            sig_index: self.sig_index,
+            _module: self._module.clone(),
+            _ty: marker::PhantomData,
+        }
+    }
+}
+
 impl ModuleInner {
     fn memory_images(&self) -> Result<Option<&ModuleMemoryImages>> {
         let images = self
@@ -863,6 +1691,7 @@ impl std::hash::Hash for HashedEngineCompileEnv<'_> {
         compiler.triple().hash(hasher);
         compiler.flags().hash(hasher);
         compiler.isa_flags().hash(hasher);
+        compiler.is_baseline().hash(hasher);
 
         // Hash configuration state read for compilation
         let config = self.0.config();
@@ -907,10 +1736,60 @@ impl wasmtime_runtime::ModuleRuntimeInfo for ModuleInner {
     fn signature_ids(&self) -> &[VMSharedSignatureIndex] {
         self.signatures.as_module_map().values().as_slice()
     }
+
+    fn shared_globals(&self) -> *const VMGlobalDefinition {
+        match &self.shared_globals {
+            Some(globals) => globals.as_ptr(),
+            None => std::ptr::null(),
+        }
+    }
+
+    fn shared_tables(&self) -> Option<&SharedTables> {
+        self.shared_tables.as_ref()
+    }
+
+    #[cfg(compiler)]
+    fn compile_lazily(&self, index: DefinedFuncIndex) -> Result<usize> {
+        match &self.lazy {
+            Some(lazy) => lazy.compile(&self.engine, &self.module, &self.types, index),
+            None => bail!("function {} isn't compiled lazily", index.as_u32()),
+        }
+    }
+
+    #[cfg(compiler)]
+    fn tier_up(self: Arc<Self>, index: DefinedFuncIndex) {
+        if self.lazy.is_none() {
+            return;
+        }
+
+        // Queued jobs don't keep the module alive, so that they can't keep
+        // its engine alive either.
+        let module = Arc::downgrade(&self);
+        self.engine.background_compiler().spawn(move || {
+            let module = match module.upgrade() {
+                Some(module) => module,
+                None => return,
+            };
+            let lazy = module.lazy.as_ref().unwrap();
+            if let Err(e) = lazy.tier_up(&module.engine, &module.module, &module.types, index) {
+                log::warn!("failed to recompile a hot function: {:?}", e);
+            }
+        });
+    }
 }
 
 impl wasmtime_runtime::ModuleInfo for ModuleInner {
     fn lookup_stack_map(&self, pc: usize) -> Option<&wasmtime_environ::StackMap> {
+        if let Some(lazy) = &self.lazy {
+            if let Some(stack_map) = lazy.lookup_stack_map(pc) {
+                return Some(stack_map);
+            }
+            let code = self.module.code().as_ptr_range();
+            if !code.contains(&(pc as *const u8)) {
+                return None;
+            }
+        }
+
         let text_offset = pc - self.module.code().as_ptr() as usize;
         let (index, func_offset) = self.module.func_by_text_offset(text_offset)?;
         let info = self.module.func_info(index);
@@ -935,9 +1814,38 @@ impl wasmtime_runtime::ModuleInfo for ModuleInner {
     }
 }
 
+impl ModuleInner {
+    fn code_bytes(&self) -> usize {
+        let lazy = self.lazy.as_ref().map_or(0, |lazy| lazy.code_bytes());
+        self.module.code().len() + lazy
+    }
+}
+
 impl Drop for ModuleInner {
     fn drop(&mut self) {
+        let code_bytes = self.code_bytes();
+        if let Some(lazy) = &self.lazy {
+            lazy.unregister();
+        }
         registry::unregister(&self.module);
+        self.engine.metrics_counters().module_dropped(code_bytes);
+    }
+}
+
+/// A handle to a [`Module`] which doesn't keep it alive, created with
+/// [`Module::downgrade`].
+#[derive(Clone)]
+pub struct WeakModule {
+    inner: std::sync::Weak<ModuleInner>,
+}
+
+impl WeakModule {
+    /// Returns the module, or `None` if it was unloaded because nothing uses
+    /// it anymore.
+    pub fn upgrade(&self) -> Option<Module> {
+        Some(Module {
+            inner: self.inner.upgrade()?,
+        })
     }
 }
 
@@ -1035,6 +1943,14 @@ impl wasmtime_runtime::ModuleRuntimeInfo for BareModuleInfo {
             None => &[],
         }
     }
+
+    fn shared_globals(&self) -> *const VMGlobalDefinition {
+        std::ptr::null()
+    }
+
+    fn shared_tables(&self) -> Option<&SharedTables> {
+        None
+    }
 }
 
 /// Helper method to construct a `ModuleMemoryImages` for an associated
@@ -0,0 +1,103 @@
+//! Translating between the compiled code of a module and offsets in its wasm.
+
+use crate::FrameInfo;
+use std::ops::Range;
+use wasmtime_jit::CompiledModule;
+
+/// The map between the machine code compiled for a [`Module`](crate::Module)
+/// and the offsets of the WebAssembly instructions it was compiled from,
+/// returned by [`Module::address_map`](crate::Module::address_map).
+///
+/// This is the same information Wasmtime uses to report where a trap
+/// happened, and it can be used to resolve native program counters captured
+/// outside of Wasmtime, such as by a sampling profiler or a crash handler, as
+/// well as to find the machine code of an instruction, such as to set a
+/// breakpoint on it.
+///
+/// Program counters here are addresses in the memory of the host process.
+/// Return addresses captured from the stack point just after a call
+/// instruction, so the address before them should be looked up to find the
+/// instruction making the call.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let engine = Engine::default();
+/// let module = Module::new(&engine, r#"(module (func $f (result i32) i32.const 1))"#)?;
+/// let map = module.address_map().unwrap();
+///
+/// // Find the first code compiled from an instruction, and translate it back
+/// // to its function and instruction.
+/// let (pc, offset) = map
+///     .entries()
+///     .find_map(|(pc, offset)| Some((pc, offset? as usize)))
+///     .unwrap();
+/// assert!(map.text_range().contains(&pc));
+/// let frame = map.lookup_pc(pc).unwrap();
+/// assert_eq!(frame.func_name(), Some("f"));
+/// assert_eq!(frame.module_offset(), Some(offset));
+/// assert!(map.pcs_for_offset(offset).any(|start| start == pc));
+/// # Ok(())
+/// # }
+/// ```
+pub struct AddressMap<'a> {
+    module: &'a CompiledModule,
+}
+
+impl<'a> AddressMap<'a> {
+    pub(crate) fn new(module: &'a CompiledModule) -> Option<AddressMap<'a>> {
+        if !module.has_address_map() {
+            return None;
+        }
+        Some(AddressMap { module })
+    }
+
+    /// Returns the range of addresses holding the module's compiled code.
+    pub fn text_range(&self) -> Range<usize> {
+        let code = self.module.code();
+        let start = code.as_ptr() as usize;
+        start..start + code.len()
+    }
+
+    /// Returns the function and the offset of the instruction within the
+    /// module which the code at `pc` was compiled from, or `None` if `pc`
+    /// isn't within one of the module's functions.
+    ///
+    /// The [`FrameInfo`] returned is the same as that of a frame of a
+    /// [`Trap`](crate::Trap) at `pc`.
+    pub fn lookup_pc(&self, pc: usize) -> Option<FrameInfo> {
+        let text_offset = pc.checked_sub(self.text_range().start)?;
+        FrameInfo::new(self.module, text_offset)
+    }
+
+    /// Returns the address of each range of code compiled from the
+    /// instruction at `offset` within the module, in increasing order.
+    ///
+    /// The code of an instruction isn't always contiguous, so it may have
+    /// several ranges, and it may not have any if its code was optimized
+    /// away or merged with that of another instruction. Nothing is returned
+    /// if `offset` isn't the start of an instruction.
+    pub fn pcs_for_offset(&self, offset: usize) -> impl Iterator<Item = usize> + 'a {
+        let wasm_offset = u32::try_from(offset).ok();
+        self.entries()
+            .filter(move |(_, entry)| entry.is_some() && *entry == wasm_offset)
+            .map(|(pc, _)| pc)
+    }
+
+    /// Returns every entry of the address map, in increasing order of the
+    /// address at which it starts.
+    ///
+    /// Each entry is an address along with the offset within the module of
+    /// the instruction which the code from there up to the next entry was
+    /// compiled from, or `None` for code which wasn't compiled from any
+    /// particular instruction, such as a function's prologue.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, Option<u32>)> + 'a {
+        let start = self.text_range().start;
+        wasmtime_environ::iterate_address_map(self.module.address_map_data())
+            .into_iter()
+            .flatten()
+            .map(move |(text_offset, pos)| (start + text_offset as usize, pos.file_offset()))
+    }
+}
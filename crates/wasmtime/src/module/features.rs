@@ -0,0 +1,184 @@
+//! Naming the WebAssembly feature behind a validation error.
+
+use std::fmt;
+use wasmparser::BinaryReaderError;
+use wasmtime_environ::WasmError;
+
+/// The error of a module which uses a WebAssembly feature that isn't enabled
+/// in the [`Config`](crate::Config) of the engine compiling or validating it.
+///
+/// Validation reports features which are disabled in a number of ways, some
+/// of them hard to recognize, such as `multiple tables` when a module has
+/// more than one table without the reference types proposal. Those errors
+/// are annotated with this, which can be recovered from the returned
+/// [`anyhow::Error`] with `downcast_ref` to find out which [`feature`] was
+/// used and where, such as to enforce a policy of which features modules may
+/// use.
+///
+/// [`feature`]: DisabledFeatureError::feature
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let mut config = Config::new();
+/// config.wasm_simd(false);
+/// let engine = Engine::new(&config)?;
+/// let wat = r#"(module (func (drop (v128.const i64x2 0 0))))"#;
+/// let error = Module::new(&engine, wat).err().unwrap();
+/// let disabled = error.downcast_ref::<DisabledFeatureError>().unwrap();
+/// assert_eq!(disabled.feature(), "simd");
+/// assert_eq!(disabled.offset(), 0x17);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DisabledFeatureError {
+    feature: &'static str,
+    method: &'static str,
+    offset: usize,
+}
+
+impl DisabledFeatureError {
+    /// Returns the name of the feature which the module used, which is the
+    /// same as its name for the `--wasm-features` option of the `wasmtime`
+    /// command, such as `simd` or `reference-types`.
+    pub fn feature(&self) -> &str {
+        self.feature
+    }
+
+    /// Returns the offset within the module at which the feature was used.
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+}
+
+impl fmt::Display for DisabledFeatureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the WebAssembly `{}` feature is used at offset {:#x} but is not enabled, \
+             see `Config::{}`",
+            self.feature, self.offset, self.method
+        )
+    }
+}
+
+impl std::error::Error for DisabledFeatureError {}
+
+/// The validation errors of each feature which can be disabled, along with
+/// the name of the feature and of the `Config` method enabling it.
+const DISABLED_FEATURE_ERRORS: &[(&str, &str, &str)] = &[
+    (
+        "reference types support is not enabled",
+        "reference-types",
+        "wasm_reference_types",
+    ),
+    (
+        "reference types must be enabled for externref elem segment",
+        "reference-types",
+        "wasm_reference_types",
+    ),
+    (
+        "reference-types not enabled: zero byte expected",
+        "reference-types",
+        "wasm_reference_types",
+    ),
+    (
+        "element is not anyfunc",
+        "reference-types",
+        "wasm_reference_types",
+    ),
+    ("multiple tables", "reference-types", "wasm_reference_types"),
+    ("SIMD support is not enabled", "simd", "wasm_simd"),
+    ("threads support is not enabled", "threads", "wasm_threads"),
+    (
+        "threads must be enabled for shared memories",
+        "threads",
+        "wasm_threads",
+    ),
+    (
+        "bulk memory support is not enabled",
+        "bulk-memory",
+        "wasm_bulk_memory",
+    ),
+    (
+        "bulk memory must be enabled",
+        "bulk-memory",
+        "wasm_bulk_memory",
+    ),
+    (
+        "func type returns multiple values but the multi-value feature is not enabled",
+        "multi-value",
+        "wasm_multi_value",
+    ),
+    (
+        "blocks, loops, and ifs may only produce a resulttype when multi-value is not enabled",
+        "multi-value",
+        "wasm_multi_value",
+    ),
+    (
+        "multi-memory support is not enabled",
+        "multi-memory",
+        "wasm_multi_memory",
+    ),
+    (
+        "multi-memory not enabled: zero byte expected",
+        "multi-memory",
+        "wasm_multi_memory",
+    ),
+    ("multiple memories", "multi-memory", "wasm_multi_memory"),
+    (
+        "memory64 must be enabled for 64-bit memories",
+        "memory64",
+        "wasm_memory64",
+    ),
+    (
+        "saturating float to int conversions support is not enabled",
+        "sat-float-to-int",
+        "wasm_sat_float_to_int",
+    ),
+    (
+        "sign extension operations support is not enabled",
+        "sign-extension",
+        "wasm_sign_extension",
+    ),
+    (
+        "mutable global support is not enabled",
+        "mutable-global",
+        "wasm_mutable_global",
+    ),
+];
+
+/// Annotates `error` with a [`DisabledFeatureError`] if it's the validation
+/// error of a module using a disabled feature.
+pub(crate) fn name_disabled_feature(error: impl Into<anyhow::Error>) -> anyhow::Error {
+    let error = error.into();
+    if error.downcast_ref::<DisabledFeatureError>().is_some() {
+        return error;
+    }
+    let disabled = error.chain().find_map(|e| {
+        let (message, offset) = if let Some(e) = e.downcast_ref::<BinaryReaderError>() {
+            (e.message(), e.offset())
+        } else if let Some(WasmError::InvalidWebAssembly { message, offset }) =
+            e.downcast_ref::<WasmError>()
+        {
+            (message.as_str(), *offset)
+        } else {
+            return None;
+        };
+        DISABLED_FEATURE_ERRORS
+            .iter()
+            .find(|(m, _, _)| *m == message)
+            .map(|&(_, feature, method)| DisabledFeatureError {
+                feature,
+                method,
+                offset,
+            })
+    });
+    match disabled {
+        Some(disabled) => error.context(disabled),
+        None => error,
+    }
+}
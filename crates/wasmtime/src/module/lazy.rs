@@ -0,0 +1,394 @@
+//! Compilation of a module's functions on their first call, for engines
+//! configured with [`Config::lazy_compilation`](crate::Config::lazy_compilation)
+//! or [`Config::tiered_compilation`](crate::Config::tiered_compilation).
+
+// Lazily compiled modules can only be created with a compiler.
+#![cfg_attr(not(compiler), allow(dead_code))]
+
+use super::registry;
+use once_cell::sync::OnceCell;
+use std::ops::Range;
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use wasmparser::{FuncValidator, ValidatorResources};
+use wasmtime_environ::{
+    DefinedFuncIndex, EntityRef, FunctionInfo, FunctionProfile, LazyFunctionTable, PrimaryMap,
+    StackMap,
+};
+use wasmtime_jit::{CodeMemory, CompiledModule};
+
+#[cfg(compiler)]
+use {
+    crate::Engine,
+    anyhow::{Context, Result},
+    wasmparser::{Parser, ValidPayload, Validator},
+    wasmtime_environ::{FunctionBodyData, TypeTables},
+};
+
+/// The functions of a module which are compiled the first time they're
+/// called.
+///
+/// The module's own code only contains a stub for each of its functions, which
+/// loads the address of the function's code from its slot in `code` and calls
+/// it, first asking the module to compile the function if the slot is still
+/// null. Functions compiled lazily call each other through the same slots.
+///
+/// With tiered compilation, functions are first compiled with the baseline
+/// compiler, which counts how hot they are with their counter in `counters`.
+/// Once a function is hot it's recompiled with Cranelift in the background,
+/// and its slot in `code` is updated to point to the recompiled code.
+pub(crate) struct LazyFunctions {
+    /// The original wasm module, which function bodies are compiled from.
+    wasm: Box<[u8]>,
+    /// Whether 64-bit memory immediates are allowed in function bodies.
+    memory64: bool,
+    bodies: PrimaryMap<DefinedFuncIndex, LazyBody>,
+    /// The address of each function's compiled code, or zero if it hasn't been
+    /// compiled yet.
+    code: Box<[AtomicUsize]>,
+    compiled: PrimaryMap<DefinedFuncIndex, OnceCell<Arc<LazyFunction>>>,
+    /// Where lazily compiled functions find the code of the module's other
+    /// functions, created when the first function is compiled.
+    table: OnceCell<LazyFunctionTable>,
+    /// The hotness counter of each function, with tiered compilation.
+    counters: Option<Box<[AtomicU32]>>,
+    /// The code of each function recompiled once it was hot.
+    optimized: PrimaryMap<DefinedFuncIndex, OnceCell<Arc<LazyFunction>>>,
+    /// The validators of the bodies of functions to recompile once they're
+    /// hot, since the original ones are used up by their first compilation.
+    /// They're created by validating the module again on the first tier-up.
+    tier_up_validators:
+        OnceCell<PrimaryMap<DefinedFuncIndex, Mutex<Option<FuncValidator<ValidatorResources>>>>>,
+    /// The profile counters of the module's functions, when collecting a
+    /// profile.
+    profile: Option<ProfileCounters>,
+}
+
+/// The profile counters of all of a module's functions, of which each
+/// function has the range in `functions`.
+struct ProfileCounters {
+    counters: Box<[AtomicU64]>,
+    functions: PrimaryMap<DefinedFuncIndex, Range<usize>>,
+}
+
+struct LazyBody {
+    /// The range of the function's body within the original wasm module.
+    range: Range<usize>,
+    /// The validator of the body, which is taken to compile the function.
+    validator: Mutex<Option<FuncValidator<ValidatorResources>>>,
+}
+
+/// The code of a function compiled on its first call, which is placed in its
+/// own text section outside of its module's code.
+pub(crate) struct LazyFunction {
+    index: DefinedFuncIndex,
+    info: FunctionInfo,
+    code_memory: CodeMemory,
+    text: Range<usize>,
+    trap_data: Range<usize>,
+    address_map_data: Range<usize>,
+}
+
+impl LazyFunctions {
+    /// Creates the lazy state of the module `wasm`, whose function bodies are
+    /// `functions`.
+    ///
+    /// With tiered compilation, functions are recompiled after
+    /// `tier_up_threshold` calls and loop iterations. With `collect_profile`
+    /// they're compiled to count their calls and branches.
+    #[cfg(compiler)]
+    pub(crate) fn new(
+        wasm: &[u8],
+        functions: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'_>>,
+        memory64: bool,
+        tier_up_threshold: Option<u32>,
+        collect_profile: bool,
+    ) -> Result<LazyFunctions> {
+        let profile = match collect_profile {
+            true => {
+                let mut len = 0;
+                let mut ranges = PrimaryMap::new();
+                for data in functions.values() {
+                    let branches = wasmtime_environ::count_profiled_branches(&data.body)?;
+                    let start = len;
+                    len += FunctionProfile::num_counters(branches);
+                    ranges.push(start..len);
+                }
+                Some(ProfileCounters {
+                    counters: (0..len).map(|_| AtomicU64::new(0)).collect(),
+                    functions: ranges,
+                })
+            }
+            false => None,
+        };
+        let bodies = functions
+            .into_iter()
+            .map(|(_, data)| {
+                let reader = data.body.get_binary_reader();
+                let start = reader.original_position();
+                LazyBody {
+                    range: start..start + reader.bytes_remaining(),
+                    validator: Mutex::new(Some(data.validator)),
+                }
+            })
+            .collect::<PrimaryMap<_, _>>();
+        Ok(LazyFunctions {
+            wasm: wasm.into(),
+            memory64,
+            code: bodies.iter().map(|_| AtomicUsize::new(0)).collect(),
+            compiled: bodies.iter().map(|_| Default::default()).collect(),
+            counters: tier_up_threshold.map(|threshold| {
+                let threshold = threshold.max(1);
+                bodies.iter().map(|_| AtomicU32::new(threshold)).collect()
+            }),
+            optimized: bodies.iter().map(|_| Default::default()).collect(),
+            bodies,
+            table: Default::default(),
+            tier_up_validators: Default::default(),
+            profile,
+        })
+    }
+
+    /// Returns the profile of each function collected so far, if the
+    /// functions are compiled to collect one.
+    pub(crate) fn profile(&self) -> Option<PrimaryMap<DefinedFuncIndex, FunctionProfile>> {
+        let profile = self.profile.as_ref()?;
+        Some(
+            profile
+                .functions
+                .values()
+                .map(|range| {
+                    let counters = profile.counters[range.clone()]
+                        .iter()
+                        .map(|c| c.load(Ordering::Relaxed))
+                        .collect::<Vec<_>>();
+                    FunctionProfile::from_counters(&counters)
+                })
+                .collect(),
+        )
+    }
+
+    /// Returns the address of the slot holding the address of the compiled
+    /// code of the function `index`.
+    pub(crate) fn code_slot(&self, index: DefinedFuncIndex) -> usize {
+        &self.code[index.index()] as *const AtomicUsize as usize
+    }
+
+    /// Compiles the function `index` of `module`, if it hasn't been already,
+    /// returning the address of its code.
+    #[cfg(compiler)]
+    pub(crate) fn compile(
+        &self,
+        engine: &Engine,
+        module: &Arc<CompiledModule>,
+        types: &TypeTables,
+        index: DefinedFuncIndex,
+    ) -> Result<usize> {
+        let func = self.compiled[index].get_or_try_init(|| -> Result<_> {
+            let validator = self.bodies[index].validator.lock().unwrap().take();
+            let func = self.compile_function(engine, module, types, index, validator, false)?;
+            Ok(self.publish(engine, module, index, func))
+        })?;
+        Ok(func.address())
+    }
+
+    /// Recompiles the function `index` of `module`, which became hot, with
+    /// the optimizing tier, if it hasn't been already.
+    #[cfg(compiler)]
+    pub(crate) fn tier_up(
+        &self,
+        engine: &Engine,
+        module: &Arc<CompiledModule>,
+        types: &TypeTables,
+        index: DefinedFuncIndex,
+    ) -> Result<()> {
+        self.optimized[index].get_or_try_init(|| -> Result<_> {
+            let validators = self.tier_up_validators.get_or_try_init(|| -> Result<_> {
+                let mut validator = Validator::new_with_features(engine.config().features);
+                let mut validators = PrimaryMap::new();
+                for payload in Parser::new(0).parse_all(&self.wasm) {
+                    if let ValidPayload::Func(func, _) = validator.payload(&payload?)? {
+                        validators.push(Mutex::new(Some(func)));
+                    }
+                }
+                Ok(validators)
+            })?;
+            let validator = validators[index].lock().unwrap().take();
+            let func = self.compile_function(engine, module, types, index, validator, true)?;
+            engine.metrics_counters().function_tiered_up();
+            Ok(self.publish(engine, module, index, func))
+        })?;
+        Ok(())
+    }
+
+    /// Registers `func`, the newly compiled code of the function `index`, and
+    /// points the function's slot in `code` to it.
+    #[cfg(compiler)]
+    fn publish(
+        &self,
+        engine: &Engine,
+        module: &Arc<CompiledModule>,
+        index: DefinedFuncIndex,
+        func: LazyFunction,
+    ) -> Arc<LazyFunction> {
+        let func = Arc::new(func);
+        registry::register_lazy(engine, module, &func);
+        engine.metrics_counters().code_compiled(func.text().len());
+        self.code[index.index()].store(func.address(), Ordering::Release);
+        func
+    }
+
+    #[cfg(compiler)]
+    fn compile_function(
+        &self,
+        engine: &Engine,
+        module: &Arc<CompiledModule>,
+        types: &TypeTables,
+        index: DefinedFuncIndex,
+        validator: Option<FuncValidator<ValidatorResources>>,
+        optimize: bool,
+    ) -> Result<LazyFunction> {
+        let body = &self.bodies[index];
+        let validator = validator.context("function previously failed to compile")?;
+        let mut data = FunctionBodyData {
+            body: wasmparser::FunctionBody::new(body.range.start, &self.wasm[body.range.clone()]),
+            validator,
+        };
+        data.body.allow_memarg64(self.memory64);
+        let table = self.table.get_or_init(|| LazyFunctionTable {
+            code: self.code.as_ptr() as usize,
+            stubs: module
+                .finished_functions()
+                .map(|(_, stub)| stub as *const u8 as usize)
+                .collect(),
+            counters: self
+                .counters
+                .as_ref()
+                .map(|counters| counters.as_ptr() as usize),
+            profile: self.profile.as_ref().map(|profile| {
+                let base = profile.counters.as_ptr() as usize;
+                profile
+                    .functions
+                    .values()
+                    .map(|range| base + range.start * 8)
+                    .collect()
+            }),
+        });
+
+        let compiler = engine.compiler();
+        let tunables = &engine.config().tunables;
+        let func = compiler
+            .compile_lazy_function(
+                module.module(),
+                index,
+                module.func_name(module.module().func_index(index)),
+                data,
+                tunables,
+                types,
+                table,
+                optimize,
+            )
+            .with_context(|| format!("failed to compile function {}", index.as_u32()))?;
+        let mut obj = compiler.object()?;
+        let info =
+            compiler.emit_lazy_function_obj(module.module(), index, func, tunables, &mut obj)?;
+        let mmap = wasmtime_jit::mmap_vec_from_obj(obj)?;
+
+        // Copy the function into executable memory of its own, which also
+        // registers its unwind information.
+        let mut code_memory = CodeMemory::new(mmap);
+        let code = code_memory.publish()?;
+        crate::trampoline::register_trampolines(engine.config().profiler.as_ref(), &code.obj);
+
+        let section = |name: &str| {
+            use object::{Object, ObjectSection};
+            code.obj
+                .section_by_name(name)
+                .and_then(|s| s.data().ok())
+                .map(|data| wasmtime_jit::subslice_range(data, code.mmap))
+                .unwrap_or(0..0)
+        };
+        let trap_data = section(wasmtime_environ::ELF_WASMTIME_TRAPS);
+        let address_map_data = section(wasmtime_environ::ELF_WASMTIME_ADDRMAP);
+        let text = wasmtime_jit::subslice_range(code.text, code.mmap);
+        Ok(LazyFunction {
+            index,
+            info,
+            code_memory,
+            text,
+            trap_data,
+            address_map_data,
+        })
+    }
+
+    /// Returns the total size of the code of the functions compiled so far.
+    pub(crate) fn code_bytes(&self) -> usize {
+        self.compiled_functions().map(|f| f.text().len()).sum()
+    }
+
+    /// Looks up the stack map at `pc`, if it's within a lazily compiled
+    /// function of this module.
+    pub(crate) fn lookup_stack_map(&self, pc: usize) -> Option<&StackMap> {
+        let index = registry::lazy_function_index(pc)?;
+        let func = [&self.compiled, &self.optimized]
+            .iter()
+            .filter_map(|tier| tier.get(index)?.get())
+            .find(|func| func.text().as_ptr_range().contains(&(pc as *const u8)))?;
+        let offset = u32::try_from(pc - func.address()).ok()?;
+        let stack_maps = &func.info.stack_maps;
+        let i = stack_maps
+            .binary_search_by_key(&offset, |i| i.code_offset)
+            .ok()?;
+        Some(&stack_maps[i].stack_map)
+    }
+
+    /// Removes the functions compiled so far from the global registry of
+    /// code, once their module is dropped.
+    pub(crate) fn unregister(&self) {
+        for func in self.compiled_functions() {
+            registry::unregister_lazy(func);
+        }
+    }
+
+    fn compiled_functions(&self) -> impl Iterator<Item = &Arc<LazyFunction>> {
+        self.compiled
+            .values()
+            .chain(self.optimized.values())
+            .filter_map(|f| f.get())
+    }
+}
+
+impl LazyFunction {
+    /// The index of this function within its module.
+    pub(crate) fn index(&self) -> DefinedFuncIndex {
+        self.index
+    }
+
+    /// The description of this function, relative to the start of its text
+    /// section.
+    pub(crate) fn info(&self) -> &FunctionInfo {
+        &self.info
+    }
+
+    /// The text section containing this function's code.
+    pub(crate) fn text(&self) -> &[u8] {
+        &self.code_memory.mmap()[self.text.clone()]
+    }
+
+    /// The encoded trap information of this function, relative to the start of
+    /// its text section.
+    pub(crate) fn trap_data(&self) -> &[u8] {
+        &self.code_memory.mmap()[self.trap_data.clone()]
+    }
+
+    /// The encoded address map of this function, relative to the start of its
+    /// text section.
+    pub(crate) fn address_map_data(&self) -> &[u8] {
+        &self.code_memory.mmap()[self.address_map_data.clone()]
+    }
+
+    /// The address of the start of this function's code.
+    fn address(&self) -> usize {
+        self.text().as_ptr() as usize + self.info.start as usize
+    }
+}
@@ -1,13 +1,15 @@
 //! Implements a registry of modules for a store.
 
+use super::lazy::LazyFunction;
 use crate::{Engine, Module};
 use std::{
     collections::BTreeMap,
+    ops::Range,
     sync::{Arc, RwLock},
 };
-use wasmtime_environ::{EntityRef, FilePos, TrapCode};
+use wasmtime_environ::{DefinedFuncIndex, EntityRef, FilePos, FunctionInfo, TrapCode};
 use wasmtime_jit::CompiledModule;
-use wasmtime_runtime::{ModuleInfo, VMCallerCheckedAnyfunc, VMTrampoline};
+use wasmtime_runtime::{ModuleInfo, VMCallerCheckedAnyfunc, VMNativeTrampoline, VMTrampoline};
 
 lazy_static::lazy_static! {
     static ref GLOBAL_MODULES: RwLock<GlobalModuleRegistry> = Default::default();
@@ -42,11 +44,16 @@ impl ModuleRegistry {
     }
 
     fn module(&self, pc: usize) -> Option<&Module> {
-        let (end, module) = self.modules_with_code.range(pc..).next()?;
-        if pc < start(module) || *end < pc {
-            return None;
+        if let Some((end, module)) = self.modules_with_code.range(pc..).next() {
+            if start(module) <= pc && pc <= *end {
+                return Some(module);
+            }
         }
-        Some(module)
+
+        // Functions compiled lazily are placed outside of their module's code,
+        // so find the module they belong to through the global registry.
+        let end = GlobalModuleRegistry::with(|modules| modules.lazy_function_module_end(pc))?;
+        self.modules_with_code.get(&end)
     }
 
     /// Registers a new module with the registry.
@@ -90,11 +97,29 @@ impl ModuleRegistry {
         assert!(prev.is_none());
     }
 
+    /// Fetches frame information about a program counter in a backtrace,
+    /// along with the module that the program counter belongs to.
+    pub fn lookup_frame_info(&self, pc: usize) -> Option<(FrameInfo, &Module)> {
+        let module = self.module(pc)?;
+        let (info, _, _) = GlobalModuleRegistry::with(|modules| modules.lookup_frame_info(pc))?;
+        Some((info, module))
+    }
+
     /// Looks up a trampoline from an anyfunc.
     pub fn lookup_trampoline(&self, anyfunc: &VMCallerCheckedAnyfunc) -> Option<VMTrampoline> {
         let module = self.module(anyfunc.func_ptr.as_ptr() as usize)?;
         module.signatures().trampoline(anyfunc.type_index)
     }
+
+    /// Looks up the trampoline taking arguments natively from an anyfunc, if
+    /// it's a wasm function and its module has one.
+    pub fn lookup_native_trampoline(
+        &self,
+        anyfunc: &VMCallerCheckedAnyfunc,
+    ) -> Option<VMNativeTrampoline> {
+        let module = self.module(anyfunc.func_ptr.as_ptr() as usize)?;
+        module.signatures().native_trampoline(anyfunc.type_index)
+    }
 }
 
 // Counterpart to `RegisteredModule`, but stored in the global registry.
@@ -102,6 +127,12 @@ struct GlobalRegisteredModule {
     start: usize,
     module: Arc<CompiledModule>,
     wasm_backtrace_details_env_used: bool,
+    /// Whether the module's functions are compiled lazily, in which case its
+    /// own code only contains their stubs.
+    lazy: bool,
+    /// The function of `module` this code belongs to, if it was compiled
+    /// lazily.
+    lazy_function: Option<Arc<LazyFunction>>,
 }
 
 /// This is the global module registry that stores information for all modules
@@ -124,12 +155,11 @@ impl GlobalModuleRegistry {
     /// Returns whether the `pc`, according to globally registered information,
     /// is a wasm trap or not.
     pub(crate) fn is_wasm_trap_pc(pc: usize) -> bool {
-        let (module, text_offset) = match GLOBAL_MODULES.read().unwrap().module(pc) {
-            Some((module, offset)) => (module.module.clone(), offset),
-            None => return false,
-        };
-
-        wasmtime_environ::lookup_trap_code(module.trap_data(), text_offset).is_some()
+        GLOBAL_MODULES
+            .read()
+            .unwrap()
+            .lookup_trap_code(pc)
+            .is_some()
     }
 
     /// Returns, if found, the corresponding module for the `pc` as well as the
@@ -142,6 +172,18 @@ impl GlobalModuleRegistry {
         Some((info, pc - info.start))
     }
 
+    /// Returns the address ranges of the compiled code of every module
+    /// currently registered, in ascending order.
+    pub(crate) fn code_ranges() -> Vec<Range<usize>> {
+        GLOBAL_MODULES
+            .read()
+            .unwrap()
+            .0
+            .iter()
+            .map(|(end, info)| info.start..*end + 1)
+            .collect()
+    }
+
     // Work with the global instance of `GlobalModuleRegistry`. Note that only
     // shared access is allowed, this isn't intended to mutate the contents.
     pub(crate) fn with<R>(f: impl FnOnce(&GlobalModuleRegistry) -> R) -> R {
@@ -170,8 +212,29 @@ impl GlobalModuleRegistry {
     /// Fetches trap information about a program counter in a backtrace.
     pub(crate) fn lookup_trap_code(&self, pc: usize) -> Option<TrapCode> {
         let (module, offset) = self.module(pc)?;
-        wasmtime_environ::lookup_trap_code(module.module.trap_data(), offset)
+        let trap_data = match &module.lazy_function {
+            Some(func) => func.trap_data(),
+            None => module.module.trap_data(),
+        };
+        wasmtime_environ::lookup_trap_code(trap_data, offset)
     }
+
+    /// Returns the end address, as registered, of the code of the module which
+    /// the lazily compiled function at `pc` belongs to.
+    fn lazy_function_module_end(&self, pc: usize) -> Option<usize> {
+        let (module, _) = self.module(pc)?;
+        module.lazy_function.as_ref()?;
+        let code = module.module.code();
+        Some(code.as_ptr() as usize + code.len() - 1)
+    }
+}
+
+/// Returns the index, within its module, of the lazily compiled function at
+/// `pc`.
+pub(crate) fn lazy_function_index(pc: usize) -> Option<DefinedFuncIndex> {
+    let modules = GLOBAL_MODULES.read().unwrap();
+    let (module, _) = modules.module(pc)?;
+    Some(module.lazy_function.as_ref()?.index())
 }
 
 /// Registers a new region of code.
@@ -182,7 +245,10 @@ impl GlobalModuleRegistry {
 /// This is required to enable traps to work correctly since the signal handler
 /// will lookup in the `GLOBAL_MODULES` list to determine which a particular pc
 /// is a trap or not.
-pub fn register(engine: &Engine, module: &Arc<CompiledModule>) {
+///
+/// If `lazy` is set then the module's functions are compiled lazily, and its
+/// code only contains their stubs.
+pub fn register(engine: &Engine, module: &Arc<CompiledModule>, lazy: bool) {
     let code = module.code();
     if code.is_empty() {
         return;
@@ -193,11 +259,44 @@ pub fn register(engine: &Engine, module: &Arc<CompiledModule>) {
         start,
         wasm_backtrace_details_env_used: engine.config().wasm_backtrace_details_env_used,
         module: module.clone(),
+        lazy,
+        lazy_function: None,
     };
     let prev = GLOBAL_MODULES.write().unwrap().0.insert(end, module);
     assert!(prev.is_none());
 }
 
+/// Registers the code of `func`, a function of `module` which was compiled
+/// lazily.
+///
+/// Must be `unregister_lazy`'d before `module` is unregistered.
+pub(crate) fn register_lazy(
+    engine: &Engine,
+    module: &Arc<CompiledModule>,
+    func: &Arc<LazyFunction>,
+) {
+    let text = func.text();
+    let start = text.as_ptr() as usize;
+    let end = start + text.len() - 1;
+    let func = GlobalRegisteredModule {
+        start,
+        wasm_backtrace_details_env_used: engine.config().wasm_backtrace_details_env_used,
+        module: module.clone(),
+        lazy: true,
+        lazy_function: Some(func.clone()),
+    };
+    let prev = GLOBAL_MODULES.write().unwrap().0.insert(end, func);
+    assert!(prev.is_none());
+}
+
+/// Unregisters a lazily compiled function from the global map.
+pub(crate) fn unregister_lazy(func: &LazyFunction) {
+    let text = func.text();
+    let end = (text.as_ptr() as usize) + text.len() - 1;
+    let func = GLOBAL_MODULES.write().unwrap().0.remove(&end);
+    assert!(func.is_some());
+}
+
 /// Unregisters a module from the global map.
 ///
 /// Must hae been previously registered with `register`.
@@ -222,9 +321,42 @@ impl GlobalRegisteredModule {
     /// Returns an object if this `pc` is known to this module, or returns `None`
     /// if no information can be found.
     pub fn lookup_frame_info(&self, text_offset: usize) -> Option<FrameInfo> {
-        let (index, _func_offset) = self.module.func_by_text_offset(text_offset)?;
-        let info = self.module.func_info(index);
-        let instr = wasmtime_environ::lookup_file_pos(self.module.address_map_data(), text_offset);
+        match &self.lazy_function {
+            Some(func) => Some(FrameInfo::new_lazy(&self.module, func, text_offset)),
+            // The code of a lazily compiled module only contains stubs which
+            // call its functions, so its frames are omitted.
+            None if self.lazy => None,
+            None => FrameInfo::new(&self.module, text_offset),
+        }
+    }
+}
+
+/// Description of a frame in a backtrace for a [`Trap`].
+///
+/// Whenever a WebAssembly trap occurs an instance of [`Trap`] is created. Each
+/// [`Trap`] has a backtrace of the WebAssembly frames that led to the trap, and
+/// each frame is described by this structure.
+///
+/// [`Trap`]: crate::Trap
+#[derive(Debug)]
+pub struct FrameInfo {
+    module_name: Option<String>,
+    func_index: u32,
+    func_name: Option<String>,
+    func_start: FilePos,
+    instr: Option<FilePos>,
+    symbols: Vec<FrameSymbol>,
+}
+
+impl FrameInfo {
+    /// Fetches frame information about a program counter in a backtrace.
+    ///
+    /// Returns an object if this `text_offset` is known to the `module`, or
+    /// returns `None` if no information can be found.
+    pub(super) fn new(module: &CompiledModule, text_offset: usize) -> Option<FrameInfo> {
+        let (index, _func_offset) = module.func_by_text_offset(text_offset)?;
+        let info = module.func_info(index);
+        let instr = wasmtime_environ::lookup_file_pos(module.address_map_data(), text_offset);
 
         // In debug mode for now assert that we found a mapping for `pc` within
         // the function, because otherwise something is buggy along the way and
@@ -234,11 +366,27 @@ impl GlobalRegisteredModule {
         // Note that if the module doesn't even have an address map due to
         // compilation settings then it's expected that `instr` is `None`.
         debug_assert!(
-            instr.is_some() || !self.module.has_address_map(),
+            instr.is_some() || !module.has_address_map(),
             "failed to find instruction for {:#x}",
             text_offset
         );
 
+        Some(FrameInfo::for_function(module, index, info, instr))
+    }
+
+    /// Same as `new`, but for a function of `module` which was compiled lazily
+    /// and `text_offset` is relative to its own text section.
+    fn new_lazy(module: &CompiledModule, func: &LazyFunction, text_offset: usize) -> FrameInfo {
+        let instr = wasmtime_environ::lookup_file_pos(func.address_map_data(), text_offset);
+        FrameInfo::for_function(module, func.index(), func.info(), instr)
+    }
+
+    fn for_function(
+        module: &CompiledModule,
+        index: DefinedFuncIndex,
+        info: &FunctionInfo,
+        instr: Option<FilePos>,
+    ) -> FrameInfo {
         // Use our wasm-relative pc to symbolize this frame. If there's a
         // symbolication context (dwarf debug info) available then we can try to
         // look this up there.
@@ -249,7 +397,7 @@ impl GlobalRegisteredModule {
         // custom section contents.
         let mut symbols = Vec::new();
 
-        if let Some(s) = &self.module.symbolize_context().ok().and_then(|c| c) {
+        if let Some(s) = &module.symbolize_context().ok().and_then(|c| c) {
             if let Some(offset) = instr.and_then(|i| i.file_offset()) {
                 let to_lookup = u64::from(offset) - s.code_section_offset();
                 if let Ok(mut frames) = s.addr2line().find_frames(to_lookup) {
@@ -273,38 +421,35 @@ impl GlobalRegisteredModule {
             }
         }
 
-        let module = self.module.module();
-        let index = module.func_index(index);
+        let env_module = module.module();
+        let index = env_module.func_index(index);
 
-        Some(FrameInfo {
-            module_name: module.name.clone(),
+        FrameInfo {
+            module_name: env_module.name.clone(),
             func_index: index.index() as u32,
-            func_name: self.module.func_name(index).map(|s| s.to_string()),
+            func_name: module.func_name(index).map(|s| s.to_string()),
             instr,
             func_start: info.start_srcloc,
             symbols,
-        })
+        }
     }
-}
 
-/// Description of a frame in a backtrace for a [`Trap`].
-///
-/// Whenever a WebAssembly trap occurs an instance of [`Trap`] is created. Each
-/// [`Trap`] has a backtrace of the WebAssembly frames that led to the trap, and
-/// each frame is described by this structure.
-///
-/// [`Trap`]: crate::Trap
-#[derive(Debug)]
-pub struct FrameInfo {
-    module_name: Option<String>,
-    func_index: u32,
-    func_name: Option<String>,
-    func_start: FilePos,
-    instr: Option<FilePos>,
-    symbols: Vec<FrameSymbol>,
-}
+    /// Looks up the WebAssembly function and instruction which the code at
+    /// `pc`, an address in the host process, was compiled from, in any module
+    /// which is currently loaded.
+    ///
+    /// This resolves program counters captured outside of Wasmtime, such as by
+    /// a sampling profiler or a crash handler, without needing to know which
+    /// module they belong to. Resolving them takes a lock shared with loading
+    /// and dropping modules. Use
+    /// [`Module::address_map`](crate::Module::address_map) to look up program
+    /// counters within a particular module instead.
+    ///
+    /// Returns `None` if `pc` isn't within the code of a WebAssembly function.
+    pub fn from_pc(pc: usize) -> Option<FrameInfo> {
+        GlobalModuleRegistry::with(|modules| modules.lookup_frame_info(pc)).map(|(info, _, _)| info)
+    }
 
-impl FrameInfo {
     /// Returns the WebAssembly function index for this frame.
     ///
     /// This function index is the index in the function index space of the
@@ -344,6 +489,25 @@ impl FrameInfo {
         self.func_name.as_deref()
     }
 
+    /// Returns the name of this frame's function as `module!function`, the
+    /// same name as that of the function in trap backtraces, in profiles
+    /// recorded with a [`ProfilingStrategy`](crate::ProfilingStrategy) or a
+    /// [`GuestProfiler`](crate::GuestProfiler), in the debug information of
+    /// the module and in `wasmtime explore`.
+    ///
+    /// The module is named by [`FrameInfo::module_name`], or `<unknown>`
+    /// without a name, and the function by the name of its first debug
+    /// symbol, [`FrameInfo::func_name`] or `<wasm function N>` otherwise, any
+    /// of which is demangled if it's a mangled Rust or C++ name.
+    pub fn symbol_name(&self) -> String {
+        let func_name = self
+            .symbols
+            .first()
+            .and_then(|s| s.name())
+            .or_else(|| self.func_name());
+        wasmtime_environ::symbol_name(self.module_name(), func_name, self.func_index() as usize)
+    }
+
     /// Returns the offset within the original wasm module this frame's program
     /// counter was at.
     ///
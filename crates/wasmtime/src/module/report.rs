@@ -0,0 +1,170 @@
+//! Reporting the size and compilation cost of each function of a module.
+
+use std::fmt;
+use std::time::Duration;
+use wasmtime_jit::CompiledModule;
+
+/// A report of what it cost to compile each function of a
+/// [`Module`](crate::Module), returned by
+/// [`Module::compilation_report`](crate::Module::compilation_report).
+///
+/// This can be used to find the functions of a module which are responsible
+/// for most of its code size or most of the time it takes to compile.
+#[derive(Clone, Debug)]
+pub struct CompilationReport {
+    functions: Vec<FunctionReport>,
+}
+
+/// What it cost to compile one function of a module, within a
+/// [`CompilationReport`].
+#[derive(Clone, Debug)]
+pub struct FunctionReport {
+    index: u32,
+    name: Option<String>,
+    wasm_size: u32,
+    code_size: u32,
+    frame_size: u32,
+    compile_time: Option<Duration>,
+}
+
+impl CompilationReport {
+    pub(crate) fn new(module: &CompiledModule) -> CompilationReport {
+        let env_module = module.module();
+        let functions = env_module
+            .functions
+            .keys()
+            .filter_map(|index| {
+                let defined = env_module.defined_func_index(index)?;
+                let info = module.func_info(defined);
+                Some(FunctionReport {
+                    index: index.as_u32(),
+                    name: module.func_name(index).map(|s| s.to_string()),
+                    wasm_size: info.wasm_size,
+                    code_size: info.length,
+                    frame_size: info.frame_size,
+                    compile_time: info.compile_time,
+                })
+            })
+            .collect();
+        CompilationReport { functions }
+    }
+
+    /// Returns the report of each function defined by the module, in the
+    /// order of their indices.
+    pub fn functions(&self) -> &[FunctionReport] {
+        &self.functions
+    }
+
+    /// Returns the total size of the bodies of the module's functions in the
+    /// wasm module, in bytes.
+    pub fn total_wasm_size(&self) -> u64 {
+        self.functions.iter().map(|f| u64::from(f.wasm_size)).sum()
+    }
+
+    /// Returns the total size of the machine code generated for the module's
+    /// functions, in bytes.
+    pub fn total_code_size(&self) -> u64 {
+        self.functions.iter().map(|f| u64::from(f.code_size)).sum()
+    }
+
+    /// Returns the total time spent compiling the module's functions, or
+    /// `None` if the module wasn't compiled in this process.
+    ///
+    /// Functions are compiled on several threads at once with
+    /// [`Config::parallel_compilation`](crate::Config::parallel_compilation),
+    /// so this can be longer than it took to compile the module.
+    pub fn total_compile_time(&self) -> Option<Duration> {
+        self.functions.iter().map(|f| f.compile_time).sum()
+    }
+
+    /// Returns the size of the largest stack frame of any of the module's
+    /// functions, in bytes.
+    pub fn max_frame_size(&self) -> u32 {
+        self.functions
+            .iter()
+            .map(|f| f.frame_size)
+            .max()
+            .unwrap_or(0)
+    }
+}
+
+impl FunctionReport {
+    /// Returns the index of the function within its module.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the name of the function from its module's name section, if it
+    /// has one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Returns the size of the function's body in the wasm module, in bytes.
+    pub fn wasm_size(&self) -> u32 {
+        self.wasm_size
+    }
+
+    /// Returns the size of the machine code generated for the function, in
+    /// bytes.
+    pub fn code_size(&self) -> u32 {
+        self.code_size
+    }
+
+    /// Returns the size of the function's stack frame, in bytes, not counting
+    /// its return address and the registers it saves.
+    pub fn frame_size(&self) -> u32 {
+        self.frame_size
+    }
+
+    /// Returns how long it took to compile the function, or `None` if its
+    /// module wasn't compiled in this process, such as when it was loaded
+    /// with [`Module::deserialize`](crate::Module::deserialize) or from the
+    /// compilation cache.
+    pub fn compile_time(&self) -> Option<Duration> {
+        self.compile_time
+    }
+}
+
+impl fmt::Display for CompilationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:>8} {:>10} {:>10} {:>8} {:>12}  name",
+            "index", "wasm size", "code size", "frame", "compile time"
+        )?;
+        for func in &self.functions {
+            writeln!(
+                f,
+                "{:>8} {:>10} {:>10} {:>8} {:>12?}  {}",
+                func.index,
+                func.wasm_size,
+                func.code_size,
+                func.frame_size,
+                CompileTime(func.compile_time),
+                func.name().unwrap_or("<unnamed>"),
+            )?;
+        }
+        writeln!(
+            f,
+            "{:>8} {:>10} {:>10} {:>8} {:>12?}",
+            "total",
+            self.total_wasm_size(),
+            self.total_code_size(),
+            self.max_frame_size(),
+            CompileTime(self.total_compile_time()),
+        )
+    }
+}
+
+/// Displays a compile time which may not be known.
+struct CompileTime(Option<Duration>);
+
+impl fmt::Debug for CompileTime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Some(time) => time.fmt(f),
+            None => f.pad("-"),
+        }
+    }
+}
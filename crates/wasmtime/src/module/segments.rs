@@ -0,0 +1,250 @@
+//! Introspection of a module's active data and element segments, and the
+//! error reported when one of them doesn't fit while instantiating it.
+
+use std::fmt;
+use wasmtime_environ::{ActiveSegment, WASM_PAGE_SIZE};
+use wasmtime_runtime::SegmentIndex;
+
+/// Whether a segment is a data segment or an element segment.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SegmentKind {
+    /// A data segment, which is written to a linear memory.
+    Data,
+    /// An element segment, which is written to a table.
+    Element,
+}
+
+impl fmt::Display for SegmentKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SegmentKind::Data => f.write_str("data"),
+            SegmentKind::Element => f.write_str("element"),
+        }
+    }
+}
+
+/// An active data or element segment of a [`Module`](crate::Module), which is
+/// written to a memory or table when the module is instantiated.
+///
+/// Segments are returned by
+/// [`Module::initializers`](crate::Module::initializers) in the order they're
+/// written, so that an embedder can check them against the sizes of the
+/// memories and tables it's going to provide before instantiating.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentInitializer {
+    kind: SegmentKind,
+    index: u32,
+    target: u32,
+    global_base: Option<u32>,
+    offset: u64,
+    len: u64,
+    minimum: u64,
+}
+
+impl SegmentInitializer {
+    pub(crate) fn from_wasmtime(
+        module: &wasmtime_environ::Module,
+        segment: &ActiveSegment,
+    ) -> SegmentInitializer {
+        match *segment {
+            ActiveSegment::Data {
+                index,
+                memory_index,
+                base,
+                offset,
+                len,
+            } => SegmentInitializer {
+                kind: SegmentKind::Data,
+                index: index.as_u32(),
+                target: memory_index.as_u32(),
+                global_base: base.map(|g| g.as_u32()),
+                offset,
+                len: u64::from(len),
+                minimum: module.memory_plans[memory_index]
+                    .memory
+                    .minimum
+                    .saturating_mul(u64::from(WASM_PAGE_SIZE)),
+            },
+            ActiveSegment::Elem {
+                index,
+                table_index,
+                base,
+                offset,
+                len,
+            } => SegmentInitializer {
+                kind: SegmentKind::Element,
+                index: index.as_u32(),
+                target: table_index.as_u32(),
+                global_base: base.map(|g| g.as_u32()),
+                offset: u64::from(offset),
+                len: u64::from(len),
+                minimum: u64::from(module.table_plans[table_index].table.minimum),
+            },
+        }
+    }
+
+    /// Returns whether this is a data or element segment.
+    pub fn kind(&self) -> SegmentKind {
+        self.kind
+    }
+
+    /// Returns the index of this segment within the module's data or element
+    /// segments.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the index of the memory or table this segment is written to.
+    pub fn target(&self) -> u32 {
+        self.target
+    }
+
+    /// Returns the index of the global whose value is added to
+    /// [`offset`](SegmentInitializer::offset) to get where this segment is
+    /// written, if there is one.
+    pub fn global_base(&self) -> Option<u32> {
+        self.global_base
+    }
+
+    /// Returns the offset in bytes, or index in elements, this segment is
+    /// written at, not including its global base.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the number of bytes or elements in this segment.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether this segment is empty, which still doesn't fit if its
+    /// offset is beyond the end of its memory or table.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns whether this segment fits in a memory of `size` bytes, or a
+    /// table of `size` elements, when its global base, if any, is `base`.
+    pub fn fits(&self, base: u64, size: u64) -> bool {
+        base.checked_add(self.offset)
+            .and_then(|start| start.checked_add(self.len))
+            .map_or(false, |end| end <= size)
+    }
+
+    /// Returns whether this segment fits in the minimum size its memory or
+    /// table is declared with, or `None` if that depends on the value of its
+    /// global base.
+    ///
+    /// For an imported memory or table this is the minimum size of the
+    /// import, which the memory or table provided for it may exceed.
+    pub fn fits_minimum(&self) -> Option<bool> {
+        match self.global_base {
+            Some(_) => None,
+            None => Some(self.fits(0, self.minimum)),
+        }
+    }
+}
+
+/// The error which instantiation fails with when a data or element segment
+/// doesn't fit in the memory or table it's written to.
+///
+/// This is the context of the error rather than its cause, which is still a
+/// [`Trap`](crate::Trap) when bulk memory is enabled, so that both can be
+/// retrieved with `downcast_ref`.
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let engine = Engine::default();
+/// let module = Module::new(&engine, r#"
+///     (module
+///         (memory 1)
+///         (data (i32.const 0) "ok")
+///         (data (i32.const 65535) "oops"))
+/// "#)?;
+/// let mut store = Store::new(&engine, ());
+/// let err = Instance::new(&mut store, &module, &[]).err().unwrap();
+/// let oob = err.downcast_ref::<SegmentOutOfBounds>().unwrap();
+/// assert_eq!(oob.kind(), SegmentKind::Data);
+/// assert_eq!(oob.index(), 1);
+/// assert_eq!(oob.offset(), 65535);
+/// assert_eq!(oob.len(), 4);
+/// assert_eq!(oob.size(), 65536);
+/// assert!(err.downcast_ref::<Trap>().is_some());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SegmentOutOfBounds {
+    kind: SegmentKind,
+    index: u32,
+    offset: u64,
+    len: u64,
+    size: u64,
+}
+
+impl SegmentOutOfBounds {
+    pub(crate) fn from_runtime(oob: wasmtime_runtime::SegmentOutOfBounds) -> SegmentOutOfBounds {
+        let (kind, index) = match oob.segment {
+            SegmentIndex::Data(index) => (SegmentKind::Data, index.as_u32()),
+            SegmentIndex::Elem(index) => (SegmentKind::Element, index.as_u32()),
+        };
+        SegmentOutOfBounds {
+            kind,
+            index,
+            offset: oob.start,
+            len: oob.len,
+            size: oob.size,
+        }
+    }
+
+    /// Returns whether the segment is a data or element segment.
+    pub fn kind(&self) -> SegmentKind {
+        self.kind
+    }
+
+    /// Returns the index of the segment within the module's data or element
+    /// segments.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Returns the offset in bytes, or index in elements, the segment was
+    /// written at, including its global base.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Returns the number of bytes or elements in the segment.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Returns whether the segment is empty, in which case its offset was
+    /// beyond the end of its memory or table.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the size in bytes of the memory, or in elements of the table,
+    /// the segment was written to.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl fmt::Display for SegmentOutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, target) = match self.kind {
+            SegmentKind::Data => ("bytes", "memory"),
+            SegmentKind::Element => ("elements", "table"),
+        };
+        write!(
+            f,
+            "{} segment {} does not fit: {} {} at offset {} of a {} of {} {}",
+            self.kind, self.index, self.len, unit, self.offset, target, self.size, unit
+        )
+    }
+}
+
+impl std::error::Error for SegmentOutOfBounds {}
@@ -45,6 +45,7 @@ use object::read::elf::FileHeader;
 use object::Bytes;
 use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
+use std::fmt;
 use std::path::Path;
 use std::str::FromStr;
 use wasmtime_environ::{FlagValue, Tunables, TypeTables};
@@ -53,6 +54,36 @@ use wasmtime_runtime::MmapVec;
 
 const HEADER: &[u8] = b"\0wasmtime-aot";
 
+/// Error for a precompiled module which is incompatible with the [`Engine`] it's
+/// being deserialized into.
+///
+/// This is returned by [`Module::deserialize`] and
+/// [`Module::deserialize_file`] when the module was produced by a different
+/// version of Wasmtime, for a different target, for CPU features the host
+/// doesn't have, or with compilation settings or WebAssembly features which
+/// differ from the engine's. It can be recovered from the returned
+/// [`anyhow::Error`] with `downcast_ref`.
+#[derive(Debug)]
+pub struct IncompatibleModuleError {
+    error: anyhow::Error,
+}
+
+impl fmt::Display for IncompatibleModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for IncompatibleModuleError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.error.source()
+    }
+}
+
+fn incompatible(error: anyhow::Error) -> anyhow::Error {
+    IncompatibleModuleError { error }.into()
+}
+
 // This exists because `wasmparser::WasmFeatures` isn't serializable
 #[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct WasmFeatures {
@@ -69,6 +100,9 @@ struct WasmFeatures {
     pub memory64: bool,
     pub relaxed_simd: bool,
     pub extended_const: bool,
+    pub saturating_float_to_int: bool,
+    pub sign_extension: bool,
+    pub mutable_global: bool,
 }
 
 impl From<&wasmparser::WasmFeatures> for WasmFeatures {
@@ -87,11 +121,9 @@ impl From<&wasmparser::WasmFeatures> for WasmFeatures {
             memory64,
             relaxed_simd,
             extended_const,
-
-            // Always on; we don't currently have knobs for these.
-            mutable_global: _,
-            saturating_float_to_int: _,
-            sign_extension: _,
+            saturating_float_to_int,
+            sign_extension,
+            mutable_global,
         } = *other;
 
         Self {
@@ -108,6 +140,9 @@ impl From<&wasmparser::WasmFeatures> for WasmFeatures {
             memory64,
             relaxed_simd,
             extended_const,
+            saturating_float_to_int,
+            sign_extension,
+            mutable_global,
         }
     }
 }
@@ -205,7 +240,17 @@ impl<'a> SerializedModule<'a> {
 
     pub fn into_module(self, engine: &Engine) -> Result<Module> {
         let (mmap, info, types) = self.into_parts(engine)?;
-        Module::from_parts(engine, mmap, info, types)
+        Module::from_parts(engine, mmap, info, types, None)
+    }
+
+    /// Same as `into_module` except that the compilation settings of the
+    /// module aren't checked against `engine`.
+    ///
+    /// This is unsafe because loading a module compiled with incompatible
+    /// settings is undefined behavior.
+    pub unsafe fn into_module_unchecked(self, engine: &Engine) -> Result<Module> {
+        let (mmap, info, types) = self.into_parts_unchecked();
+        Module::from_parts(engine, mmap, info, types, None)
     }
 
     pub fn into_parts(
@@ -214,16 +259,23 @@ impl<'a> SerializedModule<'a> {
     ) -> Result<(MmapVec, Option<CompiledModuleInfo>, TypeTables)> {
         // Verify that the compilation settings in the engine match the
         // compilation settings of the module that's being loaded.
+        self.check_compatible(engine).map_err(incompatible)?;
+        Ok(self.into_parts_unchecked())
+    }
+
+    fn into_parts_unchecked(self) -> (MmapVec, Option<CompiledModuleInfo>, TypeTables) {
+        let module = self.artifacts.unwrap_owned();
+        (module, None, self.metadata.types.unwrap_owned())
+    }
+
+    fn check_compatible(&mut self, engine: &Engine) -> Result<()> {
         self.check_triple(engine)?;
         self.check_shared_flags(engine)?;
         self.check_isa_flags(engine)?;
 
         self.check_tunables(&engine.config().tunables)?;
         self.check_features(&engine.config().features)?;
-
-        let module = self.artifacts.unwrap_owned();
-
-        Ok((module, None, self.metadata.types.unwrap_owned()))
+        Ok(())
     }
 
     pub fn to_bytes(&self, version_strat: &ModuleVersionStrategy) -> Result<Vec<u8>> {
@@ -287,19 +339,19 @@ impl<'a> SerializedModule<'a> {
             ModuleVersionStrategy::WasmtimeVersion => {
                 let version = std::str::from_utf8(&metadata[1..1 + version_len])?;
                 if version != env!("CARGO_PKG_VERSION") {
-                    bail!(
+                    return Err(incompatible(anyhow!(
                         "Module was compiled with incompatible Wasmtime version '{}'",
                         version
-                    );
+                    )));
                 }
             }
             ModuleVersionStrategy::Custom(v) => {
                 let version = std::str::from_utf8(&metadata[1..1 + version_len])?;
                 if version != v {
-                    bail!(
+                    return Err(incompatible(anyhow!(
                         "Module was compiled with incompatible version '{}'",
                         version
-                    );
+                    )));
                 }
             }
             ModuleVersionStrategy::None => { /* ignore the version info, accept all */ }
@@ -417,10 +469,40 @@ impl<'a> SerializedModule<'a> {
             epoch_interruption,
             static_memory_bound_is_maximum,
             guard_before_linear_memory,
+            signals_based_traps,
+            guest_debug,
+            coredump_frames,
+            count_instructions,
+            trace_memory_accesses,
+            check_integer_overflow,
+            ref integer_overflow_allowlist,
+            pinned_context_register,
 
             // This doesn't affect compilation, it's just a runtime setting.
             dynamic_memory_growth_reserve: _,
 
+            // Modules compiled lazily, including with tiered compilation,
+            // can't be serialized, and modules compiled up front work just as
+            // well in engines which compile lazily.
+            lazy_compilation: _,
+            tiered_compilation: _,
+            collect_profile: _,
+
+            // Functions which are skipped can't be called by any module
+            // compiled from the same wasm, whatever this setting.
+            skip_unreachable_functions: _,
+
+            // Import thunks are recorded with the module, and work whether
+            // or not its calls end up devirtualized.
+            devirtualize_imports: _,
+
+            // Inline bulk memory operations behave just like the runtime's.
+            bulk_memory_inline_threshold: _,
+
+            // The `call_indirect` instructions with a cache are recorded with
+            // the module, which sizes the `VMContext` for them.
+            cache_call_indirects: _,
+
             // This does technically affect compilation but modules with/without
             // trap information can be loaded into engines with the opposite
             // setting just fine (it's just a section in the compiled file and
@@ -428,6 +510,13 @@ impl<'a> SerializedModule<'a> {
             generate_address_map: _,
         } = self.metadata.tunables;
 
+        // This is checked first since it also changes the memory settings
+        // below, and it's the more helpful error to report.
+        Self::check_bool(
+            signals_based_traps,
+            other.signals_based_traps,
+            "signals-based traps",
+        )?;
         Self::check_int(
             static_memory_bound,
             other.static_memory_bound,
@@ -459,6 +548,39 @@ impl<'a> SerializedModule<'a> {
             other.epoch_interruption,
             "epoch interruption",
         )?;
+        Self::check_bool(guest_debug, other.guest_debug, "guest debugging")?;
+        Self::check_bool(
+            coredump_frames,
+            other.coredump_frames,
+            "core dump frame records",
+        )?;
+        Self::check_bool(
+            count_instructions,
+            other.count_instructions,
+            "instruction counting",
+        )?;
+        Self::check_bool(
+            trace_memory_accesses,
+            other.trace_memory_accesses,
+            "memory access tracing",
+        )?;
+        Self::check_bool(
+            check_integer_overflow,
+            other.check_integer_overflow,
+            "integer overflow checks",
+        )?;
+        if check_integer_overflow && *integer_overflow_allowlist != other.integer_overflow_allowlist
+        {
+            bail!(
+                "Module was compiled with different functions exempted from integer overflow \
+                 checks than the host"
+            );
+        }
+        Self::check_bool(
+            pinned_context_register,
+            other.pinned_context_register,
+            "a pinned context register",
+        )?;
         Self::check_bool(
             static_memory_bound_is_maximum,
             other.static_memory_bound_is_maximum,
@@ -488,6 +610,9 @@ impl<'a> SerializedModule<'a> {
             memory64,
             relaxed_simd,
             extended_const,
+            saturating_float_to_int,
+            sign_extension,
+            mutable_global,
         } = self.metadata.features;
 
         Self::check_bool(
@@ -543,6 +668,21 @@ impl<'a> SerializedModule<'a> {
             other.relaxed_simd,
             "WebAssembly relaxed-simd support",
         )?;
+        Self::check_bool(
+            saturating_float_to_int,
+            other.saturating_float_to_int,
+            "WebAssembly saturating float-to-int support",
+        )?;
+        Self::check_bool(
+            sign_extension,
+            other.sign_extension,
+            "WebAssembly sign-extension support",
+        )?;
+        Self::check_bool(
+            mutable_global,
+            other.mutable_global,
+            "WebAssembly mutable-global support",
+        )?;
 
         Ok(())
     }
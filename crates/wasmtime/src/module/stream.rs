@@ -0,0 +1,342 @@
+//! Compiling a module while its bytes are still arriving.
+
+use super::name_disabled_feature;
+use crate::{Engine, Module};
+use anyhow::{Context, Result};
+use std::any::Any;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use wasmparser::{Chunk, FunctionBody, Parser, Payload, Validator};
+use wasmtime_environ::{
+    CompileError, DefinedFuncIndex, EntityRef, FunctionBodyData, ModuleEnvironment,
+    ModuleTranslation, PrimaryMap, TypeTables,
+};
+
+/// A WebAssembly module which is compiled as its bytes arrive, created with
+/// [`ModuleStream::new`].
+///
+/// Bytes of the module are handed to [`ModuleStream::push`] in whatever
+/// pieces they're received in, such as the chunks of a download, and once all
+/// of them have been pushed [`ModuleStream::finish`] returns the compiled
+/// [`Module`]. Each function of the module is validated and compiled as soon
+/// as all of its body has been pushed, in parallel with receiving the rest of
+/// the module when
+/// [`Config::parallel_compilation`](crate::Config::parallel_compilation) is
+/// enabled, so a module is compiled soon after its last byte arrives rather
+/// than only starting then.
+///
+/// Because `push` doesn't care where the bytes come from this works with any
+/// source of bytes, including asynchronous ones: an embedder using an async
+/// runtime can push each chunk of a stream as it's awaited without blocking
+/// the runtime on compilation. [`Module::from_reader`] compiles the bytes of
+/// a [`std::io::Read`] this way.
+///
+/// Some configurations need all of a module before any of its functions can
+/// be compiled, in which case the module is compiled by `finish` as
+/// [`Module::new`] would compile it: engines which compile lazily, skip
+/// unreachable functions, generate native debug information, exempt
+/// functions from integer overflow checks by name or use the compilation
+/// cache, as well as modules in the text format. Functions
+/// compiled as they arrive are also compiled before the name section at the
+/// end of the module, so [`CompilationHook`](crate::CompilationHook)s see
+/// them without names.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// let engine = Engine::default();
+/// let wasm = wat::parse_str(r#"(module (func (export "run") (result i32) i32.const 42))"#)?;
+///
+/// let mut stream = ModuleStream::new(&engine)?;
+/// for chunk in wasm.chunks(8) {
+///     stream.push(chunk)?;
+/// }
+/// let module = stream.finish()?;
+///
+/// let mut store = Store::new(&engine, ());
+/// let instance = Instance::new(&mut store, &module, &[])?;
+/// let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+/// assert_eq!(run.call(&mut store, ())?, 42);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ModuleStream {
+    engine: Engine,
+    bytes: Vec<u8>,
+    streaming: Option<Box<Streaming>>,
+}
+
+/// The state of a module whose functions are compiled as they arrive.
+struct Streaming {
+    parser: Parser,
+    /// How many bytes of the module `parser` has consumed.
+    parsed: usize,
+    validator: Validator,
+    /// The translation of the module up to its code section, which is shared
+    /// with the functions being compiled once the code section starts.
+    prefix: Option<Arc<(ModuleTranslation<'static>, TypeTables)>>,
+    funcs: Vec<Option<(Box<dyn Any + Send>, Duration)>>,
+    /// How many of `funcs` are still being compiled.
+    pending: usize,
+    sender: mpsc::Sender<CompiledFunction>,
+    receiver: mpsc::Receiver<CompiledFunction>,
+    finished: bool,
+}
+
+/// The result of compiling a function, or the panic it was compiled with,
+/// which is resumed by the thread receiving it.
+type CompiledFunction = (
+    DefinedFuncIndex,
+    thread::Result<Result<(Box<dyn Any + Send>, Duration), CompileError>>,
+);
+
+impl ModuleStream {
+    /// Creates a new stream of the bytes of a module to be compiled by
+    /// `engine`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `engine`'s compilation settings aren't compatible
+    /// with the host.
+    pub fn new(engine: &Engine) -> Result<ModuleStream> {
+        engine
+            .check_compatible_with_native_host()
+            .context("compilation settings are not compatible with the native host")?;
+
+        let tunables = &engine.config().tunables;
+        #[cfg(feature = "cache")]
+        let cached = engine.cache_config().enabled();
+        #[cfg(not(feature = "cache"))]
+        let cached = false;
+        let buffered = tunables.lazy_compilation
+            || tunables.tiered_compilation
+            || tunables.skip_unreachable_functions
+            || tunables.generate_native_debuginfo
+            || (tunables.check_integer_overflow && !tunables.integer_overflow_allowlist.is_empty())
+            || cached;
+
+        let streaming = if buffered {
+            None
+        } else {
+            let (sender, receiver) = mpsc::channel();
+            Some(Box::new(Streaming {
+                parser: Parser::new(0),
+                parsed: 0,
+                validator: Validator::new_with_features(engine.config().features),
+                prefix: None,
+                funcs: Vec::new(),
+                pending: 0,
+                sender,
+                receiver,
+                finished: false,
+            }))
+        };
+        Ok(ModuleStream {
+            engine: engine.clone(),
+            bytes: Vec::new(),
+            streaming,
+        })
+    }
+
+    /// Pushes the next `bytes` of the module, compiling any of its functions
+    /// which are now complete.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes pushed so far aren't the start of a
+    /// valid module, or if one of its functions failed to compile. The stream
+    /// can't be used after an error.
+    pub fn push(&mut self, bytes: &[u8]) -> Result<()> {
+        self.bytes.extend_from_slice(bytes);
+
+        // Anything which doesn't start like a binary module, such as the
+        // text format, is left for `Module::new` to compile once it's all
+        // here.
+        let magic = b"\0asm";
+        let len = self.bytes.len().min(magic.len());
+        if self.bytes[..len] != magic[..len] {
+            self.streaming = None;
+        }
+
+        match &mut self.streaming {
+            Some(streaming) => streaming
+                .advance(&self.engine, &self.bytes, false)
+                .map_err(name_disabled_feature),
+            None => Ok(()),
+        }
+    }
+
+    /// Finishes compiling the module once all of its bytes have been pushed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the bytes pushed don't make up a valid module, or
+    /// for any of the reasons [`Module::new`] can fail.
+    pub fn finish(self) -> Result<Module> {
+        let ModuleStream {
+            engine,
+            bytes,
+            streaming,
+        } = self;
+        let mut streaming = match streaming {
+            Some(streaming) => streaming,
+            None => return Module::new(&engine, &bytes),
+        };
+        streaming
+            .advance(&engine, &bytes, true)
+            .map_err(name_disabled_feature)?;
+
+        // Wait for the functions which are still being compiled.
+        let Streaming {
+            mut funcs,
+            pending,
+            sender,
+            receiver,
+            ..
+        } = *streaming;
+        drop(sender);
+        for _ in 0..pending {
+            let (index, result) = receiver
+                .recv()
+                .expect("every function sends the result of compiling it");
+            funcs[index.index()] = Some(resume(result).map_err(name_disabled_feature)?);
+        }
+
+        // The functions are compiled, but the translation of the sections
+        // after the code section is still needed. This translates them,
+        // along with the sections before the code section once more, which is
+        // quick compared to compiling the functions.
+        let tunables = &engine.config().tunables;
+        let (mut translation, types) = ModuleEnvironment::new(tunables, &engine.config().features)
+            .translate(&bytes)
+            .map_err(name_disabled_feature)
+            .context("failed to parse WebAssembly module")?;
+        translation.function_body_inputs = PrimaryMap::new();
+        // The functions were compiled before the whole module was known, so
+        // none of their `call_indirect`s have a cache, and they read all of
+        // the tables as ones which aren't shared.
+        translation.module.call_indirect_caches.clear();
+        translation.module.shared_tables.clear();
+
+        let (funcs, compile_times): (Vec<_>, Vec<_>) = funcs
+            .into_iter()
+            .map(|func| func.expect("every function is compiled"))
+            .unzip();
+        let (mmap, info) = Module::emit_artifacts(
+            &engine,
+            translation,
+            &types,
+            funcs.into_iter().collect(),
+            Some(&compile_times),
+        )?;
+        Module::from_parts(&engine, mmap, Some(info), types, None)
+    }
+}
+
+impl Streaming {
+    /// Parses as much of `bytes`, all of the module pushed so far, as
+    /// possible, and sends off the functions which are now complete to be
+    /// compiled. All of the module has been pushed if `eof` is set.
+    fn advance(&mut self, engine: &Engine, bytes: &[u8], eof: bool) -> Result<()> {
+        while !self.finished {
+            let (consumed, payload) = match self
+                .parser
+                .parse(&bytes[self.parsed..], eof)
+                .context("failed to parse WebAssembly module")?
+            {
+                Chunk::NeedMoreData(_) => break,
+                Chunk::Parsed { consumed, payload } => (consumed, payload),
+            };
+            match payload {
+                Payload::CodeSectionStart { count, range, .. } => {
+                    self.start_code(engine, &bytes[..self.parsed])?;
+                    self.validator.code_section_start(count, &range)?;
+                }
+                Payload::CodeSectionEntry(body) => {
+                    self.compile(engine, bytes, body)?;
+                }
+                Payload::End(_) => self.finished = true,
+                _ => {}
+            }
+            self.parsed += consumed;
+        }
+
+        // Report failures to compile as early as possible, so that the rest
+        // of a module which won't compile isn't waited for.
+        while let Ok((index, result)) = self.receiver.try_recv() {
+            self.funcs[index.index()] = Some(resume(result)?);
+            self.pending -= 1;
+        }
+        Ok(())
+    }
+
+    /// Translates `prefix`, the sections of the module which come before its
+    /// code section, for compiling its functions.
+    fn start_code(&mut self, engine: &Engine, prefix: &[u8]) -> Result<()> {
+        let (translation, types) =
+            ModuleEnvironment::new(&engine.config().tunables, &engine.config().features)
+                .translate_prefix(prefix, &mut self.validator)
+                .context("failed to parse WebAssembly module")?;
+
+        // Only the module's metadata is kept, as the rest of the translation
+        // borrows `prefix`, which keeps growing.
+        let mut owned = ModuleTranslation::default();
+        owned.module = translation.module;
+        self.prefix = Some(Arc::new((owned, types)));
+        Ok(())
+    }
+
+    /// Compiles the function `body`, which is within `bytes`, in the
+    /// background if compilation is parallel.
+    fn compile(&mut self, engine: &Engine, bytes: &[u8], body: FunctionBody<'_>) -> Result<()> {
+        let validator = self.validator.code_section_entry(&body)?;
+        let index = DefinedFuncIndex::new(self.funcs.len());
+        self.funcs.push(None);
+        self.pending += 1;
+
+        let range = body.range();
+        let code = bytes[range.start..range.end].to_vec();
+        let prefix = self.prefix.clone().unwrap();
+        let sender = self.sender.clone();
+        let compiling = engine.clone();
+        engine.spawn_maybe_parallel(move || {
+            let (translation, types) = &*prefix;
+            let config = compiling.config();
+            let mut body = FunctionBody::new(range.start, &code);
+            body.allow_memarg64(config.features.memory64);
+            let start = Instant::now();
+            // A panic is caught to be resumed by `push` or `finish`, as it
+            // would be by `Module::new`, rather than aborting the process on
+            // a thread of the pool.
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                compiling
+                    .compiler()
+                    .compile_function(
+                        translation,
+                        index,
+                        FunctionBodyData { body, validator },
+                        &config.tunables,
+                        types,
+                    )
+                    .map(|func| (func, start.elapsed()))
+            }));
+            let _ = sender.send((index, result));
+        });
+
+        Ok(())
+    }
+}
+
+/// Returns the result of compiling a function, resuming the panic it was
+/// compiled with if any.
+fn resume<T>(result: thread::Result<T>) -> T {
+    match result {
+        Ok(result) => result,
+        Err(panic) => panic::resume_unwind(panic),
+    }
+}
@@ -0,0 +1,191 @@
+//! Sampling of the wasm stack of a single store, for profiling guest code in
+//! process.
+
+use crate::store::AsContext;
+use crate::trap::lookup_pcs;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Instant;
+use wasmtime_runtime::Backtrace;
+
+/// A sampling profiler for the WebAssembly guests running within a
+/// [`Store`](crate::Store).
+///
+/// Unlike system profilers such as `perf`, which attribute time to all code
+/// running in the process, this profiler only records the wasm frames which
+/// belong to the store it's sampling. This allows profiling one tenant of a
+/// host which runs many unrelated guests at once.
+///
+/// The profiler doesn't sample on its own. Instead the embedder calls
+/// [`GuestProfiler::sample`] periodically while the guest is running, for
+/// example from an epoch deadline callback (see
+/// [`Store::epoch_deadline_callback`](crate::Store::epoch_deadline_callback))
+/// or from a host function called by the guest. Each sample is weighted by the
+/// time elapsed since the previous one.
+///
+/// Once profiling is done, [`GuestProfiler::finish`] writes out the collected
+/// samples in the [speedscope] file format, which can be viewed at
+/// <https://www.speedscope.app>.
+///
+/// [speedscope]: https://github.com/jlfwong/speedscope/wiki/Importing-from-custom-sources
+pub struct GuestProfiler {
+    name: String,
+    start: Instant,
+    last_sample: Instant,
+    frames: Vec<ProfileFrame>,
+    /// Maps a module, identified by the address of its metadata, and a
+    /// function index within it to the function's entry in `frames`.
+    frame_indices: HashMap<(usize, u32), usize>,
+    samples: Vec<Vec<usize>>,
+    weights: Vec<u64>,
+}
+
+struct ProfileFrame {
+    name: String,
+    file: Option<String>,
+}
+
+impl GuestProfiler {
+    /// Creates a new profiler with no samples.
+    ///
+    /// The `name` is recorded in the profile to identify what was profiled,
+    /// and time is measured starting from the call to this function.
+    pub fn new(name: &str) -> GuestProfiler {
+        let now = Instant::now();
+        GuestProfiler {
+            name: name.to_string(),
+            start: now,
+            last_sample: now,
+            frames: Vec::new(),
+            frame_indices: HashMap::new(),
+            samples: Vec::new(),
+            weights: Vec::new(),
+        }
+    }
+
+    /// Records a sample of the wasm stack of `store`.
+    ///
+    /// This must be called on the thread which is currently executing wasm
+    /// within `store`, such as from within a host function or an epoch
+    /// deadline callback. If no wasm from `store` is currently on the stack
+    /// then an empty sample is recorded, attributing the elapsed time to the
+    /// host.
+    pub fn sample(&mut self, store: impl AsContext) {
+        let now = Instant::now();
+        let weight = now.duration_since(self.last_sample).as_nanos() as u64;
+        self.last_sample = now;
+
+        let store = store.as_context();
+        let modules = store.0.modules();
+        let backtrace = Backtrace::new();
+        let mut stack = Vec::new();
+        for pc in lookup_pcs(None, &backtrace) {
+            let (info, module) = match modules.lookup_frame_info(pc) {
+                Some(pair) => pair,
+                None => continue,
+            };
+            let key = (
+                module.env_module() as *const wasmtime_environ::Module as usize,
+                info.func_index(),
+            );
+            let frames = &mut self.frames;
+            let index = *self.frame_indices.entry(key).or_insert_with(|| {
+                frames.push(ProfileFrame {
+                    name: info.symbol_name(),
+                    file: info.module_name().map(|s| s.to_string()),
+                });
+                frames.len() - 1
+            });
+            stack.push(index);
+        }
+
+        // Backtraces start with the most recently called frame while
+        // speedscope expects the outermost frame first.
+        stack.reverse();
+        self.samples.push(stack);
+        self.weights.push(weight);
+    }
+
+    /// Writes the samples collected so far to `output` as a speedscope JSON
+    /// profile.
+    pub fn finish(self, mut output: impl Write) -> Result<()> {
+        let end = self.last_sample.duration_since(self.start).as_nanos();
+        let name = JsonStr(&self.name);
+        write!(
+            output,
+            "{{\"$schema\":\"https://www.speedscope.app/file-format-schema.json\",\
+             \"exporter\":\"wasmtime {}\",\"name\":{},\"shared\":{{\"frames\":",
+            env!("CARGO_PKG_VERSION"),
+            name,
+        )?;
+        write_list(&mut output, &self.frames, |output, frame| {
+            write!(output, "{{\"name\":{}", JsonStr(&frame.name))?;
+            if let Some(file) = &frame.file {
+                write!(output, ",\"file\":{}", JsonStr(file))?;
+            }
+            write!(output, "}}")
+        })?;
+        write!(
+            output,
+            "}},\"profiles\":[{{\"type\":\"sampled\",\"name\":{},\"unit\":\"nanoseconds\",\
+             \"startValue\":0,\"endValue\":{},\"samples\":",
+            name, end,
+        )?;
+        write_list(&mut output, &self.samples, |output, sample| {
+            write_list(output, sample, |output, frame| write!(output, "{}", frame))
+        })?;
+        write!(output, ",\"weights\":")?;
+        write_list(&mut output, &self.weights, |output, weight| {
+            write!(output, "{}", weight)
+        })?;
+        write!(output, "}}]}}")?;
+        output.flush()?;
+        Ok(())
+    }
+}
+
+/// Writes `items` to `output` as a JSON array, using `write_item` to write
+/// each element.
+fn write_list<W: Write, T>(
+    output: &mut W,
+    items: &[T],
+    mut write_item: impl FnMut(&mut W, &T) -> io::Result<()>,
+) -> io::Result<()> {
+    write!(output, "[")?;
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            write!(output, ",")?;
+        }
+        write_item(output, item)?;
+    }
+    write!(output, "]")
+}
+
+impl fmt::Debug for GuestProfiler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GuestProfiler")
+            .field("name", &self.name)
+            .field("samples", &self.samples.len())
+            .finish()
+    }
+}
+
+/// A string formatted as a quoted and escaped JSON string literal.
+struct JsonStr<'a>(&'a str);
+
+impl fmt::Display for JsonStr<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str("\\\"")?,
+                '\\' => f.write_str("\\\\")?,
+                c if c < ' ' => write!(f, "\\u{:04x}", c as u32)?,
+                c => write!(f, "{}", c)?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
@@ -1,7 +1,8 @@
 #![allow(missing_docs)]
 
 use crate::AsContextMut;
-use std::any::Any;
+use std::any::{Any, TypeId};
+use std::sync::{Arc, Mutex};
 use wasmtime_runtime::VMExternRef;
 
 /// Represents an opaque reference to any data within WebAssembly.
@@ -21,9 +22,77 @@ impl ExternRef {
         ExternRef { inner }
     }
 
+    /// Creates a new `ExternRef` wrapping `value` whose value is handed to
+    /// the drop hook `store` has for its type, if any, once the reference is
+    /// reclaimed.
+    ///
+    /// This is the same as [`ExternRef::new`] unless a hook for `T` was
+    /// registered with [`Store::externref_drop_hook`] beforehand. Otherwise
+    /// once the last reference to the value is dropped, whether by the host
+    /// or by the store collecting the references WebAssembly held, the value
+    /// is set aside and given to the hook by the next [`Store::gc`]. This lets
+    /// the host release resources kept in the store's data for the value,
+    /// such as an entry of a table of handles, which the value's `Drop`
+    /// implementation can't reach.
+    ///
+    /// If the store is dropped first then the values set aside are dropped
+    /// without calling the hooks.
+    ///
+    /// [`Store::externref_drop_hook`]: crate::Store::externref_drop_hook
+    /// [`Store::gc`]: crate::Store::gc
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// struct Handle(usize);
+    ///
+    /// let engine = Engine::default();
+    /// let mut store = Store::new(&engine, vec![true]);
+    /// store.externref_drop_hook(|open: &mut Vec<bool>, handle: Handle| {
+    ///     open[handle.0] = false;
+    /// });
+    ///
+    /// let externref = ExternRef::new_in(&mut store, Handle(0));
+    /// assert_eq!(externref.downcast_ref::<Handle>().unwrap().0, 0);
+    /// drop(externref);
+    /// store.gc();
+    /// assert_eq!(store.data(), &[false]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_in<T>(mut store: impl AsContextMut, value: T) -> ExternRef
+    where
+        T: 'static + Any + Send + Sync,
+    {
+        let store = store.as_context_mut().0;
+        match store.reclaimed_externrefs(TypeId::of::<T>()) {
+            Some(reclaimed) => ExternRef::new(Reclaimable {
+                value: Some(Box::new(value)),
+                reclaimed,
+            }),
+            None => ExternRef::new(value),
+        }
+    }
+
     /// Get the underlying data for this `ExternRef`.
     pub fn data(&self) -> &dyn Any {
-        &*self.inner
+        let data = &*self.inner;
+        match data.downcast_ref::<Reclaimable>() {
+            Some(reclaimable) => reclaimable.value.as_deref().unwrap(),
+            None => data,
+        }
+    }
+
+    /// Returns whether the underlying data of this `ExternRef` is a `T`.
+    pub fn is<T: Any>(&self) -> bool {
+        self.data().is::<T>()
+    }
+
+    /// Returns the underlying data of this `ExternRef` if it's a `T`.
+    pub fn downcast_ref<T: Any>(&self) -> Option<&T> {
+        self.data().downcast_ref()
     }
 
     /// Get the strong reference count for this `ExternRef`.
@@ -100,3 +169,22 @@ impl ExternRef {
         externref_ptr as usize
     }
 }
+
+/// The values of `ExternRef`s created with `ExternRef::new_in` which have
+/// been reclaimed and are waiting to be handed to their store's drop hooks.
+pub(crate) type ReclaimedExternRefs = Arc<Mutex<Vec<Box<dyn Any + Send + Sync>>>>;
+
+/// The data of an `ExternRef` created with `ExternRef::new_in` for a type
+/// with a drop hook, which sets its value aside for the hook when dropped.
+struct Reclaimable {
+    value: Option<Box<dyn Any + Send + Sync>>,
+    reclaimed: ReclaimedExternRefs,
+}
+
+impl Drop for Reclaimable {
+    fn drop(&mut self) {
+        if let (Some(value), Ok(mut reclaimed)) = (self.value.take(), self.reclaimed.lock()) {
+            reclaimed.push(value);
+        }
+    }
+}
@@ -7,7 +7,7 @@ use std::{
 };
 use std::{convert::TryFrom, sync::Arc};
 use wasmtime_environ::{PrimaryMap, SignatureIndex, TypeTables, WasmFuncType};
-use wasmtime_runtime::{VMSharedSignatureIndex, VMTrampoline};
+use wasmtime_runtime::{VMNativeTrampoline, VMSharedSignatureIndex, VMTrampoline};
 
 /// Represents a collection of shared signatures.
 ///
@@ -20,6 +20,7 @@ pub struct SignatureCollection {
     registry: Arc<RwLock<SignatureRegistryInner>>,
     signatures: PrimaryMap<SignatureIndex, VMSharedSignatureIndex>,
     trampolines: HashMap<VMSharedSignatureIndex, (usize, VMTrampoline)>,
+    native_trampolines: HashMap<VMSharedSignatureIndex, VMNativeTrampoline>,
 }
 
 impl SignatureCollection {
@@ -29,17 +30,22 @@ impl SignatureCollection {
         registry: &SignatureRegistry,
         types: &TypeTables,
         trampolines: impl Iterator<Item = (SignatureIndex, VMTrampoline)>,
+        native_trampolines: impl Iterator<Item = (SignatureIndex, VMNativeTrampoline)>,
     ) -> Self {
         let (signatures, trampolines) = registry
             .0
             .write()
             .unwrap()
             .register_for_module(types, trampolines);
+        let native_trampolines = native_trampolines
+            .map(|(index, trampoline)| (signatures[index], trampoline))
+            .collect();
 
         Self {
             registry: registry.0.clone(),
             signatures,
             trampolines,
+            native_trampolines,
         }
     }
 
@@ -63,6 +69,12 @@ impl SignatureCollection {
             .get(&index)
             .map(|(_, trampoline)| *trampoline)
     }
+
+    /// Gets the trampoline taking its arguments natively for a registered
+    /// signature, if one was compiled.
+    pub fn native_trampoline(&self, index: VMSharedSignatureIndex) -> Option<VMNativeTrampoline> {
+        self.native_trampolines.get(&index).copied()
+    }
 }
 
 impl Drop for SignatureCollection {
@@ -0,0 +1,463 @@
+//! Capturing the mutable state of an instance so that it can later be restored
+//! into another instance of the same module.
+
+use crate::store::{InstanceId, StoreOpaque};
+use crate::{Func, Global, Instance, Memory, StoreContextMut, Table, Val};
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::fmt;
+#[cfg(compiler)]
+use std::ops::Range;
+use std::sync::Arc;
+#[cfg(compiler)]
+use wasmtime_environ::{
+    packed_option::ReservedValue, ActiveSegment, GlobalInit, MemoryInitialization,
+    MemoryInitializer, MemoryPlan, ModuleTranslation, TableInitialization, TableInitializer,
+    TablePlan, Tunables, WASM_PAGE_SIZE,
+};
+use wasmtime_environ::{DataIndex, ElemIndex, FuncIndex, WasmType};
+
+/// A copy of the mutable state of an [`Instance`], created with
+/// [`Instance::snapshot`] and applied with [`Instance::restore`].
+///
+/// A snapshot holds the contents of every linear memory and table, and the
+/// value of every mutable global, which the instance defines, along with which
+/// of its passive segments have been dropped. Items which the instance imports
+/// belong to another instance, or to the host, and aren't part of its
+/// snapshot.
+///
+/// Function references in tables and globals are recorded as an index into
+/// the module's function index space. Restoring a snapshot into a fresh
+/// instance of the same module therefore makes them refer to that instance's
+/// functions, or to the functions it imports at the same indices.
+pub struct Snapshot {
+    module: Arc<wasmtime_environ::Module>,
+    memories: Vec<Vec<u8>>,
+    globals: Vec<SnapshotValue>,
+    tables: Vec<Vec<SnapshotValue>>,
+    dropped_elements: Vec<ElemIndex>,
+    dropped_data: Vec<DataIndex>,
+}
+
+enum SnapshotValue {
+    Val(Val),
+    Func(Option<FuncIndex>),
+}
+
+impl Snapshot {
+    pub(crate) fn capture<T>(
+        mut store: StoreContextMut<'_, T>,
+        instance: &Instance,
+    ) -> Result<Snapshot> {
+        let id = instance.id(store.0);
+        let module = store.0.instance(id).module().clone();
+        let mut funcs = FuncIndices::new(id);
+
+        let mut memories = Vec::new();
+        for (index, _) in module
+            .memory_plans
+            .iter()
+            .skip(module.num_imported_memories)
+        {
+            let memory = unsafe {
+                let export = store.0.instance_mut(id).get_exported_memory(index);
+                Memory::from_wasmtime_memory(export, store.0)
+            };
+            memories.push(memory.data(&store).to_vec());
+        }
+
+        let mut globals = Vec::new();
+        for (index, global) in module.globals.iter().skip(module.num_imported_globals) {
+            if !global.mutability {
+                continue;
+            }
+            let global = unsafe {
+                let export = store.0.instance_mut(id).get_exported_global(index);
+                Global::from_wasmtime_global(export, store.0)
+            };
+            let val = global.get(&mut store);
+            globals.push(funcs.capture(store.0, val)?);
+        }
+
+        let mut tables = Vec::new();
+        for (index, _) in module.table_plans.iter().skip(module.num_imported_tables) {
+            let table = unsafe {
+                let export = store.0.instance_mut(id).get_exported_table(index);
+                Table::from_wasmtime_table(export, store.0)
+            };
+            let mut elements = Vec::new();
+            for i in 0..table.size(&store) {
+                let val = table.get(&mut store, i).unwrap();
+                elements.push(funcs.capture(store.0, val)?);
+            }
+            tables.push(elements);
+        }
+
+        let handle = store.0.instance(id);
+        let dropped_elements = handle.dropped_elements().collect();
+        let dropped_data = handle.dropped_data().collect();
+
+        Ok(Snapshot {
+            module,
+            memories,
+            globals,
+            tables,
+            dropped_elements,
+            dropped_data,
+        })
+    }
+
+    pub(crate) fn restore<T>(
+        &self,
+        mut store: StoreContextMut<'_, T>,
+        instance: &Instance,
+    ) -> Result<()> {
+        let id = instance.id(store.0);
+        let module = store.0.instance(id).module().clone();
+        if !Arc::ptr_eq(&module, &self.module) {
+            bail!("snapshot was taken from an instance of a different module");
+        }
+
+        // Everything which can fail is checked, and every memory and table
+        // grown, before anything is written so that a failure leaves the
+        // instance as it was, apart from the size of what was grown.
+        let memories = module
+            .memory_plans
+            .keys()
+            .skip(module.num_imported_memories)
+            .map(|index| unsafe {
+                let export = store.0.instance_mut(id).get_exported_memory(index);
+                Memory::from_wasmtime_memory(export, store.0)
+            })
+            .collect::<Vec<_>>();
+        for (memory, data) in memories.iter().zip(&self.memories) {
+            if memory.data_size(&store) > data.len() {
+                bail!("memory has grown beyond its size in the snapshot");
+            }
+            memory
+                .try_data_mut(&mut store)
+                .context("can't restore a memory with read-only ranges")?;
+        }
+        let tables = module
+            .table_plans
+            .iter()
+            .skip(module.num_imported_tables)
+            .map(|(index, plan)| {
+                let table = unsafe {
+                    let export = store.0.instance_mut(id).get_exported_table(index);
+                    Table::from_wasmtime_table(export, store.0)
+                };
+                (table, plan.table.wasm_ty)
+            })
+            .collect::<Vec<_>>();
+        for ((table, _), elements) in tables.iter().zip(&self.tables) {
+            if table.size(&store) as usize > elements.len() {
+                bail!("table has grown beyond its size in the snapshot");
+            }
+        }
+
+        for (memory, data) in memories.iter().zip(&self.memories) {
+            let current = memory.data_size(&store);
+            let delta = (data.len() - current) / wasmtime_environ::WASM_PAGE_SIZE as usize;
+            if delta > 0 {
+                memory.grow(&mut store, delta as u64)?;
+            }
+        }
+        for ((table, ty), elements) in tables.iter().zip(&self.tables) {
+            let current = table.size(&store) as usize;
+            if current < elements.len() {
+                let init = match ty {
+                    WasmType::FuncRef => Val::FuncRef(None),
+                    _ => Val::ExternRef(None),
+                };
+                table.grow(&mut store, (elements.len() - current) as u32, init)?;
+            }
+        }
+
+        for (memory, data) in memories.iter().zip(&self.memories) {
+            memory.try_data_mut(&mut store)?.copy_from_slice(data);
+        }
+
+        let globals = module
+            .globals
+            .iter()
+            .skip(module.num_imported_globals)
+            .filter(|(_, global)| global.mutability);
+        for ((index, _), value) in globals.zip(&self.globals) {
+            let global = unsafe {
+                let export = store.0.instance_mut(id).get_exported_global(index);
+                Global::from_wasmtime_global(export, store.0)
+            };
+            let val = value.restore(store.0, id);
+            global.set(&mut store, val)?;
+        }
+
+        let indices = module.table_plans.keys().skip(module.num_imported_tables);
+        for (((table, _), elements), index) in tables.iter().zip(&self.tables).zip(indices) {
+            // Tables whose elements are shared by all instances are never
+            // modified, so they already hold what the snapshot does.
+            if module.is_shared_table(index) {
+                continue;
+            }
+            for (i, element) in elements.iter().enumerate() {
+                let val = element.restore(store.0, id);
+                table.set(&mut store, i as u32, val)?;
+            }
+        }
+
+        // The `call_indirect` caches may hold functions which the tables no
+        // longer do.
+        let handle = store.0.instance_mut(id);
+        handle.reset_call_indirect_caches();
+
+        // Segments can't be brought back once they're dropped, so only those
+        // which were dropped in the snapshot are dropped here.
+        for index in &self.dropped_elements {
+            handle.elem_drop(*index);
+        }
+        for index in &self.dropped_data {
+            handle.data_drop(*index);
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the initial state which `translation` declares with the state
+    /// in this snapshot, for [`Module::preinitialize`](crate::Module::preinitialize).
+    ///
+    /// The snapshot must have been taken from an instance of the module which
+    /// `translation` describes.
+    #[cfg(compiler)]
+    pub(crate) fn bake(
+        &self,
+        translation: &mut ModuleTranslation<'_>,
+        tunables: &Tunables,
+    ) -> Result<()> {
+        let module = &mut translation.module;
+
+        // The contents of each memory become its only data segments, and its
+        // minimum size is whatever size it has grown to. They're numbered
+        // after all of the module's own segments, so that they can't take the
+        // index of a passive one.
+        let num_data = module.passive_data_map.len()
+            + module
+                .active_segments
+                .iter()
+                .filter(|segment| matches!(segment, ActiveSegment::Data { .. }))
+                .count();
+        translation.data.clear();
+        module.active_segments.clear();
+        let mut initializers = Vec::new();
+        let mut total_data = 0u32;
+        let memories = module
+            .memory_plans
+            .keys()
+            .skip(module.num_imported_memories)
+            .collect::<Vec<_>>();
+        for (index, data) in memories.into_iter().zip(&self.memories) {
+            let mut memory = module.memory_plans[index].memory;
+            memory.minimum = (data.len() / WASM_PAGE_SIZE as usize) as u64;
+            module.memory_plans[index] = MemoryPlan::for_memory(memory, tunables);
+            for range in nonzero_ranges(data) {
+                let start = total_data;
+                total_data = u32::try_from(range.len())
+                    .ok()
+                    .and_then(|len| start.checked_add(len))
+                    .ok_or_else(|| anyhow::anyhow!("more than 4 gigabytes of data in snapshot"))?;
+                let data_index = DataIndex::from_u32((num_data + initializers.len()) as u32);
+                module.active_segments.push(ActiveSegment::Data {
+                    index: data_index,
+                    memory_index: index,
+                    base: None,
+                    offset: range.start as u64,
+                    len: total_data - start,
+                });
+                initializers.push(MemoryInitializer {
+                    data_index,
+                    memory_index: index,
+                    base: None,
+                    offset: range.start as u64,
+                    data: start..total_data,
+                });
+                translation.data.push(data[range].to_vec().into());
+            }
+        }
+        module.memory_initialization = MemoryInitialization::Segmented(initializers);
+
+        let num_imported_globals = module.num_imported_globals;
+        let globals = module
+            .globals
+            .values_mut()
+            .skip(num_imported_globals)
+            .filter(|global| global.mutability);
+        for (global, value) in globals.zip(&self.globals) {
+            global.initializer = value.global_init()?;
+        }
+
+        // Function references are written into each table by a single
+        // segment. Tables of `externref`s can only be recreated if they hold
+        // nothing but null references, which is what they are created with.
+        let mut segments = Vec::new();
+        let tables = module
+            .table_plans
+            .keys()
+            .skip(module.num_imported_tables)
+            .collect::<Vec<_>>();
+        for (index, elements) in tables.into_iter().zip(&self.tables) {
+            let mut table = module.table_plans[index].table;
+            table.minimum = elements.len() as u32;
+            module.table_plans[index] = TablePlan::for_table(table, tunables);
+            if table.wasm_ty != WasmType::FuncRef {
+                for element in elements {
+                    element.global_init()?;
+                }
+                continue;
+            }
+            let elements = elements
+                .iter()
+                .map(|element| match element {
+                    SnapshotValue::Func(Some(index)) => *index,
+                    _ => FuncIndex::reserved_value(),
+                })
+                .collect::<Box<[_]>>();
+            let elem_index = ElemIndex::from_u32(segments.len() as u32);
+            module.active_segments.push(ActiveSegment::Elem {
+                index: elem_index,
+                table_index: index,
+                base: None,
+                offset: 0,
+                len: elements.len() as u32,
+            });
+            segments.push(TableInitializer {
+                elem_index,
+                table_index: index,
+                base: None,
+                offset: 0,
+                elements,
+            });
+        }
+        module.table_initialization = TableInitialization::Segments { segments };
+
+        // Passive segments which were dropped are left empty, as `data.drop`
+        // and `elem.drop` leave them, since their indices have to stay valid.
+        for index in &self.dropped_data {
+            if let Some(range) = module.passive_data_map.get_mut(index) {
+                *range = 0..0;
+            }
+        }
+        for index in &self.dropped_elements {
+            if let Some(i) = module.passive_elements_map.get(index) {
+                module.passive_elements[*i] = Box::new([]);
+            }
+        }
+
+        // The start function already ran before the snapshot was taken.
+        module.start_func = None;
+
+        Ok(())
+    }
+}
+
+/// Returns the ranges of `data` which aren't entirely zero, in units of
+/// 4 KiB, so that large zeroed areas of a memory stay out of its data segments.
+#[cfg(compiler)]
+fn nonzero_ranges(data: &[u8]) -> Vec<Range<usize>> {
+    const CHUNK: usize = 4096;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    for (i, chunk) in data.chunks(CHUNK).enumerate() {
+        if chunk.iter().all(|b| *b == 0) {
+            continue;
+        }
+        let start = i * CHUNK;
+        let end = start + chunk.len();
+        match ranges.last_mut() {
+            Some(last) if last.end == start => last.end = end,
+            _ => ranges.push(start..end),
+        }
+    }
+    ranges
+}
+
+impl SnapshotValue {
+    #[cfg(compiler)]
+    fn global_init(&self) -> Result<GlobalInit> {
+        Ok(match self {
+            SnapshotValue::Val(Val::I32(i)) => GlobalInit::I32Const(*i),
+            SnapshotValue::Val(Val::I64(i)) => GlobalInit::I64Const(*i),
+            SnapshotValue::Val(Val::F32(bits)) => GlobalInit::F32Const(*bits),
+            SnapshotValue::Val(Val::F64(bits)) => GlobalInit::F64Const(*bits),
+            SnapshotValue::Val(Val::V128(bits)) => GlobalInit::V128Const(*bits),
+            SnapshotValue::Val(Val::ExternRef(None)) | SnapshotValue::Func(None) => {
+                GlobalInit::RefNullConst
+            }
+            SnapshotValue::Func(Some(index)) => GlobalInit::RefFunc(*index),
+            SnapshotValue::Val(Val::ExternRef(Some(_))) => {
+                bail!("cannot pre-initialize a module which holds on to an `externref`")
+            }
+            SnapshotValue::Val(Val::FuncRef(_)) => unreachable!(),
+        })
+    }
+
+    fn restore(&self, store: &mut StoreOpaque, id: InstanceId) -> Val {
+        match self {
+            SnapshotValue::Val(val) => val.clone(),
+            SnapshotValue::Func(None) => Val::FuncRef(None),
+            SnapshotValue::Func(Some(index)) => unsafe {
+                let anyfunc = store.instance_mut(id).get_exported_func(*index).anyfunc;
+                Val::FuncRef(Func::from_caller_checked_anyfunc(store, anyfunc.as_ptr()))
+            },
+        }
+    }
+}
+
+/// A lazily-built map from the functions of an instance, identified by their
+/// code and `VMContext`, to their index in the module.
+struct FuncIndices {
+    id: InstanceId,
+    indices: Option<HashMap<(usize, usize), FuncIndex>>,
+}
+
+impl FuncIndices {
+    fn new(id: InstanceId) -> FuncIndices {
+        FuncIndices { id, indices: None }
+    }
+
+    fn capture(&mut self, store: &mut StoreOpaque, val: Val) -> Result<SnapshotValue> {
+        let func = match val {
+            Val::FuncRef(Some(func)) => func,
+            Val::FuncRef(None) => return Ok(SnapshotValue::Func(None)),
+            val => return Ok(SnapshotValue::Val(val)),
+        };
+        let id = self.id;
+        let indices = self.indices.get_or_insert_with(|| {
+            let mut indices = HashMap::new();
+            let module = store.instance(id).module().clone();
+            for index in module.functions.keys() {
+                let anyfunc = store.instance_mut(id).get_exported_func(index).anyfunc;
+                indices.entry(anyfunc_key(anyfunc)).or_insert(index);
+            }
+            indices
+        });
+        match indices.get(&anyfunc_key(func.caller_checked_anyfunc(store))) {
+            Some(index) => Ok(SnapshotValue::Func(Some(*index))),
+            None => bail!("cannot snapshot a reference to a function from outside the instance"),
+        }
+    }
+}
+
+fn anyfunc_key(
+    anyfunc: std::ptr::NonNull<wasmtime_runtime::VMCallerCheckedAnyfunc>,
+) -> (usize, usize) {
+    let anyfunc = unsafe { anyfunc.as_ref() };
+    (anyfunc.func_ptr.as_ptr() as usize, anyfunc.vmctx as usize)
+}
+
+impl fmt::Debug for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Snapshot")
+            .field("memories", &self.memories.len())
+            .field("globals", &self.globals.len())
+            .field("tables", &self.tables.len())
+            .finish()
+    }
+}
@@ -76,10 +76,19 @@
 //! contents of `StoreOpaque`. This is an invariant that we, as the authors of
 //! `wasmtime`, must uphold for the public interface to be safe.
 
+use crate::debug::DebugState;
+use crate::integer_overflow::IntegerOverflowCallback;
 use crate::linker::Definition;
+use crate::memory_access::MemoryAccessCallback;
 use crate::module::BareModuleInfo;
-use crate::{module::ModuleRegistry, Engine, Module, Trap, Val, ValRaw};
-use anyhow::{bail, Result};
+use crate::r#ref::ReclaimedExternRefs;
+use crate::{
+    module::ModuleRegistry, DebugAction, DebugFrame, Engine, InstructionCounts, IntegerOverflow,
+    IntegerOverflowKind, MemoryAccess, MemoryAccessKind, MemoryGrowFailure, Module, Trap, Val,
+    ValRaw, ValType,
+};
+use anyhow::{anyhow, bail, Result};
+use std::any::{Any, TypeId};
 use std::cell::UnsafeCell;
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -87,23 +96,29 @@ use std::fmt;
 use std::future::Future;
 use std::marker;
 use std::mem::{self, ManuallyDrop};
+use std::num::NonZeroU64;
 use std::ops::{Deref, DerefMut};
 use std::pin::Pin;
 use std::ptr;
 use std::sync::atomic::AtomicU64;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::Duration;
 use wasmtime_runtime::{
-    InstanceAllocationRequest, InstanceAllocator, InstanceHandle, ModuleInfo,
-    OnDemandInstanceAllocator, SignalHandler, StorePtr, VMCallerCheckedAnyfunc, VMContext,
-    VMExternRef, VMExternRefActivationsTable, VMRuntimeLimits, VMSharedSignatureIndex,
-    VMTrampoline,
+    CompiledModuleId, InstanceAllocationRequest, InstanceAllocator, InstanceHandle, ModuleInfo,
+    OnDemandInstanceAllocator, ProtectionKey, ProtectionMask, SignalHandler, StorePtr,
+    VMCallerCheckedAnyfunc, VMContext, VMExternRef, VMExternRefActivationsTable, VMRuntimeLimits,
+    VMSharedSignatureIndex, VMTrampoline,
 };
 
 mod context;
 pub use self::context::*;
 mod data;
 pub use self::data::*;
+mod cpu_time;
+use self::cpu_time::CpuTimer;
+mod gc_stats;
+pub use self::gc_stats::GcStats;
 
 /// A [`Store`] is a collection of WebAssembly instances and host-defined state.
 ///
@@ -202,10 +217,19 @@ pub struct StoreInner<T> {
 
     limiter: Option<ResourceLimiterInner<T>>,
     call_hook: Option<CallHookInner<T>>,
+    epoch_deadline_behavior: EpochDeadline<T>,
+    memory_grow_failure_behavior: MemoryGrowFailureBehavior<T>,
+    debug: DebugState<T>,
+    memory_access_callback: Option<MemoryAccessCallback<T>>,
+    integer_overflow_callback: Option<IntegerOverflowCallback<T>>,
+    externref_drop_hooks: HashMap<TypeId, ExternRefDropHook<T>>,
+    reclaimed_externrefs: ReclaimedExternRefs,
     // for comments about `ManuallyDrop`, see `Store::into_data`
     data: ManuallyDrop<T>,
 }
 
+type ExternRefDropHook<T> = Box<dyn FnMut(&mut T, Box<dyn Any + Send + Sync>) + Send + Sync>;
+
 enum ResourceLimiterInner<T> {
     Sync(Box<dyn FnMut(&mut T) -> &mut (dyn crate::ResourceLimiter) + Send + Sync>),
     #[cfg(feature = "async")]
@@ -293,10 +317,23 @@ pub struct StoreOpaque {
     /// An adjustment to add to the fuel consumed value in `runtime_limits` above
     /// to get the true amount of fuel consumed.
     fuel_adj: i64,
+    /// Fuel added to this store which hasn't yet been handed to wasm in
+    /// `runtime_limits`, because wasm is only given up to
+    /// `fuel_yield_interval` at a time.
+    fuel_reserve: u64,
+    fuel_yield_interval: Option<NonZeroU64>,
+    /// The stack space wasm is given when it's entered from the host, which
+    /// defaults to `Config::max_wasm_stack`.
+    max_wasm_stack: usize,
     #[cfg(feature = "async")]
     async_state: AsyncState,
     out_of_gas_behavior: OutOfGas,
-    epoch_deadline_behavior: EpochDeadline,
+    /// Counts of instructions executed by modules compiled with instruction
+    /// counting, keyed by module.
+    instruction_counts: HashMap<CompiledModuleId, InstructionCounts>,
+    /// The CPU time consumed by wasm, if enabled with
+    /// `Store::track_cpu_time`.
+    cpu_time: CpuTimer,
     /// Indexed data within this `Store`, used to store information about
     /// globals, functions, memories, etc.
     ///
@@ -327,6 +364,10 @@ pub struct StoreOpaque {
     /// Note that this is `ManuallyDrop` as it must be dropped after
     /// `store_data` above, where the function pointers are stored.
     rooted_host_funcs: ManuallyDrop<Vec<Arc<[Definition]>>>,
+
+    /// The memory protection key that this store's memories are protected
+    /// with, if the pooling allocator stripes memories across keys.
+    pkey: Option<ProtectionKey>,
 }
 
 #[cfg(feature = "async")]
@@ -424,18 +465,38 @@ enum OutOfGas {
     },
 }
 
+type EpochDeadlineCallback<T> = Box<dyn FnMut(StoreContextMut<'_, T>) -> Result<u64> + Send + Sync>;
+
 /// What to do when the engine epoch reaches the deadline for a Store
 /// during execution of a function using that store.
-#[derive(Copy, Clone)]
-enum EpochDeadline {
+enum EpochDeadline<T> {
     /// Return early with a trap.
     Trap,
+    /// Call a custom deadline handler.
+    Callback(EpochDeadlineCallback<T>),
+    /// The custom deadline handler is running, and is put back once it
+    /// returns unless it configured another behavior. Reaching the deadline
+    /// in the meantime traps.
+    RunningCallback,
     /// Extend the deadline by the specified number of ticks after
     /// yielding to the async executor loop.
     #[cfg(feature = "async")]
     YieldAndExtendDeadline { delta: u64 },
 }
 
+type MemoryGrowFailureCallback<T> =
+    Box<dyn FnMut(&mut T, &MemoryGrowFailure<'_>) -> Result<bool> + Send + Sync>;
+
+/// What to do when growing a linear memory of a Store fails.
+enum MemoryGrowFailureBehavior<T> {
+    /// Return failure to wasm, as the spec says.
+    ReturnFailure,
+    /// Return early with a trap.
+    Trap,
+    /// Call a custom handler, which may free resources and retry.
+    Callback(MemoryGrowFailureCallback<T>),
+}
+
 impl<T> Store<T> {
     /// Creates a new [`Store`] to be associated with the given [`Engine`] and
     /// `data` provided.
@@ -463,10 +524,19 @@ impl<T> Store<T> {
                     imports: Default::default(),
                     store: StorePtr::empty(),
                     runtime_info: &shim,
+                    pkey: None,
                 })
                 .expect("failed to allocate default callee")
         };
 
+        // Stores whose memories are protected with a key allow the current
+        // thread to access them in case it was spawned before the key was
+        // allocated.
+        let pkey = engine.allocator().next_available_pkey();
+        if pkey.is_some() {
+            ProtectionMask::all().allow();
+        }
+
         let mut inner = Box::new(StoreInner {
             inner: StoreOpaque {
                 _marker: marker::PhantomPinned,
@@ -485,21 +555,33 @@ impl<T> Store<T> {
                 table_count: 0,
                 table_limit: crate::DEFAULT_TABLE_LIMIT,
                 fuel_adj: 0,
+                fuel_reserve: 0,
+                fuel_yield_interval: None,
+                max_wasm_stack: engine.config().max_wasm_stack,
                 #[cfg(feature = "async")]
                 async_state: AsyncState {
                     current_suspend: UnsafeCell::new(ptr::null()),
                     current_poll_cx: UnsafeCell::new(ptr::null_mut()),
                 },
                 out_of_gas_behavior: OutOfGas::Trap,
-                epoch_deadline_behavior: EpochDeadline::Trap,
+                instruction_counts: HashMap::new(),
+                cpu_time: CpuTimer::default(),
                 store_data: ManuallyDrop::new(StoreData::new()),
                 default_callee,
                 hostcall_val_storage: Vec::new(),
                 wasm_val_raw_storage: Vec::new(),
                 rooted_host_funcs: ManuallyDrop::new(Vec::new()),
+                pkey,
             },
             limiter: None,
             call_hook: None,
+            epoch_deadline_behavior: EpochDeadline::Trap,
+            memory_grow_failure_behavior: MemoryGrowFailureBehavior::ReturnFailure,
+            debug: DebugState::new(),
+            memory_access_callback: None,
+            integer_overflow_callback: None,
+            externref_drop_hooks: HashMap::new(),
+            reclaimed_externrefs: Default::default(),
             data: ManuallyDrop::new(data),
         });
 
@@ -692,8 +774,106 @@ impl<T> Store<T> {
     /// Note that it is not required to actively call this function. GC will
     /// automatically happen when internal buffers fill up. This is provided if
     /// fine-grained control over the GC is desired.
+    ///
+    /// Once collected, the values of [`ExternRef`]s which were reclaimed
+    /// since the last call are handed to their hooks registered with
+    /// [`Store::externref_drop_hook`].
+    ///
+    /// [`ExternRef`]: crate::ExternRef
     pub fn gc(&mut self) {
-        self.inner.gc()
+        self.inner.gc();
+        self.inner.run_externref_drop_hooks();
+    }
+
+    /// Returns statistics about the [`ExternRef`]s this store holds and
+    /// about its collections so far.
+    ///
+    /// [`ExternRef`]: crate::ExternRef
+    pub fn gc_stats(&self) -> GcStats {
+        GcStats::new(&self.inner)
+    }
+
+    /// Configures how many [`ExternRef`]s WebAssembly can use from its
+    /// stack after a collection before the next collection happens
+    /// automatically.
+    ///
+    /// Each reference WebAssembly passes to or receives from the host, or
+    /// loads from a table or global, is kept alive until the next
+    /// collection, since it could still be on WebAssembly's stack. A lower
+    /// threshold reclaims those which aren't sooner, and bounds how long a
+    /// collection takes, at the cost of collecting more often. Each
+    /// reference used takes a word of memory until the next collection.
+    ///
+    /// The threshold takes effect from the next collection on, including
+    /// for the first collection of a store which hasn't collected yet, which
+    /// happens as soon as WebAssembly uses a reference. Calling
+    /// [`Store::gc`] applies it right away. Defaults to as many references as
+    /// fit in 4KiB, 512 on 64-bit platforms, and a threshold of zero is
+    /// treated as one.
+    ///
+    /// [`ExternRef`]: crate::ExternRef
+    pub fn externref_gc_threshold(&mut self, refs: usize) {
+        self.inner
+            .externref_activations_table
+            .set_gc_threshold(refs);
+    }
+
+    /// Configures a hook to call with the value of each [`ExternRef`] of
+    /// type `R` created with [`ExternRef::new_in`] once the reference is
+    /// reclaimed, replacing any previous hook for `R`.
+    ///
+    /// The hook is given mutable access to the store's data along with the
+    /// value, such as to release a resource which the store holds for it,
+    /// and is called by [`Store::gc`] for the references reclaimed since its
+    /// previous call. Collections which happen automatically while
+    /// WebAssembly runs reclaim references too, but leave their values for
+    /// the next call to `gc`.
+    ///
+    /// Only references created after the hook is registered are handed to
+    /// it; see [`ExternRef::new_in`] for an example.
+    ///
+    /// [`ExternRef`]: crate::ExternRef
+    /// [`ExternRef::new_in`]: crate::ExternRef::new_in
+    pub fn externref_drop_hook<R>(
+        &mut self,
+        mut hook: impl FnMut(&mut T, R) + Send + Sync + 'static,
+    ) where
+        R: Any + Send + Sync,
+    {
+        self.inner.externref_drop_hooks.insert(
+            TypeId::of::<R>(),
+            Box::new(move |data, value| match value.downcast::<R>() {
+                Ok(value) => hook(data, *value),
+                Err(_) => unreachable!(),
+            }),
+        );
+    }
+
+    /// Configures the maximum amount of stack space available to
+    /// WebAssembly executing in this store, overriding
+    /// [`Config::max_wasm_stack`](crate::Config::max_wasm_stack).
+    ///
+    /// WebAssembly which takes more stack space than `size` raises a stack
+    /// overflow trap, as with the engine-wide setting. This can be used to
+    /// give untrusted guests less stack than others sharing the same
+    /// [`Engine`], for example, or to give a guest which is known to recurse
+    /// deeply more. The new limit applies from the next time WebAssembly is
+    /// entered from the host with no WebAssembly of this store already on the
+    /// stack.
+    ///
+    /// Note that without [async support](crate::Config::async_support)
+    /// WebAssembly executes on the stack of the thread calling it, so `size`
+    /// must leave room on that thread's stack for the host functions called
+    /// by WebAssembly, just like [`Config::max_wasm_stack`].
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if `size` is zero or, for a store
+    /// with async support, if `size` exceeds
+    /// [`Config::async_stack_size`](crate::Config::async_stack_size), the
+    /// size of the stacks WebAssembly executes on.
+    pub fn max_wasm_stack(&mut self, size: usize) -> Result<()> {
+        self.inner.set_max_wasm_stack(size)
     }
 
     /// Returns the amount of fuel consumed by this store's execution so far.
@@ -753,6 +933,54 @@ impl<T> Store<T> {
         self.inner.consume_fuel(fuel)
     }
 
+    /// Configures whether this store tracks the CPU time its WebAssembly
+    /// executes for, as returned by [`Store::cpu_time_consumed`].
+    ///
+    /// Fuel measures the abstract cost of the instructions WebAssembly
+    /// executes, while this measures the CPU time of the threads executing
+    /// it, as the operating system accounts it to them, such as to bill each
+    /// tenant of a host for the CPU seconds of its guests. The time is
+    /// accumulated from each entry into WebAssembly until control returns to
+    /// the host, whether by returning or by calling a host function, so host
+    /// functions and [`Store::call_hook`] don't count towards it. With
+    /// [async support](crate::Config::async_support) the time WebAssembly
+    /// spends suspended doesn't count either, and WebAssembly which resumes
+    /// on a different thread is timed there.
+    ///
+    /// Enabling tracking starts its count from zero, unless the store
+    /// already tracks its CPU time, and disabling it discards the count. It
+    /// is disabled by default, as reading the thread's CPU time on every
+    /// transition between the host and WebAssembly has a cost.
+    pub fn track_cpu_time(&mut self, enable: bool) {
+        self.inner.cpu_time.enable(enable);
+    }
+
+    /// Returns the CPU time this store's WebAssembly has executed for, or
+    /// `None` if the store doesn't track it.
+    ///
+    /// For more information see [`Store::track_cpu_time`].
+    pub fn cpu_time_consumed(&self) -> Option<Duration> {
+        self.inner.cpu_time.consumed()
+    }
+
+    /// Returns the counts of instructions executed so far by instances of
+    /// `module` within this store.
+    ///
+    /// Instructions are only counted when `module` is compiled with
+    /// [`Config::count_instructions`](crate::Config::count_instructions)
+    /// enabled, and this returns `None` if `module` hasn't executed any in
+    /// this store since counts were last reset with
+    /// [`Store::reset_instruction_counts`].
+    pub fn instruction_counts(&self, module: &Module) -> Option<&InstructionCounts> {
+        self.inner.instruction_counts(module)
+    }
+
+    /// Resets the counts of instructions executed by all modules within this
+    /// store, for example to measure a single call into WebAssembly.
+    pub fn reset_instruction_counts(&mut self) {
+        self.inner.instruction_counts.clear();
+    }
+
     /// Configures a [`Store`] to generate a [`Trap`] whenever it runs out of
     /// fuel.
     ///
@@ -806,6 +1034,39 @@ impl<T> Store<T> {
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
 
+    /// Configures a [`Store`] to yield execution of async WebAssembly code
+    /// every `interval` units of fuel it consumes.
+    ///
+    /// When a [`Store`] is configured to consume fuel with
+    /// [`Config::consume_fuel`](crate::Config::consume_fuel) this method
+    /// hands the fuel added with [`Store::add_fuel`] to WebAssembly at most
+    /// `interval` units at a time. Each time WebAssembly consumes that much,
+    /// its future yields back to the async executor, flagging itself to be
+    /// re-polled, and then continues with the next `interval` units of the
+    /// store's fuel. This keeps a single compute-heavy guest from starving
+    /// the other tasks of an executor while still trapping once all of the
+    /// store's fuel is consumed, or refueling as configured with
+    /// [`Store::out_of_fuel_async_yield`].
+    ///
+    /// Passing `None` turns yielding off again, so that all of the store's
+    /// fuel is handed to WebAssembly at once. The amounts reported by
+    /// [`Store::fuel_consumed`] and [`Store::consume_fuel`] are the same
+    /// either way.
+    ///
+    /// This is the fuel-based counterpart of
+    /// [`Store::epoch_deadline_async_yield_and_update`], which yields based on
+    /// wall-clock time instead, and the two can be used together.
+    ///
+    /// # Errors
+    ///
+    /// This method will return an error if the store's
+    /// [`Config`](crate::Config) doesn't have both fuel consumption and
+    /// [async support](crate::Config::async_support) enabled, or if
+    /// `interval` is `Some(0)`.
+    pub fn fuel_async_yield_interval(&mut self, interval: Option<u64>) -> Result<()> {
+        self.inner.fuel_async_yield_interval(interval)
+    }
+
     /// Sets the epoch deadline to a certain number of ticks in the future.
     ///
     /// When the Wasm guest code is compiled with epoch-interruption
@@ -857,6 +1118,33 @@ impl<T> Store<T> {
         self.inner.epoch_deadline_trap();
     }
 
+    /// Configures epoch-deadline expiration to invoke a custom callback
+    /// function.
+    ///
+    /// When epoch-interruption-instrumented code is executed on this
+    /// store and the epoch deadline is reached before completion, the
+    /// provided callback function is invoked with access to the store.
+    ///
+    /// The callback may either return an error, in which case execution
+    /// terminates with that error as a trap, or an `Ok(delta)` to resume
+    /// execution with a new epoch deadline equal to the current epoch plus
+    /// `delta` ticks.
+    ///
+    /// This setting is intended to allow the embedder to observe the guest
+    /// periodically while it is running, for example to sample its stack
+    /// with a [`GuestProfiler`](crate::GuestProfiler) or to decide
+    /// dynamically whether it should be interrupted.
+    ///
+    /// See documentation on
+    /// [`Config::epoch_interruption()`](crate::Config::epoch_interruption)
+    /// for an introduction to epoch-based interruption.
+    pub fn epoch_deadline_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>) -> Result<u64> + Send + Sync + 'static,
+    ) {
+        self.inner.epoch_deadline_callback(Box::new(callback));
+    }
+
     #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
     /// Configures epoch-deadline expiration to yield to the async
     /// caller and the update the deadline.
@@ -885,6 +1173,153 @@ impl<T> Store<T> {
     pub fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
         self.inner.epoch_deadline_async_yield_and_update(delta);
     }
+
+    /// Configures a handler to call whenever WebAssembly executing in this
+    /// store pauses at a breakpoint or while single-stepping.
+    ///
+    /// Pausing is only possible in modules compiled with guest debugging (see
+    /// [`Config::guest_debug`](crate::Config::guest_debug)), and happens
+    /// before an instruction executes:
+    ///
+    /// * when the instruction is at a breakpoint set with
+    ///   [`Store::set_breakpoint`], or
+    /// * when single-stepping, enabled either with [`Store::single_step`] or
+    ///   by the handler returning [`DebugAction::Step`].
+    ///
+    /// The handler is given mutable access to the store, so it may for
+    /// example inspect memories or set and clear breakpoints, along with a
+    /// [`DebugFrame`] describing the paused function's locals and the call
+    /// stack. Execution resumes once the handler returns, continuing to the
+    /// next breakpoint with [`DebugAction::Continue`] or to the next
+    /// instruction with [`DebugAction::Step`]. If the handler returns an error
+    /// then execution terminates with that error as a trap.
+    ///
+    /// While the handler is running WebAssembly it calls doesn't pause.
+    pub fn debug_handler(
+        &mut self,
+        handler: impl FnMut(StoreContextMut<'_, T>, &DebugFrame) -> Result<DebugAction>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.inner.debug.handler = Some(Box::new(handler));
+    }
+
+    /// Sets a breakpoint before the instruction at `offset` within the
+    /// original wasm of `module`.
+    ///
+    /// When any instance of `module` in this store is about to execute the
+    /// instruction at `offset` then execution pauses and the handler
+    /// configured with [`Store::debug_handler`] is called. Offsets are the
+    /// same as those reported by [`FrameInfo::module_offset`] and
+    /// [`DebugFrame::module_offset`], and offsets which aren't the start of an
+    /// instruction are never reached.
+    ///
+    /// This has no effect unless `module` was compiled with guest debugging
+    /// (see [`Config::guest_debug`](crate::Config::guest_debug)).
+    pub fn set_breakpoint(&mut self, module: &Module, offset: usize) {
+        self.inner.debug.set_breakpoint(module, offset);
+    }
+
+    /// Removes a breakpoint previously set with [`Store::set_breakpoint`].
+    pub fn clear_breakpoint(&mut self, module: &Module, offset: usize) {
+        self.inner.debug.clear_breakpoint(module, offset);
+    }
+
+    /// Configures whether WebAssembly executing in this store pauses before
+    /// every instruction.
+    ///
+    /// This is typically enabled before calling into WebAssembly to pause at
+    /// its first instruction. The value returned from the debug handler then
+    /// controls whether stepping continues. See [`Store::debug_handler`] for
+    /// more information.
+    pub fn single_step(&mut self, enable: bool) {
+        self.inner.debug.single_step(enable);
+    }
+
+    /// Configures a callback to call after each load from or store to linear
+    /// memory made by WebAssembly executing in this store.
+    ///
+    /// Accesses are only reported by modules compiled with
+    /// [`Config::trace_memory_accesses`](crate::Config::trace_memory_accesses)
+    /// enabled. The callback is given mutable access to the store along with
+    /// a [`MemoryAccess`] describing the access, its address and the bytes
+    /// loaded or stored. If the callback returns an error then execution
+    /// terminates with that error as a trap.
+    ///
+    /// While the callback is running accesses made by WebAssembly it calls
+    /// aren't reported.
+    pub fn memory_access_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>, &MemoryAccess) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.inner.memory_access_callback = Some(Box::new(callback));
+    }
+
+    /// Configures a callback to call when integer arithmetic made by
+    /// WebAssembly executing in this store overflows.
+    ///
+    /// Overflows are only reported by modules compiled with
+    /// [`Config::check_integer_overflow`](crate::Config::check_integer_overflow)
+    /// enabled. The callback is given mutable access to the store along with
+    /// an [`IntegerOverflow`] describing the operation which overflowed. If
+    /// the callback returns `Ok` then execution continues with the wrapped
+    /// result of the operation, as though it hadn't been checked, and if it
+    /// returns an error then execution terminates with that error as a trap.
+    ///
+    /// Without a callback, overflows trap with
+    /// [`TrapCode::IntegerOverflow`](crate::TrapCode::IntegerOverflow).
+    pub fn integer_overflow_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>, &IntegerOverflow) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.inner.integer_overflow_callback = Some(Box::new(callback));
+    }
+
+    /// Configures linear memories which fail to grow to trap.
+    ///
+    /// Growing a memory with `memory.grow` fails when the store's
+    /// [resource limiter](Store::limiter) rejects it or when the host fails
+    /// to allocate the memory, such as when the process is out of memory. By
+    /// default `memory.grow` then returns -1 to WebAssembly, as the
+    /// specification says, leaving it to the guest to cope. Once this is
+    /// configured WebAssembly traps with
+    /// [`TrapCode::MemoryGrowFailed`](crate::TrapCode::MemoryGrowFailed)
+    /// instead, which tells running out of memory apart from the guest's
+    /// own errors, and [`Memory::grow`](crate::Memory::grow) returns that
+    /// trap as its error. Growing a memory past its maximum size still
+    /// returns -1.
+    pub fn memory_grow_failure_trap(&mut self) {
+        self.inner.memory_grow_failure_behavior = MemoryGrowFailureBehavior::Trap;
+    }
+
+    /// Configures a callback to call when a linear memory fails to grow.
+    ///
+    /// The callback is called for the same failures as
+    /// [`Store::memory_grow_failure_trap`] traps for, and is given mutable
+    /// access to the store's data along with a [`MemoryGrowFailure`]
+    /// describing the failure. The callback may free resources of the host,
+    /// such as by evicting caches, and return `Ok(true)` to try growing the
+    /// memory again, which asks the resource limiter again as well. It
+    /// returns `Ok(false)` for `memory.grow` to return -1, and an error to
+    /// terminate execution with that error as a trap.
+    ///
+    /// The callback is called again each time a retry fails, so it has to
+    /// give up eventually. While it runs the memory which failed to grow
+    /// can't be accessed, which is why it's only given the store's data.
+    pub fn memory_grow_failure_callback(
+        &mut self,
+        callback: impl FnMut(&mut T, &MemoryGrowFailure<'_>) -> Result<bool> + Send + Sync + 'static,
+    ) {
+        self.inner.memory_grow_failure_behavior =
+            MemoryGrowFailureBehavior::Callback(Box::new(callback));
+    }
 }
 
 impl<'a, T> StoreContext<'a, T> {
@@ -910,6 +1345,27 @@ impl<'a, T> StoreContext<'a, T> {
     pub fn fuel_consumed(&self) -> Option<u64> {
         self.0.fuel_consumed()
     }
+
+    /// Returns the CPU time consumed by this store's WebAssembly.
+    ///
+    /// For more information see [`Store::cpu_time_consumed`].
+    pub fn cpu_time_consumed(&self) -> Option<Duration> {
+        self.0.cpu_time.consumed()
+    }
+
+    /// Returns statistics about the `ExternRef`s held by this store.
+    ///
+    /// For more information see [`Store::gc_stats`].
+    pub fn gc_stats(&self) -> GcStats {
+        GcStats::new(self.0)
+    }
+
+    /// Returns the counts of instructions executed by instances of `module`.
+    ///
+    /// For more information see [`Store::instruction_counts`].
+    pub fn instruction_counts(&self, module: &Module) -> Option<&'a InstructionCounts> {
+        self.0.instruction_counts(module)
+    }
 }
 
 impl<'a, T> StoreContextMut<'a, T> {
@@ -936,7 +1392,31 @@ impl<'a, T> StoreContextMut<'a, T> {
     ///
     /// Same as [`Store::gc`].
     pub fn gc(&mut self) {
-        self.0.gc()
+        self.0.gc();
+        self.0.run_externref_drop_hooks();
+    }
+
+    /// Returns statistics about the `ExternRef`s held by this store.
+    ///
+    /// For more information see [`Store::gc_stats`].
+    pub fn gc_stats(&self) -> GcStats {
+        GcStats::new(self.0)
+    }
+
+    /// Configures how many `ExternRef`s WebAssembly can use before the next
+    /// automatic collection.
+    ///
+    /// For more information see [`Store::externref_gc_threshold`].
+    pub fn externref_gc_threshold(&mut self, refs: usize) {
+        self.0.externref_activations_table.set_gc_threshold(refs);
+    }
+
+    /// Configures the maximum amount of stack space available to
+    /// WebAssembly executing in this store.
+    ///
+    /// For more information see [`Store::max_wasm_stack`].
+    pub fn max_wasm_stack(&mut self, size: usize) -> Result<()> {
+        self.0.set_max_wasm_stack(size)
     }
 
     /// Returns the fuel consumed by this store.
@@ -946,6 +1426,13 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.fuel_consumed()
     }
 
+    /// Returns the CPU time consumed by this store's WebAssembly.
+    ///
+    /// For more information see [`Store::cpu_time_consumed`].
+    pub fn cpu_time_consumed(&self) -> Option<Duration> {
+        self.0.cpu_time.consumed()
+    }
+
     /// Inject more fuel into this store to be consumed when executing wasm code.
     ///
     /// For more information see [`Store::add_fuel`]
@@ -960,6 +1447,20 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.consume_fuel(fuel)
     }
 
+    /// Returns the counts of instructions executed by instances of `module`.
+    ///
+    /// For more information see [`Store::instruction_counts`].
+    pub fn instruction_counts(&self, module: &Module) -> Option<&InstructionCounts> {
+        self.0.instruction_counts(module)
+    }
+
+    /// Resets the counts of instructions executed by all modules.
+    ///
+    /// For more information see [`Store::reset_instruction_counts`].
+    pub fn reset_instruction_counts(&mut self) {
+        self.0.instruction_counts.clear();
+    }
+
     /// Configures this `Store` to trap whenever fuel runs out.
     ///
     /// For more information see [`Store::out_of_fuel_trap`]
@@ -976,6 +1477,14 @@ impl<'a, T> StoreContextMut<'a, T> {
             .out_of_fuel_async_yield(injection_count, fuel_to_inject)
     }
 
+    /// Configures this `Store` to yield while executing futures every
+    /// `interval` units of fuel.
+    ///
+    /// For more information see [`Store::fuel_async_yield_interval`]
+    pub fn fuel_async_yield_interval(&mut self, interval: Option<u64>) -> Result<()> {
+        self.0.fuel_async_yield_interval(interval)
+    }
+
     /// Sets the epoch deadline to a certain number of ticks in the future.
     ///
     /// For more information see [`Store::set_epoch_deadline`].
@@ -990,6 +1499,17 @@ impl<'a, T> StoreContextMut<'a, T> {
         self.0.epoch_deadline_trap();
     }
 
+    /// Configures epoch-deadline expiration to invoke a custom callback
+    /// function.
+    ///
+    /// For more information see [`Store::epoch_deadline_callback`].
+    pub fn epoch_deadline_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>) -> Result<u64> + Send + Sync + 'static,
+    ) {
+        self.0.epoch_deadline_callback(Box::new(callback));
+    }
+
     #[cfg_attr(nightlydoc, doc(cfg(feature = "async")))]
     /// Configures epoch-deadline expiration to yield to the async
     /// caller and the update the deadline.
@@ -1000,6 +1520,85 @@ impl<'a, T> StoreContextMut<'a, T> {
     pub fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
         self.0.epoch_deadline_async_yield_and_update(delta);
     }
+
+    /// Configures a handler to call whenever WebAssembly pauses at a
+    /// breakpoint or while single-stepping.
+    ///
+    /// For more information see [`Store::debug_handler`].
+    pub fn debug_handler(
+        &mut self,
+        handler: impl FnMut(StoreContextMut<'_, T>, &DebugFrame) -> Result<DebugAction>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.0.debug.handler = Some(Box::new(handler));
+    }
+
+    /// Sets a breakpoint before the instruction at `offset` within `module`.
+    ///
+    /// For more information see [`Store::set_breakpoint`].
+    pub fn set_breakpoint(&mut self, module: &Module, offset: usize) {
+        self.0.debug.set_breakpoint(module, offset);
+    }
+
+    /// Removes a breakpoint previously set with
+    /// [`StoreContextMut::set_breakpoint`].
+    pub fn clear_breakpoint(&mut self, module: &Module, offset: usize) {
+        self.0.debug.clear_breakpoint(module, offset);
+    }
+
+    /// Configures whether WebAssembly pauses before every instruction.
+    ///
+    /// For more information see [`Store::single_step`].
+    pub fn single_step(&mut self, enable: bool) {
+        self.0.debug.single_step(enable);
+    }
+
+    /// Configures a callback to call after each load from or store to linear
+    /// memory.
+    ///
+    /// For more information see [`Store::memory_access_callback`].
+    pub fn memory_access_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>, &MemoryAccess) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.0.memory_access_callback = Some(Box::new(callback));
+    }
+
+    /// Configures a callback to call when integer arithmetic overflows.
+    ///
+    /// For more information see [`Store::integer_overflow_callback`].
+    pub fn integer_overflow_callback(
+        &mut self,
+        callback: impl FnMut(StoreContextMut<'_, T>, &IntegerOverflow) -> Result<()>
+            + Send
+            + Sync
+            + 'static,
+    ) {
+        self.0.integer_overflow_callback = Some(Box::new(callback));
+    }
+
+    /// Configures linear memories which fail to grow to trap.
+    ///
+    /// For more information see [`Store::memory_grow_failure_trap`].
+    pub fn memory_grow_failure_trap(&mut self) {
+        self.0.memory_grow_failure_behavior = MemoryGrowFailureBehavior::Trap;
+    }
+
+    /// Configures a callback to call when a linear memory fails to grow.
+    ///
+    /// For more information see [`Store::memory_grow_failure_callback`].
+    pub fn memory_grow_failure_callback(
+        &mut self,
+        callback: impl FnMut(&mut T, &MemoryGrowFailure<'_>) -> Result<bool> + Send + Sync + 'static,
+    ) {
+        self.0.memory_grow_failure_behavior =
+            MemoryGrowFailureBehavior::Callback(Box::new(callback));
+    }
 }
 
 impl<T> StoreInner<T> {
@@ -1008,13 +1607,43 @@ impl<T> StoreInner<T> {
         &self.data
     }
 
+    /// Returns where the values of `ExternRef`s of type `ty` are set aside
+    /// once reclaimed, if this store has a drop hook for them.
+    pub(crate) fn reclaimed_externrefs(&self, ty: TypeId) -> Option<ReclaimedExternRefs> {
+        if self.externref_drop_hooks.contains_key(&ty) {
+            Some(self.reclaimed_externrefs.clone())
+        } else {
+            None
+        }
+    }
+
+    fn run_externref_drop_hooks(&mut self) {
+        let reclaimed = mem::take(&mut *self.reclaimed_externrefs.lock().unwrap());
+        for value in reclaimed {
+            if let Some(hook) = self.externref_drop_hooks.get_mut(&(*value).type_id()) {
+                hook(&mut self.data, value);
+            }
+        }
+    }
+
     #[inline]
     fn data_mut(&mut self) -> &mut T {
         &mut self.data
     }
 
     pub fn call_hook(&mut self, s: CallHook) -> Result<(), Trap> {
-        match &mut self.call_hook {
+        // Host functions run with access to all memories, and wasm with only
+        // its own store's, see `invoke_wasm_and_catch_traps`.
+        let pkey = self.inner.pkey;
+        if let (Some(_), CallHook::CallingHost) = (pkey, s) {
+            ProtectionMask::all().allow();
+        }
+        // The hook itself runs on the host's time.
+        if s.entering_host() {
+            self.inner.cpu_time.enter_host();
+        }
+
+        let result = match &mut self.call_hook {
             Some(CallHookInner::Sync(hook)) => hook(&mut self.data, s),
 
             #[cfg(feature = "async")]
@@ -1027,7 +1656,15 @@ impl<T> StoreInner<T> {
             },
 
             None => Ok(()),
+        };
+
+        if let (Some(pkey), CallHook::ReturningFromHost) = (pkey, s) {
+            ProtectionMask::zero().or(pkey).allow();
+        }
+        if s.exiting_host() && result.is_ok() {
+            self.inner.cpu_time.enter_wasm();
         }
+        result
     }
 }
 
@@ -1087,7 +1724,27 @@ impl StoreOpaque {
         &mut self.modules
     }
 
+    #[inline]
+    pub(crate) fn modules(&self) -> &ModuleRegistry {
+        &self.modules
+    }
+
+    /// Returns the handles of all instances in this store which were created
+    /// by instantiating a `Module`, in the order they were created.
+    ///
+    /// This excludes instances created internally to host items such as
+    /// host-defined memories or globals.
+    pub fn module_instances(&self) -> impl Iterator<Item = &InstanceHandle> {
+        self.instances
+            .iter()
+            .filter(|i| !i.ondemand)
+            .map(|i| &i.handle)
+    }
+
     pub unsafe fn add_instance(&mut self, handle: InstanceHandle, ondemand: bool) -> InstanceId {
+        if !ondemand {
+            self.engine.metrics_counters().instances_created(1);
+        }
         self.instances.push(StoreInstance {
             handle: handle.clone(),
             ondemand,
@@ -1113,6 +1770,23 @@ impl StoreOpaque {
         &self.runtime_limits
     }
 
+    #[inline]
+    pub fn max_wasm_stack(&self) -> usize {
+        self.max_wasm_stack
+    }
+
+    fn set_max_wasm_stack(&mut self, size: usize) -> Result<()> {
+        if size == 0 {
+            bail!("wasm stack size cannot be zero");
+        }
+        #[cfg(feature = "async")]
+        if self.async_support() && size > self.engine().config().async_stack_size {
+            bail!("wasm stack size cannot exceed the async stack size");
+        }
+        self.max_wasm_stack = size;
+        Ok(())
+    }
+
     #[inline]
     pub fn externref_activations_table(&mut self) -> &mut VMExternRefActivationsTable {
         &mut self.externref_activations_table
@@ -1243,6 +1917,11 @@ impl StoreOpaque {
         Some(u64::try_from(self.fuel_adj + consumed).unwrap())
     }
 
+    pub fn instruction_counts(&self, module: &Module) -> Option<&InstructionCounts> {
+        self.instruction_counts
+            .get(&module.compiled_module().unique_id())
+    }
+
     fn out_of_fuel_trap(&mut self) {
         self.out_of_gas_behavior = OutOfGas::Trap;
     }
@@ -1258,6 +1937,26 @@ impl StoreOpaque {
         };
     }
 
+    fn fuel_async_yield_interval(&mut self, interval: Option<u64>) -> Result<()> {
+        anyhow::ensure!(
+            self.engine().config().tunables.consume_fuel,
+            "fuel is not configured in this store"
+        );
+        anyhow::ensure!(
+            self.async_support(),
+            "cannot use `fuel_async_yield_interval` without enabling async support in the config"
+        );
+        self.fuel_yield_interval = match interval {
+            Some(interval) => Some(
+                NonZeroU64::new(interval)
+                    .ok_or_else(|| anyhow!("fuel yield interval cannot be 0"))?,
+            ),
+            None => None,
+        };
+        self.refuel();
+        Ok(())
+    }
+
     /// Yields execution to the caller on out-of-gas or epoch interruption.
     ///
     /// This only works on async futures and stores, and assumes that we're
@@ -1309,62 +2008,55 @@ impl StoreOpaque {
             "fuel is not configured in this store"
         );
 
-        // Fuel is stored as an i64, so we need to cast it. If the provided fuel
-        // value overflows that just assume that i64::max will suffice. Wasm
-        // execution isn't fast enough to burn through i64::max fuel in any
-        // reasonable amount of time anyway.
-        let fuel = i64::try_from(fuel).unwrap_or(i64::max_value());
-        let adj = self.fuel_adj;
-        let consumed_ptr = unsafe { &mut *self.runtime_limits.fuel_consumed.get() };
-
-        match (consumed_ptr.checked_sub(fuel), adj.checked_add(fuel)) {
-            // If we succesfully did arithmetic without overflowing then we can
-            // just update our fields.
-            (Some(consumed), Some(adj)) => {
-                self.fuel_adj = adj;
-                *consumed_ptr = consumed;
-            }
-
-            // Otherwise something overflowed. Make sure that we preserve the
-            // amount of fuel that's already consumed, but otherwise assume that
-            // we were given infinite fuel.
-            _ => {
-                self.fuel_adj = i64::max_value();
-                *consumed_ptr = (*consumed_ptr + adj) - i64::max_value();
-            }
-        }
-
+        self.fuel_reserve = self.fuel_reserve.saturating_add(fuel);
+        self.refuel();
         Ok(())
     }
 
     fn consume_fuel(&mut self, fuel: u64) -> Result<u64> {
         let consumed_ptr = unsafe { &mut *self.runtime_limits.fuel_consumed.get() };
-        match i64::try_from(fuel)
-            .ok()
-            .and_then(|fuel| consumed_ptr.checked_add(fuel))
-        {
-            Some(consumed) if consumed < 0 => {
-                *consumed_ptr = consumed;
-                Ok(u64::try_from(-consumed).unwrap())
-            }
-            _ => bail!("not enough fuel remaining in store"),
+        let active = u64::try_from(-*consumed_ptr).unwrap_or(0);
+        let remaining = active.saturating_add(self.fuel_reserve);
+        if fuel >= remaining {
+            bail!("not enough fuel remaining in store");
         }
-    }
-
-    fn epoch_deadline_trap(&mut self) {
-        self.epoch_deadline_behavior = EpochDeadline::Trap;
-    }
 
-    fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
-        assert!(
-            self.async_support(),
-            "cannot use `epoch_deadline_async_yield_and_update` without enabling async support in the config"
-        );
-        #[cfg(feature = "async")]
-        {
-            self.epoch_deadline_behavior = EpochDeadline::YieldAndExtendDeadline { delta };
-        }
-        drop(delta); // suppress warning in non-async build
+        // Consume what wasm has been handed first, and then any of the
+        // reserve, which is counted as consumed by adjusting `fuel_adj`.
+        let from_active = fuel.min(active);
+        *consumed_ptr += i64::try_from(from_active).unwrap();
+        let from_reserve = fuel - from_active;
+        self.fuel_reserve -= from_reserve;
+        self.fuel_adj = self
+            .fuel_adj
+            .saturating_add(i64::try_from(from_reserve).unwrap_or(i64::max_value()));
+        self.refuel();
+        Ok(remaining - fuel)
+    }
+
+    /// Moves fuel between `fuel_reserve` and what wasm has been handed in
+    /// `runtime_limits`, so that wasm has as much as it's allowed at once.
+    ///
+    /// The fuel consumed and remaining is tracked as an i64, so if their
+    /// total overflows that just assume that i64::max will suffice. Wasm
+    /// execution isn't fast enough to burn through i64::max fuel in any
+    /// reasonable amount of time anyway.
+    fn refuel(&mut self) {
+        let consumed_ptr = unsafe { &mut *self.runtime_limits.fuel_consumed.get() };
+        let max = i128::from(i64::max_value());
+        let total_consumed = i128::from(self.fuel_adj) + i128::from(*consumed_ptr);
+        let available =
+            (i128::from(self.fuel_reserve) - i128::from(*consumed_ptr)).min(max - total_consumed);
+        let limit = self
+            .fuel_yield_interval
+            .map_or(max, |i| i128::from(i.get()));
+        let target = available.min(limit);
+        self.fuel_reserve = u64::try_from(available - target).unwrap();
+
+        // Preserve the amount of fuel that's already consumed, which is
+        // `fuel_adj` plus the consumed value in `runtime_limits`.
+        self.fuel_adj = i64::try_from(total_consumed + target).unwrap();
+        *consumed_ptr = i64::try_from(-target).unwrap();
     }
 
     #[inline]
@@ -1382,6 +2074,11 @@ impl StoreOpaque {
         self.externref_activations_table.insert_without_gc(r);
     }
 
+    #[inline]
+    pub fn pkey(&self) -> Option<ProtectionKey> {
+        self.pkey
+    }
+
     #[inline]
     pub fn default_callee(&self) -> *mut VMContext {
         self.default_callee.vmctx_ptr()
@@ -1455,6 +2152,7 @@ impl<T> StoreContextMut<'_, T> {
         let future = {
             let current_poll_cx = self.0.async_state.current_poll_cx.get();
             let current_suspend = self.0.async_state.current_suspend.get();
+            let cpu_time = self.0.cpu_time.as_ptr();
             let stack = self
                 .engine()
                 .allocator()
@@ -1492,6 +2190,7 @@ impl<T> StoreContextMut<'_, T> {
             FiberFuture {
                 fiber,
                 current_poll_cx,
+                cpu_time,
                 engine,
             }
         };
@@ -1502,16 +2201,37 @@ impl<T> StoreContextMut<'_, T> {
         struct FiberFuture<'a> {
             fiber: wasmtime_fiber::Fiber<'a, Result<(), Trap>, (), Result<(), Trap>>,
             current_poll_cx: *mut *mut Context<'static>,
+            cpu_time: *mut Option<cpu_time::CpuTime>,
             engine: Engine,
         }
 
+        impl FiberFuture<'_> {
+            /// Resumes the fiber, with the store's CPU timer only running
+            /// while the fiber does, as the fiber may be resumed on a
+            /// different thread each time and other work happens on the
+            /// thread while it's suspended.
+            fn resume(&mut self, val: Result<(), Trap>) -> Result<Result<(), Trap>, ()> {
+                unsafe {
+                    if let Some(cpu_time) = &mut *self.cpu_time {
+                        cpu_time.resume();
+                    }
+                    let result = self.fiber.resume(val);
+                    if let Some(cpu_time) = &mut *self.cpu_time {
+                        cpu_time.pause();
+                    }
+                    result
+                }
+            }
+        }
+
         // This is surely the most dangerous `unsafe impl Send` in the entire
-        // crate. There are two members in `FiberFuture` which cause it to not
-        // be `Send`. One is `current_poll_cx` and is entirely uninteresting.
-        // This is just used to manage `Context` pointers across `await` points
-        // in the future, and requires raw pointers to get it to happen easily.
-        // Nothing too weird about the `Send`-ness, values aren't actually
-        // crossing threads.
+        // crate. There are three members in `FiberFuture` which cause it to
+        // not be `Send`. One is `current_poll_cx` and is entirely
+        // uninteresting. This is just used to manage `Context` pointers across
+        // `await` points in the future, and requires raw pointers to get it to
+        // happen easily. Nothing too weird about the `Send`-ness, values aren't
+        // actually crossing threads. The same goes for `cpu_time`, which points
+        // into the store the fiber is executing.
         //
         // The really interesting piece is `fiber`. Now the "fiber" here is
         // actual honest-to-god Rust code which we're moving around. What we're
@@ -1598,7 +2318,7 @@ impl<T> StoreContextMut<'_, T> {
                     // `Err` with the payload passed to `suspend`, which in our case
                     // is `()`. If `Err` is returned that means the fiber polled a
                     // future but it said "Pending", so we propagate that here.
-                    match self.fiber.resume(Ok(())) {
+                    match self.resume(Ok(())) {
                         Ok(result) => Poll::Ready(result),
                         Err(()) => Poll::Pending,
                     }
@@ -1624,7 +2344,7 @@ impl<T> StoreContextMut<'_, T> {
         impl Drop for FiberFuture<'_> {
             fn drop(&mut self) {
                 if !self.fiber.done() {
-                    let result = self.fiber.resume(Err(Trap::new("future dropped")));
+                    let result = self.resume(Err(Trap::new("future dropped")));
                     // This resumption with an error should always complete the
                     // fiber. While it's technically possible for host code to catch
                     // the trap and re-resume, we'd ideally like to signal that to
@@ -1772,6 +2492,45 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
         }
     }
 
+    fn memory_grown(&mut self, old: usize, new: usize) {
+        match self.limiter {
+            Some(ResourceLimiterInner::Sync(ref mut limiter)) => {
+                limiter(&mut self.data).memory_grown(old, new)
+            }
+            #[cfg(feature = "async")]
+            Some(ResourceLimiterInner::Async(ref mut limiter)) => {
+                limiter(&mut self.data).memory_grown(old, new)
+            }
+            None => {}
+        }
+    }
+
+    fn memory_grow_failure(
+        &mut self,
+        current: usize,
+        desired: usize,
+        error: Option<anyhow::Error>,
+    ) -> Result<bool, anyhow::Error> {
+        match &mut self.memory_grow_failure_behavior {
+            MemoryGrowFailureBehavior::ReturnFailure => match error {
+                // Whether the host can allocate memory isn't deterministic.
+                Some(e) if self.inner.engine().config().deterministic => {
+                    Err(e.context("failed to grow linear memory"))
+                }
+                _ => Ok(false),
+            },
+            MemoryGrowFailureBehavior::Trap => Err(Trap::memory_grow_failed().into()),
+            MemoryGrowFailureBehavior::Callback(callback) => {
+                let failure = MemoryGrowFailure {
+                    current,
+                    desired,
+                    error: error.as_ref(),
+                };
+                callback(&mut self.data, &failure)
+            }
+        }
+    }
+
     fn table_growing(
         &mut self,
         current: u32,
@@ -1819,7 +2578,29 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
         }
     }
 
+    fn table_grown(&mut self, old: u32, new: u32) {
+        match self.limiter {
+            Some(ResourceLimiterInner::Sync(ref mut limiter)) => {
+                limiter(&mut self.data).table_grown(old, new)
+            }
+            #[cfg(feature = "async")]
+            Some(ResourceLimiterInner::Async(ref mut limiter)) => {
+                limiter(&mut self.data).table_grown(old, new)
+            }
+            None => {}
+        }
+    }
+
     fn out_of_gas(&mut self) -> Result<(), anyhow::Error> {
+        // If wasm was only handed part of the store's fuel then this is
+        // where it yields before being handed the next part.
+        #[cfg(feature = "async")]
+        if self.fuel_yield_interval.is_some() && self.fuel_reserve > 0 {
+            self.async_yield_impl()?;
+            self.refuel();
+            return Ok(());
+        }
+
         return match &mut self.out_of_gas_behavior {
             OutOfGas::Trap => Err(anyhow::Error::new(OutOfGasError)),
             #[cfg(feature = "async")]
@@ -1855,8 +2636,8 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
     }
 
     fn new_epoch(&mut self) -> Result<u64, anyhow::Error> {
-        return match &self.epoch_deadline_behavior {
-            &EpochDeadline::Trap => {
+        return match &mut self.epoch_deadline_behavior {
+            EpochDeadline::Trap | EpochDeadline::RunningCallback => {
                 let trap = Trap::new_wasm(
                     None,
                     wasmtime_environ::TrapCode::Interrupt,
@@ -1864,8 +2645,29 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
                 );
                 Err(anyhow::Error::from(trap))
             }
+            EpochDeadline::Callback(_) => {
+                // Temporarily take the callback out of the store so it can be
+                // given mutable access to the store itself.
+                let mut behavior = std::mem::replace(
+                    &mut self.epoch_deadline_behavior,
+                    EpochDeadline::RunningCallback,
+                );
+                let result = match &mut behavior {
+                    EpochDeadline::Callback(callback) => callback(StoreContextMut(self)),
+                    _ => unreachable!(),
+                };
+                // Restore the callback unless it configured another behavior
+                // while running.
+                if let EpochDeadline::RunningCallback = self.epoch_deadline_behavior {
+                    self.epoch_deadline_behavior = behavior;
+                }
+                let delta = result?;
+                self.set_epoch_deadline(delta);
+                Ok(self.get_epoch_deadline())
+            }
             #[cfg(feature = "async")]
-            &EpochDeadline::YieldAndExtendDeadline { delta } => {
+            EpochDeadline::YieldAndExtendDeadline { delta } => {
+                let delta = *delta;
                 // Do the async yield. May return a trap if future was
                 // canceled while we're yielded.
                 self.async_yield_impl()?;
@@ -1878,6 +2680,117 @@ unsafe impl<T> wasmtime_runtime::Store for StoreInner<T> {
             }
         };
     }
+
+    fn instructions_executed(
+        &mut self,
+        module: Option<CompiledModuleId>,
+        func_index: u32,
+        offset: u32,
+        count: u32,
+    ) {
+        if let Some(module) = module {
+            self.instruction_counts.entry(module).or_default().record(
+                func_index,
+                offset as usize,
+                count,
+            );
+        }
+    }
+
+    fn memory_accessed(
+        &mut self,
+        memory: wasmtime_environ::MemoryIndex,
+        is_store: bool,
+        address: u64,
+        size: u32,
+        value: u128,
+    ) -> Result<(), anyhow::Error> {
+        // Temporarily take the callback out of the store so it can be given
+        // mutable access to the store itself.
+        let mut callback = match self.memory_access_callback.take() {
+            Some(callback) => callback,
+            None => return Ok(()),
+        };
+        let access = MemoryAccess {
+            kind: if is_store {
+                MemoryAccessKind::Store
+            } else {
+                MemoryAccessKind::Load
+            },
+            memory_index: memory.as_u32(),
+            address,
+            size,
+            value,
+        };
+        let result = callback(StoreContextMut(self), &access);
+        // Restore the callback unless it was replaced while running.
+        if self.memory_access_callback.is_none() {
+            self.memory_access_callback = Some(callback);
+        }
+        result
+    }
+
+    fn integer_overflowed(
+        &mut self,
+        func_index: u32,
+        op: wasmtime_environ::OverflowingOp,
+        bits: u32,
+        lhs: i64,
+        rhs: i64,
+    ) -> Result<bool, anyhow::Error> {
+        use wasmtime_environ::OverflowingOp;
+
+        let mut callback = match self.integer_overflow_callback.take() {
+            Some(callback) => callback,
+            None => return Ok(false),
+        };
+        let overflow = IntegerOverflow {
+            kind: match op {
+                OverflowingOp::Add => IntegerOverflowKind::Add,
+                OverflowingOp::Sub => IntegerOverflowKind::Sub,
+                OverflowingOp::Mul => IntegerOverflowKind::Mul,
+            },
+            ty: if bits == 64 {
+                ValType::I64
+            } else {
+                ValType::I32
+            },
+            func_index,
+            lhs,
+            rhs,
+        };
+        let result = callback(StoreContextMut(self), &overflow);
+        if self.integer_overflow_callback.is_none() {
+            self.integer_overflow_callback = Some(callback);
+        }
+        result.map(|()| true)
+    }
+
+    fn debug_hook(
+        &mut self,
+        module: Option<CompiledModuleId>,
+        func_index: u32,
+        offset: u32,
+        locals: &[ValRaw],
+        types: &[u8],
+    ) -> Result<(), anyhow::Error> {
+        let offset = offset as usize;
+        if !self.debug.should_pause(module, offset) {
+            return Ok(());
+        }
+        let frame = DebugFrame::new(StoreContextMut(self), func_index, offset, locals, types);
+
+        // Temporarily take the handler out of the store so it can be given
+        // mutable access to the store itself.
+        let mut handler = self.debug.handler.take().unwrap();
+        let result = handler(StoreContextMut(self), &frame);
+        // Restore the handler unless it was replaced while running.
+        if self.debug.handler.is_none() {
+            self.debug.handler = Some(handler);
+        }
+        self.debug.resume(result?);
+        Ok(())
+    }
 }
 
 impl<T> StoreInner<T> {
@@ -1895,6 +2808,26 @@ impl<T> StoreInner<T> {
         *epoch_deadline = self.engine().current_epoch() + delta;
     }
 
+    fn epoch_deadline_trap(&mut self) {
+        self.epoch_deadline_behavior = EpochDeadline::Trap;
+    }
+
+    fn epoch_deadline_callback(&mut self, callback: EpochDeadlineCallback<T>) {
+        self.epoch_deadline_behavior = EpochDeadline::Callback(callback);
+    }
+
+    fn epoch_deadline_async_yield_and_update(&mut self, delta: u64) {
+        assert!(
+            self.async_support(),
+            "cannot use `epoch_deadline_async_yield_and_update` without enabling async support in the config"
+        );
+        #[cfg(feature = "async")]
+        {
+            self.epoch_deadline_behavior = EpochDeadline::YieldAndExtendDeadline { delta };
+        }
+        drop(delta); // suppress warning in non-async build
+    }
+
     fn get_epoch_deadline(&self) -> u64 {
         // Safety: this is safe because, as above, it is only invoked
         // from within `new_epoch` which is called from guest Wasm
@@ -1947,6 +2880,12 @@ impl Drop for StoreOpaque {
             }
             ondemand.deallocate(&self.default_callee);
 
+            let metrics = self.engine.metrics_counters();
+            metrics.instances_dropped(self.instances.iter().filter(|i| !i.ondemand).count());
+            if let Some(fuel) = self.fuel_consumed() {
+                metrics.fuel_consumed(fuel);
+            }
+
             // See documentation for these fields on `StoreOpaque` for why they
             // must be dropped in this order.
             ManuallyDrop::drop(&mut self.store_data);
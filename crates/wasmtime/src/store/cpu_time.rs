@@ -0,0 +1,129 @@
+//! Accounting of the CPU time a store's WebAssembly executes for.
+
+use std::cell::UnsafeCell;
+use std::time::Duration;
+
+/// The CPU time consumed by a store's WebAssembly, if the store tracks it.
+///
+/// The time is kept in an `UnsafeCell` so that the future of a fiber, which
+/// can't borrow the store while the fiber borrows it, can pause and resume
+/// the timer around each poll, see `FiberFuture`.
+#[derive(Default)]
+pub struct CpuTimer(UnsafeCell<Option<CpuTime>>);
+
+// The timer is only ever used by the thread executing the store, like the
+// `AsyncState` of a store.
+unsafe impl Send for CpuTimer {}
+unsafe impl Sync for CpuTimer {}
+
+/// The state of a store which tracks its CPU time.
+#[derive(Default)]
+pub struct CpuTime {
+    /// The CPU time consumed by WebAssembly other than the current slice.
+    consumed: Duration,
+    /// The CPU time of the thread when WebAssembly was last entered or the
+    /// fiber executing it was last resumed, or `None` while the host executes.
+    since: Option<Duration>,
+}
+
+impl CpuTimer {
+    /// Starts tracking CPU time, from zero, or stops tracking it.
+    pub fn enable(&mut self, enable: bool) {
+        let time = self.0.get_mut();
+        match (enable, time.is_some()) {
+            (true, false) => *time = Some(CpuTime::default()),
+            (false, true) => *time = None,
+            _ => {}
+        }
+    }
+
+    /// Returns the CPU time consumed so far, or `None` if it isn't tracked.
+    pub fn consumed(&self) -> Option<Duration> {
+        // Safety: the timer is only modified through `&mut self` or by the
+        // future of a fiber, which mutably borrows the store.
+        let time = unsafe { (*self.0.get()).as_ref()? };
+        let current = time
+            .since
+            .map(|since| thread_cpu_time().saturating_sub(since))
+            .unwrap_or_default();
+        Some(time.consumed + current)
+    }
+
+    /// Starts the timer as WebAssembly is entered.
+    pub fn enter_wasm(&mut self) {
+        if let Some(time) = self.0.get_mut() {
+            time.since = Some(thread_cpu_time());
+        }
+    }
+
+    /// Stops the timer as the host is entered.
+    pub fn enter_host(&mut self) {
+        if let Some(time) = self.0.get_mut() {
+            time.pause();
+            time.since = None;
+        }
+    }
+
+    /// Returns a pointer to the timer, for pausing and resuming it when the
+    /// fiber executing WebAssembly is suspended and resumed.
+    pub fn as_ptr(&self) -> *mut Option<CpuTime> {
+        self.0.get()
+    }
+}
+
+impl CpuTime {
+    /// Adds the time since the timer was started or resumed to the time
+    /// consumed, if WebAssembly is executing.
+    pub fn pause(&mut self) {
+        if let Some(since) = &mut self.since {
+            let now = thread_cpu_time();
+            self.consumed += now.saturating_sub(*since);
+            *since = now;
+        }
+    }
+
+    /// Restarts the timer on the thread a fiber is resumed on, if the fiber
+    /// was suspended while executing WebAssembly.
+    pub fn resume(&mut self) {
+        if let Some(since) = &mut self.since {
+            *since = thread_cpu_time();
+        }
+    }
+}
+
+/// Returns the CPU time consumed by the current thread.
+#[cfg(unix)]
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe {
+        libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts);
+    }
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Returns the CPU time consumed by the current thread.
+#[cfg(windows)]
+fn thread_cpu_time() -> Duration {
+    use winapi::shared::minwindef::FILETIME;
+    use winapi::um::processthreadsapi::{GetCurrentThread, GetThreadTimes};
+
+    let time = |t: FILETIME| (u64::from(t.dwHighDateTime) << 32) | u64::from(t.dwLowDateTime);
+    unsafe {
+        let mut creation = std::mem::zeroed();
+        let mut exit = std::mem::zeroed();
+        let mut kernel = std::mem::zeroed();
+        let mut user = std::mem::zeroed();
+        GetThreadTimes(
+            GetCurrentThread(),
+            &mut creation,
+            &mut exit,
+            &mut kernel,
+            &mut user,
+        );
+        // The times are in units of 100 nanoseconds.
+        Duration::from_nanos((time(kernel) + time(user)) * 100)
+    }
+}
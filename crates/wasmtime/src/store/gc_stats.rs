@@ -0,0 +1,118 @@
+//! Statistics about the `externref`s of a store and their collections.
+
+use super::StoreOpaque;
+use std::collections::HashSet;
+use std::time::Duration;
+use wasmtime_runtime::{TableElement, TableElementType};
+
+/// A snapshot of the [`ExternRef`](crate::ExternRef)s held by a
+/// [`Store`](crate::Store) and of their garbage collections, returned by
+/// [`Store::gc_stats`](crate::Store::gc_stats).
+///
+/// References are counted where the store holds them: in the tables and
+/// globals of its instances, and in the table of references which
+/// WebAssembly may still use from its stack. References which WebAssembly
+/// dropped since the last collection are counted in the latter until the
+/// next collection, while references only held by the host aren't counted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GcStats {
+    live_externrefs: usize,
+    table_externrefs: usize,
+    global_externrefs: usize,
+    activations_externrefs: usize,
+    collections: u64,
+    total_collection_duration: Duration,
+    last_collection: Option<(Duration, usize)>,
+}
+
+impl GcStats {
+    pub(crate) fn new(store: &StoreOpaque) -> GcStats {
+        let mut live = HashSet::new();
+        let mut table_externrefs = 0;
+        let mut global_externrefs = 0;
+        for instance in store.instances.iter() {
+            for table in instance.handle.defined_tables() {
+                if table.element_type() != TableElementType::Extern {
+                    continue;
+                }
+                for index in 0..table.size() {
+                    if let Some(TableElement::ExternRef(Some(r))) = table.get(index) {
+                        live.insert(r.as_raw());
+                        table_externrefs += 1;
+                    }
+                }
+            }
+            for r in instance.handle.defined_externref_globals() {
+                live.insert(r.as_raw());
+                global_externrefs += 1;
+            }
+        }
+
+        let table = &store.externref_activations_table;
+        table.elements(|r| {
+            live.insert(r.as_raw());
+        });
+        let stats = table.gc_stats();
+        GcStats {
+            live_externrefs: live.len(),
+            table_externrefs,
+            global_externrefs,
+            activations_externrefs: table.num_refs(),
+            collections: stats.collections,
+            total_collection_duration: stats.total_duration,
+            last_collection: stats.last,
+        }
+    }
+
+    /// Returns the number of distinct references held by the store, counting
+    /// each reference held in several places once.
+    pub fn live_externrefs(&self) -> usize {
+        self.live_externrefs
+    }
+
+    /// Returns the number of slots of `externref` tables which hold a
+    /// reference, rather than `null`.
+    pub fn table_externrefs(&self) -> usize {
+        self.table_externrefs
+    }
+
+    /// Returns the number of `externref` globals which hold a reference,
+    /// rather than `null`.
+    pub fn global_externrefs(&self) -> usize {
+        self.global_externrefs
+    }
+
+    /// Returns the number of references WebAssembly used from its stack
+    /// since the last collection, which are kept alive until the next one,
+    /// counting a reference once for each time it was used.
+    pub fn activations_externrefs(&self) -> usize {
+        self.activations_externrefs
+    }
+
+    /// Returns the number of collections performed by the store, whether by
+    /// [`Store::gc`](crate::Store::gc) or automatically.
+    pub fn collections(&self) -> u64 {
+        self.collections
+    }
+
+    /// Returns the time all of the store's collections took together.
+    pub fn total_collection_duration(&self) -> Duration {
+        self.total_collection_duration
+    }
+
+    /// Returns the time the last collection took, or `None` if the store
+    /// hasn't collected yet.
+    pub fn last_collection_duration(&self) -> Option<Duration> {
+        self.last_collection.map(|(duration, _)| duration)
+    }
+
+    /// Returns the number of references which the last collection released
+    /// from WebAssembly's stack, or `None` if the store hasn't collected
+    /// yet.
+    ///
+    /// References released by a collection are reclaimed unless the host,
+    /// or a table or global, still holds them.
+    pub fn last_collection_released(&self) -> Option<usize> {
+        self.last_collection.map(|(_, released)| released)
+    }
+}
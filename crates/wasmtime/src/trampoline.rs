@@ -41,12 +41,13 @@ fn create_handle(
         let module = Arc::new(module);
         let runtime_info =
             &BareModuleInfo::maybe_imported_func(module, one_signature).into_traitobj();
-        let handle = OnDemandInstanceAllocator::new(config.mem_creator.clone(), 0).allocate(
+        let handle = OnDemandInstanceAllocator::new(config.memory_creator(), 0).allocate(
             InstanceAllocationRequest {
                 imports,
                 host_state,
                 store: StorePtr::new(store.traitobj()),
                 runtime_info,
+                pkey: None,
             },
         )?;
 
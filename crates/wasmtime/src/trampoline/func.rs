@@ -1,14 +1,20 @@
 //! Support for a calling of an imported function.
 
 use crate::module::BareModuleInfo;
-use crate::{Engine, FuncType, Trap, ValRaw};
+#[cfg(compiler)]
+use crate::{Engine, FuncType, UndeclaredSignatureError};
+use crate::{Trap, ValRaw};
 use anyhow::Result;
 use std::any::Any;
+#[cfg(compiler)]
+use std::collections::HashMap;
 use std::panic::{self, AssertUnwindSafe};
 use std::sync::Arc;
 use wasmtime_environ::{
     AnyfuncIndex, EntityIndex, FunctionInfo, Module, ModuleType, SignatureIndex,
 };
+#[cfg(compiler)]
+use wasmtime_environ::{Compiler, WasmFuncType};
 use wasmtime_jit::{CodeMemory, ProfilingAgent};
 use wasmtime_runtime::{
     Imports, InstanceAllocationRequest, InstanceAllocator, InstanceHandle,
@@ -16,19 +22,27 @@ use wasmtime_runtime::{
     VMTrampoline,
 };
 
-struct TrampolineState<F> {
-    func: F,
+#[cfg(compiler)]
+type HostFn = dyn Fn(*mut VMContext, *mut ValRaw) -> Result<(), Trap> + Send + Sync;
+
+#[cfg(compiler)]
+struct TrampolineState {
+    func: Box<HostFn>,
+    // The trampolines compiled for this function alone, when they aren't
+    // the engine's.
     #[allow(dead_code)]
-    code_memory: CodeMemory,
+    trampolines: Option<HostTrampoline>,
 }
 
-unsafe extern "C" fn stub_fn<F>(
+// Note that this isn't generic over the host function, which is called
+// through a trait object instead, so that the trampolines calling it only
+// depend on the signature and may be shared by every function with it.
+#[cfg(compiler)]
+unsafe extern "C" fn stub_fn(
     vmctx: *mut VMContext,
     caller_vmctx: *mut VMContext,
     values_vec: *mut ValRaw,
-) where
-    F: Fn(*mut VMContext, *mut ValRaw) -> Result<(), Trap> + 'static,
-{
+) {
     // Here we are careful to use `catch_unwind` to ensure Rust panics don't
     // unwind past us. The primary reason for this is that Rust considers it UB
     // to unwind past an `extern "C"` function. Here we are in an `extern "C"`
@@ -47,8 +61,8 @@ unsafe extern "C" fn stub_fn<F>(
         // the `Any` here so an unsafe downcast should also
         // work.
         let state = (*vmctx).host_state();
-        debug_assert!(state.is::<TrampolineState<F>>());
-        let state = &*(state as *const _ as *const TrampolineState<F>);
+        debug_assert!(state.is::<TrampolineState>());
+        let state = &*(state as *const _ as *const TrampolineState);
         (state.func)(caller_vmctx, values_vec)
     }));
 
@@ -70,7 +84,7 @@ unsafe extern "C" fn stub_fn<F>(
 }
 
 #[cfg(compiler)]
-fn register_trampolines(profiler: &dyn ProfilingAgent, image: &object::File<'_>) {
+pub(crate) fn register_trampolines(profiler: &dyn ProfilingAgent, image: &object::File<'_>) {
     use object::{Object as _, ObjectSection, ObjectSymbol, SectionKind, SymbolKind};
     let pid = std::process::id();
     let tid = pid;
@@ -102,6 +116,94 @@ fn register_trampolines(profiler: &dyn ProfilingAgent, image: &object::File<'_>)
     }
 }
 
+/// The trampolines entering and leaving the host functions of a signature.
+#[cfg(compiler)]
+struct HostTrampoline {
+    #[allow(dead_code)]
+    code_memory: CodeMemory,
+    host_to_wasm: VMTrampoline,
+    wasm_to_host: *mut [VMFunctionBody],
+}
+
+// The trampolines are immutable once they're published, and they're only
+// deallocated along with `code_memory`.
+#[cfg(compiler)]
+unsafe impl Send for HostTrampoline {}
+#[cfg(compiler)]
+unsafe impl Sync for HostTrampoline {}
+
+#[cfg(compiler)]
+impl HostTrampoline {
+    fn new(
+        compiler: &dyn Compiler,
+        profiler: &dyn ProfilingAgent,
+        ty: &WasmFuncType,
+    ) -> Result<HostTrampoline> {
+        let mut obj = compiler.object()?;
+        let (t1, t2) = compiler.emit_trampoline_obj(ty, stub_fn as usize, &mut obj)?;
+        let obj = wasmtime_jit::mmap_vec_from_obj(obj)?;
+
+        // Copy the results of JIT compilation into executable memory, and this
+        // will also take care of unwind table registration.
+        let mut code_memory = CodeMemory::new(obj);
+        let code = code_memory.publish()?;
+
+        register_trampolines(profiler, &code.obj);
+
+        // Extract the host/wasm trampolines from the results of compilation
+        // since we know their start/length.
+        let host_to_wasm = code.text[t1.start as usize..][..t1.length as usize].as_ptr();
+        let wasm_to_host = &code.text[t2.start as usize..][..t2.length as usize];
+        let wasm_to_host = wasm_to_host as *const [u8] as *mut [VMFunctionBody];
+        let host_to_wasm = unsafe { std::mem::transmute::<*const u8, VMTrampoline>(host_to_wasm) };
+
+        Ok(HostTrampoline {
+            code_memory,
+            host_to_wasm,
+            wasm_to_host,
+        })
+    }
+}
+
+/// The trampolines of the signatures declared with
+/// [`Config::host_trampoline_signatures`](crate::Config::host_trampoline_signatures),
+/// which an engine compiles when it's created.
+#[cfg(compiler)]
+pub(crate) struct HostTrampolines {
+    signatures: Vec<FuncType>,
+    trampolines: HashMap<WasmFuncType, HostTrampoline>,
+}
+
+#[cfg(compiler)]
+impl HostTrampolines {
+    pub(crate) fn new(
+        compiler: &dyn Compiler,
+        profiler: &dyn ProfilingAgent,
+        signatures: &[FuncType],
+    ) -> Result<HostTrampolines> {
+        let mut trampolines = HashMap::new();
+        let mut unique = Vec::new();
+        for ty in signatures {
+            let wasm = ty.as_wasm_func_type();
+            if trampolines.contains_key(wasm) {
+                continue;
+            }
+            trampolines.insert(wasm.clone(), HostTrampoline::new(compiler, profiler, wasm)?);
+            unique.push(ty.clone());
+        }
+        Ok(HostTrampolines {
+            signatures: unique,
+            trampolines,
+        })
+    }
+
+    /// Returns each declared signature, without duplicates, in the order
+    /// they were declared in.
+    pub(crate) fn signatures(&self) -> &[FuncType] {
+        &self.signatures
+    }
+}
+
 #[cfg(compiler)]
 pub fn create_function<F>(
     ft: &FuncType,
@@ -111,37 +213,36 @@ pub fn create_function<F>(
 where
     F: Fn(*mut VMContext, *mut ValRaw) -> Result<(), Trap> + Send + Sync + 'static,
 {
-    let mut obj = engine.compiler().object()?;
-    let (t1, t2) = engine.compiler().emit_trampoline_obj(
-        ft.as_wasm_func_type(),
-        stub_fn::<F> as usize,
-        &mut obj,
-    )?;
-    let obj = wasmtime_jit::mmap_vec_from_obj(obj)?;
-
-    // Copy the results of JIT compilation into executable memory, and this will
-    // also take care of unwind table registration.
-    let mut code_memory = CodeMemory::new(obj);
-    let code = code_memory.publish()?;
-
-    register_trampolines(engine.config().profiler.as_ref(), &code.obj);
-
-    // Extract the host/wasm trampolines from the results of compilation since
-    // we know their start/length.
-    let host_trampoline = code.text[t1.start as usize..][..t1.length as usize].as_ptr();
-    let wasm_trampoline = &code.text[t2.start as usize..][..t2.length as usize];
-    let wasm_trampoline = wasm_trampoline as *const [u8] as *mut [VMFunctionBody];
+    // Functions use the engine's trampolines when it has them, and may then
+    // only have the signatures they were compiled for.
+    let (trampolines, host_to_wasm, wasm_to_host) = match engine.host_trampolines() {
+        Some(declared) => match declared.trampolines.get(ft.as_wasm_func_type()) {
+            Some(t) => (None, t.host_to_wasm, t.wasm_to_host),
+            None => return Err(UndeclaredSignatureError::new(ft.clone()).into()),
+        },
+        None => {
+            let t = HostTrampoline::new(
+                engine.compiler(),
+                engine.config().profiler.as_ref(),
+                ft.as_wasm_func_type(),
+            )?;
+            let (host_to_wasm, wasm_to_host) = (t.host_to_wasm, t.wasm_to_host);
+            (Some(t), host_to_wasm, wasm_to_host)
+        }
+    };
 
     let sig = engine.signatures().register(ft.as_wasm_func_type());
 
     unsafe {
         let instance = create_raw_function(
-            wasm_trampoline,
+            wasm_to_host,
             sig,
-            Box::new(TrampolineState { func, code_memory }),
+            Box::new(TrampolineState {
+                func: Box::new(func),
+                trampolines,
+            }),
         )?;
-        let host_trampoline = std::mem::transmute::<*const u8, VMTrampoline>(host_trampoline);
-        Ok((instance, host_trampoline))
+        Ok((instance, host_to_wasm))
     }
 }
 
@@ -176,6 +277,7 @@ pub unsafe fn create_raw_function(
             host_state,
             store: StorePtr::empty(),
             runtime_info,
+            pkey: None,
         })?,
     )
 }
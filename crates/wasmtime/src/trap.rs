@@ -1,9 +1,13 @@
 use crate::module::GlobalModuleRegistry;
+#[cfg(feature = "coredump")]
+use crate::store::StoreOpaque;
 use crate::FrameInfo;
+#[cfg(feature = "coredump")]
+use crate::WasmCoreDump;
 use std::fmt;
 use std::sync::Arc;
 use wasmtime_environ::TrapCode as EnvTrapCode;
-use wasmtime_jit::{demangle_function_name, demangle_function_name_or_index};
+use wasmtime_jit::demangle_function_name;
 use wasmtime_runtime::Backtrace;
 
 /// A struct representing an aborted instruction execution, with a message
@@ -86,6 +90,10 @@ pub enum TrapCode {
 
     /// Execution has potentially run too long and may be interrupted.
     Interrupt,
+
+    /// A `memory.grow` failed in a store configured to trap instead, see
+    /// [`Store::memory_grow_failure_trap`](crate::Store::memory_grow_failure_trap).
+    MemoryGrowFailed,
 }
 
 impl TrapCode {
@@ -122,6 +130,7 @@ impl fmt::Display for TrapCode {
             BadConversionToInteger => "invalid conversion to integer",
             UnreachableCodeReached => "wasm `unreachable` instruction executed",
             Interrupt => "interrupt",
+            MemoryGrowFailed => "failed to grow linear memory",
         };
         write!(f, "{}", desc)
     }
@@ -134,6 +143,32 @@ struct TrapInner {
     native_trace: Backtrace,
     #[cfg(feature = "wasm-backtrace")]
     hint_wasm_backtrace_details_env: bool,
+    #[cfg(feature = "coredump")]
+    coredump: Option<WasmCoreDump>,
+}
+
+/// Returns the program counters within `native_trace` which should be used to
+/// look up wasm frame information.
+///
+/// Note that we need to be careful about the pc we pass in here to lookup
+/// frame information. This program counter is used to translate back to an
+/// original source location in the origin wasm module. If this pc is the exact
+/// pc that the trap happened at, then we look up that pc precisely. Otherwise
+/// backtrace information typically points at the pc *after* the call
+/// instruction (because otherwise it's likely a call instruction on the
+/// stack). In that case we want to lookup information for the previous
+/// instruction (the call instruction) so we subtract one as the lookup.
+#[cfg(feature = "wasm-backtrace")]
+pub(crate) fn lookup_pcs(
+    trap_pc: Option<usize>,
+    native_trace: &Backtrace,
+) -> impl Iterator<Item = usize> + '_ {
+    native_trace
+        .frames()
+        .iter()
+        .map(|frame| frame.ip() as usize)
+        .filter(|pc| *pc != 0)
+        .map(move |pc| if Some(pc) == trap_pc { pc } else { pc - 1 })
 }
 
 fn _assert_trap_is_sync_and_send(t: &Trap) -> (&dyn Sync, &dyn Send) {
@@ -188,6 +223,12 @@ impl Trap {
         }
     }
 
+    #[cold] // see Trap::new
+    pub(crate) fn memory_grow_failed() -> Self {
+        let reason = TrapReason::InstructionTrap(TrapCode::MemoryGrowFailed);
+        Trap::new_with_trace(None, reason, Backtrace::new())
+    }
+
     #[cold] // see Trap::new
     pub(crate) fn new_wasm(
         trap_pc: Option<usize>,
@@ -217,22 +258,7 @@ impl Trap {
 
         #[cfg(feature = "wasm-backtrace")]
         GlobalModuleRegistry::with(|registry| {
-            for frame in native_trace.frames() {
-                let pc = frame.ip() as usize;
-                if pc == 0 {
-                    continue;
-                }
-                // Note that we need to be careful about the pc we pass in
-                // here to lookup frame information. This program counter is
-                // used to translate back to an original source location in
-                // the origin wasm module. If this pc is the exact pc that
-                // the trap happened at, then we look up that pc precisely.
-                // Otherwise backtrace information typically points at the
-                // pc *after* the call instruction (because otherwise it's
-                // likely a call instruction on the stack). In that case we
-                // want to lookup information for the previous instruction
-                // (the call instruction) so we subtract one as the lookup.
-                let pc_to_lookup = if Some(pc) == trap_pc { pc } else { pc - 1 };
+            for pc_to_lookup in lookup_pcs(trap_pc, &native_trace) {
                 if let Some((info, has_unparsed_debuginfo, wasm_backtrace_details_env_used)) =
                     registry.lookup_frame_info(pc_to_lookup)
                 {
@@ -258,6 +284,8 @@ impl Trap {
                 wasm_trace,
                 #[cfg(feature = "wasm-backtrace")]
                 hint_wasm_backtrace_details_env,
+                #[cfg(feature = "coredump")]
+                coredump: None,
             }),
         }
     }
@@ -289,6 +317,38 @@ impl Trap {
         &self.inner.wasm_trace
     }
 
+    /// Returns the core dump of the guest's state which was captured when this
+    /// trap happened, if any.
+    ///
+    /// Core dumps are only captured when
+    /// [`Config::coredump_on_trap`](crate::Config::coredump_on_trap) is
+    /// enabled and the trap originated from, or passed through, WebAssembly
+    /// code.
+    #[cfg(feature = "coredump")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "coredump")))]
+    pub fn coredump(&self) -> Option<&WasmCoreDump> {
+        self.inner.coredump.as_ref()
+    }
+
+    /// Captures a core dump of `store` and attaches it to this trap, unless
+    /// one was already captured by a more deeply nested call into wasm.
+    #[cfg(feature = "coredump")]
+    pub(crate) fn with_coredump(
+        mut self,
+        store: &StoreOpaque,
+        stack: &[wasmtime_runtime::CoreDumpStackFrame],
+    ) -> Trap {
+        // If this trap is shared, for example because a host function returned
+        // a clone of a trap it's holding onto, then there's nowhere to stash
+        // the core dump so one isn't captured.
+        if self.inner.coredump.is_some() || Arc::strong_count(&self.inner) != 1 {
+            return self;
+        }
+        let coredump = WasmCoreDump::new(store, stack);
+        Arc::get_mut(&mut self.inner).unwrap().coredump = Some(coredump);
+        self
+    }
+
     /// Code of a trap that happened while executing a WASM instruction.
     /// If the trap was triggered by a host export this will be `None`.
     pub fn trap_code(&self) -> Option<TrapCode> {
@@ -297,6 +357,20 @@ impl Trap {
             _ => None,
         }
     }
+
+    /// Returns the offset, within the original wasm module, of the instruction
+    /// which raised this trap.
+    ///
+    /// This is `None` if the trap wasn't raised by a wasm instruction, in which
+    /// case [`Trap::trap_code`] is also `None`, or if the module was compiled
+    /// without an address map (see
+    /// [`Config::generate_address_map`](crate::Config::generate_address_map)).
+    #[cfg(feature = "wasm-backtrace")]
+    #[cfg_attr(nightlydoc, doc(cfg(feature = "wasm-backtrace")))]
+    pub fn module_offset(&self) -> Option<usize> {
+        self.trap_code()?;
+        self.trace().first()?.module_offset()
+    }
 }
 
 impl fmt::Debug for Trap {
@@ -325,35 +399,24 @@ impl fmt::Display for Trap {
             writeln!(f, "\nwasm backtrace:")?;
 
             for (i, frame) in self.trace().iter().enumerate() {
-                let name = frame.module_name().unwrap_or("<unknown>");
                 write!(f, "  {:>3}: ", i)?;
 
                 if let Some(offset) = frame.module_offset() {
                     write!(f, "{:#6x} - ", offset)?;
                 }
 
-                let write_raw_func_name = |f: &mut fmt::Formatter<'_>| {
-                    demangle_function_name_or_index(
-                        f,
-                        frame.func_name(),
-                        frame.func_index() as usize,
-                    )
-                };
                 if frame.symbols().is_empty() {
-                    write!(f, "{}!", name)?;
-                    write_raw_func_name(f)?;
-                    writeln!(f, "")?;
+                    writeln!(f, "{}", frame.symbol_name())?;
                 } else {
                     for (i, symbol) in frame.symbols().iter().enumerate() {
-                        if i > 0 {
-                            write!(f, "              - ")?;
+                        if i == 0 {
+                            write!(f, "{}", frame.symbol_name())?;
                         } else {
-                            // ...
-                        }
-                        match symbol.name() {
-                            Some(name) => demangle_function_name(f, name)?,
-                            None if i == 0 => write_raw_func_name(f)?,
-                            None => write!(f, "<inlined function>")?,
+                            write!(f, "              - ")?;
+                            match symbol.name() {
+                                Some(name) => demangle_function_name(f, name)?,
+                                None => write!(f, "<inlined function>")?,
+                            }
                         }
                         writeln!(f, "")?;
                         if let Some(file) = symbol.file() {
@@ -9,7 +9,9 @@
 //! throughout the `wasmtime` crate with extra functionality that's only
 //! available on Unix.
 
+use crate::module::GlobalModuleRegistry;
 use crate::{AsContextMut, Store};
+use std::ops::Range;
 
 /// Extensions for the [`Store`] type only available on Unix.
 pub trait StoreExt {
@@ -37,3 +39,48 @@ impl<T> StoreExt for Store<T> {
             .set_signal_handler(Some(Box::new(handler)));
     }
 }
+
+/// Returns the address ranges of all compiled WebAssembly code currently
+/// loaded in this process.
+///
+/// Only code for modules which are alive at the time of the call is reported,
+/// and the ranges may change as modules are created and dropped. This function
+/// takes a lock and allocates, so it's not async-signal-safe; signal handlers
+/// should call [`handle_trap`] instead to determine whether a signal was raised
+/// by WebAssembly.
+pub fn code_ranges() -> Vec<Range<usize>> {
+    GlobalModuleRegistry::code_ranges()
+}
+
+/// Handles a signal raised by WebAssembly code from within a signal handler
+/// installed by the embedder.
+///
+/// This is intended for embedders which install their own handlers for
+/// `SIGSEGV`, `SIGBUS`, `SIGILL` and `SIGFPE`, typically after disabling
+/// [`Config::install_signal_handlers`](crate::Config::install_signal_handlers).
+/// Such a handler should call this function first with the arguments it
+/// received:
+///
+/// * If this returns `false` then the signal wasn't raised by WebAssembly and
+///   the embedder's handler should carry on with its own handling.
+///
+/// * If the signal is a WebAssembly trap then this function usually doesn't
+///   return, instead unwinding to where WebAssembly was entered so the trap
+///   is reported as an error. On some platforms it instead returns `true`
+///   after updating `context`, in which case the handler must return
+///   immediately to resume execution.
+///
+/// Custom handlers configured with [`StoreExt::set_signal_handler`] are
+/// consulted by this function as well.
+///
+/// # Safety
+///
+/// This must only be called from within a signal handler installed with
+/// `SA_SIGINFO`, passing along the exact arguments the handler received.
+pub unsafe fn handle_trap(
+    signum: libc::c_int,
+    siginfo: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) -> bool {
+    wasmtime_runtime::handle_trap(signum, siginfo, context)
+}
@@ -9,7 +9,9 @@
 //! throughout the `wasmtime` crate with extra functionality that's only
 //! available on Windows.
 
+use crate::module::GlobalModuleRegistry;
 use crate::{AsContextMut, Store};
+use std::ops::Range;
 
 /// Extensions for the [`Store`] type only available on Windows.
 pub trait StoreExt {
@@ -31,3 +33,42 @@ impl<T> StoreExt for Store<T> {
             .set_signal_handler(Some(Box::new(handler)));
     }
 }
+
+/// Returns the address ranges of all compiled WebAssembly code currently
+/// loaded in this process.
+///
+/// Only code for modules which are alive at the time of the call is reported,
+/// and the ranges may change as modules are created and dropped. This function
+/// takes a lock and allocates, so exception handlers should call
+/// [`handle_trap`] instead to determine whether an exception was raised by
+/// WebAssembly.
+pub fn code_ranges() -> Vec<Range<usize>> {
+    GlobalModuleRegistry::code_ranges()
+}
+
+/// Handles an exception raised by WebAssembly code from within an exception
+/// handler installed by the embedder.
+///
+/// This is intended for embedders which install their own vectored exception
+/// handler, typically after disabling
+/// [`Config::install_signal_handlers`](crate::Config::install_signal_handlers).
+/// Such a handler should call this function first with the exception
+/// information it received:
+///
+/// * If this returns `false` then the exception wasn't raised by WebAssembly
+///   and the embedder's handler should carry on with its own handling.
+///
+/// * If the exception is a WebAssembly trap then this function usually doesn't
+///   return, instead unwinding to where WebAssembly was entered so the trap is
+///   reported as an error. If a custom handler configured with
+///   [`StoreExt::set_signal_handler`] handled the exception then this returns
+///   `true`, in which case the handler should return
+///   `EXCEPTION_CONTINUE_EXECUTION`.
+///
+/// # Safety
+///
+/// This must only be called from within an exception handler, passing along
+/// the exact exception information the handler received.
+pub unsafe fn handle_trap(exception_info: winapi::um::winnt::PEXCEPTION_POINTERS) -> bool {
+    wasmtime_runtime::handle_trap(exception_info)
+}
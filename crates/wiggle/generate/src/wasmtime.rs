@@ -118,9 +118,10 @@ fn generate_func(
                 return Err(#rt::wasmtime_crate::Trap::new("missing required memory export"));
             }
         };
+        let read_only = mem.read_only_ranges(&mut caller);
         let (mem , ctx) = mem.data_and_store_mut(&mut caller);
         let ctx = get_cx(ctx);
-        let mem = #rt::wasmtime::WasmtimeGuestMemory::new(mem);
+        let mem = #rt::wasmtime::WasmtimeGuestMemory::with_read_only(mem, read_only);
         match #abi_func(ctx, &mem #(, #arg_names)*) #await_ {
             Ok(r) => Ok(<#ret_ty>::from(r)),
             Err(#rt::Trap::String(err)) => Err(#rt::wasmtime_crate::Trap::new(err)),
@@ -15,6 +15,8 @@ pub enum GuestError {
     PtrNotAligned(Region, u32),
     #[error("Pointer already borrowed: {0:?}")]
     PtrBorrowed(Region),
+    #[error("Pointer to read-only memory: {0:?}")]
+    PtrReadOnly(Region),
     #[error("Borrow checker out of handles")]
     BorrowCheckerOutOfHandles,
     #[error("Slice length mismatch")]
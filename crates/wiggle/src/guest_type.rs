@@ -104,6 +104,9 @@ macro_rules! primitives {
                     start: offset,
                     len: size,
                 };
+                if ptr.mem().is_read_only(region) {
+                    return Err(GuestError::PtrReadOnly(region));
+                }
                 if ptr.mem().is_shared_borrowed(region) || ptr.mem().is_mut_borrowed(region) {
                     return Err(GuestError::PtrBorrowed(region));
                 }
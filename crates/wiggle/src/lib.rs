@@ -176,6 +176,11 @@ pub unsafe trait GuestMemory: Send + Sync {
     fn is_mut_borrowed(&self, r: Region) -> bool;
     /// Check if a region of linear memory has any shared borrows.
     fn is_shared_borrowed(&self, r: Region) -> bool;
+    /// Check if any of a region of linear memory is read-only, in which case
+    /// `GuestPtr::write` fails and so does `mut_borrow` of the region.
+    fn is_read_only(&self, _r: Region) -> bool {
+        false
+    }
     /// Exclusively borrow a region of linear memory. This is used when constructing a
     /// `GuestSliceMut` or `GuestStrMut`. Those types will give Rust `&mut` access
     /// to the region of linear memory, therefore, the `GuestMemory` impl must
@@ -218,6 +223,9 @@ unsafe impl<'a, T: ?Sized + GuestMemory> GuestMemory for &'a T {
     fn is_shared_borrowed(&self, r: Region) -> bool {
         T::is_shared_borrowed(self, r)
     }
+    fn is_read_only(&self, r: Region) -> bool {
+        T::is_read_only(self, r)
+    }
     fn mut_borrow(&self, r: Region) -> Result<BorrowHandle, GuestError> {
         T::mut_borrow(self, r)
     }
@@ -245,6 +253,9 @@ unsafe impl<'a, T: ?Sized + GuestMemory> GuestMemory for &'a mut T {
     fn is_shared_borrowed(&self, r: Region) -> bool {
         T::is_shared_borrowed(self, r)
     }
+    fn is_read_only(&self, r: Region) -> bool {
+        T::is_read_only(self, r)
+    }
     fn mut_borrow(&self, r: Region) -> Result<BorrowHandle, GuestError> {
         T::mut_borrow(self, r)
     }
@@ -272,6 +283,9 @@ unsafe impl<T: ?Sized + GuestMemory> GuestMemory for Box<T> {
     fn is_shared_borrowed(&self, r: Region) -> bool {
         T::is_shared_borrowed(self, r)
     }
+    fn is_read_only(&self, r: Region) -> bool {
+        T::is_read_only(self, r)
+    }
     fn mut_borrow(&self, r: Region) -> Result<BorrowHandle, GuestError> {
         T::mut_borrow(self, r)
     }
@@ -299,6 +313,9 @@ unsafe impl<T: ?Sized + GuestMemory> GuestMemory for Arc<T> {
     fn is_shared_borrowed(&self, r: Region) -> bool {
         T::is_shared_borrowed(self, r)
     }
+    fn is_read_only(&self, r: Region) -> bool {
+        T::is_read_only(self, r)
+    }
     fn mut_borrow(&self, r: Region) -> Result<BorrowHandle, GuestError> {
         T::mut_borrow(self, r)
     }
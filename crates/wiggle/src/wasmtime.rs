@@ -1,17 +1,29 @@
 use crate::borrow::BorrowChecker;
 use crate::{BorrowHandle, GuestError, GuestMemory, Region};
+use std::ops::Range;
 
 /// Lightweight `wasmtime::Memory` wrapper so we can implement the
 /// `wiggle::GuestMemory` trait on it.
 pub struct WasmtimeGuestMemory<'a> {
     mem: &'a mut [u8],
     bc: BorrowChecker,
+    /// The ranges of the memory which were made read-only with
+    /// `wasmtime::Memory::protect_read_only`, which can't be written to.
+    read_only: Vec<Range<usize>>,
 }
 
 impl<'a> WasmtimeGuestMemory<'a> {
     pub fn new(mem: &'a mut [u8]) -> Self {
+        Self::with_read_only(mem, Vec::new())
+    }
+
+    /// Creates a guest memory whose `read_only` ranges, as returned by
+    /// `wasmtime::Memory::read_only_ranges`, fail to be written to or
+    /// mutably borrowed with `GuestError::PtrReadOnly`.
+    pub fn with_read_only(mem: &'a mut [u8], read_only: Vec<Range<usize>>) -> Self {
         Self {
             mem,
+            read_only,
             // Wiggle does not expose any methods for functions to re-enter
             // the WebAssembly instance, or expose the memory via non-wiggle
             // mechanisms. However, the user-defined code may end up
@@ -42,7 +54,19 @@ unsafe impl GuestMemory for WasmtimeGuestMemory<'_> {
     fn shared_borrow(&self, r: Region) -> Result<BorrowHandle, GuestError> {
         self.bc.shared_borrow(r)
     }
+    fn is_read_only(&self, r: Region) -> bool {
+        let start = r.start as usize;
+        let end = start + r.len as usize;
+        r.len > 0
+            && self
+                .read_only
+                .iter()
+                .any(|range| start < range.end && range.start < end)
+    }
     fn mut_borrow(&self, r: Region) -> Result<BorrowHandle, GuestError> {
+        if self.is_read_only(r) {
+            return Err(GuestError::PtrReadOnly(r));
+        }
         self.bc.mut_borrow(r)
     }
     fn shared_unborrow(&self, h: BorrowHandle) {
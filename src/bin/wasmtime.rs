@@ -5,8 +5,10 @@
 
 use anyhow::Result;
 use clap::{ErrorKind, Parser};
+#[cfg(feature = "explore")]
+use wasmtime_cli::commands::ExploreCommand;
 use wasmtime_cli::commands::{
-    CompileCommand, ConfigCommand, RunCommand, SettingsCommand, WastCommand,
+    CompileCommand, ConfigCommand, RunCommand, ServeCommand, SettingsCommand, WastCommand,
 };
 
 /// Wasmtime WebAssembly Runtime
@@ -35,8 +37,13 @@ enum Wasmtime {
     Config(ConfigCommand),
     /// Compiles a WebAssembly module.
     Compile(CompileCommand),
+    /// Explores the compilation of a WebAssembly module.
+    #[cfg(feature = "explore")]
+    Explore(ExploreCommand),
     /// Runs a WebAssembly module
     Run(RunCommand),
+    /// Serves requests with a WebAssembly module, instantiating it for each one
+    Serve(ServeCommand),
     /// Displays available Cranelift settings for a target.
     Settings(SettingsCommand),
     /// Runs a WebAssembly test script file
@@ -49,7 +56,10 @@ impl Wasmtime {
         match self {
             Self::Config(c) => c.execute(),
             Self::Compile(c) => c.execute(),
+            #[cfg(feature = "explore")]
+            Self::Explore(c) => c.execute(),
             Self::Run(c) => c.execute(),
+            Self::Serve(c) => c.execute(),
             Self::Settings(c) => c.execute(),
             Self::Wast(c) => c.execute(),
         }
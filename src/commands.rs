@@ -2,8 +2,14 @@
 
 mod compile;
 mod config;
+#[cfg(feature = "explore")]
+mod explore;
 mod run;
+mod serve;
 mod settings;
 mod wast;
 
-pub use self::{compile::*, config::*, run::*, settings::*, wast::*};
+pub use self::{compile::*, config::*, run::*, serve::*, settings::*, wast::*};
+
+#[cfg(feature = "explore")]
+pub use self::explore::*;
@@ -5,7 +5,7 @@ use clap::Parser;
 use std::fs;
 use std::path::PathBuf;
 use target_lexicon::Triple;
-use wasmtime::Engine;
+use wasmtime::{Engine, Module};
 use wasmtime_cli_flags::CommonOptions;
 
 lazy_static::lazy_static! {
@@ -52,6 +52,10 @@ pub struct CompileCommand {
     #[clap(short = 'o', long, value_name = "OUTPUT", parse(from_os_str))]
     output: Option<PathBuf>,
 
+    /// An export to call before compiling, whose resulting state becomes the module's initial state
+    #[clap(long, value_name = "EXPORT")]
+    preinitialize: Option<String>,
+
     /// The path of the WebAssembly to compile
     #[clap(index = 1, value_name = "MODULE", parse(from_os_str))]
     module: PathBuf,
@@ -86,7 +90,11 @@ impl CompileCommand {
             output
         });
 
-        fs::write(output, engine.precompile_module(&input)?)?;
+        let compiled = match &self.preinitialize {
+            Some(init) => Module::preinitialize(&engine, &input, init)?.serialize()?,
+            None => engine.precompile_module(&input)?,
+        };
+        fs::write(output, compiled)?;
 
         Ok(())
     }
@@ -97,7 +105,7 @@ mod test {
     use super::*;
     use std::io::Write;
     use tempfile::NamedTempFile;
-    use wasmtime::{Instance, Module, Store};
+    use wasmtime::{Instance, Store};
 
     #[test]
     fn test_successful_compile() -> Result<()> {
@@ -130,6 +138,43 @@ mod test {
         Ok(())
     }
 
+    #[test]
+    fn test_preinitialize_compile() -> Result<()> {
+        let (mut input, input_path) = NamedTempFile::new()?.into_parts();
+        input.write_all(
+            "(module
+                (global $g (mut i32) (i32.const 0))
+                (func (export \"init\") (global.set $g (i32.const 1234)))
+                (func (export \"f\") (result i32) global.get $g))"
+                .as_bytes(),
+        )?;
+        drop(input);
+
+        let output_path = NamedTempFile::new()?.into_temp_path();
+
+        let command = CompileCommand::try_parse_from(vec![
+            "compile",
+            "--disable-logging",
+            "--preinitialize",
+            "init",
+            "-o",
+            output_path.to_str().unwrap(),
+            input_path.to_str().unwrap(),
+        ])?;
+
+        command.execute()?;
+
+        let engine = Engine::default();
+        let contents = std::fs::read(output_path)?;
+        let module = unsafe { Module::deserialize(&engine, contents)? };
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+        assert_eq!(f.call(&mut store, ()).unwrap(), 1234);
+
+        Ok(())
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_x64_flags_compile() -> Result<()> {
@@ -0,0 +1,318 @@
+//! The module that implements the `wasmtime explore` command.
+
+use anyhow::{anyhow, bail, Context, Result};
+use capstone::prelude::*;
+use clap::Parser;
+use object::{Object, ObjectSection, ObjectSymbol};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+use target_lexicon::{Architecture, Triple};
+use wasmparser::{Name, NameSectionReader, Naming, Parser as WasmParser, Payload, TypeRef};
+use wasmtime::Engine;
+use wasmtime_cli_flags::CommonOptions;
+use wasmtime_environ::obj::try_parse_func_name;
+use wasmtime_environ::symbol_name;
+
+lazy_static::lazy_static! {
+    static ref AFTER_HELP: String = {
+        format!(
+            "{}\
+            \n\
+            Usage examples:\n\
+            \n\
+            Printing the compilation of each function of a module:\n\
+            \n  \
+            wasmtime explore example.wasm\n\
+            \n\
+            Writing it as an HTML report instead:\n\
+            \n  \
+            wasmtime explore --html report.html example.wasm\n",
+            crate::FLAG_EXPLANATIONS.as_str()
+        )
+    };
+}
+
+/// Explores the compilation of a WebAssembly module.
+#[derive(Parser)]
+#[structopt(
+    name = "explore",
+    version,
+    after_help = AFTER_HELP.as_str()
+)]
+pub struct ExploreCommand {
+    #[clap(flatten)]
+    common: CommonOptions,
+
+    /// Write the report as an HTML page to the given path instead of printing
+    /// it
+    #[clap(long, value_name = "PATH", parse(from_os_str))]
+    html: Option<PathBuf>,
+
+    /// The path of the WebAssembly module to explore
+    #[clap(index = 1, value_name = "MODULE", parse(from_os_str))]
+    module: PathBuf,
+}
+
+/// Everything the report shows about one of the module's functions.
+struct Function {
+    index: u32,
+    /// The function's `module!function` name, as it's named in backtraces and
+    /// profiles.
+    name: String,
+    /// The function's operators, with their offsets in the module.
+    wasm: String,
+    /// The CLIF it was translated to, followed by its relocations, traps and
+    /// unwind info.
+    clif: String,
+    /// The disassembly of its machine code.
+    disassembly: String,
+}
+
+impl ExploreCommand {
+    /// Executes the command.
+    pub fn execute(self) -> Result<()> {
+        self.common.init_logging();
+
+        let wasm = wat::parse_file(&self.module)?;
+
+        // Cranelift writes out the CLIF of each function it compiles to this
+        // directory, which is gone again once the report is done.
+        let clif_dir = tempfile::tempdir().context("failed to create a temporary directory")?;
+        let functions = self.compile(&wasm, clif_dir.path())?;
+
+        match &self.html {
+            Some(path) => {
+                let title = self.module.display().to_string();
+                fs::write(path, html_report(&title, &functions))
+                    .with_context(|| format!("failed to write {}", path.display()))?;
+            }
+            None => print!("{}", text_report(&functions)),
+        }
+        Ok(())
+    }
+
+    fn compile(&self, wasm: &[u8], clif_dir: &Path) -> Result<Vec<Function>> {
+        let mut config = self.common.config(None)?;
+        let dir = clif_dir
+            .to_str()
+            .ok_or_else(|| anyhow!("{} isn't valid UTF-8", clif_dir.display()))?;
+        unsafe {
+            config.cranelift_flag_set("wasmtime_clif_dir", dir)?;
+        }
+        let engine = Engine::new(&config)?;
+        let image = engine
+            .precompile_module(wasm)
+            .with_context(|| format!("failed to compile {}", self.module.display()))?;
+        let mut disassembly = disassemble(&image)?;
+
+        Ok(wasm_functions(wasm)?
+            .into_iter()
+            .map(|(index, name, wasm)| {
+                let clif = fs::read_to_string(clif_dir.join(format!("wasm_func_{}.clif", index)))
+                    .unwrap_or_else(|_| "; not compiled with Cranelift\n".to_string());
+                Function {
+                    index,
+                    name,
+                    wasm,
+                    clif,
+                    disassembly: disassembly.remove(&index).unwrap_or_default(),
+                }
+            })
+            .collect())
+    }
+}
+
+/// Lists the operators of each function defined in `wasm`, along with its
+/// function index and symbol name.
+fn wasm_functions(wasm: &[u8]) -> Result<Vec<(u32, String, String)>> {
+    let mut functions = Vec::new();
+    let mut index = 0;
+    let mut module_name = None;
+    let mut func_names = HashMap::new();
+    for payload in WasmParser::new(0).parse_all(wasm) {
+        match payload? {
+            Payload::ImportSection(imports) => {
+                for import in imports {
+                    if let TypeRef::Func(_) = import?.ty {
+                        index += 1;
+                    }
+                }
+            }
+            Payload::CodeSectionEntry(body) => {
+                let mut listing = String::new();
+                let mut operators = body.get_operators_reader()?;
+                while !operators.eof() {
+                    let (op, offset) = operators.read_with_offset()?;
+                    writeln!(listing, "{:#08x}  {:?}", offset, op).unwrap();
+                }
+                functions.push((index, listing));
+                index += 1;
+            }
+            Payload::CustomSection {
+                name: "name",
+                data,
+                data_offset,
+                ..
+            } => {
+                // Like compilation, a malformed name section only loses the
+                // names it would have provided.
+                let _ = read_names(data, data_offset, &mut module_name, &mut func_names);
+            }
+            _ => {}
+        }
+    }
+    Ok(functions
+        .into_iter()
+        .map(|(index, listing)| {
+            let func_name = func_names.get(&index).copied();
+            let name = symbol_name(module_name, func_name, index as usize);
+            (index, name, listing)
+        })
+        .collect())
+}
+
+/// Reads the module's name and the names of its functions from the name
+/// section in `data`.
+fn read_names<'a>(
+    data: &'a [u8],
+    offset: usize,
+    module_name: &mut Option<&'a str>,
+    func_names: &mut HashMap<u32, &'a str>,
+) -> Result<()> {
+    for subsection in NameSectionReader::new(data, offset)? {
+        match subsection? {
+            Name::Module(module) => *module_name = Some(module.get_name()?),
+            Name::Function(names) => {
+                let mut names = names.get_map()?;
+                for _ in 0..names.get_count() {
+                    let Naming { index, name } = names.read()?;
+                    func_names.insert(index, name);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Disassembles each wasm function in the compiled `image`, keyed by its
+/// function index.
+fn disassemble(image: &[u8]) -> Result<HashMap<u32, String>> {
+    let cs = disassembler()?;
+    let elf = object::File::parse(image).context("failed to parse the compiled module")?;
+    let text = elf
+        .section_by_name(".text")
+        .ok_or_else(|| anyhow!("the compiled module has no text section"))?
+        .data()?;
+
+    let mut functions = HashMap::new();
+    for symbol in elf.symbols() {
+        let index = match symbol.name().ok().and_then(try_parse_func_name) {
+            Some(index) => index,
+            None => continue,
+        };
+        let start = symbol.address() as usize;
+        let code = &text[start..][..symbol.size() as usize];
+        let insns = cs.disasm_all(code, 0).map_err(|e| anyhow!("{}", e))?;
+        let mut listing = String::new();
+        for insn in insns.iter() {
+            let line = format!(
+                "{:#06x}  {} {}",
+                insn.address(),
+                insn.mnemonic().unwrap_or(""),
+                insn.op_str().unwrap_or("")
+            );
+            writeln!(listing, "{}", line.trim_end()).unwrap();
+        }
+        functions.insert(index.as_u32(), listing);
+    }
+    Ok(functions)
+}
+
+fn disassembler() -> Result<Capstone> {
+    let cs = match Triple::host().architecture {
+        Architecture::X86_64 => Capstone::new()
+            .x86()
+            .mode(arch::x86::ArchMode::Mode64)
+            .build(),
+        Architecture::Aarch64 { .. } => Capstone::new()
+            .arm64()
+            .mode(arch::arm64::ArchMode::Arm)
+            .build()
+            .and_then(|mut cs| {
+                // Inline constants aren't valid instructions, and would stop
+                // the disassembly without this.
+                cs.set_skipdata(true)?;
+                Ok(cs)
+            }),
+        Architecture::S390x => Capstone::new()
+            .sysz()
+            .mode(arch::sysz::ArchMode::Default)
+            .build(),
+        arch => bail!("disassembling {} code isn't supported", arch),
+    };
+    cs.map_err(|e| anyhow!("failed to create a disassembler: {}", e))
+}
+
+fn text_report(functions: &[Function]) -> String {
+    let mut report = String::new();
+    for func in functions {
+        writeln!(report, ";; function {}: {}", func.index, func.name).unwrap();
+        writeln!(report, ";; wasm\n{}", func.wasm).unwrap();
+        writeln!(report, ";; clif\n{}", func.clif).unwrap();
+        writeln!(report, ";; disassembly\n{}", func.disassembly).unwrap();
+    }
+    report
+}
+
+fn html_report(title: &str, functions: &[Function]) -> String {
+    let mut report = String::new();
+    writeln!(
+        report,
+        "<!DOCTYPE html>\n\
+         <html>\n\
+         <head>\n\
+         <meta charset=\"utf-8\">\n\
+         <title>wasmtime explore: {}</title>\n\
+         <style>\n\
+         .columns {{ display: grid; grid-template-columns: repeat(3, 1fr); gap: 1em; }}\n\
+         pre {{ overflow-x: auto; background: #f4f4f4; padding: 0.5em; }}\n\
+         </style>\n\
+         </head>\n\
+         <body>\n\
+         <h1>{}</h1>",
+        escape(title),
+        escape(title)
+    )
+    .unwrap();
+    for func in functions {
+        writeln!(
+            report,
+            "<section id=\"func{index}\">\n\
+             <h2>function {index}: {}</h2>\n\
+             <div class=\"columns\">\n\
+             <div><h3>wasm</h3><pre>{}</pre></div>\n\
+             <div><h3>clif</h3><pre>{}</pre></div>\n\
+             <div><h3>disassembly</h3><pre>{}</pre></div>\n\
+             </div>\n\
+             </section>",
+            escape(&func.name),
+            escape(&func.wasm),
+            escape(&func.clif),
+            escape(&func.disassembly),
+            index = func.index,
+        )
+        .unwrap();
+    }
+    report.push_str("</body>\n</html>\n");
+    report
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
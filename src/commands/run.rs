@@ -11,9 +11,13 @@ use std::{
     path::{Component, Path, PathBuf},
     process,
 };
-use wasmtime::{Engine, Func, Linker, Module, Store, Trap, Val, ValType};
+use wasmtime::{Engine, Func, Linker, Module, Store, Trap, TrapCode, Val, ValType};
 use wasmtime_cli_flags::{CommonOptions, WasiModules};
-use wasmtime_wasi::sync::{ambient_authority, Dir, TcpListener, WasiCtxBuilder};
+use wasmtime_wasi::fs::{DirCaps, FileCaps, MemDir, Overlay};
+use wasmtime_wasi::sync::{
+    ambient_authority, net::Socket, Dir, TcpListener, TcpStream, WasiCtxBuilder,
+};
+use wasmtime_wasi::{WasiCtx, WasiDir};
 
 #[cfg(feature = "wasi-nn")]
 use wasmtime_wasi_nn::WasiNnCtx;
@@ -24,14 +28,15 @@ use wasmtime_wasi_crypto::WasiCryptoCtx;
 fn parse_module(s: &OsStr) -> anyhow::Result<PathBuf> {
     // Do not accept wasmtime subcommand names as the module name
     match s.to_str() {
-        Some("help") | Some("config") | Some("run") | Some("wast") | Some("compile") => {
+        Some("help") | Some("config") | Some("run") | Some("wast") | Some("compile")
+        | Some("explore") | Some("serve") => {
             bail!("module name cannot be the same as a subcommand")
         }
         _ => Ok(s.into()),
     }
 }
 
-fn parse_env_var(s: &str) -> Result<(String, String)> {
+pub(crate) fn parse_env_var(s: &str) -> Result<(String, String)> {
     let parts: Vec<_> = s.splitn(2, '=').collect();
     if parts.len() != 2 {
         bail!("must be of the form `key=value`");
@@ -39,15 +44,108 @@ fn parse_env_var(s: &str) -> Result<(String, String)> {
     Ok((parts[0].to_owned(), parts[1].to_owned()))
 }
 
-fn parse_map_dirs(s: &str) -> Result<(String, String)> {
+/// A directory to preopen for the guest, and the capabilities it's granted.
+/// Directories without a host directory are held in memory.
+#[derive(Clone, Debug)]
+pub(crate) struct PreopenDir {
+    host: Option<String>,
+    guest: String,
+    caps: DirCaps,
+    file_caps: FileCaps,
+    overlay: bool,
+}
+
+impl PreopenDir {
+    fn new(host: Option<&str>, guest: &str, perms: Option<&str>) -> Result<Self> {
+        let mut caps = DirCaps::all();
+        let mut file_caps = FileCaps::all();
+        let mut overlay = false;
+        for perm in perms.into_iter().flat_map(|perms| perms.split(',')) {
+            match perm {
+                "rw" => {}
+                "overlay" if host.is_some() => overlay = true,
+                "ro" => {
+                    caps &= DirCaps::read_only();
+                    file_caps &= FileCaps::read_only();
+                }
+                "nocreate" => {
+                    caps -= DirCaps::CREATE_DIRECTORY
+                        | DirCaps::CREATE_FILE
+                        | DirCaps::LINK_TARGET
+                        | DirCaps::RENAME_TARGET
+                        | DirCaps::SYMLINK;
+                }
+                "nounlink" => {
+                    caps -=
+                        DirCaps::REMOVE_DIRECTORY | DirCaps::UNLINK_FILE | DirCaps::RENAME_SOURCE;
+                }
+                _ => bail!(
+                    "unknown directory permission `{}`, expected `ro`, `rw`, `nocreate`, `nounlink` or `overlay`",
+                    perm
+                ),
+            }
+        }
+        Ok(Self {
+            host: host.map(Into::into),
+            guest: guest.into(),
+            caps,
+            file_caps,
+            overlay,
+        })
+    }
+
+    /// Opens the directory and preopens it for the guest of `wasi`.
+    pub(crate) fn preopen(&self, wasi: &mut WasiCtx) -> Result<()> {
+        let dir = self.open()?;
+        wasi.push_preopened_dir_with_caps(dir, &self.guest, self.caps, self.file_caps)?;
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Box<dyn WasiDir>> {
+        let host = match &self.host {
+            Some(host) => host,
+            None => return Ok(Box::new(MemDir::new())),
+        };
+        let dir = Dir::open_ambient_dir(host, ambient_authority())
+            .with_context(|| format!("failed to open directory '{}'", host))?;
+        let dir = Box::new(wasmtime_wasi::sync::dir::Dir::from_cap_std(dir));
+        if self.overlay {
+            Ok(Box::new(Overlay::new(dir)))
+        } else {
+            Ok(dir)
+        }
+    }
+}
+
+pub(crate) fn parse_dirs(s: &str) -> Result<PreopenDir> {
     let parts: Vec<&str> = s.split("::").collect();
-    if parts.len() != 2 {
-        bail!("must contain exactly one double colon ('::')");
+    match parts[..] {
+        [host] => PreopenDir::new(Some(host), host, None),
+        [host, guest] => PreopenDir::new(Some(host), guest, None),
+        [host, guest, perms] => PreopenDir::new(Some(host), guest, Some(perms)),
+        _ => bail!("must contain at most two double colons ('::')"),
+    }
+}
+
+fn parse_map_dirs(s: &str) -> Result<PreopenDir> {
+    let parts: Vec<&str> = s.split("::").collect();
+    match parts[..] {
+        [guest, host] => PreopenDir::new(Some(host), guest, None),
+        [guest, host, perms] => PreopenDir::new(Some(host), guest, Some(perms)),
+        _ => bail!("must contain one or two double colons ('::')"),
+    }
+}
+
+fn parse_mem_dirs(s: &str) -> Result<PreopenDir> {
+    let parts: Vec<&str> = s.split("::").collect();
+    match parts[..] {
+        [guest] => PreopenDir::new(None, guest, None),
+        [guest, perms] => PreopenDir::new(None, guest, Some(perms)),
+        _ => bail!("must contain at most one double colon ('::')"),
     }
-    Ok((parts[0].into(), parts[1].into()))
 }
 
-fn parse_dur(s: &str) -> Result<Duration> {
+pub(crate) fn parse_dur(s: &str) -> Result<Duration> {
     // assume an integer without a unit specified is a number of seconds ...
     if let Ok(val) = s.parse() {
         return Ok(Duration::from_secs(val));
@@ -65,6 +163,82 @@ fn parse_preloads(s: &str) -> Result<(String, PathBuf)> {
     Ok((parts[0].into(), parts[1].into()))
 }
 
+/// Parses an argument to `--invoke` as a value of type `ty`, checking the
+/// type it's prefixed with, if any.
+fn parse_arg(ty: &ValType, arg: &str) -> Result<Val> {
+    let value = match arg.split_once(':') {
+        Some((prefix, value)) => {
+            if prefix != ty.to_string() {
+                bail!("the argument is typed as `{}`", prefix);
+            }
+            value
+        }
+        None => arg,
+    };
+    Ok(match ty {
+        ValType::I32 => Val::I32(parse_int(value, 32)? as u32 as i32),
+        ValType::I64 => Val::I64(parse_int(value, 64)? as u64 as i64),
+        ValType::F32 => Val::F32(value.parse::<f32>()?.to_bits()),
+        ValType::F64 => Val::F64(value.parse::<f64>()?.to_bits()),
+        ValType::V128 => Val::V128(parse_int(value, 128)?),
+        ValType::ExternRef if value == "null" => Val::ExternRef(None),
+        ValType::FuncRef if value == "null" => Val::FuncRef(None),
+        _ => bail!("only `null` references can be passed"),
+    })
+}
+
+/// Parses a decimal or `0x`-prefixed hex integer of the given bit width,
+/// accepting both its signed and unsigned range and returning its bit pattern.
+fn parse_int(s: &str, bits: u32) -> Result<u128> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    if digits.starts_with(['-', '+']) {
+        bail!("invalid digit found in string");
+    }
+    let magnitude = match digits.strip_prefix("0x") {
+        Some(hex) => u128::from_str_radix(hex, 16)?,
+        None => digits.parse::<u128>()?,
+    };
+    let max = u128::MAX >> (128 - bits);
+    if negative {
+        if magnitude > max / 2 + 1 {
+            bail!("`{}` is out of range for a {}-bit integer", s, bits);
+        }
+        Ok(magnitude.wrapping_neg() & max)
+    } else {
+        if magnitude > max {
+            bail!("`{}` is out of range for a {}-bit integer", s, bits);
+        }
+        Ok(magnitude)
+    }
+}
+
+/// Copies `text` into the main module's memory with the canonical ABI's
+/// `cabi_realloc` export, returning its address there.
+fn pass_string(store: &mut Store<Host>, linker: &Linker<Host>, text: &str) -> Result<u32> {
+    let memory = linker
+        .get(&mut *store, "", "memory")
+        .and_then(|export| export.into_memory())
+        .ok_or_else(|| anyhow!("the module doesn't export a `memory`"))?;
+    let realloc = linker
+        .get(&mut *store, "", "cabi_realloc")
+        .and_then(|export| export.into_func())
+        .ok_or_else(|| anyhow!("the module doesn't export `cabi_realloc`"))?
+        .typed::<(u32, u32, u32, u32), u32, _>(&*store)?;
+    let ptr = realloc.call(&mut *store, (0, 0, 1, text.len() as u32))?;
+    memory.write(&mut *store, ptr as usize, text.as_bytes())?;
+    Ok(ptr)
+}
+
+/// The exit status when the module runs for longer than `--wasm-timeout`,
+/// which is the same as that of the `timeout` utility.
+const TIMEOUT_EXIT_STATUS: i32 = 124;
+
+/// The exit status when the module runs out of `--fuel`.
+const OUT_OF_FUEL_EXIT_STATUS: i32 = 125;
+
 lazy_static::lazy_static! {
     static ref AFTER_HELP: String = {
         crate::FLAG_EXPLANATIONS.to_string()
@@ -92,7 +266,10 @@ pub struct RunCommand {
     allow_precompiled: bool,
 
     /// Inherit environment variables and file descriptors following the
-    /// systemd listen fd specification (UNIX only)
+    /// systemd listen fd specification (UNIX only). Passed TCP listeners are
+    /// preopened for the guest from descriptor 3, before any `--tcplisten`
+    /// sockets, so the guest can accept connections without being able to
+    /// bind sockets itself
     #[clap(long = "listenfd")]
     listenfd: bool,
 
@@ -104,21 +281,68 @@ pub struct RunCommand {
     )]
     tcplisten: Vec<String>,
 
-    /// Grant access to the given host directory
-    #[clap(long = "dir", number_of_values = 1, value_name = "DIRECTORY")]
-    dirs: Vec<String>,
+    /// Grant access to a TCP connection to the given socket address, which
+    /// is connected before the module runs
+    #[clap(
+        long = "tcpconnect",
+        number_of_values = 1,
+        value_name = "SOCKET ADDRESS"
+    )]
+    tcpconnect: Vec<String>,
+
+    /// Grant access to the given host directory, at the given guest path if
+    /// there is one. The permissions are a comma-separated list of `ro` to
+    /// make it read-only, `nocreate` or `nounlink` to prevent creating or
+    /// removing anything in it, `overlay` to keep any changes to it in memory
+    /// instead of writing them to the host directory, or `rw`, the default.
+    #[clap(
+        long = "dir",
+        number_of_values = 1,
+        value_name = "HOST_DIR[::GUEST_DIR[::PERMS]]",
+        parse(try_from_str = parse_dirs)
+    )]
+    dirs: Vec<PreopenDir>,
 
     /// Pass an environment variable to the program
     #[clap(long = "env", number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
     vars: Vec<(String, String)>,
 
-    /// The name of the function to run
+    /// The name of the function to run.
+    ///
+    /// The module's arguments are parsed as the function's parameters, in
+    /// order. Integers may be written in decimal or as `0x`-prefixed hex, and
+    /// floats as decimals, `inf` or `nan`. Any argument may be prefixed with
+    /// its type, as in `f32:1.5`, which is checked against the function's
+    /// signature. A `string:TEXT` argument is copied into the module's
+    /// `memory` with its `cabi_realloc` export and passed as a pointer and
+    /// length, taking up two `i32` parameters. Negative arguments need to come
+    /// after a `--`.
     #[clap(long, value_name = "FUNCTION")]
     invoke: Option<String>,
 
-    /// Grant access to a guest directory mapped as a host directory
-    #[clap(long = "mapdir", number_of_values = 1, value_name = "GUEST_DIR::HOST_DIR", parse(try_from_str = parse_map_dirs))]
-    map_dirs: Vec<(String, String)>,
+    /// Print the results of the invoked function with their types, in the
+    /// same `TYPE:VALUE` form typed arguments take
+    #[clap(long = "typed-results", requires = "invoke")]
+    typed_results: bool,
+
+    /// Grant access to a guest directory mapped as a host directory, with
+    /// the same optional permissions as `--dir`
+    #[clap(long = "mapdir", number_of_values = 1, value_name = "GUEST_DIR::HOST_DIR[::PERMS]", parse(try_from_str = parse_map_dirs))]
+    map_dirs: Vec<PreopenDir>,
+
+    /// Grant access to an empty in-memory directory at the given guest path,
+    /// with the same optional permissions as `--dir`
+    #[clap(long = "memdir", number_of_values = 1, value_name = "GUEST_DIR[::PERMS]", parse(try_from_str = parse_mem_dirs))]
+    mem_dirs: Vec<PreopenDir>,
+
+    /// Only allow the module to use the given wasi-crypto algorithm, such as
+    /// `Ed25519`; every algorithm is allowed unless this is passed
+    #[clap(
+        long = "wasi-crypto-algorithm",
+        number_of_values = 1,
+        value_name = "ALGORITHM"
+    )]
+    wasi_crypto_algorithms: Vec<String>,
 
     /// The path of the WebAssembly module to run
     #[clap(
@@ -137,7 +361,8 @@ pub struct RunCommand {
     )]
     preloads: Vec<(String, PathBuf)>,
 
-    /// Maximum execution time of wasm code before timing out (1, 2s, 100ms, etc)
+    /// Maximum execution time of wasm code before timing out (1, 2s, 100ms,
+    /// etc), exiting with status 124 if it does
     #[clap(
         long = "wasm-timeout",
         value_name = "TIME",
@@ -145,6 +370,11 @@ pub struct RunCommand {
     )]
     wasm_timeout: Option<Duration>,
 
+    /// Write a wasm core dump to the given path if the module traps
+    #[cfg(feature = "coredump")]
+    #[clap(long = "coredump-on-trap", value_name = "PATH")]
+    coredump_on_trap: Option<PathBuf>,
+
     // NOTE: this must come last for trailing varargs
     /// The arguments to pass to the module
     #[clap(value_name = "ARGS")]
@@ -160,6 +390,10 @@ impl RunCommand {
         if self.wasm_timeout.is_some() {
             config.epoch_interruption(true);
         }
+        #[cfg(feature = "coredump")]
+        if self.coredump_on_trap.is_some() {
+            config.coredump_on_trap(true);
+        }
         let engine = Engine::new(&config)?;
         let mut store = Store::new(&engine, Host::default());
 
@@ -187,6 +421,8 @@ impl RunCommand {
             &self.common.wasi_modules.unwrap_or(WasiModules::default()),
             self.listenfd,
             preopen_sockets,
+            self.common.deterministic,
+            &self.wasi_crypto_algorithms,
         )?;
 
         // Load the preload wasm modules.
@@ -224,6 +460,23 @@ impl RunCommand {
 
                     eprintln!("Error: {:?}", e);
 
+                    #[cfg(feature = "coredump")]
+                    if let Some(path) = &self.coredump_on_trap {
+                        self.write_coredump(trap, path)?;
+                    }
+
+                    // Running out of time or fuel has its own exit status, so
+                    // that it can be told apart from the module failing.
+                    if self.wasm_timeout.is_some() && trap.trap_code() == Some(TrapCode::Interrupt)
+                    {
+                        process::exit(TIMEOUT_EXIT_STATUS);
+                    }
+                    if let Some(fuel) = self.common.fuel {
+                        if store.fuel_consumed() >= Some(fuel) {
+                            process::exit(OUT_OF_FUEL_EXIT_STATUS);
+                        }
+                    }
+
                     // If the program exited because of a trap, return an error code
                     // to the outside environment indicating a more severe problem
                     // than a simple failure.
@@ -243,30 +496,31 @@ impl RunCommand {
         Ok(())
     }
 
-    fn compute_preopen_dirs(&self) -> Result<Vec<(String, Dir)>> {
-        let mut preopen_dirs = Vec::new();
+    #[cfg(feature = "coredump")]
+    fn write_coredump(&self, trap: &Trap, path: &Path) -> Result<()> {
+        let coredump = match trap.coredump() {
+            Some(coredump) => coredump,
+            None => return Ok(()),
+        };
+        let name = self.compute_argv().swap_remove(0);
+        std::fs::write(path, coredump.serialize(&name))
+            .with_context(|| format!("failed to write core dump to '{}'", path.display()))?;
+        eprintln!("Core dump written to {}", path.display());
+        Ok(())
+    }
 
-        for dir in self.dirs.iter() {
-            preopen_dirs.push((
-                dir.clone(),
-                Dir::open_ambient_dir(dir, ambient_authority())
-                    .with_context(|| format!("failed to open directory '{}'", dir))?,
-            ));
-        }
+    fn compute_preopen_dirs(&self) -> Result<Vec<(PreopenDir, Box<dyn WasiDir>)>> {
+        let mut preopen_dirs = Vec::new();
 
-        for (guest, host) in self.map_dirs.iter() {
-            preopen_dirs.push((
-                guest.clone(),
-                Dir::open_ambient_dir(host, ambient_authority())
-                    .with_context(|| format!("failed to open directory '{}'", host))?,
-            ));
+        for dir in self.dirs.iter().chain(&self.map_dirs).chain(&self.mem_dirs) {
+            preopen_dirs.push((dir.clone(), dir.open()?));
         }
 
         Ok(preopen_dirs)
     }
 
-    fn compute_preopen_sockets(&self) -> Result<Vec<TcpListener>> {
-        let mut listeners = vec![];
+    fn compute_preopen_sockets(&self) -> Result<Vec<Socket>> {
+        let mut sockets = vec![];
 
         for address in &self.tcplisten {
             let stdlistener = std::net::TcpListener::bind(address)
@@ -274,9 +528,16 @@ impl RunCommand {
 
             let _ = stdlistener.set_nonblocking(true)?;
 
-            listeners.push(TcpListener::from_std(stdlistener))
+            sockets.push(TcpListener::from_std(stdlistener).into())
+        }
+
+        for address in &self.tcpconnect {
+            let stdstream = std::net::TcpStream::connect(address)
+                .with_context(|| format!("failed to connect to address '{}'", address))?;
+
+            sockets.push(TcpStream::from_std(stdstream).into())
         }
-        Ok(listeners)
+        Ok(sockets)
     }
 
     fn compute_argv(&self) -> Vec<String> {
@@ -324,7 +585,7 @@ impl RunCommand {
             self.invoke_export(store, linker, name)
         } else {
             let func = linker.get_default(&mut *store, "")?;
-            self.invoke_func(store, func, None)
+            self.invoke_func(store, linker, func, None)
         }
     }
 
@@ -342,10 +603,16 @@ impl RunCommand {
             Some(func) => func,
             None => bail!("export of `{}` wasn't a function", name),
         };
-        self.invoke_func(store, func, Some(name))
+        self.invoke_func(store, linker, func, Some(name))
     }
 
-    fn invoke_func(&self, store: &mut Store<Host>, func: Func, name: Option<&str>) -> Result<()> {
+    fn invoke_func(
+        &self,
+        store: &mut Store<Host>,
+        linker: &Linker<Host>,
+        func: Func,
+        name: Option<&str>,
+    ) -> Result<()> {
         let ty = func.ty(&store);
         if ty.params().len() > 0 {
             eprintln!(
@@ -353,41 +620,46 @@ impl RunCommand {
                  is experimental and may break in the future"
             );
         }
-        let mut args = self.module_args.iter();
+        let what = match name {
+            Some(name) => format!("`{}`", name),
+            None => "command default".to_string(),
+        };
+        let params = ty.params().collect::<Vec<_>>();
+        let mut args = self.module_args.iter().enumerate();
         let mut values = Vec::new();
-        for ty in ty.params() {
-            let val = match args.next() {
-                Some(s) => s,
-                None => {
-                    if let Some(name) = name {
-                        bail!("not enough arguments for `{}`", name)
-                    } else {
-                        bail!("not enough arguments for command default")
-                    }
-                }
+        while values.len() < params.len() {
+            let (i, arg) = match args.next() {
+                Some(arg) => arg,
+                None => bail!("not enough arguments for {}", what),
             };
-            values.push(match ty {
-                // TODO: integer parsing here should handle hexadecimal notation
-                // like `0x0...`, but the Rust standard library currently only
-                // parses base-10 representations.
-                ValType::I32 => Val::I32(val.parse()?),
-                ValType::I64 => Val::I64(val.parse()?),
-                ValType::F32 => Val::F32(val.parse()?),
-                ValType::F64 => Val::F64(val.parse()?),
-                t => bail!("unsupported argument type {:?}", t),
-            });
+            if let Some(text) = arg.strip_prefix("string:") {
+                match params[values.len()..] {
+                    [ValType::I32, ValType::I32, ..] => {}
+                    _ => bail!(
+                        "argument {} is a string, but {} doesn't take an `i32` pointer \
+                         and length at parameter {}",
+                        i,
+                        what,
+                        values.len()
+                    ),
+                }
+                let ptr = pass_string(store, linker, text)
+                    .with_context(|| format!("failed to pass argument {} to {}", i, what))?;
+                values.push(Val::I32(ptr as i32));
+                values.push(Val::I32(text.len() as i32));
+                continue;
+            }
+            let ty = &params[values.len()];
+            values.push(parse_arg(ty, arg).with_context(|| {
+                format!("failed to parse argument {} for {} as `{}`", i, what, ty)
+            })?);
         }
 
         // Invoke the function and then afterwards print all the results that came
         // out, if there are any.
         let mut results = vec![Val::null(); ty.results().len()];
-        func.call(store, &values, &mut results).with_context(|| {
-            if let Some(name) = name {
-                format!("failed to invoke `{}`", name)
-            } else {
-                format!("failed to invoke command default")
-            }
-        })?;
+        func.call(&mut *store, &values, &mut results)
+            .with_context(|| format!("failed to invoke {}", what))?;
         if !results.is_empty() {
             eprintln!(
                 "warning: using `--invoke` with a function that returns values \
@@ -396,14 +668,23 @@ impl RunCommand {
         }
 
         for result in results {
-            match result {
-                Val::I32(i) => println!("{}", i),
-                Val::I64(i) => println!("{}", i),
-                Val::F32(f) => println!("{}", f32::from_bits(f)),
-                Val::F64(f) => println!("{}", f64::from_bits(f)),
-                Val::ExternRef(_) => println!("<externref>"),
-                Val::FuncRef(_) => println!("<funcref>"),
-                Val::V128(i) => println!("{}", i),
+            let value = match &result {
+                Val::I32(i) => i.to_string(),
+                Val::I64(i) => i.to_string(),
+                Val::F32(f) => f32::from_bits(*f).to_string(),
+                Val::F64(f) => f64::from_bits(*f).to_string(),
+                Val::ExternRef(None) | Val::FuncRef(None) if self.typed_results => {
+                    "null".to_string()
+                }
+                Val::ExternRef(_) => "<externref>".to_string(),
+                Val::FuncRef(_) => "<funcref>".to_string(),
+                Val::V128(i) if self.typed_results => format!("{:#034x}", i),
+                Val::V128(i) => i.to_string(),
+            };
+            if self.typed_results {
+                println!("{}:{}", result.ty(), value);
+            } else {
+                println!("{}", value);
             }
         }
 
@@ -411,28 +692,34 @@ impl RunCommand {
     }
 
     fn load_module(&self, engine: &Engine, path: &Path) -> Result<Module> {
-        // Peek at the first few bytes of the file to figure out if this is
-        // something we can pass off to `deserialize_file` which is fastest if
-        // we don't actually read the whole file into memory. Note that this
-        // behavior is disabled by default, though, because it's not safe to
-        // pass arbitrary user input to this command with `--allow-precompiled`
-        let mut file =
-            File::open(path).with_context(|| format!("failed to open: {}", path.display()))?;
-        let mut magic = [0; 4];
-        if let Ok(()) = file.read_exact(&mut magic) {
-            if &magic == b"\x7fELF" {
-                if self.allow_precompiled {
-                    return unsafe { Module::deserialize_file(engine, path) };
-                }
-                bail!(
-                    "cannot load precompiled module `{}` unless --allow-precompiled is passed",
-                    path.display()
-                )
+        load_module(engine, path, self.allow_precompiled)
+    }
+}
+
+/// Loads the module at `path`, which is only deserialized if it's precompiled
+/// when `allow_precompiled` is set.
+pub(crate) fn load_module(engine: &Engine, path: &Path, allow_precompiled: bool) -> Result<Module> {
+    // Peek at the first few bytes of the file to figure out if this is
+    // something we can pass off to `deserialize_file` which is fastest if
+    // we don't actually read the whole file into memory. Note that this
+    // behavior is disabled by default, though, because it's not safe to
+    // pass arbitrary user input to this command with `--allow-precompiled`
+    let mut file =
+        File::open(path).with_context(|| format!("failed to open: {}", path.display()))?;
+    let mut magic = [0; 4];
+    if let Ok(()) = file.read_exact(&mut magic) {
+        if &magic == b"\x7fELF" {
+            if allow_precompiled {
+                return unsafe { Module::deserialize_file(engine, path) };
             }
+            bail!(
+                "cannot load precompiled module `{}` unless --allow-precompiled is passed",
+                path.display()
+            )
         }
-
-        Module::from_file(engine, path)
     }
+
+    Module::from_file(engine, path)
 }
 
 #[derive(Default)]
@@ -448,18 +735,23 @@ struct Host {
 fn populate_with_wasi(
     store: &mut Store<Host>,
     linker: &mut Linker<Host>,
-    preopen_dirs: Vec<(String, Dir)>,
+    preopen_dirs: Vec<(PreopenDir, Box<dyn WasiDir>)>,
     argv: &[String],
     vars: &[(String, String)],
     wasi_modules: &WasiModules,
     listenfd: bool,
-    mut tcplisten: Vec<TcpListener>,
+    preopen_sockets: Vec<Socket>,
+    deterministic: bool,
+    wasi_crypto_algorithms: &[String],
 ) -> Result<()> {
     if wasi_modules.wasi_common {
         wasmtime_wasi::add_to_linker(linker, |host| host.wasi.as_mut().unwrap())?;
 
         let mut builder = WasiCtxBuilder::new();
         builder = builder.inherit_stdio().args(argv)?.envs(vars)?;
+        if deterministic {
+            builder = builder.deterministic(0);
+        }
 
         let mut num_fd: usize = 3;
 
@@ -469,16 +761,17 @@ fn populate_with_wasi(
             builder = b;
         }
 
-        for listener in tcplisten.drain(..) {
-            builder = builder.preopened_socket(num_fd as _, listener)?;
+        for socket in preopen_sockets {
+            builder = builder.preopened_socket(num_fd as _, socket)?;
             num_fd += 1;
         }
 
-        for (name, dir) in preopen_dirs.into_iter() {
-            builder = builder.preopened_dir(dir, name)?;
+        let mut wasi = builder.build();
+        for (preopen, dir) in preopen_dirs.into_iter() {
+            wasi.push_preopened_dir_with_caps(dir, preopen.guest, preopen.caps, preopen.file_caps)?;
         }
 
-        store.data_mut().wasi = Some(builder.build());
+        store.data_mut().wasi = Some(wasi);
     }
 
     if wasi_modules.wasi_nn {
@@ -493,6 +786,9 @@ fn populate_with_wasi(
         }
     }
 
+    if !wasi_crypto_algorithms.is_empty() && !wasi_modules.wasi_crypto {
+        bail!("--wasi-crypto-algorithm requires --wasi-modules=experimental-wasi-crypto");
+    }
     if wasi_modules.wasi_crypto {
         #[cfg(not(feature = "wasi-crypto"))]
         {
@@ -501,7 +797,11 @@ fn populate_with_wasi(
         #[cfg(feature = "wasi-crypto")]
         {
             wasmtime_wasi_crypto::add_to_linker(linker, |host| host.wasi_crypto.as_mut().unwrap())?;
-            store.data_mut().wasi_crypto = Some(WasiCryptoCtx::new());
+            let mut ctx = WasiCryptoCtx::new();
+            if !wasi_crypto_algorithms.is_empty() {
+                ctx = ctx.allow_algorithms(wasi_crypto_algorithms.iter().cloned());
+            }
+            store.data_mut().wasi_crypto = Some(ctx);
         }
     }
 
@@ -518,7 +818,6 @@ fn ctx_set_listenfd(num_fd: usize, builder: WasiCtxBuilder) -> Result<(usize, Wa
     use listenfd::ListenFd;
 
     let mut builder = builder;
-    let mut num_fd = num_fd;
 
     for env in ["LISTEN_FDS", "LISTEN_FDNAMES"] {
         if let Ok(val) = std::env::var(env) {
@@ -528,14 +827,16 @@ fn ctx_set_listenfd(num_fd: usize, builder: WasiCtxBuilder) -> Result<(usize, Wa
 
     let mut listenfd = ListenFd::from_env();
 
+    // The guest finds the sockets at the same descriptors as the host
+    // passed them in, relative to `num_fd`, so descriptors of sockets which
+    // can't be passed on are left unused rather than reused.
     for i in 0..listenfd.len() {
         if let Some(stdlistener) = listenfd.take_tcp_listener(i)? {
             let _ = stdlistener.set_nonblocking(true)?;
             let listener = TcpListener::from_std(stdlistener);
-            builder = builder.preopened_socket((3 + i) as _, listener)?;
-            num_fd = 3 + i;
+            builder = builder.preopened_socket((num_fd + i) as _, listener)?;
         }
     }
 
-    Ok((num_fd, builder))
+    Ok((num_fd + listenfd.len(), builder))
 }
@@ -0,0 +1,301 @@
+//! The module that implements the `wasmtime serve` command.
+
+use super::run::{load_module, parse_dirs, parse_dur, parse_env_var, PreopenDir};
+use anyhow::{bail, Result};
+use clap::Parser;
+use std::ffi::OsStr;
+use std::io::{self, BufRead, Write};
+use std::net::TcpListener;
+use std::path::{Component, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use wasmtime::{Engine, Linker, Module, Store, TrapCode};
+use wasmtime_cli_flags::CommonOptions;
+use wasmtime_wasi::pipe::{ReadPipe, WritePipe};
+use wasmtime_wasi::sync::{net, TcpStream, WasiCtxBuilder};
+use wasmtime_wasi::WasiCtx;
+
+/// How often the epoch is incremented to time requests out with
+/// `--request-timeout`, which is the precision of the timeout.
+const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+lazy_static::lazy_static! {
+    static ref AFTER_HELP: String = {
+        crate::FLAG_EXPLANATIONS.to_string()
+    };
+}
+
+/// Serves requests with a WebAssembly module, instantiating it for each one
+///
+/// The module is loaded once, and a new instance of it is created for each
+/// request, in a store of its own, and runs until its function returns.
+/// Requests are each line of stdin by default, which the instance reads from
+/// its stdin without its line terminator, and whatever it writes to its stdout
+/// is written to stdout once it's done. With `--listen` requests are
+/// connections instead, which the instance reads from and writes to as its
+/// stdin and stdout. Failed requests are reported on stderr and don't stop
+/// the server.
+#[derive(Parser)]
+#[structopt(name = "serve", trailing_var_arg = true, after_help = AFTER_HELP.as_str())]
+pub struct ServeCommand {
+    #[clap(flatten)]
+    common: CommonOptions,
+
+    /// Allow executing precompiled WebAssembly modules as `*.cwasm` files.
+    ///
+    /// Note that this option is not safe to pass if the module being passed in
+    /// is arbitrary user input. Only `wasmtime`-precompiled modules generated
+    /// via the `wasmtime compile` command or equivalent should be passed as an
+    /// argument with this option specified.
+    #[clap(long = "allow-precompiled")]
+    allow_precompiled: bool,
+
+    /// Serve each connection accepted on the given TCP socket address as a
+    /// request, rather than each line of stdin
+    #[clap(long = "listen", value_name = "SOCKET ADDRESS")]
+    listen: Option<String>,
+
+    /// The number of requests which are handled at the same time, each on a
+    /// thread of its own
+    #[clap(long, value_name = "N", default_value = "1")]
+    concurrency: usize,
+
+    /// Maximum execution time of wasm code for each request (1, 2s, 100ms,
+    /// etc), after which the request fails
+    #[clap(
+        long = "request-timeout",
+        value_name = "TIME",
+        parse(try_from_str = parse_dur),
+    )]
+    request_timeout: Option<Duration>,
+
+    /// The name of the function to call for each request
+    #[clap(long, value_name = "FUNCTION", default_value = "_start")]
+    invoke: String,
+
+    /// Grant each request access to the given host directory, in the same
+    /// way as `wasmtime run --dir`
+    #[clap(
+        long = "dir",
+        number_of_values = 1,
+        value_name = "HOST_DIR[::GUEST_DIR[::PERMS]]",
+        parse(try_from_str = parse_dirs)
+    )]
+    dirs: Vec<PreopenDir>,
+
+    /// Pass an environment variable to each request
+    #[clap(long = "env", number_of_values = 1, value_name = "NAME=VAL", parse(try_from_str = parse_env_var))]
+    vars: Vec<(String, String)>,
+
+    /// The path of the WebAssembly module to serve requests with
+    #[clap(required = true, value_name = "MODULE", parse(from_os_str))]
+    module: PathBuf,
+
+    // NOTE: this must come last for trailing varargs
+    /// The arguments to pass to each request
+    #[clap(value_name = "ARGS")]
+    module_args: Vec<String>,
+}
+
+/// A request, numbered in the order it was received.
+enum Request {
+    /// A line of stdin.
+    Line(u64, String),
+    /// A connection accepted on the `--listen` socket.
+    Connection(u64, std::net::TcpStream),
+}
+
+/// What each request is handled with, shared by the threads handling them.
+struct Server {
+    module: Module,
+    linker: Linker<WasiCtx>,
+    argv: Vec<String>,
+    vars: Vec<(String, String)>,
+    dirs: Vec<PreopenDir>,
+    fuel: Option<u64>,
+    invoke: String,
+    /// The epoch deadline of each request, if it has a timeout.
+    deadline: Option<u64>,
+}
+
+impl ServeCommand {
+    /// Executes the command.
+    pub fn execute(&self) -> Result<()> {
+        self.common.init_logging();
+        if self.concurrency == 0 {
+            bail!("the concurrency must be at least 1");
+        }
+
+        let mut config = self.common.config(None)?;
+        if self.request_timeout.is_some() {
+            config.epoch_interruption(true);
+        }
+        let engine = Engine::new(&config)?;
+        let module = load_module(&engine, &self.module, self.allow_precompiled)?;
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |wasi| wasi)?;
+
+        let deadline = self.request_timeout.map(|timeout| {
+            let engine = engine.clone();
+            thread::spawn(move || loop {
+                thread::sleep(EPOCH_TICK);
+                engine.increment_epoch();
+            });
+            (timeout.as_nanos() / EPOCH_TICK.as_nanos()).max(1) as u64
+        });
+
+        let server = Arc::new(Server {
+            module,
+            linker,
+            argv: self.compute_argv(),
+            vars: self.vars.clone(),
+            dirs: self.dirs.clone(),
+            fuel: self.common.fuel,
+            invoke: self.invoke.clone(),
+            deadline,
+        });
+
+        // Requests are handed to the first thread which is free to handle
+        // them, so reading requests waits for one to be.
+        let (sender, receiver) = mpsc::sync_channel(0);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = (0..self.concurrency)
+            .map(|_| {
+                let server = server.clone();
+                let receiver = receiver.clone();
+                thread::spawn(move || server.handle_requests(&receiver))
+            })
+            .collect::<Vec<_>>();
+
+        match &self.listen {
+            Some(address) => {
+                let listener = TcpListener::bind(address)?;
+                eprintln!("Listening on {}", listener.local_addr()?);
+                for (id, stream) in (1..).zip(listener.incoming()) {
+                    match stream {
+                        Ok(stream) => sender.send(Request::Connection(id, stream))?,
+                        Err(e) => eprintln!("Error: failed to accept connection: {}", e),
+                    }
+                }
+            }
+            None => {
+                for (id, line) in (1..).zip(io::stdin().lock().lines()) {
+                    sender.send(Request::Line(id, line?))?;
+                }
+            }
+        }
+
+        drop(sender);
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        Ok(())
+    }
+
+    fn compute_argv(&self) -> Vec<String> {
+        // Like `wasmtime run`, only the base name of the module is passed as
+        // argv[0], to avoid leaking path information.
+        let name = self
+            .module
+            .components()
+            .next_back()
+            .map(Component::as_os_str)
+            .and_then(OsStr::to_str)
+            .unwrap_or("");
+        Some(name.to_string())
+            .into_iter()
+            .chain(self.module_args.iter().cloned())
+            .collect()
+    }
+}
+
+impl Server {
+    /// Handles the requests received from `receiver`, until it's closed.
+    fn handle_requests(&self, receiver: &Mutex<Receiver<Request>>) {
+        loop {
+            let request = match receiver.lock().unwrap().recv() {
+                Ok(request) => request,
+                Err(_) => return,
+            };
+            let id = match &request {
+                Request::Line(id, _) | Request::Connection(id, _) => *id,
+            };
+            if let Err(e) = self.handle(request) {
+                eprintln!("Error: request {} failed: {:?}", id, e);
+            }
+        }
+    }
+
+    fn handle(&self, request: Request) -> Result<()> {
+        let stdout = WritePipe::new_in_memory();
+        let builder = WasiCtxBuilder::new()
+            .inherit_stderr()
+            .args(&self.argv)?
+            .envs(&self.vars)?;
+        let builder = match &request {
+            Request::Line(_, line) => builder
+                .stdin(Box::new(ReadPipe::from(line.as_str())))
+                .stdout(Box::new(stdout.clone())),
+            Request::Connection(_, stream) => {
+                let stream = || -> Result<_> {
+                    let stream = TcpStream::from_std(stream.try_clone()?);
+                    Ok(Box::new(net::TcpStream::from_cap_std(stream)))
+                };
+                builder.stdin(stream()?).stdout(stream()?)
+            }
+        };
+        let mut wasi = builder.build();
+        for dir in &self.dirs {
+            dir.preopen(&mut wasi)?;
+        }
+
+        let mut store = Store::new(self.linker.engine(), wasi);
+        if let Some(fuel) = self.fuel {
+            store.add_fuel(fuel)?;
+        }
+        if let Some(deadline) = self.deadline {
+            store.set_epoch_deadline(deadline);
+        }
+        let result = self.run(&mut store);
+        drop(store);
+
+        // The output of a line is written all at once, so that the outputs of
+        // requests which are handled at the same time aren't interleaved.
+        if let Request::Line(..) = request {
+            let output = stdout.try_into_inner().unwrap().into_inner();
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(&output)?;
+            stdout.flush()?;
+        }
+        result
+    }
+
+    /// Instantiates the module in `store` and calls its function, which fails
+    /// unless it returns or exits with a status of 0.
+    fn run(&self, store: &mut Store<WasiCtx>) -> Result<()> {
+        let error = match self.call(store) {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        if let Some(trap) = error.downcast_ref::<wasmtime::Trap>() {
+            match trap.i32_exit_status() {
+                Some(0) => return Ok(()),
+                Some(status) => bail!("exited with status {}", status),
+                None => {}
+            }
+            if self.deadline.is_some() && trap.trap_code() == Some(TrapCode::Interrupt) {
+                bail!("timed out");
+            }
+        }
+        Err(error)
+    }
+
+    fn call(&self, store: &mut Store<WasiCtx>) -> Result<()> {
+        let instance = self.linker.instantiate(&mut *store, &self.module)?;
+        let func = instance.get_typed_func::<(), (), _>(&mut *store, &self.invoke)?;
+        func.call(&mut *store, ())?;
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module $m
+        (import "" "sample" (func $sample))
+        (func $inner (param i32) (result i32)
+            call $sample
+            (i32.add (local.get 0) (i32.const 1)))
+        (func (export "run") (param i32) (result i32)
+            (call $inner (i32.mul (local.get 0) (i32.const 2))))
+    )
+"#;
+
+#[test]
+fn entries_round_trip() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+    let map = module.address_map().unwrap();
+    let text = map.text_range();
+
+    let mut mapped = 0;
+    for (pc, offset) in map.entries() {
+        assert!(text.contains(&pc));
+        let offset = match offset {
+            Some(offset) => offset as usize,
+            None => continue,
+        };
+        let frame = map.lookup_pc(pc).unwrap();
+        assert_eq!(frame.module_offset(), Some(offset));
+        assert_eq!(frame.module_name(), Some("m"));
+        assert!(map.pcs_for_offset(offset).any(|p| p == pc));
+        mapped += 1;
+    }
+    assert!(mapped > 0);
+
+    assert!(map.lookup_pc(text.start.wrapping_sub(1)).is_none());
+    assert!(map.lookup_pc(text.end + 1).is_none());
+    assert_eq!(map.pcs_for_offset(1).count(), 0);
+    Ok(())
+}
+
+#[test]
+fn resolves_pcs_on_the_stack() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, ());
+    let pcs = Arc::new(Mutex::new(Vec::new()));
+    let captured = pcs.clone();
+    let sample = Func::wrap(&mut store, move |caller: Caller<'_, ()>| {
+        let backtrace = Backtrace::capture(&caller);
+        let mut pcs = captured.lock().unwrap();
+        for frame in backtrace.frames().iter().filter(|f| !f.is_host()) {
+            let wasm = frame.wasm().unwrap();
+            pcs.push((frame.pc(), wasm.func_index(), wasm.module_offset()));
+        }
+    });
+    let instance = Instance::new(&mut store, &module, &[sample.into()])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 3)?, 7);
+
+    // The return addresses of the calls resolve to the calls themselves.
+    let pcs = pcs.lock().unwrap();
+    assert_eq!(pcs.len(), 2);
+    let map = module.address_map().unwrap();
+    for (pc, func_index, module_offset) in pcs.iter() {
+        for frame in [map.lookup_pc(pc - 1), FrameInfo::from_pc(pc - 1)] {
+            let frame = frame.unwrap();
+            assert_eq!(frame.func_index(), *func_index);
+            assert_eq!(frame.module_offset(), *module_offset);
+        }
+        let call = module_offset.unwrap();
+        assert!(map
+            .pcs_for_offset(call)
+            .any(|start| start < *pc && (pc - start) < 0x100));
+    }
+    assert_eq!(pcs[0].1, 1);
+    assert_eq!(pcs[1].1, 2);
+    Ok(())
+}
+
+#[test]
+fn modules_without_an_address_map() -> Result<()> {
+    let mut config = Config::new();
+    config.generate_address_map(false);
+    let module = Module::new(&Engine::new(&config)?, WAT)?;
+    assert!(module.address_map().is_none());
+
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let module = Module::new(&Engine::new(&config)?, WAT)?;
+    assert!(module.address_map().is_none());
+
+    assert!(FrameInfo::from_pc(entries_round_trip as usize).is_none());
+    Ok(())
+}
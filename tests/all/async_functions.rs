@@ -352,6 +352,46 @@ async fn fuel_eventually_finishes() {
     instance.await.unwrap();
 }
 
+#[tokio::test]
+async fn iloop_with_fuel_yield_interval() -> Result<()> {
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(10_000)?;
+    store.fuel_async_yield_interval(Some(100))?;
+    let module = Module::new(&engine, "(module (func (loop br 0)) (start 0))")?;
+    let instance = Instance::new_async(&mut store, &module, &[]);
+
+    // This should yield about once per 100 units of fuel, and then trap once
+    // all of the store's fuel is consumed.
+    let (result, pending) = CountPending::new(Box::pin(instance)).await;
+    let err = format!("{:?}", result.err().unwrap());
+    assert!(err.contains("all fuel consumed"), "{}", err);
+    assert!((90..=100).contains(&pending), "{}", pending);
+    assert!(store.fuel_consumed().unwrap() >= 10_000);
+    Ok(())
+}
+
+#[tokio::test]
+async fn fuel_yield_interval_accounting() -> Result<()> {
+    let engine = Engine::new(Config::new().async_support(true).consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(1_000)?;
+    store.fuel_async_yield_interval(Some(10))?;
+    assert_eq!(store.consume_fuel(500)?, 500);
+    assert!(store.consume_fuel(500).is_err());
+    store.add_fuel(100)?;
+    assert_eq!(store.consume_fuel(50)?, 550);
+    store.fuel_async_yield_interval(None)?;
+    assert_eq!(store.consume_fuel(50)?, 500);
+    assert_eq!(store.fuel_consumed(), Some(600));
+
+    assert!(store.fuel_async_yield_interval(Some(0)).is_err());
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut store = Store::new(&engine, ());
+    assert!(store.fuel_async_yield_interval(Some(10)).is_err());
+    Ok(())
+}
+
 #[tokio::test]
 async fn async_with_pooling_stacks() {
     let mut config = Config::new();
@@ -697,6 +737,136 @@ where
     }
 }
 
+async fn resumable_instance(store: &mut Store<Vec<i32>>) -> Result<Func> {
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "" "yield" (func $yield (param i32)))
+                (func (export "run") (param i32) (result i32)
+                    (call $yield (local.get 0))
+                    (call $yield (i32.add (local.get 0) (i32.const 1)))
+                    (i32.add (local.get 0) (i32.const 2)))
+            )
+        "#,
+    )?;
+    let yield_ = Func::wrap(
+        &mut *store,
+        |mut caller: Caller<'_, Vec<i32>>, x: i32| -> Result<(), Trap> {
+            caller.data_mut().push(x);
+            caller.suspend().map_err(Trap::from)?;
+            caller.data_mut().push(-x);
+            Ok(())
+        },
+    );
+    let instance = Instance::new_async(&mut *store, &module, &[yield_.into()]).await?;
+    Ok(instance.get_func(&mut *store, "run").unwrap())
+}
+
+#[tokio::test]
+async fn resumable_calls() -> Result<()> {
+    let mut store = Store::new(&Engine::new(Config::new().async_support(true))?, Vec::new());
+    let run = resumable_instance(&mut store).await?;
+
+    let mut suspensions = 0;
+    let results = {
+        let mut call = run.call_resumable(store.as_context_mut(), &[Val::I32(10)])?;
+        loop {
+            match call {
+                ResumableCall::Finished(results) => break results,
+                ResumableCall::Suspended(resumable) => {
+                    suspensions += 1;
+                    call = resumable.resume()?;
+                }
+            }
+        }
+    };
+    assert_eq!(suspensions, 2);
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].unwrap_i32(), 12);
+    assert_eq!(store.data(), &[10, -10, 11, -11]);
+
+    // Suspending from within `call_async` yields to the executor instead.
+    let mut results = [Val::I32(0)];
+    run.call_async(&mut store, &[Val::I32(20)], &mut results)
+        .await?;
+    assert_eq!(results[0].unwrap_i32(), 22);
+    Ok(())
+}
+
+#[tokio::test]
+async fn resumable_calls_interleave() -> Result<()> {
+    let engine = Engine::new(Config::new().async_support(true))?;
+    let mut stores = [
+        Store::new(&engine, Vec::new()),
+        Store::new(&engine, Vec::new()),
+    ];
+    let mut funcs = Vec::new();
+    for store in stores.iter_mut() {
+        funcs.push(resumable_instance(store).await?);
+    }
+
+    // Run both calls round-robin until they've finished.
+    let mut results = Vec::new();
+    {
+        let [a, b] = &mut stores;
+        let mut calls = vec![
+            funcs[0].call_resumable(a.as_context_mut(), &[Val::I32(1)])?,
+            funcs[1].call_resumable(b.as_context_mut(), &[Val::I32(100)])?,
+        ];
+        while !calls.is_empty() {
+            for call in std::mem::take(&mut calls) {
+                match call {
+                    ResumableCall::Finished(r) => results.push(r[0].unwrap_i32()),
+                    ResumableCall::Suspended(resumable) => calls.push(resumable.resume()?),
+                }
+            }
+        }
+    }
+    results.sort();
+    assert_eq!(results, [3, 102]);
+    assert_eq!(stores[0].data(), &[1, -1, 2, -2]);
+    assert_eq!(stores[1].data(), &[100, -100, 101, -101]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn dropping_resumable_cancels_call() -> Result<()> {
+    let mut store = Store::new(&Engine::new(Config::new().async_support(true))?, Vec::new());
+    let run = resumable_instance(&mut store).await?;
+    match run.call_resumable(store.as_context_mut(), &[Val::I32(5)])? {
+        ResumableCall::Suspended(resumable) => drop(resumable),
+        ResumableCall::Finished(_) => panic!("call should have been suspended"),
+    }
+    // The host function saw its suspension fail and never resumed.
+    assert_eq!(store.data(), &[5]);
+
+    // The store is still usable afterwards.
+    store.data_mut().clear();
+    let mut results = [Val::I32(0)];
+    run.call_async(&mut store, &[Val::I32(7)], &mut results)
+        .await?;
+    assert_eq!(results[0].unwrap_i32(), 9);
+    Ok(())
+}
+
+#[test]
+fn suspend_outside_async_call() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"(module (import "" "" (func)) (func (export "run") call 0))"#,
+    )?;
+    let suspend = Func::wrap(&mut store, |mut caller: Caller<'_, ()>| {
+        let err = caller.suspend().unwrap_err();
+        assert!(err.to_string().contains("cannot suspend"), "{}", err);
+    });
+    let instance = Instance::new(&mut store, &module, &[suspend.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    Ok(())
+}
+
 fn noop_waker() -> Waker {
     const VTABLE: RawWakerVTable =
         RawWakerVTable::new(|ptr| RawWaker::new(ptr, &VTABLE), |_| {}, |_| {}, |_| {});
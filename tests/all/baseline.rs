@@ -0,0 +1,489 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn engine(strategy: Strategy) -> Engine {
+    let mut config = Config::new();
+    config.strategy(strategy).unwrap();
+    Engine::new(&config).unwrap()
+}
+
+/// Calls the export `name` of `wat` with `params` using both the baseline
+/// compiler and Cranelift, asserting that they agree on the outcome.
+fn call_both(wat: &str, name: &str, params: &[Val]) -> Result<Vec<Val>> {
+    let run = |strategy| -> Result<Vec<Val>> {
+        let engine = engine(strategy);
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let log = Func::wrap(&mut store, |a: i32, b: i64, c: f32, d: f64| {
+            a as f64 + b as f64 + c as f64 + d
+        });
+        let imports = module
+            .imports()
+            .map(|_| log.into())
+            .collect::<Vec<Extern>>();
+        let instance = Instance::new(&mut store, &module, &imports)?;
+        let func = instance.get_func(&mut store, name).unwrap();
+        let mut results = vec![Val::I32(0); func.ty(&store).results().len()];
+        func.call(&mut store, params, &mut results)?;
+        Ok(results)
+    };
+    let format = |result: &Result<Vec<Val>>| match result {
+        Ok(vals) => format!("{:?}", vals.iter().map(bits).collect::<Vec<_>>()),
+        Err(e) => format!("{:?}", e.downcast_ref::<Trap>().and_then(|t| t.trap_code())),
+    };
+    let baseline = run(Strategy::Baseline);
+    let cranelift = run(Strategy::Cranelift);
+    assert_eq!(format(&baseline), format(&cranelift), "calling {}", name);
+    baseline
+}
+
+fn bits(val: &Val) -> u64 {
+    match *val {
+        Val::I32(i) => i as u32 as u64,
+        Val::I64(i) => i as u64,
+        Val::F32(f) if f32::from_bits(f).is_nan() => u64::MAX,
+        Val::F64(f) if f64::from_bits(f).is_nan() => u64::MAX,
+        Val::F32(f) => f.into(),
+        Val::F64(f) => f,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn integer_arithmetic() -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "i32") (param i32 i32) (result i32)
+                (i32.add (i32.mul (local.get 0) (local.get 1))
+                    (i32.xor (i32.rotl (local.get 0) (local.get 1))
+                        (i32.add (i32.clz (local.get 0))
+                            (i32.add (i32.ctz (local.get 1))
+                                (i32.shr_s (local.get 0) (local.get 1)))))))
+            (func (export "i64") (param i64 i64) (result i64)
+                (i64.sub (i64.or (local.get 0) (local.get 1))
+                    (i64.add (i64.rotr (local.get 0) (local.get 1))
+                        (i64.add (i64.clz (local.get 1))
+                            (i64.add (i64.ctz (local.get 0))
+                                (i64.extend_i32_s (i32.wrap_i64 (local.get 1))))))))
+            (func (export "div_s") (param i32 i32) (result i32)
+                (i32.div_s (local.get 0) (local.get 1)))
+            (func (export "rem_s") (param i64 i64) (result i64)
+                (i64.rem_s (local.get 0) (local.get 1)))
+            (func (export "div_u") (param i64 i64) (result i64)
+                (i64.div_u (local.get 0) (local.get 1)))
+            (func (export "cmp") (param i32 i32) (result i32)
+                (i32.add (i32.lt_s (local.get 0) (local.get 1))
+                    (i32.add (i32.shl (i32.gt_u (local.get 0) (local.get 1)) (i32.const 1))
+                        (i32.shl (i32.eqz (local.get 0)) (i32.const 2)))))
+            (func (export "select") (param i32 i64 i64) (result i64)
+                (select (local.get 1) (local.get 2) (local.get 0)))
+        )
+    "#;
+    let values = [0, 1, -1, 7, 31, 32, i32::MIN, i32::MAX];
+    for &a in &values {
+        for &b in &values {
+            let _ = call_both(wat, "i32", &[a.into(), b.into()]);
+            let (a64, b64) = ((a as i64) << 17 | 3, b as i64);
+            let _ = call_both(wat, "i64", &[a64.into(), b64.into()]);
+            let _ = call_both(wat, "div_s", &[a.into(), b.into()]);
+            let _ = call_both(wat, "rem_s", &[(a as i64).into(), b64.into()]);
+            let _ = call_both(wat, "div_u", &[a64.into(), b64.into()]);
+            let _ = call_both(wat, "cmp", &[a.into(), b.into()]);
+        }
+        let _ = call_both(wat, "select", &[a.into(), 5i64.into(), 6i64.into()]);
+    }
+    let _ = call_both(wat, "rem_s", &[i64::MIN.into(), (-1i64).into()])?;
+    Ok(())
+}
+
+#[test]
+fn float_arithmetic() -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "f32") (param f32 f32) (result f32)
+                (f32.add (f32.mul (local.get 0) (local.get 1))
+                    (f32.div (f32.copysign (local.get 0) (local.get 1))
+                        (f32.sqrt (f32.abs (local.get 1))))))
+            (func (export "f64") (param f64 f64) (result f64)
+                (f64.sub (f64.neg (local.get 0))
+                    (f64.promote_f32 (f32.demote_f64 (local.get 1)))))
+            (func (export "min") (param f64 f64) (result f64)
+                (f64.min (local.get 0) (local.get 1)))
+            (func (export "max") (param f32 f32) (result f32)
+                (f32.max (local.get 0) (local.get 1)))
+            (func (export "round") (param f64) (result f64)
+                (f64.add (f64.floor (local.get 0))
+                    (f64.add (f64.ceil (local.get 0))
+                        (f64.add (f64.trunc (local.get 0)) (f64.nearest (local.get 0))))))
+            (func (export "cmp") (param f64 f64) (result i32)
+                (i32.add (f64.lt (local.get 0) (local.get 1))
+                    (i32.add (i32.shl (f64.ge (local.get 0) (local.get 1)) (i32.const 1))
+                        (i32.add (i32.shl (f64.eq (local.get 0) (local.get 1)) (i32.const 2))
+                            (i32.shl (f64.ne (local.get 0) (local.get 1)) (i32.const 3))))))
+            (func (export "convert") (param i64) (result f64)
+                (f64.add (f64.convert_i64_u (local.get 0))
+                    (f64.add (f64.convert_i32_u (i32.wrap_i64 (local.get 0)))
+                        (f64.promote_f32 (f32.convert_i64_u (local.get 0))))))
+        )
+    "#;
+    let values = [
+        0.0,
+        -0.0,
+        1.5,
+        -2.5,
+        0.5,
+        1e300,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ];
+    for &a in &values {
+        for &b in &values {
+            let f32s = [
+                Val::F32((a as f32).to_bits()),
+                Val::F32((b as f32).to_bits()),
+            ];
+            call_both(wat, "f32", &f32s)?;
+            call_both(wat, "max", &f32s)?;
+            let f64s = [Val::F64(a.to_bits()), Val::F64(b.to_bits())];
+            call_both(wat, "f64", &f64s)?;
+            call_both(wat, "min", &f64s)?;
+            call_both(wat, "cmp", &f64s)?;
+        }
+        call_both(wat, "round", &[Val::F64(a.to_bits())])?;
+    }
+    for &i in &[0, 1, -1, i64::MIN, i64::MAX, (1 << 53) + 1] {
+        call_both(wat, "convert", &[i.into()])?;
+    }
+    Ok(())
+}
+
+#[test]
+fn float_truncation() -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "i32_s") (param f64) (result i32)
+                (i32.trunc_f64_s (local.get 0)))
+            (func (export "i32_u") (param f32) (result i32)
+                (i32.trunc_f32_u (local.get 0)))
+            (func (export "i64_s") (param f32) (result i64)
+                (i64.trunc_f32_s (local.get 0)))
+            (func (export "i64_u") (param f64) (result i64)
+                (i64.trunc_f64_u (local.get 0)))
+            (func (export "sat") (param f64) (result i64)
+                (i64.add (i64.trunc_sat_f64_u (local.get 0))
+                    (i64.add (i64.trunc_sat_f64_s (local.get 0))
+                        (i64.add (i64.extend_i32_u (i32.trunc_sat_f64_u (local.get 0)))
+                            (i64.extend_i32_s (i32.trunc_sat_f32_s
+                                (f32.demote_f64 (local.get 0))))))))
+        )
+    "#;
+    let values = [
+        0.0,
+        -0.9,
+        1.5,
+        -1.0,
+        2147483647.9,
+        2147483648.0,
+        -2147483648.9,
+        -2147483649.0,
+        4294967295.5,
+        4294967296.0,
+        9223372036854775807.0,
+        -9223372036854775808.0,
+        18446744073709549568.0,
+        18446744073709551616.0,
+        f64::INFINITY,
+        f64::NEG_INFINITY,
+        f64::NAN,
+    ];
+    for &x in &values {
+        let _ = call_both(wat, "i32_s", &[Val::F64(x.to_bits())]);
+        let _ = call_both(wat, "i32_u", &[Val::F32((x as f32).to_bits())]);
+        let _ = call_both(wat, "i64_s", &[Val::F32((x as f32).to_bits())]);
+        let _ = call_both(wat, "i64_u", &[Val::F64(x.to_bits())]);
+        call_both(wat, "sat", &[Val::F64(x.to_bits())])?;
+    }
+    Ok(())
+}
+
+#[test]
+fn control_flow() -> Result<()> {
+    let wat = r#"
+        (module
+            (func $fib (export "fib") (param i32) (result i32)
+                (if (result i32) (i32.lt_u (local.get 0) (i32.const 2))
+                    (then (local.get 0))
+                    (else
+                        (i32.add
+                            (call $fib (i32.sub (local.get 0) (i32.const 1)))
+                            (call $fib (i32.sub (local.get 0) (i32.const 2)))))))
+            (func (export "loop") (param i32) (result i64)
+                (local i64)
+                (block $done
+                    (loop $top
+                        (br_if $done (i32.eqz (local.get 0)))
+                        (local.set 1 (i64.add (local.get 1) (i64.extend_i32_u (local.get 0))))
+                        (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                        (br $top)))
+                (local.get 1))
+            (func (export "br_table") (param i32) (result i32)
+                (block $c (result i32)
+                    (block $b (result i32)
+                        (block $a (result i32)
+                            (i32.const 100)
+                            (local.get 0)
+                            (br_table $a $b $c $a)))
+                    (i32.const 10)
+                    (i32.add))
+                (i32.const 1)
+                (i32.add))
+            (func (export "multi") (param i32) (result i32)
+                (i32.const 1)
+                (i32.const 2)
+                (block (param i32 i32) (result i32 i32)
+                    (br_if 0 (local.get 0))
+                    (drop)
+                    (i32.const 3))
+                (i32.sub)
+                (unreachable)
+                (i32.const 1)
+                (drop))
+            (func (export "deep") (param i32) (result i32)
+                (block (result i32)
+                    (i32.const 7)
+                    (br_if 0 (local.get 0))
+                    (unreachable)
+                    (block
+                        (loop
+                            (br 0)))
+                    (i32.add)))
+        )
+    "#;
+    assert_eq!(bits(&call_both(wat, "fib", &[20.into()])?[0]), 6765);
+    assert_eq!(bits(&call_both(wat, "loop", &[1000.into()])?[0]), 500500);
+    for i in 0..5 {
+        call_both(wat, "br_table", &[i.into()])?;
+    }
+    for i in 0..2 {
+        let _ = call_both(wat, "multi", &[i.into()]);
+        let _ = call_both(wat, "deep", &[i.into()]);
+    }
+    Ok(())
+}
+
+#[test]
+fn calls() -> Result<()> {
+    let wat = r#"
+        (module
+            (import "" "host" (func $host (param i32 i64 f32 f64) (result f64)))
+            (type $t (func (param i64) (result i64)))
+            (table 4 funcref)
+            (elem (i32.const 0) $double $triple $many_args)
+            (func $double (type $t) (i64.mul (local.get 0) (i64.const 2)))
+            (func $triple (type $t) (i64.mul (local.get 0) (i64.const 3)))
+            (func $many_args
+                (param i32 i64 i32 i64 i32 i64 i32 i64)
+                (param f32 f64 f32 f64 f32 f64 f32 f64 f32 f64)
+                (result f64)
+                (f64.add (f64.convert_i64_s (i64.add (local.get 1) (local.get 7)))
+                    (f64.add (f64.convert_i32_s (i32.sub (local.get 0) (local.get 6)))
+                        (f64.add (local.get 17) (f64.promote_f32 (local.get 16))))))
+            (func (export "indirect") (param i32 i64) (result i64)
+                (call_indirect (type $t) (local.get 1) (local.get 0)))
+            (func (export "many") (result f64)
+                (call $many_args
+                    (i32.const 1) (i64.const 2) (i32.const 3) (i64.const 4)
+                    (i32.const 5) (i64.const 6) (i32.const 7) (i64.const 8)
+                    (f32.const 1.5) (f64.const 2.5) (f32.const 3.5) (f64.const 4.5)
+                    (f32.const 5.5) (f64.const 6.5) (f32.const 7.5) (f64.const 8.5)
+                    (f32.const 9.5) (f64.const 10.5)))
+            (func (export "host") (param i32) (result f64)
+                (call $host (local.get 0) (i64.const -5) (f32.const 0.25) (f64.const 100)))
+        )
+    "#;
+    assert_eq!(
+        bits(&call_both(wat, "indirect", &[1.into(), 5i64.into()])?[0]),
+        15
+    );
+    for i in 0..5 {
+        let _ = call_both(wat, "indirect", &[i.into(), 5i64.into()]);
+    }
+    assert_eq!(
+        f64::from_bits(bits(&call_both(wat, "many", &[])?[0])),
+        10.0 - 6.0 + 10.5 + 9.5
+    );
+    assert_eq!(
+        f64::from_bits(bits(&call_both(wat, "host", &[3.into()])?[0])),
+        98.25
+    );
+    Ok(())
+}
+
+#[test]
+fn memories_and_globals() -> Result<()> {
+    let wat = r#"
+        (module
+            (memory 1 3)
+            (global $g (mut i64) (i64.const 0))
+            (func (export "store") (param i32 i64)
+                (i64.store offset=3 (local.get 0) (local.get 1))
+                (global.set $g (i64.add (global.get $g) (local.get 1))))
+            (func (export "load") (param i32) (result i64)
+                (i64.add (i64.load8_s (local.get 0))
+                    (i64.add (i64.load16_u offset=1 (local.get 0))
+                        (i64.add (i64.load32_s offset=2 (local.get 0))
+                            (i64.extend_i32_u (i32.load offset=65535 (local.get 0)))))))
+            (func (export "grow") (param i32) (result i32)
+                (i32.add (memory.grow (local.get 0)) (i32.shl (memory.size) (i32.const 8))))
+            (func (export "global") (result i64) (global.get $g))
+        )
+    "#;
+    let run = |strategy| -> Result<Vec<u64>> {
+        let engine = engine(strategy);
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let store_fn = instance.get_typed_func::<(i32, i64), (), _>(&mut store, "store")?;
+        let load = instance.get_typed_func::<i32, i64, _>(&mut store, "load")?;
+        let grow = instance.get_typed_func::<i32, i32, _>(&mut store, "grow")?;
+        let global = instance.get_typed_func::<(), i64, _>(&mut store, "global")?;
+        let mut results = Vec::new();
+        let mut record = |result: Result<u64, Trap>| {
+            results.push(result.unwrap_or_else(|t| t.trap_code().unwrap() as u64 + (1 << 60)))
+        };
+        for &addr in &[0, 1, 100, 65528, 65533, 65534, -1] {
+            record(
+                store_fn
+                    .call(&mut store, (addr, -0x1234_5678_9abc))
+                    .map(|()| 0),
+            );
+            record(load.call(&mut store, addr).map(|v| v as u64));
+        }
+        record(grow.call(&mut store, 1).map(|v| v as u64));
+        record(load.call(&mut store, 65534).map(|v| v as u64));
+        record(grow.call(&mut store, 5).map(|v| v as u64));
+        record(global.call(&mut store, ()).map(|v| v as u64));
+        Ok(results)
+    };
+    assert_eq!(run(Strategy::Baseline)?, run(Strategy::Cranelift)?);
+    Ok(())
+}
+
+#[test]
+fn traps_have_the_same_backtrace() -> Result<()> {
+    let wat = r#"
+        (module $m
+            (func $start (export "start") (param i32)
+                local.get 0
+                call $middle)
+            (func $middle (param i32)
+                local.get 0
+                i32.const 0
+                i32.div_u
+                drop)
+        )
+    "#;
+    let backtrace = |strategy| -> Result<Vec<_>> {
+        let engine = engine(strategy);
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let start = instance.get_typed_func::<i32, (), _>(&mut store, "start")?;
+        let trap = start.call(&mut store, 1).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::IntegerDivisionByZero));
+        Ok(trap
+            .trace()
+            .iter()
+            .map(|f| (f.func_index(), f.module_offset()))
+            .collect())
+    };
+    let baseline = backtrace(Strategy::Baseline)?;
+    assert_eq!(baseline.len(), 2, "{:?}", baseline);
+    assert_eq!(baseline, backtrace(Strategy::Cranelift)?);
+    Ok(())
+}
+
+#[test]
+fn stack_overflow() -> Result<()> {
+    let engine = engine(Strategy::Baseline);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $recurse (export "recurse") (param i64) (result i64)
+                    (i64.add (call $recurse (local.get 0)) (i64.const 1)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let recurse = instance.get_typed_func::<i64, i64, _>(&mut store, "recurse")?;
+    let trap = recurse.call(&mut store, 0).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow));
+    Ok(())
+}
+
+#[test]
+fn unsupported_functions_use_cranelift() -> Result<()> {
+    let wat = r#"
+        (module
+            (func $pair (result i32 i32) (i32.const 1) (i32.const 2))
+            (func (export "simd") (param i32) (result i32)
+                (i32x4.extract_lane 1 (i32x4.splat (local.get 0))))
+            (func (export "multi") (result i32)
+                (call $pair)
+                (i32.sub))
+            (func (export "externref") (param externref) (result externref)
+                (local.get 0))
+        )
+    "#;
+    assert_eq!(bits(&call_both(wat, "simd", &[9.into()])?[0]), 9);
+    assert_eq!(bits(&call_both(wat, "multi", &[])?[0]), u32::MAX as u64);
+
+    let mut config = Config::new();
+    config.strategy(Strategy::Baseline)?.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, "(module (func (export \"f\") (loop (br 0))))")?;
+    let mut store = Store::new(&engine, ());
+    store.add_fuel(1000)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let f = instance.get_typed_func::<(), (), _>(&mut store, "f")?;
+    let trap = f.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("all fuel consumed"), "{}", trap);
+    Ok(())
+}
+
+#[test]
+fn cache_keys_include_strategy() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "[cache]\nenabled = true\ndirectory = {:?}\n",
+            dir.path().join("cache")
+        ),
+    )?;
+    let engine = |strategy| -> Result<Engine> {
+        let mut config = Config::new();
+        config.cache_config_load(&config_path)?.strategy(strategy)?;
+        Engine::new(&config)
+    };
+
+    let cranelift = engine(Strategy::Cranelift)?;
+    Module::new(&cranelift, "(module (func))")?;
+    assert_eq!(cranelift.metrics().cache_misses(), 1);
+
+    // Artifacts compiled by Cranelift aren't reused by the baseline compiler,
+    // but each strategy reuses its own.
+    let baseline = engine(Strategy::Baseline)?;
+    Module::new(&baseline, "(module (func))")?;
+    assert_eq!(baseline.metrics().cache_misses(), 1);
+    assert_eq!(baseline.metrics().cache_hits(), 0);
+    Module::new(&baseline, "(module (func))")?;
+    assert_eq!(baseline.metrics().cache_hits(), 1);
+    Module::new(&cranelift, "(module (func))")?;
+    assert_eq!(cranelift.metrics().cache_hits(), 1);
+    Ok(())
+}
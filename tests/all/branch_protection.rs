@@ -0,0 +1,91 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (type $t (func (param i32) (result i32)))
+        (table 2 funcref)
+        (elem (i32.const 0) $classify $double)
+        (func $classify (type $t)
+            (block $c
+                (block $b
+                    (block $a
+                        (br_table $a $b $c (local.get 0)))
+                    (return (i32.const 10)))
+                (return (i32.const 20)))
+            (i32.const 30))
+        (func $double (type $t)
+            (i32.mul (local.get 0) (i32.const 2)))
+        (func (export "run") (param i32 i32) (result i32)
+            (call_indirect (type $t) (local.get 1) (local.get 0)))
+    )
+"#;
+
+fn run(config: &mut Config, flags: &[&str]) -> Result<Vec<u8>> {
+    for flag in flags {
+        unsafe {
+            config.cranelift_flag_enable(flag)?;
+        }
+    }
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, (0, 0))?, 10);
+    assert_eq!(run.call(&mut store, (0, 1))?, 20);
+    assert_eq!(run.call(&mut store, (0, 7))?, 30);
+    assert_eq!(run.call(&mut store, (1, 21))?, 42);
+    module.serialize()
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn ibt_landing_pads() -> Result<()> {
+    let serialized = run(&mut Config::new(), &["use_ibt"])?;
+    let endbr64 = [0xf3, 0x0f, 0x1e, 0xfa];
+    assert!(serialized.windows(4).any(|w| w == endbr64));
+    assert!(serialized.windows(18).any(|w| w == b".note.gnu.property"));
+
+    // Without the setting neither the landing pads nor the note are emitted.
+    let engine = Engine::default();
+    let serialized = Module::new(&engine, WAT)?.serialize()?;
+    assert!(!serialized.windows(18).any(|w| w == b".note.gnu.property"));
+    Ok(())
+}
+
+#[test]
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+fn ibt_landing_pads_with_baseline_compiler() -> Result<()> {
+    run(Config::new().strategy(Strategy::Baseline)?, &["use_ibt"])?;
+    Ok(())
+}
+
+#[test]
+#[cfg(target_arch = "aarch64")]
+fn bti_landing_pads() -> Result<()> {
+    let serialized = run(&mut Config::new(), &["use_bti"])?;
+    let bti_c = 0xd503245fu32.to_le_bytes();
+    let bti_j = 0xd503249fu32.to_le_bytes();
+    assert!(serialized.windows(4).any(|w| w == bti_c));
+    assert!(serialized.windows(4).any(|w| w == bti_j));
+    assert!(serialized.windows(18).any(|w| w == b".note.gnu.property"));
+    Ok(())
+}
+
+#[test]
+#[cfg(target_arch = "aarch64")]
+fn signed_return_addresses() -> Result<()> {
+    for flags in [
+        &["sign_return_address"][..],
+        &["sign_return_address", "sign_return_address_with_bkey"],
+        &["sign_return_address", "use_bti"],
+    ] {
+        let serialized = run(&mut Config::new(), flags)?;
+
+        // The unwind information records when the B key is used.
+        let bkey = flags.contains(&"sign_return_address_with_bkey");
+        assert_eq!(serialized.windows(4).any(|w| w == b"zRB\0"), bkey);
+    }
+    Ok(())
+}
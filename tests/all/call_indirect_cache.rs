@@ -0,0 +1,221 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn call(store: &mut Store<()>, instance: Instance) -> Result<TypedFunc<(i32, i32), i32>> {
+    instance.get_typed_func::<(i32, i32), i32, _>(store, "call")
+}
+
+#[test]
+fn calls_cached_callee() -> Result<()> {
+    let mut config = Config::new();
+    config.cache_call_indirects(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table $table 4 funcref)
+                (elem (table $table) (i32.const 0) func $double $square $nullary)
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func $square (param i32) (result i32)
+                    (i32.mul (local.get 0) (local.get 0)))
+                (func $nullary)
+                (func (export "call") (param i32 i32) (result i32)
+                    (call_indirect $table (type $unary) (local.get 1) (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call = call(&mut store, instance)?;
+
+    for _ in 0..3 {
+        assert_eq!(call.call(&mut store, (0, 5))?, 10);
+    }
+    assert_eq!(call.call(&mut store, (1, 5))?, 25);
+    assert_eq!(call.call(&mut store, (0, 6))?, 12);
+
+    // Calls which fail the checks are never cached, and fail every time.
+    for _ in 0..2 {
+        let trap = call.call(&mut store, (2, 5)).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::BadSignature));
+        let trap = call.call(&mut store, (3, 5)).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::IndirectCallToNull));
+        let trap = call.call(&mut store, (4, 5)).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::TableOutOfBounds));
+        let trap = call.call(&mut store, (-1, 5)).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::TableOutOfBounds));
+    }
+    assert_eq!(call.call(&mut store, (1, 7))?, 49);
+
+    // Each instance has caches of its own.
+    let other = Instance::new(&mut store, &module, &[])?;
+    let other = self::call(&mut store, other)?;
+    assert_eq!(other.call(&mut store, (0, 7))?, 14);
+    assert_eq!(call.call(&mut store, (1, 8))?, 64);
+    Ok(())
+}
+
+#[test]
+fn first_call_checks_its_callee() -> Result<()> {
+    // Before its first call a cache holds the index `u32::MAX`, which must
+    // still be bounds checked when it's the callee.
+    let mut config = Config::new();
+    config.cache_call_indirects(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table $table 1 funcref)
+                (elem (table $table) (i32.const 0) func $double)
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func (export "call") (param i32 i32) (result i32)
+                    (call_indirect $table (type $unary) (local.get 1) (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call = call(&mut store, instance)?;
+    let trap = call.call(&mut store, (-1, 5)).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::TableOutOfBounds));
+    assert_eq!(call.call(&mut store, (0, 5))?, 10);
+    Ok(())
+}
+
+#[test]
+fn modified_tables_are_not_cached() -> Result<()> {
+    let mut config = Config::new();
+    config.cache_call_indirects(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table $table 2 funcref)
+                (elem (table $table) (i32.const 0) func $double)
+                (elem declare func $square)
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func $square (param i32) (result i32)
+                    (i32.mul (local.get 0) (local.get 0)))
+                (func (export "swap")
+                    (table.set $table (i32.const 0) (ref.func $square)))
+                (func (export "call") (param i32 i32) (result i32)
+                    (call_indirect $table (type $unary) (local.get 1) (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call = call(&mut store, instance)?;
+    let swap = instance.get_typed_func::<(), (), _>(&mut store, "swap")?;
+    assert_eq!(call.call(&mut store, (0, 5))?, 10);
+    swap.call(&mut store, ())?;
+    assert_eq!(call.call(&mut store, (0, 5))?, 25);
+
+    // Exported tables can be modified from outside of the module.
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table $table (export "table") 2 funcref)
+                (elem (table $table) (i32.const 0) func $double $square)
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func $square (param i32) (result i32)
+                    (i32.mul (local.get 0) (local.get 0)))
+                (func (export "call") (param i32 i32) (result i32)
+                    (call_indirect $table (type $unary) (local.get 1) (local.get 0)))
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call = self::call(&mut store, instance)?;
+    let table = instance.get_table(&mut store, "table").unwrap();
+    assert_eq!(call.call(&mut store, (0, 5))?, 10);
+    let square = table.get(&mut store, 1).unwrap();
+    table.set(&mut store, 0, square)?;
+    assert_eq!(call.call(&mut store, (0, 5))?, 25);
+    Ok(())
+}
+
+#[test]
+fn restored_snapshots_empty_caches() -> Result<()> {
+    // The table's contents depend on the imported global, so the snapshot of
+    // one instance can move another's functions around.
+    let mut config = Config::new();
+    config.cache_call_indirects(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (import "" "base" (global $base i32))
+                (table $table 3 funcref)
+                (elem (table $table) (global.get $base) func $double $square)
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func $square (param i32) (result i32)
+                    (i32.mul (local.get 0) (local.get 0)))
+                (func (export "call") (param i32 i32) (result i32)
+                    (call_indirect $table (type $unary) (local.get 1) (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let ty = GlobalType::new(ValType::I32, Mutability::Const);
+    let zero = Global::new(&mut store, ty.clone(), Val::I32(0))?;
+    let one = Global::new(&mut store, ty, Val::I32(1))?;
+    let original = Instance::new(&mut store, &module, &[zero.into()])?;
+    let snapshot = original.snapshot(&mut store)?;
+
+    let instance = Instance::new(&mut store, &module, &[one.into()])?;
+    let call = call(&mut store, instance)?;
+    assert_eq!(call.call(&mut store, (1, 5))?, 10);
+    instance.restore(&mut store, &snapshot)?;
+    assert_eq!(call.call(&mut store, (1, 5))?, 25);
+    assert_eq!(call.call(&mut store, (0, 5))?, 10);
+    Ok(())
+}
+
+#[test]
+fn serialized_module_keeps_caches() -> Result<()> {
+    let mut config = Config::new();
+    config.cache_call_indirects(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $unary (func (param i32) (result i32)))
+                (table $table 1 funcref)
+                (elem (table $table) (i32.const 0) func $double)
+                (func $double (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func (export "call") (param i32 i32) (result i32)
+                    (call_indirect $table (type $unary) (local.get 1) (local.get 0)))
+            )
+        "#,
+    )?;
+    let bytes = module.serialize()?;
+
+    let engine = Engine::default();
+    let module = unsafe { Module::deserialize(&engine, bytes)? };
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call = call(&mut store, instance)?;
+    assert_eq!(call.call(&mut store, (0, 5))?, 10);
+    assert_eq!(call.call(&mut store, (0, 6))?, 12);
+    let trap = call.call(&mut store, (1, 5)).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::TableOutOfBounds));
+    Ok(())
+}
@@ -4,8 +4,8 @@ use std::path::Path;
 use std::process::{Command, Output};
 use tempfile::{NamedTempFile, TempDir};
 
-// Run the wasmtime CLI with the provided args and return the `Output`.
-fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
+// Get the command for running the wasmtime CLI.
+fn get_wasmtime_command() -> Result<Command> {
     let runner = std::env::vars()
         .filter(|(k, _v)| k.starts_with("CARGO_TARGET") && k.ends_with("RUNNER"))
         .next();
@@ -17,7 +17,7 @@ fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
     // If we're running tests with a "runner" then we might be doing something
     // like cross-emulation, so spin up the emulator rather than the tests
     // itself, which may not be natively executable.
-    let mut cmd = if let Some((_, runner)) = runner {
+    let cmd = if let Some((_, runner)) = runner {
         let mut parts = runner.split_whitespace();
         let mut cmd = Command::new(parts.next().unwrap());
         for arg in parts {
@@ -28,7 +28,15 @@ fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
     } else {
         Command::new(&me)
     };
-    cmd.args(args).output().map_err(Into::into)
+    Ok(cmd)
+}
+
+// Run the wasmtime CLI with the provided args and return the `Output`.
+fn run_wasmtime_for_output(args: &[&str]) -> Result<Output> {
+    get_wasmtime_command()?
+        .args(args)
+        .output()
+        .map_err(Into::into)
 }
 
 // Run the wasmtime CLI with the provided args and, if it succeeds, return
@@ -120,6 +128,127 @@ fn run_wasmtime_simple_wat() -> Result<()> {
     Ok(())
 }
 
+#[test]
+#[cfg(feature = "explore")]
+fn explore() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/simple.wat")?;
+    let report = run_wasmtime(&["explore", wasm.path().to_str().unwrap()])?;
+    assert!(
+        report.contains(";; function 0: <unknown>!<wasm function 0>\n;; wasm\n"),
+        "{}",
+        report
+    );
+    assert!(report.contains("LocalGet { local_index: 0 }"), "{}", report);
+    assert!(
+        report.contains("function u0:0(i64 vmctx, i64, i32) -> i32"),
+        "{}",
+        report
+    );
+    assert!(report.contains("; relocations:"), "{}", report);
+    assert!(
+        report.contains(";; function 2: <unknown>!<wasm function 2>\n"),
+        "{}",
+        report
+    );
+
+    let dir = tempfile::tempdir()?;
+    let html = dir.path().join("report.html");
+    run_wasmtime(&[
+        "explore",
+        "--html",
+        html.to_str().unwrap(),
+        wasm.path().to_str().unwrap(),
+    ])?;
+    let report = std::fs::read_to_string(&html)?;
+    assert!(
+        report.contains("<h2>function 1: &lt;unknown&gt;!&lt;wasm function 1&gt;</h2>"),
+        "{}",
+        report
+    );
+    assert!(report.contains("-&gt; f32"), "{}", report);
+    Ok(())
+}
+
+// Invoking functions with typed arguments
+#[test]
+fn run_wasmtime_typed_invoke() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/typed-invoke.wat")?;
+    let path = wasm.path().to_str().unwrap();
+    assert_eq!(
+        run_wasmtime(&[
+            "run",
+            path,
+            "--invoke",
+            "mix",
+            "--disable-cache",
+            "--",
+            "-0x10",
+            "i64:40",
+            "1.5",
+            "inf",
+        ])?,
+        "24\ninf\n"
+    );
+    assert_eq!(
+        run_wasmtime(&[
+            "run",
+            path,
+            "--invoke",
+            "mix",
+            "--typed-results",
+            "--disable-cache",
+            "4294967295",
+            "0",
+            "f32:0.5",
+            "0.25",
+        ])?,
+        "i64:-1\nf64:0.75\n"
+    );
+    assert_eq!(
+        run_wasmtime(&[
+            "run",
+            path,
+            "--invoke",
+            "sum",
+            "--disable-cache",
+            "string:ab",
+        ])?,
+        "195\n"
+    );
+
+    // Arguments must fit the function's signature.
+    let output = get_wasmtime_command()?
+        .args(&[
+            path,
+            "--invoke",
+            "mix",
+            "--disable-cache",
+            "1",
+            "f32:2",
+            "3",
+            "4",
+        ])
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("failed to parse argument 1 for `mix` as `i64`"),
+        "bad stderr: {}",
+        stderr
+    );
+    let output = get_wasmtime_command()?
+        .args(&[path, "--invoke", "mix", "--disable-cache", "0x100000000"])
+        .output()?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("out of range for a 32-bit integer"),
+        "bad stderr: {}",
+        stderr
+    );
+    Ok(())
+}
+
 // Running a wat that traps.
 #[test]
 fn run_wasmtime_unreachable_wat() -> Result<()> {
@@ -171,7 +300,7 @@ fn timeout_in_start() -> Result<()> {
         "1ms",
         "--disable-cache",
     ])?;
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(124));
     assert_eq!(output.stdout, b"");
     let stderr = String::from_utf8_lossy(&output.stderr);
     assert!(
@@ -192,7 +321,7 @@ fn timeout_in_invoke() -> Result<()> {
         "1ms",
         "--disable-cache",
     ])?;
-    assert!(!output.status.success());
+    assert_eq!(output.status.code(), Some(124));
     assert_eq!(output.stdout, b"");
     let stderr = String::from_utf8_lossy(&output.stderr);
     assert!(
@@ -203,6 +332,26 @@ fn timeout_in_invoke() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn out_of_fuel() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/iloop-invoke.wat")?;
+    let output = run_wasmtime_for_output(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--fuel",
+        "1000",
+        "--disable-cache",
+    ])?;
+    assert_eq!(output.status.code(), Some(125));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("all fuel consumed by WebAssembly"),
+        "bad stderr: {}",
+        stderr
+    );
+    Ok(())
+}
+
 // Exit with a valid non-zero exit code, snapshot0 edition.
 #[test]
 fn exit2_wasi_snapshot0() -> Result<()> {
@@ -286,6 +435,26 @@ fn minimal_command() -> Result<()> {
     Ok(())
 }
 
+// Restricting wasi-crypto algorithms needs wasi-crypto to be enabled.
+#[test]
+fn wasi_crypto_algorithm_without_wasi_crypto() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/minimal-command.wat")?;
+    let output = run_wasmtime_for_output(&[
+        wasm.path().to_str().unwrap(),
+        "--disable-cache",
+        "--wasi-crypto-algorithm",
+        "Ed25519",
+    ])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("--wasi-crypto-algorithm requires"),
+        "bad stderr: {}",
+        stderr
+    );
+    Ok(())
+}
+
 // Run a minimal reactor program.
 #[test]
 fn minimal_reactor() -> Result<()> {
@@ -397,3 +566,311 @@ fn run_cwasm() -> Result<()> {
     assert_eq!(stdout, "");
     Ok(())
 }
+
+#[test]
+fn coredump_on_trap() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/unreachable.wat")?;
+    let td = TempDir::new()?;
+    let coredump = td.path().join("unreachable.coredump");
+    let output = run_wasmtime_for_output(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--coredump-on-trap",
+        coredump.to_str().unwrap(),
+        "--disable-cache",
+    ])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Core dump written to"),
+        "bad stderr: {}",
+        stderr
+    );
+    wasmparser::validate(&std::fs::read(&coredump)?)?;
+    Ok(())
+}
+
+// Run a module which grows its memory with a memory layout that reserves as
+// little address space as possible.
+#[test]
+fn minimal_memory_reservation() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/grow-memory.wat")?;
+    run_wasmtime(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--static-memory-maximum-size",
+        "0",
+        "--dynamic-memory-guard-size",
+        "0",
+        "--dynamic-memory-reserved-for-growth",
+        "0",
+        "--disable-guard-before-linear-memory",
+        "--disable-cache",
+    ])?;
+    Ok(())
+}
+
+#[test]
+fn deterministic_wasi() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/print-random-and-time.wat")?;
+    let run = || {
+        run_wasmtime_for_output(&[
+            "run",
+            wasm.path().to_str().unwrap(),
+            "--deterministic",
+            "--disable-cache",
+        ])
+    };
+    let first = run()?;
+    assert!(first.status.success());
+    assert_eq!(first.stdout.len(), 16);
+    // The realtime clock starts at the unix epoch.
+    assert_eq!(&first.stdout[8..], &[0; 8]);
+    assert_eq!(run()?.stdout, first.stdout);
+    Ok(())
+}
+
+#[test]
+fn tcp_connect() -> Result<()> {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let wasm = build_wasm("tests/all/cli_tests/tcp_echo.wat")?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    let address = listener.local_addr()?.to_string();
+    let server = std::thread::spawn(move || -> Result<Vec<u8>> {
+        let (mut stream, _) = listener.accept()?;
+        let mut request = [0; 5];
+        stream.read_exact(&mut request)?;
+        stream.write_all(&request.to_ascii_uppercase())?;
+        // The guest shuts the connection down once it has the reply.
+        let mut rest = Vec::new();
+        stream.read_to_end(&mut rest)?;
+        assert!(rest.is_empty());
+        Ok(request.to_vec())
+    });
+    let stdout = run_wasmtime(&[
+        "run",
+        wasm.path().to_str().unwrap(),
+        "--tcpconnect",
+        &address,
+        "--disable-cache",
+    ])?;
+    assert_eq!(server.join().unwrap()?, b"hello");
+    assert_eq!(stdout, "HELLO");
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn listenfd() -> Result<()> {
+    use std::io::Read;
+    use std::net::{TcpListener, TcpStream};
+    use std::os::unix::prelude::*;
+
+    let wasm = build_wasm("tests/all/cli_tests/accept.wat")?;
+    let listener = TcpListener::bind("127.0.0.1:0")?;
+    // The connection waits in the backlog until the guest accepts it.
+    let mut stream = TcpStream::connect(listener.local_addr()?)?;
+
+    // Pass the listener as descriptor 3, like systemd would.
+    let listener_fd = listener.as_raw_fd();
+    let mut cmd = get_wasmtime_command()?;
+    cmd.args(&[
+        wasm.path().to_str().unwrap(),
+        "--listenfd",
+        "--tcplisten",
+        "127.0.0.1:0",
+        "--disable-cache",
+    ])
+    .env("LISTEN_FDS", "1")
+    .env_remove("LISTEN_PID");
+    unsafe {
+        cmd.pre_exec(move || {
+            if libc::dup2(listener_fd, 3) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let output = cmd.output()?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let mut received = Vec::new();
+    stream.read_to_end(&mut received)?;
+    assert_eq!(received, b"hello");
+    Ok(())
+}
+
+#[test]
+fn read_only_dir() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/mkdir.wat")?;
+    let dir = TempDir::new()?;
+    let run = |perms: &str| {
+        let arg = format!("{}::/::{}", dir.path().display(), perms);
+        run_wasmtime_for_output(&[
+            wasm.path().to_str().unwrap(),
+            "--dir",
+            &arg,
+            "--disable-cache",
+        ])
+    };
+
+    // `ENOTCAPABLE` when the directory is read-only.
+    assert_eq!(run("ro")?.status.code(), Some(76));
+    assert!(!dir.path().join("sub").exists());
+    assert_eq!(run("nocreate,nounlink")?.status.code(), Some(76));
+    assert!(!dir.path().join("sub").exists());
+
+    assert_eq!(run("rw")?.status.code(), Some(0));
+    assert!(dir.path().join("sub").is_dir());
+
+    let output = run("rx")?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown directory permission"));
+    Ok(())
+}
+
+#[test]
+fn overlay_and_memory_dirs() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/mkdir.wat")?;
+    let dir = TempDir::new()?;
+
+    // The guest can create `sub` in the overlay, but the host directory
+    // doesn't change.
+    let arg = format!("{}::/::overlay", dir.path().display());
+    let output = run_wasmtime_for_output(&[
+        wasm.path().to_str().unwrap(),
+        "--dir",
+        &arg,
+        "--disable-cache",
+    ])?;
+    assert_eq!(output.status.code(), Some(0));
+    assert!(!dir.path().join("sub").exists());
+
+    let output = run_wasmtime_for_output(&[
+        wasm.path().to_str().unwrap(),
+        "--memdir",
+        "/tmp",
+        "--disable-cache",
+    ])?;
+    assert_eq!(output.status.code(), Some(0));
+    Ok(())
+}
+
+// Run `wasmtime serve` with the provided args, with `stdin` as its stdin.
+fn serve(args: &[&str], stdin: &str) -> Result<Output> {
+    use std::process::Stdio;
+
+    let mut child = get_wasmtime_command()?
+        .arg("serve")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    child.stdin.take().unwrap().write_all(stdin.as_bytes())?;
+    Ok(child.wait_with_output()?)
+}
+
+#[test]
+fn serve_lines() -> Result<()> {
+    let wasm = build_wasm("tests/all/cli_tests/serve_echo.wat")?;
+    let wasm = wasm.path().to_str().unwrap();
+    let output = serve(&["--disable-cache", wasm], "hello\n\nworld\r\n")?;
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert_eq!(String::from_utf8(output.stdout)?, "hello\n\nworld\n");
+
+    // Requests handled at the same time may complete in any order.
+    let input = (0..20).map(|i| format!("{}\n", i)).collect::<String>();
+    let output = serve(&["--concurrency", "4", "--disable-cache", wasm], &input)?;
+    assert!(output.status.success());
+    let mut lines = String::from_utf8(output.stdout)?
+        .lines()
+        .map(|line| line.parse::<u32>())
+        .collect::<Result<Vec<_>, _>>()?;
+    lines.sort();
+    assert_eq!(lines, (0..20).collect::<Vec<_>>());
+
+    let output = serve(&["--concurrency", "0", wasm], "")?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn serve_failed_requests() -> Result<()> {
+    // Failed requests are reported, and the next ones are still handled.
+    let wasm = build_wasm("tests/all/cli_tests/unreachable.wat")?;
+    let output = serve(
+        &["--disable-cache", wasm.path().to_str().unwrap()],
+        "a\nb\n",
+    )?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("request 1 failed"), "{}", stderr);
+    assert!(stderr.contains("request 2 failed"), "{}", stderr);
+    assert!(stderr.contains("unreachable"), "{}", stderr);
+
+    let wasm = build_wasm("tests/all/cli_tests/iloop-start.wat")?;
+    let output = serve(
+        &[
+            "--request-timeout",
+            "100ms",
+            "--disable-cache",
+            wasm.path().to_str().unwrap(),
+        ],
+        "a\nb\n",
+    )?;
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr)?;
+    assert!(stderr.contains("request 2 failed: timed out"), "{}", stderr);
+    Ok(())
+}
+
+#[test]
+fn serve_connections() -> Result<()> {
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::{Shutdown, TcpStream};
+    use std::process::Stdio;
+
+    let wasm = build_wasm("tests/all/cli_tests/serve_echo.wat")?;
+    let mut child = get_wasmtime_command()?
+        .args([
+            "serve",
+            "--listen",
+            "127.0.0.1:0",
+            "--disable-cache",
+            wasm.path().to_str().unwrap(),
+        ])
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut line = String::new();
+    BufReader::new(child.stderr.take().unwrap()).read_line(&mut line)?;
+    let address = line
+        .trim()
+        .strip_prefix("Listening on ")
+        .unwrap()
+        .to_string();
+
+    let request = |body: &str| -> Result<String> {
+        let mut stream = TcpStream::connect(&address)?;
+        stream.write_all(body.as_bytes())?;
+        stream.shutdown(Shutdown::Write)?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response)?;
+        Ok(response)
+    };
+    let result = request("hello").and_then(|hello| Ok((hello, request("two\nlines")?)));
+    child.kill()?;
+    child.wait()?;
+    assert_eq!(result?, ("hello\n".to_string(), "two\nlines\n".to_string()));
+    Ok(())
+}
@@ -0,0 +1,40 @@
+(module
+  (import "wasi_snapshot_preview1" "proc_exit"
+    (func $__wasi_proc_exit (param i32)))
+  (import "wasi_snapshot_preview1" "fd_fdstat_get"
+    (func $__wasi_fd_fdstat_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_accept"
+    (func $__wasi_sock_accept (param i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_send"
+    (func $__wasi_sock_send (param i32 i32 i32 i32 i32) (result i32)))
+  (func $_start
+    (block
+      ;; Accept a connection on the listener passed in as fd 3.
+      (br_if 0
+        (call $__wasi_sock_accept
+          (i32.const 3)
+          (i32.const 0)
+          (i32.const 64)))
+
+      ;; Send "hello" to it.
+      (i32.store (i32.const 32) (i32.const 0))
+      (i32.store (i32.const 36) (i32.const 5))
+      (br_if 0
+        (call $__wasi_sock_send
+          (i32.load (i32.const 64))
+          (i32.const 32)
+          (i32.const 1)
+          (i32.const 0)
+          (i32.const 68)))
+
+      ;; The `--tcplisten` socket comes after it, as fd 4.
+      (br_if 0 (call $__wasi_fd_fdstat_get (i32.const 4) (i32.const 128)))
+      (br 1)
+    )
+    (call $__wasi_proc_exit (i32.const 1))
+  )
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+  (data (i32.const 0) "hello")
+)
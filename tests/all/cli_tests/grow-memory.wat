@@ -0,0 +1,9 @@
+(module
+  (memory 1)
+  (func (export "_start")
+    ;; Grow the memory and then store to its last byte, which fails only if
+    ;; the memory wasn't accessible after growing.
+    (if (i32.ne (memory.grow (i32.const 1)) (i32.const 1))
+      (then unreachable))
+    (i32.store8 (i32.const 0x1ffff) (i32.const 1)))
+)
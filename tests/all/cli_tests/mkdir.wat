@@ -0,0 +1,18 @@
+(module
+  (import "wasi_snapshot_preview1" "proc_exit"
+    (func $__wasi_proc_exit (param i32)))
+  (import "wasi_snapshot_preview1" "path_create_directory"
+    (func $__wasi_path_create_directory (param i32 i32 i32) (result i32)))
+  (func $_start
+    ;; Exit with the error from creating `sub` in the first preopen.
+    (call $__wasi_proc_exit
+      (call $__wasi_path_create_directory
+        (i32.const 3)
+        (i32.const 0)
+        (i32.const 3)))
+  )
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+  (data (i32.const 0) "sub")
+)
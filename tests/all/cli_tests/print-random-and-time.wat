@@ -0,0 +1,23 @@
+(module
+  (import "wasi_snapshot_preview1" "random_get"
+    (func $__wasi_random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "clock_time_get"
+    (func $__wasi_clock_time_get (param i32 i64 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (func $_start
+    ;; 8 random bytes at 16, followed by the realtime clock at 24.
+    (drop (call $__wasi_random_get (i32.const 16) (i32.const 8)))
+    (drop (call $__wasi_clock_time_get (i32.const 0) (i64.const 0) (i32.const 24)))
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 16))
+    (drop (call $__wasi_fd_write
+      (i32.const 1)
+      (i32.const 0)
+      (i32.const 1)
+      (i32.const 8)))
+  )
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+)
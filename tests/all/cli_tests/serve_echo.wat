@@ -0,0 +1,34 @@
+(module
+  (import "wasi_snapshot_preview1" "fd_read"
+    (func $__wasi_fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (func $_start (local $len i32)
+    ;; Read all of stdin, up to 1024 bytes, into the buffer at 16.
+    (loop $read
+      (i32.store (i32.const 0) (i32.add (i32.const 16) (local.get $len)))
+      (i32.store (i32.const 4) (i32.sub (i32.const 1024) (local.get $len)))
+      (if (call $__wasi_fd_read
+            (i32.const 0)
+            (i32.const 0)
+            (i32.const 1)
+            (i32.const 8))
+        (then unreachable))
+      (local.set $len (i32.add (local.get $len) (i32.load (i32.const 8))))
+      (br_if $read (i32.load (i32.const 8))))
+
+    ;; Write it back to stdout, followed by a newline.
+    (i32.store8 (i32.add (i32.const 16) (local.get $len)) (i32.const 10))
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.add (local.get $len) (i32.const 1)))
+    (if (call $__wasi_fd_write
+          (i32.const 1)
+          (i32.const 0)
+          (i32.const 1)
+          (i32.const 8))
+      (then unreachable))
+  )
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+)
@@ -0,0 +1,55 @@
+(module
+  (import "wasi_snapshot_preview1" "proc_exit"
+    (func $__wasi_proc_exit (param i32)))
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $__wasi_fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_send"
+    (func $__wasi_sock_send (param i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_recv"
+    (func $__wasi_sock_recv (param i32 i32 i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "sock_shutdown"
+    (func $__wasi_sock_shutdown (param i32 i32) (result i32)))
+  (func $_start
+    (block
+      ;; Send "hello" to the connection preopened as fd 3.
+      (i32.store (i32.const 32) (i32.const 0))
+      (i32.store (i32.const 36) (i32.const 5))
+      (br_if 0
+        (call $__wasi_sock_send
+          (i32.const 3)
+          (i32.const 32)
+          (i32.const 1)
+          (i32.const 0)
+          (i32.const 64)))
+      (br_if 0 (i32.ne (i32.load (i32.const 64)) (i32.const 5)))
+
+      ;; Wait for all of the five byte reply.
+      (i32.store (i32.const 40) (i32.const 16))
+      (i32.store (i32.const 44) (i32.const 5))
+      (br_if 0
+        (call $__wasi_sock_recv
+          (i32.const 3)
+          (i32.const 40)
+          (i32.const 1)
+          (i32.const 2) ;; RECV_WAITALL
+          (i32.const 64)
+          (i32.const 68)))
+      (br_if 0 (i32.ne (i32.load (i32.const 64)) (i32.const 5)))
+      (br_if 0 (call $__wasi_sock_shutdown (i32.const 3) (i32.const 3)))
+
+      ;; Print the reply.
+      (br_if 0
+        (call $__wasi_fd_write
+          (i32.const 1)
+          (i32.const 40)
+          (i32.const 1)
+          (i32.const 64)))
+      (br 1)
+    )
+    (call $__wasi_proc_exit (i32.const 1))
+  )
+  (memory 1)
+  (export "memory" (memory 0))
+  (export "_start" (func $_start))
+  (data (i32.const 0) "hello")
+)
@@ -0,0 +1,32 @@
+(module
+    (memory (export "memory") 1)
+    (global $next (mut i32) (i32.const 16))
+
+    (func (export "cabi_realloc") (param i32 i32 i32 i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (global.get $next))
+        (global.set $next (i32.add (local.get $ptr) (local.get 3)))
+        (local.get $ptr)
+    )
+
+    (func (export "mix") (param i32 i64 f32 f64) (result i64 f64)
+        (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1))
+        (f64.add (f64.promote_f32 (local.get 2)) (local.get 3))
+    )
+
+    ;; Returns the sum of the bytes of the string it's passed.
+    (func (export "sum") (param $ptr i32) (param $len i32) (result i32)
+        (local $sum i32)
+        (block $done
+            (loop $next
+                (br_if $done (i32.eqz (local.get $len)))
+                (local.set $sum
+                    (i32.add (local.get $sum) (i32.load8_u (local.get $ptr))))
+                (local.set $ptr (i32.add (local.get $ptr) (i32.const 1)))
+                (local.set $len (i32.sub (local.get $len) (i32.const 1)))
+                (br $next)
+            )
+        )
+        (local.get $sum)
+    )
+)
@@ -0,0 +1,13 @@
+//! Helpers shared by the tests of this crate.
+
+use anyhow::Result;
+use wasmtime::*;
+
+/// Instantiates `module`, which has no imports, in a new store and calls its
+/// `run` export, of type `[i32] -> [i32]`, with `arg`.
+pub(crate) fn call_run(engine: &Engine, module: &Module, arg: i32) -> Result<i32> {
+    let mut store = Store::new(engine, ());
+    let instance = Instance::new(&mut store, module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    Ok(run.call(&mut store, arg)?)
+}
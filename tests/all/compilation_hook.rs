@@ -0,0 +1,109 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (func $cold (export "cold") (result i32) i32.const 1)
+        (func $hot (export "hot") (param i32) (result i32)
+            local.get 0
+            i32.const 1
+            i32.add)
+    )
+"#;
+
+struct Reported {
+    index: u32,
+    name: Option<String>,
+    clif: Option<String>,
+    code_len: usize,
+    disassembly: Option<String>,
+}
+
+#[derive(Default)]
+struct Hook {
+    functions: Mutex<Vec<Reported>>,
+}
+
+struct Filtered(Arc<Hook>);
+
+impl CompilationHook for Filtered {
+    fn filter(&self, _index: u32, name: Option<&str>) -> bool {
+        name == Some("hot")
+    }
+
+    fn function_compiled(&self, function: &FunctionListing<'_>) {
+        self.0.functions.lock().unwrap().push(Reported {
+            index: function.index(),
+            name: function.name().map(|s| s.to_string()),
+            clif: function.clif().map(|s| s.to_string()),
+            code_len: function.code().len(),
+            disassembly: function.disassembly().map(|s| s.to_string()),
+        });
+    }
+}
+
+fn hooked(config: &mut Config) -> Arc<Hook> {
+    let hook = Arc::new(Hook::default());
+    config.compilation_hook(Filtered(hook.clone()));
+    hook
+}
+
+#[test]
+fn reports_filtered_functions() -> Result<()> {
+    let mut config = Config::new();
+    let hook = hooked(&mut config);
+    let engine = Engine::new(&config)?;
+    Module::new(&engine, WAT)?;
+
+    let functions = hook.functions.lock().unwrap();
+    assert_eq!(functions.len(), 1);
+    let hot = &functions[0];
+    assert_eq!(hot.index, 1);
+    assert_eq!(hot.name.as_deref(), Some("hot"));
+    assert!(hot.clif.as_ref().unwrap().contains("iadd"));
+    assert!(hot.code_len > 0);
+    assert!(!hot.disassembly.as_ref().unwrap().is_empty());
+    Ok(())
+}
+
+#[test]
+fn reports_lazily_compiled_functions() -> Result<()> {
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let hook = hooked(&mut config);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, WAT)?;
+    assert!(hook.functions.lock().unwrap().is_empty());
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let hot = instance.get_typed_func::<i32, i32, _>(&mut store, "hot")?;
+    assert_eq!(hot.call(&mut store, 1)?, 2);
+    assert_eq!(hot.call(&mut store, 2)?, 3);
+
+    let functions = hook.functions.lock().unwrap();
+    assert_eq!(functions.len(), 1);
+    assert_eq!(functions[0].name.as_deref(), Some("hot"));
+    Ok(())
+}
+
+#[test]
+fn reports_baseline_functions() -> Result<()> {
+    let mut config = Config::new();
+    config.strategy(Strategy::Baseline)?;
+    let hook = hooked(&mut config);
+    let engine = match Engine::new(&config) {
+        Ok(engine) => engine,
+        // The baseline compiler doesn't support every target.
+        Err(_) => return Ok(()),
+    };
+    Module::new(&engine, WAT)?;
+
+    let functions = hook.functions.lock().unwrap();
+    assert_eq!(functions.len(), 1);
+    assert!(functions[0].clif.is_none());
+    assert!(functions[0].disassembly.is_none());
+    assert!(functions[0].code_len > 0);
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (func $empty)
+        (func $locals (param i32) (result i32)
+            (local i64 i64 i64 i64 i64 i64 i64 i64)
+            (local.set 1 (i64.extend_i32_u (local.get 0)))
+            (local.set 2 (i64.mul (local.get 1) (local.get 1)))
+            (local.set 3 (i64.mul (local.get 2) (local.get 1)))
+            (i32.wrap_i64 (i64.add (local.get 2) (local.get 3))))
+        (func (export "run") (param i32) (result i32)
+            (call $empty)
+            (call $locals (local.get 0)))
+    )
+"#;
+
+#[test]
+fn reports_each_function() -> Result<()> {
+    let module = Module::new(&Engine::default(), WAT)?;
+    let report = module.compilation_report().unwrap();
+    let functions = report.functions();
+    assert_eq!(functions.len(), 3);
+    assert_eq!(functions[0].index(), 0);
+    assert_eq!(functions[0].name(), Some("empty"));
+    assert_eq!(functions[1].name(), Some("locals"));
+    assert_eq!(functions[2].name(), None);
+
+    // The body of `$empty` is its local declarations and `end`.
+    assert_eq!(functions[0].wasm_size(), 2);
+    assert!(functions[1].wasm_size() > functions[2].wasm_size());
+    assert!(functions[1].code_size() > functions[0].code_size());
+    assert!(functions.iter().all(|f| f.compile_time().is_some()));
+
+    let sum = |size: fn(&FunctionReport) -> u32| -> u64 {
+        functions.iter().map(|f| u64::from(size(f))).sum()
+    };
+    assert_eq!(report.total_wasm_size(), sum(FunctionReport::wasm_size));
+    assert_eq!(report.total_code_size(), sum(FunctionReport::code_size));
+    assert_eq!(
+        report.total_compile_time(),
+        functions.iter().map(|f| f.compile_time()).sum()
+    );
+    let rendered = report.to_string();
+    assert!(rendered.contains("locals"), "{}", rendered);
+    assert!(rendered.contains("total"), "{}", rendered);
+    Ok(())
+}
+
+#[test]
+fn reports_frame_sizes() -> Result<()> {
+    // The baseline compiler gives every local and operand a slot in the frame.
+    let mut config = Config::new();
+    config.strategy(Strategy::Baseline)?;
+    let module = Module::new(&Engine::new(&config)?, WAT)?;
+    let report = module.compilation_report().unwrap();
+    let functions = report.functions();
+    assert!(functions[1].frame_size() >= 8 * 9);
+    assert!(functions[0].frame_size() < functions[1].frame_size());
+    assert_eq!(report.max_frame_size(), functions[1].frame_size());
+    Ok(())
+}
+
+#[test]
+fn reports_survive_serialization() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+    let report = module.compilation_report().unwrap();
+    let module = unsafe { Module::deserialize(&engine, module.serialize()?)? };
+    let deserialized = module.compilation_report().unwrap();
+    for (a, b) in report.functions().iter().zip(deserialized.functions()) {
+        assert_eq!(a.wasm_size(), b.wasm_size());
+        assert_eq!(a.code_size(), b.code_size());
+        assert_eq!(a.frame_size(), b.frame_size());
+        // Compile times aren't serialized, so that serialized modules don't
+        // depend on them.
+        assert!(b.compile_time().is_none());
+    }
+    assert!(deserialized.total_compile_time().is_none());
+    Ok(())
+}
+
+#[test]
+fn lazily_compiled_modules_have_no_report() -> Result<()> {
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let module = Module::new(&Engine::new(&config)?, WAT)?;
+    assert!(module.compilation_report().is_none());
+    Ok(())
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn coredump_attached_to_trap() -> Result<()> {
+    let mut config = Config::new();
+    config.coredump_on_trap(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $m
+                (memory 1)
+                (global $g (mut i32) (i32.const 0))
+                (func $die
+                    (i32.store (i32.const 8) (i32.const 0xdead))
+                    (global.set $g (i32.const 42))
+                    unreachable)
+                (func (export "run") call $die)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+
+    let coredump = trap.coredump().expect("core dump should be captured");
+    let frames = coredump.frames();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0].instance(), 0);
+    assert_eq!(frames[0].func_index(), 0);
+    assert_eq!(frames[1].func_index(), 1);
+
+    let memories = coredump.memories().collect::<Vec<_>>();
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].len(), 0x10000);
+    assert_eq!(&memories[0][8..12], &0xdead_u32.to_le_bytes());
+
+    let bytes = coredump.serialize("coredump-test");
+    wasmparser::validate(&bytes)?;
+    let mut sections = Vec::new();
+    for payload in wasmparser::Parser::new(0).parse_all(&bytes) {
+        if let wasmparser::Payload::CustomSection { name, .. } = payload? {
+            sections.push(name.to_string());
+        }
+    }
+    assert_eq!(
+        sections,
+        ["core", "coremodules", "coreinstances", "corestack"]
+    );
+    Ok(())
+}
+
+#[test]
+fn coredump_records_locals() -> Result<()> {
+    let mut config = Config::new();
+    config.coredump_on_trap(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $div (param i32 i32) (result i32)
+                    (local f64 externref)
+                    (local.set 2 (f64.const 2.5))
+                    (i32.div_u (local.get 0) (local.get 1)))
+                (func (export "run") (param i64) (result i32)
+                    (call $div (i32.const 7) (i32.const 0)))
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i64, i32, _>(&mut store, "run")?;
+    let trap = run.call(&mut store, -3).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::IntegerDivisionByZero));
+
+    let frames = trap.coredump().unwrap().frames();
+    assert_eq!(frames.len(), 2);
+    let locals = frames[0].locals();
+    assert_eq!(locals.len(), 4);
+    assert_eq!(locals[0].as_ref().unwrap().unwrap_i32(), 7);
+    assert_eq!(locals[1].as_ref().unwrap().unwrap_i32(), 0);
+    assert_eq!(locals[2].as_ref().unwrap().unwrap_f64(), 2.5);
+    assert!(locals[3].is_none());
+    assert_eq!(frames[1].locals()[0].as_ref().unwrap().unwrap_i64(), -3);
+    assert_eq!(
+        Some(frames[0].func_offset() as usize),
+        trap.trace()[0].func_offset()
+    );
+    Ok(())
+}
+
+#[test]
+fn coredump_frames_in_their_own_instance() -> Result<()> {
+    let mut config = Config::new();
+    config.coredump_on_trap(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (func (export "run") (param i32)
+                    (i32.store (i32.const 0) (local.get 0))
+                    (if (local.get 0) (then unreachable)))
+            )
+        "#,
+    )?;
+    let first = Instance::new(&mut store, &module, &[])?;
+    let second = Instance::new(&mut store, &module, &[])?;
+    let run = first.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    run.call(&mut store, 0)?;
+    let run = second.get_typed_func::<i32, (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, 0x55).unwrap_err();
+
+    // The frame belongs to the second instance, whose memory was written.
+    let coredump = trap.coredump().unwrap();
+    assert_eq!(coredump.frames().len(), 1);
+    assert_eq!(coredump.frames()[0].instance(), 1);
+    let memories = coredump.memories().collect::<Vec<_>>();
+    assert_eq!(memories.len(), 2);
+    assert_eq!(memories[0][0], 0);
+    assert_eq!(memories[1][0], 0x55);
+    Ok(())
+}
+
+#[test]
+fn no_coredump_by_default() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"(module (func (export "run") unreachable))"#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(trap.coredump().is_none());
+    Ok(())
+}
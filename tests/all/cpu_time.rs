@@ -0,0 +1,104 @@
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (import "" "burn" (func $burn))
+        (func (export "spin") (param i32)
+            (loop
+                (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                (br_if 0 (local.get 0))))
+        (func (export "call_burn") call $burn)
+    )
+"#;
+
+/// Keeps the current thread busy for `duration`.
+fn burn(duration: Duration) {
+    let start = Instant::now();
+    while start.elapsed() < duration {
+        std::hint::spin_loop();
+    }
+}
+
+fn store(config: &Config) -> Result<(Store<()>, Module, Func)> {
+    let engine = Engine::new(config)?;
+    let module = Module::new(&engine, WAT)?;
+    let mut store = Store::new(&engine, ());
+    let burn = Func::wrap(&mut store, || burn(Duration::from_millis(50)));
+    Ok((store, module, burn))
+}
+
+#[test]
+fn counts_wasm_not_host() -> Result<()> {
+    let (mut store, module, burn) = store(&Config::new())?;
+    let instance = Instance::new(&mut store, &module, &[burn.into()])?;
+    let spin = instance.get_typed_func::<i32, (), _>(&mut store, "spin")?;
+    let call_burn = instance.get_typed_func::<(), (), _>(&mut store, "call_burn")?;
+
+    spin.call(&mut store, 1_000)?;
+    assert_eq!(store.cpu_time_consumed(), None);
+
+    store.track_cpu_time(true);
+    assert_eq!(store.cpu_time_consumed(), Some(Duration::ZERO));
+    spin.call(&mut store, 50_000_000)?;
+    let spun = store.cpu_time_consumed().unwrap();
+    assert!(spun > Duration::ZERO);
+
+    // The time spent in host functions isn't the guest's.
+    call_burn.call(&mut store, ())?;
+    let burned = store.cpu_time_consumed().unwrap() - spun;
+    assert!(burned < Duration::from_millis(25), "{:?}", burned);
+
+    // Enabling tracking again keeps the count, disabling it drops it.
+    store.track_cpu_time(true);
+    assert!(store.cpu_time_consumed().unwrap() >= spun);
+    store.track_cpu_time(false);
+    assert_eq!(store.cpu_time_consumed(), None);
+    Ok(())
+}
+
+/// Polls a future, keeping the thread busy each time it's pending.
+struct BurnWhilePending<F>(Pin<Box<F>>);
+
+impl<F: Future> Future for BurnWhilePending<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        match self.0.as_mut().poll(cx) {
+            Poll::Ready(output) => Poll::Ready(output),
+            Poll::Pending => {
+                burn(Duration::from_millis(1));
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn excludes_suspended_time() -> Result<()> {
+    let mut config = Config::new();
+    config.async_support(true).consume_fuel(true);
+    let (mut store, module, burn) = store(&config)?;
+    let instance = Instance::new_async(&mut store, &module, &[burn.into()]).await?;
+    store.out_of_fuel_async_yield(u64::max_value(), 100_000);
+    store.track_cpu_time(true);
+
+    let spin = instance.get_typed_func::<i32, (), _>(&mut store, "spin")?;
+    let start = Instant::now();
+    BurnWhilePending(Box::pin(spin.call_async(&mut store, 1_000_000)))
+        .await
+        .unwrap();
+    let elapsed = start.elapsed();
+
+    // The call yields dozens of times, each time keeping the thread busy for
+    // a millisecond which isn't counted.
+    let consumed = store.cpu_time_consumed().unwrap();
+    assert!(consumed > Duration::ZERO);
+    assert!(consumed < elapsed / 2, "{:?} of {:?}", consumed, elapsed);
+    Ok(())
+}
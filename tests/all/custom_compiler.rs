@@ -0,0 +1,261 @@
+use anyhow::Result;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::Arc;
+use wasmtime::*;
+use wasmtime_environ::object::write::Object;
+use wasmtime_environ::{
+    CompileError, Compiler, CompilerBuilder, DefinedFuncIndex, EmittedCode, FlagValue,
+    FunctionBodyData, FunctionHook, FunctionInfo, LazyFunctionTable, ModuleTranslation, PrimaryMap,
+    Setting, Trampoline, Tunables, TypeTables, WasmFuncType,
+};
+
+/// A compiler which wraps Cranelift, counting the functions it compiles and
+/// failing to compile functions once `fail_after` of them were compiled.
+#[derive(Debug)]
+struct CountingBuilder {
+    inner: Box<dyn CompilerBuilder>,
+    compiled: Arc<AtomicUsize>,
+    fail_after: usize,
+}
+
+struct CountingCompiler {
+    inner: Box<dyn Compiler>,
+    compiled: Arc<AtomicUsize>,
+    fail_after: usize,
+}
+
+impl CompilerBuilder for CountingBuilder {
+    fn clone(&self) -> Box<dyn CompilerBuilder> {
+        Box::new(CountingBuilder {
+            inner: self.inner.clone(),
+            compiled: self.compiled.clone(),
+            fail_after: self.fail_after,
+        })
+    }
+    fn target(&mut self, target: target_lexicon::Triple) -> Result<()> {
+        self.inner.target(target)
+    }
+    fn triple(&self) -> &target_lexicon::Triple {
+        self.inner.triple()
+    }
+    fn set(&mut self, name: &str, val: &str) -> Result<()> {
+        self.inner.set(name, val)
+    }
+    fn enable(&mut self, name: &str) -> Result<()> {
+        self.inner.enable(name)
+    }
+    fn settings(&self) -> Vec<Setting> {
+        self.inner.settings()
+    }
+    fn function_hook(&mut self, hook: Arc<dyn FunctionHook>) {
+        self.inner.function_hook(hook)
+    }
+    fn build(&self) -> Result<Box<dyn Compiler>> {
+        Ok(Box::new(CountingCompiler {
+            inner: self.inner.build()?,
+            compiled: self.compiled.clone(),
+            fail_after: self.fail_after,
+        }))
+    }
+}
+
+impl Compiler for CountingCompiler {
+    fn compile_function(
+        &self,
+        translation: &ModuleTranslation<'_>,
+        index: DefinedFuncIndex,
+        data: FunctionBodyData<'_>,
+        tunables: &Tunables,
+        types: &TypeTables,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        if self.compiled.fetch_add(1, SeqCst) >= self.fail_after {
+            return Err(CompileError::Codegen("out of registers".to_string()));
+        }
+        self.inner
+            .compile_function(translation, index, data, tunables, types)
+    }
+    fn compile_host_to_wasm_trampoline(
+        &self,
+        ty: &WasmFuncType,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        self.inner.compile_host_to_wasm_trampoline(ty)
+    }
+    fn emit_obj(
+        &self,
+        module: &ModuleTranslation,
+        types: &TypeTables,
+        funcs: PrimaryMap<DefinedFuncIndex, Box<dyn Any + Send>>,
+        trampolines: Vec<Box<dyn Any + Send>>,
+        tunables: &Tunables,
+        obj: &mut Object<'static>,
+    ) -> Result<EmittedCode> {
+        self.inner
+            .emit_obj(module, types, funcs, trampolines, tunables, obj)
+    }
+    fn compile_lazy_stub(
+        &self,
+        translation: &ModuleTranslation<'_>,
+        index: DefinedFuncIndex,
+        tunables: &Tunables,
+        types: &TypeTables,
+        code: usize,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        self.inner
+            .compile_lazy_stub(translation, index, tunables, types, code)
+    }
+    fn compile_lazy_function(
+        &self,
+        module: &wasmtime_environ::Module,
+        index: DefinedFuncIndex,
+        name: Option<&str>,
+        data: FunctionBodyData<'_>,
+        tunables: &Tunables,
+        types: &TypeTables,
+        table: &LazyFunctionTable,
+        optimize: bool,
+    ) -> Result<Box<dyn Any + Send>, CompileError> {
+        self.compiled.fetch_add(1, SeqCst);
+        self.inner
+            .compile_lazy_function(module, index, name, data, tunables, types, table, optimize)
+    }
+    fn emit_lazy_function_obj(
+        &self,
+        module: &wasmtime_environ::Module,
+        index: DefinedFuncIndex,
+        func: Box<dyn Any + Send>,
+        tunables: &Tunables,
+        obj: &mut Object<'static>,
+    ) -> Result<FunctionInfo> {
+        self.inner
+            .emit_lazy_function_obj(module, index, func, tunables, obj)
+    }
+    fn emit_trampoline_obj(
+        &self,
+        ty: &WasmFuncType,
+        host_fn: usize,
+        obj: &mut Object<'static>,
+    ) -> Result<(Trampoline, Trampoline)> {
+        self.inner.emit_trampoline_obj(ty, host_fn, obj)
+    }
+    fn triple(&self) -> &target_lexicon::Triple {
+        self.inner.triple()
+    }
+    fn page_size_align(&self) -> u64 {
+        self.inner.page_size_align()
+    }
+    fn flags(&self) -> BTreeMap<String, FlagValue> {
+        self.inner.flags()
+    }
+    fn isa_flags(&self) -> BTreeMap<String, FlagValue> {
+        self.inner.isa_flags()
+    }
+}
+
+fn engine(config: &mut Config, fail_after: usize) -> Result<(Engine, Arc<AtomicUsize>)> {
+    let compiled = Arc::new(AtomicUsize::new(0));
+    config.custom_compiler(Box::new(CountingBuilder {
+        inner: wasmtime_cranelift::builder(),
+        compiled: compiled.clone(),
+        fail_after,
+    }));
+    Ok((Engine::new(config)?, compiled))
+}
+
+const WAT: &str = r#"
+    (module
+        (func $double (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 2)))
+        (func (export "run") (param i32) (result i32)
+            (call $double (local.get 0)))
+    )
+"#;
+
+fn run(engine: &Engine, module: &Module) -> Result<i32> {
+    let mut store = Store::new(engine, ());
+    let instance = Instance::new(&mut store, module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    Ok(run.call(&mut store, 21)?)
+}
+
+#[test]
+fn compiles_with_custom_compiler() -> Result<()> {
+    let (engine, compiled) = engine(&mut Config::new(), usize::MAX)?;
+    let module = Module::new(&engine, WAT)?;
+    assert_eq!(compiled.load(SeqCst), 2);
+    assert_eq!(run(&engine, &module)?, 42);
+
+    // The compiler reports the same flags as Cranelift, so its modules can
+    // be loaded by engines using Cranelift and the other way around.
+    let bytes = module.serialize()?;
+    let cranelift = Engine::default();
+    let module = unsafe { Module::deserialize(&cranelift, bytes)? };
+    assert_eq!(run(&cranelift, &module)?, 42);
+    let bytes = Engine::default().precompile_module(WAT.as_bytes())?;
+    let module = unsafe { Module::deserialize(&engine, bytes)? };
+    assert_eq!(run(&engine, &module)?, 42);
+    assert_eq!(compiled.load(SeqCst), 2);
+    Ok(())
+}
+
+#[test]
+fn custom_compiler_compiles_lazily() -> Result<()> {
+    // Lazy compilation isn't a setting of the compiler, so it still applies
+    // after the compiler is replaced.
+    let (engine, compiled) = engine(Config::new().lazy_compilation(true), usize::MAX)?;
+    let module = Module::new(&engine, WAT)?;
+    assert_eq!(compiled.load(SeqCst), 0);
+    assert_eq!(run(&engine, &module)?, 42);
+    assert_eq!(compiled.load(SeqCst), 2);
+    Ok(())
+}
+
+#[test]
+fn custom_compiler_errors() -> Result<()> {
+    let (engine, _) = engine(Config::new().parallel_compilation(false), 1)?;
+    let err = match Module::new(&engine, WAT) {
+        Ok(_) => panic!("the compiler should fail"),
+        Err(e) => e,
+    };
+    assert!(
+        format!("{:?}", err).contains("out of registers"),
+        "{:?}",
+        err
+    );
+    Ok(())
+}
+
+fn assert_probestack_rejected(engine: &Engine) {
+    let err = match Module::new(engine, WAT) {
+        Ok(_) => panic!("the compiler should have probestack enabled"),
+        Err(e) => e,
+    };
+    assert!(
+        format!("{:?}", err).contains("enable_probestack"),
+        "{:?}",
+        err
+    );
+}
+
+#[test]
+fn custom_compiler_gets_config_settings() -> Result<()> {
+    // Settings made before the compiler is replaced are applied to it ...
+    let mut config = Config::new();
+    unsafe {
+        config.cranelift_flag_enable("enable_probestack")?;
+    }
+    let (before, compiled) = engine(&mut config, usize::MAX)?;
+    assert_probestack_rejected(&before);
+    assert_eq!(compiled.load(SeqCst), 0);
+
+    // ... as are the settings made after it.
+    let mut config = Config::new();
+    let (after, _) = engine(&mut config, usize::MAX)?;
+    Module::new(&after, WAT)?;
+    unsafe {
+        config.cranelift_flag_enable("enable_probestack")?;
+    }
+    assert_probestack_rejected(&Engine::new(&config)?);
+    Ok(())
+}
@@ -2,5 +2,6 @@ mod dump;
 mod gdb;
 mod lldb;
 mod obj;
+mod registration;
 mod simulate;
 mod translate;
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use std::slice;
+use wasmtime::{Config, Engine, Module};
+use wasmtime_jit_debug::gdb_jit_int::GDB_REGISTRATION;
+
+#[repr(C)]
+struct JITCodeEntry {
+    next_entry: *mut JITCodeEntry,
+    prev_entry: *mut JITCodeEntry,
+    symfile_addr: *const u8,
+    symfile_size: u64,
+}
+
+#[repr(C)]
+struct JITDescriptor {
+    version: u32,
+    action_flag: u32,
+    relevant_entry: *mut JITCodeEntry,
+    first_entry: *mut JITCodeEntry,
+}
+
+extern "C" {
+    fn wasmtime_jit_debug_descriptor() -> *mut JITDescriptor;
+}
+
+/// Returns the number of images registered with the debugger interface which
+/// contain `marker`.
+fn registered_images_containing(marker: &[u8]) -> usize {
+    let _lock = GDB_REGISTRATION.lock().unwrap();
+    let mut count = 0;
+    unsafe {
+        let desc = &*wasmtime_jit_debug_descriptor();
+        assert_eq!(desc.version, 1);
+        let mut entry = desc.first_entry;
+        while !entry.is_null() {
+            let image =
+                slice::from_raw_parts((*entry).symfile_addr, (*entry).symfile_size as usize);
+            if image.starts_with(b"\x7fELF") && image.windows(marker.len()).any(|w| w == marker) {
+                count += 1;
+            }
+            entry = (*entry).next_entry;
+        }
+    }
+    count
+}
+
+#[test]
+fn module_with_debug_info_is_registered() -> Result<()> {
+    let marker = b"registered-with-the-gdb-jit-interface";
+    let wat = format!(
+        r#"(module (memory 1) (func $f) (data (i32.const 0) "{}"))"#,
+        std::str::from_utf8(marker)?
+    );
+
+    let mut config = Config::new();
+    config.debug_info(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, &wat)?;
+    assert_eq!(registered_images_containing(marker), 1);
+    drop(module);
+    assert_eq!(registered_images_containing(marker), 0);
+
+    // Without debug info nothing is registered.
+    let engine = Engine::default();
+    let _module = Module::new(&engine, &wat)?;
+    assert_eq!(registered_images_containing(marker), 0);
+    Ok(())
+}
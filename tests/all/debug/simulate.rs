@@ -68,6 +68,7 @@ fn test_debug_dwarf_simulate_with_imports_x86_64() -> Result<()> {
 (module
 ;; check: DW_TAG_subprogram 
 ;; check: DW_AT_name	("func1")
+;; check: DW_AT_linkage_name	("<unknown>!func1")
     (import "foo" "bar" (func $import1) )
     (func $func1 (result i32)
         i32.const 1
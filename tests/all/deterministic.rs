@@ -0,0 +1,133 @@
+use anyhow::Result;
+use std::sync::Arc;
+use wasmtime::*;
+
+#[test]
+fn threads_rejected() {
+    let mut config = Config::new();
+    config.deterministic(true).wasm_threads(true);
+    assert!(Engine::new(&config).is_err());
+}
+
+#[test]
+fn nans_are_canonical() -> Result<()> {
+    let mut config = Config::new();
+    config.deterministic(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "nan32") (param f32) (result i32)
+                    (i32.reinterpret_f32 (f32.div (local.get 0) (local.get 0))))
+                (func (export "nan64") (param f64) (result i64)
+                    (i64.reinterpret_f64 (f64.sqrt (local.get 0))))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let nan32 = instance.get_typed_func::<f32, i32, _>(&mut store, "nan32")?;
+    let nan64 = instance.get_typed_func::<f64, i64, _>(&mut store, "nan64")?;
+    assert_eq!(nan32.call(&mut store, 0.0)? as u32, 0x7fc0_0000);
+    assert_eq!(nan64.call(&mut store, -1.0)? as u64, 0x7ff8_0000_0000_0000);
+    Ok(())
+}
+
+/// A memory which the host can never grow.
+struct FixedMemory(Vec<u8>);
+
+unsafe impl LinearMemory for FixedMemory {
+    fn byte_size(&self) -> usize {
+        self.0.len()
+    }
+
+    fn maximum_byte_size(&self) -> Option<usize> {
+        None
+    }
+
+    fn grow_to(&mut self, _new_size: usize) -> Result<()> {
+        anyhow::bail!("host out of memory")
+    }
+
+    fn as_ptr(&self) -> *mut u8 {
+        self.0.as_ptr() as *mut u8
+    }
+}
+
+struct FixedMemoryCreator;
+
+unsafe impl MemoryCreator for FixedMemoryCreator {
+    fn new_memory(
+        &self,
+        _ty: MemoryType,
+        minimum: usize,
+        _maximum: Option<usize>,
+        reserved_size_in_bytes: Option<usize>,
+        guard_size_in_bytes: usize,
+    ) -> Result<Box<dyn LinearMemory>, String> {
+        assert!(reserved_size_in_bytes.is_none());
+        assert_eq!(guard_size_in_bytes, 0);
+        Ok(Box::new(FixedMemory(vec![0; minimum])))
+    }
+}
+
+fn grow_with_failing_host(deterministic: bool) -> Result<Result<i32, Trap>> {
+    let mut config = Config::new();
+    config
+        .deterministic(deterministic)
+        .with_host_memory(Arc::new(FixedMemoryCreator))
+        .static_memory_maximum_size(0)
+        .dynamic_memory_guard_size(0)
+        .guard_before_linear_memory(false);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (func (export "grow") (result i32)
+                    (memory.grow (i32.const 1)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let grow = instance.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    Ok(grow.call(&mut store, ()))
+}
+
+#[test]
+fn host_grow_failure_traps() -> Result<()> {
+    assert_eq!(grow_with_failing_host(false)?.unwrap(), -1);
+
+    let trap = grow_with_failing_host(true)?.unwrap_err();
+    assert!(
+        format!("{:?}", trap).contains("host out of memory"),
+        "bad trap: {:?}",
+        trap
+    );
+    Ok(())
+}
+
+#[test]
+fn maximum_still_reported_to_guest() -> Result<()> {
+    let mut config = Config::new();
+    config.deterministic(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1 1)
+                (func (export "grow") (result i32)
+                    (memory.grow (i32.const 1)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let grow = instance.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    assert_eq!(grow.call(&mut store, ())?, -1);
+    Ok(())
+}
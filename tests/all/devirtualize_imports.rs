@@ -0,0 +1,143 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const CALLER: &str = r#"
+    (module $caller
+        (import "host" "add" (func $add (param i32 i32) (result i32)))
+        (import "callee" "double" (func $double (param i32) (result i32)))
+        (import "callee" "fail" (func $fail))
+        (memory 1)
+        (func (export "run") (param i32) (result i32)
+            (local $sum i32)
+            (loop $l
+                (local.set $sum
+                    (call $add (local.get $sum) (call $double (local.get 0))))
+                (br_if $l (local.tee 0 (i32.sub (local.get 0) (i32.const 1)))))
+            (local.get $sum))
+        (func $call_fail (export "fail") (call $fail))
+    )
+"#;
+
+fn link(store: &mut Store<()>) -> Result<Linker<()>> {
+    let mut linker = Linker::new(store.engine());
+    linker.func_wrap("host", "add", |a: i32, b: i32| a + b)?;
+    let callee = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (func (export "double") (param i32) (result i32)
+                    (i32.mul (local.get 0) (i32.const 2)))
+                (func (export "fail") unreachable)
+            )
+        "#,
+    )?;
+    let callee = linker.instantiate(&mut *store, &callee)?;
+    linker.instance(&mut *store, "callee", callee)?;
+    Ok(linker)
+}
+
+fn run(store: &mut Store<()>, instance: Instance) -> Result<i32> {
+    let run = instance.get_typed_func::<i32, i32, _>(&mut *store, "run")?;
+    Ok(run.call(store, 10)?)
+}
+
+#[test]
+fn calls_imports() -> Result<()> {
+    let mut config = Config::new();
+    config.devirtualize_imports(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    let linker = link(&mut store)?;
+    let module = Module::new(&engine, CALLER)?;
+
+    let pre = linker.instantiate_pre(&mut store, &module)?;
+    for _ in 0..3 {
+        let instance = pre.instantiate(&mut store)?;
+        assert_eq!(run(&mut store, instance)?, 110);
+    }
+
+    // Modules which aren't instantiated through an `InstancePre` call their
+    // imports through the thunks as they were compiled.
+    let instance = linker.instantiate(&mut store, &module)?;
+    assert_eq!(run(&mut store, instance)?, 110);
+    Ok(())
+}
+
+#[test]
+fn traps_in_imports() -> Result<()> {
+    let mut config = Config::new();
+    config.devirtualize_imports(true);
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    let linker = link(&mut store)?;
+    let module = Module::new(&engine, CALLER)?;
+    let instance = linker
+        .instantiate_pre(&mut store, &module)?
+        .instantiate(&mut store)?;
+
+    let fail = instance.get_typed_func::<(), (), _>(&mut store, "fail")?;
+    let trap = fail.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    let trace = trap.trace();
+    assert_eq!(trace.len(), 2);
+    assert_eq!(trace[0].module_name(), None);
+    assert_eq!(trace[1].module_name(), Some("caller"));
+    assert_eq!(trace[1].func_name(), Some("call_fail"));
+    Ok(())
+}
+
+#[test]
+fn host_imports_in_other_stores() -> Result<()> {
+    let mut config = Config::new();
+    config.devirtualize_imports(true);
+    let engine = Engine::new(&config)?;
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("", "", |mut caller: Caller<'_, i32>, x: i32| {
+        *caller.data_mut() += x;
+    })?;
+    let module = Module::new(
+        &engine,
+        r#"(module
+            (import "" "" (func $f (param i32)))
+            (func (export "run") (param i32) (call $f (local.get 0))))"#,
+    )?;
+
+    let mut store = Store::new(&engine, 0);
+    let pre = linker.instantiate_pre(&mut store, &module)?;
+    for x in 1..4 {
+        let mut store = Store::new(&engine, 0);
+        let instance = pre.instantiate(&mut store)?;
+        let run = instance.get_typed_func::<i32, (), _>(&mut store, "run")?;
+        run.call(&mut store, x)?;
+        run.call(&mut store, x)?;
+        assert_eq!(*store.data(), 2 * x);
+    }
+    Ok(())
+}
+
+#[test]
+fn precompiled_modules() -> Result<()> {
+    let mut config = Config::new();
+    config.devirtualize_imports(true);
+    let bytes = Engine::new(&config)?.precompile_module(CALLER.as_bytes())?;
+    let engine = Engine::default();
+    let module = unsafe { Module::deserialize(&engine, bytes)? };
+    let mut store = Store::new(&engine, ());
+    let linker = link(&mut store)?;
+    let instance = linker
+        .instantiate_pre(&mut store, &module)?
+        .instantiate(&mut store)?;
+    assert_eq!(run(&mut store, instance)?, 110);
+
+    // Lazily compiled functions call their imports as usual.
+    config.lazy_compilation(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, CALLER)?;
+    let mut store = Store::new(&engine, ());
+    let linker = link(&mut store)?;
+    let instance = linker
+        .instantiate_pre(&mut store, &module)?
+        .instantiate(&mut store)?;
+    assert_eq!(run(&mut store, instance)?, 110);
+    Ok(())
+}
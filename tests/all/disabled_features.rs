@@ -0,0 +1,126 @@
+use anyhow::Result;
+use wasmtime::*;
+
+type Disable = fn(&mut Config) -> &mut Config;
+
+/// The name of each feature, a module using it and how to disable it.
+fn features() -> Vec<(&'static str, &'static str, Disable)> {
+    vec![
+        (
+            "simd",
+            "(module (func (drop (v128.const i64x2 0 0))))",
+            |c| c.wasm_simd(false),
+        ),
+        ("threads", "(module (func atomic.fence))", |c| {
+            c.wasm_threads(false)
+        }),
+        (
+            "reference-types",
+            "(module (table 1 funcref) (table 1 funcref))",
+            |c| c.wasm_reference_types(false).wasm_simd(false),
+        ),
+        (
+            "multi-value",
+            "(module (func (result i32 i32) i32.const 0 i32.const 1))",
+            |c| c.wasm_multi_value(false),
+        ),
+        (
+            "bulk-memory",
+            "(module (memory 1) (func (memory.fill (i32.const 0) (i32.const 0) (i32.const 0))))",
+            |c| c.wasm_bulk_memory(false).wasm_reference_types(false),
+        ),
+        ("multi-memory", "(module (memory 1) (memory 1))", |c| {
+            c.wasm_multi_memory(false)
+        }),
+        ("memory64", "(module (memory i64 1))", |c| {
+            c.wasm_memory64(false)
+        }),
+        (
+            "sat-float-to-int",
+            "(module (func (drop (i32.trunc_sat_f32_s (f32.const 0)))))",
+            |c| c.wasm_sat_float_to_int(false),
+        ),
+        (
+            "sign-extension",
+            "(module (func (drop (i32.extend8_s (i32.const 0)))))",
+            |c| c.wasm_sign_extension(false),
+        ),
+        (
+            "mutable-global",
+            "(module (global (export \"g\") (mut i32) (i32.const 0)))",
+            |c| c.wasm_mutable_global(false),
+        ),
+    ]
+}
+
+fn disabled_feature(result: Result<impl Sized>) -> DisabledFeatureError {
+    match result {
+        Ok(_) => panic!("module using a disabled feature was accepted"),
+        Err(e) => e
+            .downcast_ref::<DisabledFeatureError>()
+            .unwrap_or_else(|| panic!("feature isn't named in {:?}", e))
+            .clone(),
+    }
+}
+
+#[test]
+fn disabled_features_are_named() -> Result<()> {
+    for (feature, wat, disable) in features() {
+        let wasm = wat::parse_str(wat)?;
+        let mut config = Config::new();
+        config
+            .wasm_threads(true)
+            .wasm_multi_memory(true)
+            .wasm_memory64(true);
+        Module::new(&Engine::new(&config)?, &wasm)?;
+
+        disable(&mut config);
+        let engine = Engine::new(&config)?;
+        let errors = [
+            disabled_feature(Module::new(&engine, &wasm)),
+            disabled_feature(Module::validate(&engine, &wasm)),
+            disabled_feature(engine.precompile_module(&wasm)),
+            disabled_feature((|| {
+                let mut stream = ModuleStream::new(&engine)?;
+                stream.push(&wasm)?;
+                stream.finish()
+            })()),
+        ];
+        for error in errors.iter() {
+            assert_eq!(error.feature(), feature);
+            assert_eq!(error.offset(), errors[0].offset());
+            assert!(error.offset() > 8 && error.offset() < wasm.len());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn disabled_feature_message() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_multi_memory(false);
+    let engine = Engine::new(&config)?;
+    let error = Module::new(&engine, "(module (memory 1) (memory 1))")
+        .err()
+        .unwrap();
+    let message = format!("{:?}", error);
+    assert!(
+        message.contains(
+            "the WebAssembly `multi-memory` feature is used at offset 0xa but is not enabled, \
+             see `Config::wasm_multi_memory`"
+        ),
+        "{}",
+        message
+    );
+    assert!(message.contains("multiple memories"), "{}", message);
+    Ok(())
+}
+
+#[test]
+fn other_errors_are_not_features() -> Result<()> {
+    let error = Module::new(&Engine::default(), "(module (func (drop)))")
+        .err()
+        .unwrap();
+    assert!(error.downcast_ref::<DisabledFeatureError>().is_none());
+    Ok(())
+}
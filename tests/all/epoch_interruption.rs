@@ -364,3 +364,107 @@ async fn drop_future_on_epoch_yield() {
 
     assert_eq!(true, alive_flag.load(Ordering::Acquire));
 }
+
+#[test]
+fn epoch_callback_continue() {
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config).unwrap();
+    let mut linker = Linker::new(&engine);
+    let bump_engine = engine.clone();
+    linker
+        .func_wrap("", "bump_epoch", move || bump_engine.increment_epoch())
+        .unwrap();
+    let module = Module::new(
+        &engine,
+        "
+        (module
+            (import \"\" \"bump_epoch\" (func $bump))
+            (func (export \"run\")
+                call $bump
+                call $subfunc
+                call $bump
+                call $subfunc)
+            (func $subfunc))
+        ",
+    )
+    .unwrap();
+    let mut store = Store::new(&engine, 0);
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(|mut cx| {
+        *cx.data_mut() += 1;
+        Ok(1)
+    });
+
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let f = instance
+        .get_typed_func::<(), (), _>(&mut store, "run")
+        .unwrap();
+    f.call(&mut store, ()).unwrap();
+    assert_eq!(*store.data(), 2);
+}
+
+#[test]
+fn epoch_callback_trap() {
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config).unwrap();
+    let module = Module::new(&engine, "(module (func (export \"run\") (loop br 0)))").unwrap();
+    let mut store = Store::new(&engine, ());
+    store.set_epoch_deadline(1);
+    store.epoch_deadline_callback(|_| Err(anyhow::anyhow!("out of time")));
+    engine.increment_epoch();
+
+    let instance = Instance::new(&mut store, &module, &[]).unwrap();
+    let f = instance
+        .get_typed_func::<(), (), _>(&mut store, "run")
+        .unwrap();
+    let trap = f.call(&mut store, ()).unwrap_err();
+    assert!(
+        trap.to_string().contains("out of time"),
+        "bad trap: {}",
+        trap
+    );
+}
+
+#[test]
+fn epoch_callback_switches_to_trap() {
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config).unwrap();
+    let mut linker = Linker::new(&engine);
+    let bump_engine = engine.clone();
+    linker
+        .func_wrap("", "bump_epoch", move || bump_engine.increment_epoch())
+        .unwrap();
+    let module = Module::new(
+        &engine,
+        "
+        (module
+            (import \"\" \"bump_epoch\" (func $bump))
+            (func (export \"run\")
+                call $bump
+                call $subfunc
+                call $bump
+                call $subfunc)
+            (func $subfunc))
+        ",
+    )
+    .unwrap();
+    let mut store = Store::new(&engine, 0);
+    store.set_epoch_deadline(1);
+    // The callback runs once, and its switch to trapping isn't undone.
+    store.epoch_deadline_callback(|mut cx| {
+        *cx.data_mut() += 1;
+        cx.epoch_deadline_trap();
+        Ok(1)
+    });
+
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let f = instance
+        .get_typed_func::<(), (), _>(&mut store, "run")
+        .unwrap();
+    let trap = f.call(&mut store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::Interrupt));
+    assert_eq!(*store.data(), 1);
+}
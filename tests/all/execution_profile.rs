@@ -0,0 +1,82 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (import "" "" (func))
+        (func (export "count") (param i32) (result i32)
+            (local i32)
+            (loop $l
+                (local.set 1 (i32.add (local.get 1) (i32.const 1)))
+                (br_if $l (i32.lt_u (local.get 1) (local.get 0))))
+            (if (result i32) (i32.eq (local.get 1) (i32.const 10))
+                (then (i32.const 1))
+                (else (i32.const 0))))
+    )
+"#;
+
+fn call_count(engine: &Engine, module: &Module, n: i32) -> Result<i32> {
+    let mut store = Store::new(engine, ());
+    let import = Func::wrap(&mut store, || {});
+    let instance = Instance::new(&mut store, module, &[import.into()])?;
+    let count = instance.get_typed_func::<i32, i32, _>(&mut store, "count")?;
+    Ok(count.call(&mut store, n)?)
+}
+
+fn collect() -> Result<ExecutionProfile> {
+    let mut config = Config::new();
+    config
+        .lazy_compilation(true)
+        .collect_execution_profile(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, WAT)?;
+    assert_eq!(call_count(&engine, &module, 10)?, 1);
+    assert_eq!(call_count(&engine, &module, 3)?, 0);
+    Ok(module.execution_profile().unwrap())
+}
+
+#[test]
+fn collects_calls_and_branches() -> Result<()> {
+    let profile = collect()?;
+    assert_eq!(profile.calls(0), 0);
+    assert_eq!(profile.calls(1), 2);
+    assert_eq!(profile.branches(1).collect::<Vec<_>>(), [(13, 11), (2, 1)]);
+    Ok(())
+}
+
+#[test]
+fn compiles_with_profile() -> Result<()> {
+    let mut profile = collect()?;
+    let bytes = profile.serialize()?;
+    let copy = ExecutionProfile::deserialize(&bytes)?;
+    assert_eq!(copy, profile);
+    profile.merge(&copy)?;
+    assert_eq!(profile.calls(1), 4);
+    assert_eq!(profile.branches(1).collect::<Vec<_>>(), [(26, 22), (4, 2)]);
+
+    let engine = Engine::default();
+    let module = Module::new_with_profile(&engine, WAT, &profile)?;
+    assert_eq!(call_count(&engine, &module, 10)?, 1);
+    assert_eq!(call_count(&engine, &module, 3)?, 0);
+    assert!(module.execution_profile().is_none());
+    Ok(())
+}
+
+#[test]
+fn profile_of_other_module() -> Result<()> {
+    let profile = collect()?;
+    let err = Module::new_with_profile(
+        &Engine::default(),
+        r#"(module (func (export "count") (param i32) (result i32) local.get 0))"#,
+        &profile,
+    )
+    .err()
+    .unwrap();
+    assert!(format!("{:?}", err).contains("profile is of a module with different functions"));
+
+    let mut config = Config::new();
+    config.collect_execution_profile(true);
+    let err = Engine::new(&config).err().unwrap();
+    assert!(err.to_string().contains("requires lazy compilation"));
+    Ok(())
+}
@@ -932,3 +932,62 @@ fn typed_funcs_count_params_correctly_in_error_messages() -> anyhow::Result<()>
 
     Ok(())
 }
+
+#[test]
+fn declared_host_trampolines() -> Result<()> {
+    let unary = FuncType::new([ValType::I32], [ValType::I32]);
+    let nullary = FuncType::new([], []);
+    let mut config = Config::new();
+    config.host_trampoline_signatures([unary.clone(), unary.clone(), nullary.clone()]);
+    let engine = Engine::new(&config)?;
+    assert_eq!(
+        engine.host_trampoline_signatures(),
+        Some(&[unary.clone(), nullary.clone()][..])
+    );
+    assert_eq!(Engine::default().host_trampoline_signatures(), None);
+
+    // Functions with the same signature share the engine's trampolines, and
+    // are called both from wasm and from the host through them.
+    let mut store = Store::new(&engine, ());
+    let double = Func::try_new(&mut store, unary.clone(), |_, params, results| {
+        results[0] = Val::I32(params[0].unwrap_i32() * 2);
+        Ok(())
+    })?;
+    let square = Func::new(&mut store, unary.clone(), |_, params, results| {
+        results[0] = Val::I32(params[0].unwrap_i32() * params[0].unwrap_i32());
+        Ok(())
+    });
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "double" (func $double (param i32) (result i32)))
+                (import "" "square" (func $square (param i32) (result i32)))
+                (func (export "run") (param i32) (result i32)
+                    (call $square (call $double (local.get 0)))))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[double.into(), square.into()])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, 3)?, 36);
+    let mut results = [Val::I32(0)];
+    double.call(&mut store, &[Val::I32(4)], &mut results)?;
+    assert_eq!(results[0].unwrap_i32(), 8);
+
+    // Other signatures are errors rather than compiled, except for
+    // `Func::wrap`, which compiles nothing.
+    let binary = FuncType::new([ValType::I32, ValType::I32], []);
+    let error = Func::try_new(&mut store, binary.clone(), |_, _, _| Ok(())).unwrap_err();
+    let undeclared = error.downcast_ref::<UndeclaredSignatureError>().unwrap();
+    assert_eq!(undeclared.ty(), &binary);
+    assert!(error.to_string().contains("(i32, i32) -> ()"), "{}", error);
+    let mut linker = Linker::<()>::new(&engine);
+    linker.func_new("", "nullary", nullary, |_, _, _| Ok(()))?;
+    let error = linker
+        .func_new("", "binary", binary, |_, _, _| Ok(()))
+        .err()
+        .unwrap();
+    assert!(error.is::<UndeclaredSignatureError>());
+    linker.func_wrap("", "add", |a: i32, b: i32| a + b)?;
+    Ok(())
+}
@@ -487,3 +487,179 @@ fn no_gc_middle_of_args() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn externref_drop_hooks() -> anyhow::Result<()> {
+    struct Handle(u32);
+
+    let mut config = Config::new();
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (table $t 2 externref)
+                (func (export "set") (param i32 externref)
+                    (table.set $t (local.get 0) (local.get 1)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.externref_drop_hook(|released: &mut Vec<u32>, handle: Handle| {
+        released.push(handle.0);
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let set = instance.get_typed_func::<(i32, Option<ExternRef>), (), _>(&mut store, "set")?;
+
+    let first = ExternRef::new_in(&mut store, Handle(1));
+    let second = ExternRef::new_in(&mut store, Handle(2));
+    let other = ExternRef::new_in(&mut store, "not a handle");
+    assert!(first.is::<Handle>());
+    assert_eq!(second.downcast_ref::<Handle>().unwrap().0, 2);
+    assert_eq!(*other.downcast_ref::<&str>().unwrap(), "not a handle");
+    assert!(other.downcast_ref::<Handle>().is_none());
+
+    // References held by the guest are only reclaimed once it lets go of
+    // them.
+    set.call(&mut store, (0, Some(first)))?;
+    set.call(&mut store, (1, Some(second.clone())))?;
+    store.gc();
+    assert!(store.data().is_empty());
+
+    set.call(&mut store, (0, None))?;
+    store.gc();
+    assert_eq!(store.data(), &[1]);
+
+    // Dropping the last reference on the host only sets the value aside
+    // until the next collection.
+    set.call(&mut store, (1, None))?;
+    drop(second);
+    drop(other);
+    assert_eq!(store.data(), &[1]);
+    store.gc();
+    assert_eq!(store.data(), &[1, 2]);
+
+    // References created without the store don't have hooks.
+    let plain = ExternRef::new(Handle(3));
+    assert!(plain.is::<Handle>());
+    drop(plain);
+    store.gc();
+    assert_eq!(store.data(), &[1, 2]);
+    Ok(())
+}
+
+#[test]
+fn externref_drop_hooks_outlive_store() -> anyhow::Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let dropped = Arc::new(AtomicBool::new(false));
+    store.externref_drop_hook(|_, _: SetFlagOnDrop| panic!("the store was dropped"));
+    let externref = ExternRef::new_in(&mut store, SetFlagOnDrop(dropped.clone()));
+    drop(store);
+    assert!(!dropped.load(SeqCst));
+    drop(externref);
+    assert!(dropped.load(SeqCst));
+    Ok(())
+}
+
+#[test]
+fn gc_stats() -> anyhow::Result<()> {
+    let (mut store, module) = ref_types_module(
+        false,
+        r#"
+            (module
+                (table $t 2 externref)
+                (global $g (mut externref) (ref.null extern))
+                (func (export "set") (param i32 externref)
+                    (table.set $t (local.get 0) (local.get 1)))
+                (func (export "set_global") (param externref)
+                    (global.set $g (local.get 0)))
+            )
+        "#,
+    )?;
+    let stats = store.gc_stats();
+    assert_eq!(stats.live_externrefs(), 0);
+    assert_eq!(stats.collections(), 0);
+    assert_eq!(stats.last_collection_duration(), None);
+    assert_eq!(stats.last_collection_released(), None);
+
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let set = instance.get_typed_func::<(i32, Option<ExternRef>), (), _>(&mut store, "set")?;
+    let set_global =
+        instance.get_typed_func::<Option<ExternRef>, (), _>(&mut store, "set_global")?;
+
+    // A reference held in several places is only live once.
+    let r = ExternRef::new(42);
+    set.call(&mut store, (0, Some(r.clone())))?;
+    set.call(&mut store, (1, Some(r.clone())))?;
+    set_global.call(&mut store, Some(r.clone()))?;
+    let stats = store.gc_stats();
+    assert_eq!(stats.table_externrefs(), 2);
+    assert_eq!(stats.global_externrefs(), 1);
+    assert_eq!(stats.live_externrefs(), 1);
+    let used = stats.activations_externrefs();
+    assert!(used > 0);
+
+    // Once collected, the references are only held by the table and global.
+    let collections = stats.collections();
+    store.gc();
+    let stats = store.gc_stats();
+    assert_eq!(stats.collections(), collections + 1);
+    assert_eq!(stats.activations_externrefs(), 0);
+    assert_eq!(stats.last_collection_released(), Some(used));
+    assert!(stats.last_collection_duration().unwrap() <= stats.total_collection_duration());
+    assert_eq!(stats.live_externrefs(), 1);
+
+    set.call(&mut store, (0, None))?;
+    set_global.call(&mut store, None)?;
+    let stats = store.gc_stats();
+    assert_eq!(stats.table_externrefs(), 1);
+    assert_eq!(stats.global_externrefs(), 0);
+    Ok(())
+}
+
+#[test]
+fn gc_threshold() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+            (func (export "drop_ref") (param externref)
+                nop
+            )
+        )
+    "#;
+    let count_collections = |threshold: Option<usize>| -> anyhow::Result<(u64, usize)> {
+        let (mut store, module) = ref_types_module(false, wat)?;
+        if let Some(threshold) = threshold {
+            store.externref_gc_threshold(threshold);
+        }
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let drop_ref =
+            instance.get_typed_func::<Option<ExternRef>, (), _>(&mut store, "drop_ref")?;
+        let dropped = Arc::new(AtomicUsize::new(0));
+        for _ in 0..100 {
+            let r = ExternRef::new(CountDrops(dropped.clone()));
+            drop_ref.call(&mut store, Some(r))?;
+        }
+        Ok((store.gc_stats().collections(), dropped.load(SeqCst)))
+    };
+
+    // The default threshold fits all of these references.
+    let (collections, dropped) = count_collections(None)?;
+    assert!(collections <= 2, "{}", collections);
+    assert!(dropped < 10, "{}", dropped);
+
+    // A lower threshold collects them as they're used.
+    let (collections, dropped) = count_collections(Some(8))?;
+    assert!(collections >= 10, "{}", collections);
+    assert!(dropped >= 90, "{}", dropped);
+    return Ok(());
+
+    struct CountDrops(Arc<AtomicUsize>);
+
+    impl Drop for CountDrops {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+}
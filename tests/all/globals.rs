@@ -103,3 +103,87 @@ fn v128() -> anyhow::Result<()> {
     assert_eq!(g.get(&mut store).v128(), Some(1));
     Ok(())
 }
+
+#[test]
+fn shared_immutable_globals() -> anyhow::Result<()> {
+    let wat = r#"
+        (module
+            (global $a (export "a") i32 (i32.const 1))
+            (global $m (export "m") (mut i32) (i32.const 2))
+            (global $b (export "b") i64 (i64.const 3))
+            (global $c (export "c") f32 (f32.const 4))
+            (global $d (export "d") f64 (f64.const 5))
+            (global (export "r") externref (ref.null extern))
+            (global (export "f") funcref (ref.func $a))
+            (func $a (export "get_a") (result i32) global.get $a)
+            (func (export "get_m") (result i32) global.get $m)
+            (func (export "get_b") (result i64) global.get $b)
+            (func (export "get_c") (result f32) global.get $c)
+            (func (export "get_d") (result f64) global.get $d)
+            (func (export "set_m") (param i32) (global.set $m (local.get 0)))
+        )
+    "#;
+    let importer = r#"
+        (module
+            (import "" "a" (global $a i32))
+            (global (export "copy") i32 (global.get $a))
+            (func (export "get_a") (result i32) global.get $a)
+        )
+    "#;
+    for strategy in [Strategy::Cranelift, Strategy::Baseline] {
+        let mut config = Config::new();
+        config.strategy(strategy)?;
+        if !super::skip_pooling_allocator_tests() {
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+                strategy: PoolingAllocationStrategy::NextAvailable,
+                instance_limits: InstanceLimits {
+                    count: 3,
+                    ..Default::default()
+                },
+            });
+        }
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let first = Instance::new(&mut store, &module, &[])?;
+        let second = Instance::new(&mut store, &module, &[])?;
+
+        for instance in [first, second] {
+            let get_a = instance.get_typed_func::<(), i32, _>(&mut store, "get_a")?;
+            let get_b = instance.get_typed_func::<(), i64, _>(&mut store, "get_b")?;
+            let get_c = instance.get_typed_func::<(), f32, _>(&mut store, "get_c")?;
+            let get_d = instance.get_typed_func::<(), f64, _>(&mut store, "get_d")?;
+            assert_eq!(get_a.call(&mut store, ())?, 1);
+            assert_eq!(get_b.call(&mut store, ())?, 3);
+            assert_eq!(get_c.call(&mut store, ())?, 4.0);
+            assert_eq!(get_d.call(&mut store, ())?, 5.0);
+
+            let r = instance.get_global(&mut store, "r").unwrap();
+            assert!(r.get(&mut store).unwrap_externref().is_none());
+            let f = instance.get_global(&mut store, "f").unwrap();
+            assert!(f.get(&mut store).unwrap_funcref().is_some());
+        }
+
+        // Mutable globals are still kept by each instance.
+        let set_m = first.get_typed_func::<i32, (), _>(&mut store, "set_m")?;
+        set_m.call(&mut store, 20)?;
+        for (instance, expected) in [(first, 20), (second, 2)] {
+            let get_m = instance.get_typed_func::<(), i32, _>(&mut store, "get_m")?;
+            assert_eq!(get_m.call(&mut store, ())?, expected);
+            let m = instance.get_global(&mut store, "m").unwrap();
+            assert_eq!(m.get(&mut store).i32(), Some(expected));
+        }
+
+        // Shared globals can be read by the host and imported, but not set.
+        let a = first.get_global(&mut store, "a").unwrap();
+        assert_eq!(a.get(&mut store).i32(), Some(1));
+        assert!(a.set(&mut store, 10.into()).is_err());
+        let importer = Module::new(&engine, importer)?;
+        let instance = Instance::new(&mut store, &importer, &[a.into()])?;
+        let get_a = instance.get_typed_func::<(), i32, _>(&mut store, "get_a")?;
+        assert_eq!(get_a.call(&mut store, ())?, 1);
+        let copy = instance.get_global(&mut store, "copy").unwrap();
+        assert_eq!(copy.get(&mut store).i32(), Some(1));
+    }
+    Ok(())
+}
@@ -0,0 +1,191 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+#[test]
+fn single_step_visits_each_instruction() -> Result<()> {
+    let mut config = Config::new();
+    config.guest_debug(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "add") (param i32 i32) (result i32)
+                    (local i64)
+                    i64.const 7
+                    local.set 2
+                    local.get 0
+                    local.get 1
+                    i32.add)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.debug_handler(|mut cx, frame| {
+        let local = frame.local(2).unwrap().unwrap_i64();
+        cx.data_mut().push((frame.module_offset(), local));
+        Ok(DebugAction::Step)
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+
+    store.single_step(true);
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+
+    // Each of the five instructions, then the function's `end`, is visited in
+    // order, and the local is observed both before and after it's set.
+    let steps = std::mem::take(store.data_mut());
+    assert_eq!(steps.len(), 6, "{:?}", steps);
+    assert!(steps.windows(2).all(|w| w[0].0 < w[1].0), "{:?}", steps);
+    let locals = steps.iter().map(|(_, local)| *local).collect::<Vec<_>>();
+    assert_eq!(locals, [0, 0, 7, 7, 7, 7]);
+
+    // Continuing instead of stepping runs to completion without pausing.
+    store.debug_handler(|mut cx, frame| {
+        cx.data_mut().push((frame.module_offset(), 0));
+        Ok(DebugAction::Continue)
+    });
+    store.single_step(true);
+    assert_eq!(add.call(&mut store, (1, 2))?, 3);
+    assert_eq!(store.data().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn breakpoints_pause_with_locals_and_backtrace() -> Result<()> {
+    let mut config = Config::new();
+    config.guest_debug(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $add (export "add") (param i32 i32) (result i32)
+                    (local i64)
+                    i64.const 7
+                    local.set 2
+                    local.get 0
+                    local.get 1
+                    i32.add)
+                (func (export "call-add") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    call $add)
+            )
+        "#,
+    )?;
+
+    // Find the offset of `i32.add` by stepping through `add` once.
+    let mut store = Store::new(&engine, Vec::new());
+    store.debug_handler(|mut cx, frame| {
+        cx.data_mut().push(frame.module_offset());
+        Ok(DebugAction::Step)
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let add = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add")?;
+    store.single_step(true);
+    add.call(&mut store, (1, 2))?;
+    let i32_add = store.data()[4];
+
+    let frames = Arc::new(Mutex::new(Vec::new()));
+    let frames2 = frames.clone();
+    let mut store = Store::new(&engine, ());
+    store.debug_handler(move |_cx, frame| {
+        let func_indices = frame
+            .backtrace()
+            .iter()
+            .map(|f| f.func_index())
+            .collect::<Vec<_>>();
+        frames2
+            .lock()
+            .unwrap()
+            .push((frame.func_index(), frame.locals().len(), func_indices));
+        assert_eq!(frame.module_offset(), i32_add);
+        assert_eq!(frame.local(0).unwrap().unwrap_i32(), 5);
+        assert_eq!(frame.local(1).unwrap().unwrap_i32(), 1);
+        assert_eq!(frame.local(2).unwrap().unwrap_i64(), 7);
+        assert!(frame.local(3).is_none());
+        Ok(DebugAction::Continue)
+    });
+    store.set_breakpoint(&module, i32_add);
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call_add = instance.get_typed_func::<i32, i32, _>(&mut store, "call-add")?;
+    assert_eq!(call_add.call(&mut store, 5)?, 6);
+    assert_eq!(*frames.lock().unwrap(), [(0, 3, vec![0, 1])]);
+
+    // Once cleared the breakpoint is no longer hit.
+    store.clear_breakpoint(&module, i32_add);
+    assert_eq!(call_add.call(&mut store, 5)?, 6);
+    assert_eq!(frames.lock().unwrap().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn debug_handler_errors_trap() -> Result<()> {
+    let mut config = Config::new();
+    config.guest_debug(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, r#"(module (func (export "run") nop))"#)?;
+    let mut store = Store::new(&engine, ());
+    store.debug_handler(|_cx, _frame| Err(anyhow::anyhow!("stopped by debugger")));
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    store.single_step(true);
+    let trap = run.call(&mut store, ()).unwrap_err();
+    assert!(
+        trap.to_string().contains("stopped by debugger"),
+        "bad trap: {}",
+        trap
+    );
+    Ok(())
+}
+
+#[test]
+fn reference_locals() -> Result<()> {
+    let mut config = Config::new();
+    config.guest_debug(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "run") (param externref funcref)
+                    nop)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.debug_handler(|mut cx, frame| {
+        let externref = frame.local(0).unwrap().unwrap_externref();
+        let value = *externref.unwrap().data().downcast_ref::<u32>().unwrap();
+        let is_func = frame.local(1).unwrap().unwrap_funcref().is_some();
+        cx.data_mut().push((value, is_func));
+        Ok(DebugAction::Continue)
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run =
+        instance.get_typed_func::<(Option<ExternRef>, Option<Func>), (), _>(&mut store, "run")?;
+    let func = Func::wrap(&mut store, || {});
+    store.single_step(true);
+    run.call(&mut store, (Some(ExternRef::new(42u32)), Some(func)))?;
+    assert_eq!(*store.data(), [(42, true)]);
+    Ok(())
+}
+
+#[test]
+fn serialized_modules_require_same_setting() -> Result<()> {
+    let module = Module::new(&Engine::default(), "(module)")?;
+    let bytes = module.serialize()?;
+    let mut config = Config::new();
+    config.guest_debug(true);
+    let err = unsafe { Module::deserialize(&Engine::new(&config)?, &bytes) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("guest debugging"),
+        "bad error: {:?}",
+        err
+    );
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn sample_from_epoch_callback() -> Result<()> {
+    let mut config = Config::new();
+    config.epoch_interruption(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $m
+                (import "" "bump_epoch" (func $bump))
+                (func $leaf)
+                (func $middle
+                    call $bump
+                    call $leaf)
+                (func (export "run") call $middle)
+            )
+        "#,
+    )?;
+
+    let profiler = Arc::new(Mutex::new(GuestProfiler::new("test")));
+    let mut store = Store::new(&engine, ());
+    store.set_epoch_deadline(1);
+    let sampler = profiler.clone();
+    store.epoch_deadline_callback(move |cx| {
+        sampler.lock().unwrap().sample(&cx);
+        Ok(1)
+    });
+
+    let bump = Func::wrap(&mut store, {
+        let engine = engine.clone();
+        move || engine.increment_epoch()
+    });
+    let instance = Instance::new(&mut store, &module, &[bump.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    drop(store);
+
+    let profiler = Arc::try_unwrap(profiler).unwrap().into_inner().unwrap();
+    let mut output = Vec::new();
+    profiler.finish(&mut output)?;
+    let output = String::from_utf8(output)?;
+
+    // Frames are numbered in the order they're first seen, starting from the
+    // innermost frame, and each sample lists the outermost frame first.
+    assert!(
+        output.contains(r#""type":"sampled""#),
+        "bad output: {}",
+        output
+    );
+    assert!(
+        output.contains(
+            r#""frames":[{"name":"m!leaf","file":"m"},{"name":"m!middle","file":"m"},{"name":"m!<wasm function 3>","file":"m"}]"#
+        ),
+        "bad output: {}",
+        output
+    );
+    assert!(
+        output.contains(r#""samples":[[2,1,0]]"#),
+        "bad output: {}",
+        output
+    );
+    Ok(())
+}
+
+#[test]
+fn sample_without_wasm_on_stack() -> Result<()> {
+    let store = Store::<()>::default();
+    let mut profiler = GuestProfiler::new("idle");
+    profiler.sample(&store);
+    let mut output = Vec::new();
+    profiler.finish(&mut output)?;
+    let output = String::from_utf8(output)?;
+    assert!(
+        output.contains(r#""samples":[[]]"#),
+        "bad output: {}",
+        output
+    );
+    Ok(())
+}
@@ -0,0 +1,134 @@
+use anyhow::Result;
+use std::ops::Range;
+use wasmtime::*;
+
+/// Returns the `VmFlags` of the mappings of this process which overlap
+/// `range`, along with their permissions, as listed in `/proc/self/smaps`.
+fn mappings(range: Range<usize>) -> Vec<(String, Vec<String>)> {
+    let smaps = std::fs::read_to_string("/proc/self/smaps").unwrap();
+    let mut ret = Vec::new();
+    let mut current = None;
+    for line in smaps.lines() {
+        if let Some(flags) = line.strip_prefix("VmFlags:") {
+            if let Some(perms) = current.take() {
+                ret.push((perms, flags.split_whitespace().map(String::from).collect()));
+            }
+            continue;
+        }
+        let mut parts = line.split_whitespace();
+        let (addrs, perms) = match (parts.next(), parts.next()) {
+            (Some(addrs), Some(perms)) if !addrs.ends_with(':') => (addrs, perms),
+            _ => continue,
+        };
+        let (start, end) = match addrs.split_once('-') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        let start = usize::from_str_radix(start, 16).unwrap();
+        let end = usize::from_str_radix(end, 16).unwrap();
+        current = if start < range.end && range.start < end {
+            Some(perms.to_string())
+        } else {
+            None
+        };
+    }
+    ret
+}
+
+fn advised(range: Range<usize>) -> bool {
+    mappings(range)
+        .iter()
+        .any(|(_, flags)| flags.iter().any(|f| f == "hg"))
+}
+
+fn memory_range(store: &Store<()>, memory: &Memory) -> Range<usize> {
+    let base = memory.data_ptr(store) as usize;
+    base..base + memory.data_size(store)
+}
+
+/// Whether the kernel supports transparent huge pages at all.
+fn supported() -> bool {
+    std::path::Path::new("/sys/kernel/mm/transparent_hugepage/enabled").exists()
+}
+
+#[test]
+fn memories() -> Result<()> {
+    if !supported() {
+        return Ok(());
+    }
+    let mut config = Config::new();
+    config.huge_pages_for_memories(true);
+    // Keep memories dynamic to check that they're still advised once they
+    // move while growing.
+    config.static_memory_maximum_size(0);
+    config.dynamic_memory_reserved_for_growth(0);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, r#"(module (memory (export "m") 1))"#)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "m").unwrap();
+    assert!(advised(memory_range(&store, &memory)));
+
+    memory.grow(&mut store, 100)?;
+    assert!(advised(memory_range(&store, &memory)));
+
+    let host = Memory::new(&mut store, MemoryType::new(1, None))?;
+    assert!(advised(memory_range(&store, &host)));
+
+    // Memories aren't advised by default.
+    let engine = Engine::default();
+    let module = Module::new(&engine, r#"(module (memory (export "m") 1))"#)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "m").unwrap();
+    assert!(!advised(memory_range(&store, &memory)));
+    Ok(())
+}
+
+#[test]
+fn pooled_memories() -> Result<()> {
+    if !supported() {
+        return Ok(());
+    }
+    let mut config = Config::new();
+    config.huge_pages_for_memories(true);
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::default(),
+        instance_limits: InstanceLimits {
+            count: 2,
+            memory_pages: 10,
+            ..Default::default()
+        },
+    });
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, r#"(module (memory (export "m") 1))"#)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "m").unwrap();
+    assert!(advised(memory_range(&store, &memory)));
+    Ok(())
+}
+
+#[test]
+fn code() -> Result<()> {
+    if !supported() {
+        return Ok(());
+    }
+    let wat = r#"(module (func (export "f") (result i32) i32.const 1))"#;
+    let code_advised = |engine: &Engine| -> Result<bool> {
+        let module = Module::new(engine, wat)?;
+        let mut store = Store::new(engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+        assert_eq!(f.call(&mut store, ())?, 1);
+        Ok(mappings(module.image_range())
+            .iter()
+            .any(|(perms, flags)| perms.contains('x') && flags.iter().any(|f| f == "hg")))
+    };
+
+    let mut config = Config::new();
+    config.huge_pages_for_code(true);
+    assert!(code_advised(&Engine::new(&config)?)?);
+    assert!(!code_advised(&Engine::default())?);
+    Ok(())
+}
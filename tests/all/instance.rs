@@ -80,3 +80,239 @@ fn linear_memory_limits() -> Result<()> {
         Ok(())
     }
 }
+
+#[test]
+fn segment_out_of_bounds() -> Result<()> {
+    let wat = r#"
+        (module
+            (import "" "mem" (memory 1))
+            (import "" "base" (global i32))
+            (table 2 funcref)
+            (data "passive")
+            (data (i32.const 0) "ok")
+            (data (global.get 0) "oops")
+            (elem (i32.const 0) func 0 0)
+            (func)
+        )"#;
+    let engine = Engine::default();
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(1, None))?;
+    let base = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        Val::I32(65534),
+    )?;
+    let err = Instance::new(&mut store, &module, &[memory.into(), base.into()])
+        .err()
+        .unwrap();
+
+    let oob = err.downcast_ref::<SegmentOutOfBounds>().unwrap();
+    assert_eq!(oob.kind(), SegmentKind::Data);
+    assert_eq!(oob.index(), 2);
+    assert_eq!(oob.offset(), 65534);
+    assert_eq!(oob.len(), 4);
+    assert_eq!(oob.size(), 65536);
+    assert_eq!(
+        err.to_string(),
+        "data segment 2 does not fit: 4 bytes at offset 65534 of a memory of 65536 bytes"
+    );
+    let trap = err.downcast_ref::<Trap>().unwrap();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+
+    // Segments before the one which didn't fit were still written.
+    let mut bytes = [0; 2];
+    memory.read(&store, 0, &mut bytes)?;
+    assert_eq!(&bytes, b"ok");
+
+    let module = Module::new(
+        &engine,
+        "(module (table 2 funcref) (elem (i32.const 1) func 0 0) (func))",
+    )?;
+    let err = Instance::new(&mut store, &module, &[]).err().unwrap();
+    let oob = err.downcast_ref::<SegmentOutOfBounds>().unwrap();
+    assert_eq!(oob.kind(), SegmentKind::Element);
+    assert_eq!(oob.index(), 0);
+    assert_eq!((oob.offset(), oob.len(), oob.size()), (1, 2, 2));
+    let trap = err.downcast_ref::<Trap>().unwrap();
+    assert_eq!(trap.trap_code(), Some(TrapCode::TableOutOfBounds));
+    Ok(())
+}
+
+#[test]
+fn segment_out_of_bounds_without_bulk_memory() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_reference_types(false).wasm_bulk_memory(false);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"(module (memory 1) (data (i32.const 0) "ok") (data (i32.const 65535) "oops"))"#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let err = Instance::new(&mut store, &module, &[]).err().unwrap();
+    let oob = err.downcast_ref::<SegmentOutOfBounds>().unwrap();
+    assert_eq!((oob.index(), oob.offset()), (1, 65535));
+    assert!(err.downcast_ref::<Trap>().is_none());
+    assert!(format!("{:?}", err).contains("data segment does not fit"));
+    Ok(())
+}
+
+#[test]
+fn initializers() -> Result<()> {
+    let wat = r#"
+        (module
+            (import "" "base" (global i32))
+            (memory 1)
+            (table 2 funcref)
+            (data "passive")
+            (data (i32.const 65534) "ok")
+            (data (global.get 0) "oops")
+            (elem (i32.const 1) func 0 0)
+            (func)
+        )"#;
+    let module = Module::new(&Engine::default(), wat)?;
+    let segments = module.initializers().collect::<Vec<_>>();
+    assert_eq!(segments.len(), 3);
+
+    // Tables are initialized before memories.
+    assert_eq!(segments[0].kind(), SegmentKind::Element);
+    assert_eq!((segments[0].index(), segments[0].target()), (0, 0));
+    assert_eq!((segments[0].offset(), segments[0].len()), (1, 2));
+    assert_eq!(segments[0].fits_minimum(), Some(false));
+
+    assert_eq!(segments[1].kind(), SegmentKind::Data);
+    assert_eq!((segments[1].index(), segments[1].target()), (1, 0));
+    assert_eq!((segments[1].offset(), segments[1].len()), (65534, 2));
+    assert_eq!(segments[1].global_base(), None);
+    assert_eq!(segments[1].fits_minimum(), Some(true));
+
+    assert_eq!(segments[2].index(), 2);
+    assert_eq!(segments[2].global_base(), Some(0));
+    assert_eq!(segments[2].fits_minimum(), None);
+    assert!(segments[2].fits(65532, 65536));
+    assert!(!segments[2].fits(65533, 65536));
+    Ok(())
+}
+
+#[test]
+fn exports_with_types() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (memory (export "memory") 2)
+                (func (export "add") (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1)))
+                (table (export "table") 3 10 funcref)
+                (global (export "global") (mut i64) (i64.const 0)))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    let exports = instance
+        .exports_with_types(&mut store)
+        .map(|(export, ty)| (export.name().to_string(), ty))
+        .collect::<Vec<_>>();
+    assert_eq!(exports.len(), 4);
+    assert_eq!(exports[0].0, "memory");
+    assert_eq!(exports[0].1.memory().unwrap().minimum(), 2);
+    assert_eq!(exports[1].0, "add");
+    let add = exports[1].1.func().unwrap();
+    assert_eq!(
+        add.params().collect::<Vec<_>>(),
+        [ValType::I32, ValType::I32]
+    );
+    assert_eq!(add.results().collect::<Vec<_>>(), [ValType::I32]);
+    assert_eq!(exports[2].0, "table");
+    let table = exports[2].1.table().unwrap();
+    assert_eq!((table.minimum(), table.maximum()), (3, Some(10)));
+    assert_eq!(exports[3].0, "global");
+    assert_eq!(exports[3].1.global().unwrap().mutability(), Mutability::Var);
+    Ok(())
+}
+
+#[test]
+fn get_module_export() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"(module (func (export "a") (result i32) i32.const 1) (memory (export "b") 1))"#,
+    )?;
+    let a = module.get_export_index("a").unwrap();
+    let b = module.get_export_index("b").unwrap();
+    assert_eq!(a.name(), "a");
+    assert!(module.get_export_index("c").is_none());
+
+    for _ in 0..2 {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let func = instance.get_module_export(&mut store, &a).unwrap();
+        let func = func.into_func().unwrap().typed::<(), i32, _>(&store)?;
+        assert_eq!(func.call(&mut store, ())?, 1);
+        assert!(instance
+            .get_module_export(&mut store, &b)
+            .unwrap()
+            .into_memory()
+            .is_some());
+    }
+
+    // Exports found in other modules aren't exports of this one, even when
+    // they're at the same position.
+    let other = Module::new(&engine, r#"(module (func (export "x")))"#)?;
+    let x = other.get_export_index("x").unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    assert!(instance.get_module_export(&mut store, &x).is_none());
+    let same = Module::new(
+        &engine,
+        r#"(module (func (export "a") (result i32) i32.const 2))"#,
+    )?;
+    let same = same.get_export_index("a").unwrap();
+    assert!(instance.get_module_export(&mut store, &same).is_some());
+    Ok(())
+}
+
+#[test]
+fn get_typed_module_export() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "add") (param i32 i32) (result i32)
+                    (i32.add (local.get 0) (local.get 1)))
+                (memory (export "memory") 1))
+        "#,
+    )?;
+    let add = module.get_typed_func_export::<(i32, i32), i32>("add")?;
+    assert_eq!(add.name(), "add");
+    assert!(module.get_typed_func_export::<(), ()>("add").is_err());
+    assert!(module.get_typed_func_export::<(), ()>("memory").is_err());
+    assert!(module.get_typed_func_export::<(), ()>("missing").is_err());
+
+    for i in 0..2 {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let func = instance.get_typed_module_export(&mut store, &add).unwrap();
+        assert_eq!(func.call(&mut store, (i, 2))?, i + 2);
+    }
+
+    // A function at the same position in another module is only found if it
+    // has the same type.
+    let mut store = Store::new(&engine, ());
+    let other = Module::new(
+        &engine,
+        r#"(module (func (export "add") (param i64) (result i64) local.get 0))"#,
+    )?;
+    let instance = Instance::new(&mut store, &other, &[])?;
+    assert!(instance.get_typed_module_export(&mut store, &add).is_none());
+    let same = Module::new(
+        &engine,
+        r#"(module (func (export "add") (param i32 i32) (result i32) local.get 0))"#,
+    )?;
+    let instance = Instance::new(&mut store, &same, &[])?;
+    let func = instance.get_typed_module_export(&mut store, &add).unwrap();
+    assert_eq!(func.call(&mut store, (3, 2))?, 3);
+    Ok(())
+}
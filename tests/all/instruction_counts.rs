@@ -0,0 +1,109 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn counts_per_function() -> Result<()> {
+    let mut config = Config::new();
+    config.count_instructions(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $inc (export "inc") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1
+                    i32.add)
+                (func (export "call-inc") (result i32)
+                    i32.const 2
+                    call $inc)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let inc = instance.get_typed_func::<i32, i32, _>(&mut store, "inc")?;
+    let call_inc = instance.get_typed_func::<(), i32, _>(&mut store, "call-inc")?;
+    assert!(store.instruction_counts(&module).is_none());
+
+    // Each call of `inc` executes its three instructions and its `end`.
+    inc.call(&mut store, 1)?;
+    inc.call(&mut store, 2)?;
+    let counts = store.instruction_counts(&module).unwrap();
+    assert_eq!(counts.function(0), 8);
+    assert_eq!(counts.total(), 8);
+    assert_eq!(
+        counts
+            .block_hits()
+            .map(|(_, hits)| hits)
+            .collect::<Vec<_>>(),
+        [2]
+    );
+
+    // The call splits `call-inc` into two blocks.
+    store.reset_instruction_counts();
+    assert_eq!(call_inc.call(&mut store, ())?, 3);
+    let counts = store.instruction_counts(&module).unwrap();
+    assert_eq!(counts.functions().collect::<Vec<_>>(), [(0, 4), (1, 3)]);
+    assert_eq!(counts.block_hits().count(), 3);
+    Ok(())
+}
+
+#[test]
+fn counts_loop_iterations() -> Result<()> {
+    let mut config = Config::new();
+    config.count_instructions(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "countdown") (param i32)
+                    (loop $l
+                        local.get 0
+                        i32.const 1
+                        i32.sub
+                        local.tee 0
+                        br_if $l))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let countdown = instance.get_typed_func::<i32, (), _>(&mut store, "countdown")?;
+
+    // The `loop`, five instructions per iteration, and then the `end`s of the
+    // loop and the function.
+    countdown.call(&mut store, 3)?;
+    let counts = store.instruction_counts(&module).unwrap();
+    assert_eq!(counts.function(0), 18);
+    let hits = counts
+        .block_hits()
+        .map(|(_, hits)| hits)
+        .collect::<Vec<_>>();
+    assert_eq!(hits, [1, 3, 1, 1]);
+
+    // Counts are deterministic and shared by all instances of the module.
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let countdown2 = instance.get_typed_func::<i32, (), _>(&mut store, "countdown")?;
+    countdown2.call(&mut store, 3)?;
+    assert_eq!(store.instruction_counts(&module).unwrap().function(0), 36);
+    Ok(())
+}
+
+#[test]
+fn serialized_modules_require_same_setting() -> Result<()> {
+    let module = Module::new(&Engine::default(), "(module)")?;
+    let bytes = module.serialize()?;
+    let mut config = Config::new();
+    config.count_instructions(true);
+    let err = unsafe { Module::deserialize(&Engine::new(&config)?, &bytes) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("instruction counting"),
+        "bad error: {:?}",
+        err
+    );
+    Ok(())
+}
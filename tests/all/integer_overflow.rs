@@ -0,0 +1,156 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (func (export "add32") (param i32 i32) (result i32)
+            (i32.add (local.get 0) (local.get 1)))
+        (func (export "sub32") (param i32 i32) (result i32)
+            (i32.sub (local.get 0) (local.get 1)))
+        (func (export "mul32") (param i32 i32) (result i32)
+            (i32.mul (local.get 0) (local.get 1)))
+        (func (export "add64") (param i64 i64) (result i64)
+            (i64.add (local.get 0) (local.get 1)))
+        (func (export "sub64") (param i64 i64) (result i64)
+            (i64.sub (local.get 0) (local.get 1)))
+        (func (export "mul64") (param i64 i64) (result i64)
+            (i64.mul (local.get 0) (local.get 1)))
+        (func $hash (export "hash") (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 0x9e3779b9)))
+    )
+"#;
+
+fn engine(allow: &[&str]) -> Result<Engine> {
+    let mut config = Config::new();
+    config.check_integer_overflow(true);
+    for name in allow {
+        config.allow_integer_overflow(*name);
+    }
+    Engine::new(&config)
+}
+
+fn instantiate(engine: &Engine) -> Result<(Store<()>, Instance)> {
+    let module = Module::new(engine, WAT)?;
+    let mut store = Store::new(engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    Ok((store, instance))
+}
+
+#[test]
+fn overflow_traps() -> Result<()> {
+    let (mut store, instance) = instantiate(&engine(&[])?)?;
+    let add32 = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add32")?;
+    let sub32 = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "sub32")?;
+    let mul32 = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "mul32")?;
+    let add64 = instance.get_typed_func::<(i64, i64), i64, _>(&mut store, "add64")?;
+    let sub64 = instance.get_typed_func::<(i64, i64), i64, _>(&mut store, "sub64")?;
+    let mul64 = instance.get_typed_func::<(i64, i64), i64, _>(&mut store, "mul64")?;
+
+    assert_eq!(add32.call(&mut store, (i32::MAX - 1, 1))?, i32::MAX);
+    assert_eq!(add32.call(&mut store, (-1, i32::MIN + 1))?, i32::MIN);
+    assert_eq!(sub32.call(&mut store, (-1, i32::MAX))?, i32::MIN);
+    assert_eq!(mul32.call(&mut store, (-0x8000, 0x10000))?, i32::MIN);
+    assert_eq!(add64.call(&mut store, (i64::MIN, i64::MAX))?, -1);
+    assert_eq!(sub64.call(&mut store, (0, i64::MAX))?, i64::MIN + 1);
+    assert_eq!(mul64.call(&mut store, (-1, i64::MAX))?, -i64::MAX);
+
+    let traps = [
+        add32.call(&mut store, (i32::MAX, 1)).unwrap_err(),
+        add32.call(&mut store, (i32::MIN, -1)).unwrap_err(),
+        sub32.call(&mut store, (i32::MIN, 1)).unwrap_err(),
+        sub32.call(&mut store, (0, i32::MIN)).unwrap_err(),
+        mul32.call(&mut store, (0x10000, 0x8000)).unwrap_err(),
+        mul32.call(&mut store, (-1, i32::MIN)).unwrap_err(),
+        add64.call(&mut store, (i64::MAX, 1)).unwrap_err(),
+        sub64.call(&mut store, (i64::MIN, 1)).unwrap_err(),
+        mul64.call(&mut store, (1 << 32, 1 << 31)).unwrap_err(),
+        mul64.call(&mut store, (-1, i64::MIN)).unwrap_err(),
+    ];
+    for trap in traps {
+        assert_eq!(trap.trap_code(), Some(TrapCode::IntegerOverflow));
+    }
+    Ok(())
+}
+
+#[test]
+fn overflow_callback() -> Result<()> {
+    let (mut store, instance) = instantiate(&engine(&[])?)?;
+    let overflows = Arc::new(Mutex::new(Vec::new()));
+    let reported = overflows.clone();
+    store.integer_overflow_callback(move |_, overflow| {
+        reported.lock().unwrap().push(overflow.clone());
+        if overflow.kind() == IntegerOverflowKind::Sub {
+            anyhow::bail!("subtraction overflowed");
+        }
+        Ok(())
+    });
+
+    // Overflows the callback returns from continue with the wrapped result.
+    let add32 = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add32")?;
+    assert_eq!(add32.call(&mut store, (i32::MAX, 2))?, i32::MIN + 1);
+    let mul64 = instance.get_typed_func::<(i64, i64), i64, _>(&mut store, "mul64")?;
+    assert_eq!(mul64.call(&mut store, (i64::MAX, 2))?, -2);
+
+    let sub32 = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "sub32")?;
+    let trap = sub32.call(&mut store, (i32::MIN, 1)).unwrap_err();
+    assert!(
+        trap.to_string().contains("subtraction overflowed"),
+        "{}",
+        trap
+    );
+
+    let overflows = overflows.lock().unwrap();
+    assert_eq!(overflows.len(), 3);
+    assert_eq!(overflows[0].kind(), IntegerOverflowKind::Add);
+    assert_eq!(overflows[0].ty(), ValType::I32);
+    assert_eq!(overflows[0].func_index(), 0);
+    assert_eq!(
+        (overflows[0].lhs(), overflows[0].rhs()),
+        (i32::MAX.into(), 2)
+    );
+    assert_eq!(overflows[1].kind(), IntegerOverflowKind::Mul);
+    assert_eq!(overflows[1].ty(), ValType::I64);
+    assert_eq!(overflows[1].func_index(), 5);
+    assert_eq!((overflows[1].lhs(), overflows[1].rhs()), (i64::MAX, 2));
+    assert_eq!(overflows[2].kind(), IntegerOverflowKind::Sub);
+    assert_eq!(
+        (overflows[2].lhs(), overflows[2].rhs()),
+        (i32::MIN.into(), 1)
+    );
+    Ok(())
+}
+
+#[test]
+fn allowed_functions_wrap() -> Result<()> {
+    let (mut store, instance) = instantiate(&engine(&[])?)?;
+    let hash = instance.get_typed_func::<i32, i32, _>(&mut store, "hash")?;
+    let trap = hash.call(&mut store, 2).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::IntegerOverflow));
+
+    let (mut store, instance) = instantiate(&engine(&["hash"])?)?;
+    let hash = instance.get_typed_func::<i32, i32, _>(&mut store, "hash")?;
+    assert_eq!(
+        hash.call(&mut store, 2)?,
+        0x9e3779b9_u32.wrapping_mul(2) as i32
+    );
+    let add32 = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "add32")?;
+    assert!(add32.call(&mut store, (i32::MAX, 1)).is_err());
+    Ok(())
+}
+
+#[test]
+fn checks_are_part_of_compatibility() -> Result<()> {
+    let bytes = engine(&[])?.precompile_module(WAT.as_bytes())?;
+    match unsafe { Module::deserialize(&Engine::default(), &bytes) } {
+        Ok(_) => panic!("module compiled with overflow checks was deserialized"),
+        Err(e) => assert!(
+            format!("{:?}", e).contains("integer overflow checks"),
+            "{:?}",
+            e
+        ),
+    }
+    assert!(unsafe { Module::deserialize(&engine(&["hash"])?, &bytes) }.is_err());
+    unsafe { Module::deserialize(&engine(&[])?, &bytes)? };
+    Ok(())
+}
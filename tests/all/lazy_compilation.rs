@@ -0,0 +1,192 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn functions_compile_on_first_call() -> Result<()> {
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $double (param i32) (result i32)
+                    local.get 0
+                    i32.const 2
+                    i32.mul)
+                (func (export "quadruple") (param i32) (result i32)
+                    local.get 0
+                    call $double
+                    call $double)
+                (func (export "unused") (param i32) (result i32)
+                    local.get 0
+                    i32.const 1000
+                    i32.div_u
+                    i32.const 1000
+                    i32.rem_u)
+            )
+        "#,
+    )?;
+    let stubs = engine.metrics().code_bytes();
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let quadruple = instance.get_typed_func::<i32, i32, _>(&mut store, "quadruple")?;
+    assert_eq!(engine.metrics().code_bytes(), stubs);
+    assert_eq!(quadruple.call(&mut store, 3)?, 12);
+    let compiled = engine.metrics().code_bytes();
+    assert!(compiled > stubs);
+    assert_eq!(module.code_size(), compiled);
+
+    // Functions are only compiled once, even across instances.
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let quadruple = instance.get_typed_func::<i32, i32, _>(&mut store, "quadruple")?;
+    assert_eq!(quadruple.call(&mut store, 5)?, 20);
+    assert_eq!(engine.metrics().code_bytes(), compiled);
+
+    let unused = instance.get_typed_func::<i32, i32, _>(&mut store, "unused")?;
+    assert_eq!(unused.call(&mut store, 123_456)?, 123);
+    assert!(engine.metrics().code_bytes() > compiled);
+
+    drop(store);
+    drop(module);
+    assert_eq!(engine.metrics().code_bytes(), 0);
+    Ok(())
+}
+
+#[test]
+fn calls_through_tables() -> Result<()> {
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (type $t (func (result i32)))
+                (table (export "table") 2 funcref)
+                (elem (i32.const 0) $one $two)
+                (func $one (result i32) i32.const 1)
+                (func $two (result i32) i32.const 2)
+                (func (export "call") (param i32) (result i32)
+                    local.get 0
+                    call_indirect (type $t))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let call = instance.get_typed_func::<i32, i32, _>(&mut store, "call")?;
+    assert_eq!(call.call(&mut store, 1)?, 2);
+    assert_eq!(call.call(&mut store, 0)?, 1);
+
+    let table = instance.get_table(&mut store, "table").unwrap();
+    let one = table.get(&mut store, 0).unwrap();
+    let one = one.funcref().unwrap().unwrap();
+    assert_eq!(one.typed::<(), i32, _>(&store)?.call(&mut store, ())?, 1);
+    Ok(())
+}
+
+#[test]
+fn traps_have_the_same_backtrace() -> Result<()> {
+    let wat = r#"
+        (module $m
+            (func $start (export "start") (param i32)
+                local.get 0
+                call $middle)
+            (func $middle (param i32)
+                local.get 0
+                br_if 0
+                unreachable)
+        )
+    "#;
+
+    let backtrace = |engine: &Engine| -> Result<Vec<_>> {
+        let module = Module::new(engine, wat)?;
+        let mut store = Store::new(engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let start = instance.get_typed_func::<i32, (), _>(&mut store, "start")?;
+        start.call(&mut store, 1)?;
+        let trap = start.call(&mut store, 0).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+        Ok(trap
+            .trace()
+            .iter()
+            .map(|f| {
+                (
+                    f.func_index(),
+                    f.func_name().map(|s| s.to_string()),
+                    f.module_offset(),
+                )
+            })
+            .collect())
+    };
+
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let lazy = backtrace(&Engine::new(&config)?)?;
+    assert_eq!(lazy.len(), 2, "{:?}", lazy);
+    assert_eq!(lazy[0].1.as_deref(), Some("middle"));
+    assert_eq!(lazy[1].1.as_deref(), Some("start"));
+    assert_eq!(lazy, backtrace(&Engine::default())?);
+    Ok(())
+}
+
+#[test]
+fn externrefs_survive_gc() -> Result<()> {
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    config.wasm_reference_types(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "gc" (func $gc))
+                (func (export "run") (param externref) (result externref)
+                    call $gc
+                    local.get 0)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let gc = Func::wrap(&mut store, |mut caller: Caller<'_, ()>| caller.gc());
+    let instance = Instance::new(&mut store, &module, &[gc.into()])?;
+    let run =
+        instance.get_typed_func::<Option<ExternRef>, Option<ExternRef>, _>(&mut store, "run")?;
+    let result = run.call(&mut store, Some(ExternRef::new(7u32)))?.unwrap();
+    assert_eq!(result.data().downcast_ref::<u32>(), Some(&7));
+    Ok(())
+}
+
+#[test]
+fn unsupported_combinations() -> Result<()> {
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, "(module (func))")?;
+    let err = module.serialize().unwrap_err();
+    assert!(
+        err.to_string().contains("compiled lazily"),
+        "bad error: {}",
+        err
+    );
+
+    config.debug_info(true);
+    let err = Engine::new(&config).err().unwrap();
+    assert!(
+        err.to_string().contains("lazy compilation"),
+        "bad error: {}",
+        err
+    );
+
+    // Modules compiled up front can still be used with lazy compilation.
+    let bytes = Module::new(&Engine::default(), "(module (func (export \"f\")))")?.serialize()?;
+    let module = unsafe { Module::deserialize(&engine, &bytes)? };
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance
+        .get_typed_func::<(), (), _>(&mut store, "f")?
+        .call(&mut store, ())?;
+    Ok(())
+}
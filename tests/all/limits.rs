@@ -236,6 +236,73 @@ fn test_limits_memory_only() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_total_memory_limit() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, r#"(module (memory (export "m") 1))"#)?;
+
+    let mut store = Store::new(
+        &engine,
+        StoreLimitsBuilder::new()
+            .total_memory_size(3 * WASM_PAGE_SIZE)
+            .build(),
+    );
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+
+    let first = Instance::new(&mut store, &module, &[])?;
+    Instance::new(&mut store, &module, &[])?;
+    let memory = first.get_memory(&mut store, "m").unwrap();
+    memory.grow(&mut store, 1)?;
+    assert!(memory.grow(&mut store, 1).is_err());
+    assert!(Memory::new(&mut store, MemoryType::new(1, None)).is_err());
+
+    match Instance::new(&mut store, &module, &[]) {
+        Ok(_) => unreachable!(),
+        Err(e) => assert_eq!(
+            e.to_string(),
+            "Insufficient resources: memory minimum size of 1 pages exceeds memory limits"
+        ),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_store_limits_usage() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"(module (memory (export "m") 1) (table (export "t") 2 funcref))"#,
+    )?;
+
+    let mut store = Store::new(
+        &engine,
+        StoreLimitsBuilder::new()
+            .total_memory_size(4 * WASM_PAGE_SIZE)
+            .build(),
+    );
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    assert_eq!(store.data().memory_allocated(), 0);
+    assert_eq!(store.data().memory_remaining(), Some(4 * WASM_PAGE_SIZE));
+    assert_eq!(store.data().table_elements_allocated(), 0);
+
+    let instance = Instance::new(&mut store, &module, &[])?;
+    assert_eq!(store.data().memory_allocated(), WASM_PAGE_SIZE);
+    assert_eq!(store.data().table_elements_allocated(), 2);
+
+    let memory = instance.get_memory(&mut store, "m").unwrap();
+    memory.grow(&mut store, 2)?;
+    let table = instance.get_table(&mut store, "t").unwrap();
+    table.grow(&mut store, 3, Val::FuncRef(None))?;
+    assert_eq!(store.data().memory_allocated(), 3 * WASM_PAGE_SIZE);
+    assert_eq!(store.data().memory_remaining(), Some(WASM_PAGE_SIZE));
+    assert_eq!(store.data().table_elements_allocated(), 5);
+
+    let store = Store::new(&engine, StoreLimitsBuilder::new().build());
+    assert_eq!(store.data().memory_remaining(), None);
+    Ok(())
+}
+
 #[test]
 fn test_initial_memory_limits_exceeded() -> Result<()> {
     let engine = Engine::default();
@@ -796,6 +863,171 @@ fn custom_limiter_detect_grow_failure() -> Result<()> {
     Ok(())
 }
 
+#[derive(Default)]
+struct GrowthTracker {
+    /// Arguments of every call to memory_grown
+    memory: Vec<(usize, usize)>,
+    /// Arguments of every call to table_grown
+    table: Vec<(u32, u32)>,
+}
+
+impl ResourceLimiter for GrowthTracker {
+    fn memory_growing(
+        &mut self,
+        _current: usize,
+        _desired: usize,
+        _maximum: Option<usize>,
+    ) -> bool {
+        true
+    }
+    fn memory_grown(&mut self, old: usize, new: usize) {
+        self.memory.push((old, new));
+    }
+    fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+        true
+    }
+    fn table_grown(&mut self, old: u32, new: u32) {
+        self.table.push((old, new));
+    }
+}
+
+#[test]
+fn custom_limiter_observes_growth() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "m") 1 3)
+                (table (export "t") 0 2 funcref)
+                (func (export "grow") (param i32) (result i32)
+                    (drop (table.grow (ref.null func) (local.get 0)))
+                    (memory.grow (local.get 0)))
+            )
+        "#,
+    )?;
+
+    let mut store = Store::new(&engine, GrowthTracker::default());
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let grow = instance.get_typed_func::<i32, i32, _>(&mut store, "grow")?;
+    let memory = instance.get_memory(&mut store, "m").unwrap();
+
+    assert_eq!(grow.call(&mut store, 1)?, 1);
+    memory.grow(&mut store, 1)?;
+    assert_eq!(
+        store.data().memory,
+        [(0, 0x10000), (0x10000, 0x20000), (0x20000, 0x30000)]
+    );
+    assert_eq!(store.data().table, [(0, 1)]);
+
+    // Creating the empty table, growing by zero and failing to grow past the
+    // maximum aren't reported.
+    assert_eq!(grow.call(&mut store, 0)?, 3);
+    assert_eq!(grow.call(&mut store, 2)?, -1);
+    assert_eq!(store.data().memory.len(), 3);
+    assert_eq!(store.data().table.len(), 1);
+
+    Ok(())
+}
+
+/// Permits memories to grow up to `budget` bytes.
+struct Budget {
+    budget: usize,
+    /// The current and desired sizes of every failure to grow a memory.
+    failures: Vec<(usize, usize)>,
+}
+
+impl ResourceLimiter for Budget {
+    fn memory_growing(&mut self, _current: usize, desired: usize, _maximum: Option<usize>) -> bool {
+        desired <= self.budget
+    }
+    fn table_growing(&mut self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+        true
+    }
+}
+
+fn budget_store(engine: &Engine, pages: usize) -> Result<(Store<Budget>, TypedFunc<i32, i32>)> {
+    let module = Module::new(
+        engine,
+        r#"
+            (module
+                (memory (export "m") 1 4)
+                (func (export "grow") (param i32) (result i32)
+                    (memory.grow (local.get 0)))
+            )
+        "#,
+    )?;
+    let budget = Budget {
+        budget: pages * WASM_PAGE_SIZE,
+        failures: Vec::new(),
+    };
+    let mut store = Store::new(engine, budget);
+    store.limiter(|s| s as &mut dyn ResourceLimiter);
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let grow = instance.get_typed_func::<i32, i32, _>(&mut store, "grow")?;
+    Ok((store, grow))
+}
+
+#[test]
+fn memory_grow_failure_trap() -> Result<()> {
+    let engine = Engine::default();
+    let (mut store, grow) = budget_store(&engine, 2)?;
+    assert_eq!(grow.call(&mut store, 1)?, 1);
+    assert_eq!(grow.call(&mut store, 1)?, -1);
+
+    store.memory_grow_failure_trap();
+    let trap = grow.call(&mut store, 1).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryGrowFailed));
+    let memory = Memory::new(&mut store, MemoryType::new(0, None))?;
+    let error = memory.grow(&mut store, 3).unwrap_err();
+    assert_eq!(
+        error.downcast::<Trap>()?.trap_code(),
+        Some(TrapCode::MemoryGrowFailed)
+    );
+
+    // Growing past the maximum is no failure to allocate.
+    assert_eq!(grow.call(&mut store, 10)?, -1);
+    Ok(())
+}
+
+#[test]
+fn memory_grow_failure_callback() -> Result<()> {
+    let engine = Engine::default();
+    let (mut store, grow) = budget_store(&engine, 1)?;
+    store.memory_grow_failure_callback(|budget, failure| {
+        assert!(failure.error().is_none());
+        budget.failures.push((failure.current(), failure.desired()));
+        match failure.desired() / WASM_PAGE_SIZE {
+            2 => {
+                budget.budget = failure.desired();
+                Ok(true)
+            }
+            3 => Ok(false),
+            _ => anyhow::bail!("no budget for {} bytes", failure.desired()),
+        }
+    });
+
+    // The first failure frees enough to retry successfully, the second
+    // returns failure and the third traps.
+    assert_eq!(grow.call(&mut store, 1)?, 1);
+    assert_eq!(grow.call(&mut store, 1)?, -1);
+    let trap = grow.call(&mut store, 2).unwrap_err();
+    assert!(
+        trap.to_string().contains("no budget for 262144 bytes"),
+        "{}",
+        trap
+    );
+    assert_eq!(grow.call(&mut store, 10)?, -1);
+
+    let page = WASM_PAGE_SIZE;
+    assert_eq!(
+        store.data().failures,
+        [(page, 2 * page), (2 * page, 3 * page), (2 * page, 4 * page)]
+    );
+    Ok(())
+}
+
 #[async_trait::async_trait]
 impl ResourceLimiterAsync for FailureDetector {
     async fn memory_growing(
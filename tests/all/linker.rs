@@ -340,3 +340,111 @@ fn instance_pre() -> Result<()> {
     instance_pre.instantiate(&mut store)?;
     Ok(())
 }
+
+#[test]
+fn host_funcs_shared_across_many_stores() -> Result<()> {
+    struct DropMe(Arc<AtomicUsize>);
+
+    impl Drop for DropMe {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, SeqCst);
+        }
+    }
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let drops = Arc::new(AtomicUsize::new(0));
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    let drop_me = DropMe(drops.clone());
+    let calls2 = calls.clone();
+    linker.func_wrap("", "", move || {
+        let _ = &drop_me;
+        calls2.fetch_add(1, SeqCst);
+    })?;
+
+    let module = Module::new(&engine, r#"(module (import "" "" (func $f)) (start $f))"#)?;
+    let instance_pre = linker.instantiate_pre(&mut Store::new(&engine, ()), &module)?;
+    drop(linker);
+
+    // Every store calls the one closure defined in the linker, which lives on
+    // until the last `InstancePre` referring to it is gone.
+    for _ in 0..1000 {
+        instance_pre.instantiate(&mut Store::new(&engine, ()))?;
+    }
+    assert_eq!(calls.load(SeqCst), 1000);
+    assert_eq!(drops.load(SeqCst), 0);
+    drop(instance_pre);
+    assert_eq!(drops.load(SeqCst), 1);
+    Ok(())
+}
+
+#[test]
+fn import_resolver() -> Result<()> {
+    let engine = Engine::default();
+    let mut linker = Linker::<Vec<String>>::new(&engine);
+    linker.func_wrap("host", "defined", || 1)?;
+    linker.import_resolver(
+        |mut store: StoreContextMut<'_, Vec<String>>, import: &ImportType<'_>| {
+            store.data_mut().push(import.name().to_string());
+            match (import.module(), import.name()) {
+                ("host", "denied") => anyhow::bail!("not allowed"),
+                ("host", "unknown") => Ok(None),
+                ("host", "global") => {
+                    let ty = GlobalType::new(ValType::I32, Mutability::Const);
+                    Ok(Some(Global::new(&mut store, ty, Val::I32(7))?.into()))
+                }
+                (_, name) => {
+                    let ret = name.len() as i32;
+                    Ok(Some(Func::wrap(&mut store, move || ret).into()))
+                }
+            }
+        },
+    );
+
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "host" "defined" (func $defined (result i32)))
+                (import "host" "resolved" (func $resolved (result i32)))
+                (import "host" "global" (global $global i32))
+                (func (export "run") (result i32)
+                    (i32.add
+                        (i32.add (call $defined) (call $resolved))
+                        (global.get $global))))
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    let instance = linker.instantiate(&mut store, &module)?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 1 + 8 + 7);
+    // Items defined in the linker aren't resolved.
+    assert_eq!(*store.data(), ["resolved", "global"]);
+
+    let module = Module::new(&engine, r#"(module (import "host" "denied" (func)))"#)?;
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("not allowed"),
+        "bad error: {:?}",
+        err
+    );
+
+    let module = Module::new(&engine, r#"(module (import "host" "unknown" (func)))"#)?;
+    let err = linker.instantiate(&mut store, &module).unwrap_err();
+    assert!(
+        err.to_string().contains("unknown import"),
+        "bad error: {}",
+        err
+    );
+
+    // Resolved items are type-checked against the import.
+    let module = Module::new(
+        &engine,
+        r#"(module (import "host" "x" (func (param i32))))"#,
+    )?;
+    assert!(linker.instantiate(&mut store, &module).is_err());
+
+    let import = module.imports().next().unwrap();
+    assert!(linker.get_by_import(&mut store, &import).is_some());
+    Ok(())
+}
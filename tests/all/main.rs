@@ -1,34 +1,75 @@
+mod address_map;
 mod async_functions;
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+mod baseline;
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+mod branch_protection;
 mod call_hook;
+mod call_indirect_cache;
 mod cli_tests;
+mod common;
+mod compilation_hook;
+mod compilation_report;
+mod coredump;
+mod cpu_time;
+mod custom_compiler;
 mod custom_signal_handler;
 mod debug;
+mod deterministic;
+mod devirtualize_imports;
+mod disabled_features;
 mod epoch_interruption;
+mod execution_profile;
 mod externals;
 mod fuel;
 mod func;
 mod funcref;
 mod gc;
 mod globals;
+mod guest_debug;
+mod guest_profiler;
 mod host_funcs;
+#[cfg(target_os = "linux")]
+mod huge_pages;
 mod iloop;
 mod import_calling_export;
 mod import_indexes;
 mod instance;
+mod instruction_counts;
+mod integer_overflow;
 mod invoke_func_via_table;
+mod lazy_compilation;
 mod limits;
 mod linker;
 mod memory;
 mod memory_creator;
+mod memory_tracing;
+mod metrics;
 mod module;
 mod module_serialize;
+mod module_stream;
 mod name;
+mod native_backtrace;
+mod perfmap;
+mod pinned_context;
 mod pooling_allocator;
+mod preinitialize;
 mod relocs;
+mod sandbox_policy;
+mod signals_based_traps;
+mod snapshot;
 mod stack_overflow;
 mod store;
 mod table;
+#[cfg(all(target_arch = "x86_64", not(windows)))]
+mod tiered_compilation;
 mod traps;
+mod unreachable_functions;
+mod wasi_fs;
+#[cfg(feature = "wasi-nn")]
+mod wasi_nn;
+mod wasi_overrides;
+mod wasi_stdio;
 mod wast;
 
 /// A helper to compile a module in a new store with reference types enabled.
@@ -1,6 +1,7 @@
 use anyhow::Result;
 use rayon::prelude::*;
 use wasmtime::*;
+use wasmtime_wasi::sync::WasiCtxBuilder;
 
 fn module(engine: &Engine) -> Result<Module> {
     let mut wat = format!("(module\n");
@@ -468,3 +469,368 @@ fn memory64_maximum_minimum() -> Result<()> {
 
     Ok(())
 }
+
+const READ_ONLY: &str = r#"
+    (module
+        (import "" "" (memory $imported 2))
+        (memory $defined (export "memory") 3)
+        (func (export "load") (param i32) (result i32)
+            (i32.load $defined (local.get 0)))
+        (func (export "store") (param i32)
+            (i32.store $defined (local.get 0) (i32.const 1)))
+        (func (export "fill") (param i32 i32)
+            (memory.fill $defined (local.get 0) (i32.const 1) (local.get 1)))
+        (func (export "copy") (param i32)
+            (memory.copy $defined $defined (local.get 0) (i32.const 0) (i32.const 4)))
+        (func (export "fill_imported") (param i32)
+            (memory.fill $imported (local.get 0) (i32.const 1) (i32.const 4)))
+        (func (export "grow") (result i32)
+            (memory.grow $defined (i32.const 1)))
+    )
+"#;
+
+fn read_only_instance(store: &mut Store<()>) -> Result<(Instance, Memory, Memory)> {
+    let module = Module::new(store.engine(), READ_ONLY)?;
+    let imported = Memory::new(&mut *store, MemoryType::new(2, None))?;
+    let instance = Instance::new(&mut *store, &module, &[imported.into()])?;
+    let defined = instance.get_memory(&mut *store, "memory").unwrap();
+    Ok((instance, defined, imported))
+}
+
+fn multi_memory_engine(config: &mut Config) -> Result<Engine> {
+    config.wasm_multi_memory(true);
+    Engine::new(config)
+}
+
+#[test]
+fn read_only_ranges() -> Result<()> {
+    let engine = multi_memory_engine(&mut Config::new())?;
+    let mut store = Store::new(&engine, ());
+    let (instance, memory, imported) = read_only_instance(&mut store)?;
+    memory.write(&mut store, 65536, &[7])?;
+    memory.protect_read_only(&mut store, 65536..131072)?;
+    imported.protect_read_only(&mut store, 0..65536)?;
+    let ranges = memory.read_only_ranges(&mut store);
+    assert_eq!(ranges.len(), 1);
+    assert_eq!(ranges[0], 65536..131072);
+
+    let load = instance.get_typed_func::<i32, i32, _>(&mut store, "load")?;
+    let store_ = instance.get_typed_func::<i32, (), _>(&mut store, "store")?;
+    let fill = instance.get_typed_func::<(i32, i32), (), _>(&mut store, "fill")?;
+    let copy = instance.get_typed_func::<i32, (), _>(&mut store, "copy")?;
+    let fill_imported = instance.get_typed_func::<i32, (), _>(&mut store, "fill_imported")?;
+    let assert_traps = |result: Result<(), Trap>| {
+        assert_eq!(
+            result.unwrap_err().trap_code(),
+            Some(TrapCode::MemoryOutOfBounds)
+        );
+    };
+
+    // Reads of the range and writes around it still work.
+    assert_eq!(load.call(&mut store, 65536)?, 7);
+    store_.call(&mut store, 65532)?;
+    store_.call(&mut store, 131072)?;
+    fill.call(&mut store, (65536, 0))?;
+    fill_imported.call(&mut store, 65536)?;
+
+    assert_traps(store_.call(&mut store, 65536));
+    assert_traps(store_.call(&mut store, 131068));
+    assert_traps(fill.call(&mut store, (65534, 4)));
+    assert_traps(copy.call(&mut store, 100000));
+    assert_traps(fill_imported.call(&mut store, 0));
+    assert!(memory.write(&mut store, 65535, &[0, 0]).is_err());
+    assert!(memory.try_data_mut(&mut store).is_err());
+    assert_eq!(memory.data(&store)[65536..65540], [7, 0, 0, 0]);
+    Ok(())
+}
+
+#[test]
+fn read_only_ranges_invalid() -> Result<()> {
+    let engine = multi_memory_engine(&mut Config::new())?;
+    let mut store = Store::new(&engine, ());
+    let (_, memory, _) = read_only_instance(&mut store)?;
+    assert!(memory.protect_read_only(&mut store, 1..65536).is_err());
+    assert!(memory.protect_read_only(&mut store, 0..65537).is_err());
+    assert!(memory
+        .protect_read_only(&mut store, 3 * 65536..4 * 65536)
+        .is_err());
+    memory.protect_read_only(&mut store, 0..0)?;
+    assert!(memory.read_only_ranges(&mut store).is_empty());
+
+    let mut config = Config::new();
+    config.signals_based_traps(false);
+    let engine = multi_memory_engine(&mut config)?;
+    let mut store = Store::new(&engine, ());
+    let (_, memory, _) = read_only_instance(&mut store)?;
+    assert!(memory.protect_read_only(&mut store, 0..65536).is_err());
+    Ok(())
+}
+
+#[test]
+fn read_only_ranges_survive_growth() -> Result<()> {
+    // Dynamic memories without any room to grow into are moved by growing.
+    let mut config = Config::new();
+    config
+        .static_memory_maximum_size(0)
+        .dynamic_memory_reserved_for_growth(0);
+    let engine = multi_memory_engine(&mut config)?;
+    let mut store = Store::new(&engine, ());
+    let (instance, memory, _) = read_only_instance(&mut store)?;
+    memory.protect_read_only(&mut store, 0..65536)?;
+    let base = memory.data_ptr(&store);
+
+    let grow = instance.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    assert_eq!(grow.call(&mut store, ())?, 3);
+    assert_ne!(memory.data_ptr(&store), base);
+
+    let store_ = instance.get_typed_func::<i32, (), _>(&mut store, "store")?;
+    let trap = store_.call(&mut store, 0).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+    store_.call(&mut store, 3 * 65536)?;
+    Ok(())
+}
+
+#[test]
+fn read_only_ranges_pooling() -> Result<()> {
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::default(),
+        instance_limits: InstanceLimits {
+            count: 1,
+            memory_pages: 10,
+            ..Default::default()
+        },
+    });
+    let engine = multi_memory_engine(&mut config)?;
+    let module = Module::new(&engine, READ_ONLY)?;
+
+    // The memory's slot is reused by the second instance, which mustn't
+    // inherit the first one's protection.
+    for _ in 0..2 {
+        let mut store = Store::new(&engine, ());
+        let imported = Memory::new(&mut store, MemoryType::new(2, None))?;
+        let instance = Instance::new(&mut store, &module, &[imported.into()])?;
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        let store_ = instance.get_typed_func::<i32, (), _>(&mut store, "store")?;
+        store_.call(&mut store, 0)?;
+        memory.protect_read_only(&mut store, 0..65536)?;
+        assert!(store_.call(&mut store, 0).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn read_only_ranges_wasi() -> Result<()> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |wasi| wasi)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "wasi_snapshot_preview1" "random_get"
+                    (func $random_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "clock_time_get"
+                    (func $clock_time_get (param i32 i64 i32) (result i32)))
+                (memory (export "memory") 2)
+                (func (export "random_get") (param i32) (result i32)
+                    (call $random_get (local.get 0) (i32.const 16)))
+                (func (export "clock_time_get") (param i32) (result i32)
+                    (call $clock_time_get (i32.const 0) (i64.const 1) (local.get 0))))
+        "#,
+    )?;
+    let mut store = Store::new(&engine, WasiCtxBuilder::new().build());
+    let instance = linker.instantiate(&mut store, &module)?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    memory.protect_read_only(&mut store, 0..65536)?;
+    let random_get = instance.get_typed_func::<i32, i32, _>(&mut store, "random_get")?;
+    let clock_time_get = instance.get_typed_func::<i32, i32, _>(&mut store, "clock_time_get")?;
+
+    // Writes into the read-only range fail rather than crashing: buffers
+    // with `EFAULT`, and results with a trap, like out of bounds ones. The
+    // rest of the memory is still written to.
+    const EFAULT: i32 = 21;
+    assert_eq!(random_get.call(&mut store, 16)?, EFAULT);
+    assert_eq!(random_get.call(&mut store, 65528)?, EFAULT);
+    let trap = clock_time_get.call(&mut store, 16).unwrap_err();
+    assert!(trap.to_string().contains("read-only"), "{}", trap);
+    assert_eq!(random_get.call(&mut store, 65536 + 16)?, 0);
+    assert_eq!(clock_time_get.call(&mut store, 65536 + 16)?, 0);
+    assert!(memory.data(&store)[..65536].iter().all(|b| *b == 0));
+    assert_ne!(memory.data(&store)[65536 + 16..65536 + 24], [0; 8]);
+    Ok(())
+}
+
+const PIPELINE_STAGE: &str = r#"
+    (module
+        (import "" "memory" (memory 1))
+        (func (export "size") (result i32) memory.size)
+        (func (export "grow") (param i32) (result i32)
+            (memory.grow (local.get 0)))
+        (func (export "add") (param i32 i32)
+            (i32.store (local.get 0)
+                (i32.add (i32.load (local.get 0)) (local.get 1)))))
+"#;
+
+#[test]
+fn host_managed_growth() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, PIPELINE_STAGE)?;
+    let mut store = Store::new(&engine, ());
+    let memory = Memory::new(&mut store, MemoryType::new(1, None))?;
+    assert!(memory.guest_growable(&mut store));
+    memory.set_guest_growable(&mut store, false);
+    assert!(!memory.guest_growable(&mut store));
+
+    // Both stages work on the same buffer.
+    let first = Instance::new(&mut store, &module, &[memory.into()])?;
+    let second = Instance::new(&mut store, &module, &[memory.into()])?;
+    let add = |store: &mut Store<()>, instance: Instance| {
+        instance.get_typed_func::<(i32, i32), (), _>(store, "add")
+    };
+    add(&mut store, first)?.call(&mut store, (0, 1))?;
+    add(&mut store, second)?.call(&mut store, (0, 2))?;
+    assert_eq!(memory.data(&store)[0], 3);
+
+    for instance in [first, second] {
+        let grow = instance.get_typed_func::<i32, i32, _>(&mut store, "grow")?;
+        assert_eq!(grow.call(&mut store, 0)?, 1);
+        let trap = grow.call(&mut store, 1).unwrap_err();
+        assert!(trap.to_string().contains("managed by the host"), "{}", trap);
+    }
+    assert_eq!(memory.size(&store), 1);
+
+    // Growth by the host is seen by every stage.
+    memory.grow(&mut store, 2)?;
+    for instance in [first, second] {
+        let size = instance.get_typed_func::<(), i32, _>(&mut store, "size")?;
+        assert_eq!(size.call(&mut store, ())?, 3);
+    }
+    add(&mut store, second)?.call(&mut store, (2 * 65536, 5))?;
+    assert_eq!(memory.data(&store)[2 * 65536], 5);
+
+    memory.set_guest_growable(&mut store, true);
+    let grow = first.get_typed_func::<i32, i32, _>(&mut store, "grow")?;
+    assert_eq!(grow.call(&mut store, 1)?, 3);
+    Ok(())
+}
+
+#[test]
+fn host_managed_growth_of_defined_memory() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "grow") (result i32) (memory.grow (i32.const 1))))
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    memory.set_guest_growable(&mut store, false);
+    let grow = instance.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    assert!(grow.call(&mut store, ()).is_err());
+    assert_eq!(memory.grow(&mut store, 1)?, 1);
+    assert_eq!(memory.size(&store), 2);
+    Ok(())
+}
+
+/// Lengths of `memory.copy` and `memory.fill` instructions, around the inline
+/// thresholds and the sizes of the loads and stores they're split into.
+const BULK_LENGTHS: &[u32] = &[
+    1, 2, 3, 7, 8, 15, 16, 17, 31, 33, 63, 64, 65, 255, 256, 257, 4096,
+];
+
+/// Whether each of a run's copies and fills trapped, and the memory's contents
+/// after those of each length.
+type BulkMemoryRun = (Vec<Option<TrapCode>>, Vec<Vec<u8>>);
+
+/// Runs constant-length copies and fills in an engine with the inline
+/// `threshold`.
+fn run_bulk_memory(threshold: u32) -> Result<BulkMemoryRun> {
+    let mut wat = String::from("(module (memory (export \"memory\") 1)\n");
+    for len in BULK_LENGTHS {
+        wat.push_str(&format!(
+            "(func (export \"copy {0}\") (param i32 i32)
+                (memory.copy (local.get 0) (local.get 1) (i32.const {0})))
+            (func (export \"fill {0}\") (param i32 i32)
+                (memory.fill (local.get 0) (local.get 1) (i32.const {0})))\n",
+            len
+        ));
+    }
+    wat.push(')');
+    let mut config = Config::new();
+    config.bulk_memory_inline_threshold(threshold);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, &wat)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+
+    let mut traps = Vec::new();
+    let mut contents = Vec::new();
+    for &len in BULK_LENGTHS {
+        for (i, byte) in memory.data_mut(&mut store).iter_mut().enumerate() {
+            *byte = (i % 251) as u8;
+        }
+        let copy =
+            instance.get_typed_func::<(i32, i32), (), _>(&mut store, &format!("copy {}", len))?;
+        let fill =
+            instance.get_typed_func::<(i32, i32), (), _>(&mut store, &format!("fill {}", len))?;
+        let end = 65536 - len as i32;
+        for args in [
+            (0, 5000),
+            (5000, 0),
+            (10, 13),
+            (13, 10),
+            (end, 0),
+            (0, end),
+            (end + 1, 0),
+            (0, end + 1),
+        ] {
+            traps.push(
+                copy.call(&mut store, args)
+                    .err()
+                    .map(|t| t.trap_code().unwrap()),
+            );
+        }
+        for args in [(7, 0x1ab), (end, 3), (end + 1, 9)] {
+            traps.push(
+                fill.call(&mut store, args)
+                    .err()
+                    .map(|t| t.trap_code().unwrap()),
+            );
+        }
+        contents.push(memory.data(&store).to_vec());
+    }
+    Ok((traps, contents))
+}
+
+#[test]
+fn bulk_memory_inline() -> Result<()> {
+    let (traps, contents) = run_bulk_memory(0)?;
+    let per_len = traps.len() / BULK_LENGTHS.len();
+    for traps in traps.chunks(per_len) {
+        assert_eq!(traps[..6], [None; 6]);
+        assert_eq!(traps[6..8], [Some(TrapCode::MemoryOutOfBounds); 2]);
+        assert_eq!(traps[8..], [None, None, Some(TrapCode::MemoryOutOfBounds)]);
+    }
+    let first = &contents[0];
+    assert_eq!(first[7], 0xab);
+    assert_eq!(first[65535], 3);
+
+    // Inline copies and fills behave just like those of the runtime,
+    // including overlapping copies and not writing anything when they trap.
+    for threshold in [16, 64, 256] {
+        let inline = run_bulk_memory(threshold)?;
+        assert!(inline.0 == traps, "threshold {}", threshold);
+        assert!(inline.1 == contents, "threshold {}", threshold);
+    }
+
+    let mut config = Config::new();
+    config.bulk_memory_inline_threshold(257);
+    assert!(Engine::new(&config).is_err());
+    Ok(())
+}
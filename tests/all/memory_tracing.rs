@@ -0,0 +1,107 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn loads_and_stores_are_traced() -> Result<()> {
+    let mut config = Config::new();
+    config.trace_memory_accesses(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (func (export "run") (param i32) (result i64)
+                    (i32.store8 offset=4 (local.get 0) (i32.const 0x1ff))
+                    (f32.store offset=8 (local.get 0) (f32.const 1.5))
+                    (v128.store offset=16 (local.get 0) (v128.const i64x2 1 2))
+                    (drop (v128.load offset=16 (local.get 0)))
+                    (drop (i32.load8_s offset=4 (local.get 0)))
+                    (i64.load offset=8 (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, Vec::new());
+    store.memory_access_callback(|mut cx, access| {
+        cx.data_mut().push(*access);
+        Ok(())
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i64, _>(&mut store, "run")?;
+    let loaded = run.call(&mut store, 100)?;
+
+    let accesses = store
+        .data()
+        .iter()
+        .map(|a| (a.kind(), a.memory_index(), a.address(), a.size(), a.value()))
+        .collect::<Vec<_>>();
+    let v128 = 1 | (2 << 64);
+    assert_eq!(
+        accesses,
+        [
+            (MemoryAccessKind::Store, 0, 104, 1, 0xff),
+            (MemoryAccessKind::Store, 0, 108, 4, 1.5f32.to_bits().into()),
+            (MemoryAccessKind::Store, 0, 116, 16, v128),
+            (MemoryAccessKind::Load, 0, 116, 16, v128),
+            (MemoryAccessKind::Load, 0, 104, 1, 0xff),
+            (MemoryAccessKind::Load, 0, 108, 8, loaded as u64 as u128),
+        ]
+    );
+    assert_eq!(loaded as u32, 1.5f32.to_bits());
+    Ok(())
+}
+
+#[test]
+fn callback_errors_trap() -> Result<()> {
+    let mut config = Config::new();
+    config.trace_memory_accesses(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "run") (param i32) (result i32)
+                    (i32.store8 offset=4 (local.get 0) (i32.const 0x1ff))
+                    (i32.load offset=4 (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    store.memory_access_callback(|_cx, access| {
+        if access.kind() == MemoryAccessKind::Load {
+            anyhow::bail!("load rejected");
+        }
+        Ok(())
+    });
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    let trap = run.call(&mut store, 0).unwrap_err();
+    assert!(
+        trap.to_string().contains("load rejected"),
+        "bad trap: {}",
+        trap
+    );
+
+    // The store preceding the load still happened.
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(memory.data(&store)[4], 0xff);
+    Ok(())
+}
+
+#[test]
+fn serialized_modules_require_same_setting() -> Result<()> {
+    let module = Module::new(&Engine::default(), "(module)")?;
+    let bytes = module.serialize()?;
+    let mut config = Config::new();
+    config.trace_memory_accesses(true);
+    let err = unsafe { Module::deserialize(&Engine::new(&config)?, &bytes) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("memory access tracing"),
+        "bad error: {:?}",
+        err
+    );
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use super::skip_pooling_allocator_tests;
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn modules_instances_and_traps() -> Result<()> {
+    let engine = Engine::default();
+    let metrics = engine.metrics();
+    assert_eq!(metrics.code_bytes(), 0);
+    assert_eq!(metrics.live_modules(), 0);
+    assert_eq!(metrics.live_instances(), 0);
+    assert_eq!(metrics.instance_slots(), None);
+    assert_eq!(metrics.traps(), 0);
+
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "trap") unreachable)
+                (func (export "ok"))
+            )
+        "#,
+    )?;
+    let metrics = engine.metrics();
+    assert!(metrics.code_bytes() > 0);
+    assert_eq!(metrics.live_modules(), 1);
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    Instance::new(&mut store, &module, &[])?;
+    // Host functions don't count as instances.
+    Func::wrap(&mut store, || {});
+    assert_eq!(engine.metrics().live_instances(), 2);
+
+    let trap = instance.get_typed_func::<(), (), _>(&mut store, "trap")?;
+    let ok = instance.get_typed_func::<(), (), _>(&mut store, "ok")?;
+    assert!(trap.call(&mut store, ()).is_err());
+    assert!(trap.call(&mut store, ()).is_err());
+    ok.call(&mut store, ())?;
+    assert_eq!(engine.metrics().traps(), 2);
+
+    drop(store);
+    assert_eq!(engine.metrics().live_instances(), 0);
+    drop(module);
+    let metrics = engine.metrics();
+    assert_eq!(metrics.code_bytes(), 0);
+    assert_eq!(metrics.live_modules(), 0);
+    assert_eq!(metrics.traps(), 2);
+    Ok(())
+}
+
+#[test]
+fn code_is_shared_by_instances() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, r#"(module (func (export "run")))"#)?;
+    let code_size = module.code_size();
+    assert!(code_size > 0);
+    assert_eq!(engine.metrics().code_bytes(), code_size);
+
+    let stores = (0..3)
+        .map(|_| -> Result<_> {
+            let mut store = Store::new(&engine, ());
+            Instance::new(&mut store, &module, &[])?;
+            Instance::new(&mut store, &module.clone(), &[])?;
+            Ok(store)
+        })
+        .collect::<Result<Vec<_>>>()?;
+    assert_eq!(engine.metrics().live_instances(), 6);
+    assert_eq!(module.code_size(), code_size);
+    assert_eq!(engine.metrics().code_bytes(), code_size);
+
+    // A module compiled again from the same wasm has code of its own.
+    let copy = Module::new(&engine, r#"(module (func (export "run")))"#)?;
+    assert_eq!(engine.metrics().code_bytes(), code_size + copy.code_size());
+    drop(stores);
+    Ok(())
+}
+
+#[test]
+fn fuel_of_dropped_stores() -> Result<()> {
+    let mut config = Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, r#"(module (func (export "run") nop nop nop))"#)?;
+
+    let mut consumed = 0;
+    for _ in 0..2 {
+        let mut store = Store::new(&engine, ());
+        store.add_fuel(100)?;
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+        run.call(&mut store, ())?;
+        consumed += store.fuel_consumed().unwrap();
+        assert!(engine.metrics().fuel_consumed() < consumed);
+    }
+    assert!(consumed > 0);
+    assert_eq!(engine.metrics().fuel_consumed(), consumed);
+    Ok(())
+}
+
+#[test]
+fn pooling_slot_occupancy() -> Result<()> {
+    if skip_pooling_allocator_tests() {
+        return Ok(());
+    }
+
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::NextAvailable,
+        instance_limits: InstanceLimits {
+            count: 3,
+            memory_pages: 1,
+            ..Default::default()
+        },
+    });
+    config.dynamic_memory_guard_size(0);
+    config.static_memory_guard_size(0);
+    config.static_memory_maximum_size(65536);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(&engine, "(module (memory 1))")?;
+    assert_eq!(engine.metrics().instance_slots(), Some((0, 3)));
+
+    let mut store = Store::new(&engine, ());
+    Instance::new(&mut store, &module, &[])?;
+    Instance::new(&mut store, &module, &[])?;
+    assert_eq!(engine.metrics().instance_slots(), Some((2, 3)));
+    drop(store);
+    assert_eq!(engine.metrics().instance_slots(), Some((0, 3)));
+    Ok(())
+}
+
+#[test]
+fn cache_hits_and_misses() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    let config_path = dir.path().join("config.toml");
+    std::fs::write(
+        &config_path,
+        format!(
+            "[cache]\nenabled = true\ndirectory = {:?}\n",
+            dir.path().join("cache")
+        ),
+    )?;
+
+    let mut config = Config::new();
+    config.cache_config_load(&config_path)?;
+    let engine = Engine::new(&config)?;
+    assert_eq!(engine.metrics().cache_hit_rate(), None);
+
+    Module::new(&engine, "(module (func))")?;
+    Module::new(&engine, "(module (func))")?;
+    Module::new(&engine, "(module (func))")?;
+    let metrics = engine.metrics();
+    assert_eq!(metrics.cache_misses(), 1);
+    assert_eq!(metrics.cache_hits(), 2);
+    assert_eq!(metrics.cache_hit_rate(), Some(2.0 / 3.0));
+
+    // Without a cache there are no lookups.
+    let engine = Engine::default();
+    Module::new(&engine, "(module (func))")?;
+    assert_eq!(engine.metrics().cache_misses(), 0);
+    Ok(())
+}
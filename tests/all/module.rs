@@ -168,3 +168,97 @@ fn serialize_not_overly_massive() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn custom_sections() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $m
+                (@custom "manifest" "read")
+                (@custom "abi" "1")
+                (@custom "manifest" "write")
+                (@custom ".debug_info" "dwarf")
+                (func)
+            )
+        "#,
+    )?;
+
+    let check = |module: &Module| {
+        let manifest = module.custom_sections("manifest").collect::<Vec<_>>();
+        assert_eq!(manifest, [&b"read"[..], b"write"]);
+        assert_eq!(module.custom_sections("abi").collect::<Vec<_>>(), [b"1"]);
+        assert_eq!(module.custom_sections(".debug_info").count(), 0);
+        assert_eq!(module.custom_sections("name").count(), 0);
+        let names = module
+            .all_custom_sections()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["manifest", "abi", "manifest"]);
+    };
+    check(&module);
+    check(&unsafe { Module::deserialize(&engine, module.serialize()?)? });
+    Ok(())
+}
+
+#[test]
+fn parallel_compilation_threads() -> Result<()> {
+    // Lots of functions, with lots of different signatures to compile
+    // trampolines for.
+    let mut wat = String::from("(module\n");
+    for i in 0..100 {
+        let params = "i32 ".repeat(i % 10);
+        wat.push_str(&format!(
+            "(func (export \"f{}\") (param {}i64) (result i64)
+                local.get {}
+                i64.const {}
+                i64.add)\n",
+            i,
+            params,
+            i % 10,
+            i
+        ));
+    }
+    wat.push_str(")");
+
+    let serialize = |config: &mut Config| -> Result<Vec<u8>> {
+        let engine = Engine::new(config)?;
+        let module = Module::new(&engine, &wat)?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let f = instance.get_typed_func::<(i32, i32, i64), i64, _>(&mut store, "f2")?;
+        assert_eq!(f.call(&mut store, (0, 0, 40))?, 42);
+        module.serialize()
+    };
+
+    let pooled = serialize(Config::new().parallel_compilation_threads(2))?;
+    let single = serialize(Config::new().parallel_compilation(false))?;
+    let global = serialize(&mut Config::new())?;
+    assert!(pooled == single);
+    assert!(pooled == global);
+    Ok(())
+}
+
+#[test]
+fn sat_float_to_int_feature() -> Result<()> {
+    let wat = r#"
+        (module
+            (func (export "f") (param f32) (result i32)
+                (i32.trunc_sat_f32_u (local.get 0))))
+    "#;
+    let engine = Engine::default();
+    let module = Module::new(&engine, wat)?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let f = instance.get_typed_func::<f32, i32, _>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, -1.5)?, 0);
+    assert_eq!(f.call(&mut store, 4e9)?, 4_000_000_000_u32 as i32);
+    assert_eq!(f.call(&mut store, 1e10)?, -1);
+    assert_eq!(f.call(&mut store, f32::NAN)?, 0);
+
+    let disabled = Engine::new(Config::new().wasm_sat_float_to_int(false))?;
+    assert!(Module::new(&disabled, wat).is_err());
+    assert!(unsafe { Module::deserialize(&disabled, module.serialize()?) }.is_err());
+    Ok(())
+}
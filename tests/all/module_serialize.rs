@@ -75,6 +75,42 @@ fn test_module_serialize_fail() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_module_serialize_incompatible() -> Result<()> {
+    let mut config = Config::new();
+    config.wasm_simd(false);
+    let buffer = serialize(
+        &Engine::new(&config)?,
+        "(module (func (export \"run\") (result i32) i32.const 42))",
+    )?;
+
+    let engine = Engine::default();
+    let err = unsafe { Module::deserialize(&engine, &buffer) }
+        .err()
+        .unwrap();
+    let err = err.downcast_ref::<IncompatibleModuleError>().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "Module was compiled without WebAssembly SIMD support but it is enabled for the host"
+    );
+
+    let mut config = Config::new();
+    config
+        .module_version(ModuleVersionStrategy::Custom("custom!".to_owned()))
+        .unwrap();
+    let err = unsafe { Module::deserialize(&Engine::new(&config)?, &buffer) }
+        .err()
+        .unwrap();
+    assert!(err.is::<IncompatibleModuleError>());
+
+    let module = unsafe { Module::deserialize_unchecked(&engine, &buffer)? };
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<(), i32, _>(&mut store, "run")?;
+    assert_eq!(run.call(&mut store, ())?, 42);
+    Ok(())
+}
+
 #[test]
 fn test_deserialize_from_file() -> Result<()> {
     serialize_and_call("(module (func (export \"run\") (result i32) i32.const 42))")?;
@@ -0,0 +1,156 @@
+use crate::common::call_run;
+use anyhow::Result;
+use std::io::Read;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module $streamed
+        (memory 1)
+        (data (i32.const 0) "\2a")
+        (func $load (result i32) (i32.load8_u (i32.const 0)))
+        (func (export "run") (param i32) (result i32)
+            (i32.add (call $load) (local.get 0)))
+    )
+"#;
+
+fn stream(engine: &Engine, wasm: &[u8], chunk: usize) -> Result<Module> {
+    let mut stream = ModuleStream::new(engine)?;
+    for bytes in wasm.chunks(chunk) {
+        stream.push(bytes)?;
+    }
+    stream.finish()
+}
+
+#[test]
+fn compiles_streamed_modules() -> Result<()> {
+    let wasm = wat::parse_str(WAT)?;
+    for parallel in [true, false] {
+        let mut config = Config::new();
+        config.parallel_compilation(parallel);
+        let engine = Engine::new(&config)?;
+        for chunk in [1, 7, wasm.len()] {
+            let module = stream(&engine, &wasm, chunk)?;
+            assert_eq!(module.name(), Some("streamed"));
+            assert_eq!(call_run(&engine, &module, 1)?, 43);
+            let report = module.compilation_report().unwrap();
+            assert!(report.total_compile_time().is_some());
+        }
+    }
+
+    // Streamed modules are compiled to the same code as any other.
+    let engine = Engine::default();
+    let streamed = stream(&engine, &wasm, 5)?;
+    let compiled = Module::new(&engine, &wasm)?;
+    assert_eq!(streamed.serialize()?, compiled.serialize()?);
+    Ok(())
+}
+
+#[test]
+fn from_reader() -> Result<()> {
+    // A reader which returns a few bytes at a time, like a socket.
+    struct Trickle<'a>(&'a [u8]);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let engine = Engine::default();
+    let wasm = wat::parse_str(WAT)?;
+    let module = Module::from_reader(&engine, Trickle(&wasm))?;
+    assert_eq!(call_run(&engine, &module, 1)?, 43);
+
+    // The text format is compiled once it has all been read.
+    let module = Module::from_reader(&engine, Trickle(WAT.as_bytes()))?;
+    assert_eq!(call_run(&engine, &module, 1)?, 43);
+    Ok(())
+}
+
+#[test]
+fn invalid_functions_fail_early() -> Result<()> {
+    let wasm = wat::parse_str(
+        r#"(module
+            (func (result i32) i64.const 0)
+            (data "trailing data"))"#,
+    )?;
+    let mut config = Config::new();
+    config.parallel_compilation(false);
+    let engine = Engine::new(&config)?;
+
+    // The function fails to compile as soon as its body has arrived, before
+    // the data section.
+    let data_section = wasm.len() - "trailing data".len() - 4;
+    let mut stream = ModuleStream::new(&engine)?;
+    let err = stream.push(&wasm[..data_section]).unwrap_err();
+    assert!(format!("{:?}", err).contains("type mismatch"), "{:?}", err);
+
+    let engine = Engine::default();
+    let err = stream_err(&engine, &wasm);
+    assert!(format!("{:?}", err).contains("type mismatch"), "{:?}", err);
+    Ok(())
+}
+
+fn stream_err(engine: &Engine, wasm: &[u8]) -> anyhow::Error {
+    match stream(engine, wasm, 4) {
+        Ok(_) => panic!("module should fail to compile"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn truncated_modules() -> Result<()> {
+    let engine = Engine::default();
+    let wasm = wat::parse_str(WAT)?;
+    for len in [2, 9, wasm.len() / 2, wasm.len() - 1] {
+        stream_err(&engine, &wasm[..len]);
+    }
+    Ok(())
+}
+
+#[test]
+fn buffered_configurations() -> Result<()> {
+    let wasm = wat::parse_str(WAT)?;
+    let mut config = Config::new();
+    config.lazy_compilation(true);
+    let engine = Engine::new(&config)?;
+    let module = stream(&engine, &wasm, 3)?;
+    assert_eq!(call_run(&engine, &module, 1)?, 43);
+    assert!(module.compilation_report().is_none());
+    Ok(())
+}
+
+#[test]
+fn call_indirect_through_immutable_tables() -> Result<()> {
+    // Functions are compiled before the module's last sections arrive, so
+    // they can't assume that this table is shared by all instances.
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (type $r (func (result i32)))
+                (table $t 3 funcref)
+                (elem (table $t) (i32.const 0) func $one $two)
+                (func $one (result i32) i32.const 1)
+                (func $two (result i32) i32.const 2)
+                (func (export "run") (param i32) (result i32)
+                    (call_indirect $t (type $r) (local.get 0)))
+            )
+        "#,
+    )?;
+    for parallel in [true, false] {
+        let mut config = Config::new();
+        config.parallel_compilation(parallel);
+        config.cache_call_indirects(true);
+        let engine = Engine::new(&config)?;
+        let module = stream(&engine, &wasm, 5)?;
+        assert_eq!(call_run(&engine, &module, 0)?, 1);
+        assert_eq!(call_run(&engine, &module, 1)?, 2);
+        let err = call_run(&engine, &module, 2).unwrap_err();
+        let trap = err.downcast::<Trap>()?;
+        assert_eq!(trap.trap_code(), Some(TrapCode::IndirectCallToNull));
+    }
+    Ok(())
+}
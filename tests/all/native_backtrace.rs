@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module $m
+        (import "" "capture" (func $capture))
+        (func $outer (export "run") call $middle)
+        (func $middle nop call $inner)
+        (func $inner call $capture)
+    )
+"#;
+
+fn run(capture: impl Fn(Caller<'_, ()>) -> Backtrace + Send + Sync + 'static) -> Result<Backtrace> {
+    let mut store = Store::<()>::default();
+    let captured = Arc::new(Mutex::new(None));
+    let slot = captured.clone();
+    let func = Func::wrap(&mut store, move |caller: Caller<'_, ()>| {
+        *slot.lock().unwrap() = Some(capture(caller));
+    });
+    let module = Module::new(store.engine(), WAT)?;
+    let instance = Instance::new(&mut store, &module, &[func.into()])?;
+    let run = instance.get_typed_func::<(), (), _>(&mut store, "run")?;
+    run.call(&mut store, ())?;
+    let backtrace = captured.lock().unwrap().take().unwrap();
+    Ok(backtrace)
+}
+
+fn wasm_names(backtrace: &Backtrace) -> Vec<&str> {
+    backtrace
+        .wasm_frames()
+        .map(|frame| frame.func_name().unwrap())
+        .collect()
+}
+
+#[test]
+fn wasm_frames_within_host_frames() -> Result<()> {
+    let backtrace = run(|caller| Backtrace::capture(caller))?;
+    assert_eq!(wasm_names(&backtrace), ["inner", "middle", "outer"]);
+
+    // The host function and its caller are host frames on either side of the
+    // wasm frames.
+    let frames = backtrace.frames();
+    let first = frames.iter().position(|f| !f.is_host()).unwrap();
+    let last = frames.iter().rposition(|f| !f.is_host()).unwrap();
+    assert!(first > 0);
+    assert!(last < frames.len() - 1);
+    assert_eq!(last - first, 2);
+    for frame in backtrace.wasm_frames() {
+        assert_eq!(frame.module_name(), Some("m"));
+        assert!(frame.module_offset().is_some());
+    }
+
+    let rendered = backtrace.to_string();
+    assert!(rendered.contains("m!inner"), "{}", rendered);
+    assert!(rendered.contains("<host>"), "{}", rendered);
+    Ok(())
+}
+
+#[test]
+fn capture_without_a_store() -> Result<()> {
+    let backtrace = run(|_| Backtrace::capture_all())?;
+    assert_eq!(wasm_names(&backtrace), ["inner", "middle", "outer"]);
+    Ok(())
+}
+
+#[test]
+fn frames_of_other_stores_are_host_frames() -> Result<()> {
+    let other = Store::<()>::default();
+    let backtrace = run(move |_| Backtrace::capture(&other))?;
+    assert_eq!(backtrace.wasm_frames().count(), 0);
+
+    let backtrace = Backtrace::capture_all();
+    assert_eq!(backtrace.wasm_frames().count(), 0);
+    assert!(backtrace.frames().iter().all(|f| f.is_host()));
+    Ok(())
+}
+
+#[test]
+#[cfg(all(windows, target_arch = "x86_64"))]
+fn code_is_registered_with_windows() -> Result<()> {
+    use winapi::um::winnt::RtlLookupFunctionEntry;
+
+    let lookup = |pc: usize| unsafe {
+        let mut base = 0;
+        let entry = RtlLookupFunctionEntry(pc as u64, &mut base, std::ptr::null_mut());
+        (!entry.is_null()).then_some(base as usize)
+    };
+    let engine = Engine::default();
+    let module = Module::new(&engine, WAT)?;
+    let map = module.address_map().unwrap();
+    let start = map.text_range().start;
+    let pcs = map.entries().map(|(pc, _)| pc).collect::<Vec<_>>();
+    assert!(!pcs.is_empty());
+    for &pc in &pcs {
+        assert_eq!(lookup(pc), Some(start), "{:#x}", pc);
+    }
+
+    // Unloading the module unregisters its code.
+    drop(module);
+    assert_eq!(lookup(pcs[0]), None);
+    Ok(())
+}
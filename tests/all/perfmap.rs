@@ -0,0 +1,42 @@
+#![cfg(target_os = "linux")]
+
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn perfmap_lists_functions() -> Result<()> {
+    let mut config = Config::new();
+    config.profiler(ProfilingStrategy::PerfMap)?;
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $perfmap
+                (func $perfmap_test_function (export "f") (result i32) i32.const 1)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+    assert_eq!(f.call(&mut store, ())?, 1);
+
+    // Other tests in this process may append to the same file concurrently,
+    // so only the line of this test's function is checked.
+    let map = std::fs::read_to_string(format!("/tmp/perf-{}.map", std::process::id()))?;
+    let line = map
+        .lines()
+        .find(|line| line.ends_with(" perfmap!perfmap_test_function"))
+        .expect("function should be listed in the perf map");
+    let mut fields = line.splitn(3, ' ');
+    let addr = usize::from_str_radix(fields.next().unwrap(), 16)?;
+    let size = usize::from_str_radix(fields.next().unwrap(), 16)?;
+    assert_ne!(addr, 0);
+    assert_ne!(size, 0);
+
+    // The function is named the same as it is everywhere else.
+    let frame = module.address_map().unwrap().lookup_pc(addr).unwrap();
+    assert!(line.ends_with(&format!(" {}", frame.symbol_name())));
+    assert!(map.ends_with('\n'));
+    Ok(())
+}
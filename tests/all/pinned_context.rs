@@ -0,0 +1,123 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (import "" "callback" (func $callback))
+        (global $started (export "started") (mut i32) (i32.const 0))
+        (func $start (global.set $started (i32.const 1)))
+        (start $start)
+        (func $count (export "count") (param i32)
+            (loop $l
+                (br_if $l (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))))
+        (func (export "call_back") (param i32)
+            call $callback
+            (call $count (local.get 0)))
+        (func (export "sum") (param i64 i64 i64 i64 i64 i64 i64 i64 i64 i32) (result i64)
+            (i64.add
+                (i64.add
+                    (i64.add (local.get 0) (local.get 1))
+                    (i64.add (local.get 2) (local.get 3)))
+                (i64.add
+                    (i64.add
+                        (i64.add (local.get 4) (local.get 5))
+                        (i64.add (local.get 6) (local.get 7)))
+                    (i64.add (local.get 8) (i64.extend_i32_u (local.get 9))))))
+    )
+"#;
+
+fn engine(pinned: bool) -> Result<Engine> {
+    let mut config = Config::new();
+    config
+        .consume_fuel(true)
+        .epoch_interruption(true)
+        .pinned_context_register(pinned);
+    Engine::new(&config)
+}
+
+fn instantiate(
+    engine: &Engine,
+    module: &Module,
+    callback: impl Fn(Caller<'_, ()>) + Send + Sync + 'static,
+) -> Result<(Store<()>, Instance)> {
+    let mut store = Store::new(engine, ());
+    store.add_fuel(u64::max_value())?;
+    store.set_epoch_deadline(1);
+    let callback = Func::wrap(&mut store, callback);
+    let instance = Instance::new(&mut store, module, &[callback.into()])?;
+    Ok((store, instance))
+}
+
+// Returns the fuel consumed by instantiating `WAT`, calling `count` directly
+// and through `Func::call`, and calling `sum`.
+fn fuel_consumed(pinned: bool) -> Result<u64> {
+    let engine = engine(pinned)?;
+    let module = Module::new(&engine, WAT)?;
+    let (mut store, instance) = instantiate(&engine, &module, |_| {})?;
+    let started = instance.get_global(&mut store, "started").unwrap();
+    assert_eq!(started.get(&mut store).i32(), Some(1));
+
+    let count = instance.get_typed_func::<i32, (), _>(&mut store, "count")?;
+    count.call(&mut store, 100)?;
+    instance
+        .get_func(&mut store, "count")
+        .unwrap()
+        .call(&mut store, &[Val::I32(50)], &mut [])?;
+    let sum = instance
+        .get_typed_func::<(i64, i64, i64, i64, i64, i64, i64, i64, i64, i32), i64, _>(
+            &mut store, "sum",
+        )?;
+    assert_eq!(sum.call(&mut store, (1, 2, 3, 4, 5, 6, 7, 8, 9, 10))?, 55);
+    Ok(store.fuel_consumed().unwrap())
+}
+
+#[test]
+fn consumes_the_same_fuel() -> Result<()> {
+    assert_eq!(fuel_consumed(true)?, fuel_consumed(false)?);
+    Ok(())
+}
+
+#[test]
+fn nested_stores() -> Result<()> {
+    let engine = engine(true)?;
+    let module = Module::new(&engine, WAT)?;
+
+    // The callback of the outer store runs the inner store's wasm, after
+    // which the outer store's wasm has to find its own context again.
+    let inner_engine = engine.clone();
+    let inner_module = module.clone();
+    let (mut outer, instance) = instantiate(&engine, &module, move |_| {
+        let (mut inner, instance) = instantiate(&inner_engine, &inner_module, |_| {}).unwrap();
+        let before = inner.fuel_consumed().unwrap();
+        let count = instance
+            .get_typed_func::<i32, (), _>(&mut inner, "count")
+            .unwrap();
+        count.call(&mut inner, 1000).unwrap();
+        assert!(inner.fuel_consumed().unwrap() > before + 1000);
+    })?;
+    let before = outer.fuel_consumed().unwrap();
+    let call_back = instance.get_typed_func::<i32, (), _>(&mut outer, "call_back")?;
+    call_back.call(&mut outer, 10)?;
+    let consumed = outer.fuel_consumed().unwrap() - before;
+    assert!(consumed > 10 && consumed < 1000);
+
+    // Epoch deadlines are found through the context too.
+    engine.increment_epoch();
+    let err = call_back.call(&mut outer, 10).unwrap_err();
+    assert_eq!(err.trap_code(), Some(TrapCode::Interrupt));
+    Ok(())
+}
+
+#[test]
+fn incompatible_modules() -> Result<()> {
+    let serialized = Module::new(&engine(false)?, WAT)?.serialize()?;
+    let err = unsafe { Module::deserialize(&engine(true)?, &serialized) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("enable_pinned_reg"),
+        "{:?}",
+        err
+    );
+    Ok(())
+}
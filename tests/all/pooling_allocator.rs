@@ -630,29 +630,40 @@ fn instance_too_large() -> Result<()> {
 
     let engine = Engine::new(&config)?;
     let expected = "\
-instance allocation for this module requires 304 bytes which exceeds the \
+instance allocation for this module requires 352 bytes which exceeds the \
 configured maximum of 16 bytes; breakdown of allocation requirement:
 
- * 78.95% - 240 bytes - instance state management
- * 5.26% - 16 bytes - jit store state
+ * 81.82% - 288 bytes - instance state management
 ";
     match Module::new(&engine, "(module)") {
         Ok(_) => panic!("should have failed to compile"),
         Err(e) => assert_eq!(e.to_string(), expected),
     }
 
+    // Immutable globals initialized with constants are shared by all
+    // instances, so they don't take up any room in each of them.
     let mut lots_of_globals = format!("(module");
     for _ in 0..100 {
         lots_of_globals.push_str("(global i32 i32.const 0)\n");
     }
     lots_of_globals.push_str(")");
+    match Module::new(&engine, &lots_of_globals) {
+        Ok(_) => panic!("should have failed to compile"),
+        Err(e) => assert_eq!(e.to_string(), expected),
+    }
+
+    let mut lots_of_globals = format!("(module");
+    for _ in 0..100 {
+        lots_of_globals.push_str("(global (mut i32) i32.const 0)\n");
+    }
+    lots_of_globals.push_str(")");
 
     let expected = "\
-instance allocation for this module requires 1904 bytes which exceeds the \
+instance allocation for this module requires 1952 bytes which exceeds the \
 configured maximum of 16 bytes; breakdown of allocation requirement:
 
- * 12.61% - 240 bytes - instance state management
- * 84.03% - 1600 bytes - defined globals
+ * 14.75% - 288 bytes - instance state management
+ * 81.97% - 1600 bytes - defined globals
 ";
     match Module::new(&engine, &lots_of_globals) {
         Ok(_) => panic!("should have failed to compile"),
@@ -661,3 +672,154 @@ configured maximum of 16 bytes; breakdown of allocation requirement:
 
     Ok(())
 }
+
+fn memory_protection_keys_engine(count: u32) -> Result<Option<Engine>> {
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::NextAvailable,
+        instance_limits: InstanceLimits {
+            count,
+            memory_pages: 1,
+            table_elements: 10,
+            ..Default::default()
+        },
+    });
+    config.memory_protection_keys(MpkEnabled::Enable);
+    match Engine::new(&config) {
+        Ok(engine) => Ok(Some(engine)),
+        Err(e) if e.to_string().contains("not supported on this host") => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[test]
+#[cfg(target_pointer_width = "64")]
+fn memory_protection_keys_isolate_stores() -> Result<()> {
+    let engine = match memory_protection_keys_engine(4)? {
+        Some(engine) => engine,
+        None => return Ok(()),
+    };
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "read" (func $read (param i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "load") (param i32) (result i32)
+                    (i32.load (local.get 0)))
+                (func (export "read") (param i32) (result i32)
+                    (call $read (local.get 0)))
+            )
+        "#,
+    )?;
+
+    let mut stores = Vec::new();
+    for i in 0..2 {
+        let mut store = Store::new(&engine, ());
+        let read = Func::wrap(&mut store, |mut caller: Caller<'_, ()>, addr: u32| {
+            let memory = caller.get_export("memory").unwrap().into_memory().unwrap();
+            let mut bytes = [0; 4];
+            memory.read(&caller, addr as usize, &mut bytes).unwrap();
+            i32::from_le_bytes(bytes)
+        });
+        let instance = Instance::new(&mut store, &module, &[read.into()])?;
+        let memory = instance.get_memory(&mut store, "memory").unwrap();
+        memory.write(&mut store, 0, &(i + 1i32).to_le_bytes())?;
+        stores.push((store, instance, memory));
+    }
+
+    // The memories are closer together than their reservations, but each
+    // store's wasm can only access its own.
+    let base = |(store, _, memory): &(Store<()>, Instance, Memory)| memory.data_ptr(store) as u64;
+    let (lo, hi) = if base(&stores[0]) < base(&stores[1]) {
+        (0, 1)
+    } else {
+        (1, 0)
+    };
+    let distance = base(&stores[hi]) - base(&stores[lo]);
+    assert!(distance < 4 << 30);
+
+    for (i, (store, instance, _)) in stores.iter_mut().enumerate() {
+        let load = instance.get_typed_func::<u32, i32, _>(&mut *store, "load")?;
+        let read = instance.get_typed_func::<u32, i32, _>(&mut *store, "read")?;
+        assert_eq!(load.call(&mut *store, 0)?, i as i32 + 1);
+        assert_eq!(read.call(&mut *store, 0)?, i as i32 + 1);
+    }
+    let (store, instance, _) = &mut stores[lo];
+    let load = instance.get_typed_func::<u32, i32, _>(&mut *store, "load")?;
+    let trap = load.call(&mut *store, distance as u32).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+
+    // Stores can be created and instantiated again once the others are gone.
+    drop(stores);
+    for _ in 0..8 {
+        let mut store = Store::new(&engine, ());
+        let read = Func::wrap(&mut store, |_: i32| 0);
+        Instance::new(&mut store, &module, &[read.into()])?;
+    }
+    Ok(())
+}
+
+#[test]
+fn memory_protection_keys_require_sync() -> Result<()> {
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::pooling());
+    config.memory_protection_keys(MpkEnabled::Enable);
+    config.async_support(true);
+    assert!(Engine::new(&config).is_err());
+
+    config.memory_protection_keys(MpkEnabled::Auto);
+    Engine::new(&config)?;
+    Ok(())
+}
+
+#[test]
+fn purge_unused_code() -> Result<()> {
+    let mut config = Config::new();
+    config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+        strategy: PoolingAllocationStrategy::NextAvailable,
+        instance_limits: InstanceLimits {
+            count: 1,
+            memory_pages: 1,
+            table_elements: 0,
+            ..Default::default()
+        },
+    });
+    let engine = Engine::new(&config)?;
+    let wat = |offset: usize, data: &str| {
+        format!(
+            r#"(module (memory (export "m") 1) (data (i32.const {}) "{}"))"#,
+            offset, data,
+        )
+    };
+    // Instantiates `module`, checks the memory at `offset`, then dirties the
+    // start of the memory before the instance is dropped.
+    let run = |module: &Module, offset: usize, expected: &[u8]| -> Result<()> {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, module, &[])?;
+        let memory = instance.get_memory(&mut store, "m").unwrap();
+        assert_eq!(&memory.data(&store)[offset..][..expected.len()], expected);
+        memory.data_mut(&mut store)[..3].copy_from_slice(b"bar");
+        Ok(())
+    };
+
+    // The only slot keeps the image of the first module after its instance
+    // is gone, even once the module itself is dropped.
+    let first = Module::new(&engine, wat(100, "foo"))?;
+    run(&first, 100, b"foo")?;
+    let weak = first.downgrade();
+    drop(first);
+    assert!(weak.upgrade().is_none());
+    engine.purge_unused_code();
+
+    // Purging the image leaves the slot zeroed for the next module.
+    let second = Module::new(&engine, wat(200, "baz"))?;
+    run(&second, 100, b"\0\0\0")?;
+    run(&second, 0, b"\0\0\0")?;
+    run(&second, 200, b"baz")?;
+
+    // The image of a module which is still alive is kept.
+    engine.purge_unused_code();
+    run(&second, 200, b"baz")?;
+    Ok(())
+}
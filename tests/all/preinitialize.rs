@@ -0,0 +1,189 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn memory_globals_and_tables() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::preinitialize(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (global $g (export "g") (mut i32) (i32.const 0))
+                (global $started (mut i32) (i32.const 0))
+                (table $t 1 funcref)
+                (elem declare func $seven)
+                (data (i32.const 0) "original")
+                (func $seven (result i32) i32.const 7)
+                (func $start (global.set $started (i32.add (global.get $started) (i32.const 1))))
+                (start $start)
+                (func (export "init")
+                    (drop (memory.grow (i32.const 1)))
+                    (i32.store (i32.const 0x10000) (i32.const 0xfeed))
+                    (i32.store8 (i32.const 0) (i32.const 0x4f))
+                    (global.set $g (i32.const 100))
+                    (drop (table.grow $t (ref.func $seven) (i32.const 2))))
+                (func (export "call") (param i32) (result i32)
+                    (call_indirect $t (result i32) (local.get 0)))
+                (func (export "started") (result i32) global.get $started)
+            )
+        "#,
+        "init",
+    )?;
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(memory.size(&store), 2);
+    assert_eq!(&memory.data(&store)[..8], b"Original");
+    assert_eq!(
+        &memory.data(&store)[0x10000..0x10004],
+        &0xfeed_u32.to_le_bytes()
+    );
+
+    let g = instance.get_global(&mut store, "g").unwrap();
+    assert_eq!(g.get(&mut store).i32(), Some(100));
+
+    let call = instance.get_typed_func::<i32, i32, _>(&mut store, "call")?;
+    assert!(call.call(&mut store, 0).is_err());
+    assert_eq!(call.call(&mut store, 2)?, 7);
+
+    // The start function ran once, before `init`, and doesn't run again.
+    let started = instance.get_typed_func::<(), i32, _>(&mut store, "started")?;
+    assert_eq!(started.call(&mut store, ())?, 1);
+    Ok(())
+}
+
+#[test]
+fn serialize_preinitialized() -> Result<()> {
+    let engine = Engine::default();
+    let module = Module::preinitialize(
+        &engine,
+        r#"
+            (module
+                (global (export "g") (mut i64) (i64.const 0))
+                (func (export "init") (global.set 0 (i64.const -1))))
+        "#,
+        "init",
+    )?;
+    let module = unsafe { Module::deserialize(&engine, module.serialize()?)? };
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let g = instance.get_global(&mut store, "g").unwrap();
+    assert_eq!(g.get(&mut store).i64(), Some(-1));
+    Ok(())
+}
+
+#[test]
+fn rejects_imports() {
+    let engine = Engine::default();
+    let err = Module::preinitialize(
+        &engine,
+        r#"
+            (module
+                (import "host" "f" (func))
+                (func (export "init")))
+        "#,
+        "init",
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("host::f"), "{:?}", err);
+}
+
+#[test]
+fn init_trap() {
+    let engine = Engine::default();
+    let err = Module::preinitialize(
+        &engine,
+        r#"(module (func (export "init") unreachable))"#,
+        "init",
+    )
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("`init`"), "{:?}", err);
+}
+
+#[test]
+fn dropped_segments() -> Result<()> {
+    let engine = Engine::default();
+    let wat = r#"
+        (module
+            (memory (export "memory") 1)
+            (table 1 funcref)
+            (data $d "passive")
+            (elem $e func $f)
+            (func $f)
+            (func (export "init")
+                (memory.init $d (i32.const 0) (i32.const 0) (i32.const 7))
+                (data.drop $d)
+                (elem.drop $e))
+            (func (export "init_data") (param i32)
+                (memory.init $d (i32.const 8) (i32.const 0) (local.get 0)))
+            (func (export "init_elem") (param i32)
+                (table.init $e (i32.const 0) (i32.const 0) (local.get 0)))
+        )
+    "#;
+    let module = Module::preinitialize(&engine, wat, "init")?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[..7], b"passive");
+
+    // The segments are empty, as they were when the snapshot was taken.
+    let init_data = instance.get_typed_func::<i32, (), _>(&mut store, "init_data")?;
+    init_data.call(&mut store, 0)?;
+    assert!(init_data.call(&mut store, 1).is_err());
+    let init_elem = instance.get_typed_func::<i32, (), _>(&mut store, "init_elem")?;
+    init_elem.call(&mut store, 0)?;
+    assert!(init_elem.call(&mut store, 1).is_err());
+
+    // Restoring a snapshot drops them too.
+    let module = Module::new(&engine, wat)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance
+        .get_typed_func::<(), (), _>(&mut store, "init")?
+        .call(&mut store, ())?;
+    let snapshot = instance.snapshot(&mut store)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    instance.restore(&mut store, &snapshot)?;
+    let init_data = instance.get_typed_func::<i32, (), _>(&mut store, "init_data")?;
+    assert!(init_data.call(&mut store, 1).is_err());
+    let init_elem = instance.get_typed_func::<i32, (), _>(&mut store, "init_elem")?;
+    assert!(init_elem.call(&mut store, 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn baked_data_segment_indices() -> Result<()> {
+    // The baked segment takes an index after the module's own segments, and
+    // the passive one keeps working.
+    let engine = Engine::default();
+    let module = Module::preinitialize(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (data $d "passive")
+                (data (i32.const 0) "active")
+                (func (export "init")
+                    (i32.store8 (i32.const 0) (i32.const 0x41)))
+                (func (export "init_data") (param i32)
+                    (memory.init $d (i32.const 8) (i32.const 0) (local.get 0)))
+            )
+        "#,
+        "init",
+    )?;
+    let segments = module.initializers().collect::<Vec<_>>();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].kind(), SegmentKind::Data);
+    assert_eq!(segments[0].index(), 2);
+
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let init_data = instance.get_typed_func::<i32, (), _>(&mut store, "init_data")?;
+    init_data.call(&mut store, 7)?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[..15], b"Active\0\0passive");
+    Ok(())
+}
@@ -0,0 +1,121 @@
+use anyhow::Result;
+use wasmtime::*;
+use wasmtime_wasi::{SandboxPolicy, SandboxPolicyBuilder, WasiCtx};
+
+struct Host {
+    wasi: WasiCtx,
+    limits: StoreLimits,
+}
+
+fn store(policy: &SandboxPolicy) -> Result<Store<Host>> {
+    let mut config = Config::new();
+    policy.configure(&mut config)?;
+    let engine = Engine::new(&config)?;
+    let host = Host {
+        wasi: policy.wasi_ctx()?,
+        limits: policy.store_limits(),
+    };
+    let mut store = Store::new(&engine, host);
+    policy.configure_store(&mut store, |host| &mut host.limits)?;
+    Ok(store)
+}
+
+#[test]
+fn round_trips_through_toml() -> Result<()> {
+    let policy = SandboxPolicyBuilder::new()
+        .wasm_feature("simd", false)
+        .wasm_feature("memory64", true)
+        .fuel(1_000)
+        .epoch_deadline(5)
+        .memory_size(1 << 20)
+        .instances(2)
+        .memory_grow_failure_trap(true)
+        .arg("guest.wasm")
+        .env("LANG", "C")
+        .inherit_stdio(false, true, true)
+        .preopened_dir("/srv/data", "/data", true)
+        .build();
+    let toml = toml::to_string(&policy)?;
+    assert_eq!(toml::from_str::<SandboxPolicy>(&toml)?, policy);
+
+    // Anything left out is the default, while typos are errors.
+    let policy: SandboxPolicy = toml::from_str("fuel = 1000")?;
+    assert_eq!(policy, SandboxPolicyBuilder::new().fuel(1_000).build());
+    assert!(toml::from_str::<SandboxPolicy>("[limits]\nmemory_sise = 1").is_err());
+    Ok(())
+}
+
+#[test]
+fn enables_exactly_its_features() -> Result<()> {
+    let policy = SandboxPolicyBuilder::new()
+        .wasm_feature("simd", false)
+        .build();
+    let store = store(&policy)?;
+    let wat = r#"(module (func (drop (v128.const i64x2 0 0))))"#;
+    let error = Module::new(store.engine(), wat).err().unwrap();
+    let disabled = error.downcast_ref::<DisabledFeatureError>().unwrap();
+    assert_eq!(disabled.feature(), "simd");
+
+    let policy = SandboxPolicyBuilder::new()
+        .wasm_feature("simd-everywhere", true)
+        .build();
+    let error = policy.configure(&mut Config::new()).unwrap_err();
+    assert!(error.to_string().contains("simd-everywhere"), "{}", error);
+    Ok(())
+}
+
+#[test]
+fn limits_memory_and_fuel() -> Result<()> {
+    let policy = SandboxPolicyBuilder::new()
+        .memory_size(2 << 16)
+        .fuel(10_000)
+        .build();
+    let mut store = store(&policy)?;
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (memory 1)
+                (func (export "grow") (param i32) (result i32)
+                    (memory.grow (local.get 0)))
+                (func (export "spin") (loop (br 0))))
+        "#,
+    )?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let grow = instance.get_typed_func::<i32, i32, _>(&mut store, "grow")?;
+    assert_eq!(grow.call(&mut store, 1)?, 1);
+    assert_eq!(grow.call(&mut store, 1)?, -1);
+
+    let spin = instance.get_typed_func::<(), (), _>(&mut store, "spin")?;
+    let trap = spin.call(&mut store, ()).unwrap_err();
+    assert!(trap.to_string().contains("all fuel consumed"), "{}", trap);
+    Ok(())
+}
+
+#[test]
+fn grants_wasi_args() -> Result<()> {
+    let policy = SandboxPolicyBuilder::new()
+        .arg("guest.wasm")
+        .arg("--verbose")
+        .build();
+    let mut store = store(&policy)?;
+    let mut linker = Linker::new(store.engine());
+    wasmtime_wasi::add_to_linker(&mut linker, |host: &mut Host| &mut host.wasi)?;
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (import "wasi_snapshot_preview1" "args_sizes_get"
+                    (func $args_sizes_get (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (func (export "argc") (result i32)
+                    (if (call $args_sizes_get (i32.const 16) (i32.const 20))
+                        (then unreachable))
+                    (i32.load (i32.const 16))))
+        "#,
+    )?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let argc = instance.get_typed_func::<(), i32, _>(&mut store, "argc")?;
+    assert_eq!(argc.call(&mut store, ())?, 2);
+    Ok(())
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use wasmtime::*;
+
+fn engine() -> Engine {
+    let mut config = Config::new();
+    config.signals_based_traps(false);
+    Engine::new(&config).unwrap()
+}
+
+#[test]
+fn traps_without_signals() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (table 2 funcref)
+                (type $i (func (result i32)))
+                (elem (i32.const 1) $f)
+                (func $f)
+
+                (func (export "unreachable") unreachable)
+                (func (export "load") (param i32) (result i32)
+                    (i32.load (local.get 0)))
+                (func (export "load-offset") (param i32) (result i64)
+                    (i64.load offset=0xffffffff (local.get 0)))
+                (func (export "store") (param i32)
+                    (i32.store (local.get 0) (i32.const 1)))
+                (func (export "div-u") (param i32 i32) (result i32)
+                    (i32.div_u (local.get 0) (local.get 1)))
+                (func (export "div-s") (param i32 i32) (result i32)
+                    (i32.div_s (local.get 0) (local.get 1)))
+                (func (export "rem-s") (param i32 i32) (result i32)
+                    (i32.rem_s (local.get 0) (local.get 1)))
+                (func (export "div-s64") (param i64 i64) (result i64)
+                    (i64.div_s (local.get 0) (local.get 1)))
+                (func (export "trunc-f32-s") (param f32) (result i32)
+                    (i32.trunc_f32_s (local.get 0)))
+                (func (export "trunc-f64-s") (param f64) (result i32)
+                    (i32.trunc_f64_s (local.get 0)))
+                (func (export "trunc-f32-u") (param f32) (result i32)
+                    (i32.trunc_f32_u (local.get 0)))
+                (func (export "trunc-f64-u64") (param f64) (result i64)
+                    (i64.trunc_f64_u (local.get 0)))
+                (func (export "call-indirect") (param i32) (result i32)
+                    (call_indirect (type $i) (local.get 0)))
+                (func $recurse (export "recurse") (call $recurse))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    let code = |trap: Trap| trap.trap_code().unwrap();
+
+    let unreachable = instance.get_typed_func::<(), (), _>(&mut store, "unreachable")?;
+    let trap = unreachable.call(&mut store, ()).unwrap_err();
+    assert_eq!(code(trap), TrapCode::UnreachableCodeReached);
+
+    let load = instance.get_typed_func::<i32, i32, _>(&mut store, "load")?;
+    assert_eq!(load.call(&mut store, 0xfffc)?, 0);
+    let trap = load.call(&mut store, 0xfffd).unwrap_err();
+    assert_eq!(code(trap), TrapCode::MemoryOutOfBounds);
+    let load_offset = instance.get_typed_func::<i32, i64, _>(&mut store, "load-offset")?;
+    let trap = load_offset.call(&mut store, 0).unwrap_err();
+    assert_eq!(code(trap), TrapCode::MemoryOutOfBounds);
+    let store_ = instance.get_typed_func::<i32, (), _>(&mut store, "store")?;
+    let trap = store_.call(&mut store, -1).unwrap_err();
+    assert_eq!(code(trap), TrapCode::MemoryOutOfBounds);
+
+    let div_u = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "div-u")?;
+    assert_eq!(div_u.call(&mut store, (7, 2))?, 3);
+    let trap = div_u.call(&mut store, (7, 0)).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerDivisionByZero);
+    let div_s = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "div-s")?;
+    assert_eq!(div_s.call(&mut store, (i32::MIN, 1))?, i32::MIN);
+    let trap = div_s.call(&mut store, (i32::MIN, -1)).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerOverflow);
+    let rem_s = instance.get_typed_func::<(i32, i32), i32, _>(&mut store, "rem-s")?;
+    assert_eq!(rem_s.call(&mut store, (i32::MIN, -1))?, 0);
+    let div_s64 = instance.get_typed_func::<(i64, i64), i64, _>(&mut store, "div-s64")?;
+    let trap = div_s64.call(&mut store, (i64::MIN, -1)).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerOverflow);
+
+    let trunc_f32_s = instance.get_typed_func::<f32, i32, _>(&mut store, "trunc-f32-s")?;
+    assert_eq!(trunc_f32_s.call(&mut store, -2147483648.0)?, i32::MIN);
+    let trap = trunc_f32_s.call(&mut store, 2147483648.0).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerOverflow);
+    let trap = trunc_f32_s.call(&mut store, f32::NAN).unwrap_err();
+    assert_eq!(code(trap), TrapCode::BadConversionToInteger);
+    let trunc_f64_s = instance.get_typed_func::<f64, i32, _>(&mut store, "trunc-f64-s")?;
+    assert_eq!(trunc_f64_s.call(&mut store, -2147483648.9)?, i32::MIN);
+    let trap = trunc_f64_s.call(&mut store, -2147483649.0).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerOverflow);
+    let trunc_f32_u = instance.get_typed_func::<f32, i32, _>(&mut store, "trunc-f32-u")?;
+    assert_eq!(trunc_f32_u.call(&mut store, -0.9)?, 0);
+    let trap = trunc_f32_u.call(&mut store, -1.0).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerOverflow);
+    let trunc_f64_u64 = instance.get_typed_func::<f64, i64, _>(&mut store, "trunc-f64-u64")?;
+    let trap = trunc_f64_u64
+        .call(&mut store, 18446744073709551616.0)
+        .unwrap_err();
+    assert_eq!(code(trap), TrapCode::IntegerOverflow);
+
+    let call_indirect = instance.get_typed_func::<i32, i32, _>(&mut store, "call-indirect")?;
+    let trap = call_indirect.call(&mut store, 0).unwrap_err();
+    assert_eq!(code(trap), TrapCode::IndirectCallToNull);
+    let trap = call_indirect.call(&mut store, 1).unwrap_err();
+    assert_eq!(code(trap), TrapCode::BadSignature);
+    let trap = call_indirect.call(&mut store, 2).unwrap_err();
+    assert_eq!(code(trap), TrapCode::TableOutOfBounds);
+
+    let recurse = instance.get_typed_func::<(), (), _>(&mut store, "recurse")?;
+    let trap = recurse.call(&mut store, ()).unwrap_err();
+    assert_eq!(code(trap), TrapCode::StackOverflow);
+    Ok(())
+}
+
+#[test]
+fn grown_memory_is_accessible() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "load") (param i32) (result i32)
+                    (i32.load8_u (local.get 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let load = instance.get_typed_func::<i32, i32, _>(&mut store, "load")?;
+
+    assert!(load.call(&mut store, 0x10000).is_err());
+    memory.grow(&mut store, 1)?;
+    memory.data_mut(&mut store)[0x1ffff] = 42;
+    assert_eq!(load.call(&mut store, 0x1ffff)?, 42);
+    assert!(load.call(&mut store, 0x20000).is_err());
+    Ok(())
+}
+
+#[test]
+fn serialized_modules_require_same_setting() -> Result<()> {
+    let module = Module::new(&engine(), "(module)")?;
+    let bytes = module.serialize()?;
+    let err = unsafe { Module::deserialize(&Engine::default(), &bytes) }
+        .err()
+        .unwrap();
+    assert!(
+        format!("{:?}", err).contains("signals-based traps"),
+        "bad error: {:?}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn pooling_allocator_requires_signals() {
+    let mut config = Config::new();
+    config
+        .signals_based_traps(false)
+        .allocation_strategy(InstanceAllocationStrategy::pooling());
+    let err = Engine::new(&config).err().unwrap();
+    assert!(
+        err.to_string().contains("requires signals-based traps"),
+        "bad error: {}",
+        err
+    );
+}
+
+#[test]
+fn trap_offsets_without_signals() -> Result<()> {
+    let engine = engine();
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (func (export "run") (param i32) (result i32)
+                    (if (local.get 0) (then unreachable))
+                    (i32.load (i32.const 0x10000)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+
+    // Each trap is attributed to its own instruction even though both are in
+    // the same function.
+    let trap = run.call(&mut store, 1).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    let unreachable = trap.module_offset().unwrap();
+    let trap = run.call(&mut store, 0).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+    let load = trap.module_offset().unwrap();
+    assert!(unreachable < load, "{} >= {}", unreachable, load);
+    Ok(())
+}
+
+#[test]
+fn stack_check_covers_whole_frame() -> Result<()> {
+    // With guest debugging each local gets a spill slot, so this function's
+    // frame alone is larger than the maximum wasm stack.
+    let mut config = Config::new();
+    config
+        .signals_based_traps(false)
+        .guest_debug(true)
+        .max_wasm_stack(128 << 10)?;
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        format!(
+            r#"
+                (module
+                    (func (export "small") (param i32) (result i32)
+                        (local i64)
+                        local.get 0)
+                    (func (export "large") (param i32) (result i32)
+                        (local {})
+                        local.get 0)
+                )
+            "#,
+            "i64 ".repeat(16 << 10)
+        ),
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+
+    let small = instance.get_typed_func::<i32, i32, _>(&mut store, "small")?;
+    assert_eq!(small.call(&mut store, 1)?, 1);
+    let large = instance.get_typed_func::<i32, i32, _>(&mut store, "large")?;
+    let trap = large.call(&mut store, 1).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow));
+    Ok(())
+}
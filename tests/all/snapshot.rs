@@ -0,0 +1,128 @@
+use anyhow::Result;
+use wasmtime::*;
+
+const COUNTER: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (global $count (export "count") (mut i32) (i32.const 0))
+        (table (export "table") 1 funcref)
+        (elem declare func $get)
+        (func $get (result i32) global.get $count)
+        (func (export "bump")
+            (global.set $count (i32.add (global.get $count) (i32.const 1)))
+            (i32.store (i32.const 0) (global.get $count))
+            (table.set (i32.const 0) (ref.func $get)))
+        (func (export "grow") (result i32)
+            (drop (table.grow (ref.null func) (i32.const 1)))
+            (memory.grow (i32.const 1)))
+        (func (export "call") (result i32)
+            (call_indirect (result i32) (i32.const 0)))
+    )
+"#;
+
+fn count(store: &mut Store<()>, instance: &Instance) -> i32 {
+    instance
+        .get_global(&mut *store, "count")
+        .unwrap()
+        .get(&mut *store)
+        .unwrap_i32()
+}
+
+#[test]
+fn restore_same_instance() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), COUNTER)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let bump = instance.get_typed_func::<(), (), _>(&mut store, "bump")?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+
+    bump.call(&mut store, ())?;
+    let snapshot = instance.snapshot(&mut store)?;
+    bump.call(&mut store, ())?;
+    bump.call(&mut store, ())?;
+    assert_eq!(count(&mut store, &instance), 3);
+
+    instance.restore(&mut store, &snapshot)?;
+    assert_eq!(count(&mut store, &instance), 1);
+    assert_eq!(&memory.data(&store)[..4], &1i32.to_le_bytes());
+    Ok(())
+}
+
+#[test]
+fn restore_fresh_instance() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), COUNTER)?;
+    let original = Instance::new(&mut store, &module, &[])?;
+    let bump = original.get_typed_func::<(), (), _>(&mut store, "bump")?;
+    let grow = original.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    bump.call(&mut store, ())?;
+    grow.call(&mut store, ())?;
+    let snapshot = original.snapshot(&mut store)?;
+
+    // The copy starts out with the memory and table grown like the original
+    // and with its table referring to its own functions.
+    let copy = Instance::new(&mut store, &module, &[])?;
+    copy.restore(&mut store, &snapshot)?;
+    let memory = copy.get_memory(&mut store, "memory").unwrap();
+    let table = copy.get_table(&mut store, "table").unwrap();
+    assert_eq!(memory.size(&store), 2);
+    assert_eq!(table.size(&store), 2);
+    assert_eq!(count(&mut store, &copy), 1);
+
+    bump.call(&mut store, ())?;
+    let call = copy.get_typed_func::<(), i32, _>(&mut store, "call")?;
+    assert_eq!(call.call(&mut store, ())?, 1);
+    assert_eq!(count(&mut store, &original), 2);
+
+    // Memories and tables can't shrink back to the size in the snapshot.
+    let grow = copy.get_typed_func::<(), i32, _>(&mut store, "grow")?;
+    grow.call(&mut store, ())?;
+    assert!(copy.restore(&mut store, &snapshot).is_err());
+    Ok(())
+}
+
+#[test]
+fn failed_restore_leaves_instance_untouched() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), COUNTER)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let bump = instance.get_typed_func::<(), (), _>(&mut store, "bump")?;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    let table = instance.get_table(&mut store, "table").unwrap();
+    let snapshot = instance.snapshot(&mut store)?;
+
+    // Only the table has grown, which is checked after the memory and the
+    // global are, but neither is overwritten.
+    bump.call(&mut store, ())?;
+    table.grow(&mut store, 1, Val::FuncRef(None))?;
+    let err = instance.restore(&mut store, &snapshot).unwrap_err();
+    assert!(err.to_string().contains("table has grown"), "{}", err);
+    assert_eq!(count(&mut store, &instance), 1);
+    assert_eq!(&memory.data(&store)[..4], &1i32.to_le_bytes());
+    assert!(table.get(&mut store, 0).unwrap().unwrap_funcref().is_some());
+    Ok(())
+}
+
+#[test]
+fn restore_other_module() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), COUNTER)?;
+    let other = Module::new(store.engine(), COUNTER)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let snapshot = instance.snapshot(&mut store)?;
+    let other = Instance::new(&mut store, &other, &[])?;
+    assert!(other.restore(&mut store, &snapshot).is_err());
+    Ok(())
+}
+
+#[test]
+fn host_function_in_table() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), COUNTER)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let table = instance.get_table(&mut store, "table").unwrap();
+    let host = Func::wrap(&mut store, || 0i32);
+    table.set(&mut store, 0, Val::FuncRef(Some(host)))?;
+    assert!(instance.snapshot(&mut store).is_err());
+    Ok(())
+}
@@ -58,3 +58,54 @@ fn host_always_has_some_stack() -> anyhow::Result<()> {
         consume_some_stack(space.as_mut_ptr() as usize, stack.saturating_sub(1024))
     }
 }
+
+fn recursion_depth(store: &mut Store<()>) -> anyhow::Result<u32> {
+    let module = Module::new(
+        store.engine(),
+        r#"
+            (module
+                (global $depth (export "depth") (mut i32) (i32.const 0))
+                (func $recursive (export "recurse")
+                    (global.set $depth (i32.add (global.get $depth) (i32.const 1)))
+                    call $recursive)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&mut *store, &module, &[])?;
+    let recurse = instance.get_typed_func::<(), (), _>(&mut *store, "recurse")?;
+    let trap = recurse.call(&mut *store, ()).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow));
+    let depth = instance.get_global(&mut *store, "depth").unwrap();
+    Ok(depth.get(&mut *store).unwrap_i32() as u32)
+}
+
+#[test]
+fn per_store_stack_limits() -> anyhow::Result<()> {
+    let engine = Engine::default();
+    let mut store = Store::new(&engine, ());
+    let default = recursion_depth(&mut store)?;
+
+    let mut small = Store::new(&engine, ());
+    small.max_wasm_stack(64 * 1024)?;
+    let shallow = recursion_depth(&mut small)?;
+    assert!(shallow * 4 < default, "{} vs {}", shallow, default);
+
+    // Other stores of the engine keep its limit.
+    assert_eq!(recursion_depth(&mut store)?, default);
+
+    assert!(store.max_wasm_stack(0).is_err());
+    Ok(())
+}
+
+#[test]
+fn per_store_stack_limits_fit_async_stacks() -> anyhow::Result<()> {
+    let mut config = Config::new();
+    config.async_support(true);
+    config.async_stack_size(1 << 20)?;
+    let engine = Engine::new(&config)?;
+    let mut store = Store::new(&engine, ());
+    store.max_wasm_stack(1 << 20)?;
+    let err = store.max_wasm_stack((1 << 20) + 1).unwrap_err();
+    assert!(err.to_string().contains("async stack size"), "{}", err);
+    Ok(())
+}
@@ -50,3 +50,188 @@ fn copy_wrong() {
         "tables do not have the same element type"
     );
 }
+
+const SEGMENTS: &str = r#"
+    (module
+        (import "" "base" (global $base i32))
+        (table (export "table") 8 funcref)
+        (func $a (result i32) i32.const 1)
+        (func $b (result i32) i32.const 2)
+        (func $c (result i32) i32.const 3)
+        (elem (i32.const 0) $c $c)
+        (elem (global.get $base) $a $a $a $a)
+        (elem (i32.const 3) $b)
+        (func (export "call") (param i32) (result i32)
+            local.get 0
+            call_indirect (result i32))
+    )
+"#;
+
+fn instantiate_segments(store: &mut Store<()>, base: i32) -> anyhow::Result<Instance> {
+    let module = Module::new(store.engine(), SEGMENTS)?;
+    let base = Global::new(
+        &mut *store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        base.into(),
+    )?;
+    Instance::new(&mut *store, &module, &[base.into()])
+}
+
+#[test]
+fn segments_apply_in_order() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let instance = instantiate_segments(&mut store, 1)?;
+    let call = instance.get_typed_func::<i32, i32, _>(&mut store, "call")?;
+    let results = (0..5)
+        .map(|i| call.call(&mut store, i))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(results, [3, 1, 1, 2, 1]);
+    let trap = call.call(&mut store, 5).unwrap_err();
+    assert_eq!(trap.trap_code(), Some(TrapCode::IndirectCallToNull));
+
+    // The same elements are seen through the host API.
+    let table = instance.get_table(&mut store, "table").unwrap();
+    for (i, expected) in [3, 1, 1, 2, 1].into_iter().enumerate() {
+        let func = table.get(&mut store, i as u32).unwrap();
+        let func = func.unwrap_funcref().unwrap().typed::<(), i32, _>(&store)?;
+        assert_eq!(func.call(&mut store, ())?, expected);
+    }
+    assert!(table.get(&mut store, 5).unwrap().unwrap_funcref().is_none());
+    Ok(())
+}
+
+#[test]
+fn segments_out_of_bounds() -> anyhow::Result<()> {
+    let mut store = Store::<()>::default();
+    let err = instantiate_segments(&mut store, 5).err().unwrap();
+    assert!(err.to_string().contains("does not fit"), "{:?}", err);
+    Ok(())
+}
+
+#[test]
+fn many_segments_apply_in_order() -> anyhow::Result<()> {
+    // More segments than are written lazily, where each segment overwrites
+    // the previous one, and then a copy within the table.
+    let mut wat = String::from(
+        r#"(module
+            (import "" "base" (global $base i32))
+            (table 8 funcref)
+            (func (export "copy") (table.copy (i32.const 4) (i32.const 0) (i32.const 2)))
+            (func (export "call") (param i32) (result i32)
+                local.get 0
+                call_indirect (result i32))"#,
+    );
+    for i in 0..40 {
+        wat.push_str(&format!("(func $f{} (result i32) i32.const {})", i, i));
+        let offset = if i % 3 == 0 {
+            "(i32.const 1)"
+        } else {
+            "(global.get $base)"
+        };
+        wat.push_str(&format!("(elem {} $f{} $f{})", offset, i, i));
+    }
+    wat.push(')');
+
+    let mut store = Store::<()>::default();
+    let module = Module::new(store.engine(), &wat)?;
+    let base = Global::new(
+        &mut store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        0.into(),
+    )?;
+    let instance = Instance::new(&mut store, &module, &[base.into()])?;
+    let call = instance.get_typed_func::<i32, i32, _>(&mut store, "call")?;
+    let copy = instance.get_typed_func::<(), (), _>(&mut store, "copy")?;
+    copy.call(&mut store, ())?;
+    let results = [0, 1, 2, 4, 5]
+        .iter()
+        .map(|i| call.call(&mut store, *i))
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(results, [38, 39, 39, 38, 39]);
+    Ok(())
+}
+
+#[test]
+fn shared_immutable_tables() -> anyhow::Result<()> {
+    // `$t` is never modified, so all instances share its elements, which
+    // still refer to the functions of each instance.
+    let wat = r#"
+        (module
+            (import "" "id" (func $id (result i32)))
+            (type $r (func (result i32)))
+            (table $t 5 funcref)
+            (table $copy 5 funcref)
+            (global $count (mut i32) (i32.const 0))
+            (elem (table $t) (i32.const 0) func $id $two $count)
+            (elem (table $t) (i32.const 1) func $one)
+            (elem (table $t) (i32.const 3) func $bump)
+            (func $one (result i32) i32.const 1)
+            (func $two (result i32) i32.const 2)
+            (func $count (result i32) global.get $count)
+            (func $bump
+                (global.set $count (i32.add (global.get $count) (i32.const 1))))
+            (func (export "call") (param i32) (result i32)
+                (call_indirect $t (type $r) (local.get 0)))
+            (func (export "bump")
+                (call_indirect $t (i32.const 3)))
+            (func (export "is_null") (param i32) (result i32)
+                (ref.is_null (table.get $t (local.get 0))))
+            (func (export "call_copy") (param i32) (result i32)
+                (table.copy $copy $t (i32.const 0) (i32.const 0) (i32.const 5))
+                (call_indirect $copy (type $r) (local.get 0)))
+        )
+    "#;
+    for strategy in [Strategy::Cranelift, Strategy::Baseline] {
+        let mut config = Config::new();
+        config.strategy(strategy)?;
+        if !super::skip_pooling_allocator_tests() {
+            config.allocation_strategy(InstanceAllocationStrategy::Pooling {
+                strategy: PoolingAllocationStrategy::NextAvailable,
+                instance_limits: InstanceLimits {
+                    count: 2,
+                    tables: 2,
+                    ..Default::default()
+                },
+            });
+        }
+        let engine = Engine::new(&config)?;
+        let module = Module::new(&engine, wat)?;
+        let mut store = Store::new(&engine, ());
+        let mut instances = Vec::new();
+        for id in [10, 20] {
+            let id = Func::wrap(&mut store, move || id);
+            instances.push(Instance::new(&mut store, &module, &[id.into()])?);
+        }
+        let bump = instances[0].get_typed_func::<(), (), _>(&mut store, "bump")?;
+        bump.call(&mut store, ())?;
+
+        for (instance, id, count) in [(instances[0], 10, 1), (instances[1], 20, 0)] {
+            let call = instance.get_typed_func::<i32, i32, _>(&mut store, "call")?;
+            assert_eq!(call.call(&mut store, 0)?, id);
+            assert_eq!(call.call(&mut store, 1)?, 1);
+            assert_eq!(call.call(&mut store, 2)?, count);
+            let trap = call.call(&mut store, 3).unwrap_err();
+            assert_eq!(trap.trap_code(), Some(TrapCode::BadSignature));
+            let trap = call.call(&mut store, 4).unwrap_err();
+            assert_eq!(trap.trap_code(), Some(TrapCode::IndirectCallToNull));
+            let trap = call.call(&mut store, 5).unwrap_err();
+            assert_eq!(trap.trap_code(), Some(TrapCode::TableOutOfBounds));
+
+            let is_null = instance.get_typed_func::<i32, i32, _>(&mut store, "is_null")?;
+            assert_eq!(is_null.call(&mut store, 0)?, 0);
+            assert_eq!(is_null.call(&mut store, 4)?, 1);
+
+            let call_copy = instance.get_typed_func::<i32, i32, _>(&mut store, "call_copy")?;
+            assert_eq!(call_copy.call(&mut store, 0)?, id);
+            assert_eq!(call_copy.call(&mut store, 2)?, count);
+        }
+
+        // Restoring a snapshot leaves shared tables as they are.
+        let snapshot = instances[0].snapshot(&mut store)?;
+        instances[1].restore(&mut store, &snapshot)?;
+        let call = instances[1].get_typed_func::<i32, i32, _>(&mut store, "call")?;
+        assert_eq!(call.call(&mut store, 0)?, 20);
+        assert_eq!(call.call(&mut store, 2)?, 1);
+    }
+    Ok(())
+}
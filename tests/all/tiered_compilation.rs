@@ -0,0 +1,204 @@
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use wasmtime::*;
+
+fn engine(threshold: u32) -> Engine {
+    let mut config = Config::new();
+    config.tiered_compilation(true).tier_up_threshold(threshold);
+    Engine::new(&config).unwrap()
+}
+
+/// Waits for `engine` to have recompiled `count` functions in the background.
+fn wait_for_tier_ups(engine: &Engine, count: u64) {
+    let start = Instant::now();
+    while engine.metrics().tier_ups() < count {
+        assert!(
+            start.elapsed() < Duration::from_secs(60),
+            "timed out waiting for {} tier-ups",
+            count
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn hot_functions_tier_up() -> Result<()> {
+    let engine = engine(10);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func $double (param i64) (result i64)
+                    local.get 0
+                    i64.const 2
+                    i64.mul)
+                (func (export "quadruple") (param i64) (result i64)
+                    local.get 0
+                    call $double
+                    call $double)
+                (func (export "cold") (result i32)
+                    i32.const 7)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let quadruple = instance.get_typed_func::<i64, i64, _>(&mut store, "quadruple")?;
+    let cold = instance.get_typed_func::<(), i32, _>(&mut store, "cold")?;
+    assert_eq!(cold.call(&mut store, ())?, 7);
+
+    // `double` is called twice as often as `quadruple`, so it's hot first.
+    for i in 0..5 {
+        assert_eq!(quadruple.call(&mut store, i)?, i * 4);
+    }
+    wait_for_tier_ups(&engine, 1);
+    for i in 5..10 {
+        assert_eq!(quadruple.call(&mut store, i)?, i * 4);
+    }
+    wait_for_tier_ups(&engine, 2);
+    let tiered = engine.metrics().code_bytes();
+    for i in 10..1000 {
+        assert_eq!(quadruple.call(&mut store, i)?, i * 4);
+    }
+    assert_eq!(engine.metrics().tier_ups(), 2);
+    assert_eq!(engine.metrics().code_bytes(), tiered);
+    assert_eq!(cold.call(&mut store, ())?, 7);
+
+    drop(store);
+    drop(module);
+    assert_eq!(engine.metrics().code_bytes(), 0);
+    Ok(())
+}
+
+#[test]
+fn loop_iterations_count_towards_tier_up() -> Result<()> {
+    let engine = engine(100);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (func (export "sum") (param i32) (result i64)
+                    (local i64)
+                    (block $done
+                        (loop $top
+                            (br_if $done (i32.eqz (local.get 0)))
+                            (local.set 1 (i64.add (local.get 1) (i64.extend_i32_u (local.get 0))))
+                            (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                            (br $top)))
+                    (local.get 1))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let sum = instance.get_typed_func::<i32, i64, _>(&mut store, "sum")?;
+
+    // The loop keeps running in the baseline code after it tiers up.
+    assert_eq!(sum.call(&mut store, 1000)?, 500500);
+    wait_for_tier_ups(&engine, 1);
+    assert_eq!(sum.call(&mut store, 2000)?, 2001000);
+    assert_eq!(engine.metrics().tier_ups(), 1);
+    Ok(())
+}
+
+#[test]
+fn traps_are_the_same_in_both_tiers() -> Result<()> {
+    let engine = engine(3);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module $m
+                (func $divide (param i32) (result i32)
+                    i32.const 100
+                    local.get 0
+                    i32.div_u)
+                (func (export "run") (param i32) (result i32)
+                    local.get 0
+                    call $divide)
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+
+    let trace = |trap: Trap| {
+        assert_eq!(trap.trap_code(), Some(TrapCode::IntegerDivisionByZero));
+        trap.trace()
+            .iter()
+            .map(|f| (f.func_index(), f.module_offset()))
+            .collect::<Vec<_>>()
+    };
+    let baseline = trace(run.call(&mut store, 0).unwrap_err());
+    assert_eq!(baseline.len(), 2);
+    for _ in 0..3 {
+        assert_eq!(run.call(&mut store, 5)?, 20);
+    }
+    wait_for_tier_ups(&engine, 2);
+    assert_eq!(trace(run.call(&mut store, 0).unwrap_err()), baseline);
+    Ok(())
+}
+
+#[test]
+fn functions_through_tables_and_imports_tier_up() -> Result<()> {
+    let engine = engine(5);
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "" "add" (func $add (param i32 i32) (result i32)))
+                (type $t (func (param i32) (result i32)))
+                (table 1 funcref)
+                (elem (i32.const 0) $inc)
+                (func $inc (type $t)
+                    (call $add (local.get 0) (i32.const 1)))
+                (func (export "run") (param i32) (result i32)
+                    (call_indirect (type $t) (local.get 0) (i32.const 0)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, ());
+    let add = Func::wrap(&mut store, |a: i32, b: i32| a + b);
+    let instance = Instance::new(&mut store, &module, &[add.into()])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    for i in 0..5 {
+        assert_eq!(run.call(&mut store, i)?, i + 1);
+    }
+    wait_for_tier_ups(&engine, 2);
+    for i in 5..100 {
+        assert_eq!(run.call(&mut store, i)?, i + 1);
+    }
+    Ok(())
+}
+
+#[test]
+fn dropping_modules_while_tiering_up() -> Result<()> {
+    let engine = engine(1);
+    for _ in 0..10 {
+        let module = Module::new(
+            &engine,
+            r#"(module (func (export "f") (result i32) i32.const 1))"#,
+        )?;
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let f = instance.get_typed_func::<(), i32, _>(&mut store, "f")?;
+        assert_eq!(f.call(&mut store, ())?, 1);
+    }
+
+    // Modules which were dropped before their functions were recompiled
+    // aren't kept alive by the recompilation.
+    let start = Instant::now();
+    while engine.metrics().live_modules() > 0 {
+        assert!(start.elapsed() < Duration::from_secs(60));
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(engine.metrics().code_bytes(), 0);
+    Ok(())
+}
+
+#[test]
+fn tiered_compilation_with_debug_info() {
+    let mut config = Config::new();
+    config.tiered_compilation(true).debug_info(true);
+    assert!(Engine::new(&config).is_err());
+}
@@ -25,6 +25,8 @@ fn test_trap_return() -> Result<()> {
         .err()
         .expect("error calling function");
     assert!(e.to_string().contains("test 123"));
+    assert_eq!(e.trap_code(), None);
+    assert_eq!(e.module_offset(), None);
 
     Ok(())
 }
@@ -61,6 +63,8 @@ fn test_trap_trace() -> Result<()> {
     assert_eq!(trace[1].func_name(), None);
     assert_eq!(trace[1].func_offset(), Some(1));
     assert_eq!(trace[1].module_offset(), Some(0x21));
+    assert_eq!(e.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    assert_eq!(e.module_offset(), Some(0x26));
     assert!(
         e.to_string().contains("unreachable"),
         "wrong message: {}",
@@ -219,6 +223,51 @@ wasm backtrace:
     Ok(())
 }
 
+#[test]
+#[cfg_attr(all(target_os = "macos", target_arch = "aarch64"), ignore)] // TODO #2808 system libunwind is broken on aarch64
+fn trap_display_demangled() -> Result<()> {
+    let mut store = Store::<()>::default();
+    let wat = r#"
+        (module $m
+            (func $_ZN4core9panicking5panic17h0123456789abcdefE unreachable)
+            (func $_Z3foov call 0)
+            (func (export "bar") call 1)
+        )
+    "#;
+
+    let module = Module::new(store.engine(), wat)?;
+    let instance = Instance::new(&mut store, &module, &[])?;
+    let run_func = instance.get_typed_func::<(), (), _>(&mut store, "bar")?;
+
+    let e = run_func
+        .call(&mut store, ())
+        .err()
+        .expect("error calling function");
+
+    // The raw names from the name section are available on each frame...
+    let trace = e.trace();
+    assert_eq!(trace.len(), 3);
+    assert_eq!(
+        trace[0].func_name(),
+        Some("_ZN4core9panicking5panic17h0123456789abcdefE")
+    );
+    assert_eq!(trace[1].func_name(), Some("_Z3foov"));
+    assert_eq!(trace[2].func_name(), None);
+
+    // ... and the rendered backtrace demangles them.
+    assert_eq!(
+        e.to_string(),
+        "\
+wasm trap: wasm `unreachable` instruction executed
+wasm backtrace:
+    0:   0x22 - m!core::panicking::panic::h0123456789abcdef
+    1:   0x26 - m!foo()
+    2:   0x2b - m!<wasm function 2>
+"
+    );
+    Ok(())
+}
+
 #[test]
 fn trap_start_function_import() -> Result<()> {
     let mut store = Store::<()>::default();
@@ -666,6 +715,7 @@ fn traps_without_address_map() -> Result<()> {
     assert_eq!(trace[0].func_name(), Some("hello"));
     assert_eq!(trace[0].func_index(), 1);
     assert_eq!(trace[0].module_offset(), None);
+    assert_eq!(e.module_offset(), None);
     assert_eq!(trace[1].func_name(), None);
     assert_eq!(trace[1].func_index(), 0);
     assert_eq!(trace[1].module_offset(), None);
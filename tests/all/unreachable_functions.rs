@@ -0,0 +1,102 @@
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use wasmtime::*;
+
+const WAT: &str = r#"
+    (module
+        (global $started (mut i32) (i32.const 0))
+        (table 1 funcref)
+        (elem (i32.const 0) $in_table)
+        (start $start)
+
+        (func $start (global.set $started (i32.const 1)))
+        (func $in_table (result i32) (call $called_from_table))
+        (func $called_from_table (result i32) (i32.const 2))
+        (func $called (param i32) (result i32) (i32.mul (local.get 0) (i32.const 3)))
+        (func (export "run") (param i32) (result i32)
+            (i32.add
+                (i32.add (global.get $started) (call $called (local.get 0)))
+                (call_indirect (result i32) (i32.const 0))))
+
+        (func $unused (param i32) (result i32)
+            (local i32 i64 f64)
+            (call $also_unused (local.get 0))
+            (local.set 1)
+            (local.set 2 (i64.extend_i32_u (local.get 1)))
+            (local.set 3 (f64.convert_i64_s (local.get 2)))
+            (i32.trunc_f64_s (f64.mul (local.get 3) (local.get 3))))
+        (func $also_unused (param i32) (result i32)
+            (if (result i32) (local.get 0)
+                (then (call $unused (i32.sub (local.get 0) (i32.const 1))))
+                (else (i32.const 0))))
+    )
+"#;
+
+#[derive(Clone, Default)]
+struct CodeSizes(Arc<Mutex<HashMap<u32, usize>>>);
+
+impl CompilationHook for CodeSizes {
+    fn function_compiled(&self, function: &FunctionListing<'_>) {
+        let mut sizes = self.0.lock().unwrap();
+        sizes.insert(function.index(), function.code().len());
+    }
+}
+
+impl CodeSizes {
+    fn get(&self, index: u32) -> usize {
+        self.0.lock().unwrap()[&index]
+    }
+}
+
+fn engine(skip: bool) -> Result<(Engine, CodeSizes)> {
+    let sizes = CodeSizes::default();
+    let mut config = Config::new();
+    config
+        .skip_unreachable_functions(skip)
+        .compilation_hook(sizes.clone());
+    Ok((Engine::new(&config)?, sizes))
+}
+
+fn run(engine: &Engine, module: &Module) -> Result<i32> {
+    let mut store = Store::new(engine, ());
+    let instance = Instance::new(&mut store, module, &[])?;
+    let run = instance.get_typed_func::<i32, i32, _>(&mut store, "run")?;
+    Ok(run.call(&mut store, 5)?)
+}
+
+#[test]
+fn reachable_functions_still_work() -> Result<()> {
+    let (all, all_sizes) = engine(false)?;
+    let module = Module::new(&all, WAT)?;
+    assert_eq!(run(&all, &module)?, 18);
+
+    let (skipped, skipped_sizes) = engine(true)?;
+    let module = Module::new(&skipped, WAT)?;
+    assert_eq!(run(&skipped, &module)?, 18);
+
+    // Only `$unused` and `$also_unused` are compiled to a stub.
+    for index in 0..5 {
+        assert_eq!(skipped_sizes.get(index), all_sizes.get(index));
+    }
+    for index in 5..7 {
+        assert!(skipped_sizes.get(index) < all_sizes.get(index));
+    }
+
+    let bytes = skipped.precompile_module(WAT.as_bytes())?;
+    let module = unsafe { Module::deserialize(&all, bytes)? };
+    assert_eq!(run(&all, &module)?, 18);
+    Ok(())
+}
+
+#[test]
+fn unreachable_functions_are_validated() -> Result<()> {
+    let err = Module::new(
+        &engine(true)?.0,
+        r#"(module (func (export "ok")) (func (result i32) (i64.const 0)))"#,
+    )
+    .err()
+    .unwrap();
+    assert!(format!("{:?}", err).contains("type mismatch"), "{:?}", err);
+    Ok(())
+}
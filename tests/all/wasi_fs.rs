@@ -0,0 +1,175 @@
+use anyhow::Result;
+use std::any::Any;
+use std::collections::HashMap;
+use std::io::IoSliceMut;
+use wasmtime::*;
+use wasmtime_wasi::fs::{DirCaps, ErrorExt, FdFlags, FileCaps, FileType, MemDir, OFlags};
+use wasmtime_wasi::sync::{ambient_authority, Dir, WasiCtxBuilder};
+use wasmtime_wasi::{Error, WasiCtx, WasiDir, WasiFile};
+
+/// A read-only directory of files held in memory, standing in for something
+/// like an archive.
+struct Archive(HashMap<String, Vec<u8>>);
+
+#[async_trait::async_trait]
+impl WasiDir for Archive {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn open_file(
+        &self,
+        _symlink_follow: bool,
+        path: &str,
+        oflags: OFlags,
+        _read: bool,
+        write: bool,
+        _fdflags: FdFlags,
+    ) -> Result<Box<dyn WasiFile>, Error> {
+        if write || !oflags.is_empty() {
+            return Err(Error::not_supported());
+        }
+        let data = self.0.get(path).ok_or_else(Error::not_found)?;
+        Ok(Box::new(ArchiveFile {
+            data: data.clone(),
+            pos: 0,
+        }))
+    }
+}
+
+struct ArchiveFile {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+#[async_trait::async_trait]
+impl WasiFile for ArchiveFile {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    async fn get_filetype(&mut self) -> Result<FileType, Error> {
+        Ok(FileType::RegularFile)
+    }
+
+    async fn read_vectored<'a>(&mut self, bufs: &mut [IoSliceMut<'a>]) -> Result<u64, Error> {
+        let mut read = 0;
+        for buf in bufs {
+            let n = buf.len().min(self.data.len() - self.pos);
+            buf[..n].copy_from_slice(&self.data[self.pos..][..n]);
+            self.pos += n;
+            read += n;
+        }
+        Ok(read as u64)
+    }
+}
+
+fn instantiate(wasi: WasiCtx) -> Result<(Store<WasiCtx>, Instance)> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "wasi_snapshot_preview1" "path_open"
+                    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_read"
+                    (func $fd_read (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "path_create_directory"
+                    (func $path_create_directory (param i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "greeting.txt")
+
+                ;; Reads up to 64 bytes of `greeting.txt` in the preopened
+                ;; directory to offset 64, returning how many were read.
+                (func (export "read") (result i32)
+                    (if (call $path_open
+                            (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 12)
+                            (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0)
+                            (i32.const 16))
+                        (then unreachable))
+                    (i32.store (i32.const 24) (i32.const 64))
+                    (i32.store (i32.const 28) (i32.const 64))
+                    (if (call $fd_read
+                            (i32.load (i32.const 16)) (i32.const 24) (i32.const 1)
+                            (i32.const 32))
+                        (then unreachable))
+                    (i32.load (i32.const 32)))
+
+                (func (export "mkdir") (result i32)
+                    (call $path_create_directory (i32.const 3) (i32.const 0) (i32.const 8)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, wasi);
+    let instance = linker.instantiate(&mut store, &module)?;
+    Ok((store, instance))
+}
+
+#[test]
+fn custom_preopened_dir() -> Result<()> {
+    let mut files = HashMap::new();
+    files.insert(
+        "greeting.txt".to_string(),
+        b"hello from an archive".to_vec(),
+    );
+    let wasi = WasiCtxBuilder::new()
+        .preopened_custom_dir(Box::new(Archive(files)), "/data")?
+        .build();
+    let (mut store, instance) = instantiate(wasi)?;
+
+    let read = instance.get_typed_func::<(), i32, _>(&mut store, "read")?;
+    let n = read.call(&mut store, ())? as usize;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[64..][..n], b"hello from an archive");
+
+    // Operations the directory doesn't implement fail with `ENOTSUP`.
+    let mkdir = instance.get_typed_func::<(), i32, _>(&mut store, "mkdir")?;
+    assert_eq!(mkdir.call(&mut store, ())?, 58);
+    Ok(())
+}
+
+#[test]
+fn read_only_preopened_dir() -> Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    std::fs::write(tempdir.path().join("greeting.txt"), "hello from the host")?;
+    let dir = Dir::open_ambient_dir(tempdir.path(), ambient_authority())?;
+    let wasi = WasiCtxBuilder::new()
+        .preopened_dir_with_caps(dir, "/data", DirCaps::read_only(), FileCaps::read_only())?
+        .build();
+    let (mut store, instance) = instantiate(wasi)?;
+
+    let read = instance.get_typed_func::<(), i32, _>(&mut store, "read")?;
+    let n = read.call(&mut store, ())? as usize;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[64..][..n], b"hello from the host");
+
+    // Creating a directory fails with `ENOTCAPABLE`, and doesn't happen.
+    let mkdir = instance.get_typed_func::<(), i32, _>(&mut store, "mkdir")?;
+    assert_eq!(mkdir.call(&mut store, ())?, 76);
+    assert!(!tempdir.path().join("greeting").exists());
+    Ok(())
+}
+
+#[test]
+fn memory_preopened_dir() -> Result<()> {
+    let dir = MemDir::new();
+    dir.add_file("greeting.txt", "hello from memory")?;
+    let wasi = WasiCtxBuilder::new()
+        .preopened_custom_dir(Box::new(dir.clone()), "/data")?
+        .build();
+    let (mut store, instance) = instantiate(wasi)?;
+
+    let read = instance.get_typed_func::<(), i32, _>(&mut store, "read")?;
+    let n = read.call(&mut store, ())? as usize;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[64..][..n], b"hello from memory");
+
+    // The host sees what the guest creates.
+    let mkdir = instance.get_typed_func::<(), i32, _>(&mut store, "mkdir")?;
+    assert_eq!(mkdir.call(&mut store, ())?, 0);
+    let err = dir.read_file("greeting").unwrap_err();
+    assert_eq!(err.to_string(), "Isdir: Is a directory");
+    Ok(())
+}
@@ -0,0 +1,128 @@
+use anyhow::Result;
+use wasmtime::*;
+use wasmtime_wasi_nn::{
+    Backend, BackendError, BackendExecutionContext, BackendGraph, ExecutionTarget, GraphEncoding,
+    Tensor, WasiNnCtx,
+};
+
+/// A backend whose graphs output their input reversed, standing in for an
+/// embedder's own ML library.
+struct Reverse;
+
+impl Backend for Reverse {
+    fn name(&self) -> &str {
+        "reverse"
+    }
+
+    fn load(
+        &mut self,
+        builders: &[&[u8]],
+        _target: ExecutionTarget,
+    ) -> Result<Box<dyn BackendGraph>, BackendError> {
+        if builders.len() != 1 {
+            return Err(BackendError::InvalidNumberOfBuilders(
+                1,
+                builders.len() as u32,
+            ));
+        }
+        Ok(Box::new(ReverseGraph))
+    }
+}
+
+struct ReverseGraph;
+
+impl BackendGraph for ReverseGraph {
+    fn init_execution_context(&mut self) -> Result<Box<dyn BackendExecutionContext>, BackendError> {
+        Ok(Box::new(ReverseExecution(Vec::new())))
+    }
+}
+
+struct ReverseExecution(Vec<u8>);
+
+impl BackendExecutionContext for ReverseExecution {
+    fn set_input(&mut self, _index: u32, tensor: &Tensor<'_>) -> Result<(), BackendError> {
+        assert_eq!(tensor.dimensions, [4]);
+        self.0 = tensor.data.to_vec();
+        Ok(())
+    }
+
+    fn compute(&mut self) -> Result<(), BackendError> {
+        self.0.reverse();
+        Ok(())
+    }
+
+    fn get_output(&mut self, _index: u32, destination: &mut [u8]) -> Result<u32, BackendError> {
+        destination[..self.0.len()].copy_from_slice(&self.0);
+        Ok(self.0.len() as u32)
+    }
+}
+
+#[test]
+fn custom_backend() -> Result<()> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi_nn::add_to_linker(&mut linker, |cx| cx)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "wasi_ephemeral_nn" "load"
+                    (func $load (param i32 i32 i32 i32 i32) (result i32)))
+                (import "wasi_ephemeral_nn" "init_execution_context"
+                    (func $init_execution_context (param i32 i32) (result i32)))
+                (import "wasi_ephemeral_nn" "set_input"
+                    (func $set_input (param i32 i32 i32) (result i32)))
+                (import "wasi_ephemeral_nn" "compute"
+                    (func $compute (param i32) (result i32)))
+                (import "wasi_ephemeral_nn" "get_output"
+                    (func $get_output (param i32 i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                ;; One graph builder, followed by an empty one.
+                (data (i32.const 0) "\64\00\00\00\01\00\00\00")
+                ;; A tensor of four `u8`s.
+                (data (i32.const 16) "\c8\00\00\00\01\00\00\00\02\00\00\00\2c\01\00\00\04\00\00\00")
+                (data (i32.const 100) "\00")
+                (data (i32.const 200) "\04\00\00\00")
+                (data (i32.const 300) "\01\02\03\04")
+
+                ;; Runs the graph, writing its output to offset 400 and
+                ;; returning how long it is.
+                (func (export "run") (result i32)
+                    (if (call $load (i32.const 0) (i32.const 1) (i32.const 0) (i32.const 0)
+                            (i32.const 40))
+                        (then unreachable))
+                    (if (call $init_execution_context (i32.load (i32.const 40)) (i32.const 44))
+                        (then unreachable))
+                    (if (call $set_input (i32.load (i32.const 44)) (i32.const 0) (i32.const 16))
+                        (then unreachable))
+                    (if (call $compute (i32.load (i32.const 44)))
+                        (then unreachable))
+                    (if (call $get_output (i32.load (i32.const 44)) (i32.const 0) (i32.const 400)
+                            (i32.const 64) (i32.const 48))
+                        (then unreachable))
+                    (i32.load (i32.const 48)))
+
+                (func (export "load_two") (result i32)
+                    (call $load (i32.const 0) (i32.const 2) (i32.const 0) (i32.const 0)
+                        (i32.const 40)))
+            )
+        "#,
+    )?;
+    let backends = vec![(
+        GraphEncoding::Openvino,
+        Box::new(Reverse) as Box<dyn Backend>,
+    )];
+    let mut store = Store::new(&engine, WasiNnCtx::with_backends(backends));
+    let instance = linker.instantiate(&mut store, &module)?;
+
+    let run = instance.get_typed_func::<(), u32, _>(&mut store, "run")?;
+    let n = run.call(&mut store, ())? as usize;
+    let memory = instance.get_memory(&mut store, "memory").unwrap();
+    assert_eq!(&memory.data(&store)[400..][..n], [4, 3, 2, 1]);
+
+    // Errors from the backend are reported to the guest as
+    // `invalid_argument`.
+    let load_two = instance.get_typed_func::<(), u32, _>(&mut store, "load_two")?;
+    assert_eq!(load_two.call(&mut store, ())?, 1);
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use wasmtime::*;
+use wasmtime_wasi::fs::MemDir;
+use wasmtime_wasi::sync::WasiCtxBuilder;
+use wasmtime_wasi::{WasiCtx, WasiOverrides};
+
+fn instantiate(wasi: WasiCtx) -> Result<(Store<WasiCtx>, Instance)> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "wasi_snapshot_preview1" "args_sizes_get"
+                    (func $args_sizes_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "args_get"
+                    (func $args_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "environ_sizes_get"
+                    (func $environ_sizes_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "environ_get"
+                    (func $environ_get (param i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "path_open"
+                    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_read"
+                    (func $fd_read (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "greeting.txt")
+
+                ;; Writes the arguments to offset 1024, returning their size.
+                (func (export "args") (result i32)
+                    (if (call $args_sizes_get (i32.const 16) (i32.const 20))
+                        (then unreachable))
+                    (if (call $args_get (i32.const 256) (i32.const 1024))
+                        (then unreachable))
+                    (i32.load (i32.const 20)))
+
+                ;; Writes the environment variables to offset 1024, returning
+                ;; their size.
+                (func (export "env") (result i32)
+                    (if (call $environ_sizes_get (i32.const 16) (i32.const 20))
+                        (then unreachable))
+                    (if (call $environ_get (i32.const 256) (i32.const 1024))
+                        (then unreachable))
+                    (i32.load (i32.const 20)))
+
+                ;; Reads up to 64 bytes of `greeting.txt` in the directory
+                ;; preopened as fd 3 to offset 64, returning how many were
+                ;; read, or the negated error of opening it.
+                (func (export "read") (result i32)
+                    (local $errno i32)
+                    (local.set $errno
+                        (call $path_open
+                            (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 12)
+                            (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0)
+                            (i32.const 16)))
+                    (if (local.get $errno)
+                        (then (return (i32.sub (i32.const 0) (local.get $errno)))))
+                    (i32.store (i32.const 24) (i32.const 64))
+                    (i32.store (i32.const 28) (i32.const 64))
+                    (if (call $fd_read
+                            (i32.load (i32.const 16)) (i32.const 24) (i32.const 1)
+                            (i32.const 32))
+                        (then unreachable))
+                    (i32.load (i32.const 32)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, wasi);
+    let instance = linker.instantiate(&mut store, &module)?;
+    Ok((store, instance))
+}
+
+/// Calls the guest's function `name`, returning the strings it wrote.
+fn strings(store: &mut Store<WasiCtx>, instance: &Instance, name: &str) -> Result<Vec<String>> {
+    let func = instance.get_typed_func::<(), i32, _>(&mut *store, name)?;
+    let size = func.call(&mut *store, ())? as usize;
+    let memory = instance.get_memory(&mut *store, "memory").unwrap();
+    let data = &memory.data(&*store)[1024..][..size];
+    Ok(data
+        .split(|b| *b == 0)
+        .filter(|s| !s.is_empty())
+        .map(|s| String::from_utf8(s.to_vec()).unwrap())
+        .collect())
+}
+
+/// Reads `greeting.txt` in the guest's working directory.
+fn greeting(store: &mut Store<WasiCtx>, instance: &Instance) -> Result<Option<String>> {
+    let read = instance.get_typed_func::<(), i32, _>(&mut *store, "read")?;
+    let n = read.call(&mut *store, ())?;
+    if n < 0 {
+        return Ok(None);
+    }
+    let memory = instance.get_memory(&mut *store, "memory").unwrap();
+    let data = &memory.data(&*store)[64..][..n as usize];
+    Ok(Some(String::from_utf8(data.to_vec())?))
+}
+
+fn cwd(greeting: &str) -> Result<Box<MemDir>> {
+    let dir = MemDir::new();
+    dir.add_file("greeting.txt", greeting)?;
+    Ok(Box::new(dir))
+}
+
+fn strs(strings: &[&str]) -> Vec<String> {
+    strings.iter().map(|s| s.to_string()).collect()
+}
+
+#[test]
+fn update_between_calls() -> Result<()> {
+    let wasi = WasiCtxBuilder::new()
+        .args(&strs(&["guest", "first"]))?
+        .env("REQUEST", "1")?
+        .preopened_custom_dir(cwd("first")?, ".")?
+        .build();
+    let (mut store, instance) = instantiate(wasi)?;
+    assert_eq!(strings(&mut store, &instance, "args")?, ["guest", "first"]);
+    assert_eq!(strings(&mut store, &instance, "env")?, ["REQUEST=1"]);
+    assert_eq!(greeting(&mut store, &instance)?.as_deref(), Some("first"));
+
+    let wasi = store.data_mut();
+    wasi.set_args(&strs(&["guest", "second"]))?;
+    wasi.set_envs(&[("REQUEST".to_string(), "2".to_string())])?;
+    wasi.set_cwd(cwd("second")?)?;
+    assert_eq!(strings(&mut store, &instance, "args")?, ["guest", "second"]);
+    assert_eq!(strings(&mut store, &instance, "env")?, ["REQUEST=2"]);
+    assert_eq!(greeting(&mut store, &instance)?.as_deref(), Some("second"));
+    Ok(())
+}
+
+#[test]
+fn scoped_overrides() -> Result<()> {
+    let wasi = WasiCtxBuilder::new()
+        .args(&strs(&["guest"]))?
+        .env("REQUEST", "0")?
+        .build();
+    let (mut store, instance) = instantiate(wasi)?;
+    assert_eq!(greeting(&mut store, &instance)?, None);
+
+    let overrides = WasiOverrides::new()
+        .args(&strs(&["guest", "--verbose"]))?
+        .cwd(cwd("request")?);
+    let restore = store.data_mut().apply_overrides(overrides)?;
+    assert_eq!(
+        strings(&mut store, &instance, "args")?,
+        ["guest", "--verbose"]
+    );
+    assert_eq!(strings(&mut store, &instance, "env")?, ["REQUEST=0"]);
+    assert_eq!(greeting(&mut store, &instance)?.as_deref(), Some("request"));
+
+    // Applying the returned overrides puts everything back, including
+    // removing the working directory which the context didn't have.
+    let undone = store.data_mut().apply_overrides(restore)?;
+    assert_eq!(strings(&mut store, &instance, "args")?, ["guest"]);
+    assert_eq!(greeting(&mut store, &instance)?, None);
+
+    // And applying those redoes the overrides.
+    store.data_mut().apply_overrides(undone)?;
+    assert_eq!(
+        strings(&mut store, &instance, "args")?,
+        ["guest", "--verbose"]
+    );
+    assert_eq!(greeting(&mut store, &instance)?.as_deref(), Some("request"));
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anyhow::Result;
+use std::io::Read;
+use wasmtime::*;
+use wasmtime_wasi::pipe::{self, WritePipe};
+use wasmtime_wasi::sync::WasiCtxBuilder;
+use wasmtime_wasi::WasiCtx;
+
+/// Runs a module which writes "hello, world" to stdout in two writes.
+fn run(wasi: WasiCtx) -> Result<()> {
+    let engine = Engine::default();
+    let mut linker = Linker::new(&engine);
+    wasmtime_wasi::add_to_linker(&mut linker, |s| s)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (import "wasi_snapshot_preview1" "fd_write"
+                    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\10\00\00\00\07\00\00\00\17\00\00\00\05\00\00\00")
+                (data (i32.const 16) "hello, world")
+                (func (export "_start")
+                    (if (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 32))
+                        (then unreachable))
+                    (if (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 32))
+                        (then unreachable)))
+            )
+        "#,
+    )?;
+    let mut store = Store::new(&engine, wasi);
+    let instance = linker.instantiate(&mut store, &module)?;
+    let start = instance.get_typed_func::<(), (), _>(&mut store, "_start")?;
+    start.call(&mut store, ())?;
+    Ok(())
+}
+
+#[test]
+fn capture_stdout() -> Result<()> {
+    let stdout = WritePipe::new_in_memory();
+    run(WasiCtxBuilder::new()
+        .stdout(Box::new(stdout.clone()))
+        .build())?;
+    assert_eq!(stdout.contents(), b"hello, world");
+
+    let file = tempfile::NamedTempFile::new()?;
+    run(WasiCtxBuilder::new().stdout_writer(file.reopen()?).build())?;
+    assert_eq!(std::fs::read(file.path())?, b"hello, world");
+    Ok(())
+}
+
+#[test]
+fn stream_stdout() -> Result<()> {
+    // The guest has to wait for the host to read its output, since the pipe
+    // can't hold all of it at once.
+    let (stdout, mut output) = pipe::bounded(4);
+    let guest =
+        std::thread::spawn(move || run(WasiCtxBuilder::new().stdout(Box::new(stdout)).build()));
+    let mut received = Vec::new();
+    output.read_to_end(&mut received)?;
+    guest.join().unwrap()?;
+    assert_eq!(received, b"hello, world");
+    Ok(())
+}
@@ -13,7 +13,7 @@ include!(concat!(env!("OUT_DIR"), "/wast_testsuite_tests.rs"));
 // to compile it.
 fn run_wast(wast: &str, strategy: Strategy, pooling: bool) -> anyhow::Result<()> {
     match strategy {
-        Strategy::Cranelift => {}
+        Strategy::Cranelift | Strategy::Baseline => {}
         _ => unimplemented!(),
     }
     let wast = Path::new(wast);
@@ -24,6 +24,7 @@ fn run_wast(wast: &str, strategy: Strategy, pooling: bool) -> anyhow::Result<()>
     let threads = feature_found(wast, "threads");
 
     let mut cfg = Config::new();
+    cfg.strategy(strategy)?;
     cfg.wasm_simd(simd)
         .wasm_multi_memory(multi_memory)
         .wasm_threads(threads)
@@ -0,0 +1,122 @@
+// This test replaces the process's `SIGSEGV` and `SIGILL` handlers, so it
+// lives in its own test binary where it can't intercept, or be confused by,
+// the faults of other tests.
+#![cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+
+use anyhow::Result;
+use std::mem::MaybeUninit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use wasmtime::*;
+
+static IMAGE_START: AtomicUsize = AtomicUsize::new(0);
+static IMAGE_END: AtomicUsize = AtomicUsize::new(0);
+static WASM_FAULTS: AtomicUsize = AtomicUsize::new(0);
+static mut PREV_SIGSEGV: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
+static mut PREV_SIGILL: MaybeUninit<libc::sigaction> = MaybeUninit::uninit();
+
+unsafe fn faulting_pc(context: *mut libc::c_void) -> usize {
+    let context = &*(context as *const libc::ucontext_t);
+    #[cfg(target_arch = "x86_64")]
+    let pc = context.uc_mcontext.gregs[libc::REG_RIP as usize];
+    #[cfg(target_arch = "aarch64")]
+    let pc = context.uc_mcontext.pc;
+    pc as usize
+}
+
+unsafe extern "C" fn embedder_handler(
+    signum: libc::c_int,
+    siginfo: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let pc = faulting_pc(context);
+    if IMAGE_START.load(Ordering::SeqCst) <= pc && pc < IMAGE_END.load(Ordering::SeqCst) {
+        WASM_FAULTS.fetch_add(1, Ordering::SeqCst);
+    }
+    if wasmtime::unix::handle_trap(signum, siginfo, context) {
+        return;
+    }
+
+    // Not a wasm trap, so defer to whichever handler was installed before
+    // this one.
+    let previous = match signum {
+        libc::SIGSEGV => &*PREV_SIGSEGV.as_ptr(),
+        _ => &*PREV_SIGILL.as_ptr(),
+    };
+    if previous.sa_flags & libc::SA_SIGINFO != 0 {
+        std::mem::transmute::<
+            usize,
+            extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void),
+        >(previous.sa_sigaction)(signum, siginfo, context)
+    } else {
+        libc::sigaction(signum, previous, std::ptr::null_mut());
+    }
+}
+
+#[test]
+fn embedder_signal_handler() -> Result<()> {
+    let mut config = Config::new();
+    config.install_signal_handlers(false);
+    let engine = Engine::new(&config)?;
+    let module = Module::new(
+        &engine,
+        r#"
+            (module
+                (memory 1)
+                (func (export "oob") (result i32)
+                    (i32.load (i32.const 0x10000)))
+                (func (export "unreachable") unreachable))
+        "#,
+    )?;
+
+    let image = module.image_range();
+    assert!(wasmtime::unix::code_ranges()
+        .iter()
+        .any(|r| image.start <= r.start && r.end <= image.end));
+    IMAGE_START.store(image.start, Ordering::SeqCst);
+    IMAGE_END.store(image.end, Ordering::SeqCst);
+
+    unsafe {
+        let mut handler: libc::sigaction = std::mem::zeroed();
+        handler.sa_flags = libc::SA_SIGINFO | libc::SA_NODEFER | libc::SA_ONSTACK;
+        handler.sa_sigaction = embedder_handler as usize;
+        libc::sigemptyset(&mut handler.sa_mask);
+        assert_eq!(
+            libc::sigaction(libc::SIGSEGV, &handler, PREV_SIGSEGV.as_mut_ptr()),
+            0
+        );
+        assert_eq!(
+            libc::sigaction(libc::SIGILL, &handler, PREV_SIGILL.as_mut_ptr()),
+            0
+        );
+    }
+
+    let result = (|| -> Result<()> {
+        let mut store = Store::new(&engine, ());
+        let instance = Instance::new(&mut store, &module, &[])?;
+        let oob = instance.get_typed_func::<(), i32, _>(&mut store, "oob")?;
+        let trap = oob.call(&mut store, ()).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::MemoryOutOfBounds));
+        let unreachable = instance.get_typed_func::<(), (), _>(&mut store, "unreachable")?;
+        let trap = unreachable.call(&mut store, ()).unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+        Ok(())
+    })();
+
+    unsafe {
+        assert_eq!(
+            libc::sigaction(libc::SIGSEGV, PREV_SIGSEGV.as_ptr(), std::ptr::null_mut()),
+            0
+        );
+        assert_eq!(
+            libc::sigaction(libc::SIGILL, PREV_SIGILL.as_ptr(), std::ptr::null_mut()),
+            0
+        );
+    }
+    result?;
+
+    assert_eq!(WASM_FAULTS.load(Ordering::SeqCst), 2);
+    Ok(())
+}